@@ -0,0 +1,201 @@
+use crate::cache::Cache;
+use crate::combine::{CombineResult, OllamaClient};
+use crate::theories::Card;
+use crate::{combine_all, Stats};
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// What one set of materials made.
+pub struct SetResult {
+    pub inputs: Vec<String>,
+    pub result: CombineResult,
+    /// A pair within the set that's cached as making the same thing, e.g.
+    /// "Fire + Water" when Fire + Water + Wind is Steam too.
+    pub same_as_pair: Option<String>,
+}
+
+impl SetResult {
+    fn label(&self) -> String {
+        self.inputs.join(" + ")
+    }
+
+    fn valid(&self) -> bool {
+        self.result.name != "Not possible"
+    }
+
+    /// Whether the result is just one of the materials, e.g. Stone + Sand +
+    /// Wind = Stone.
+    fn absorbed(&self) -> bool {
+        self.inputs.iter().any(|i| i.eq_ignore_ascii_case(&self.result.name))
+    }
+}
+
+/// How combines of three or four materials at once behave, next to the
+/// pairs within them.
+pub struct Arity {
+    pub arity: usize,
+    pub sets: Vec<SetResult>,
+    /// Distinct pairs within the sets with a cached result, and how many
+    /// of those are valid.
+    pub pairs_cached: usize,
+    pub pairs_valid: usize,
+}
+
+/// Combine each set of materials at once, then check the cache for the
+/// pairs within them, so higher-order results can be compared with what
+/// two of the same materials make. Pairs aren't combined here; run step 1
+/// with `--pairs full` first to have them all.
+pub async fn run(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    cache: &mut Cache,
+    sets: Vec<Vec<Card>>,
+    stats: &mut Stats,
+) -> Arity {
+    let arity = sets.first().map_or(0, |s| s.len());
+    let combos: Vec<(String, Vec<Card>)> = sets
+        .iter()
+        .map(|set| {
+            let names: Vec<&str> = set.iter().map(|c| c.name.as_str()).collect();
+            (names.join(" + "), set.clone())
+        })
+        .collect();
+    let results = combine_all(client, limit, cache, combos, stats).await;
+
+    let mut seen_pairs = HashSet::new();
+    let (mut pairs_cached, mut pairs_valid) = (0, 0);
+    // Short of every set when the budget ran out partway
+    let sets = sets
+        .iter()
+        .zip(results)
+        .map(|(set, result)| {
+            let mut same_as_pair = None;
+            for i in 0..set.len() {
+                for j in (i + 1)..set.len() {
+                    let Some(pair) = cache.get(&[set[i].clone(), set[j].clone()]) else {
+                        continue;
+                    };
+                    let valid = pair.name != "Not possible";
+                    if seen_pairs.insert((set[i].name.clone(), set[j].name.clone())) {
+                        pairs_cached += 1;
+                        if valid {
+                            pairs_valid += 1;
+                        }
+                    }
+                    let same = valid && pair.name.eq_ignore_ascii_case(&result.name);
+                    if same && same_as_pair.is_none() {
+                        same_as_pair = Some(format!("{} + {}", set[i].name, set[j].name));
+                    }
+                }
+            }
+            SetResult {
+                inputs: set.iter().map(|c| c.name.clone()).collect(),
+                result,
+                same_as_pair,
+            }
+        })
+        .collect();
+
+    Arity {
+        arity,
+        sets,
+        pairs_cached,
+        pairs_valid,
+    }
+}
+
+impl Arity {
+    fn valid(&self) -> impl Iterator<Item = &SetResult> {
+        self.sets.iter().filter(|s| s.valid())
+    }
+
+    fn pct(n: usize, of: usize) -> f64 {
+        if of > 0 {
+            n as f64 / of as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Counts: validity, distinct names, and results that add nothing over
+    /// an input or a pair.
+    fn summary(&self) -> Vec<(String, String)> {
+        let valid = self.valid().count();
+        let names: HashSet<String> = self.valid().map(|s| s.result.name.to_lowercase()).collect();
+        let absorbed = self.valid().filter(|s| s.absorbed()).count();
+        let same = self.valid().filter(|s| s.same_as_pair.is_some()).count();
+        vec![
+            ("Sets combined".to_string(), self.sets.len().to_string()),
+            (
+                "Valid".to_string(),
+                format!("{valid} ({:.0}%)", Self::pct(valid, self.sets.len())),
+            ),
+            (
+                "Pairs within them valid".to_string(),
+                format!(
+                    "{:.0}% of {} cached",
+                    Self::pct(self.pairs_valid, self.pairs_cached),
+                    self.pairs_cached
+                ),
+            ),
+            ("Distinct names".to_string(), names.len().to_string()),
+            (
+                "Result is an input".to_string(),
+                format!("{absorbed} ({:.0}% of valid)", Self::pct(absorbed, valid)),
+            ),
+            (
+                "Same as a pair within".to_string(),
+                format!("{same} ({:.0}% of valid)", Self::pct(same, valid)),
+            ),
+        ]
+    }
+
+    /// Print the counts and the results that add nothing over a pair.
+    pub fn print(&self) {
+        println!("\n{}", "=".repeat(60));
+        println!("{} MATERIALS AT ONCE", self.arity);
+        println!("{}\n", "=".repeat(60));
+
+        for (label, value) in self.summary() {
+            println!("  {:<26}{value}", format!("{label}:"));
+        }
+
+        let echoes: Vec<&SetResult> = self.valid().filter(|s| s.same_as_pair.is_some()).collect();
+        if !echoes.is_empty() {
+            println!("\n  Same as a pair within:");
+        }
+        for s in echoes {
+            let pair = s.same_as_pair.as_deref().unwrap_or_default();
+            println!("    {} = {} (as {pair})", s.label(), s.result.name);
+        }
+    }
+
+    /// Write the counts and every set's result.
+    pub fn write_to_file(&self, path: &str) {
+        let mut f = std::fs::File::create(path).expect("failed to create arity report file");
+
+        writeln!(f, "# {} Materials at Once\n", self.arity).unwrap();
+        writeln!(f, "| | |").unwrap();
+        writeln!(f, "|--|--|").unwrap();
+        for (label, value) in self.summary() {
+            writeln!(f, "| {label} | {value} |").unwrap();
+        }
+
+        writeln!(f, "\n## Results\n").unwrap();
+        writeln!(f, "| Materials | Result | Note |").unwrap();
+        writeln!(f, "|-----------|--------|------|").unwrap();
+        for s in &self.sets {
+            let note = match &s.same_as_pair {
+                _ if !s.valid() => String::new(),
+                _ if s.absorbed() => "an input".to_string(),
+                Some(pair) => format!("as {pair}"),
+                None => String::new(),
+            };
+            writeln!(f, "| {} | {} | {note} |", s.label(), s.result.name).unwrap();
+        }
+
+        println!("\nArity report written to {path}");
+    }
+}
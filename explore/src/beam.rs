@@ -0,0 +1,365 @@
+use crate::cache::Cache;
+use crate::combine::OllamaClient;
+use crate::theories::Card;
+use crate::{combine_all, Stats};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+pub struct BeamSettings {
+    /// Items to find; a card counts when its name contains one.
+    pub targets: Vec<String>,
+    /// Cards expanded per level.
+    pub width: usize,
+    pub max_depth: usize,
+}
+
+struct Found {
+    card: Card,
+    depth: usize,
+    /// The two cards first combined to make it; none for starting elements.
+    recipe: Option<(String, String)>,
+    /// How close it is to each target still missing when it was found.
+    scores: HashMap<String, u32>,
+}
+
+/// The shortest recipe found for a target: every combine needed, starting
+/// from the elements.
+pub struct Hit {
+    pub target: String,
+    pub card: String,
+    pub depth: usize,
+    /// (input, input, result), in the order they have to be made.
+    pub steps: Vec<(String, String, String)>,
+}
+
+pub struct BeamLevel {
+    pub depth: usize,
+    pub combos: usize,
+    pub new: usize,
+    /// (card, its best score against a missing target) expanded this level.
+    pub beam: Vec<(String, u32)>,
+}
+
+pub struct Beam {
+    pub targets: Vec<String>,
+    pub hits: Vec<Hit>,
+    pub levels: Vec<BeamLevel>,
+}
+
+/// Search for the targets from a set of elements: level 1 combines every
+/// pair of elements, and each later level expands only the `width` cards
+/// scored closest to a target still missing, combining each with the
+/// elements and every card expanded before. A target is found when a
+/// card's name matches it; since levels only go deeper, the first recipe
+/// found is the shortest this search knows.
+pub async fn run(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    cache: &mut Cache,
+    elements: &[Card],
+    settings: &BeamSettings,
+    stats: &mut Stats,
+) -> Beam {
+    let mut found: Vec<Found> = Vec::new();
+    // Lowercased name -> index into found
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for element in elements {
+        index.entry(element.name.to_lowercase()).or_insert_with(|| {
+            found.push(Found {
+                card: element.clone(),
+                depth: 0,
+                recipe: None,
+                scores: HashMap::new(),
+            });
+            found.len() - 1
+        });
+    }
+    let mut expanded: Vec<usize> = (0..found.len()).collect();
+    let mut combined: HashSet<(usize, usize)> = HashSet::new();
+    // An element can be a target itself
+    let mut hits: Vec<Hit> = Vec::new();
+    for target in &settings.targets {
+        if let Some(element) = found.iter().find(|f| matches(&f.card.name, target)) {
+            hits.push(Hit {
+                target: target.clone(),
+                card: element.card.name.clone(),
+                depth: 0,
+                steps: Vec::new(),
+            });
+        }
+    }
+    let mut levels = Vec::new();
+
+    for depth in 1..=settings.max_depth {
+        let missing = missing_targets(&settings.targets, &hits);
+        if missing.is_empty() {
+            break;
+        }
+
+        // Level 1 pairs up the elements; later levels the best cards so far
+        let beam: Vec<usize> = if depth == 1 {
+            Vec::new()
+        } else {
+            let mut candidates: Vec<usize> = (0..found.len())
+                .filter(|&i| found[i].depth > 0 && !expanded.contains(&i))
+                .collect();
+            candidates.sort_by_key(|&i| std::cmp::Reverse(best_score(&found[i], &missing)));
+            candidates.truncate(settings.width);
+            candidates
+        };
+        if depth > 1 && beam.is_empty() {
+            println!("Nothing left to expand; stopping\n");
+            break;
+        }
+        expanded.extend(&beam);
+
+        let mut pairs = Vec::new();
+        let sources = if depth == 1 { &expanded } else { &beam };
+        for &a in sources {
+            for &b in &expanded {
+                let pair = (a.min(b), a.max(b));
+                if combined.insert(pair) {
+                    pairs.push(pair);
+                }
+            }
+        }
+
+        let beam_scores: Vec<(String, u32)> = beam
+            .iter()
+            .map(|&i| (found[i].card.name.clone(), best_score(&found[i], &missing)))
+            .collect();
+        println!("--- Level {depth}: {} combinations ---", pairs.len());
+        for (name, score) in &beam_scores {
+            println!("  beam: {name} ({score}/10)");
+        }
+
+        let combos: Vec<(String, Vec<Card>)> = pairs
+            .iter()
+            .map(|&(a, b)| {
+                let (a, b) = (&found[a].card, &found[b].card);
+                (format!("{} + {}", a.name, b.name), vec![a.clone(), b.clone()])
+            })
+            .collect();
+        let results = combine_all(client, limit, cache, combos, stats).await;
+        // Short of every pair when the budget ran out partway
+        let combined = results.len();
+        println!();
+
+        let first_new = found.len();
+        for (&(a, b), result) in pairs.iter().zip(results) {
+            let key = result.name.to_lowercase();
+            if result.name == "Not possible" || index.contains_key(&key) {
+                continue;
+            }
+            index.insert(key, found.len());
+            found.push(Found {
+                card: Card::material(&result.name, &result.description),
+                depth,
+                recipe: Some((found[a].card.name.clone(), found[b].card.name.clone())),
+                scores: HashMap::new(),
+            });
+        }
+
+        for i in first_new..found.len() {
+            for target in &missing {
+                let already = hits.iter().any(|h| h.target == *target);
+                if already || !matches(&found[i].card.name, target) {
+                    continue;
+                }
+                let mut steps = Vec::new();
+                recipe_steps(&found, &index, i, &mut steps, &mut HashSet::new());
+                println!("  ** {target}: {} in {} steps", found[i].card.name, steps.len());
+                hits.push(Hit {
+                    target: target.clone(),
+                    card: found[i].card.name.clone(),
+                    depth,
+                    steps,
+                });
+            }
+        }
+
+        levels.push(BeamLevel {
+            depth,
+            combos: combined,
+            new: found.len() - first_new,
+            beam: beam_scores,
+        });
+        if client.budget().is_spent() {
+            break;
+        }
+
+        // Score what's new against what's still missing, to pick the next beam
+        let missing = missing_targets(&settings.targets, &hits);
+        if depth < settings.max_depth && !missing.is_empty() {
+            let new_cards: Vec<Card> = found[first_new..].iter().map(|f| f.card.clone()).collect();
+            println!("Scoring {} new cards against {} targets...", new_cards.len(), missing.len());
+            let scores = score_all(client, limit, new_cards, Arc::new(missing)).await;
+            for (f, scores) in found[first_new..].iter_mut().zip(scores) {
+                f.scores = scores;
+            }
+            println!();
+        }
+    }
+
+    hits.sort_by_key(|h| settings.targets.iter().position(|t| *t == h.target));
+    Beam {
+        targets: settings.targets.clone(),
+        hits,
+        levels,
+    }
+}
+
+fn missing_targets(targets: &[String], hits: &[Hit]) -> Vec<String> {
+    targets
+        .iter()
+        .filter(|t| !hits.iter().any(|h| h.target == **t))
+        .cloned()
+        .collect()
+}
+
+fn matches(name: &str, target: &str) -> bool {
+    name.to_lowercase().contains(&target.to_lowercase())
+}
+
+fn best_score(found: &Found, missing: &[String]) -> u32 {
+    missing
+        .iter()
+        .filter_map(|t| found.scores.get(t))
+        .copied()
+        .max()
+        .unwrap_or(0)
+}
+
+/// Add the combines that make `found[i]`, inputs first, to `steps`.
+fn recipe_steps(
+    found: &[Found],
+    index: &HashMap<String, usize>,
+    i: usize,
+    steps: &mut Vec<(String, String, String)>,
+    seen: &mut HashSet<usize>,
+) {
+    let Some((a, b)) = &found[i].recipe else {
+        return;
+    };
+    if !seen.insert(i) {
+        return;
+    }
+    for input in [a, b] {
+        if let Some(&j) = index.get(&input.to_lowercase()) {
+            recipe_steps(found, index, j, steps, seen);
+        }
+    }
+    steps.push((a.clone(), b.clone(), found[i].card.name.clone()));
+}
+
+/// Score each card against `targets`, with at most `limit` Ollama calls in
+/// flight. A card that fails to score gets no scores, so it's expanded last.
+async fn score_all(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    cards: Vec<Card>,
+    targets: Arc<Vec<String>>,
+) -> Vec<HashMap<String, u32>> {
+    let calls: Vec<_> = cards
+        .into_iter()
+        .map(|card| {
+            let (client, limit, targets) = (client.clone(), limit.clone(), targets.clone());
+            tokio::spawn(async move {
+                let _permit = limit.acquire_owned().await.map_err(|e| e.to_string())?;
+                let result = client.score_targets(&card.name, &card.description, &targets).await;
+                match &result {
+                    Err(e) if e != crate::budget::SPENT => {
+                        eprintln!("  Scoring {}... error: {e}", card.name);
+                    }
+                    _ => {}
+                }
+                result
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(calls.len());
+    for call in calls {
+        let scores = call.await.map_err(|e| e.to_string()).and_then(|r| r);
+        results.push(scores.unwrap_or_default());
+    }
+    results
+}
+
+impl Beam {
+    /// Print each target's shortest recipe, or that it wasn't found.
+    pub fn print(&self) {
+        println!("\n{}", "=".repeat(60));
+        println!("BEAM SEARCH");
+        println!("{}\n", "=".repeat(60));
+
+        for level in &self.levels {
+            println!(
+                "  Level {}: {} combinations, {} new cards, {} expanded",
+                level.depth,
+                level.combos,
+                level.new,
+                level.beam.len()
+            );
+        }
+        println!();
+
+        for target in &self.targets {
+            match self.hits.iter().find(|h| h.target == *target) {
+                Some(hit) => {
+                    println!(
+                        "  [x] {target}: {} (level {}, {} steps)",
+                        hit.card,
+                        hit.depth,
+                        hit.steps.len()
+                    );
+                    for (a, b, result) in &hit.steps {
+                        println!("        {a} + {b} = {result}");
+                    }
+                }
+                None => println!("  [ ] {target}"),
+            }
+        }
+        println!("\n  {}/{} targets found", self.hits.len(), self.targets.len());
+    }
+
+    /// Write each target's shortest recipe and the cards expanded per level.
+    pub fn write_to_file(&self, path: &str) {
+        let mut f = std::fs::File::create(path).expect("failed to create beam report file");
+
+        writeln!(f, "# Beam Search Report\n").unwrap();
+        writeln!(f, "{}/{} targets found.", self.hits.len(), self.targets.len()).unwrap();
+
+        writeln!(f, "\n## Targets\n").unwrap();
+        for target in &self.targets {
+            match self.hits.iter().find(|h| h.target == *target) {
+                Some(hit) => {
+                    writeln!(f, "### {target}: {} ({} steps)\n", hit.card, hit.steps.len())
+                        .unwrap();
+                    for (n, (a, b, result)) in hit.steps.iter().enumerate() {
+                        writeln!(f, "{}. {a} + {b} = {result}", n + 1).unwrap();
+                    }
+                    writeln!(f).unwrap();
+                }
+                None => writeln!(f, "### {target}: not found\n").unwrap(),
+            }
+        }
+
+        writeln!(f, "## Levels\n").unwrap();
+        for level in &self.levels {
+            writeln!(
+                f,
+                "- Level {}: {} combinations, {} new cards",
+                level.depth, level.combos, level.new
+            )
+            .unwrap();
+            for (name, score) in &level.beam {
+                writeln!(f, "  - expanded {name} ({score}/10)").unwrap();
+            }
+        }
+
+        println!("\nBeam report written to {path}");
+    }
+}
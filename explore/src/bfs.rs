@@ -0,0 +1,235 @@
+use crate::cache::Cache;
+use crate::combine::OllamaClient;
+use crate::theories::Card;
+use crate::{combine_all, Stats};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// A card and the level it was first made at; the starting elements are
+/// level 0.
+pub struct Discovery {
+    pub card: Card,
+    pub depth: usize,
+}
+
+/// One valid combine, whether or not it made anything new.
+pub struct Recipe {
+    pub inputs: (String, String),
+    pub result: String,
+}
+
+pub struct LevelStats {
+    pub depth: usize,
+    pub combos: usize,
+    pub valid: usize,
+    /// Results not seen before, fed into the next level.
+    pub new: usize,
+    /// Results already found at an earlier level or earlier in this one.
+    pub duplicates: usize,
+    /// Results that are one of their own inputs, e.g. Stone + Sand = Stone.
+    pub cycles: usize,
+}
+
+/// Everything reachable from a set of elements within some number of
+/// combines.
+pub struct Bfs {
+    pub discoveries: Vec<Discovery>,
+    pub recipes: Vec<Recipe>,
+    pub levels: Vec<LevelStats>,
+}
+
+/// Combine every pair of known cards, level by level: level 1 pairs up the
+/// elements, and each later level pairs every card found at the level
+/// before with every card known so far (itself included). Valid results not
+/// seen before become cards for the next level. Stops after `max_depth`
+/// levels, or once a level finds nothing new.
+pub async fn run(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    cache: &mut Cache,
+    elements: &[Card],
+    max_depth: usize,
+    stats: &mut Stats,
+) -> Bfs {
+    let mut bfs = Bfs {
+        discoveries: Vec::new(),
+        recipes: Vec::new(),
+        levels: Vec::new(),
+    };
+    // Lowercased names of every card so far
+    let mut known: HashSet<String> = HashSet::new();
+    for element in elements {
+        if !known.insert(element.name.to_lowercase()) {
+            continue;
+        }
+        bfs.discoveries.push(Discovery {
+            card: element.clone(),
+            depth: 0,
+        });
+    }
+
+    let mut frontier_start = 0;
+    for depth in 1..=max_depth {
+        let frontier_end = bfs.discoveries.len();
+        // Each pair once: a frontier card with every card before it, or
+        // with itself
+        let mut combos = Vec::new();
+        for i in frontier_start..frontier_end {
+            for j in 0..=i {
+                let (a, b) = (&bfs.discoveries[j].card, &bfs.discoveries[i].card);
+                let label = format!("{} + {}", a.name, b.name);
+                combos.push((label, vec![a.clone(), b.clone()]));
+            }
+        }
+        println!("--- Level {depth}: {} combinations ---", combos.len());
+
+        let inputs: Vec<(String, String)> = combos
+            .iter()
+            .map(|(_, cards)| (cards[0].name.clone(), cards[1].name.clone()))
+            .collect();
+        let mut level = LevelStats {
+            depth,
+            combos: combos.len(),
+            valid: 0,
+            new: 0,
+            duplicates: 0,
+            cycles: 0,
+        };
+        let results = combine_all(client, limit, cache, combos, stats).await;
+        // Short of every combo when the budget ran out partway
+        level.combos = results.len();
+        println!();
+
+        for ((a, b), result) in inputs.into_iter().zip(results) {
+            if result.name == "Not possible" {
+                continue;
+            }
+            level.valid += 1;
+            let key = result.name.to_lowercase();
+            if key == a.to_lowercase() || key == b.to_lowercase() {
+                level.cycles += 1;
+            } else if known.insert(key) {
+                level.new += 1;
+                bfs.discoveries.push(Discovery {
+                    card: Card::material(&result.name, &result.description),
+                    depth,
+                });
+            } else {
+                level.duplicates += 1;
+            }
+            bfs.recipes.push(Recipe {
+                inputs: (a, b),
+                result: result.name,
+            });
+        }
+
+        let found_nothing = level.new == 0;
+        bfs.levels.push(level);
+        frontier_start = frontier_end;
+        if client.budget().is_spent() {
+            break;
+        }
+        if found_nothing {
+            println!("Level {depth} found nothing new; stopping\n");
+            break;
+        }
+    }
+    bfs
+}
+
+impl Bfs {
+    /// Print per-level stats.
+    pub fn print(&self) {
+        println!("\n{}", "=".repeat(60));
+        println!("BFS DISCOVERY");
+        println!("{}\n", "=".repeat(60));
+
+        println!(
+            "  {:>5}  {:>7}  {:>6}  {:>5}  {:>10}  {:>6}",
+            "Level", "Combos", "Valid", "New", "Duplicates", "Cycles"
+        );
+        for level in &self.levels {
+            println!(
+                "  {:>5}  {:>7}  {:>5.0}%  {:>5}  {:>10}  {:>6}",
+                level.depth,
+                level.combos,
+                if level.combos > 0 {
+                    level.valid as f64 / level.combos as f64 * 100.0
+                } else {
+                    0.0
+                },
+                level.new,
+                level.duplicates,
+                level.cycles
+            );
+        }
+
+        let elements = self.discoveries.iter().filter(|d| d.depth == 0).count();
+        println!(
+            "\n  {} cards discovered from {elements} elements, by {} recipes",
+            self.discoveries.len() - elements,
+            self.recipes.len()
+        );
+    }
+
+    /// Write per-level stats and every discovery with its recipes, first
+    /// found first.
+    pub fn write_to_file(&self, path: &str) {
+        let mut f = std::fs::File::create(path).expect("failed to create BFS report file");
+
+        writeln!(f, "# BFS Discovery Report\n").unwrap();
+        writeln!(f, "| Level | Combos | Valid | New | Duplicates | Cycles |").unwrap();
+        writeln!(f, "|-------|--------|-------|-----|------------|--------|").unwrap();
+        for level in &self.levels {
+            writeln!(
+                f,
+                "| {} | {} | {} | {} | {} | {} |",
+                level.depth, level.combos, level.valid, level.new, level.duplicates, level.cycles
+            )
+            .unwrap();
+        }
+
+        let mut recipes: HashMap<String, Vec<&Recipe>> = HashMap::new();
+        for recipe in &self.recipes {
+            recipes.entry(recipe.result.to_lowercase()).or_default().push(recipe);
+        }
+
+        let max_depth = self.levels.len();
+        for depth in 0..=max_depth {
+            let found: Vec<&Discovery> =
+                self.discoveries.iter().filter(|d| d.depth == depth).collect();
+            if found.is_empty() {
+                continue;
+            }
+            if depth == 0 {
+                writeln!(f, "\n## Elements\n").unwrap();
+            } else {
+                writeln!(f, "\n## Level {depth}\n").unwrap();
+            }
+            for d in found {
+                let made_by: Vec<String> = recipes
+                    .get(&d.card.name.to_lowercase())
+                    .into_iter()
+                    .flatten()
+                    .map(|r| format!("{} + {}", r.inputs.0, r.inputs.1))
+                    .collect();
+                if made_by.is_empty() {
+                    writeln!(f, "- **{}** — {}", d.card.name, d.card.description).unwrap();
+                } else {
+                    writeln!(
+                        f,
+                        "- **{}** = {} — {}",
+                        d.card.name,
+                        made_by.join(", "),
+                        d.card.description
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        println!("\nBFS report written to {path}");
+    }
+}
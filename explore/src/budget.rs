@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// The error a call gets once the budget is spent. Callers drop these
+/// quietly: the run is stopping, not failing.
+pub const SPENT: &str = "Run budget spent";
+
+/// Caps on a run's calls and wall time, from `--max-calls` and
+/// `--max-minutes`. Every Ollama or generation server call takes one call
+/// from it as it starts; calls already in flight when it runs out finish.
+pub struct Budget {
+    max_calls: Option<usize>,
+    max_minutes: Option<f64>,
+    started: Instant,
+    calls: AtomicUsize,
+    spent: AtomicBool,
+}
+
+impl Budget {
+    pub fn new(max_calls: Option<usize>, max_minutes: Option<f64>) -> Self {
+        Self {
+            max_calls,
+            max_minutes,
+            started: Instant::now(),
+            calls: AtomicUsize::new(0),
+            spent: AtomicBool::new(false),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None, None)
+    }
+
+    /// Take one call, or refuse with `SPENT` once either limit is reached.
+    pub fn take(&self) -> Result<(), String> {
+        let out_of_time = self
+            .max_minutes
+            .is_some_and(|m| self.started.elapsed() >= Duration::from_secs_f64(m * 60.0));
+        let out_of_calls = || {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst);
+            self.max_calls.is_some_and(|max| calls >= max)
+        };
+        if self.is_spent() || out_of_time || out_of_calls() {
+            self.spent.store(true, Ordering::SeqCst);
+            return Err(SPENT.to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether a call has been refused, so whatever it was part of is
+    /// incomplete.
+    pub fn is_spent(&self) -> bool {
+        self.spent.load(Ordering::SeqCst)
+    }
+
+    /// Which limit stopped the run, e.g. "the 500 call budget".
+    pub fn describe(&self) -> String {
+        match self.max_calls {
+            Some(max) if self.calls.load(Ordering::SeqCst) > max => {
+                format!("the {max} call budget")
+            }
+            _ => format!("the {} minute budget", self.max_minutes.unwrap_or_default()),
+        }
+    }
+}
@@ -1,57 +1,253 @@
 use crate::combine::CombineResult;
 use crate::theories::Card;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use rusqlite::{params, Connection, OptionalExtension};
 use std::path::Path;
+use std::time::Duration;
 
-#[derive(Serialize, Deserialize, Default)]
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS combines (
+        model       TEXT NOT NULL,
+        prompt      TEXT NOT NULL,
+        key         TEXT NOT NULL,
+        name        TEXT NOT NULL,
+        description TEXT NOT NULL,
+        PRIMARY KEY (model, prompt, key)
+    );
+    CREATE TABLE IF NOT EXISTS errors (
+        model  TEXT NOT NULL,
+        prompt TEXT NOT NULL,
+        key    TEXT NOT NULL,
+        label  TEXT NOT NULL,
+        cards  TEXT NOT NULL,
+        error  TEXT NOT NULL,
+        PRIMARY KEY (model, prompt, key)
+    );
+    CREATE TABLE IF NOT EXISTS latencies (
+        model TEXT NOT NULL,
+        ms    INTEGER NOT NULL
+    );
+";
+
+/// Call times kept per model for `recent_latency`.
+const LATENCY_WINDOW: usize = 200;
+
+/// Combine results in SQLite, kept apart per model and prompt fingerprint:
+/// switching either starts on an empty cache without losing the old one, and
+/// switching back picks it up again.
 pub struct Cache {
-    results: HashMap<String, CachedEntry>,
+    conn: Connection,
+    model: String,
+    prompt: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
 pub struct CachedEntry {
     pub name: String,
     pub description: String,
 }
 
 impl Cache {
-    pub fn load(path: &Path) -> Self {
-        match std::fs::read_to_string(path) {
-            Ok(data) => serde_json::from_str(&data).unwrap_or_default(),
-            Err(_) => Self::default(),
-        }
-    }
-
-    pub fn save(&self, path: &Path) {
-        let data = serde_json::to_string_pretty(self).expect("failed to serialize cache");
-        std::fs::write(path, data).expect("failed to write cache file");
+    /// Open (or create) the cache at `path`, reading and writing only the
+    /// results for `model` under the prompt fingerprinted by `prompt`.
+    pub fn open(path: &Path, model: &str, prompt: &str) -> Result<Self, String> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open cache {}: {e}", path.display()))?;
+        // Every result is its own small write; WAL at NORMAL sync keeps those cheap
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|_| conn.pragma_update(None, "synchronous", "NORMAL"))
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .map_err(|e| format!("Failed to initialize cache: {e}"))?;
+        Ok(Self {
+            conn,
+            model: model.to_string(),
+            prompt: prompt.to_string(),
+        })
     }
 
     pub fn get(&self, cards: &[Card]) -> Option<CombineResult> {
-        let key = cache_key(cards);
-        self.results.get(&key).map(|e| CombineResult {
-            name: e.name.clone(),
-            description: e.description.clone(),
-        })
+        self.conn
+            .query_row(
+                "SELECT name, description FROM combines
+                 WHERE model = ?1 AND prompt = ?2 AND key = ?3",
+                params![self.model, self.prompt, cache_key(cards)],
+                |r| {
+                    Ok(CombineResult {
+                        name: r.get(0)?,
+                        description: r.get(1)?,
+                    })
+                },
+            )
+            .optional()
+            .expect("failed to read cache")
     }
 
+    /// Cache a result, and forget any error the same cards got before.
     pub fn insert(&mut self, cards: &[Card], result: &CombineResult) {
         let key = cache_key(cards);
-        self.results.insert(
-            key,
-            CachedEntry {
-                name: result.name.clone(),
-                description: result.description.clone(),
-            },
-        );
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO combines (model, prompt, key, name, description)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![self.model, self.prompt, key, result.name, result.description],
+            )
+            .and_then(|_| {
+                self.conn.execute(
+                    "DELETE FROM errors WHERE model = ?1 AND prompt = ?2 AND key = ?3",
+                    params![self.model, self.prompt, key],
+                )
+            })
+            .expect("failed to write cache");
     }
 
+    /// Note a combine that failed, so `--retry-errors` can run it again.
+    /// Errors are kept apart from results: they're never served as one.
+    pub fn record_error(&mut self, label: &str, cards: &[Card], error: &str) {
+        let cards_json = serde_json::to_string(cards).expect("failed to serialize cards");
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO errors (model, prompt, key, label, cards, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![self.model, self.prompt, cache_key(cards), label, cards_json, error],
+            )
+            .expect("failed to write cache");
+    }
+
+    /// Every combine that last failed for this model and prompt, as
+    /// (label, cards, error).
+    pub fn errors(&self) -> Vec<(String, Vec<Card>, String)> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT label, cards, error FROM errors WHERE model = ?1 AND prompt = ?2
+                 ORDER BY label",
+            )
+            .expect("failed to read cache");
+        let rows = stmt
+            .query_map(params![self.model, self.prompt], |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, String>(2)?))
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .expect("failed to read cache");
+        rows.into_iter()
+            .map(|(label, cards, error)| {
+                let cards = serde_json::from_str(&cards).expect("failed to read cached cards");
+                (label, cards, error)
+            })
+            .collect()
+    }
+
+    /// The description of a result cached under `name`, for using it as a
+    /// card again.
+    pub fn describe(&self, name: &str) -> Option<String> {
+        self.conn
+            .query_row(
+                "SELECT description FROM combines
+                 WHERE model = ?1 AND prompt = ?2 AND name = ?3 COLLATE NOCASE LIMIT 1",
+                params![self.model, self.prompt, name],
+                |r| r.get(0),
+            )
+            .optional()
+            .expect("failed to read cache")
+    }
+
+    /// Results cached for this model and prompt.
     pub fn len(&self) -> usize {
-        self.results.len()
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM combines WHERE model = ?1 AND prompt = ?2",
+                params![self.model, self.prompt],
+                |r| r.get::<_, i64>(0),
+            )
+            .expect("failed to read cache") as usize
+    }
+
+    /// Every model and prompt with results in this cache file, as (model,
+    /// prompt, results).
+    pub fn slices(&self) -> Vec<(String, String, usize)> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT model, prompt, COUNT(*) FROM combines GROUP BY model, prompt
+                 ORDER BY model, prompt",
+            )
+            .expect("failed to read cache");
+        stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get::<_, i64>(2)? as usize)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .expect("failed to read cache")
+    }
+
+    /// Note how long an uncached combine took, keeping only the latest few
+    /// per model.
+    pub fn record_latency(&mut self, elapsed: Duration) {
+        self.conn
+            .execute(
+                "INSERT INTO latencies (model, ms) VALUES (?1, ?2)",
+                params![self.model, elapsed.as_millis() as i64],
+            )
+            .and_then(|_| {
+                self.conn.execute(
+                    "DELETE FROM latencies WHERE model = ?1 AND rowid NOT IN
+                     (SELECT rowid FROM latencies WHERE model = ?1 ORDER BY rowid DESC LIMIT ?2)",
+                    params![self.model, LATENCY_WINDOW as i64],
+                )
+            })
+            .expect("failed to write cache");
+    }
+
+    /// Mean time of this model's recent combines, if any were recorded.
+    pub fn recent_latency(&self) -> Option<Duration> {
+        self.conn
+            .query_row(
+                "SELECT AVG(ms) FROM latencies WHERE model = ?1",
+                params![self.model],
+                |r| r.get::<_, Option<f64>>(0),
+            )
+            .expect("failed to read cache")
+            .map(|ms| Duration::from_millis(ms as u64))
+    }
+
+    /// Every combine cached for this model and prompt as (materials,
+    /// modifier, result). Input names are lowercased, as they are in cache
+    /// keys.
+    pub fn recipes(&self) -> Vec<(Vec<String>, Option<String>, CachedEntry)> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, name, description FROM combines WHERE model = ?1 AND prompt = ?2")
+            .expect("failed to read cache");
+        let rows = stmt
+            .query_map(params![self.model, self.prompt], |r| {
+                Ok((
+                    r.get::<_, String>(0)?,
+                    CachedEntry {
+                        name: r.get(1)?,
+                        description: r.get(2)?,
+                    },
+                ))
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .expect("failed to read cache");
+        rows.into_iter()
+            .map(|(key, entry)| {
+                let (materials, intent) = split_key(&key);
+                (materials, intent, entry)
+            })
+            .collect()
     }
 }
 
+/// The material and intent names a `cache_key` was made from.
+fn split_key(key: &str) -> (Vec<String>, Option<String>) {
+    let (materials, intent) = match key.split_once("+[") {
+        Some((materials, intent)) => (materials, intent.strip_suffix(']')),
+        None => (key, None),
+    };
+    let materials = materials
+        .split('+')
+        .filter(|m| !m.is_empty())
+        .map(str::to_string)
+        .collect();
+    (materials, intent.map(str::to_string))
+}
+
 fn cache_key(cards: &[Card]) -> String {
     use crate::theories::CardKind;
 
@@ -72,3 +268,35 @@ fn cache_key(cards: &[Card]) -> String {
         None => materials.join("+"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_key_ignores_order_and_case() {
+        let fire = Card::material("Fire", "");
+        let water = Card::material("water", "");
+        assert_eq!(cache_key(&[fire.clone(), water.clone()]), "fire+water");
+        assert_eq!(cache_key(&[water, fire]), "fire+water");
+    }
+
+    #[test]
+    fn cache_key_puts_the_intent_last() {
+        let cards = [
+            Card::intent("Tool", "tools"),
+            Card::material("Stone", ""),
+            Card::material("Wood", ""),
+        ];
+        assert_eq!(cache_key(&cards), "stone+wood+[tool]");
+    }
+
+    #[test]
+    fn split_key_undoes_cache_key() {
+        assert_eq!(
+            split_key("stone+wood+[tool]"),
+            (vec!["stone".to_string(), "wood".to_string()], Some("tool".to_string()))
+        );
+        assert_eq!(split_key("fire+water"), (vec!["fire".to_string(), "water".to_string()], None));
+    }
+}
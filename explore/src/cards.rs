@@ -0,0 +1,49 @@
+use crate::theories::{Card, ElementTheory, ModifierFamily};
+use serde::Serialize;
+use std::path::Path;
+
+/// A card as the game's `cards.json` lists it.
+#[derive(Serialize)]
+struct GameCard<'a> {
+    name: &'a str,
+    description: &'a str,
+}
+
+/// The game's `cards.json`: materials to draw, and intents to steer with.
+#[derive(Serialize)]
+struct CardsFile<'a> {
+    materials: Vec<GameCard<'a>>,
+    intents: Vec<GameCard<'a>>,
+}
+
+fn game_cards(cards: &[Card]) -> Vec<GameCard<'_>> {
+    cards
+        .iter()
+        .map(|c| GameCard {
+            name: &c.name,
+            description: &c.description,
+        })
+        .collect()
+}
+
+/// Write `theory`'s elements as materials and `family`'s modifiers as
+/// intents, in the format the game server loads. Descriptions are kept as
+/// explored, since the game sends them to the model the same way.
+pub fn write(path: &Path, theory: &ElementTheory, family: &ModifierFamily) -> Result<(), String> {
+    let file = CardsFile {
+        materials: game_cards(&theory.elements),
+        intents: game_cards(&family.modifiers),
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    std::fs::write(path, json + "\n")
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    println!(
+        "\nCards written to {}: {} materials from theory {}, {} intents from family {}",
+        path.display(),
+        file.materials.len(),
+        theory.name,
+        file.intents.len(),
+        family.name
+    );
+    Ok(())
+}
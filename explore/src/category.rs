@@ -0,0 +1,338 @@
+use crate::cache::Cache;
+use crate::combine::OllamaClient;
+use crate::theories::Card;
+use crate::{combine_all, Stats};
+use std::collections::HashSet;
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Intents tried with every combine, the ones scored best for the category.
+const PROMISING_INTENTS: usize = 3;
+
+/// Score at which an item counts as filling the category.
+pub const HIT_SCORE: u32 = 7;
+
+pub struct CategorySettings {
+    pub category: String,
+    /// Materials combined, and results chained further, per level.
+    pub width: usize,
+    pub max_depth: usize,
+}
+
+/// A distinct result, scored for the category.
+pub struct Item {
+    pub card: Card,
+    pub depth: usize,
+    pub score: u32,
+    /// Every combine that makes it, e.g. "Wood + Metal [Loud] = Bell", in
+    /// the order they have to be made.
+    pub steps: Vec<String>,
+}
+
+pub struct CategoryLevel {
+    pub depth: usize,
+    pub combos: usize,
+    pub new: usize,
+    /// New items scoring at least `HIT_SCORE`.
+    pub hits: usize,
+    /// Items chained from at this level, none at level 1.
+    pub chained: Vec<String>,
+}
+
+pub struct CategoryDive {
+    pub category: String,
+    /// (card, category score), best first.
+    pub materials: Vec<(String, u32)>,
+    pub intents: Vec<(String, u32)>,
+    pub items: Vec<Item>,
+    pub levels: Vec<CategoryLevel>,
+}
+
+/// Search for items that fit one category. The `width` materials and
+/// `PROMISING_INTENTS` intents scored best for it are picked first; level 1
+/// combines every pair of those materials, bare and with each intent, and
+/// each later level chains the `width` best new items from the level
+/// before with the same materials and intents. Every distinct result is
+/// scored, so the report can list all that fit.
+pub async fn run(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    cache: &mut Cache,
+    elements: &[Card],
+    intents: &[Card],
+    settings: &CategorySettings,
+    stats: &mut Stats,
+) -> CategoryDive {
+    let category = Arc::new(settings.category.clone());
+    println!("Scoring {} materials and {} intents...", elements.len(), intents.len());
+    let materials = promising(client, limit, elements, &category, settings.width).await;
+    let intents = promising(client, limit, intents, &category, PROMISING_INTENTS).await;
+    let shown = |cards: &[(Card, u32)]| {
+        let shown: Vec<String> =
+            cards.iter().map(|(c, score)| format!("{} ({score})", c.name)).collect();
+        shown.join(", ")
+    };
+    println!("  Materials: {}", shown(&materials));
+    println!("  Intents: {}\n", shown(&intents));
+
+    let mut dive = CategoryDive {
+        category: settings.category.clone(),
+        materials: materials.iter().map(|(c, s)| (c.name.clone(), *s)).collect(),
+        intents: intents.iter().map(|(c, s)| (c.name.clone(), *s)).collect(),
+        items: Vec::new(),
+        levels: Vec::new(),
+    };
+    if client.budget().is_spent() {
+        return dive;
+    }
+    // Lowercased names of every card so far, so each item is scored once
+    let mut known: HashSet<String> = elements
+        .iter()
+        .chain(intents.iter().map(|(c, _)| c))
+        .map(|c| c.name.to_lowercase())
+        .collect();
+    let materials: Vec<Card> = materials.into_iter().map(|(c, _)| c).collect();
+    let intents: Vec<Card> = intents.into_iter().map(|(c, _)| c).collect();
+
+    let mut previous = 0..0;
+    for depth in 1..=settings.max_depth {
+        // Level 1 pairs up the materials; later levels chain the best new
+        // items with them
+        let sources: Vec<(Card, Vec<String>)> = if depth == 1 {
+            materials.iter().map(|m| (m.clone(), Vec::new())).collect()
+        } else {
+            let mut best: Vec<&Item> = dive.items[previous.clone()].iter().collect();
+            best.sort_by_key(|item| std::cmp::Reverse(item.score));
+            best.truncate(settings.width);
+            best.iter().map(|item| (item.card.clone(), item.steps.clone())).collect()
+        };
+        if sources.is_empty() {
+            println!("Nothing left to chain; stopping\n");
+            break;
+        }
+
+        let mut combos = Vec::new();
+        let mut chains = Vec::new();
+        for (i, (source, steps)) in sources.iter().enumerate() {
+            // At level 1, each pair of materials once
+            let partners = if depth == 1 { &materials[i..] } else { &materials[..] };
+            for partner in partners {
+                let pair = format!("{} + {}", source.name, partner.name);
+                combos.push((pair.clone(), vec![source.clone(), partner.clone()]));
+                chains.push(steps);
+                for intent in &intents {
+                    let label = format!("{pair} [{}]", intent.name);
+                    let cards = vec![source.clone(), partner.clone(), intent.clone()];
+                    combos.push((label, cards));
+                    chains.push(steps);
+                }
+            }
+        }
+        let chained = if depth == 1 {
+            Vec::new()
+        } else {
+            sources.iter().map(|(c, _)| c.name.clone()).collect()
+        };
+        println!("--- Level {depth}: {} combinations ---", combos.len());
+        for name in &chained {
+            println!("  chaining: {name}");
+        }
+
+        let labels: Vec<String> = combos.iter().map(|(label, _)| label.clone()).collect();
+        let results = combine_all(client, limit, cache, combos, stats).await;
+        // Short of every combine when the budget ran out partway
+        let combined = results.len();
+        println!();
+
+        let mut new = Vec::new();
+        for ((label, steps), result) in labels.iter().zip(chains).zip(results) {
+            if result.name == "Not possible" || !known.insert(result.name.to_lowercase()) {
+                continue;
+            }
+            let mut steps = steps.clone();
+            steps.push(format!("{label} = {}", result.name));
+            new.push((Card::material(&result.name, &result.description), steps));
+        }
+
+        let cards: Vec<Card> = new.iter().map(|(card, _)| card.clone()).collect();
+        if !cards.is_empty() {
+            println!("Scoring {} new items for {}...", cards.len(), settings.category);
+        }
+        let scores = score_all(client, limit, &cards, &category).await;
+        let first_new = dive.items.len();
+        for ((card, steps), score) in new.into_iter().zip(scores) {
+            // Unscored items are neither hits nor worth chaining
+            let Some(score) = score else {
+                continue;
+            };
+            if score >= HIT_SCORE {
+                println!("  ** {} ({score}/10)", card.name);
+            }
+            dive.items.push(Item {
+                card,
+                depth,
+                score,
+                steps,
+            });
+        }
+        previous = first_new..dive.items.len();
+        dive.levels.push(CategoryLevel {
+            depth,
+            combos: combined,
+            new: previous.len(),
+            hits: dive.items[previous.clone()].iter().filter(|i| i.score >= HIT_SCORE).count(),
+            chained,
+        });
+        println!();
+        if client.budget().is_spent() {
+            break;
+        }
+    }
+
+    dive.items.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.depth.cmp(&b.depth))
+            .then_with(|| a.card.name.cmp(&b.card.name))
+    });
+    dive
+}
+
+/// The `count` cards scored best for the category, best first. Cards that
+/// fail to score come last.
+async fn promising(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    cards: &[Card],
+    category: &Arc<String>,
+    count: usize,
+) -> Vec<(Card, u32)> {
+    let scores = score_all(client, limit, cards, category).await;
+    let mut ranked: Vec<(Card, u32)> = cards
+        .iter()
+        .cloned()
+        .zip(scores.into_iter().map(|s| s.unwrap_or(0)))
+        .collect();
+    ranked.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    ranked.truncate(count);
+    ranked
+}
+
+/// Score each card for the category, with at most `limit` Ollama calls in
+/// flight. A card that fails to score gets none.
+async fn score_all(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    cards: &[Card],
+    category: &Arc<String>,
+) -> Vec<Option<u32>> {
+    let calls: Vec<_> = cards
+        .iter()
+        .map(|card| {
+            let (client, limit, category) = (client.clone(), limit.clone(), category.clone());
+            let card = card.clone();
+            tokio::spawn(async move {
+                let _permit = limit.acquire_owned().await.map_err(|e| e.to_string())?;
+                let categories = [category.to_string()];
+                let result =
+                    client.score_categories(&card.name, &card.description, &categories).await;
+                match &result {
+                    Err(e) if e != crate::budget::SPENT => {
+                        eprintln!("  Scoring {}... error: {e}", card.name);
+                    }
+                    _ => {}
+                }
+                result.map(|scores| scores.get(category.as_str()).copied().unwrap_or(0))
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(calls.len());
+    for call in calls {
+        results.push(call.await.map_err(|e| e.to_string()).and_then(|r| r).ok());
+    }
+    results
+}
+
+impl CategoryDive {
+    fn hits(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter().filter(|i| i.score >= HIT_SCORE)
+    }
+
+    /// Print each level, then every item that fits the category with how
+    /// to make it.
+    pub fn print(&self) {
+        println!("\n{}", "=".repeat(60));
+        println!("CATEGORY: {}", self.category);
+        println!("{}\n", "=".repeat(60));
+
+        for level in &self.levels {
+            println!(
+                "  Level {}: {} combinations, {} new items, {} scoring {HIT_SCORE}+",
+                level.depth, level.combos, level.new, level.hits
+            );
+        }
+        println!();
+
+        for item in self.hits() {
+            println!("  {}/10  {} (level {})", item.score, item.card.name, item.depth);
+            for step in &item.steps {
+                println!("          {step}");
+            }
+        }
+        println!(
+            "\n  {} of {} distinct items score {HIT_SCORE}+ for {}",
+            self.hits().count(),
+            self.items.len(),
+            self.category
+        );
+    }
+
+    /// Write the cards picked, every item that fits with its recipe, and
+    /// each level's counts.
+    pub fn write_to_file(&self, path: &str) {
+        let mut f = std::fs::File::create(path).expect("failed to create category report file");
+
+        writeln!(f, "# Category Report: {}\n", self.category).unwrap();
+        writeln!(
+            f,
+            "{} of {} distinct items score {HIT_SCORE}+.\n",
+            self.hits().count(),
+            self.items.len()
+        )
+        .unwrap();
+        let listed = |cards: &[(String, u32)]| {
+            let listed: Vec<String> =
+                cards.iter().map(|(name, score)| format!("{name} ({score})")).collect();
+            listed.join(", ")
+        };
+        writeln!(f, "- Materials: {}", listed(&self.materials)).unwrap();
+        writeln!(f, "- Intents: {}", listed(&self.intents)).unwrap();
+
+        writeln!(f, "\n## Items\n").unwrap();
+        for item in self.hits() {
+            writeln!(f, "### {} ({}/10)\n", item.card.name, item.score).unwrap();
+            writeln!(f, "{}\n", item.card.description).unwrap();
+            for (n, step) in item.steps.iter().enumerate() {
+                writeln!(f, "{}. {step}", n + 1).unwrap();
+            }
+            writeln!(f).unwrap();
+        }
+
+        writeln!(f, "## Levels\n").unwrap();
+        for level in &self.levels {
+            writeln!(
+                f,
+                "- Level {}: {} combinations, {} new items, {} scoring {HIT_SCORE}+",
+                level.depth, level.combos, level.new, level.hits
+            )
+            .unwrap();
+            for name in &level.chained {
+                writeln!(f, "  - chained {name}").unwrap();
+            }
+        }
+
+        println!("\nCategory report written to {path}");
+    }
+}
@@ -0,0 +1,127 @@
+use crate::report::Report;
+use crate::theories::PairSampling;
+use crate::Stats;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// The flags that decide what a run covers. A run can only be resumed with
+/// the same ones.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct RunOptions {
+    pub step: Option<u32>,
+    pub deep: bool,
+    pub sensory: bool,
+    pub no_score: bool,
+    pub quality: bool,
+    pub judge_model: Option<String>,
+    pub model: String,
+    pub server_url: Option<String>,
+    pub images: Option<u32>,
+    pub config: Option<PathBuf>,
+    pub pairs: PairSampling,
+    pub pair_seed: u64,
+    pub samples: u32,
+    pub sample_temperature: f32,
+}
+
+/// A run's progress, saved after every section (a family, a theory, a chunk
+/// of scoring) so `--resume` carries on after the last one finished. The
+/// combine cache alone would replay finished combines, but not scoring or
+/// the report built so far.
+pub struct Checkpoint {
+    path: PathBuf,
+    options: RunOptions,
+    /// Sections finished, e.g. "step1/bare" or "step2/A".
+    done: BTreeSet<String>,
+    /// Where the run writes its reports, so a resumed run carries on there.
+    run_dir: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    options: RunOptions,
+    done: BTreeSet<String>,
+    #[serde(default)]
+    run_dir: Option<PathBuf>,
+    report: Report,
+    stats: Stats,
+}
+
+impl Checkpoint {
+    pub fn new(path: &Path, options: RunOptions) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            options,
+            done: BTreeSet::new(),
+            run_dir: None,
+        }
+    }
+
+    /// Pick up the run saved at `path`, with the report and stats it had.
+    /// Starts over when there's nothing saved.
+    pub fn resume(path: &Path, options: RunOptions) -> Result<(Self, Report, Stats), String> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(_) => {
+                println!("No run to resume at {}, starting over", path.display());
+                return Ok((Self::new(path, options), Report::new(), Stats::new()));
+            }
+        };
+        let saved: Saved = serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        if saved.options != options {
+            return Err(format!(
+                "{} was started with {:?}; resume with the same flags",
+                path.display(),
+                saved.options
+            ));
+        }
+        println!("Resuming: {} sections already done", saved.done.len());
+        let checkpoint = Self {
+            path: path.to_path_buf(),
+            options: saved.options,
+            done: saved.done,
+            run_dir: saved.run_dir,
+        };
+        Ok((checkpoint, saved.report, saved.stats))
+    }
+
+    pub fn run_dir(&self) -> Option<&Path> {
+        self.run_dir.as_deref()
+    }
+
+    pub fn set_run_dir(&mut self, path: &Path) {
+        self.run_dir = Some(path.to_path_buf());
+    }
+
+    pub fn is_done(&self, section: &str) -> bool {
+        self.done.contains(section)
+    }
+
+    /// Mark `section` finished and save the run as it stands.
+    pub fn finish(&mut self, section: &str, report: &Report, stats: &Stats) {
+        self.done.insert(section.to_string());
+        self.save(report, stats);
+    }
+
+    pub fn save(&self, report: &Report, stats: &Stats) {
+        let saved = serde_json::json!({
+            "options": self.options,
+            "done": self.done,
+            "run_dir": self.run_dir,
+            "report": report,
+            "stats": stats,
+        });
+        let data = serde_json::to_string_pretty(&saved).expect("failed to serialize run state");
+        // Written aside and renamed over, so a kill mid-write can't lose the run
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, data).expect("failed to write run state file");
+        std::fs::rename(&tmp, &self.path).expect("failed to write run state file");
+    }
+
+    /// The run finished; nothing is left to resume.
+    pub fn remove(&self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -1,7 +1,10 @@
+use crate::budget::Budget;
 use crate::theories::{Card, CardKind};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Replicates the exact system prompt from generation/src/ollama.rs
@@ -46,6 +49,23 @@ Rules:
 - Name: 1-3 words.
 - Description: MUST start with an adjective or noun. NEVER start with A, An, The, This, It, or Its. One short funny sentence about what it is, not how it was made.";
 
+/// What `score_quality` rates each result on.
+pub const QUALITY_KEYS: &[&str] = &["fun", "recognizability", "fit"];
+
+/// Bump when `build_user_prompt` or the parsing of the model's answer
+/// changes in a way that could change results. Edits to the system prompt
+/// are picked up automatically.
+const PROMPT_REVISION: u32 = 1;
+
+/// First 12 hex chars of SHA-256 over a system prompt, so cached results can
+/// be kept per prompt.
+pub fn prompt_fingerprint(system_prompt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(system_prompt.as_bytes());
+    hasher.update(PROMPT_REVISION.to_le_bytes());
+    format!("{:x}", hasher.finalize())[..12].to_string()
+}
+
 #[derive(Serialize)]
 struct GenerateRequest {
     model: String,
@@ -73,10 +93,39 @@ pub struct CombineResult {
     pub description: String,
 }
 
+/// Sampling settings other than the game's, for measuring how much answers
+/// vary.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Sampling {
+    pub temperature: f32,
+    pub seed: u32,
+}
+
+#[derive(Serialize)]
+struct ServerCombineRequest<'a> {
+    cards: &'a [Card],
+}
+
+#[derive(Deserialize)]
+struct ServerError {
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct ServerVersion {
+    combine_version: String,
+}
+
+#[derive(Clone)]
 pub struct OllamaClient {
     client: Client,
     base_url: String,
     model: String,
+    /// A generation server to send combines to instead of Ollama.
+    server_url: Option<String>,
+    system_prompt: String,
+    /// Shared by clones, so one budget covers every call of a run.
+    budget: Arc<Budget>,
 }
 
 impl OllamaClient {
@@ -89,17 +138,160 @@ impl OllamaClient {
             client,
             base_url: base_url.to_string(),
             model: model.to_string(),
+            server_url: None,
+            system_prompt: SYSTEM_PROMPT.to_string(),
+            budget: Arc::new(Budget::unlimited()),
         }
     }
 
+    /// Combine under another system prompt than the game's.
+    pub fn with_system_prompt(mut self, system_prompt: &str) -> Self {
+        self.system_prompt = system_prompt.to_string();
+        self
+    }
+
+    pub fn system_prompt(&self) -> &str {
+        &self.system_prompt
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Score with another Ollama model; combines are unaffected when they
+    /// go through a generation server.
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    /// Stop making calls once `budget` is spent.
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = Arc::new(budget);
+        self
+    }
+
+    pub fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
+    /// Send combines through a generation server's `/combine`, so they get
+    /// its prompt and reality check rather than this file's copy of the
+    /// prompt. Scoring still goes to Ollama.
+    pub fn with_server(mut self, server_url: &str) -> Self {
+        self.server_url = Some(server_url.trim_end_matches('/').to_string());
+        self
+    }
+
     pub async fn combine(&self, cards: &[Card]) -> Result<CombineResult, String> {
+        match &self.server_url {
+            Some(server_url) => self.combine_on_server(server_url, cards).await,
+            None => self.combine_with(cards, 0.0, 42).await,
+        }
+    }
+
+    async fn combine_on_server(
+        &self,
+        server_url: &str,
+        cards: &[Card],
+    ) -> Result<CombineResult, String> {
+        self.budget.take()?;
+        let resp = self
+            .client
+            .post(format!("{server_url}/combine"))
+            .json(&ServerCombineRequest { cards })
+            .send()
+            .await
+            .map_err(|e| format!("Generation server request failed: {e}"))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            let reason = serde_json::from_str::<ServerError>(&body).map_or(body, |e| e.reason);
+            return Err(format!("Generation server returned {status}: {reason}"));
+        }
+
+        let result: CombineResult = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse generation server response: {e}"))?;
+
+        // The server passes the model's own wording through, e.g. "Not Possible"
+        if result.name.to_lowercase().contains("not possible") {
+            return Ok(CombineResult {
+                name: "Not possible".to_string(),
+                description: result.description,
+            });
+        }
+        Ok(result)
+    }
+
+    /// The generation server's combine fingerprint (model and prompt).
+    pub async fn server_version(&self) -> Result<String, String> {
+        let Some(server_url) = &self.server_url else {
+            return Err("No generation server configured".to_string());
+        };
+        let resp = self
+            .client
+            .get(format!("{server_url}/version"))
+            .send()
+            .await
+            .map_err(|e| format!("Generation server request failed: {e}"))?;
+        if !resp.status().is_success() {
+            return Err(format!("Generation server returned {}", resp.status()));
+        }
+        let version: ServerVersion = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse generation server version: {e}"))?;
+        Ok(version.combine_version)
+    }
+
+    /// Card art for a result from the generation server's
+    /// `/generate-image`, as PNG bytes.
+    pub async fn generate_image(&self, card: &Card) -> Result<Vec<u8>, String> {
+        let Some(server_url) = &self.server_url else {
+            return Err("No generation server configured".to_string());
+        };
+        self.budget.take()?;
+        let resp = self
+            .client
+            .post(format!("{server_url}/generate-image"))
+            .json(card)
+            .send()
+            .await
+            .map_err(|e| format!("Generation server request failed: {e}"))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            let reason = serde_json::from_str::<ServerError>(&body).map_or(body, |e| e.reason);
+            return Err(format!("Generation server returned {status}: {reason}"));
+        }
+
+        let bytes = resp
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to read image: {e}"))?;
+        Ok(bytes.to_vec())
+    }
+
+    /// `combine` with other sampling settings; the game always uses 0.0 and 42.
+    /// Always asks Ollama directly, since the generation server can't sample.
+    pub async fn combine_with(
+        &self,
+        cards: &[Card],
+        temperature: f32,
+        seed: u32,
+    ) -> Result<CombineResult, String> {
+        self.budget.take()?;
         let prompt = build_user_prompt(cards);
         let url = format!("{}/api/generate", self.base_url);
 
         let request = GenerateRequest {
             model: self.model.clone(),
             prompt,
-            system: SYSTEM_PROMPT.to_string(),
+            system: self.system_prompt.clone(),
             stream: false,
             format: serde_json::json!({
                 "type": "object",
@@ -109,10 +301,7 @@ impl OllamaClient {
                 },
                 "required": ["name", "description"]
             }),
-            options: GenerateOptions {
-                temperature: 0.0,
-                seed: 42,
-            },
+            options: GenerateOptions { temperature, seed },
         };
 
         let resp = self
@@ -145,7 +334,7 @@ impl OllamaClient {
         &self,
         card_name: &str,
         card_description: &str,
-        categories: &[&str],
+        categories: &[String],
     ) -> Result<HashMap<String, u32>, String> {
         let cats_list = categories
             .iter()
@@ -158,19 +347,79 @@ impl OllamaClient {
              1-3 = poor fit, 4-6 = moderate, 7-10 = strong fit. Be strict.\n\
              Return JSON with exactly these keys:\n{{\n{cats_list}\n}}"
         );
+        let prompt = format!("Item: {card_name}\nDescription: {card_description}");
+        self.score(system, prompt, categories).await
+    }
+
+    /// Score how close a card is to each target item, for guiding a search
+    /// toward them. Returns a map of target -> score (1-10).
+    pub async fn score_targets(
+        &self,
+        card_name: &str,
+        card_description: &str,
+        targets: &[String],
+    ) -> Result<HashMap<String, u32>, String> {
+        let targets_list = targets
+            .iter()
+            .map(|t| format!("  \"{t}\": <1-10>"))
+            .collect::<Vec<_>>()
+            .join(",\n");
 
+        let system = format!(
+            "Rate how close this item is to becoming each target when combined with \
+             simple materials. Score 1-10.\n\
+             1-3 = unrelated, 4-6 = a plausible ingredient, 7-9 = one or two steps away, \
+             10 = it is the target. Be strict.\n\
+             Return JSON with exactly these keys:\n{{\n{targets_list}\n}}"
+        );
         let prompt = format!("Item: {card_name}\nDescription: {card_description}");
+        self.score(system, prompt, targets).await
+    }
+
+    /// Judge a result on `QUALITY_KEYS`: how fun it is to find, how
+    /// recognizable, and how well it follows from `inputs` (e.g. "Fire +
+    /// Clay [Hollow]"). Returns a map of key -> score (1-10).
+    pub async fn score_quality(
+        &self,
+        inputs: &str,
+        card_name: &str,
+        card_description: &str,
+    ) -> Result<HashMap<String, u32>, String> {
+        let system = "Rate this item, crafted in an alchemy card game by combining the inputs \
+             given. Score 1-10.\n\
+             fun: how delightful or surprising it is to discover. \
+             1-3 = dull, 7-10 = a great find.\n\
+             recognizability: how surely a player knows what it is from the name alone.\n\
+             fit: how sensibly it follows from the inputs.\n\
+             Be strict.\n\
+             Return JSON with exactly these keys:\n\
+             {\n  \"fun\": <1-10>,\n  \"recognizability\": <1-10>,\n  \"fit\": <1-10>\n}"
+            .to_string();
+        let prompt =
+            format!("Inputs: {inputs}\nItem: {card_name}\nDescription: {card_description}");
+        let keys: Vec<String> = QUALITY_KEYS.iter().map(|k| k.to_string()).collect();
+        self.score(system, prompt, &keys).await
+    }
+
+    /// Ask for an integer score per key of `prompt` under `system`'s
+    /// instructions.
+    async fn score(
+        &self,
+        system: String,
+        prompt: String,
+        keys: &[String],
+    ) -> Result<HashMap<String, u32>, String> {
+        self.budget.take()?;
         let url = format!("{}/api/generate", self.base_url);
 
         // Build properties for JSON schema
         let mut props = serde_json::Map::new();
-        for cat in categories {
+        for cat in keys {
             props.insert(
                 cat.to_string(),
                 serde_json::json!({ "type": "integer" }),
             );
         }
-        let required: Vec<&str> = categories.to_vec();
 
         let request = GenerateRequest {
             model: self.model.clone(),
@@ -180,7 +429,7 @@ impl OllamaClient {
             format: serde_json::json!({
                 "type": "object",
                 "properties": props,
-                "required": required
+                "required": keys
             }),
             options: GenerateOptions {
                 temperature: 0.0,
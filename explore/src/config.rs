@@ -0,0 +1,86 @@
+use crate::theories::{
+    all_modifier_families, all_theories, sensory_variations, Card, ElementTheory, ModifierFamily,
+    TargetGroup, BOARD_CATEGORIES, TARGET_ITEMS,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// What a run explores: element sets, modifier families, and what results
+/// are scored and checked against. Built in (see `theories.rs`), or read
+/// from a `--config` file so an experiment doesn't need a rebuild; sections
+/// the file leaves out keep the built-in ones. See
+/// `explore/theories.example.toml`.
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Step 2 compares these; step 1 pairs up the first one's elements.
+    pub theories: Vec<ElementTheory>,
+    pub modifier_families: Vec<ModifierFamily>,
+    /// Compared by `--sensory`, on theory G's elements.
+    pub sensory_variations: Vec<ModifierFamily>,
+    pub board_categories: Vec<String>,
+    pub target_items: Vec<TargetGroup>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theories: all_theories(),
+            modifier_families: all_modifier_families(),
+            sensory_variations: sensory_variations(),
+            board_categories: BOARD_CATEGORIES.iter().map(|c| c.to_string()).collect(),
+            target_items: TARGET_ITEMS
+                .iter()
+                .map(|(category, items)| TargetGroup {
+                    category: category.to_string(),
+                    items: items.iter().map(|i| i.to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Config {
+    /// Read a TOML file, or JSON if the name ends in `.json`.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let config: Self = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&data).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&data).map_err(|e| e.to_string())
+        }
+        .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+        config.validate().map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.theories.is_empty() {
+            return Err("no theories".into());
+        }
+        if let Some(t) = self.theories.iter().find(|t| t.elements.len() < 2) {
+            return Err(format!("theory {} needs at least 2 elements", t.name));
+        }
+        if self.modifier_families.is_empty() {
+            return Err("no modifier_families".into());
+        }
+        let mut families = self.modifier_families.iter().chain(&self.sensory_variations);
+        if let Some(f) = families.find(|f| f.modifiers.is_empty()) {
+            return Err(format!("modifier family {} has no modifiers", f.name));
+        }
+        if self.board_categories.is_empty() {
+            return Err("no board_categories".into());
+        }
+        Ok(())
+    }
+
+    /// The elements step 1 pairs up.
+    pub fn baseline_elements(&self) -> &[Card] {
+        &self.theories[0].elements
+    }
+
+    pub fn theory(&self, name: &str) -> Option<&ElementTheory> {
+        self.theories.iter().find(|t| t.name == name)
+    }
+}
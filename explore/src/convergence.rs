@@ -0,0 +1,149 @@
+use crate::combine::CombineResult;
+use crate::report::Report;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+/// Shared names shown per group when printing, and inputs shown per name;
+/// the report lists them all.
+const PRINTED_SINKS: usize = 3;
+const PRINTED_INPUTS: usize = 4;
+
+/// How far the distinct input sets in one group of results (a theory, a
+/// modifier family) collapse onto the same output names.
+pub struct Convergence {
+    pub group: String,
+    /// Distinct input sets with a valid result.
+    pub inputs: usize,
+    /// Distinct valid result names.
+    pub outputs: usize,
+    /// Names more than one input set made, with those inputs, most first.
+    pub sinks: Vec<(String, Vec<String>)>,
+}
+
+impl Convergence {
+    /// Group `results` by lowercased name. Repeats of one input set, e.g.
+    /// extra `--samples`, count once.
+    fn of(group: String, results: Vec<(String, &CombineResult)>) -> Self {
+        let mut by_name: HashMap<String, (String, Vec<String>)> = HashMap::new();
+        let mut inputs = HashSet::new();
+        for (input, result) in results {
+            if result.name == "Not possible" {
+                continue;
+            }
+            let (_, sources) = by_name
+                .entry(result.name.to_lowercase())
+                .or_insert_with(|| (result.name.clone(), Vec::new()));
+            if !sources.contains(&input) {
+                sources.push(input.clone());
+            }
+            inputs.insert(input);
+        }
+
+        let outputs = by_name.len();
+        let mut sinks: Vec<(String, Vec<String>)> = by_name
+            .into_values()
+            .filter(|(_, sources)| sources.len() > 1)
+            .map(|(name, mut sources)| {
+                sources.sort();
+                (name, sources)
+            })
+            .collect();
+        sinks.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+        Self {
+            group,
+            inputs: inputs.len(),
+            outputs,
+            sinks,
+        }
+    }
+
+    /// Share of input sets whose result some other input set made too.
+    pub fn collision_rate(&self) -> f64 {
+        let collided: usize = self.sinks.iter().map(|(_, sources)| sources.len()).sum();
+        if self.inputs > 0 {
+            collided as f64 / self.inputs as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Convergence within each of the report's result groups.
+pub fn analyze(report: &Report) -> Vec<Convergence> {
+    report
+        .result_groups()
+        .into_iter()
+        .map(|(group, results)| Convergence::of(group, results))
+        .collect()
+}
+
+/// Print each group's collision rate and its biggest sinks.
+pub fn print(groups: &[Convergence]) {
+    if groups.is_empty() {
+        return;
+    }
+
+    println!("\n{}", "=".repeat(60));
+    println!("CONVERGENCE");
+    println!("{}\n", "=".repeat(60));
+
+    let w = groups.iter().map(|g| g.group.len()).max().unwrap_or(0).max(5);
+    println!("  {:<w$}  {:>6}  {:>7}  {:>8}", "Group", "Inputs", "Outputs", "Collided");
+    for g in groups {
+        println!(
+            "  {:<w$}  {:>6}  {:>7}  {:>7.0}%",
+            g.group,
+            g.inputs,
+            g.outputs,
+            g.collision_rate() * 100.0
+        );
+    }
+
+    for g in groups.iter().filter(|g| !g.sinks.is_empty()) {
+        println!("\n  {}:", g.group);
+        for (name, sources) in g.sinks.iter().take(PRINTED_SINKS) {
+            let more = if sources.len() > PRINTED_INPUTS { ", ..." } else { "" };
+            let shown = sources[..sources.len().min(PRINTED_INPUTS)].join(", ");
+            println!("    {name} <- {} inputs: {shown}{more}", sources.len());
+        }
+        if g.sinks.len() > PRINTED_SINKS {
+            println!("    ... and {} more shared names", g.sinks.len() - PRINTED_SINKS);
+        }
+    }
+}
+
+/// Write the convergence table and every group's shared names as a report
+/// section.
+pub fn write(f: &mut impl Write, groups: &[Convergence]) {
+    if groups.is_empty() {
+        return;
+    }
+
+    writeln!(f, "\n## Convergence\n").unwrap();
+    writeln!(
+        f,
+        "Collided is the share of input sets whose result another input set in the group \
+         also made.\n"
+    )
+    .unwrap();
+    writeln!(f, "| Group | Inputs | Outputs | Collided |").unwrap();
+    writeln!(f, "|-------|--------|---------|----------|").unwrap();
+    for g in groups {
+        writeln!(
+            f,
+            "| {} | {} | {} | {:.0}% |",
+            g.group,
+            g.inputs,
+            g.outputs,
+            g.collision_rate() * 100.0
+        )
+        .unwrap();
+    }
+
+    for g in groups.iter().filter(|g| !g.sinks.is_empty()) {
+        writeln!(f, "\n### {}\n", g.group).unwrap();
+        for (name, sources) in &g.sinks {
+            writeln!(f, "- **{name}** ({}): {}", sources.len(), sources.join(", ")).unwrap();
+        }
+    }
+}
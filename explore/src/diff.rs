@@ -0,0 +1,241 @@
+use crate::cache::{Cache, CachedEntry};
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+/// Changed combinations shown when printing; the report lists them all.
+const PRINTED_CHANGES: usize = 20;
+
+/// One side of a diff: a model and prompt fingerprint within the cache.
+pub struct Side {
+    pub model: String,
+    pub prompt: String,
+}
+
+impl Side {
+    /// `MODEL[@PROMPT]`, with `default_prompt` when no fingerprint is given.
+    pub fn parse(spec: &str, default_prompt: &str) -> Self {
+        let (model, prompt) = spec.rsplit_once('@').unwrap_or((spec, default_prompt));
+        Self {
+            model: model.to_string(),
+            prompt: prompt.to_string(),
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("{}@{}", self.model, self.prompt)
+    }
+
+    /// Every cached result as recipe -> entry, e.g. "earth + fire [forge]".
+    fn results(&self, path: &Path) -> Result<BTreeMap<String, CachedEntry>, String> {
+        let cache = Cache::open(path, &self.model, &self.prompt)?;
+        let results: BTreeMap<String, CachedEntry> = cache
+            .recipes()
+            .into_iter()
+            .map(|(materials, modifier, entry)| {
+                let modifier = modifier.map(|m| format!(" [{m}]")).unwrap_or_default();
+                (format!("{}{modifier}", materials.join(" + ")), entry)
+            })
+            .collect();
+        if results.is_empty() {
+            let cached: Vec<String> = cache
+                .slices()
+                .into_iter()
+                .map(|(model, prompt, count)| format!("{model}@{prompt} ({count})"))
+                .collect();
+            return Err(format!(
+                "Nothing cached for {}; cached: {}",
+                self.label(),
+                if cached.is_empty() { "nothing".to_string() } else { cached.join(", ") }
+            ));
+        }
+        Ok(results)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ChangeKind {
+    BecameValid,
+    BecameInvalid,
+    Renamed,
+}
+
+impl ChangeKind {
+    const ALL: [Self; 3] = [Self::BecameValid, Self::BecameInvalid, Self::Renamed];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::BecameValid => "Became valid",
+            Self::BecameInvalid => "Became invalid",
+            Self::Renamed => "Renamed",
+        }
+    }
+}
+
+struct Change {
+    recipe: String,
+    kind: ChangeKind,
+    old: String,
+    new: String,
+}
+
+/// How the results two models or prompts gave for the same combinations
+/// differ.
+pub struct Diff {
+    old: String,
+    new: String,
+    /// Combinations cached on both sides.
+    compared: usize,
+    changes: Vec<Change>,
+    old_valid: usize,
+    new_valid: usize,
+    old_names: usize,
+    new_names: usize,
+    only_old: usize,
+    only_new: usize,
+}
+
+/// Compare what `old` and `new` cached in the cache at `path`, over the
+/// combinations both have.
+pub fn run(path: &Path, old: &Side, new: &Side) -> Result<Diff, String> {
+    let old_results = old.results(path)?;
+    let new_results = new.results(path)?;
+
+    let mut diff = Diff {
+        old: old.label(),
+        new: new.label(),
+        compared: 0,
+        changes: Vec::new(),
+        old_valid: 0,
+        new_valid: 0,
+        old_names: 0,
+        new_names: 0,
+        only_old: old_results.keys().filter(|k| !new_results.contains_key(*k)).count(),
+        only_new: new_results.keys().filter(|k| !old_results.contains_key(*k)).count(),
+    };
+    let (mut old_names, mut new_names) = (HashSet::new(), HashSet::new());
+    for (recipe, before) in &old_results {
+        let Some(after) = new_results.get(recipe) else {
+            continue;
+        };
+        diff.compared += 1;
+        let (was_valid, is_valid) = (before.name != "Not possible", after.name != "Not possible");
+        if was_valid {
+            diff.old_valid += 1;
+            old_names.insert(before.name.to_lowercase());
+        }
+        if is_valid {
+            diff.new_valid += 1;
+            new_names.insert(after.name.to_lowercase());
+        }
+
+        let kind = match (was_valid, is_valid) {
+            (false, true) => ChangeKind::BecameValid,
+            (true, false) => ChangeKind::BecameInvalid,
+            _ if !before.name.eq_ignore_ascii_case(&after.name) => ChangeKind::Renamed,
+            _ => continue,
+        };
+        diff.changes.push(Change {
+            recipe: recipe.clone(),
+            kind,
+            old: before.name.clone(),
+            new: after.name.clone(),
+        });
+    }
+    diff.old_names = old_names.len();
+    diff.new_names = new_names.len();
+    Ok(diff)
+}
+
+impl Diff {
+    fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|c| c.kind == kind).count()
+    }
+
+    fn pct(&self, n: usize) -> f64 {
+        if self.compared > 0 {
+            n as f64 / self.compared as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Print the summary and the first few changed combinations.
+    pub fn print(&self) {
+        println!("\n{}", "=".repeat(60));
+        println!("DIFF: {} -> {}", self.old, self.new);
+        println!("{}\n", "=".repeat(60));
+
+        println!("  {} combinations cached on both sides", self.compared);
+        println!(
+            "  {} only in {}, {} only in {}\n",
+            self.only_old, self.old, self.only_new, self.new
+        );
+        println!(
+            "  Changed:        {:>5} ({:.0}%)",
+            self.changes.len(),
+            self.pct(self.changes.len())
+        );
+        for kind in ChangeKind::ALL {
+            println!("    {:<14}{:>5}", kind.label().to_lowercase(), self.count(kind));
+        }
+        println!(
+            "  Valid:          {:.0}% -> {:.0}%",
+            self.pct(self.old_valid),
+            self.pct(self.new_valid)
+        );
+        println!("  Distinct names: {} -> {}", self.old_names, self.new_names);
+
+        if !self.changes.is_empty() {
+            println!();
+        }
+        for change in self.changes.iter().take(PRINTED_CHANGES) {
+            println!("  {}: {} -> {}", change.recipe, change.old, change.new);
+        }
+        if self.changes.len() > PRINTED_CHANGES {
+            println!("  ... and {} more", self.changes.len() - PRINTED_CHANGES);
+        }
+    }
+
+    /// Write the summary and every changed combination as markdown.
+    pub fn write_to_file(&self, path: &str) {
+        let mut f = std::fs::File::create(path).expect("failed to create diff report file");
+
+        writeln!(f, "# Diff Report\n").unwrap();
+        writeln!(f, "`{}` -> `{}`\n", self.old, self.new).unwrap();
+        writeln!(f, "| | Count |").unwrap();
+        writeln!(f, "|--|-------|").unwrap();
+        writeln!(f, "| Cached on both sides | {} |", self.compared).unwrap();
+        writeln!(f, "| Only in {} | {} |", self.old, self.only_old).unwrap();
+        writeln!(f, "| Only in {} | {} |", self.new, self.only_new).unwrap();
+        writeln!(
+            f,
+            "| Changed | {} ({:.0}%) |",
+            self.changes.len(),
+            self.pct(self.changes.len())
+        )
+        .unwrap();
+        for kind in ChangeKind::ALL {
+            writeln!(f, "| {} | {} |", kind.label(), self.count(kind)).unwrap();
+        }
+        writeln!(
+            f,
+            "| Valid | {:.0}% -> {:.0}% |",
+            self.pct(self.old_valid),
+            self.pct(self.new_valid)
+        )
+        .unwrap();
+        writeln!(f, "| Distinct names | {} -> {} |", self.old_names, self.new_names).unwrap();
+
+        if !self.changes.is_empty() {
+            writeln!(f, "\n## Changed\n").unwrap();
+            writeln!(f, "| Combination | Before | After |").unwrap();
+            writeln!(f, "|-------------|--------|-------|").unwrap();
+            for change in &self.changes {
+                writeln!(f, "| {} | {} | {} |", change.recipe, change.old, change.new).unwrap();
+            }
+        }
+
+        println!("\nDiff report written to {path}");
+    }
+}
@@ -0,0 +1,200 @@
+use crate::cache::Cache;
+use crate::config::Config;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+struct Node {
+    name: String,
+    description: String,
+    /// A starting element of some theory, rather than a combine's result.
+    element: bool,
+}
+
+/// One input of a recipe, pointing at what the recipe makes.
+struct Edge {
+    from: String,
+    to: String,
+    modifier: Option<String>,
+    /// The whole recipe, e.g. "Metal + Fire [Forge]".
+    recipe: String,
+}
+
+/// Every valid combine in the cache as a crafting graph: cards are nodes,
+/// and each recipe is an edge from each of its inputs to its result. Nodes
+/// are keyed by lowercased name, as the cache is.
+pub struct Graph {
+    nodes: BTreeMap<String, Node>,
+    edges: Vec<Edge>,
+}
+
+impl Graph {
+    pub fn from_cache(cache: &Cache, config: &Config) -> Self {
+        let mut nodes = BTreeMap::new();
+        for theory in &config.theories {
+            for element in &theory.elements {
+                nodes.entry(element.name.to_lowercase()).or_insert_with(|| Node {
+                    name: element.name.clone(),
+                    description: element.description.clone(),
+                    element: true,
+                });
+            }
+        }
+        // Cache keys only keep lowercased modifier names
+        let modifiers: BTreeMap<String, &str> = config
+            .modifier_families
+            .iter()
+            .chain(&config.sensory_variations)
+            .flat_map(|family| &family.modifiers)
+            .map(|m| (m.name.to_lowercase(), m.name.as_str()))
+            .collect();
+
+        let mut recipes: Vec<_> = cache
+            .recipes()
+            .into_iter()
+            .filter(|(materials, _, result)| !materials.is_empty() && result.name != "Not possible")
+            .collect();
+        // Sort so the same cache always exports the same file
+        recipes.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        for (_, _, result) in &recipes {
+            nodes.entry(result.name.to_lowercase()).or_insert_with(|| Node {
+                name: result.name.clone(),
+                description: result.description.clone(),
+                element: false,
+            });
+        }
+
+        let mut edges = Vec::new();
+        for (materials, modifier, result) in recipes {
+            let name = |key: &str| nodes.get(key).map_or(key.to_string(), |n| n.name.clone());
+            let modifier = modifier.map(|m| modifiers.get(&m).map_or(m, |name| name.to_string()));
+            let mut recipe = materials.iter().map(|m| name(m)).collect::<Vec<_>>().join(" + ");
+            if let Some(modifier) = &modifier {
+                recipe = format!("{recipe} [{modifier}]");
+            }
+            let to = result.name.to_lowercase();
+            // Water + Water still only feeds the result once
+            let inputs: BTreeSet<String> = materials.into_iter().collect();
+            for from in inputs {
+                edges.push(Edge {
+                    from,
+                    to: to.clone(),
+                    modifier: modifier.clone(),
+                    recipe: recipe.clone(),
+                });
+            }
+        }
+        // Inputs only ever seen in cache keys, e.g. results of older prompts
+        for edge in &edges {
+            nodes.entry(edge.from.clone()).or_insert_with(|| Node {
+                name: edge.from.clone(),
+                description: String::new(),
+                element: false,
+            });
+        }
+
+        Self { nodes, edges }
+    }
+
+    /// Write Graphviz DOT, or GraphML (for Gephi and friends) if `path` ends
+    /// in `.graphml`.
+    pub fn write_to_file(&self, path: &Path) -> Result<(), String> {
+        let mut out = Vec::new();
+        if path.extension().is_some_and(|ext| ext == "graphml") {
+            self.write_graphml(&mut out)
+        } else {
+            self.write_dot(&mut out)
+        }
+        .map_err(|e| e.to_string())?;
+        std::fs::write(path, out).map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        println!(
+            "Graph with {} cards and {} edges written to {}",
+            self.nodes.len(),
+            self.edges.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    fn write_dot(&self, f: &mut impl Write) -> std::io::Result<()> {
+        writeln!(f, "digraph crafting {{")?;
+        writeln!(f, "  rankdir=LR;")?;
+        for (id, node) in &self.nodes {
+            let shape = if node.element { "box" } else { "ellipse" };
+            writeln!(
+                f,
+                "  \"{}\" [label=\"{}\", tooltip=\"{}\", shape={shape}];",
+                dot_escape(id),
+                dot_escape(&node.name),
+                dot_escape(&node.description)
+            )?;
+        }
+        for edge in &self.edges {
+            let label = edge.modifier.as_deref().unwrap_or("");
+            writeln!(
+                f,
+                "  \"{}\" -> \"{}\" [label=\"{}\", tooltip=\"{}\"];",
+                dot_escape(&edge.from),
+                dot_escape(&edge.to),
+                dot_escape(label),
+                dot_escape(&edge.recipe)
+            )?;
+        }
+        writeln!(f, "}}")
+    }
+
+    fn write_graphml(&self, f: &mut impl Write) -> std::io::Result<()> {
+        writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(f, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+        for (id, on, kind) in [
+            ("name", "node", "string"),
+            ("description", "node", "string"),
+            ("element", "node", "boolean"),
+            ("modifier", "edge", "string"),
+            ("recipe", "edge", "string"),
+        ] {
+            writeln!(
+                f,
+                r#"  <key id="{id}" for="{on}" attr.name="{id}" attr.type="{kind}"/>"#
+            )?;
+        }
+        writeln!(f, r#"  <graph id="crafting" edgedefault="directed">"#)?;
+        for (id, node) in &self.nodes {
+            writeln!(f, r#"    <node id="{}">"#, xml_escape(id))?;
+            writeln!(f, r#"      <data key="name">{}</data>"#, xml_escape(&node.name))?;
+            writeln!(
+                f,
+                r#"      <data key="description">{}</data>"#,
+                xml_escape(&node.description)
+            )?;
+            writeln!(f, r#"      <data key="element">{}</data>"#, node.element)?;
+            writeln!(f, "    </node>")?;
+        }
+        for edge in &self.edges {
+            writeln!(
+                f,
+                r#"    <edge source="{}" target="{}">"#,
+                xml_escape(&edge.from),
+                xml_escape(&edge.to)
+            )?;
+            if let Some(modifier) = &edge.modifier {
+                writeln!(f, r#"      <data key="modifier">{}</data>"#, xml_escape(modifier))?;
+            }
+            writeln!(f, r#"      <data key="recipe">{}</data>"#, xml_escape(&edge.recipe))?;
+            writeln!(f, "    </edge>")?;
+        }
+        writeln!(f, "  </graph>")?;
+        writeln!(f, "</graphml>")
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
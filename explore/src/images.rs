@@ -0,0 +1,165 @@
+use crate::combine::OllamaClient;
+use crate::report::Report;
+use crate::theories::Card;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// A result picked for card art, by its best category score.
+pub struct Preview {
+    pub card: Card,
+    pub category: String,
+    pub score: u32,
+    /// File name within the output folder; none if generation failed.
+    pub image: Option<String>,
+}
+
+/// The `count` scored results with the best category scores, best first.
+pub fn top_results(report: &Report, count: usize) -> Vec<Preview> {
+    let descriptions: HashMap<String, String> =
+        report.all_result_names_with_desc().into_iter().collect();
+    let mut previews: Vec<Preview> = report
+        .category_scores
+        .iter()
+        .filter_map(|(name, scores)| {
+            // Ties go to the category named first, so reruns pick the same
+            let (category, &score) =
+                scores.iter().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))?;
+            let description = descriptions.get(name).cloned().unwrap_or_default();
+            Some(Preview {
+                card: Card::material(name, &description),
+                category: category.clone(),
+                score,
+                image: None,
+            })
+        })
+        .collect();
+    previews.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.card.name.cmp(&b.card.name)));
+    previews.truncate(count);
+    previews
+}
+
+/// Generate art for each preview through the generation server, with at most
+/// `limit` calls in flight, into `dir`. Images already there from an earlier
+/// run are kept rather than generated again.
+pub async fn generate(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    previews: &mut [Preview],
+    dir: &Path,
+) {
+    let calls: Vec<_> = previews
+        .iter()
+        .map(|preview| {
+            let file = format!("{}.png", slug(&preview.card.name));
+            let path = dir.join(&file);
+            let (client, limit, card) = (client.clone(), limit.clone(), preview.card.clone());
+            let call = (!path.exists()).then(|| {
+                tokio::spawn(async move {
+                    let _permit = limit.acquire_owned().await.map_err(|e| e.to_string())?;
+                    client.generate_image(&card).await
+                })
+            });
+            (file, path, call)
+        })
+        .collect();
+
+    for (preview, (file, path, call)) in previews.iter_mut().zip(calls) {
+        let Some(call) = call else {
+            println!("  {} (already generated)", preview.card.name);
+            preview.image = Some(file);
+            continue;
+        };
+        let written = call
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r)
+            .and_then(|bytes| std::fs::write(&path, bytes).map_err(|e| e.to_string()));
+        match written {
+            Ok(()) => {
+                println!("  {} -> {}", preview.card.name, path.display());
+                preview.image = Some(file);
+            }
+            Err(e) if e == crate::budget::SPENT => {}
+            Err(e) => eprintln!("  {} ERROR: {e}", preview.card.name),
+        }
+    }
+}
+
+/// Write `index.html` in `dir`: each preview as a card with its art, name,
+/// description and best category, to open in a browser or share.
+pub fn write_gallery(previews: &[Preview], dir: &Path) -> PathBuf {
+    let path = dir.join("index.html");
+    let mut f = std::fs::File::create(&path).expect("failed to create gallery file");
+
+    writeln!(f, "<!DOCTYPE html>").unwrap();
+    writeln!(f, "<html><head><meta charset=\"utf-8\"><title>Explore previews</title>").unwrap();
+    writeln!(f, "<style>").unwrap();
+    writeln!(f, "body {{ font-family: sans-serif; background: #222; color: #eee; }}").unwrap();
+    writeln!(f, ".cards {{ display: flex; flex-wrap: wrap; gap: 16px; }}").unwrap();
+    writeln!(
+        f,
+        ".card {{ width: 220px; background: #f4ecd8; color: #222; border: 4px solid #8b6b3d; \
+         border-radius: 12px; padding: 10px; }}"
+    )
+    .unwrap();
+    writeln!(f, ".card img {{ width: 100%; border-radius: 6px; background: #ccc; }}").unwrap();
+    writeln!(f, ".card h2 {{ font-size: 18px; margin: 8px 0 4px; }}").unwrap();
+    writeln!(f, ".card p {{ font-size: 13px; margin: 4px 0; }}").unwrap();
+    writeln!(f, ".score {{ color: #8b6b3d; font-weight: bold; }}").unwrap();
+    writeln!(f, "</style></head><body>").unwrap();
+    writeln!(f, "<h1>Top {} results</h1>", previews.len()).unwrap();
+    writeln!(f, "<div class=\"cards\">").unwrap();
+    for preview in previews {
+        writeln!(f, "<div class=\"card\">").unwrap();
+        match &preview.image {
+            Some(file) => writeln!(
+                f,
+                "<img src=\"{}\" alt=\"{}\">",
+                html_escape(file),
+                html_escape(&preview.card.name)
+            )
+            .unwrap(),
+            None => writeln!(f, "<p><em>No image</em></p>").unwrap(),
+        }
+        writeln!(f, "<h2>{}</h2>", html_escape(&preview.card.name)).unwrap();
+        writeln!(f, "<p>{}</p>", html_escape(&preview.card.description)).unwrap();
+        writeln!(
+            f,
+            "<p class=\"score\">{} {}/10</p>",
+            html_escape(&preview.category),
+            preview.score
+        )
+        .unwrap();
+        writeln!(f, "</div>").unwrap();
+    }
+    writeln!(f, "</div></body></html>").unwrap();
+    path
+}
+
+/// A file name for a card, e.g. "Iron Sword" -> "iron-sword".
+fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "card".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,21 +1,54 @@
+mod arity;
+mod beam;
+mod bfs;
+mod budget;
 mod cache;
+mod cards;
+mod category;
+mod checkpoint;
 mod combine;
+mod config;
+mod convergence;
+mod diff;
+mod graph;
+mod images;
+mod plan;
+mod progress;
+mod prompts;
+mod quality;
+mod repl;
 mod report;
+mod runs;
+mod significance;
+mod sweep;
 mod theories;
 
+use beam::BeamSettings;
+use budget::Budget;
 use cache::Cache;
-use clap::Parser;
-use combine::OllamaClient;
+use category::CategorySettings;
+use checkpoint::{Checkpoint, RunOptions};
+use clap::{Parser, Subcommand};
+use combine::{OllamaClient, Sampling};
+use config::Config;
+use progress::Progress;
 use report::Report;
-use std::path::PathBuf;
-use theories::{
-    all_modifier_families, all_theories, baseline_elements, sample_pairs, sensory_variations,
-    theory_g_elements, Card, BOARD_CATEGORIES,
-};
+use runs::{RawResult, RunDir, RunInfo};
+use sweep::SweepSettings;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use theories::{sample_pairs, sample_sets, Card, PairSampling};
 
 #[derive(Parser)]
 #[command(name = "explore", about = "Explore element and modifier combinations")]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Run only a specific step (1 = modifiers, 2 = elements)
     #[arg(long)]
     step: Option<u32>,
@@ -32,19 +65,194 @@ struct Cli {
     #[arg(long)]
     no_score: bool,
 
+    /// Have the judge model rate every valid result for fun,
+    /// recognizability and fit to its inputs, averaged per family and theory
+    #[arg(long)]
+    quality: bool,
+
+    /// Ollama model for --quality (default: --model)
+    #[arg(long)]
+    judge_model: Option<String>,
+
     /// Ollama base URL
     #[arg(long, default_value = "http://localhost:11434")]
     ollama_url: String,
 
+    /// Generation server base URL; combines go through its /combine, as the
+    /// game's do, and are cached under its combine version. Scoring still
+    /// uses Ollama
+    #[arg(long)]
+    server_url: Option<String>,
+
+    /// After scoring, generate card art for this many of the best-scoring
+    /// results through --server-url, into the run's images folder with an
+    /// index.html
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    images: Option<u32>,
+
     /// Ollama model name
     #[arg(long, default_value = "gemma3:4b")]
     model: String,
+
+    /// Ollama calls in flight at once; results are still merged in order
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    concurrency: u32,
+
+    /// Repeat the sample pairs across a grid of temperatures and seeds and
+    /// report how stable and varied the results are
+    #[arg(long)]
+    sweep: bool,
+
+    /// Temperatures for --sweep
+    #[arg(long, value_delimiter = ',', default_value = "0,0.4,0.8,1.2")]
+    temperatures: Vec<f32>,
+
+    /// Seeds for --sweep
+    #[arg(long, value_delimiter = ',', default_value = "42,1,2,3")]
+    seeds: Vec<u32>,
+
+    /// Calls per temperature and seed for --sweep
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    repeats: u32,
+
+    /// Combine every pair of cards from an element set, feeding new results
+    /// back in level by level
+    #[arg(long)]
+    bfs: bool,
+
+    /// Search for the target items, expanding only the cards scored
+    /// closest to one, and report the shortest recipe found for each
+    #[arg(long)]
+    beam: bool,
+
+    /// Search for items that fit this one board category, e.g. "Musical
+    /// Instrument": combine the materials and intents scored best for it,
+    /// chain the best results, and report every item scoring 7 or more
+    #[arg(long)]
+    category: Option<String>,
+
+    /// Combine sets of this many materials at once (3 or 4), sampled from
+    /// the --theory elements by --pairs, and compare what they make with the
+    /// cached results of the pairs within them
+    #[arg(long, value_parser = clap::value_parser!(u32).range(3..=4))]
+    arity: Option<u32>,
+
+    /// Cards expanded per level for --beam, and materials combined and
+    /// results chained per level for --category
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    beam_width: u32,
+
+    /// Levels of combinations for --bfs, --beam and --category
+    #[arg(long, default_value_t = 2)]
+    max_depth: usize,
+
+    /// Combine system prompt files to compare with the game's, each over
+    /// every pair of the --theory elements
+    #[arg(long, value_delimiter = ',')]
+    prompts: Vec<PathBuf>,
+
+    /// Element set for --bfs, --beam, --category, --arity and --prompts, by
+    /// theory name (default: the first)
+    #[arg(long)]
+    theory: Option<String>,
+
+    /// Pairs step 1, --sensory and --sweep combine, or sets --arity does:
+    /// stride (15 spread evenly, the same every run), full, random:N, or
+    /// stratified[:N] (N at random with every element used about equally, 15
+    /// by default)
+    #[arg(long, default_value = "stride")]
+    pairs: PairSampling,
+
+    /// Seed for random and stratified --pairs; change it for another sample
+    #[arg(long, default_value_t = 42)]
+    pair_seed: u64,
+
+    /// Times to run each step 1 (or --sensory) combination; after the first,
+    /// runs are sampled at --sample-temperature so families can be compared on
+    /// more than one answer each
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    samples: u32,
+
+    /// Temperature for the extra --samples
+    #[arg(long, default_value_t = 0.7)]
+    sample_temperature: f32,
+
+    /// Write every valid combination cached for this model and prompt (or
+    /// --server-url) as a crafting graph to this file (Graphviz DOT, or
+    /// GraphML if named *.graphml) and exit
+    #[arg(long)]
+    export_graph: Option<PathBuf>,
+
+    /// After the run, write the winning element set as materials and the
+    /// winning modifier family as intents to this file, in the game's
+    /// cards.json format
+    #[arg(long)]
+    export_cards: Option<PathBuf>,
+
+    /// Element sets, modifier families, categories and targets to use
+    /// instead of the built-in ones (TOML, or JSON if named *.json)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Print the calls a run would make, how many aren't cached and about how
+    /// long they'd take, without calling anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Stop cleanly after this many Ollama or generation server calls,
+    /// writing the report and run state so far for --resume
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_calls: Option<u32>,
+
+    /// Stop cleanly once this many minutes have passed, as --max-calls does
+    #[arg(long)]
+    max_minutes: Option<f64>,
+
+    /// Run again only the combines that failed before (for this model and
+    /// prompt, or --server-url), caching those that now succeed
+    #[arg(long)]
+    retry_errors: bool,
+
+    /// Continue the last run from where it stopped (explore/run-state.json)
+    #[arg(long)]
+    resume: bool,
 }
 
+/// Distinct first-order results step 3 combines with the elements, and
+/// distinct results on each side of its third-order combines.
+const SECOND_ORDER_INPUTS: usize = 15;
+const THIRD_ORDER_INPUTS: usize = 10;
+
+/// Tries step 4 gives each card before recording it as failing to score.
+const SCORE_ATTEMPTS: u32 = 3;
+/// Wait before the second try, doubled for each one after, so an overloaded
+/// Ollama gets a moment to recover.
+const SCORE_BACKOFF: Duration = Duration::from_secs(2);
+
+#[derive(Subcommand)]
+enum Command {
+    /// Type combinations like `Fire + Clay [Hollow]` and see each result
+    /// straight away, cached or fresh; `:help` lists the other commands
+    Repl,
+    /// Compare the results two models or prompts cached for the same
+    /// combinations, e.g. to see what a prompt tweak changed
+    Diff {
+        /// MODEL[@PROMPT] to compare from; PROMPT is a prompt fingerprint,
+        /// by default the game prompt's (see the Cache line of any run)
+        old: String,
+        /// MODEL[@PROMPT] to compare to
+        new: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
 struct Stats {
     calls: usize,
     valid: usize,
     cached: usize,
+    /// Every combine so far, for the run's results.json.
+    #[serde(default)]
+    results: Vec<RawResult>,
 }
 
 impl Stats {
@@ -53,142 +261,733 @@ impl Stats {
             calls: 0,
             valid: 0,
             cached: 0,
+            results: Vec::new(),
         }
     }
+}
 
-    fn print_running(&self) {
-        let valid_pct = if self.calls > 0 {
-            self.valid as f64 / self.calls as f64 * 100.0
-        } else {
-            0.0
+/// A combine that was either cached or is running on Ollama, timed for
+/// `--dry-run`'s estimates.
+enum Pending {
+    Cached(combine::CombineResult),
+    Running(tokio::task::JoinHandle<Result<(combine::CombineResult, Duration), String>>),
+}
+
+/// Run every `(label, cards)` combine, with at most `limit` Ollama calls in
+/// flight, and return the results in the order given. Results are logged and
+/// cached in that order too, so output reads the same at any concurrency.
+/// Once the budget is spent the results stop short, at the first combine it
+/// refused.
+async fn combine_all(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    cache: &mut Cache,
+    combos: Vec<(String, Vec<Card>)>,
+    stats: &mut Stats,
+) -> Vec<combine::CombineResult> {
+    combine_all_with(client, limit, cache, combos, None, stats).await
+}
+
+/// `combine_all`, or with `sampling` given, the same combines sampled
+/// differently. Those skip the cache, which only holds the game's answers.
+async fn combine_all_with(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    cache: &mut Cache,
+    combos: Vec<(String, Vec<Card>)>,
+    sampling: Option<Sampling>,
+    stats: &mut Stats,
+) -> Vec<combine::CombineResult> {
+    // Start every uncached combine up front; each waits for a permit
+    let pending: Vec<_> = combos
+        .into_iter()
+        .map(|(label, cards)| {
+            let cached = match sampling {
+                Some(_) => None,
+                None => cache.get(&cards),
+            };
+            let call = match cached {
+                Some(cached) => Pending::Cached(cached),
+                None => {
+                    let (client, limit) = (client.clone(), limit.clone());
+                    let cards = cards.clone();
+                    Pending::Running(tokio::spawn(async move {
+                        let _permit = limit.acquire_owned().await.map_err(|e| e.to_string())?;
+                        let started = Instant::now();
+                        let result = match sampling {
+                            Some(s) => client.combine_with(&cards, s.temperature, s.seed).await,
+                            None => client.combine(&cards).await,
+                        };
+                        result.map(|r| (r, started.elapsed()))
+                    }))
+                }
+            };
+            (label, cards, call)
+        })
+        .collect();
+
+    let mut progress = Progress::new(pending.len());
+    let mut results = Vec::with_capacity(pending.len());
+    // (label, cached) per result, for the run's results.json
+    let mut raw = Vec::with_capacity(pending.len());
+    // Where the budget first refused a call; results stop there
+    let mut refused = None;
+    for (label, cards, call) in pending {
+        let was_cached = matches!(call, Pending::Cached(_));
+        let result = match call {
+            Pending::Cached(cached) => {
+                stats.cached += 1;
+                let valid = cached.name != "Not possible";
+                if valid {
+                    stats.valid += 1;
+                }
+                let marker = if valid { "+" } else { "-" };
+                progress.println(&format!("  [{marker}] {label} = {} (cached)", cached.name));
+                cached
+            }
+            Pending::Running(call) => {
+                match call.await.map_err(|e| e.to_string()).and_then(|r| r) {
+                    Ok((result, elapsed)) => {
+                        cache.record_latency(elapsed);
+                        let valid = result.name != "Not possible";
+                        if valid {
+                            stats.valid += 1;
+                        }
+                        let marker = if valid { "+" } else { "-" };
+                        progress.println(&format!(
+                            "  [{marker}] {label} = {} — {}",
+                            result.name, result.description
+                        ));
+                        if sampling.is_none() {
+                            cache.insert(&cards, &result);
+                        }
+                        result
+                    }
+                    Err(e) if e == budget::SPENT => {
+                        refused.get_or_insert(results.len());
+                        continue;
+                    }
+                    Err(e) => {
+                        progress.eprintln(&format!("  [!] {label} ERROR: {e}"));
+                        if sampling.is_none() {
+                            cache.record_error(&label, &cards, &e);
+                        }
+                        combine::CombineResult {
+                            name: "Not possible".to_string(),
+                            description: format!("Error: {e}"),
+                        }
+                    }
+                }
+            }
         };
-        eprint!(
-            "\r  [{} calls, {:.0}% valid, {} cached]",
-            self.calls, valid_pct, self.cached
-        );
+        stats.calls += 1;
+        progress.tick(was_cached, result.name != "Not possible");
+        raw.push((label, was_cached));
+        results.push(result);
     }
+    progress.finish();
+    if let Some(refused) = refused {
+        results.truncate(refused);
+        raw.truncate(refused);
+    }
+    stats.results.extend(raw.into_iter().zip(&results).map(|((label, cached), result)| {
+        RawResult {
+            label,
+            name: result.name.clone(),
+            description: result.description.clone(),
+            cached,
+            sampling,
+        }
+    }));
+    results
 }
 
-async fn do_combine(
-    client: &OllamaClient,
+/// Combine each of `combos` the game's way, then `--samples` - 1 more times
+/// sampled with other seeds, and return every result: all of the first round,
+/// then all of the next, and so on.
+async fn combine_samples(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
     cache: &mut Cache,
-    cache_path: &PathBuf,
-    cards: &[Card],
-    label: &str,
+    combos: Vec<(String, Vec<Card>)>,
+    cli: &Cli,
     stats: &mut Stats,
-) -> combine::CombineResult {
-    stats.calls += 1;
-
-    // Check cache
-    if let Some(cached) = cache.get(cards) {
-        stats.cached += 1;
-        let valid = cached.name != "Not possible";
-        if valid {
-            stats.valid += 1;
+) -> Vec<combine::CombineResult> {
+    let mut results = combine_all(client, limit, cache, combos.clone(), stats).await;
+    for seed in 1..cli.samples {
+        if client.budget().is_spent() {
+            break;
         }
-        let marker = if valid { "+" } else { "-" };
-        println!("  [{marker}] {label} = {} (cached)", cached.name);
-        stats.print_running();
-        return cached;
+        let sampling = Sampling {
+            temperature: cli.sample_temperature,
+            seed,
+        };
+        let combos = combos
+            .iter()
+            .map(|(label, cards)| (format!("{label} (sample {})", seed + 1), cards.clone()))
+            .collect();
+        results.extend(combine_all_with(client, limit, cache, combos, Some(sampling), stats).await);
     }
+    results
+}
 
-    match client.combine(cards).await {
-        Ok(result) => {
-            let valid = result.name != "Not possible";
-            if valid {
-                stats.valid += 1;
-            }
-            let marker = if valid { "+" } else { "-" };
-            println!(
-                "  [{marker}] {label} = {} — {}",
-                result.name, result.description
-            );
-            cache.insert(cards, &result);
-            cache.save(cache_path);
-            stats.print_running();
-            result
+/// The pairs `--pairs` picks from `elements`, saying how many and how.
+fn sample_pairs_for(cli: &Cli, elements: &[Card]) -> Vec<(Card, Card)> {
+    let pairs = sample_pairs(elements, cli.pairs, cli.pair_seed);
+    match cli.pairs {
+        PairSampling::Stride | PairSampling::Full => {
+            println!("{} pairs ({})\n", pairs.len(), cli.pairs);
         }
-        Err(e) => {
-            eprintln!("  [!] {label} ERROR: {e}");
-            stats.print_running();
-            combine::CombineResult {
-                name: "Not possible".to_string(),
-                description: format!("Error: {e}"),
+        _ => println!("{} pairs ({}, seed {})\n", pairs.len(), cli.pairs, cli.pair_seed),
+    }
+    pairs
+}
+
+/// Score each `(name, description)` against the board categories, with at
+/// most `limit` Ollama calls in flight, logging and returning results in order.
+/// A card that fails is tried again after a backoff, up to `SCORE_ATTEMPTS`
+/// times in all.
+async fn score_all(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    categories: &Arc<Vec<String>>,
+    cards: &[(String, String)],
+    progress: &mut Progress,
+) -> Vec<(String, Result<HashMap<String, u32>, String>)> {
+    let calls: Vec<_> = cards
+        .iter()
+        .map(|(name, desc)| {
+            let (client, limit) = (client.clone(), limit.clone());
+            let categories = categories.clone();
+            let (name, desc) = (name.clone(), desc.clone());
+            tokio::spawn(async move {
+                let _permit = limit.acquire_owned().await.map_err(|e| e.to_string())?;
+                let mut attempt = 1;
+                loop {
+                    match client.score_categories(&name, &desc, &categories).await {
+                        Err(e) if e != budget::SPENT && attempt < SCORE_ATTEMPTS => {
+                            tokio::time::sleep(SCORE_BACKOFF * 2u32.pow(attempt - 1)).await;
+                            attempt += 1;
+                        }
+                        Err(e) if e != budget::SPENT => {
+                            return Err(format!("{e} (tried {SCORE_ATTEMPTS} times)"));
+                        }
+                        result => return result,
+                    }
+                }
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(calls.len());
+    for ((name, _), call) in cards.iter().zip(calls) {
+        let scores = call.await.map_err(|e| e.to_string()).and_then(|r| r);
+        match &scores {
+            Ok(scores) => {
+                let top_cat = scores
+                    .iter()
+                    .max_by_key(|(_, &v)| v)
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .unwrap_or_default();
+                progress.eprintln(&format!("  Scoring {name}... done (best: {top_cat})"));
             }
+            Err(e) if *e == budget::SPENT => continue,
+            Err(e) => progress.eprintln(&format!("  Scoring {name}... error: {e}")),
         }
+        progress.tick(false, scores.is_ok());
+        results.push((name.clone(), scores));
+    }
+    results
+}
+
+/// Write the report so far, once the budget has stopped a run partway. The
+/// run state saved after the last finished section is kept for `--resume`;
+/// the section it stopped in is left out, but its finished combines are
+/// cached, so resuming replays them.
+fn stop_early(
+    client: &OllamaClient,
+    report: &Report,
+    config: &Config,
+    stats: &Stats,
+    run: &RunDir,
+) {
+    report.print_target_checklist(config);
+    report.write_to_file(&run.file("report.md"), config);
+    println!(
+        "\nStopped at {}: {} total calls ({} cached). Continue with --resume",
+        client.budget().describe(),
+        stats.calls,
+        stats.cached
+    );
+    run.finish(stats, client.budget());
+}
+
+/// Say so if the budget cut a mode without run state short, and what its
+/// report `covers` as a result.
+fn note_stopped(client: &OllamaClient, covers: &str) {
+    if client.budget().is_spent() {
+        println!("\nStopped at {}; {covers}", client.budget().describe());
     }
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
-    let client = OllamaClient::new(&cli.ollama_url, &cli.model);
-    let cache_path = PathBuf::from("explore/cache.json");
-    let mut cache = Cache::load(&cache_path);
-    let mut report = Report::new();
-    let mut stats = Stats::new();
+    let config = match &cli.config {
+        Some(path) => Config::load(path).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }),
+        None => Config::default(),
+    };
+    // The server only answers the game's way, so it can't sample
+    if cli.server_url.is_some() && (cli.sweep || cli.samples > 1 || !cli.prompts.is_empty()) {
+        eprintln!("--sweep, --samples and --prompts need Ollama directly; drop --server-url");
+        std::process::exit(1);
+    }
+    let other_mode = cli.sweep
+        || cli.bfs
+        || cli.beam
+        || cli.category.is_some()
+        || cli.arity.is_some()
+        || !cli.prompts.is_empty()
+        || cli.retry_errors;
+    if cli.dry_run && (other_mode || cli.command.is_some() || cli.export_graph.is_some()) {
+        eprintln!("--dry-run only plans the steps or --sensory run");
+        std::process::exit(1);
+    }
+    if cli.quality && (other_mode || cli.sensory) {
+        eprintln!("--quality judges the results of steps 1 to 3");
+        std::process::exit(1);
+    }
+    if cli.export_cards.is_some() && (other_mode || cli.sensory || cli.command.is_some()) {
+        eprintln!("--export-cards writes the winners of steps 1 and 2");
+        std::process::exit(1);
+    }
+    if cli.judge_model.is_some() && !cli.quality {
+        eprintln!("--judge-model is only used with --quality");
+        std::process::exit(1);
+    }
+    if let Some(category) = &cli.category {
+        if !config.board_categories.iter().any(|c| c.eq_ignore_ascii_case(category)) {
+            eprintln!(
+                "No board category named {category}; categories: {}",
+                config.board_categories.join(", ")
+            );
+            std::process::exit(1);
+        }
+    }
+    if cli.images.is_some() && (cli.server_url.is_none() || cli.no_score) {
+        eprintln!("--images needs --server-url to draw with and scoring to rank by");
+        std::process::exit(1);
+    }
+    if cli.max_minutes.is_some_and(|m| m <= 0.0) {
+        eprintln!("--max-minutes must be more than 0");
+        std::process::exit(1);
+    }
+    let budget = Budget::new(cli.max_calls.map(|n| n as usize), cli.max_minutes);
+    let mut client = OllamaClient::new(&cli.ollama_url, &cli.model).with_budget(budget);
+    if let Some(server_url) = &cli.server_url {
+        client = client.with_server(server_url);
+    }
+    let client = Arc::new(client);
+    let limit = Arc::new(Semaphore::new(cli.concurrency as usize));
+    // The server's combine version covers its model and prompt both
+    let (cache_model, prompt) = match &cli.server_url {
+        Some(server_url) => match client.server_version().await {
+            Ok(version) => ("generation-server".to_string(), version),
+            Err(e) => {
+                eprintln!("Generation server at {server_url} unavailable: {e}");
+                std::process::exit(1);
+            }
+        },
+        None => (cli.model.clone(), combine::prompt_fingerprint(client.system_prompt())),
+    };
+    let cache_path = Path::new("explore/cache.db");
+    let mut cache = Cache::open(cache_path, &cache_model, &prompt).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    let state_path = PathBuf::from("explore/run-state.json");
+    let options = RunOptions {
+        step: cli.step,
+        deep: cli.deep,
+        sensory: cli.sensory,
+        no_score: cli.no_score,
+        quality: cli.quality,
+        judge_model: cli.judge_model.clone(),
+        model: cli.model.clone(),
+        server_url: cli.server_url.clone(),
+        images: cli.images,
+        config: cli.config.clone(),
+        pairs: cli.pairs,
+        pair_seed: cli.pair_seed,
+        samples: cli.samples,
+        sample_temperature: cli.sample_temperature,
+    };
+    let (mut checkpoint, mut report, mut stats) = if cli.resume {
+        match Checkpoint::resume(&state_path, options) {
+            Ok(resumed) => resumed,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        (Checkpoint::new(&state_path, options), Report::new(), Stats::new())
+    };
+
+    // ========== Graph export ==========
+    if let Some(path) = &cli.export_graph {
+        if let Err(e) = graph::Graph::from_cache(&cache, &config).write_to_file(path) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // ========== Diff ==========
+    if let Some(Command::Diff { old, new }) = &cli.command {
+        let game_prompt = combine::prompt_fingerprint(client.system_prompt());
+        let old = diff::Side::parse(old, &game_prompt);
+        let new = diff::Side::parse(new, &game_prompt);
+        let diff = diff::run(cache_path, &old, &new).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+        diff.print();
+        diff.write_to_file("explore/diff.md");
+        return;
+    }
+
+    println!(
+        "Explore: Ollama at {}, model {}, {} at a time",
+        cli.ollama_url, cli.model, cli.concurrency
+    );
+    if let Some(server_url) = &cli.server_url {
+        println!("Combining via generation server at {server_url}");
+    }
+    println!("Cache: {} entries for {cache_model} (prompt {prompt})", cache.len());
+    let failed = cache.errors().len();
+    if failed > 0 {
+        println!("{failed} combines failed before; --retry-errors runs them again");
+    }
+    println!();
+
+    // ========== Dry run ==========
+    if cli.dry_run {
+        plan::Plan::build(&cli, &config, &cache, &checkpoint, &report).print();
+        return;
+    }
+
+    // ========== REPL ==========
+    if let Some(Command::Repl) = cli.command {
+        repl::run(&client, &mut cache, &config, Path::new("explore/pins.json")).await;
+        return;
+    }
+
+    let info = RunInfo {
+        started: runs::timestamp(std::time::SystemTime::now()),
+        args: std::env::args().skip(1).collect(),
+        model: cli.model.clone(),
+        ollama_url: cli.ollama_url.clone(),
+        server_url: cli.server_url.clone(),
+        cache_model: cache_model.clone(),
+        prompt: prompt.clone(),
+        resumed: Vec::new(),
+    };
+    let run = match checkpoint.run_dir() {
+        Some(path) => RunDir::reopen(path, info),
+        None => RunDir::create(Path::new("explore/runs"), info, &config),
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    checkpoint.set_run_dir(run.path());
+    println!("Writing to {}\n", run.path().display());
+
+    // ========== Retry errors ==========
+    if cli.retry_errors {
+        let combos: Vec<_> = cache
+            .errors()
+            .into_iter()
+            .map(|(label, cards, _)| (label, cards))
+            .collect();
+        println!("=== RETRYING {} FAILED COMBINES ===\n", combos.len());
+
+        let tried = combos.len();
+        combine_all(&client, &limit, &mut cache, combos, &mut stats).await;
+        let failing = cache.errors().len();
+        println!("\n{} of {tried} now succeed; {failing} still fail", tried - failing);
+        note_stopped(&client, "the rest weren't retried");
+        run.finish(&stats, client.budget());
+        return;
+    }
+
+    // ========== Sweep mode ==========
+    if cli.sweep {
+        println!("=== TEMPERATURE AND SEED SWEEP ===\n");
+
+        let pairs = sample_pairs_for(&cli, config.baseline_elements());
+        let settings = SweepSettings {
+            temperatures: cli.temperatures.clone(),
+            seeds: cli.seeds.clone(),
+            repeats: cli.repeats,
+        };
+        let sweep = sweep::run(&client, &limit, &pairs, settings).await;
+        sweep.print();
+        sweep.write_to_file(&run.file("sweep.md"));
+        note_stopped(&client, "the sweep covers only the pairs finished before it");
+        run.finish(&stats, client.budget());
+        return;
+    }
+
+    let theory = match &cli.theory {
+        Some(name) => config.theory(name).unwrap_or_else(|| {
+            eprintln!("No theory named {name}");
+            std::process::exit(1);
+        }),
+        None => &config.theories[0],
+    };
+
+    // ========== Arity mode ==========
+    if let Some(arity) = cli.arity {
+        let arity = arity as usize;
+        if theory.elements.len() < arity {
+            eprintln!("Theory {} has fewer than {arity} elements", theory.name);
+            std::process::exit(1);
+        }
+        println!(
+            "=== {arity} MATERIALS AT ONCE: Theory {}: {} ===\n",
+            theory.name, theory.label
+        );
+        let sets = sample_sets(&theory.elements, arity, cli.pairs, cli.pair_seed);
+        match cli.pairs {
+            PairSampling::Stride | PairSampling::Full => {
+                println!("{} sets ({})\n", sets.len(), cli.pairs);
+            }
+            _ => println!("{} sets ({}, seed {})\n", sets.len(), cli.pairs, cli.pair_seed),
+        }
+
+        let arity = arity::run(&client, &limit, &mut cache, sets, &mut stats).await;
+        arity.print();
+        arity.write_to_file(&run.file("arity.md"));
+        note_stopped(&client, "the rest of the sets weren't combined");
+        run.finish(&stats, client.budget());
+        return;
+    }
+
+    // ========== Prompt comparison mode ==========
+    if !cli.prompts.is_empty() {
+        println!(
+            "=== PROMPT COMPARISON: Theory {}: {}, {} prompts ===\n",
+            theory.name,
+            theory.label,
+            cli.prompts.len() + 1
+        );
+
+        let elements = &theory.elements;
+        let mut combos = Vec::new();
+        for (i, a) in elements.iter().enumerate() {
+            for b in &elements[i..] {
+                combos.push((format!("{} + {}", a.name, b.name), vec![a.clone(), b.clone()]));
+            }
+        }
+        let comparison = match prompts::PromptVariant::load(&client, &cli.prompts) {
+            Ok(variants) => {
+                prompts::run(&client, &limit, cache_path, variants, combos, &config, &mut stats)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let comparison = comparison.unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+        comparison.print();
+        comparison.write_to_file(&run.file("prompts.md"));
+        note_stopped(&client, "prompts it didn't finish are left out");
+        run.finish(&stats, client.budget());
+        return;
+    }
+
+    // ========== BFS mode ==========
+    if cli.bfs {
+        println!(
+            "=== BFS DISCOVERY: Theory {}: {}, {} levels ===\n",
+            theory.name, theory.label, cli.max_depth
+        );
+
+        let bfs = bfs::run(
+            &client,
+            &limit,
+            &mut cache,
+            &theory.elements,
+            cli.max_depth,
+            &mut stats,
+        )
+        .await;
+        bfs.print();
+        bfs.write_to_file(&run.file("bfs.md"));
+        note_stopped(&client, "the last level is partial");
+        run.finish(&stats, client.budget());
+        return;
+    }
+
+    // ========== Beam search mode ==========
+    if cli.beam {
+        let settings = BeamSettings {
+            targets: config
+                .target_items
+                .iter()
+                .flat_map(|group| group.items.iter().cloned())
+                .collect(),
+            width: cli.beam_width as usize,
+            max_depth: cli.max_depth,
+        };
+        println!(
+            "=== BEAM SEARCH: Theory {}: {}, {} targets, width {}, {} levels ===\n",
+            theory.name,
+            theory.label,
+            settings.targets.len(),
+            settings.width,
+            settings.max_depth
+        );
+
+        let beam = beam::run(
+            &client,
+            &limit,
+            &mut cache,
+            &theory.elements,
+            &settings,
+            &mut stats,
+        )
+        .await;
+        beam.print();
+        beam.write_to_file(&run.file("beam.md"));
+        note_stopped(&client, "the last level is partial");
+        run.finish(&stats, client.budget());
+        return;
+    }
+
+    // ========== Category deep-dive mode ==========
+    if let Some(category) = &cli.category {
+        let settings = CategorySettings {
+            category: config
+                .board_categories
+                .iter()
+                .find(|c| c.eq_ignore_ascii_case(category))
+                .cloned()
+                .unwrap_or_else(|| category.clone()),
+            width: cli.beam_width as usize,
+            max_depth: cli.max_depth,
+        };
+        // Every family's intents, each once
+        let mut seen = std::collections::HashSet::new();
+        let intents: Vec<Card> = config
+            .modifier_families
+            .iter()
+            .flat_map(|f| &f.modifiers)
+            .filter(|m| seen.insert(m.name.to_lowercase()))
+            .cloned()
+            .collect();
+        println!(
+            "=== CATEGORY: {}, Theory {}: {}, width {}, {} levels ===\n",
+            settings.category, theory.name, theory.label, settings.width, settings.max_depth
+        );
 
-    println!("Explore: Ollama at {}, model {}", cli.ollama_url, cli.model);
-    println!("Cache: {} entries loaded\n", cache.len());
+        let dive = category::run(
+            &client,
+            &limit,
+            &mut cache,
+            &theory.elements,
+            &intents,
+            &settings,
+            &mut stats,
+        )
+        .await;
+        dive.print();
+        dive.write_to_file(&run.file("category.md"));
+        note_stopped(&client, "the last level is partial");
+        run.finish(&stats, client.budget());
+        return;
+    }
 
     // ========== Sensory variations mode ==========
     if cli.sensory {
         println!("=== SENSORY MODIFIER VARIATIONS (Theory G) ===\n");
 
-        let elements = theory_g_elements();
-        let pairs = sample_pairs(&elements);
-        let variations = sensory_variations();
+        let Some(theory_g) = config.theory("G") else {
+            eprintln!("--sensory needs a theory named G");
+            std::process::exit(1);
+        };
+        let pairs = sample_pairs_for(&cli, &theory_g.elements);
+        let variations = &config.sensory_variations;
 
         // Bare pairs first
-        println!("--- Bare pairs (Theory G, no modifier) ---");
-        for (a, b) in &pairs {
-            let label = format!("{} + {}", a.name, b.name);
-            let cards = vec![a.clone(), b.clone()];
-            let result =
-                do_combine(&client, &mut cache, &cache_path, &cards, &label, &mut stats).await;
-            report.bare_results.insert(label, result);
+        if checkpoint.is_done("sensory/bare") {
+            println!("--- Bare pairs: done before resuming ---\n");
+        } else {
+            println!("--- Bare pairs (Theory G, no modifier) ---");
+            let combos: Vec<_> = pairs
+                .iter()
+                .map(|(a, b)| (format!("{} + {}", a.name, b.name), vec![a.clone(), b.clone()]))
+                .collect();
+            let labels: Vec<String> = combos.iter().map(|(label, _)| label.clone()).collect();
+            let results = combine_all(&client, &limit, &mut cache, combos, &mut stats).await;
+            if client.budget().is_spent() {
+                return stop_early(&client, &report, &config, &stats, &run);
+            }
+            report.bare_results.extend(labels.into_iter().zip(results));
+            println!();
+            checkpoint.finish("sensory/bare", &report, &stats);
         }
-        println!();
 
         // Test each Sensory variation
-        for family in &variations {
+        for family in variations {
+            let section = format!("sensory/{}", family.name);
+            if checkpoint.is_done(&section) {
+                println!("--- {}: done before resuming ---\n", family.name);
+                continue;
+            }
             println!("--- {} ---", family.name);
 
-            let mut family_results = Vec::new();
-
+            let mut keys = Vec::new();
+            let mut combos = Vec::new();
             for (a, b) in &pairs {
                 for modifier in &family.modifiers {
                     let label = format!(
                         "{} + {} [{}]",
                         a.name, b.name, modifier.name
                     );
-                    let cards = vec![a.clone(), b.clone(), modifier.clone()];
-                    let result = do_combine(
-                        &client,
-                        &mut cache,
-                        &cache_path,
-                        &cards,
-                        &label,
-                        &mut stats,
-                    )
-                    .await;
-                    family_results.push((
-                        format!("{} + {}", a.name, b.name),
-                        modifier.name.clone(),
-                        result,
-                    ));
+                    combos.push((label, vec![a.clone(), b.clone(), modifier.clone()]));
+                    keys.push((format!("{} + {}", a.name, b.name), modifier.name.clone()));
                 }
             }
+            let results =
+                combine_samples(&client, &limit, &mut cache, combos, &cli, &mut stats).await;
+            if client.budget().is_spent() {
+                return stop_early(&client, &report, &config, &stats, &run);
+            }
+            let family_results: Vec<_> = keys
+                .iter()
+                .cycle()
+                .zip(results)
+                .map(|((pair, modifier), result)| (pair.clone(), modifier.clone(), result))
+                .collect();
             println!();
 
             report
                 .modifier_results
                 .insert(family.name.to_string(), family_results);
+            checkpoint.finish(&section, &report, &stats);
         }
 
         report.print_modifier_comparison();
-        report.print_target_checklist();
-        report.write_to_file("explore/report.md");
+        report.print_convergence();
+        report.print_target_checklist(&config);
+        report.write_to_file(&run.file("report.md"), &config);
 
         println!(
             "\nDone! {} total calls ({} cached), {:.0}% valid",
@@ -200,6 +999,8 @@ async fn main() {
                 0.0
             }
         );
+        checkpoint.remove();
+        run.finish(&stats, client.budget());
         return;
     }
 
@@ -210,55 +1011,66 @@ async fn main() {
     if run_step1 {
         println!("=== STEP 1: Modifier Family Comparison ===\n");
 
-        let elements = baseline_elements();
-        let pairs = sample_pairs(&elements);
-        let families = all_modifier_families();
+        let pairs = sample_pairs_for(&cli, config.baseline_elements());
+        let families = &config.modifier_families;
 
         // Bare pairs (no modifier)
-        println!("--- Bare pairs ---");
-        for (a, b) in &pairs {
-            let label = format!("{} + {}", a.name, b.name);
-            let cards = vec![a.clone(), b.clone()];
-            let result =
-                do_combine(&client, &mut cache, &cache_path, &cards, &label, &mut stats).await;
-            report.bare_results.insert(label, result);
+        if checkpoint.is_done("step1/bare") {
+            println!("--- Bare pairs: done before resuming ---\n");
+        } else {
+            println!("--- Bare pairs ---");
+            let combos: Vec<_> = pairs
+                .iter()
+                .map(|(a, b)| (format!("{} + {}", a.name, b.name), vec![a.clone(), b.clone()]))
+                .collect();
+            let labels: Vec<String> = combos.iter().map(|(label, _)| label.clone()).collect();
+            let results = combine_all(&client, &limit, &mut cache, combos, &mut stats).await;
+            if client.budget().is_spent() {
+                return stop_early(&client, &report, &config, &stats, &run);
+            }
+            report.bare_results.extend(labels.into_iter().zip(results));
+            println!();
+            checkpoint.finish("step1/bare", &report, &stats);
         }
-        println!();
 
         // Each modifier family
-        for family in &families {
+        for family in families {
+            let section = format!("step1/{}", family.name);
+            if checkpoint.is_done(&section) {
+                println!("--- Family: {}: done before resuming ---\n", family.name);
+                continue;
+            }
             println!("--- Family: {} ---", family.name);
 
-            let mut family_results = Vec::new();
-
+            let mut keys = Vec::new();
+            let mut combos = Vec::new();
             for (a, b) in &pairs {
                 for modifier in &family.modifiers {
                     let label = format!(
                         "{} + {} [{}]",
                         a.name, b.name, modifier.name
                     );
-                    let cards = vec![a.clone(), b.clone(), modifier.clone()];
-                    let result = do_combine(
-                        &client,
-                        &mut cache,
-                        &cache_path,
-                        &cards,
-                        &label,
-                        &mut stats,
-                    )
-                    .await;
-                    family_results.push((
-                        format!("{} + {}", a.name, b.name),
-                        modifier.name.clone(),
-                        result,
-                    ));
+                    combos.push((label, vec![a.clone(), b.clone(), modifier.clone()]));
+                    keys.push((format!("{} + {}", a.name, b.name), modifier.name.clone()));
                 }
             }
+            let results =
+                combine_samples(&client, &limit, &mut cache, combos, &cli, &mut stats).await;
+            if client.budget().is_spent() {
+                return stop_early(&client, &report, &config, &stats, &run);
+            }
+            let family_results: Vec<_> = keys
+                .iter()
+                .cycle()
+                .zip(results)
+                .map(|((pair, modifier), result)| (pair.clone(), modifier.clone(), result))
+                .collect();
             println!();
 
             report
                 .modifier_results
                 .insert(family.name.to_string(), family_results);
+            checkpoint.finish(&section, &report, &stats);
         }
 
         report.print_modifier_comparison();
@@ -268,31 +1080,31 @@ async fn main() {
     if run_step2 {
         println!("\n=== STEP 2: Element Set Comparison ===\n");
 
-        // Determine best modifier to use (from step 1 or cache)
-        let winning_family_name = report
+        // Determine best modifier to use (from step 1, or the first family)
+        let families = &config.modifier_families;
+        let winning_family = report
             .winning_family
-            .clone()
-            .unwrap_or_else(|| "Evocative".to_string());
-
-        let families = all_modifier_families();
-        let winning_family = families
-            .iter()
-            .find(|f| f.name == winning_family_name)
-            .expect("winning family not found");
+            .as_ref()
+            .and_then(|name| families.iter().find(|f| f.name == *name))
+            .unwrap_or(&families[0]);
 
         // Pick a representative modifier from the winning family (first one)
         let best_modifier = &winning_family.modifiers[0];
         println!(
             "Using modifier family '{}', representative modifier '{}'\n",
-            winning_family_name, best_modifier.name
+            winning_family.name, best_modifier.name
         );
 
-        for theory in all_theories() {
+        for theory in &config.theories {
+            let section = format!("step2/{}", theory.name);
+            if checkpoint.is_done(&section) {
+                println!("--- Theory {}: done before resuming ---\n", theory.name);
+                continue;
+            }
             println!("--- Theory {}: {} ---", theory.name, theory.label);
 
             let n = theory.elements.len();
-            let mut bare_results = Vec::new();
-            let mut mod_results = Vec::new();
+            let mut combos = Vec::new();
 
             for i in 0..n {
                 for j in (i + 1)..n {
@@ -301,34 +1113,30 @@ async fn main() {
 
                     // Bare combination
                     let label = format!("{} + {}", a.name, b.name);
-                    let cards = vec![a.clone(), b.clone()];
-                    let result = do_combine(
-                        &client,
-                        &mut cache,
-                        &cache_path,
-                        &cards,
-                        &label,
-                        &mut stats,
-                    )
-                    .await;
-                    bare_results.push((label, result));
+                    combos.push((label, vec![a.clone(), b.clone()]));
 
                     // With modifier
                     let label = format!(
                         "{} + {} [{}]",
                         a.name, b.name, best_modifier.name
                     );
-                    let cards = vec![a.clone(), b.clone(), best_modifier.clone()];
-                    let result = do_combine(
-                        &client,
-                        &mut cache,
-                        &cache_path,
-                        &cards,
-                        &label,
-                        &mut stats,
-                    )
-                    .await;
-                    mod_results.push((label, result));
+                    combos.push((label, vec![a.clone(), b.clone(), best_modifier.clone()]));
+                }
+            }
+            let labels: Vec<String> = combos.iter().map(|(label, _)| label.clone()).collect();
+            let results = combine_all(&client, &limit, &mut cache, combos, &mut stats).await;
+            if client.budget().is_spent() {
+                return stop_early(&client, &report, &config, &stats, &run);
+            }
+
+            // Bare and modified combinations alternate
+            let mut bare_results = Vec::new();
+            let mut mod_results = Vec::new();
+            for (i, labelled) in labels.into_iter().zip(results).enumerate() {
+                if i % 2 == 0 {
+                    bare_results.push(labelled);
+                } else {
+                    mod_results.push(labelled);
                 }
             }
             println!();
@@ -336,9 +1144,10 @@ async fn main() {
             let key = format!("{}: {}", theory.name, theory.label);
             report.theory_results.insert(key.clone(), bare_results);
             report.theory_modifier_results.insert(key, mod_results);
+            checkpoint.finish(&section, &report, &stats);
         }
 
-        report.print_theory_comparison();
+        report.print_theory_comparison(&config);
     }
 
     // ========== STEP 3: Deep chains ==========
@@ -359,13 +1168,13 @@ async fn main() {
             .cloned()
             .collect();
 
-        // Deduplicate by name, take top 15
+        // Deduplicate by name, take the top few
         let mut seen = std::collections::HashSet::new();
         let top_first_order: Vec<combine::CombineResult> = all_valid
             .iter()
             .filter(|(_, r)| seen.insert(r.name.clone()))
             .map(|(_, r)| r.clone())
-            .take(15)
+            .take(SECOND_ORDER_INPUTS)
             .collect();
 
         println!(
@@ -373,8 +1182,8 @@ async fn main() {
             top_first_order.len()
         );
 
-        // Get base elements from winning theory (or default to Classical)
-        let theories = all_theories();
+        // Get base elements from winning theory (or default to the first)
+        let theories = &config.theories;
         let winning_theory_name = report
             .winning_theory
             .as_deref()
@@ -389,26 +1198,28 @@ async fn main() {
             .elements;
 
         // Second-order: each first-order result + each base element
-        println!("--- Second-order ---");
-        for first_result in &top_first_order {
-            let result_card = Card::material(&first_result.name, &first_result.description);
-
-            for base in base_elements {
-                let label = format!("{} + {}", first_result.name, base.name);
-                let cards = vec![result_card.clone(), base.clone()];
-                let result = do_combine(
-                    &client,
-                    &mut cache,
-                    &cache_path,
-                    &cards,
-                    &label,
-                    &mut stats,
-                )
-                .await;
-                report.second_order_results.push((label, result));
+        if checkpoint.is_done("deep/second") {
+            println!("--- Second-order: done before resuming ---\n");
+        } else {
+            println!("--- Second-order ---");
+            let mut combos = Vec::new();
+            for first_result in &top_first_order {
+                let result_card = Card::material(&first_result.name, &first_result.description);
+
+                for base in base_elements {
+                    let label = format!("{} + {}", first_result.name, base.name);
+                    combos.push((label, vec![result_card.clone(), base.clone()]));
+                }
+            }
+            let labels: Vec<String> = combos.iter().map(|(label, _)| label.clone()).collect();
+            let results = combine_all(&client, &limit, &mut cache, combos, &mut stats).await;
+            if client.budget().is_spent() {
+                return stop_early(&client, &report, &config, &stats, &run);
             }
+            report.second_order_results.extend(labels.into_iter().zip(results));
+            println!();
+            checkpoint.finish("deep/second", &report, &stats);
         }
-        println!();
 
         // Collect top second-order for third-order
         let mut seen2 = std::collections::HashSet::new();
@@ -417,32 +1228,32 @@ async fn main() {
             .iter()
             .filter(|(_, r)| r.name != "Not possible" && seen2.insert(r.name.clone()))
             .map(|(_, r)| r.clone())
-            .take(10)
+            .take(THIRD_ORDER_INPUTS)
             .collect();
 
         // Third-order: top second-order × top first-order
-        if !top_second_order.is_empty() {
+        if checkpoint.is_done("deep/third") {
+            println!("--- Third-order: done before resuming ---\n");
+        } else if !top_second_order.is_empty() {
             println!("--- Third-order ---");
-            let first_top10: Vec<_> = top_first_order.iter().take(10).collect();
+            let first_top: Vec<_> = top_first_order.iter().take(THIRD_ORDER_INPUTS).collect();
+            let mut combos = Vec::new();
             for second in &top_second_order {
                 let s_card = Card::material(&second.name, &second.description);
-                for first in &first_top10 {
+                for first in &first_top {
                     let f_card = Card::material(&first.name, &first.description);
                     let label = format!("{} + {}", second.name, first.name);
-                    let cards = vec![s_card.clone(), f_card.clone()];
-                    let result = do_combine(
-                        &client,
-                        &mut cache,
-                        &cache_path,
-                        &cards,
-                        &label,
-                        &mut stats,
-                    )
-                    .await;
-                    report.third_order_results.push((label, result));
+                    combos.push((label, vec![s_card.clone(), f_card.clone()]));
                 }
             }
+            let labels: Vec<String> = combos.iter().map(|(label, _)| label.clone()).collect();
+            let results = combine_all(&client, &limit, &mut cache, combos, &mut stats).await;
+            if client.budget().is_spent() {
+                return stop_early(&client, &report, &config, &stats, &run);
+            }
+            report.third_order_results.extend(labels.into_iter().zip(results));
             println!();
+            checkpoint.finish("deep/third", &report, &stats);
         }
 
         report.print_deep_results();
@@ -454,42 +1265,98 @@ async fn main() {
 
         let all_names = report.all_result_names_with_desc();
 
-        // Limit to unique valid results
+        // Limit to unique valid results, less any scored before resuming
         let mut scored = std::collections::HashSet::new();
         let to_score: Vec<(String, String)> = all_names
             .into_iter()
             .filter(|(name, _)| scored.insert(name.clone()))
+            .filter(|(name, _)| !report.category_scores.contains_key(name))
             .collect();
 
-        println!("Scoring {} unique cards against {} categories...\n", to_score.len(), BOARD_CATEGORIES.len());
-
-        for (name, desc) in &to_score {
-            eprint!("  Scoring {name}...");
-            match client
-                .score_categories(name, desc, BOARD_CATEGORIES)
-                .await
-            {
-                Ok(scores) => {
-                    let top_cat = scores
-                        .iter()
-                        .max_by_key(|(_, &v)| v)
-                        .map(|(k, v)| format!("{k}={v}"))
-                        .unwrap_or_default();
-                    eprintln!(" done (best: {top_cat})");
-                    report.category_scores.insert(name.clone(), scores);
-                }
-                Err(e) => {
-                    eprintln!(" error: {e}");
+        println!(
+            "Scoring {} unique cards against {} categories...\n",
+            to_score.len(),
+            config.board_categories.len()
+        );
+
+        // Saved every few rounds of calls so a stop loses little scoring
+        let categories = Arc::new(config.board_categories.clone());
+        let mut progress = Progress::plain(to_score.len());
+        for chunk in to_score.chunks(cli.concurrency as usize * 4) {
+            let scored = score_all(&client, &limit, &categories, chunk, &mut progress).await;
+            for (name, scores) in scored {
+                match scores {
+                    Ok(scores) => {
+                        report.scoring_errors.remove(&name);
+                        report.category_scores.insert(name, scores);
+                    }
+                    Err(e) => {
+                        report.scoring_errors.insert(name, e);
+                    }
                 }
             }
+            checkpoint.save(&report, &stats);
+            if client.budget().is_spent() {
+                progress.finish();
+                return stop_early(&client, &report, &config, &stats, &run);
+            }
         }
+        progress.finish();
 
-        report.print_category_coverage();
+        report.print_category_coverage(&config);
+    }
+
+    // ========== STEP 5: Quality judging ==========
+    if cli.quality {
+        println!("\n=== STEP 5: Quality Judging ===\n");
+
+        let judge = match &cli.judge_model {
+            Some(model) => Arc::new(client.as_ref().clone().with_model(model)),
+            None => client.clone(),
+        };
+        let to_judge = quality::unjudged(&report);
+        println!(
+            "Judging {} results with {} on {}...\n",
+            to_judge.len(),
+            judge.model(),
+            combine::QUALITY_KEYS.join(", ")
+        );
+
+        let mut progress = Progress::plain(to_judge.len());
+        for chunk in to_judge.chunks(cli.concurrency as usize * 4) {
+            for (key, scores) in quality::judge_all(&judge, &limit, chunk, &mut progress).await {
+                report.quality_scores.insert(key, scores);
+            }
+            checkpoint.save(&report, &stats);
+            if client.budget().is_spent() {
+                progress.finish();
+                return stop_early(&client, &report, &config, &stats, &run);
+            }
+        }
+        progress.finish();
+
+        report.print_quality();
+    }
+
+    // ========== Card art for the best results ==========
+    if let Some(count) = cli.images {
+        println!("\n=== Generating images for the top {count} results ===\n");
+
+        let dir = run.path().join("images");
+        std::fs::create_dir_all(&dir).expect("failed to create images folder");
+        let mut previews = images::top_results(&report, count as usize);
+        images::generate(&client, &limit, &mut previews, &dir).await;
+        let gallery = images::write_gallery(&previews, &dir);
+        println!("\nPreviews written to {}", gallery.display());
+        if client.budget().is_spent() {
+            return stop_early(&client, &report, &config, &stats, &run);
+        }
     }
 
     // ========== Final output ==========
-    report.print_target_checklist();
-    report.write_to_file("explore/report.md");
+    report.print_convergence();
+    report.print_target_checklist(&config);
+    report.write_to_file(&run.file("report.md"), &config);
 
     println!(
         "\nDone! {} total calls ({} cached), {:.0}% valid",
@@ -501,4 +1368,36 @@ async fn main() {
             0.0
         }
     );
+    checkpoint.remove();
+    run.finish(&stats, client.budget());
+
+    // ========== Cards export ==========
+    if let Some(path) = &cli.export_cards {
+        let families = &config.modifier_families;
+        let family = report
+            .winning_family
+            .as_ref()
+            .and_then(|name| families.iter().find(|f| f.name == *name))
+            .unwrap_or_else(|| {
+                println!("\nNo winning modifier family this run; exporting the first");
+                &families[0]
+            });
+        let theory = report
+            .winning_theory
+            .as_deref()
+            .and_then(|key| {
+                config
+                    .theories
+                    .iter()
+                    .find(|t| format!("{}: {}", t.name, t.label) == key)
+            })
+            .unwrap_or_else(|| {
+                println!("\nNo winning element set this run; exporting the first");
+                &config.theories[0]
+            });
+        if let Err(e) = cards::write(path, theory, family) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
 }
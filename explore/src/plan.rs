@@ -0,0 +1,206 @@
+use crate::cache::Cache;
+use crate::checkpoint::Checkpoint;
+use crate::config::Config;
+use crate::report::Report;
+use crate::theories::{sample_pairs, Card, ModifierFamily};
+use crate::{Cli, SECOND_ORDER_INPUTS, THIRD_ORDER_INPUTS};
+use std::time::Duration;
+
+/// One section of a run and what it would cost.
+pub struct PlannedStep {
+    pub name: String,
+    pub calls: usize,
+    pub uncached: usize,
+    /// Depends on results not known yet, so `calls` is an upper bound and
+    /// every call is counted as uncached.
+    pub estimate: bool,
+    /// Finished before resuming.
+    pub done: bool,
+}
+
+/// The calls a run would make, without making any.
+pub struct Plan {
+    pub steps: Vec<PlannedStep>,
+    pub latency: Option<Duration>,
+    pub concurrency: u32,
+}
+
+impl Plan {
+    /// Plan the run `cli` asks for: `--sensory`, or steps 1 to 4.
+    pub fn build(
+        cli: &Cli,
+        config: &Config,
+        cache: &Cache,
+        checkpoint: &Checkpoint,
+        report: &Report,
+    ) -> Self {
+        let mut plan = Planner {
+            cli,
+            cache,
+            checkpoint,
+            steps: Vec::new(),
+        };
+
+        if cli.sensory {
+            if let Some(theory_g) = config.theory("G") {
+                plan.families("sensory", &theory_g.elements, &config.sensory_variations);
+            }
+        } else {
+            if cli.step.is_none() || cli.step == Some(1) {
+                plan.families("step1", config.baseline_elements(), &config.modifier_families);
+            }
+            if cli.step.is_none() || cli.step == Some(2) {
+                // Step 1 picks the modifier; until it has, assume the first family's
+                let families = &config.modifier_families;
+                let family = report
+                    .winning_family
+                    .as_ref()
+                    .and_then(|name| families.iter().find(|f| f.name == *name))
+                    .unwrap_or(&families[0]);
+                for theory in &config.theories {
+                    let mut combos = Vec::new();
+                    for (i, a) in theory.elements.iter().enumerate() {
+                        for b in &theory.elements[i + 1..] {
+                            combos.push(vec![a.clone(), b.clone()]);
+                            combos.push(vec![a.clone(), b.clone(), family.modifiers[0].clone()]);
+                        }
+                    }
+                    plan.exact(&format!("step2/{}", theory.name), &combos);
+                }
+            }
+            if cli.deep {
+                let elements = config
+                    .theories
+                    .iter()
+                    .map(|t| t.elements.len())
+                    .max()
+                    .unwrap_or(0);
+                plan.estimate("deep/second", SECOND_ORDER_INPUTS * elements);
+                plan.estimate("deep/third", THIRD_ORDER_INPUTS * THIRD_ORDER_INPUTS);
+            }
+        }
+
+        // Every new valid result is scored (and judged) once, but which are
+        // valid isn't known yet: assume each planned combine makes one
+        let results = plan.steps.iter().filter(|s| !s.done).map(|s| s.calls).sum();
+        if !cli.no_score {
+            plan.estimate("score", results);
+        }
+        if cli.quality {
+            plan.estimate("quality", results);
+        }
+
+        Self {
+            steps: plan.steps,
+            latency: cache.recent_latency(),
+            concurrency: cli.concurrency,
+        }
+    }
+
+    /// Print each step's calls, the totals and how long they'd take.
+    pub fn print(&self) {
+        println!("\n{}", "=".repeat(60));
+        println!("DRY RUN");
+        println!("{}\n", "=".repeat(60));
+
+        let w = self.steps.iter().map(|s| s.name.len()).max().unwrap_or(0).max(4);
+        println!("  {:<w$}  {:>10}  {:>9}", "Step", "Calls", "Uncached");
+        for step in &self.steps {
+            if step.done {
+                println!("  {:<w$}  {:>10}  {:>9}", step.name, "done", "-");
+            } else {
+                let mark = if step.estimate { "up to " } else { "" };
+                println!(
+                    "  {:<w$}  {:>10}  {:>9}",
+                    step.name,
+                    format!("{mark}{}", step.calls),
+                    step.uncached
+                );
+            }
+        }
+
+        let uncached: usize = self.steps.iter().filter(|s| !s.done).map(|s| s.uncached).sum();
+        let calls: usize = self.steps.iter().filter(|s| !s.done).map(|s| s.calls).sum();
+        println!("\n  {calls} calls, {uncached} of them uncached (at most)");
+        match self.latency {
+            Some(latency) => {
+                let wall = latency * uncached as u32 / self.concurrency;
+                println!(
+                    "  About {} at {} at a time, from a recent {:.1}s per call",
+                    duration(wall),
+                    self.concurrency,
+                    latency.as_secs_f64()
+                );
+            }
+            None => println!("  No recent calls recorded for this model to estimate time from"),
+        }
+        if self.steps.iter().any(|s| s.estimate && !s.done) {
+            println!("\n  Steps marked \"up to\" depend on earlier results.");
+        }
+    }
+}
+
+struct Planner<'a> {
+    cli: &'a Cli,
+    cache: &'a Cache,
+    checkpoint: &'a Checkpoint,
+    steps: Vec<PlannedStep>,
+}
+
+impl Planner<'_> {
+    /// Bare pairs, then each family's modifiers on every pair, as step 1 and
+    /// `--sensory` run them. Extra `--samples` are never cached.
+    fn families(&mut self, prefix: &str, elements: &[Card], families: &[ModifierFamily]) {
+        let pairs = sample_pairs(elements, self.cli.pairs, self.cli.pair_seed);
+        let bare: Vec<Vec<Card>> = pairs.iter().map(|(a, b)| vec![a.clone(), b.clone()]).collect();
+        self.exact(&format!("{prefix}/bare"), &bare);
+
+        let extra = self.cli.samples as usize - 1;
+        for family in families {
+            let mut combos = Vec::new();
+            for (a, b) in &pairs {
+                for modifier in &family.modifiers {
+                    combos.push(vec![a.clone(), b.clone(), modifier.clone()]);
+                }
+            }
+            let section = format!("{prefix}/{}", family.name);
+            self.exact(&section, &combos);
+            if let Some(step) = self.steps.last_mut() {
+                step.calls += combos.len() * extra;
+                step.uncached += combos.len() * extra;
+            }
+        }
+    }
+
+    /// Combines known up front, checked against the cache.
+    fn exact(&mut self, section: &str, combos: &[Vec<Card>]) {
+        let uncached = combos.iter().filter(|c| self.cache.get(c).is_none()).count();
+        self.steps.push(PlannedStep {
+            name: section.to_string(),
+            calls: combos.len(),
+            uncached,
+            estimate: false,
+            done: self.checkpoint.is_done(section),
+        });
+    }
+
+    fn estimate(&mut self, section: &str, calls: usize) {
+        self.steps.push(PlannedStep {
+            name: section.to_string(),
+            calls,
+            uncached: calls,
+            estimate: true,
+            done: self.checkpoint.is_done(section),
+        });
+    }
+}
+
+/// e.g. "2h 05m" or "3m 20s".
+fn duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
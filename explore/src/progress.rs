@@ -0,0 +1,84 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::time::Instant;
+
+/// A progress bar for one batch of calls, with cache-hit rate, validity,
+/// throughput and ETA. Hidden when stderr isn't a terminal.
+pub struct Progress {
+    bar: ProgressBar,
+    started: Instant,
+    cached: usize,
+    valid: usize,
+    /// Calls that went to Ollama, for throughput.
+    called: usize,
+    show_valid: bool,
+}
+
+impl Progress {
+    /// For combines, which can be cached and valid or not.
+    pub fn new(len: usize) -> Self {
+        Self::with(len, true)
+    }
+
+    /// For calls without a valid result to count, e.g. scoring.
+    pub fn plain(len: usize) -> Self {
+        Self::with(len, false)
+    }
+
+    fn with(len: usize, show_valid: bool) -> Self {
+        let bar = ProgressBar::new(len as u64);
+        bar.set_style(
+            ProgressStyle::with_template("  [{bar:30}] {pos}/{len} {msg}, ETA {eta}")
+                .expect("valid progress template")
+                .progress_chars("=> "),
+        );
+        Self {
+            bar,
+            started: Instant::now(),
+            cached: 0,
+            valid: 0,
+            called: 0,
+            show_valid,
+        }
+    }
+
+    /// Print a line above the bar.
+    pub fn println(&self, line: &str) {
+        self.bar.suspend(|| println!("{line}"));
+    }
+
+    /// Print a line to stderr above the bar.
+    pub fn eprintln(&self, line: &str) {
+        self.bar.suspend(|| eprintln!("{line}"));
+    }
+
+    /// Count one finished call.
+    pub fn tick(&mut self, cached: bool, valid: bool) {
+        if cached {
+            self.cached += 1;
+        } else {
+            self.called += 1;
+        }
+        if valid {
+            self.valid += 1;
+        }
+        let done = self.cached + self.called;
+
+        let mut parts = Vec::new();
+        if self.cached > 0 {
+            parts.push(format!("{:.0}% cached", self.cached as f64 / done as f64 * 100.0));
+        }
+        if self.show_valid {
+            parts.push(format!("{:.0}% valid", self.valid as f64 / done as f64 * 100.0));
+        }
+        let minutes = self.started.elapsed().as_secs_f64() / 60.0;
+        if self.called > 0 && minutes > 0.0 {
+            parts.push(format!("{:.1} calls/min", self.called as f64 / minutes));
+        }
+        self.bar.set_message(parts.join(", "));
+        self.bar.inc(1);
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish();
+    }
+}
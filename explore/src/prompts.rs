@@ -0,0 +1,236 @@
+use crate::cache::Cache;
+use crate::combine::{prompt_fingerprint, CombineResult, OllamaClient};
+use crate::config::Config;
+use crate::report::found_target_items;
+use crate::theories::Card;
+use crate::{combine_all, Stats};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// A combine system prompt to try, named after its file.
+pub struct PromptVariant {
+    pub name: String,
+    pub text: String,
+}
+
+impl PromptVariant {
+    /// The game's prompt, first, then one variant per file.
+    pub fn load(client: &OllamaClient, paths: &[PathBuf]) -> Result<Vec<Self>, String> {
+        let mut variants = vec![Self {
+            name: "game".to_string(),
+            text: client.system_prompt().to_string(),
+        }];
+        for path in paths {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read prompt {}: {e}", path.display()))?;
+            let name = path
+                .file_stem()
+                .map_or(path.display().to_string(), |s| s.to_string_lossy().into_owned());
+            if variants.iter().any(|v| v.name == name) {
+                return Err(format!("Two prompts named {name}; rename one of the files"));
+            }
+            variants.push(Self {
+                name,
+                text: text.trim().to_string(),
+            });
+        }
+        Ok(variants)
+    }
+}
+
+/// One prompt's results over the pair matrix, in pair order.
+pub struct PromptRun {
+    pub name: String,
+    pub fingerprint: String,
+    pub results: Vec<CombineResult>,
+    pub valid: usize,
+    pub unique: usize,
+    pub targets: Vec<String>,
+}
+
+/// Every prompt run over the same pairs; the first is the game's, which the
+/// others are compared against.
+pub struct PromptComparison {
+    pub pairs: Vec<String>,
+    pub runs: Vec<PromptRun>,
+    pub total_targets: usize,
+}
+
+/// Combine every pair under each prompt in turn, each cached under its own
+/// fingerprint in the cache at `cache_path`.
+pub async fn run(
+    client: &OllamaClient,
+    limit: &Arc<Semaphore>,
+    cache_path: &Path,
+    variants: Vec<PromptVariant>,
+    combos: Vec<(String, Vec<Card>)>,
+    config: &Config,
+    stats: &mut Stats,
+) -> Result<PromptComparison, String> {
+    let pairs: Vec<String> = combos.iter().map(|(label, _)| label.clone()).collect();
+    let mut runs = Vec::new();
+    for variant in variants {
+        let fingerprint = prompt_fingerprint(&variant.text);
+        println!("--- Prompt {} ({fingerprint}) ---", variant.name);
+        let client = Arc::new(client.clone().with_system_prompt(&variant.text));
+        let mut cache = Cache::open(cache_path, client.model(), &fingerprint)?;
+        let results = combine_all(&client, limit, &mut cache, combos.clone(), stats).await;
+        println!();
+        // Compared pair by pair, so a prompt the budget cut short is left out
+        if client.budget().is_spent() {
+            break;
+        }
+
+        let names: HashSet<String> = results
+            .iter()
+            .filter(|r| r.name != "Not possible")
+            .map(|r| r.name.clone())
+            .collect();
+        runs.push(PromptRun {
+            name: variant.name,
+            fingerprint,
+            valid: results.iter().filter(|r| r.name != "Not possible").count(),
+            unique: names.len(),
+            targets: found_target_items(&config.target_items, &names)
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            results,
+        });
+    }
+    if runs.is_empty() {
+        return Err(format!(
+            "Stopped at {} before the game prompt finished",
+            client.budget().describe()
+        ));
+    }
+    Ok(PromptComparison {
+        pairs,
+        runs,
+        total_targets: config.target_items.iter().map(|g| g.items.len()).sum(),
+    })
+}
+
+impl PromptComparison {
+    fn baseline(&self) -> &PromptRun {
+        &self.runs[0]
+    }
+
+    /// Pairs whose result name differs from the game prompt's.
+    fn changed(&self, run: &PromptRun) -> usize {
+        run.results
+            .iter()
+            .zip(&self.baseline().results)
+            .filter(|(a, b)| !a.name.eq_ignore_ascii_case(&b.name))
+            .count()
+    }
+
+    /// Targets this run found that the game prompt didn't, and the reverse.
+    fn target_changes<'a>(&'a self, run: &'a PromptRun) -> (Vec<&'a str>, Vec<&'a str>) {
+        let base = &self.baseline().targets;
+        let gained = run.targets.iter().filter(|t| !base.contains(t)).map(|t| t.as_str());
+        let lost = base.iter().filter(|t| !run.targets.contains(t)).map(|t| t.as_str());
+        (gained.collect(), lost.collect())
+    }
+
+    /// Print each prompt's metrics and how far they moved from the game's.
+    pub fn print(&self) {
+        println!("\n{}", "=".repeat(60));
+        println!("PROMPT COMPARISON");
+        println!("{}\n", "=".repeat(60));
+        println!("{} pairs per prompt, deltas against the game prompt\n", self.pairs.len());
+
+        println!(
+            "  {:<16}  {:>10}  {:>10}  {:>10}  {:>7}",
+            "Prompt", "Valid", "Unique", "Targets", "Changed"
+        );
+        let base = self.baseline();
+        for run in &self.runs {
+            println!(
+                "  {:<16}  {:>3} ({:>+4})  {:>3} ({:>+4})  {:>3} ({:>+4})  {:>7}",
+                run.name,
+                run.valid,
+                run.valid as i64 - base.valid as i64,
+                run.unique,
+                run.unique as i64 - base.unique as i64,
+                run.targets.len(),
+                run.targets.len() as i64 - base.targets.len() as i64,
+                self.changed(run)
+            );
+        }
+
+        for run in &self.runs[1..] {
+            let (gained, lost) = self.target_changes(run);
+            if !gained.is_empty() {
+                println!("\n  {} finds: {}", run.name, gained.join(", "));
+            }
+            if !lost.is_empty() {
+                println!("  {} misses: {}", run.name, lost.join(", "));
+            }
+        }
+    }
+
+    /// Write the metrics table, target changes and every pair's result under
+    /// each prompt as markdown.
+    pub fn write_to_file(&self, path: &str) {
+        let mut f = std::fs::File::create(path).expect("failed to create prompt report file");
+
+        writeln!(f, "# Prompt Comparison Report\n").unwrap();
+        writeln!(
+            f,
+            "{} pairs per prompt; deltas are against the game prompt.\n",
+            self.pairs.len()
+        )
+        .unwrap();
+        writeln!(f, "| Prompt | Fingerprint | Valid | Unique | Targets | Changed |").unwrap();
+        writeln!(f, "|--------|-------------|-------|--------|---------|---------|").unwrap();
+        let base = self.baseline();
+        for run in &self.runs {
+            writeln!(
+                f,
+                "| {} | {} | {} ({:+}) | {} ({:+}) | {}/{} ({:+}) | {} |",
+                run.name,
+                run.fingerprint,
+                run.valid,
+                run.valid as i64 - base.valid as i64,
+                run.unique,
+                run.unique as i64 - base.unique as i64,
+                run.targets.len(),
+                self.total_targets,
+                run.targets.len() as i64 - base.targets.len() as i64,
+                self.changed(run)
+            )
+            .unwrap();
+        }
+        writeln!(
+            f,
+            "\nChanged counts the pairs whose result differs from the game prompt's."
+        )
+        .unwrap();
+
+        writeln!(f, "\n## Targets\n").unwrap();
+        for run in &self.runs[1..] {
+            let (gained, lost) = self.target_changes(run);
+            let list = |items: &[&str]| match items {
+                [] => "none".to_string(),
+                _ => items.join(", "),
+            };
+            writeln!(f, "- **{}** finds {}; misses {}", run.name, list(&gained), list(&lost))
+                .unwrap();
+        }
+
+        writeln!(f, "\n## By Pair\n").unwrap();
+        let names: Vec<&str> = self.runs.iter().map(|r| r.name.as_str()).collect();
+        writeln!(f, "| Pair | {} |", names.join(" | ")).unwrap();
+        writeln!(f, "|------|{}", "------|".repeat(names.len())).unwrap();
+        for (i, pair) in self.pairs.iter().enumerate() {
+            let results: Vec<&str> = self.runs.iter().map(|r| r.results[i].name.as_str()).collect();
+            writeln!(f, "| {pair} | {} |", results.join(" | ")).unwrap();
+        }
+
+        println!("\nPrompt report written to {path}");
+    }
+}
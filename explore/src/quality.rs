@@ -0,0 +1,235 @@
+use crate::budget;
+use crate::combine::{OllamaClient, QUALITY_KEYS};
+use crate::progress::Progress;
+use crate::report::Report;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Best and worst results listed in the report.
+const LISTED: usize = 5;
+
+/// A valid result to judge, with the inputs that made it.
+pub struct Judgeable {
+    pub key: String,
+    pub inputs: String,
+    pub name: String,
+    pub description: String,
+}
+
+/// Each valid result in the report that isn't judged yet, once.
+pub fn unjudged(report: &Report) -> Vec<Judgeable> {
+    let mut seen = HashSet::new();
+    let mut todo = Vec::new();
+    for (_, results) in report.result_groups() {
+        for (inputs, result) in results {
+            let key = key(&inputs, &result.name);
+            if result.name == "Not possible"
+                || report.quality_scores.contains_key(&key)
+                || !seen.insert(key.clone())
+            {
+                continue;
+            }
+            todo.push(Judgeable {
+                key,
+                inputs,
+                name: result.name.clone(),
+                description: result.description.clone(),
+            });
+        }
+    }
+    todo
+}
+
+/// Judged results are stored per inputs and name, since fit depends on both.
+fn key(inputs: &str, name: &str) -> String {
+    format!("{inputs} = {name}")
+}
+
+/// Judge each result with at most `limit` Ollama calls in flight, logging
+/// and returning the scores in order. Results that fail to judge are left
+/// out.
+pub async fn judge_all(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    results: &[Judgeable],
+    progress: &mut Progress,
+) -> Vec<(String, HashMap<String, u32>)> {
+    let calls: Vec<_> = results
+        .iter()
+        .map(|r| {
+            let (client, limit) = (client.clone(), limit.clone());
+            let (inputs, name, desc) = (r.inputs.clone(), r.name.clone(), r.description.clone());
+            tokio::spawn(async move {
+                let _permit = limit.acquire_owned().await.map_err(|e| e.to_string())?;
+                client.score_quality(&inputs, &name, &desc).await
+            })
+        })
+        .collect();
+
+    let mut judged = Vec::with_capacity(calls.len());
+    for (result, call) in results.iter().zip(calls) {
+        let scores = call.await.map_err(|e| e.to_string()).and_then(|r| r);
+        match &scores {
+            Ok(scores) => {
+                let line: Vec<String> = QUALITY_KEYS
+                    .iter()
+                    .map(|k| format!("{k}={}", scores.get(*k).copied().unwrap_or(0)))
+                    .collect();
+                progress.eprintln(&format!("  Judging {}... {}", result.key, line.join(" ")));
+            }
+            Err(e) if *e == budget::SPENT => continue,
+            Err(e) => progress.eprintln(&format!("  Judging {}... error: {e}", result.key)),
+        }
+        progress.tick(false, scores.is_ok());
+        if let Ok(scores) = scores {
+            judged.push((result.key.clone(), scores));
+        }
+    }
+    judged
+}
+
+/// Mean judged scores over one group of results.
+struct GroupQuality {
+    group: String,
+    judged: usize,
+    /// Per key, in `QUALITY_KEYS` order.
+    means: Vec<f64>,
+}
+
+impl GroupQuality {
+    fn overall(&self) -> f64 {
+        self.means.iter().sum::<f64>() / self.means.len() as f64
+    }
+}
+
+fn mean_score(scores: &HashMap<String, u32>) -> f64 {
+    let total: u32 = QUALITY_KEYS.iter().map(|k| scores.get(*k).copied().unwrap_or(0)).sum();
+    total as f64 / QUALITY_KEYS.len() as f64
+}
+
+/// Each result group's means, over the results in it that were judged.
+fn by_group(report: &Report) -> Vec<GroupQuality> {
+    report
+        .result_groups()
+        .into_iter()
+        .filter_map(|(group, results)| {
+            let mut seen = HashSet::new();
+            let scores: Vec<&HashMap<String, u32>> = results
+                .iter()
+                .map(|(inputs, r)| key(inputs, &r.name))
+                .filter(|key| seen.insert(key.clone()))
+                .filter_map(|key| report.quality_scores.get(&key))
+                .collect();
+            if scores.is_empty() {
+                return None;
+            }
+            let means = QUALITY_KEYS
+                .iter()
+                .map(|k| {
+                    let total: u32 = scores.iter().map(|s| s.get(*k).copied().unwrap_or(0)).sum();
+                    total as f64 / scores.len() as f64
+                })
+                .collect();
+            Some(GroupQuality {
+                group,
+                judged: scores.len(),
+                means,
+            })
+        })
+        .collect()
+}
+
+/// Every judged result by mean score, best first.
+fn ranked(report: &Report) -> Vec<(&str, f64)> {
+    let mut ranked: Vec<(&str, f64)> = report
+        .quality_scores
+        .iter()
+        .map(|(key, scores)| (key.as_str(), mean_score(scores)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    ranked
+}
+
+/// Print each group's mean scores, then the best and worst results.
+pub fn print(report: &Report) {
+    let groups = by_group(report);
+    if groups.is_empty() {
+        return;
+    }
+
+    println!("\n{}", "=".repeat(60));
+    println!("RESULT QUALITY");
+    println!("{}\n", "=".repeat(60));
+
+    let w = groups.iter().map(|g| g.group.len()).max().unwrap_or(0).max(5);
+    print!("  {:<w$}  {:>6}", "Group", "Judged");
+    for k in QUALITY_KEYS {
+        print!("  {:>w$}", k, w = k.len().max(4));
+    }
+    println!("  {:>7}", "Overall");
+    for g in &groups {
+        print!("  {:<w$}  {:>6}", g.group, g.judged);
+        for (k, mean) in QUALITY_KEYS.iter().zip(&g.means) {
+            print!("  {:>w$.1}", mean, w = k.len().max(4));
+        }
+        println!("  {:>7.1}", g.overall());
+    }
+
+    let ranked = ranked(report);
+    let listed = LISTED.min(ranked.len() / 2);
+    if listed > 0 {
+        println!("\n  Best:");
+        for (key, mean) in &ranked[..listed] {
+            println!("    {mean:.1}  {key}");
+        }
+        println!("  Worst:");
+        for (key, mean) in ranked[ranked.len() - listed..].iter().rev() {
+            println!("    {mean:.1}  {key}");
+        }
+    }
+}
+
+/// Write the per-group means and the best and worst results as a report
+/// section.
+pub fn write(f: &mut impl Write, report: &Report) {
+    let groups = by_group(report);
+    if groups.is_empty() {
+        return;
+    }
+
+    writeln!(f, "\n## Result Quality\n").unwrap();
+    writeln!(
+        f,
+        "Mean 1-10 scores from the judge model over each group's valid results.\n"
+    )
+    .unwrap();
+    writeln!(f, "| Group | Judged | {} | Overall |", QUALITY_KEYS.join(" | ")).unwrap();
+    writeln!(f, "|-------|--------|{}---------|", "------|".repeat(QUALITY_KEYS.len())).unwrap();
+    for g in &groups {
+        let means: Vec<String> = g.means.iter().map(|m| format!("{m:.1}")).collect();
+        writeln!(
+            f,
+            "| {} | {} | {} | {:.1} |",
+            g.group,
+            g.judged,
+            means.join(" | "),
+            g.overall()
+        )
+        .unwrap();
+    }
+
+    let ranked = ranked(report);
+    let listed = LISTED.min(ranked.len() / 2);
+    if listed > 0 {
+        writeln!(f, "\n### Best\n").unwrap();
+        for (key, mean) in &ranked[..listed] {
+            writeln!(f, "- {mean:.1}: {key}").unwrap();
+        }
+        writeln!(f, "\n### Worst\n").unwrap();
+        for (key, mean) in ranked[ranked.len() - listed..].iter().rev() {
+            writeln!(f, "- {mean:.1}: {key}").unwrap();
+        }
+    }
+}
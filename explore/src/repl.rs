@@ -0,0 +1,263 @@
+use crate::cache::Cache;
+use crate::combine::{CombineResult, OllamaClient};
+use crate::config::Config;
+use crate::theories::{Card, CardKind};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+const HELP: &str = "\
+  Fire + Clay            combine cards (a result from earlier works as a card too)
+  Fire + Clay [Hollow]   with a modifier
+  :cache [text]          cache size, and cached recipes mentioning text
+  :pin                   pin the last result
+  :pins                  list pinned results
+  :unpin N               unpin the Nth pinned result
+  :export [path]         write this session to markdown (default explore/repl.md)
+  :help                  this list
+  :quit                  leave (or Ctrl-D)";
+
+/// A favorite result, kept across sessions.
+#[derive(Serialize, Deserialize)]
+struct Pin {
+    recipe: String,
+    name: String,
+    description: String,
+}
+
+/// One combine typed this session.
+struct Entry {
+    recipe: String,
+    result: Result<CombineResult, String>,
+    cached: bool,
+}
+
+/// Read combines from stdin and answer each from the cache, or Ollama when
+/// it isn't cached yet. Cards are looked up by name among the config's
+/// elements and modifiers, this session's results and the cache; anything
+/// else is a material described by its name alone.
+pub async fn run(client: &OllamaClient, cache: &mut Cache, config: &Config, pins_path: &Path) {
+    let mut cards: HashMap<String, Card> = HashMap::new();
+    let families = config.modifier_families.iter().chain(&config.sensory_variations);
+    for card in config
+        .theories
+        .iter()
+        .flat_map(|t| &t.elements)
+        .chain(families.flat_map(|f| &f.modifiers))
+    {
+        cards.entry(card.name.to_lowercase()).or_insert_with(|| card.clone());
+    }
+    let mut pins: Vec<Pin> = std::fs::read_to_string(pins_path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default();
+    let mut transcript: Vec<Entry> = Vec::new();
+
+    println!("Type a combination like Fire + Clay [Hollow], or :help\n");
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush().unwrap();
+        let Some(Ok(line)) = lines.next() else {
+            println!();
+            break;
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+        match command {
+            ":quit" | ":q" => break,
+            ":help" => println!("{HELP}"),
+            ":cache" => print_cache(cache, arg),
+            ":pin" => {
+                let last = transcript.iter().rev().find_map(|e| Some((e, e.result.as_ref().ok()?)));
+                match last {
+                    Some((entry, result)) => {
+                        println!("  pinned {}", result.name);
+                        pins.push(Pin {
+                            recipe: entry.recipe.clone(),
+                            name: result.name.clone(),
+                            description: result.description.clone(),
+                        });
+                        save_pins(&pins, pins_path);
+                    }
+                    None => println!("  nothing to pin yet"),
+                }
+            }
+            ":pins" => {
+                if pins.is_empty() {
+                    println!("  no pins");
+                }
+                for (i, pin) in pins.iter().enumerate() {
+                    println!("  {}. {} = {} — {}", i + 1, pin.recipe, pin.name, pin.description);
+                }
+            }
+            ":unpin" => match arg.parse::<usize>() {
+                Ok(n) if (1..=pins.len()).contains(&n) => {
+                    let pin = pins.remove(n - 1);
+                    println!("  unpinned {}", pin.name);
+                    save_pins(&pins, pins_path);
+                }
+                _ => println!("  usage: :unpin N, with N from :pins"),
+            },
+            ":export" => {
+                let path = if arg.is_empty() { "explore/repl.md" } else { arg };
+                match write_transcript(&transcript, &pins, path) {
+                    Ok(()) => println!("  session written to {path}"),
+                    Err(e) => println!("  {e}"),
+                }
+            }
+            _ if command.starts_with(':') => println!("  unknown command {command}; try :help"),
+            _ => {
+                let entry = match parse(line, &cards, cache) {
+                    Ok(input) => combine(client, cache, line, &input).await,
+                    Err(e) => {
+                        println!("  {e}");
+                        continue;
+                    }
+                };
+                match &entry.result {
+                    Ok(result) => {
+                        let marker = if entry.cached { " (cached)" } else { "" };
+                        println!("  = {} — {}{marker}", result.name, result.description);
+                        if result.name != "Not possible" {
+                            cards.insert(
+                                result.name.to_lowercase(),
+                                Card::material(&result.name, &result.description),
+                            );
+                        }
+                    }
+                    Err(e) => println!("  ERROR: {e}"),
+                }
+                transcript.push(entry);
+            }
+        }
+    }
+}
+
+/// Turn `A + B [Modifier]` into cards.
+fn parse(line: &str, cards: &HashMap<String, Card>, cache: &Cache) -> Result<Vec<Card>, String> {
+    let (materials, modifier) = match line.split_once('[') {
+        Some((materials, rest)) => {
+            let modifier = rest
+                .strip_suffix(']')
+                .filter(|m| !m.contains(['[', ']']))
+                .ok_or("one modifier goes last, in brackets: Fire + Clay [Hollow]")?;
+            (materials, Some(modifier.trim()))
+        }
+        None => (line, None),
+    };
+
+    if materials.trim().is_empty() {
+        return Err("at least one material is needed".to_string());
+    }
+    let mut input = Vec::new();
+    for name in materials.split('+').map(str::trim) {
+        if name.is_empty() {
+            return Err("empty card name; write cards as Fire + Clay".to_string());
+        }
+        let card = match cards.get(&name.to_lowercase()) {
+            Some(card) => card.clone(),
+            None => {
+                let description = cache.describe(name).unwrap_or_else(|| name.to_string());
+                Card::material(name, &description)
+            }
+        };
+        input.push(card);
+    }
+    if let Some(name) = modifier {
+        let card = match cards.get(&name.to_lowercase()) {
+            Some(card) if card.kind == CardKind::Intent => card.clone(),
+            _ => Card::intent(name, &name.to_lowercase()),
+        };
+        input.push(card);
+    }
+
+    let intents = input.iter().filter(|c| c.kind == CardKind::Intent).count();
+    if input.len() - intents < 1 {
+        return Err("at least one material is needed".to_string());
+    }
+    if intents > 1 {
+        return Err("at most one modifier is allowed".to_string());
+    }
+    Ok(input)
+}
+
+async fn combine(client: &OllamaClient, cache: &mut Cache, recipe: &str, cards: &[Card]) -> Entry {
+    if let Some(result) = cache.get(cards) {
+        return Entry {
+            recipe: recipe.to_string(),
+            result: Ok(result),
+            cached: true,
+        };
+    }
+    let result = client.combine(cards).await;
+    if let Ok(result) = &result {
+        cache.insert(cards, result);
+    }
+    Entry {
+        recipe: recipe.to_string(),
+        result,
+        cached: false,
+    }
+}
+
+fn print_cache(cache: &Cache, filter: &str) {
+    println!("  {} cached results", cache.len());
+    if filter.is_empty() {
+        return;
+    }
+    let filter = filter.to_lowercase();
+    let mut matches: Vec<String> = cache
+        .recipes()
+        .into_iter()
+        .filter(|(materials, modifier, result)| {
+            materials.iter().chain(modifier).any(|m| m.contains(&filter))
+                || result.name.to_lowercase().contains(&filter)
+        })
+        .map(|(materials, modifier, result)| {
+            let modifier = modifier.map(|m| format!(" [{m}]")).unwrap_or_default();
+            format!("{}{modifier} = {}", materials.join(" + "), result.name)
+        })
+        .collect();
+    matches.sort();
+    for recipe in matches.iter().take(20) {
+        println!("  {recipe}");
+    }
+    if matches.len() > 20 {
+        println!("  ... and {} more", matches.len() - 20);
+    }
+}
+
+fn save_pins(pins: &[Pin], path: &Path) {
+    let data = serde_json::to_string_pretty(pins).expect("failed to serialize pins");
+    if let Err(e) = std::fs::write(path, data) {
+        println!("  failed to save pins to {}: {e}", path.display());
+    }
+}
+
+fn write_transcript(transcript: &[Entry], pins: &[Pin], path: &str) -> Result<(), String> {
+    let mut out = String::from("# Explore Session\n\n");
+    for entry in transcript {
+        let line = match &entry.result {
+            Ok(r) => {
+                let cached = if entry.cached { " (cached)" } else { "" };
+                format!("- {} = **{}** — {}{cached}\n", entry.recipe, r.name, r.description)
+            }
+            Err(e) => format!("- {} = error: {e}\n", entry.recipe),
+        };
+        out.push_str(&line);
+    }
+    if !pins.is_empty() {
+        out.push_str("\n## Pinned\n\n");
+        for pin in pins {
+            out.push_str(&format!("- {} = **{}** — {}\n", pin.recipe, pin.name, pin.description));
+        }
+    }
+    std::fs::write(path, out).map_err(|e| format!("Failed to write {path}: {e}"))
+}
@@ -1,9 +1,15 @@
 use crate::combine::CombineResult;
-use crate::theories::{BOARD_CATEGORIES, TARGET_ITEMS};
-use std::collections::{HashMap, HashSet};
+use crate::config::Config;
+use crate::convergence;
+use crate::quality;
+use crate::significance::{self, FamilySample};
+use crate::theories::TargetGroup;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Write;
 
 /// Tracks all results for reporting.
+#[derive(Serialize, Deserialize)]
 pub struct Report {
     /// Step 1 results: family_name -> [(pair_label, modifier_name, result)]
     pub modifier_results: HashMap<String, Vec<(String, String, CombineResult)>>,
@@ -19,6 +25,12 @@ pub struct Report {
     pub third_order_results: Vec<(String, CombineResult)>,
     /// Category scores: card_name -> { category -> score }
     pub category_scores: HashMap<String, HashMap<String, u32>>,
+    /// Cards that still failed to score after retrying: card_name -> error
+    #[serde(default)]
+    pub scoring_errors: BTreeMap<String, String>,
+    /// Judged quality: "inputs = name" -> { quality key -> score }
+    #[serde(default)]
+    pub quality_scores: HashMap<String, HashMap<String, u32>>,
     /// Winning modifier family from step 1
     pub winning_family: Option<String>,
     /// Winning theory from step 2
@@ -35,6 +47,8 @@ impl Report {
             second_order_results: Vec::new(),
             third_order_results: Vec::new(),
             category_scores: HashMap::new(),
+            scoring_errors: BTreeMap::new(),
+            quality_scores: HashMap::new(),
             winning_family: None,
             winning_theory: None,
         }
@@ -46,7 +60,7 @@ impl Report {
         println!("STEP 1: MODIFIER FAMILY COMPARISON");
         println!("{}\n", "=".repeat(60));
 
-        let mut family_scores: Vec<(&str, usize, usize, f64)> = Vec::new();
+        let mut samples: Vec<FamilySample> = Vec::new();
 
         // Print bare results first
         println!("--- Bare pairs (no modifier) ---");
@@ -73,6 +87,7 @@ impl Report {
             let mut valid = 0;
             let mut unique_names = HashSet::new();
             let mut differentiation_groups: HashMap<String, HashSet<String>> = HashMap::new();
+            let mut by_pair: HashMap<String, Vec<(bool, String)>> = HashMap::new();
 
             for (pair, modifier, result) in results {
                 let is_valid = result.name != "Not possible";
@@ -84,6 +99,10 @@ impl Report {
                     .entry(pair.clone())
                     .or_default()
                     .insert(result.name.clone());
+                by_pair
+                    .entry(pair.clone())
+                    .or_default()
+                    .push((is_valid, result.name.clone()));
 
                 let marker = if is_valid { "+" } else { "-" };
                 println!(
@@ -108,38 +127,70 @@ impl Report {
                 unique_names.len()
             );
 
-            family_scores.push((
-                // Leaking is fine — these are static-lifetime strings in practice
-                Box::leak(family_name.clone().into_boxed_str()),
-                valid,
-                unique_names.len(),
-                diff_score,
-            ));
+            samples.push(FamilySample {
+                name: family_name.clone(),
+                pairs: by_pair,
+            });
         }
 
-        // Pick winner: highest (valid + unique + differentiation)
-        family_scores.sort_by(|a, b| {
-            let score_a = a.1 as f64 + a.2 as f64 + a.3;
-            let score_b = b.1 as f64 + b.2 as f64 + b.3;
-            score_b.partial_cmp(&score_a).unwrap()
-        });
-
-        println!("MODIFIER RANKING:");
-        for (i, (name, valid, unique, diff)) in family_scores.iter().enumerate() {
+        // Pick winner: highest (valid + unique + differentiation), with
+        // intervals showing whether it's really ahead
+        let comparison = significance::compare(samples);
+        println!(
+            "MODIFIER RANKING (95% bootstrap intervals over {} pairs):",
+            comparison.pairs
+        );
+        for (i, family) in comparison.families.iter().enumerate() {
+            let m = &family.metrics;
             let marker = if i == 0 { " <-- WINNER" } else { "" };
             println!(
-                "  {}. {name}: valid={valid}, unique={unique}, diff={diff:.1}{marker}",
-                i + 1
+                "  {}. {}: valid={} ({:.0}% [{:.0}%, {:.0}%]), unique={}, diff={:.1}, \
+                 score={:.1} [{:.1}, {:.1}]{marker}",
+                i + 1,
+                family.name,
+                m.valid,
+                m.validity() * 100.0,
+                family.validity_ci.0 * 100.0,
+                family.validity_ci.1 * 100.0,
+                m.unique,
+                m.diff,
+                m.score(),
+                family.score_ci.0,
+                family.score_ci.1
             );
         }
 
-        if let Some((winner, _, _, _)) = family_scores.first() {
-            self.winning_family = Some(winner.to_string());
+        if let Some(chi) = &comparison.chi_square {
+            let verdict = if chi.p < 0.05 { "differ" } else { "don't clearly differ" };
+            println!(
+                "\nValidity rates {verdict} across families: chi²={:.2}, df={}, p={:.3}",
+                chi.stat, chi.df, chi.p
+            );
+        }
+        if let Some(winner) = comparison.families.first() {
+            if !comparison.versus.is_empty() {
+                println!("{} vs the rest (score difference, 95% interval):", winner.name);
+            }
+            for other in &comparison.versus {
+                let verdict = if other.significant() { "significant" } else { "not significant" };
+                println!(
+                    "  vs {}: {:+.1} [{:+.1}, {:+.1}], ahead in {:.0}% of resamples — {verdict}",
+                    other.name,
+                    other.diff,
+                    other.ci.0,
+                    other.ci.1,
+                    other.leads * 100.0
+                );
+            }
+            if comparison.versus.iter().any(|v| !v.significant()) {
+                println!("  The winner isn't clearly ahead of every family; try more --samples");
+            }
+            self.winning_family = Some(winner.name.clone());
         }
     }
 
     /// Compute element theory metrics and print comparison.
-    pub fn print_theory_comparison(&mut self) {
+    pub fn print_theory_comparison(&mut self, config: &Config) {
         println!("\n{}", "=".repeat(60));
         println!("STEP 2: ELEMENT SET COMPARISON");
         println!("{}\n", "=".repeat(60));
@@ -192,7 +243,7 @@ impl Report {
                 unique_names.len()
             );
 
-            let target_found = count_target_items(&unique_names);
+            let target_found = count_target_items(&config.target_items, &unique_names);
             theory_scores.push((
                 theory_name.clone(),
                 valid,
@@ -270,16 +321,16 @@ impl Report {
     }
 
     /// Print target items checklist.
-    pub fn print_target_checklist(&self) {
+    pub fn print_target_checklist(&self, config: &Config) {
         println!("\n{}", "=".repeat(60));
         println!("TARGET ITEMS CHECKLIST");
         println!("{}\n", "=".repeat(60));
 
         let all_names: HashSet<String> = self.all_result_names();
 
-        for (category, items) in TARGET_ITEMS {
+        for TargetGroup { category, items } in &config.target_items {
             println!("  {category}:");
-            for item in *items {
+            for item in items {
                 let found = all_names.iter().any(|n| {
                     n.eq_ignore_ascii_case(item) || n.to_lowercase().contains(&item.to_lowercase())
                 });
@@ -289,8 +340,27 @@ impl Report {
         }
     }
 
-    /// Print category coverage summary.
-    pub fn print_category_coverage(&self) {
+    /// Print which input sets converge on the same results, per group.
+    pub fn print_convergence(&self) {
+        convergence::print(&convergence::analyze(self));
+    }
+
+    /// Print judged quality per group, and the best and worst results.
+    pub fn print_quality(&self) {
+        quality::print(self);
+    }
+
+    /// Print category coverage summary, and the cards that failed to score.
+    pub fn print_category_coverage(&self, config: &Config) {
+        if !self.scoring_errors.is_empty() {
+            let names: Vec<&str> = self.scoring_errors.keys().map(|n| n.as_str()).collect();
+            let more = if names.len() > 10 { ", ... (see the report)" } else { "" };
+            println!(
+                "\n{} cards failed to score and are left out: {}{more}",
+                names.len(),
+                names[..names.len().min(10)].join(", ")
+            );
+        }
         if self.category_scores.is_empty() {
             return;
         }
@@ -300,10 +370,10 @@ impl Report {
         println!("{}\n", "=".repeat(60));
 
         // For each category, find the best-scoring card
-        for cat in BOARD_CATEGORIES {
+        for cat in &config.board_categories {
             let mut best: Option<(&str, u32)> = None;
             for (card_name, scores) in &self.category_scores {
-                if let Some(&score) = scores.get(*cat) {
+                if let Some(&score) = scores.get(cat) {
                     if best.is_none() || score > best.unwrap().1 {
                         best = Some((card_name, score));
                     }
@@ -320,7 +390,7 @@ impl Report {
     }
 
     /// Write full report to file.
-    pub fn write_to_file(&self, path: &str) {
+    pub fn write_to_file(&self, path: &str, config: &Config) {
         let mut f = std::fs::File::create(path).expect("failed to create report file");
 
         writeln!(f, "# Explore Report\n").unwrap();
@@ -335,9 +405,9 @@ impl Report {
         // Target checklist
         writeln!(f, "\n## Target Items\n").unwrap();
         let all_names = self.all_result_names();
-        for (category, items) in TARGET_ITEMS {
+        for TargetGroup { category, items } in &config.target_items {
             writeln!(f, "### {category}").unwrap();
-            for item in *items {
+            for item in items {
                 let found = all_names.iter().any(|n| {
                     n.eq_ignore_ascii_case(item) || n.to_lowercase().contains(&item.to_lowercase())
                 });
@@ -351,10 +421,10 @@ impl Report {
             writeln!(f, "\n## Category Coverage\n").unwrap();
             writeln!(f, "| Category | Best Score | Best Card |").unwrap();
             writeln!(f, "|----------|-----------|-----------|").unwrap();
-            for cat in BOARD_CATEGORIES {
+            for cat in &config.board_categories {
                 let mut best: Option<(&str, u32)> = None;
                 for (card_name, scores) in &self.category_scores {
-                    if let Some(&score) = scores.get(*cat) {
+                    if let Some(&score) = scores.get(cat) {
                         if best.is_none() || score > best.unwrap().1 {
                             best = Some((card_name, score));
                         }
@@ -366,10 +436,61 @@ impl Report {
                 }
             }
         }
+        if !self.scoring_errors.is_empty() {
+            writeln!(f, "\n### Scoring Errors\n").unwrap();
+            writeln!(f, "Left out of the coverage above.\n").unwrap();
+            writeln!(f, "| Card | Error |").unwrap();
+            writeln!(f, "|------|-------|").unwrap();
+            for (name, error) in &self.scoring_errors {
+                // Errors may carry HTTP bodies; keep them to one table cell
+                let error = error.split_whitespace().collect::<Vec<_>>().join(" ");
+                writeln!(f, "| {name} | {} |", error.replace('|', "\\|")).unwrap();
+            }
+        }
+
+        quality::write(&mut f, self);
+        convergence::write(&mut f, &convergence::analyze(self));
 
         println!("\nReport written to {path}");
     }
 
+    /// Every result by group as (group, [(inputs, result)]): the bare pairs,
+    /// each modifier family, each element theory (with and without its
+    /// modifier) and the deep chains, in that order.
+    pub fn result_groups(&self) -> Vec<(String, Vec<(String, &CombineResult)>)> {
+        let mut groups = Vec::new();
+        if !self.bare_results.is_empty() {
+            let results = self.bare_results.iter().map(|(pair, r)| (pair.clone(), r));
+            groups.push(("Bare pairs".to_string(), results.collect()));
+        }
+
+        let families: BTreeMap<_, _> = self.modifier_results.iter().collect();
+        for (family, results) in families {
+            let results = results
+                .iter()
+                .map(|(pair, modifier, r)| (format!("{pair} [{modifier}]"), r));
+            groups.push((format!("Family: {family}"), results.collect()));
+        }
+
+        let theories: BTreeMap<_, _> = self.theory_results.iter().collect();
+        for (theory, results) in theories {
+            let modified = self.theory_modifier_results.get(theory).into_iter().flatten();
+            let results = results.iter().chain(modified).map(|(label, r)| (label.clone(), r));
+            groups.push((format!("Theory {theory}"), results.collect()));
+        }
+
+        for (group, results) in [
+            ("Second-order", &self.second_order_results),
+            ("Third-order", &self.third_order_results),
+        ] {
+            if !results.is_empty() {
+                let results = results.iter().map(|(label, r)| (label.clone(), r));
+                groups.push((group.to_string(), results.collect()));
+            }
+        }
+        groups
+    }
+
     /// Returns all valid result (name, description) pairs for scoring.
     pub fn all_result_names_with_desc(&self) -> Vec<(String, String)> {
         let mut results = Vec::new();
@@ -447,16 +568,21 @@ impl Report {
     }
 }
 
-fn count_target_items(names: &HashSet<String>) -> usize {
-    let mut count = 0;
-    for (_, items) in TARGET_ITEMS {
-        for item in *items {
+fn count_target_items(targets: &[TargetGroup], names: &HashSet<String>) -> usize {
+    found_target_items(targets, names).len()
+}
+
+/// The target items some name matches, in config order.
+pub fn found_target_items<'a>(targets: &'a [TargetGroup], names: &HashSet<String>) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    for group in targets {
+        for item in &group.items {
             if names.iter().any(|n| {
                 n.eq_ignore_ascii_case(item) || n.to_lowercase().contains(&item.to_lowercase())
             }) {
-                count += 1;
+                found.push(item.as_str());
             }
         }
     }
-    count
+    found
 }
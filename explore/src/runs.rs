@@ -0,0 +1,162 @@
+use crate::budget::Budget;
+use crate::combine::Sampling;
+use crate::config::Config;
+use crate::Stats;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One combine as a run got it, cached or not.
+#[derive(Serialize, Deserialize)]
+pub struct RawResult {
+    pub label: String,
+    pub name: String,
+    pub description: String,
+    pub cached: bool,
+    /// Set for sampled calls, e.g. `--samples` or `--sweep`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<Sampling>,
+}
+
+/// What a run was: its flags, and the model and prompt its combines came
+/// from.
+#[derive(Serialize, Deserialize)]
+pub struct RunInfo {
+    pub started: String,
+    pub args: Vec<String>,
+    pub model: String,
+    pub ollama_url: String,
+    pub server_url: Option<String>,
+    /// What combines are cached under: the model, or "generation-server".
+    pub cache_model: String,
+    /// The combine prompt's fingerprint, or the server's combine version.
+    pub prompt: String,
+    /// When it was picked up again with `--resume`.
+    #[serde(default)]
+    pub resumed: Vec<String>,
+}
+
+/// `run.json`, once the run is over.
+#[derive(Serialize)]
+struct Finished<'a> {
+    #[serde(flatten)]
+    info: &'a RunInfo,
+    finished: String,
+    /// The limit that stopped it early, if one did.
+    stopped: Option<String>,
+    calls: usize,
+    cached: usize,
+    valid: usize,
+}
+
+/// Where a run writes its reports, `explore/runs/<started>/`, so one run
+/// doesn't overwrite another's. Beside them go `run.json` (what ran, and
+/// its stats), `config.json` (the element sets and families used, which
+/// `--config` can load again) and `results.json` (every combine the run
+/// got). The combine cache stays shared: it's keyed by model and prompt.
+pub struct RunDir {
+    path: PathBuf,
+    info: RunInfo,
+}
+
+impl RunDir {
+    /// Make a new directory under `root`, named for the time now.
+    pub fn create(root: &Path, info: RunInfo, config: &Config) -> Result<Self, String> {
+        let mut path = root.join(&info.started);
+        let mut n = 1;
+        while path.exists() {
+            n += 1;
+            path = root.join(format!("{}-{n}", info.started));
+        }
+        std::fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+        let run = Self { path, info };
+        let config = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+        run.write("config.json", config)?;
+        run.write_info()?;
+        Ok(run)
+    }
+
+    /// A stopped run's directory, for `--resume` to carry on in.
+    pub fn reopen(path: &Path, info: RunInfo) -> Result<Self, String> {
+        let run_json = path.join("run.json");
+        let data = std::fs::read_to_string(&run_json)
+            .map_err(|e| format!("Failed to read {}: {e}", run_json.display()))?;
+        let mut saved: RunInfo = serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to parse {}: {e}", run_json.display()))?;
+        saved.resumed.push(info.started);
+        let run = Self {
+            path: path.to_path_buf(),
+            info: saved,
+        };
+        run.write_info()?;
+        Ok(run)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the report `name` goes in this run.
+    pub fn file(&self, name: &str) -> String {
+        self.path.join(name).display().to_string()
+    }
+
+    fn write(&self, name: &str, data: String) -> Result<(), String> {
+        let path = self.path.join(name);
+        std::fs::write(&path, data + "\n")
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+
+    fn write_info(&self) -> Result<(), String> {
+        let info = serde_json::to_string_pretty(&self.info).map_err(|e| e.to_string())?;
+        self.write("run.json", info)
+    }
+
+    /// Write the run's stats and every combine it got, as it ends or is
+    /// stopped.
+    pub fn finish(&self, stats: &Stats, budget: &Budget) {
+        let finished = Finished {
+            info: &self.info,
+            finished: timestamp(SystemTime::now()),
+            stopped: budget.is_spent().then(|| budget.describe()),
+            calls: stats.calls,
+            cached: stats.cached,
+            valid: stats.valid,
+        };
+        let written = serde_json::to_string_pretty(&finished)
+            .map_err(|e| e.to_string())
+            .and_then(|json| self.write("run.json", json))
+            .and_then(|()| {
+                let results = serde_json::to_string_pretty(&stats.results);
+                self.write("results.json", results.map_err(|e| e.to_string())?)
+            });
+        match written {
+            Ok(()) => println!("\nRun written to {}", self.path.display()),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
+
+/// A UTC time as `2024-05-01T12-30-00`, for naming run directories.
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    // Days since 1970-01-01 to a civil date, from Howard Hinnant's
+    // days_from_civil inverse
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}-{:02}-{:02}",
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
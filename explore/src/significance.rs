@@ -0,0 +1,419 @@
+use std::collections::{HashMap, HashSet};
+
+/// Bootstrap resamples per comparison.
+const RESAMPLES: usize = 2000;
+
+/// One modifier family's results, grouped by pair so pairs can be resampled.
+pub struct FamilySample {
+    pub name: String,
+    /// pair label -> (valid, result name) for every modifier and sample
+    pub pairs: HashMap<String, Vec<(bool, String)>>,
+}
+
+/// The step 1 ranking metrics, over some set of pairs.
+#[derive(Clone, Copy)]
+pub struct Metrics {
+    pub valid: usize,
+    pub total: usize,
+    pub unique: usize,
+    /// Mean distinct results per pair.
+    pub diff: f64,
+}
+
+impl Metrics {
+    /// What families are ranked by.
+    pub fn score(&self) -> f64 {
+        self.valid as f64 + self.unique as f64 + self.diff
+    }
+
+    pub fn validity(&self) -> f64 {
+        if self.total > 0 {
+            self.valid as f64 / self.total as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+pub struct FamilyStats {
+    pub name: String,
+    pub metrics: Metrics,
+    /// 95% intervals.
+    pub validity_ci: (f64, f64),
+    pub score_ci: (f64, f64),
+}
+
+/// The winner's score against another family's.
+pub struct Versus {
+    pub name: String,
+    pub diff: f64,
+    pub ci: (f64, f64),
+    /// Share of resamples where the winner scored higher.
+    pub leads: f64,
+}
+
+impl Versus {
+    /// Whether the winner is ahead with 95% confidence.
+    pub fn significant(&self) -> bool {
+        self.ci.0 > 0.0
+    }
+}
+
+pub struct ChiSquare {
+    pub stat: f64,
+    pub df: usize,
+    pub p: f64,
+}
+
+pub struct Comparison {
+    /// Best score first.
+    pub families: Vec<FamilyStats>,
+    pub pairs: usize,
+    pub versus: Vec<Versus>,
+    /// Whether validity rates differ across families at all.
+    pub chi_square: Option<ChiSquare>,
+}
+
+/// Rank families by score, with confidence intervals from a paired
+/// bootstrap: each resample draws the pairs with replacement and scores
+/// every family on the same draw, since some pairs are easier than others
+/// whatever the modifier.
+pub fn compare(families: Vec<FamilySample>) -> Comparison {
+    let mut pair_labels: Vec<&String> = families.iter().flat_map(|f| f.pairs.keys()).collect();
+    pair_labels.sort();
+    pair_labels.dedup();
+
+    let mut rng = SplitMix64(42);
+    let mut resampled: Vec<Vec<Metrics>> = vec![Vec::with_capacity(RESAMPLES); families.len()];
+    for _ in 0..RESAMPLES {
+        let draw: Vec<&str> = (0..pair_labels.len())
+            .map(|_| pair_labels[rng.below(pair_labels.len())].as_str())
+            .collect();
+        for (family, samples) in families.iter().zip(&mut resampled) {
+            samples.push(metrics(family, &draw));
+        }
+    }
+
+    let all: Vec<&str> = pair_labels.iter().map(|p| p.as_str()).collect();
+    let mut ranked: Vec<(FamilyStats, Vec<Metrics>)> = families
+        .iter()
+        .zip(resampled)
+        .map(|(family, samples)| {
+            let metrics = metrics(family, &all);
+            let stats = FamilyStats {
+                name: family.name.clone(),
+                metrics,
+                validity_ci: interval(samples.iter().map(|m| m.validity()).collect()),
+                score_ci: interval(centered(
+                    metrics.score(),
+                    samples.iter().map(|m| m.score()).collect(),
+                )),
+            };
+            (stats, samples)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.metrics.score().total_cmp(&a.0.metrics.score()));
+
+    let versus = match ranked.split_first() {
+        Some(((winner, winner_samples), rest)) => rest
+            .iter()
+            .map(|(other, samples)| {
+                let diff = winner.metrics.score() - other.metrics.score();
+                let diffs = centered(
+                    diff,
+                    winner_samples
+                        .iter()
+                        .zip(samples)
+                        .map(|(w, o)| w.score() - o.score())
+                        .collect(),
+                );
+                let leads = diffs.iter().filter(|&&d| d > 0.0).count() as f64 / diffs.len() as f64;
+                Versus {
+                    name: other.name.clone(),
+                    diff,
+                    ci: interval(diffs),
+                    leads,
+                }
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let table: Vec<(usize, usize)> = ranked
+        .iter()
+        .map(|(f, _)| (f.metrics.valid, f.metrics.total - f.metrics.valid))
+        .collect();
+    Comparison {
+        families: ranked.into_iter().map(|(stats, _)| stats).collect(),
+        pairs: pair_labels.len(),
+        versus,
+        chi_square: chi_square(&table),
+    }
+}
+
+fn metrics(family: &FamilySample, pairs: &[&str]) -> Metrics {
+    let mut m = Metrics {
+        valid: 0,
+        total: 0,
+        unique: 0,
+        diff: 0.0,
+    };
+    let mut unique = HashSet::new();
+    let mut distinct_total = 0;
+    for pair in pairs {
+        let Some(results) = family.pairs.get(*pair) else {
+            continue;
+        };
+        let mut distinct = HashSet::new();
+        for (valid, name) in results {
+            m.total += 1;
+            if *valid {
+                m.valid += 1;
+                unique.insert(name);
+            }
+            distinct.insert(name);
+        }
+        distinct_total += distinct.len();
+    }
+    m.unique = unique.len();
+    if !pairs.is_empty() {
+        m.diff = distinct_total as f64 / pairs.len() as f64;
+    }
+    m
+}
+
+/// Shift resampled `values` to center on `estimate`. Resampling pairs with
+/// replacement repeats some, which always lowers the unique-name count, so
+/// raw resampled scores run low.
+fn centered(estimate: f64, values: Vec<f64>) -> Vec<f64> {
+    let mean = values.iter().sum::<f64>() / values.len().max(1) as f64;
+    values.into_iter().map(|v| v + estimate - mean).collect()
+}
+
+/// The middle 95% of `values`.
+fn interval(mut values: Vec<f64>) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    values.sort_by(f64::total_cmp);
+    let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+    (at(0.025), at(0.975))
+}
+
+/// Pearson's chi-square test of whether (valid, invalid) counts differ
+/// across rows. None when there's nothing to compare.
+fn chi_square(table: &[(usize, usize)]) -> Option<ChiSquare> {
+    let rows: Vec<(f64, f64)> = table
+        .iter()
+        .filter(|(v, i)| v + i > 0)
+        .map(|&(v, i)| (v as f64, i as f64))
+        .collect();
+    let valid: f64 = rows.iter().map(|r| r.0).sum();
+    let invalid: f64 = rows.iter().map(|r| r.1).sum();
+    let total = valid + invalid;
+    if rows.len() < 2 || valid == 0.0 || invalid == 0.0 {
+        return None;
+    }
+    let mut stat = 0.0;
+    for (v, i) in &rows {
+        let n = v + i;
+        for (observed, column) in [(v, valid), (i, invalid)] {
+            let expected = n * column / total;
+            stat += (observed - expected).powi(2) / expected;
+        }
+    }
+    let df = rows.len() - 1;
+    Some(ChiSquare {
+        stat,
+        df,
+        p: upper_gamma(df as f64 / 2.0, stat / 2.0),
+    })
+}
+
+/// Regularized upper incomplete gamma Q(a, x), i.e. the chi-square
+/// survival function at 2x with 2a degrees of freedom.
+fn upper_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        // Series for the lower function
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..500 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-14 {
+                break;
+            }
+        }
+        (1.0 - sum * prefix).clamp(0.0, 1.0)
+    } else {
+        // Continued fraction (modified Lentz)
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..500 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-14 {
+                break;
+            }
+        }
+        (prefix * h).clamp(0.0, 1.0)
+    }
+}
+
+/// Lanczos approximation of ln Γ(x), for x > 0.
+fn ln_gamma(x: f64) -> f64 {
+    const G: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for (i, g) in G.iter().enumerate() {
+        series += g / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Small seeded generator, so the same results always give the same
+/// intervals, and the same `--pair-seed` the same pairs.
+pub struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Fisher-Yates.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A family whose every pair gives `valid` of `n` results, each named
+    /// after its pair and index so they're all distinct.
+    fn family(name: &str, pairs: usize, valid: usize, n: usize) -> FamilySample {
+        let pairs = (0..pairs)
+            .map(|p| {
+                let results = (0..n).map(|i| (i < valid, format!("{name}-{p}-{i}"))).collect();
+                (format!("pair {p}"), results)
+            })
+            .collect();
+        FamilySample {
+            name: name.to_string(),
+            pairs,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn chi_square_matches_a_worked_example() {
+        // Expected counts 40/60 in both rows: 2 * (100/40 + 100/60)
+        let test = chi_square(&[(30, 70), (50, 50)]).expect("two rows");
+        assert_eq!(test.df, 1);
+        assert!(close(test.stat, 25.0 / 3.0), "stat {}", test.stat);
+        assert!((test.p - 0.003892).abs() < 1e-5, "p {}", test.p);
+    }
+
+    #[test]
+    fn chi_square_needs_something_to_compare() {
+        assert!(chi_square(&[(5, 5)]).is_none());
+        assert!(chi_square(&[(5, 0), (3, 0)]).is_none());
+        let same = chi_square(&[(5, 5), (10, 10)]).expect("two rows");
+        assert!(close(same.stat, 0.0) && close(same.p, 1.0));
+    }
+
+    #[test]
+    fn upper_gamma_is_the_chi_square_survival_function() {
+        // Two degrees of freedom: Q(1, x) = e^-x, on both sides of a + 1
+        for x in [0.5, 1.0, 3.0, 10.0] {
+            assert!(close(upper_gamma(1.0, x), (-x).exp()), "x = {x}");
+        }
+        assert!(close(upper_gamma(2.0, 0.0), 1.0));
+        assert!(close(ln_gamma(5.0), 24f64.ln()));
+    }
+
+    #[test]
+    fn interval_takes_the_middle_95_percent() {
+        let values = (0..=1000).map(f64::from).collect();
+        assert_eq!(interval(values), (25.0, 975.0));
+        assert_eq!(interval(Vec::new()), (0.0, 0.0));
+    }
+
+    #[test]
+    fn centered_moves_the_mean_to_the_estimate() {
+        assert_eq!(centered(10.0, vec![1.0, 2.0, 3.0]), [9.0, 10.0, 11.0]);
+    }
+
+    #[test]
+    fn compare_ranks_and_separates_families() {
+        let comparison =
+            compare(vec![family("weak", 30, 2, 4), family("strong", 30, 4, 4)]);
+        assert_eq!(comparison.pairs, 30);
+        assert_eq!(comparison.families[0].name, "strong");
+        let strong = comparison.families[0].metrics;
+        assert_eq!((strong.valid, strong.total, strong.unique), (120, 120, 120));
+        assert!(close(strong.diff, 4.0));
+
+        let versus = &comparison.versus[0];
+        assert_eq!(versus.name, "weak");
+        assert!(close(versus.diff, 120.0));
+        assert!(versus.significant());
+        assert!(comparison.chi_square.expect("rates differ").p < 0.001);
+    }
+
+    #[test]
+    fn compare_is_repeatable() {
+        let run = || compare(vec![family("a", 10, 1, 3), family("b", 10, 2, 3)]);
+        let (first, second) = (run(), run());
+        assert_eq!(first.families[0].score_ci, second.families[0].score_ci);
+    }
+
+    #[test]
+    fn shuffle_keeps_every_item() {
+        let mut items: Vec<u32> = (0..50).collect();
+        SplitMix64(7).shuffle(&mut items);
+        assert_ne!(items, (0..50).collect::<Vec<_>>());
+        items.sort_unstable();
+        assert_eq!(items, (0..50).collect::<Vec<_>>());
+    }
+}
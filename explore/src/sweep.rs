@@ -0,0 +1,329 @@
+use crate::combine::{CombineResult, OllamaClient};
+use crate::progress::Progress;
+use crate::theories::Card;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// The sampling grid a sweep repeats every pair across.
+pub struct SweepSettings {
+    pub temperatures: Vec<f32>,
+    pub seeds: Vec<u32>,
+    /// Calls per temperature and seed; more than one shows whether a fixed
+    /// seed really gives the same answer every time.
+    pub repeats: u32,
+}
+
+struct Run {
+    temperature: f32,
+    seed: u32,
+    result: Result<CombineResult, String>,
+}
+
+/// Every run of every pair. Nothing here is cached: the point is to see what
+/// Ollama answers each time.
+pub struct Sweep {
+    pairs: Vec<(String, Vec<Run>)>,
+    settings: SweepSettings,
+}
+
+/// How alike a set of runs came out.
+struct Stability {
+    runs: usize,
+    errors: usize,
+    valid: usize,
+    /// Share of answered runs giving the most common name.
+    agreement: f64,
+    /// Distinct names among answered runs.
+    distinct: usize,
+}
+
+impl Stability {
+    fn of<'a>(runs: impl Iterator<Item = &'a Run>) -> Self {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let (mut total, mut errors, mut valid) = (0, 0, 0);
+        for run in runs {
+            total += 1;
+            match &run.result {
+                Ok(result) => {
+                    if result.name != "Not possible" {
+                        valid += 1;
+                    }
+                    *counts.entry(result.name.to_lowercase()).or_default() += 1;
+                }
+                Err(_) => errors += 1,
+            }
+        }
+        let answered = total - errors;
+        let top = counts.values().copied().max().unwrap_or(0);
+        Self {
+            runs: total,
+            errors,
+            valid,
+            agreement: if answered > 0 { top as f64 / answered as f64 } else { 0.0 },
+            distinct: counts.len(),
+        }
+    }
+}
+
+/// One temperature's runs, averaged over pairs.
+struct TemperatureRow {
+    temperature: f32,
+    agreement: f64,
+    distinct: f64,
+    repeat_stable: f64,
+    /// Percent of runs with a result other than "Not possible".
+    valid: f64,
+    errors: usize,
+}
+
+/// Combine every pair at every temperature and seed, `repeats` times each,
+/// with at most `limit` Ollama calls in flight.
+pub async fn run(
+    client: &Arc<OllamaClient>,
+    limit: &Arc<Semaphore>,
+    pairs: &[(Card, Card)],
+    settings: SweepSettings,
+) -> Sweep {
+    let mut grid = Vec::new();
+    for &temperature in &settings.temperatures {
+        for &seed in &settings.seeds {
+            for _ in 0..settings.repeats {
+                grid.push((temperature, seed));
+            }
+        }
+    }
+
+    let calls: Vec<Vec<_>> = pairs
+        .iter()
+        .map(|(a, b)| {
+            grid.iter()
+                .map(|&(temperature, seed)| {
+                    let (client, limit) = (client.clone(), limit.clone());
+                    let cards = vec![a.clone(), b.clone()];
+                    let call = tokio::spawn(async move {
+                        let _permit = limit.acquire_owned().await.map_err(|e| e.to_string())?;
+                        client.combine_with(&cards, temperature, seed).await
+                    });
+                    (temperature, seed, call)
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut progress = Progress::new(pairs.len() * grid.len());
+    let mut results = Vec::with_capacity(pairs.len());
+    for ((a, b), calls) in pairs.iter().zip(calls) {
+        let mut runs = Vec::with_capacity(calls.len());
+        for (temperature, seed, call) in calls {
+            let result = call.await.map_err(|e| e.to_string()).and_then(|r| r);
+            if matches!(&result, Err(e) if e == crate::budget::SPENT) {
+                continue;
+            }
+            progress.tick(false, result.as_ref().is_ok_and(|r| r.name != "Not possible"));
+            runs.push(Run { temperature, seed, result });
+        }
+        // A pair the budget cut short would skew its temperatures; leave it out
+        if runs.len() < grid.len() && client.budget().is_spent() {
+            continue;
+        }
+        results.push((format!("{} + {}", a.name, b.name), runs));
+    }
+    progress.finish();
+
+    Sweep {
+        pairs: results,
+        settings,
+    }
+}
+
+impl Sweep {
+    fn at(&self, temperature: f32) -> impl Iterator<Item = (&str, Stability)> {
+        self.pairs.iter().map(move |(label, runs)| {
+            let runs = runs.iter().filter(move |r| r.temperature == temperature);
+            (label.as_str(), Stability::of(runs))
+        })
+    }
+
+    /// Share of (pair, seed) cells whose repeats all gave the same name.
+    fn repeat_stable(&self, temperature: f32) -> f64 {
+        let mut cells = 0;
+        let mut stable = 0;
+        for (_, runs) in &self.pairs {
+            for &seed in &self.settings.seeds {
+                let cell = runs
+                    .iter()
+                    .filter(|r| r.temperature == temperature && r.seed == seed);
+                cells += 1;
+                if Stability::of(cell).agreement == 1.0 {
+                    stable += 1;
+                }
+            }
+        }
+        stable as f64 / cells.max(1) as f64
+    }
+
+    fn temperature_rows(&self) -> Vec<TemperatureRow> {
+        self.settings
+            .temperatures
+            .iter()
+            .map(|&temperature| {
+                let per_pair: Vec<Stability> = self.at(temperature).map(|(_, s)| s).collect();
+                let n = per_pair.len().max(1) as f64;
+                let all = Stability::of(self.pairs.iter().flat_map(|(_, runs)| {
+                    runs.iter().filter(move |r| r.temperature == temperature)
+                }));
+                TemperatureRow {
+                    temperature,
+                    agreement: per_pair.iter().map(|s| s.agreement).sum::<f64>() / n,
+                    distinct: per_pair.iter().map(|s| s.distinct).sum::<usize>() as f64 / n,
+                    repeat_stable: self.repeat_stable(temperature),
+                    valid: pct(all.valid, all.runs),
+                    errors: all.errors,
+                }
+            })
+            .collect()
+    }
+
+    /// Print stability per temperature, then per pair.
+    pub fn print(&self) {
+        println!("\n{}", "=".repeat(60));
+        println!("SWEEP RESULTS");
+        println!("{}\n", "=".repeat(60));
+        println!(
+            "{} pairs x {} temperatures x {} seeds x {} repeats\n",
+            self.pairs.len(),
+            self.settings.temperatures.len(),
+            self.settings.seeds.len(),
+            self.settings.repeats
+        );
+
+        println!(
+            "  {:>5}  {:>9}  {:>13}  {:>14}  {:>6}  {:>6}",
+            "Temp", "Agreement", "Names / pair", "Repeat-stable", "Valid", "Errors"
+        );
+        for row in self.temperature_rows() {
+            println!(
+                "  {:>5.2}  {:>8.0}%  {:>13.1}  {:>13.0}%  {:>5.0}%  {:>6}",
+                row.temperature,
+                row.agreement * 100.0,
+                row.distinct,
+                row.repeat_stable * 100.0,
+                row.valid,
+                row.errors
+            );
+        }
+
+        println!("\n  Per pair (all temperatures and seeds):");
+        for (label, runs) in &self.pairs {
+            let s = Stability::of(runs.iter());
+            println!(
+                "    {label:<30} {:>4.0}% agree, {} names: {}",
+                s.agreement * 100.0,
+                s.distinct,
+                names(runs).join(", ")
+            );
+        }
+
+        let unique = self.unique_names();
+        println!("\n  {unique} distinct names across all runs");
+    }
+
+    /// Write the sweep as markdown.
+    pub fn write_to_file(&self, path: &str) {
+        let mut f = std::fs::File::create(path).expect("failed to create sweep report file");
+
+        writeln!(f, "# Sweep Report\n").unwrap();
+        writeln!(
+            f,
+            "Temperatures {:?}, seeds {:?}, {} repeats each; {} distinct names across all runs.",
+            self.settings.temperatures,
+            self.settings.seeds,
+            self.settings.repeats,
+            self.unique_names()
+        )
+        .unwrap();
+
+        writeln!(f, "\n## By Temperature\n").unwrap();
+        writeln!(
+            f,
+            "| Temp | Agreement | Names / pair | Repeat-stable | Valid | Errors |"
+        )
+        .unwrap();
+        writeln!(
+            f,
+            "|------|-----------|--------------|---------------|-------|--------|"
+        )
+        .unwrap();
+        for row in self.temperature_rows() {
+            writeln!(
+                f,
+                "| {:.2} | {:.0}% | {:.1} | {:.0}% | {:.0}% | {} |",
+                row.temperature,
+                row.agreement * 100.0,
+                row.distinct,
+                row.repeat_stable * 100.0,
+                row.valid,
+                row.errors
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            f,
+            "\nAgreement is the share of a pair's runs giving its most common name, averaged \
+             over pairs. Repeat-stable is the share of pair and seed combinations whose \
+             repeats all gave the same name."
+        )
+        .unwrap();
+
+        writeln!(f, "\n## By Pair\n").unwrap();
+        for (label, runs) in &self.pairs {
+            writeln!(f, "### {label}\n").unwrap();
+            writeln!(f, "| Temp | Seed | Result |").unwrap();
+            writeln!(f, "|------|------|--------|").unwrap();
+            for run in runs {
+                let result = match &run.result {
+                    Ok(r) => r.name.clone(),
+                    Err(e) => format!("error: {e}"),
+                };
+                writeln!(f, "| {:.2} | {} | {result} |", run.temperature, run.seed).unwrap();
+            }
+            writeln!(f).unwrap();
+        }
+
+        println!("\nSweep report written to {path}");
+    }
+
+    fn unique_names(&self) -> usize {
+        let runs = self.pairs.iter().flat_map(|(_, runs)| runs);
+        Stability::of(runs).distinct
+    }
+}
+
+/// Names a pair's runs gave, most common first.
+fn names(runs: &[Run]) -> Vec<String> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for run in runs {
+        if let Ok(result) = &run.result {
+            match counts.iter_mut().find(|(n, _)| n.eq_ignore_ascii_case(&result.name)) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((result.name.clone(), 1)),
+            }
+        }
+    }
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts
+        .into_iter()
+        .map(|(name, count)| format!("{name} x{count}"))
+        .collect()
+}
+
+fn pct(n: usize, of: usize) -> f64 {
+    if of > 0 {
+        n as f64 / of as f64 * 100.0
+    } else {
+        0.0
+    }
+}
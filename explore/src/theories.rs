@@ -1,4 +1,7 @@
-use serde::{Deserialize, Serialize};
+use crate::significance::SplitMix64;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -28,27 +31,62 @@ impl Card {
     pub fn intent(name: &str, meaning: &str) -> Self {
         Self {
             name: name.to_string(),
-            description: format!("Concept card — guides the combination toward {meaning}"),
+            description: format!("{INTENT_PREFIX}{meaning}"),
             kind: CardKind::Intent,
         }
     }
 }
 
+/// What `Card::intent` puts before an intent's meaning in its description.
+const INTENT_PREFIX: &str = "Concept card — guides the combination toward ";
+
+/// A concept card as written in a `--config` file: its name and what it
+/// steers combinations toward.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Intent {
+    name: String,
+    meaning: String,
+}
+
+fn intents<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Card>, D::Error> {
+    let intents = Vec::<Intent>::deserialize(deserializer)?;
+    Ok(intents
+        .iter()
+        .map(|i| Card::intent(&i.name, &i.meaning))
+        .collect())
+}
+
+/// Intents written back as a `--config` file has them.
+fn intents_out<S: Serializer>(cards: &[Card], serializer: S) -> Result<S::Ok, S::Error> {
+    let intents: Vec<Intent> = cards
+        .iter()
+        .map(|c| Intent {
+            name: c.name.clone(),
+            meaning: c.description.strip_prefix(INTENT_PREFIX).unwrap_or(&c.description).into(),
+        })
+        .collect();
+    intents.serialize(serializer)
+}
+
 // ---------- Element sets ----------
 
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ElementTheory {
-    pub name: &'static str,
-    pub label: &'static str,
-    pub hypothesis: &'static str,
+    pub name: String,
+    pub label: String,
+    #[serde(default)]
+    pub hypothesis: String,
     pub elements: Vec<Card>,
 }
 
 pub fn all_theories() -> Vec<ElementTheory> {
     vec![
         ElementTheory {
-            name: "A",
-            label: "Classical",
-            hypothesis: "Baseline — mineral-heavy, may lack diversity",
+            name: "A".into(),
+            label: "Classical".into(),
+            hypothesis: "Baseline — mineral-heavy, may lack diversity".into(),
             elements: vec![
                 Card::material("Earth", "Rich brown soil"),
                 Card::material("Water", "Clear flowing liquid"),
@@ -65,9 +103,9 @@ pub fn all_theories() -> Vec<ElementTheory> {
             ],
         },
         ElementTheory {
-            name: "E",
-            label: "Four Kingdoms",
-            hypothesis: "Balanced mineral/plant/animal/energy",
+            name: "E".into(),
+            label: "Four Kingdoms".into(),
+            hypothesis: "Balanced mineral/plant/animal/energy".into(),
             elements: vec![
                 Card::material("Fire", "Hot roaring flames"),
                 Card::material("Water", "Clear flowing liquid"),
@@ -84,9 +122,9 @@ pub fn all_theories() -> Vec<ElementTheory> {
             ],
         },
         ElementTheory {
-            name: "F",
-            label: "Workshop Bench",
-            hypothesis: "Medieval crafter, no abstract forces",
+            name: "F".into(),
+            label: "Workshop Bench".into(),
+            hypothesis: "Medieval crafter, no abstract forces".into(),
             elements: vec![
                 Card::material("Iron", "Dark heavy metal ingot"),
                 Card::material("Leather", "Tanned animal hide"),
@@ -103,9 +141,9 @@ pub fn all_theories() -> Vec<ElementTheory> {
             ],
         },
         ElementTheory {
-            name: "G",
-            label: "Primal + Organic",
-            hypothesis: "Forces + minerals + organics triad",
+            name: "G".into(),
+            label: "Primal + Organic".into(),
+            hypothesis: "Forces + minerals + organics triad".into(),
             elements: vec![
                 Card::material("Fire", "Hot roaring flames"),
                 Card::material("Water", "Clear flowing liquid"),
@@ -122,9 +160,9 @@ pub fn all_theories() -> Vec<ElementTheory> {
             ],
         },
         ElementTheory {
-            name: "H",
-            label: "Unusual Starters",
-            hypothesis: "Unusual/specific = more surprising?",
+            name: "H".into(),
+            label: "Unusual Starters".into(),
+            hypothesis: "Unusual/specific = more surprising?".into(),
             elements: vec![
                 Card::material("Honey", "Thick golden sweet syrup"),
                 Card::material("Rust", "Crumbly orange corroded metal"),
@@ -145,17 +183,21 @@ pub fn all_theories() -> Vec<ElementTheory> {
 
 // ---------- Modifier families ----------
 
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ModifierFamily {
-    pub name: &'static str,
-    pub hypothesis: &'static str,
+    pub name: String,
+    #[serde(default)]
+    pub hypothesis: String,
+    #[serde(deserialize_with = "intents", serialize_with = "intents_out")]
     pub modifiers: Vec<Card>,
 }
 
 pub fn all_modifier_families() -> Vec<ModifierFamily> {
     vec![
         ModifierFamily {
-            name: "Evocative",
-            hypothesis: "Thematic words that feel like game-world concepts",
+            name: "Evocative".into(),
+            hypothesis: "Thematic words that feel like game-world concepts".into(),
             modifiers: vec![
                 Card::intent("Forge", "crafted metal objects"),
                 Card::intent("Harmony", "music and balance"),
@@ -166,8 +208,8 @@ pub fn all_modifier_families() -> Vec<ModifierFamily> {
             ],
         },
         ModifierFamily {
-            name: "Labels",
-            hypothesis: "Clear intent, less flavorful",
+            name: "Labels".into(),
+            hypothesis: "Clear intent, less flavorful".into(),
             modifiers: vec![
                 Card::intent("Functional", "practical useful objects"),
                 Card::intent("Musical", "instruments and sound"),
@@ -178,8 +220,8 @@ pub fn all_modifier_families() -> Vec<ModifierFamily> {
             ],
         },
         ModifierFamily {
-            name: "Emotions",
-            hypothesis: "Emotional coloring might produce surprising/fun results",
+            name: "Emotions".into(),
+            hypothesis: "Emotional coloring might produce surprising/fun results".into(),
             modifiers: vec![
                 Card::intent("Happy", "joy and celebration"),
                 Card::intent("Scared", "fear and defense"),
@@ -190,8 +232,8 @@ pub fn all_modifier_families() -> Vec<ModifierFamily> {
             ],
         },
         ModifierFamily {
-            name: "Actions",
-            hypothesis: "Verb-driven, implies what to do with materials",
+            name: "Actions".into(),
+            hypothesis: "Verb-driven, implies what to do with materials".into(),
             modifiers: vec![
                 Card::intent("Build", "construction and assembly"),
                 Card::intent("Play", "games and entertainment"),
@@ -202,8 +244,8 @@ pub fn all_modifier_families() -> Vec<ModifierFamily> {
             ],
         },
         ModifierFamily {
-            name: "Sensory",
-            hypothesis: "Physical properties that steer the output",
+            name: "Sensory".into(),
+            hypothesis: "Physical properties that steer the output".into(),
             modifiers: vec![
                 Card::intent("Loud", "noise and vibration"),
                 Card::intent("Bright", "light and visibility"),
@@ -221,8 +263,8 @@ pub fn all_modifier_families() -> Vec<ModifierFamily> {
 pub fn sensory_variations() -> Vec<ModifierFamily> {
     vec![
         ModifierFamily {
-            name: "Sensory-A (Original)",
-            hypothesis: "Physical properties that steer the output",
+            name: "Sensory-A (Original)".into(),
+            hypothesis: "Physical properties that steer the output".into(),
             modifiers: vec![
                 Card::intent("Loud", "noise and vibration"),
                 Card::intent("Bright", "light and visibility"),
@@ -233,8 +275,8 @@ pub fn sensory_variations() -> Vec<ModifierFamily> {
             ],
         },
         ModifierFamily {
-            name: "Sensory-B (Intensity)",
-            hypothesis: "Force and weight properties",
+            name: "Sensory-B (Intensity)".into(),
+            hypothesis: "Force and weight properties".into(),
             modifiers: vec![
                 Card::intent("Fierce", "aggressive forceful energy"),
                 Card::intent("Gentle", "calm careful handling"),
@@ -245,8 +287,8 @@ pub fn sensory_variations() -> Vec<ModifierFamily> {
             ],
         },
         ModifierFamily {
-            name: "Sensory-C (Temperature)",
-            hypothesis: "Temperature and state transitions",
+            name: "Sensory-C (Temperature)".into(),
+            hypothesis: "Temperature and state transitions".into(),
             modifiers: vec![
                 Card::intent("Hot", "high temperature and heat"),
                 Card::intent("Cool", "low temperature and chill"),
@@ -257,8 +299,8 @@ pub fn sensory_variations() -> Vec<ModifierFamily> {
             ],
         },
         ModifierFamily {
-            name: "Sensory-D (Texture)",
-            hypothesis: "Surface and structural properties",
+            name: "Sensory-D (Texture)".into(),
+            hypothesis: "Surface and structural properties".into(),
             modifiers: vec![
                 Card::intent("Hard", "resistance and durability"),
                 Card::intent("Flexible", "bending without breaking"),
@@ -269,8 +311,8 @@ pub fn sensory_variations() -> Vec<ModifierFamily> {
             ],
         },
         ModifierFamily {
-            name: "Sensory-E (Nature)",
-            hypothesis: "Natural sensory qualities",
+            name: "Sensory-E (Nature)".into(),
+            hypothesis: "Natural sensory qualities".into(),
             modifiers: vec![
                 Card::intent("Warm", "comfortable gentle heat"),
                 Card::intent("Silent", "quiet and still"),
@@ -283,44 +325,151 @@ pub fn sensory_variations() -> Vec<ModifierFamily> {
     ]
 }
 
-// ---------- Theory G elements accessor ----------
+// ---------- Sample pairs for modifier testing ----------
 
-pub fn theory_g_elements() -> Vec<Card> {
-    all_theories()
-        .into_iter()
-        .find(|t| t.name == "G")
-        .expect("Theory G not found")
-        .elements
+/// Pairs (or sets) sampled when `--pairs` doesn't give a count.
+const SAMPLED_PAIRS: usize = 15;
+
+/// How step 1, `--sensory` and `--sweep` pick pairs from an element set,
+/// and `--arity` its larger sets.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PairSampling {
+    /// 15 pairs spread evenly through the list of pairs; the same every run.
+    Stride,
+    /// Every pair.
+    Full,
+    /// This many pairs at random.
+    Random(usize),
+    /// This many pairs at random, with every element in as close to the
+    /// same number of them as can be.
+    Stratified(usize),
 }
 
-// ---------- Baseline element set for step 1 ----------
+impl FromStr for PairSampling {
+    type Err = String;
 
-pub fn baseline_elements() -> Vec<Card> {
-    all_theories().remove(0).elements
+    /// `stride`, `full`, `random:N` or `stratified[:N]`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (kind, count) = match s.split_once(':') {
+            Some((kind, count)) => {
+                let count = count
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("pair count must be a positive number, not {count}"))?;
+                (kind, Some(count))
+            }
+            None => (s, None),
+        };
+        match (kind, count) {
+            ("stride", None) => Ok(Self::Stride),
+            ("full", None) => Ok(Self::Full),
+            ("random", Some(n)) => Ok(Self::Random(n)),
+            ("random", None) => Err("random needs a count, e.g. random:30".to_string()),
+            ("stratified", n) => Ok(Self::Stratified(n.unwrap_or(SAMPLED_PAIRS))),
+            _ => Err(format!(
+                "unknown pair sampling {s}; use stride, full, random:N or stratified[:N]"
+            )),
+        }
+    }
 }
 
-// ---------- Sample pairs for modifier testing ----------
-
-/// Returns 15 diverse pairs from the baseline set for modifier comparison.
-pub fn sample_pairs(elements: &[Card]) -> Vec<(Card, Card)> {
-    let n = elements.len();
-    let mut all_pairs = Vec::new();
-    for i in 0..n {
-        for j in (i + 1)..n {
-            all_pairs.push((elements[i].clone(), elements[j].clone()));
+impl fmt::Display for PairSampling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Stride => write!(f, "stride"),
+            Self::Full => write!(f, "full"),
+            Self::Random(n) => write!(f, "random:{n}"),
+            Self::Stratified(n) => write!(f, "stratified:{n}"),
         }
     }
+}
 
-    let total = all_pairs.len();
-    let step = total as f64 / 15.0;
-    (0..15)
-        .map(|k| {
-            let idx = (k as f64 * step) as usize;
-            all_pairs[idx].clone()
+/// Pairs of distinct elements picked by `sampling`, in the order they come
+/// in the element set. Random picks follow `seed`, so a run can be repeated.
+pub fn sample_pairs(elements: &[Card], sampling: PairSampling, seed: u64) -> Vec<(Card, Card)> {
+    sample_sets(elements, 2, sampling, seed)
+        .into_iter()
+        .map(|mut set| {
+            let b = set.pop().expect("pair");
+            (set.pop().expect("pair"), b)
         })
         .collect()
 }
 
+/// Sets of `size` distinct elements picked by `sampling`, as `sample_pairs`
+/// picks pairs.
+pub fn sample_sets(
+    elements: &[Card],
+    size: usize,
+    sampling: PairSampling,
+    seed: u64,
+) -> Vec<Vec<Card>> {
+    let n = elements.len();
+    let mut all_sets = Vec::new();
+    index_sets(n, size, &mut Vec::new(), &mut all_sets);
+
+    let total = all_sets.len();
+    let mut rng = SplitMix64(seed);
+    let mut picked: Vec<Vec<usize>> = match sampling {
+        PairSampling::Stride => {
+            let step = total as f64 / SAMPLED_PAIRS as f64;
+            (0..SAMPLED_PAIRS)
+                .map(|k| all_sets[(k as f64 * step) as usize].clone())
+                .collect()
+        }
+        PairSampling::Full => all_sets,
+        PairSampling::Random(count) => {
+            rng.shuffle(&mut all_sets);
+            all_sets.truncate(count);
+            all_sets
+        }
+        PairSampling::Stratified(count) => {
+            // Each time, the first set in shuffled order whose elements
+            // have been used least
+            rng.shuffle(&mut all_sets);
+            let mut uses = vec![0; n];
+            let mut picked = Vec::new();
+            while picked.len() < count && !all_sets.is_empty() {
+                let (k, _) = all_sets
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, set)| {
+                        let most = set.iter().map(|&i| uses[i]).max().unwrap_or(0);
+                        (most, set.iter().map(|&i| uses[i]).sum::<usize>())
+                    })
+                    .expect("sets left");
+                let set = all_sets.remove(k);
+                for &i in &set {
+                    uses[i] += 1;
+                }
+                picked.push(set);
+            }
+            picked
+        }
+    };
+    picked.sort();
+    picked
+        .into_iter()
+        .map(|set| set.into_iter().map(|i| elements[i].clone()).collect())
+        .collect()
+}
+
+/// Every increasing list of `size` indices below `n`, in order, each
+/// starting with `prefix`.
+fn index_sets(n: usize, size: usize, prefix: &mut Vec<usize>, sets: &mut Vec<Vec<usize>>) {
+    if prefix.len() == size {
+        sets.push(prefix.clone());
+        return;
+    }
+    let start = prefix.last().map_or(0, |&i| i + 1);
+    for i in start..n {
+        prefix.push(i);
+        index_sets(n, size, prefix, sets);
+        prefix.pop();
+    }
+}
+
 // ---------- Board categories ----------
 
 pub const BOARD_CATEGORIES: &[&str] = &[
@@ -353,6 +502,14 @@ pub const BOARD_CATEGORIES: &[&str] = &[
 
 // ---------- Target items checklist ----------
 
+/// Items the run hopes to discover, grouped for the checklist.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetGroup {
+    pub category: String,
+    pub items: Vec<String>,
+}
+
 pub const TARGET_ITEMS: &[(&str, &[&str])] = &[
     ("Weapons", &["Sword", "Blade", "Spear", "Arrow", "Bow"]),
     ("Transport", &["Boat", "Raft", "Cart", "Sled"]),
@@ -0,0 +1,273 @@
+use crate::auth::require_admin;
+use crate::card_cache::{now_secs, CacheDump, CacheStats, ConflictStrategy, MergeSummary};
+use crate::card_template::load_card_templates;
+use crate::claims::ClaimVoucher;
+use crate::crafted_art::disk_path;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::game_state::{load_base_cards, load_categories};
+use crate::generate::{fetch_combine_version, AppState};
+use crate::packs::load_packs;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+pub struct ReloadSummary {
+    pub base_cards: usize,
+    pub categories: usize,
+    pub packs: usize,
+    pub card_templates: usize,
+}
+
+/// POST /api/admin/reload-content — re-read cards.json, categories.json,
+/// packs.json and card-templates.json.
+///
+/// All four files are parsed and validated before either is swapped in, so a bad
+/// edit leaves the running content untouched. Games in progress keep their
+/// boards and hands; new draws and new games use the reloaded content. The
+/// card cache's in-memory layer is dropped too, so edits made by the scripts
+/// (e.g. reset-discoveries) show up.
+pub async fn reload_content(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ReloadSummary>, ApiError> {
+    require_admin(&state, &headers)?;
+
+    let base_cards = load_base_cards(&state.paths.cards_json)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    let categories = load_categories(&state.paths.categories_json)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    let packs = load_packs(&state.paths.packs_json).map_err(|e| err(ErrorCode::StorageError, e))?;
+    // Prepares every template's frames, which takes a moment
+    let card_templates =
+        tokio::task::block_in_place(|| load_card_templates(&state.paths.card_templates_json))
+            .map_err(|e| err(ErrorCode::StorageError, e))?;
+
+    let summary = ReloadSummary {
+        base_cards: base_cards.len(),
+        categories: categories.len(),
+        packs: packs.len(),
+        card_templates: card_templates.len(),
+    };
+    *state.base_cards.write().await = base_cards;
+    *state.categories.write().await = categories;
+    *state.packs.write().await = packs;
+    *state.card_templates.write().await = card_templates;
+    state.card_cache.read().await.clear_hot();
+
+    log::info!(
+        "Reloaded content: {} base cards, {} categories, {} packs, {} card templates",
+        summary.base_cards,
+        summary.categories,
+        summary.packs,
+        summary.card_templates
+    );
+    Ok(Json(summary))
+}
+
+#[derive(Serialize)]
+pub struct CardCacheReport {
+    #[serde(flatten)]
+    pub cache: CacheStats,
+    /// Possible cards with no image file on disk (renders in flight included).
+    pub missing_images: u64,
+    /// Everything under `cards/`: base art, crafted art, and variants.
+    pub cards_dir_bytes: u64,
+}
+
+/// GET /api/admin/card-cache — entry counts, recent growth, hot-cache
+/// hit/miss counters, and disk usage. Scans the cache and `cards/`, so poll
+/// it every few minutes rather than every few seconds.
+pub async fn card_cache_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<CardCacheReport>, ApiError> {
+    require_admin(&state, &headers)?;
+    let cache = state.card_cache.read().await;
+    let cards_dir = &state.paths.cards_dir;
+    let report = tokio::task::block_in_place(|| {
+        let cache_stats = cache.stats()?;
+        let missing_images = cache
+            .all_entries()
+            .iter()
+            .filter(|card| !card.impossible)
+            .filter(|card| !disk_path(cards_dir, &card.image_path).is_some_and(|p| p.is_file()))
+            .count() as u64;
+        Ok::<_, String>(CardCacheReport {
+            cache: cache_stats,
+            missing_images,
+            cards_dir_bytes: dir_size(cards_dir),
+        })
+    })
+    .map_err(|e| err(ErrorCode::StorageError, e))?;
+    Ok(Json(report))
+}
+
+/// Total size of the files under `dir`, not following symlinks.
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(t) if t.is_file() => entry.metadata().map_or(0, |m| m.len()),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// GET /api/admin/card-cache/export — every cached card and mint count as
+/// JSON, in the same layout `import` accepts. Card art under `cards/crafted`
+/// is not included; copy it alongside the dump.
+pub async fn export_card_cache(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<CacheDump>, ApiError> {
+    require_admin(&state, &headers)?;
+    let dump = state
+        .card_cache
+        .read()
+        .await
+        .export()
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    Ok(Json(dump))
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// `keep_existing` (default) or `overwrite`.
+    #[serde(default)]
+    pub on_conflict: ConflictStrategy,
+}
+
+/// POST /api/admin/card-cache/import — merge an exported dump, e.g. to promote
+/// staging discoveries to production. Mint counts in the dump are ignored.
+pub async fn import_card_cache(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    query: Result<Query<ImportQuery>, QueryRejection>,
+    Json(dump): Json<serde_json::Value>,
+) -> Result<Json<MergeSummary>, ApiError> {
+    require_admin(&state, &headers)?;
+    let Query(query) = query.map_err(|e| err(ErrorCode::InvalidRequest, e.body_text()))?;
+    let dump = CacheDump::from_json(dump).map_err(|e| err(ErrorCode::InvalidRequest, e))?;
+
+    let summary = state
+        .card_cache
+        .write()
+        .await
+        .merge(dump, query.on_conflict)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    log::info!(
+        "Imported card cache dump ({:?}): {} imported, {} skipped",
+        query.on_conflict,
+        summary.imported,
+        summary.skipped
+    );
+    Ok(Json(summary))
+}
+
+#[derive(Serialize)]
+pub struct InvalidateSummary {
+    pub combine_version: String,
+    pub removed: usize,
+}
+
+/// POST /api/admin/card-cache/invalidate — delete undiscovered cards and
+/// "not possible" verdicts that weren't made by the generation server's
+/// current combine version, so they are regenerated with the current model
+/// and prompt. Discovered cards are kept: players own them.
+pub async fn invalidate_card_cache(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<InvalidateSummary>, ApiError> {
+    require_admin(&state, &headers)?;
+    let version = fetch_combine_version(&state).await.map_err(|e| {
+        err(
+            ErrorCode::GenerationUnavailable,
+            format!("Could not get the current combine version: {e}"),
+        )
+    })?;
+
+    let cache = state.card_cache.read().await;
+    cache.set_combine_version(&version);
+    let removed = cache
+        .remove_outdated(&version)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    log::info!(
+        "Removed {} cache entries not from combine version {version}",
+        removed.len()
+    );
+    Ok(Json(InvalidateSummary {
+        combine_version: version,
+        removed: removed.len(),
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct VoucherRequest {
+    pub card_id: String,
+    /// Restrict the vouchers to one wallet.
+    #[serde(default)]
+    pub wallet_address: Option<String>,
+    /// Defaults to 1.
+    #[serde(default)]
+    pub count: Option<usize>,
+}
+
+/// Most vouchers issued by one request.
+const MAX_VOUCHERS: usize = 1000;
+
+#[derive(Serialize)]
+pub struct VoucherSummary {
+    pub card_id: String,
+    pub vouchers: Vec<String>,
+}
+
+/// POST /api/admin/claim-vouchers — issue one-time codes that let a wallet
+/// claim a crafted card as an NFT without having crafted it (giveaways,
+/// support fixes). Each code mints once.
+pub async fn issue_claim_vouchers(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<VoucherRequest>,
+) -> Result<Json<VoucherSummary>, ApiError> {
+    require_admin(&state, &headers)?;
+    let count = req.count.unwrap_or(1);
+    if count == 0 || count > MAX_VOUCHERS {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("count must be 1-{MAX_VOUCHERS}"),
+        ));
+    }
+    match state.card_cache.read().await.get(&req.card_id) {
+        Some(card) if !card.impossible => {}
+        _ => return Err(err(ErrorCode::CardNotFound, "No claimable card with that id")),
+    }
+
+    let mut vouchers = Vec::with_capacity(count);
+    for _ in 0..count {
+        let voucher = ClaimVoucher {
+            code: uuid::Uuid::new_v4().simple().to_string(),
+            card_id: req.card_id.clone(),
+            wallet: req.wallet_address.clone(),
+            created_at: now_secs(),
+        };
+        state
+            .claims
+            .put_voucher(&voucher)
+            .map_err(|e| err(ErrorCode::StorageError, e))?;
+        vouchers.push(voucher.code);
+    }
+    log::info!("Issued {} claim vouchers for {}", vouchers.len(), req.card_id);
+    Ok(Json(VoucherSummary {
+        card_id: req.card_id,
+        vouchers,
+    }))
+}
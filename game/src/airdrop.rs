@@ -0,0 +1,489 @@
+//! Promotional airdrops: mint one card to a list of wallets in the
+//! background, for giveaways and tournament prizes. Started and watched
+//! through the admin API, or with `game airdrop`, which does both.
+
+use crate::assets::{self, AssetStatus, TrackedAsset};
+use crate::auth::require_admin;
+use crate::card_cache::now_secs;
+use crate::config::Config;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use crate::packs::Rarity;
+use crate::solana::Finish;
+use crate::solana_api::require_solana;
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Most wallets one airdrop can mint to.
+const MAX_WALLETS: usize = 10_000;
+const DEFAULT_BATCH_SIZE: usize = 10;
+const MAX_BATCH_SIZE: usize = 50;
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const MAX_ATTEMPTS: u32 = 10;
+/// Pause between batches, so a big airdrop doesn't starve the RPC node.
+const BATCH_PAUSE: Duration = Duration::from_secs(1);
+/// Finished airdrops kept for their reports.
+const KEEP_FINISHED: usize = 50;
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecipientStatus {
+    Pending,
+    Minted,
+    Failed,
+    /// The card's supply cap was reached first.
+    SoldOut,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Recipient {
+    pub wallet_address: String,
+    pub status: RecipientStatus,
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edition: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Airdrop {
+    pub airdrop_id: String,
+    pub card_id: String,
+    pub started_at: u64,
+    /// Set once every wallet was tried, or the server began shutting down.
+    pub finished_at: Option<u64>,
+    pub total: usize,
+    pub minted: usize,
+    pub failed: usize,
+    pub sold_out: usize,
+    pub recipients: Vec<Recipient>,
+}
+
+impl Airdrop {
+    fn pending(&self) -> usize {
+        self.total - self.minted - self.failed - self.sold_out
+    }
+
+    fn settle(&mut self, index: usize, recipient: Recipient) {
+        match recipient.status {
+            RecipientStatus::Minted => self.minted += 1,
+            RecipientStatus::Failed => self.failed += 1,
+            RecipientStatus::SoldOut => self.sold_out += 1,
+            RecipientStatus::Pending => {}
+        }
+        self.recipients[index] = recipient;
+    }
+}
+
+/// Airdrops started since the server came up. A restart loses them, and
+/// interrupts any still running; their reports say which wallets are left.
+#[derive(Default)]
+pub struct Airdrops {
+    airdrops: Mutex<HashMap<String, Airdrop>>,
+}
+
+impl Airdrops {
+    fn insert(&self, airdrop: Airdrop) {
+        let mut airdrops = self.airdrops.lock().unwrap();
+        let mut finished: Vec<(u64, String)> = airdrops
+            .values()
+            .filter_map(|a| a.finished_at.map(|at| (at, a.airdrop_id.clone())))
+            .collect();
+        if finished.len() >= KEEP_FINISHED {
+            finished.sort();
+            for (_, id) in &finished[..=finished.len() - KEEP_FINISHED] {
+                airdrops.remove(id);
+            }
+        }
+        airdrops.insert(airdrop.airdrop_id.clone(), airdrop);
+    }
+
+    fn get(&self, id: &str) -> Option<Airdrop> {
+        self.airdrops.lock().unwrap().get(id).cloned()
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut Airdrop)) {
+        if let Some(airdrop) = self.airdrops.lock().unwrap().get_mut(id) {
+            f(airdrop);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AirdropRequest {
+    pub card_id: String,
+    pub wallets: Vec<String>,
+    /// Mints sent at once. Defaults to 10.
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+    /// Tries per wallet before it counts as failed. Defaults to 3.
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+}
+
+/// What the airdrop mints, looked up once before it starts.
+struct AirdropCard {
+    card_id: String,
+    name: String,
+    metadata_uri: String,
+    cap: Option<u64>,
+}
+
+/// POST /api/admin/airdrop — start minting `card_id` (a base or crafted
+/// card) to each of `wallets`. Returns right away with the airdrop's
+/// report; poll GET /api/admin/airdrop/{id} for progress.
+pub async fn start_airdrop(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<AirdropRequest>,
+) -> Result<Json<Airdrop>, ApiError> {
+    require_admin(&state, &headers)?;
+    let solana = require_solana(&state)?;
+
+    let batch_size = req.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+    if batch_size == 0 || batch_size > MAX_BATCH_SIZE {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("batch_size must be 1-{MAX_BATCH_SIZE}"),
+        ));
+    }
+    let max_attempts = req.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS);
+    if max_attempts == 0 || max_attempts > MAX_ATTEMPTS {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("max_attempts must be 1-{MAX_ATTEMPTS}"),
+        ));
+    }
+    let wallets = parse_wallets(req.wallets)?;
+
+    let (name, description, image_path, crafted) = {
+        let base = state.base_cards.read().await;
+        match base.iter().find(|b| b.id == req.card_id) {
+            Some(b) => (b.name.clone(), b.description.clone(), b.image_path.clone(), false),
+            None => match state.card_cache.read().await.get(&req.card_id) {
+                Some(c) if !c.impossible => (c.name, c.description, c.image_path, true),
+                _ => return Err(err(ErrorCode::CardNotFound, "No card with that id")),
+            },
+        }
+    };
+    let finish = Finish::new(false, Rarity::of(&state, &req.card_id, crafted));
+    let metadata_uri = solana
+        .ensure_metadata_json(&req.card_id, &name, &description, &image_path, finish, None)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    let card = AirdropCard {
+        cap: state.supply.cap(&req.card_id, crafted),
+        card_id: req.card_id,
+        name,
+        metadata_uri,
+    };
+
+    let airdrop = Airdrop {
+        airdrop_id: uuid::Uuid::new_v4().to_string(),
+        card_id: card.card_id.clone(),
+        started_at: now_secs(),
+        finished_at: None,
+        total: wallets.len(),
+        minted: 0,
+        failed: 0,
+        sold_out: 0,
+        recipients: wallets
+            .iter()
+            .map(|wallet| Recipient {
+                wallet_address: wallet.to_string(),
+                status: RecipientStatus::Pending,
+                attempts: 0,
+                edition: None,
+                asset_address: None,
+                signature: None,
+                error: None,
+            })
+            .collect(),
+    };
+    log::info!(
+        "Airdrop {}: minting {} to {} wallets",
+        airdrop.airdrop_id,
+        card.card_id,
+        wallets.len()
+    );
+    state.airdrops.insert(airdrop.clone());
+    tokio::spawn(run(
+        state.clone(),
+        airdrop.airdrop_id.clone(),
+        Arc::new(card),
+        wallets,
+        batch_size,
+        max_attempts,
+    ));
+    Ok(Json(airdrop))
+}
+
+/// Parse and dedupe the recipients, rejecting the whole list if any is bad.
+fn parse_wallets(wallets: Vec<String>) -> Result<Vec<Pubkey>, ApiError> {
+    if wallets.is_empty() || wallets.len() > MAX_WALLETS {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("wallets must list 1-{MAX_WALLETS} addresses"),
+        ));
+    }
+    let mut seen = HashSet::new();
+    let mut parsed = Vec::new();
+    let mut invalid = Vec::new();
+    for wallet in wallets {
+        match Pubkey::from_str(wallet.trim()) {
+            Ok(pubkey) if seen.insert(pubkey) => parsed.push(pubkey),
+            Ok(_) => {}
+            Err(_) => invalid.push(wallet),
+        }
+    }
+    if !invalid.is_empty() {
+        return Err(err(ErrorCode::InvalidWallet, "Some wallet addresses are invalid")
+            .with_details(serde_json::json!({ "invalid": invalid })));
+    }
+    Ok(parsed)
+}
+
+/// Mint to every wallet, `batch_size` at a time.
+async fn run(
+    state: Arc<AppState>,
+    id: String,
+    card: Arc<AirdropCard>,
+    wallets: Vec<Pubkey>,
+    batch_size: usize,
+    max_attempts: u32,
+) {
+    let indexed: Vec<(usize, Pubkey)> = wallets.into_iter().enumerate().collect();
+    for (n, batch) in indexed.chunks(batch_size).enumerate() {
+        if state.shutting_down.load(Ordering::Relaxed) {
+            log::warn!("Airdrop {id} interrupted by shutdown");
+            break;
+        }
+        if n > 0 {
+            tokio::time::sleep(BATCH_PAUSE).await;
+        }
+        let mints = batch.iter().map(|&(index, wallet)| {
+            let (state, card) = (state.clone(), card.clone());
+            async move {
+                let recipient = mint_one(&state, &card, wallet, max_attempts).await;
+                (index, recipient)
+            }
+        });
+        for (index, recipient) in futures::future::join_all(mints).await {
+            state.airdrops.update(&id, |a| a.settle(index, recipient));
+        }
+    }
+
+    state.airdrops.update(&id, |a| {
+        a.finished_at = Some(now_secs());
+        log::info!(
+            "Airdrop {id} finished: {} minted, {} failed, {} sold out, {} not tried",
+            a.minted,
+            a.failed,
+            a.sold_out,
+            a.pending()
+        );
+    });
+}
+
+/// Mint the card to one wallet, retrying with backoff. The edition is taken
+/// once up front, so retries don't skip numbers.
+async fn mint_one(
+    state: &Arc<AppState>,
+    card: &Arc<AirdropCard>,
+    wallet: Pubkey,
+    max_attempts: u32,
+) -> Recipient {
+    let mut recipient = Recipient {
+        wallet_address: wallet.to_string(),
+        status: RecipientStatus::Failed,
+        attempts: 0,
+        edition: None,
+        asset_address: None,
+        signature: None,
+        error: None,
+    };
+    let Some(solana) = state.solana.clone() else {
+        recipient.error = Some("Solana integration not configured".to_string());
+        return recipient;
+    };
+    let edition = state.card_cache.read().await.issue_edition(&card.card_id, card.cap);
+    let edition = match edition {
+        Ok(Some(edition)) => edition,
+        Ok(None) => {
+            recipient.status = RecipientStatus::SoldOut;
+            return recipient;
+        }
+        Err(e) => {
+            recipient.error = Some(e);
+            return recipient;
+        }
+    };
+    recipient.edition = Some(edition);
+
+    while recipient.attempts < max_attempts {
+        if recipient.attempts > 0 {
+            tokio::time::sleep(Duration::from_secs(1 << recipient.attempts)).await;
+        }
+        recipient.attempts += 1;
+        let (task_solana, task_card) = (solana.clone(), card.clone());
+        let result = tokio::task::spawn_blocking(move || {
+            let card = task_card;
+            let (id, name, uri) = (&card.card_id, &card.name, &card.metadata_uri);
+            task_solana.server_mint(id, edition, false, name, uri, &wallet)
+        })
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+        match result {
+            Ok((sig, asset_address)) => {
+                let (wallet, status) = (wallet.to_string(), AssetStatus::Confirmed);
+                let mut asset = TrackedAsset::new(&asset_address, &card.card_id, &wallet, status);
+                asset.signature = Some(sig.clone());
+                assets::track(state, &[asset]);
+                state.card_cache.read().await.record_mint(&card.card_id);
+                solana.forget_owned_cards(&wallet);
+                recipient.status = RecipientStatus::Minted;
+                recipient.asset_address = Some(asset_address);
+                recipient.signature = Some(sig);
+                recipient.error = None;
+                return recipient;
+            }
+            Err(e) => {
+                log::warn!(
+                    "Airdrop mint of {} to {wallet} failed (attempt {}): {e}",
+                    card.card_id,
+                    recipient.attempts
+                );
+                recipient.error = Some(e);
+            }
+        }
+    }
+    recipient
+}
+
+/// GET /api/admin/airdrop/{id} — an airdrop's progress, with the outcome
+/// for each wallet.
+pub async fn airdrop_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<Airdrop>, ApiError> {
+    require_admin(&state, &headers)?;
+    state
+        .airdrops
+        .get(&id)
+        .map(Json)
+        .ok_or_else(|| err(ErrorCode::AirdropNotFound, "No airdrop with that id"))
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Card to mint (base or crafted card id)
+    #[arg(long)]
+    card: String,
+    /// File of wallet addresses, one per line (`#` starts a comment)
+    #[arg(long)]
+    wallets: std::path::PathBuf,
+    /// Game server to run the airdrop on (default: [game] public_base_url)
+    #[arg(long)]
+    server: Option<String>,
+    /// Admin API key (default: the first of [auth] admin_keys)
+    #[arg(long)]
+    api_key: Option<String>,
+    /// Mints sent at once
+    #[arg(long)]
+    batch_size: Option<usize>,
+    /// Tries per wallet
+    #[arg(long)]
+    max_attempts: Option<u32>,
+}
+
+/// `game airdrop`: start an airdrop on a running server and follow it to the
+/// end, then list the wallets that didn't get the card.
+pub async fn run_cli(args: Args) -> Result<(), String> {
+    let config = Config::load()?;
+    let server = args.server.unwrap_or(config.game.public_base_url);
+    let server = server.trim_end_matches('/');
+    let api_key = args
+        .api_key
+        .or_else(|| config.auth.admin_keys.first().cloned())
+        .ok_or("No admin key: pass --api-key or set GAME_ADMIN_KEYS")?;
+    let text = std::fs::read_to_string(&args.wallets)
+        .map_err(|e| format!("Failed to read {}: {e}", args.wallets.display()))?;
+    let wallets: Vec<&str> = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let client = reqwest::Client::new();
+    let request = serde_json::json!({
+        "card_id": args.card,
+        "wallets": wallets,
+        "batch_size": args.batch_size,
+        "max_attempts": args.max_attempts,
+    });
+    let mut airdrop = send(client.post(format!("{server}/api/admin/airdrop")), &api_key, &request)
+        .await?;
+    let id = airdrop["airdrop_id"].as_str().unwrap_or_default().to_string();
+    println!("Airdrop {id}: {} to {} wallets", args.card, airdrop["total"]);
+
+    let mut last = String::new();
+    while airdrop["finished_at"].is_null() {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        let url = format!("{server}/api/admin/airdrop/{id}");
+        airdrop = send(client.get(url), &api_key, &serde_json::Value::Null).await?;
+        let progress = format!(
+            "  {} minted, {} failed, {} sold out of {}",
+            airdrop["minted"], airdrop["failed"], airdrop["sold_out"], airdrop["total"]
+        );
+        if progress != last {
+            println!("{progress}");
+            last = progress;
+        }
+    }
+
+    let missed: Vec<&serde_json::Value> = airdrop["recipients"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|r| r["status"] != "minted")
+        .collect();
+    if missed.is_empty() {
+        println!("Done: every wallet got the card");
+        return Ok(());
+    }
+    println!("Not airdropped:");
+    for r in &missed {
+        let reason = r["error"].as_str().unwrap_or_else(|| r["status"].as_str().unwrap_or(""));
+        println!("  {} ({reason})", r["wallet_address"].as_str().unwrap_or(""));
+    }
+    Err(format!("{} of {} wallets didn't get the card", missed.len(), airdrop["total"]))
+}
+
+async fn send(
+    request: reqwest::RequestBuilder,
+    api_key: &str,
+    body: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let request = request.header("x-api-key", api_key);
+    let request = if body.is_null() { request } else { request.json(body) };
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("{status}: {}", body["message"].as_str().unwrap_or("")));
+    }
+    Ok(body)
+}
@@ -0,0 +1,207 @@
+//! Replacement art for crafted cards. Generated art sometimes misses badly,
+//! so the wallet that discovered a card can upload its own: checked for
+//! size and type, optionally looked over by the generation server's model,
+//! then rendered into the card like generated art. The card's old image is
+//! kept on disk and listed in its `previous_art`.
+
+use crate::card_cache::{self, ArtVersion};
+use crate::config::ArtUploadSettings;
+use crate::crafted_art;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use crate::solana_api::check_wallet_rate;
+use crate::telemetry::SendTraced;
+use crate::wallet_auth::require_wallet;
+use axum::body::Bytes;
+use axum::extract::rejection::BytesRejection;
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Longest side of the copy sent for moderation; vision models look at
+/// far less than a full card.
+const MODERATION_PX: u32 = 768;
+
+#[derive(Serialize, ToSchema)]
+pub struct ArtUploadResponse {
+    pub card_id: String,
+    /// The card rendered with the new art.
+    pub image_path: String,
+    /// Every image the card had before, oldest first.
+    pub previous_art: Vec<ArtVersion>,
+}
+
+#[derive(Deserialize)]
+struct Moderation {
+    allowed: bool,
+    reason: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/cards/{id}/art",
+    tag = "cards",
+    params(("id" = String, Path, description = "Crafted card id")),
+    request_body(content = Vec<u8>, description = "PNG, JPEG or WebP art", content_type = "image/png"),
+    responses(
+        (status = 200, description = "Card rendered with the new art", body = ArtUploadResponse),
+        (status = 400, description = "Not a PNG, JPEG or WebP, or the wrong size; or uploads are off", body = ApiError),
+        (status = 401, description = "No wallet session", body = ApiError),
+        (status = 403, description = "The signed-in wallet didn't discover this card", body = ApiError),
+        (status = 404, description = "No discovered crafted card with this id", body = ApiError),
+        (status = 413, description = "Upload larger than `max_bytes`", body = ApiError),
+        (status = 422, description = "Moderation rejected the art", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Moderation unavailable", body = ApiError),
+    )
+)]
+pub async fn upload_art(
+    State(state): State<Arc<AppState>>,
+    Path(card_id): Path<String>,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Json<ArtUploadResponse>, ApiError> {
+    let settings = state.art_upload;
+    if !settings.enabled {
+        return Err(err(ErrorCode::InvalidRequest, "Art uploads are turned off"));
+    }
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+
+    let cached = state
+        .card_cache
+        .read()
+        .await
+        .get(&card_id)
+        .filter(|c| c.discovered && !c.impossible)
+        .ok_or_else(|| err(ErrorCode::CardNotFound, "Card not found"))?;
+    if cached.discovered_by.as_deref() != Some(wallet.as_str()) {
+        return Err(err(ErrorCode::NotDiscoverer, "Only the card's discoverer can replace its art"));
+    }
+
+    let art = body.map_err(|e| {
+        err(ErrorCode::ArtTooLarge, e.body_text())
+            .with_details(serde_json::json!({ "max_bytes": settings.max_bytes }))
+    })?;
+    let preview = {
+        let art = art.clone();
+        state.render.run(move || check_art(&art, &settings)).await??
+    };
+
+    if settings.moderate {
+        let verdict = moderate(&state, &cached.name, &cached.description, &preview).await?;
+        if !verdict.allowed {
+            log::info!("Art upload for {card_id} by {wallet} rejected: {}", verdict.reason);
+            return Err(err(ErrorCode::ArtRejected, "The art was rejected by moderation")
+                .with_details(serde_json::json!({ "reason": verdict.reason })));
+        }
+    }
+
+    // Kept, and rendered, like generated art
+    let (art_path, serve_path) =
+        crafted_art::keep_and_render(&state, &card_id, &cached.name, art).await?;
+
+    // Re-read under the write lock so a craft counted meanwhile isn't lost
+    let cache = state.card_cache.write().await;
+    let mut updated = cache
+        .get(&card_id)
+        .ok_or_else(|| err(ErrorCode::CardNotFound, "Card not found"))?;
+    if updated.image_path != serve_path {
+        updated.previous_art.push(ArtVersion {
+            image_path: std::mem::replace(&mut updated.image_path, serve_path.clone()),
+            replaced_at: card_cache::now_secs(),
+        });
+        updated.art_path = Some(art_path);
+        cache.insert(card_id.clone(), updated.clone());
+    }
+    log::info!("Card {card_id} has new art from {wallet}: {serve_path}");
+
+    Ok(Json(ArtUploadResponse {
+        card_id,
+        image_path: serve_path,
+        previous_art: updated.previous_art,
+    }))
+}
+
+/// Check that `art` is a PNG, JPEG or WebP of an allowed size, and return
+/// a small JPEG of it for moderation.
+fn check_art(art: &[u8], settings: &ArtUploadSettings) -> Result<Vec<u8>, ApiError> {
+    let undecodable = |e: image::ImageError| {
+        err(ErrorCode::InvalidArt, format!("Art doesn't decode: {e}"))
+    };
+    let reader = || {
+        ImageReader::new(Cursor::new(art))
+            .with_guessed_format()
+            .map_err(|e| err(ErrorCode::InvalidArt, format!("Art doesn't read: {e}")))
+    };
+    let format = reader()?.format();
+    if !matches!(format, Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP)) {
+        return Err(err(ErrorCode::InvalidArt, "Art must be a PNG, JPEG or WebP image"));
+    }
+    // Only the header is read until the size is known to be allowed
+    let (w, h) = reader()?.into_dimensions().map_err(undecodable)?;
+    if w.min(h) < settings.min_px || w.max(h) > settings.max_px {
+        return Err(err(
+            ErrorCode::InvalidArt,
+            format!(
+                "Art is {w}×{h}; each side must be {}-{} pixels",
+                settings.min_px, settings.max_px
+            ),
+        )
+        .with_details(serde_json::json!({
+            "min_px": settings.min_px,
+            "max_px": settings.max_px,
+        })));
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(settings.max_px);
+    limits.max_image_height = Some(settings.max_px);
+    // Room for the decoded image at up to 16-bit RGBA, and no more
+    limits.max_alloc = Some(u64::from(w) * u64::from(h) * 8);
+    let mut decoder = reader()?;
+    decoder.limits(limits);
+    let img = decoder.decode().map_err(undecodable)?;
+
+    let preview = img.resize(MODERATION_PX, MODERATION_PX, FilterType::Triangle).to_rgb8();
+    let mut jpeg = Cursor::new(Vec::new());
+    preview
+        .write_to(&mut jpeg, ImageFormat::Jpeg)
+        .map_err(|e| err(ErrorCode::RenderFailed, format!("jpeg encode error: {e}")))?;
+    Ok(jpeg.into_inner())
+}
+
+/// Ask the generation server whether `preview` is fit to be `name`'s art.
+async fn moderate(
+    state: &AppState,
+    name: &str,
+    description: &str,
+    preview: &[u8],
+) -> Result<Moderation, ApiError> {
+    let unavailable = |e: String| {
+        log::error!("Art moderation failed: {e}");
+        err(ErrorCode::GenerationUnavailable, format!("Moderation unavailable: {e}"))
+    };
+    state
+        .client
+        .post(format!("{}/moderate-image", state.generation_url))
+        .json(&serde_json::json!({
+            "name": name,
+            "description": description,
+            "image": STANDARD.encode(preview),
+        }))
+        .send_traced(tracing::info_span!("generation.moderate_image"))
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| unavailable(e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| unavailable(e.to_string()))
+}
@@ -0,0 +1,436 @@
+//! NFTs this server minted or burned, tracked until Helius reports the
+//! transaction on-chain. Helius posts our collection's transactions to
+//! `/api/webhooks/helius`; each one that touches a tracked asset settles it
+//! and tells the owner's open `/api/wallet/events` streams, so clients don't
+//! have to poll the chain.
+
+use crate::card_cache::now_secs;
+use crate::crafted_art;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use crate::indexer;
+use crate::solana::{MintTx, SolanaConfig};
+use crate::wallet_auth::require_wallet;
+use axum::extract::State;
+use axum::http::{header, HeaderMap};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::Json;
+use futures::Stream;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetStatus {
+    /// Mint transaction handed out, not seen on-chain yet.
+    Pending,
+    Confirmed,
+    /// Burn transaction handed out, not seen on-chain yet.
+    Burning,
+    Burned,
+}
+
+impl AssetStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AssetStatus::Pending => "pending",
+            AssetStatus::Confirmed => "confirmed",
+            AssetStatus::Burning => "burning",
+            AssetStatus::Burned => "burned",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(AssetStatus::Pending),
+            "confirmed" => Some(AssetStatus::Confirmed),
+            "burning" => Some(AssetStatus::Burning),
+            "burned" => Some(AssetStatus::Burned),
+            _ => None,
+        }
+    }
+
+    /// What a landed transaction touching the asset makes it, burns aside:
+    /// those depend on whether the asset is still there.
+    fn settled(self) -> Option<Self> {
+        match self {
+            AssetStatus::Pending => Some(AssetStatus::Confirmed),
+            AssetStatus::Confirmed | AssetStatus::Burning | AssetStatus::Burned => None,
+        }
+    }
+}
+
+pub struct TrackedAsset {
+    pub asset_address: String,
+    pub card_id: String,
+    /// Owner, or the last owner once burned.
+    pub wallet: String,
+    pub status: AssetStatus,
+    /// Transaction that settled it.
+    pub signature: Option<String>,
+    /// Essence the wallet earns once the burn lands.
+    pub reward: u64,
+    /// The mint transaction as handed out, and the seed of the asset's
+    /// keypair, so the wallet can get it signed again with a fresh blockhash.
+    pub mint_tx: Option<String>,
+    pub asset_seed: Option<String>,
+    pub updated_at: u64,
+    /// Minted by a wallet combine, so its landing counts as a craft of the
+    /// card, and as its discovery if nobody made that first.
+    pub crafted: bool,
+}
+
+impl TrackedAsset {
+    pub fn new(asset_address: &str, card_id: &str, wallet: &str, status: AssetStatus) -> Self {
+        TrackedAsset {
+            asset_address: asset_address.to_string(),
+            card_id: card_id.to_string(),
+            wallet: wallet.to_string(),
+            status,
+            signature: None,
+            reward: 0,
+            mint_tx: None,
+            asset_seed: None,
+            updated_at: now_secs(),
+            crafted: false,
+        }
+    }
+
+    /// The asset `tx` mints, pending until it lands.
+    pub fn minting(tx: &MintTx, card_id: &str, wallet: &str) -> Self {
+        let mut asset = TrackedAsset::new(&tx.asset_address, card_id, wallet, AssetStatus::Pending);
+        asset.mint_tx = Some(tx.transaction.clone());
+        asset.asset_seed = Some(tx.asset_seed.clone());
+        asset
+    }
+}
+
+/// Sent to the owner's event streams when an asset settles.
+#[derive(Clone, Serialize)]
+pub struct AssetEvent {
+    #[serde(skip)]
+    pub wallet: String,
+    pub asset_address: String,
+    pub card_id: String,
+    pub status: AssetStatus,
+    pub signature: String,
+    /// Essence credited for a burn.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub reward: u64,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+/// Fan-out of settled assets to `/api/wallet/events` streams on this server.
+pub struct AssetEvents {
+    events: broadcast::Sender<AssetEvent>,
+    closing: watch::Sender<bool>,
+}
+
+impl AssetEvents {
+    pub fn new() -> Self {
+        AssetEvents {
+            events: broadcast::channel(256).0,
+            closing: watch::channel(false).0,
+        }
+    }
+
+    fn publish(&self, event: AssetEvent) {
+        // No receivers just means nobody is listening
+        let _ = self.events.send(event);
+    }
+
+    /// End every open stream, so graceful shutdown doesn't wait on them.
+    pub fn close(&self) {
+        self.closing.send_replace(true);
+    }
+}
+
+/// Settle `asset` as landed in `signature`: credit a burn's reward, count a
+/// wallet combine's craft, drop the wallet's cached cards, and tell its event
+/// streams. Returns false if it was already settled (by another delivery,
+/// server, or confirm call).
+pub async fn settle(
+    state: &AppState,
+    solana: &SolanaConfig,
+    asset: TrackedAsset,
+    signature: &str,
+) -> Result<bool, ApiError> {
+    let status = match asset.status {
+        // Any transaction touching the card lands here, a transfer say, so
+        // only one that closed its account burned it
+        AssetStatus::Burning => {
+            let pubkey = Pubkey::from_str(&asset.asset_address)
+                .map_err(|e| err(ErrorCode::InvalidRequest, format!("Invalid mint: {e}")))?;
+            if solana.account_exists(&pubkey).map_err(|e| err(ErrorCode::SolanaError, e))? {
+                AssetStatus::Confirmed
+            } else {
+                AssetStatus::Burned
+            }
+        }
+        status => match status.settled() {
+            Some(status) => status,
+            None => return Ok(false),
+        },
+    };
+    let won = state
+        .assets
+        .settle_asset(&asset.asset_address, asset.status, status, signature, now_secs())
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    if !won {
+        return Ok(false);
+    }
+    log::info!("Asset {} {} ({signature})", asset.asset_address, status.as_str());
+    if asset.crafted && asset.status == AssetStatus::Pending && status == AssetStatus::Confirmed {
+        crafted_landed(state, &asset).await;
+    }
+    solana.forget_owned_cards(&asset.wallet);
+    let settled = TrackedAsset::new(&asset.asset_address, &asset.card_id, &asset.wallet, status);
+    indexer::record(state, solana, &[settled]);
+    let reward = if status == AssetStatus::Burned { asset.reward } else { 0 };
+    state.asset_events.publish(AssetEvent {
+        wallet: asset.wallet,
+        asset_address: asset.asset_address,
+        card_id: asset.card_id,
+        status,
+        signature: signature.to_string(),
+        reward,
+    });
+    Ok(true)
+}
+
+/// Count the craft of a wallet combine's card, now its mint landed, crediting
+/// the wallet with the card's discovery if no one made it first.
+async fn crafted_landed(state: &AppState, asset: &TrackedAsset) {
+    let cache = state.card_cache.write().await;
+    if let Some(mut card) = cache.get(&asset.card_id).filter(|c| !c.discovered) {
+        card.discovered = true;
+        card.discovered_at = Some(now_secs());
+        card.discovered_by = Some(asset.wallet.clone());
+        cache.insert(asset.card_id.clone(), card);
+    }
+    cache.record_craft(&asset.card_id);
+}
+
+/// Record assets a transaction we built will mint or burn. Only logged on
+/// failure: tracking is for notifications, not for the mint itself.
+pub fn track(state: &AppState, assets: &[TrackedAsset]) {
+    if let Err(e) = state.assets.track_assets(assets) {
+        log::warn!("Failed to track {} assets: {e}", assets.len());
+    }
+    if let Some(solana) = state.solana.as_deref() {
+        indexer::record(state, solana, assets);
+    }
+}
+
+/// How often to look for editions held by mints that never landed.
+const HOLD_SWEEP: Duration = Duration::from_secs(5 * 60);
+
+/// Give the editions held by wallet mints that never landed to the next mint
+/// of their card, and delete the image and metadata made for their assets,
+/// once the transactions can't be signed anymore. A mint that landed without
+/// a webhook settling it is settled here, keeping its edition.
+pub async fn release_editions(state: Arc<AppState>) {
+    let Some(solana) = state.solana.clone() else {
+        return;
+    };
+    let mut ticker = tokio::time::interval(HOLD_SWEEP);
+    loop {
+        ticker.tick().await;
+        let expired = match state.assets.expired_edition_holds(now_secs(), 100) {
+            Ok(expired) => expired,
+            Err(e) => {
+                log::warn!("Failed to list expired edition holds: {e}");
+                continue;
+            }
+        };
+        for address in expired {
+            let minted = Pubkey::from_str(&address)
+                .map_or(Ok(false), |pubkey| solana.account_exists(&pubkey));
+            let ended = match minted {
+                Ok(minted) => end_hold(&state, &solana, &address, minted).await.map(|_| minted),
+                Err(e) => Err(e),
+            };
+            match ended {
+                Ok(false) => log::info!("Released the edition held for {address}"),
+                Ok(true) => {}
+                Err(e) => log::warn!("Failed to end the edition hold of {address}: {e}"),
+            }
+        }
+    }
+}
+
+/// End the edition hold of `address`: released if its mint never landed,
+/// kept (settling the mint if no webhook did) if it did.
+async fn end_hold(
+    state: &AppState,
+    solana: &SolanaConfig,
+    address: &str,
+    minted: bool,
+) -> Result<(), String> {
+    if !minted {
+        return discard_mint(state, solana, address);
+    }
+    match state
+        .assets
+        .tracked_asset(address)?
+        .filter(|asset| asset.status == AssetStatus::Pending)
+    {
+        Some(asset) => {
+            let signature = asset.signature.clone().unwrap_or_default();
+            settle(state, solana, asset, &signature).await.map_err(|e| e.message)?;
+        }
+        None => state.assets.end_edition_hold(address, true)?,
+    }
+    Ok(())
+}
+
+/// Give the edition held for `asset`, a mint that won't land, to the next
+/// mint of its card, and delete the image and metadata made for it.
+pub fn discard_mint(state: &AppState, solana: &SolanaConfig, asset: &str) -> Result<(), String> {
+    state.assets.end_edition_hold(asset, false)?;
+    crafted_art::remove_minted(&state.paths.cards_dir, asset)?;
+    solana.remove_metadata_json(asset)
+}
+
+// --- POST /api/webhooks/helius ---
+
+/// Settle tracked assets touched by the transactions in a Helius webhook
+/// delivery, and drop the ownership index of wallets holding any other card
+/// they touched. Takes both enhanced and raw payloads. Not behind the API key:
+/// Helius authenticates with the `Authorization` value set on the webhook.
+pub async fn helius_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(txs): Json<Vec<serde_json::Value>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let solana = state.solana.as_deref().ok_or_else(|| {
+        err(
+            ErrorCode::SolanaNotConfigured,
+            "Solana integration not configured",
+        )
+    })?;
+    let expected = solana.webhook_auth.as_deref().ok_or_else(|| {
+        err(ErrorCode::SolanaNotConfigured, "Helius webhook not configured")
+    })?;
+    // Compared as digests in constant time, so timing tells nothing of the secret
+    let digest = |v: &[u8]| -> [u8; 32] { Sha256::digest(v).into() };
+    let given = headers.get(header::AUTHORIZATION).map(|v| digest(v.as_bytes()));
+    let matches = given.is_some_and(|given| bool::from(given.ct_eq(&digest(expected.as_bytes()))));
+    if !matches {
+        return Err(err(ErrorCode::Unauthorized, "Invalid webhook authorization"));
+    }
+
+    let mut settled = 0;
+    for tx in &txs {
+        let Some(signature) = tx_signature(tx) else {
+            continue;
+        };
+        if tx_failed(tx) {
+            continue;
+        }
+        for account in tx_accounts(tx) {
+            let tracked = state
+                .assets
+                .tracked_asset(account)
+                .map_err(|e| err(ErrorCode::StorageError, e))?;
+            let was_settled = match tracked {
+                Some(asset) => settle(&state, solana, asset, signature).await?,
+                None => false,
+            };
+            if was_settled {
+                settled += 1;
+            } else {
+                // Not a mint or burn we were waiting on, so the card may
+                // have changed hands
+                indexer::forget_holder(&state, account);
+            }
+        }
+    }
+
+    Ok(Json(serde_json::json!({
+        "transactions": txs.len(),
+        "settled": settled,
+    })))
+}
+
+fn tx_signature(tx: &serde_json::Value) -> Option<&str> {
+    tx.get("signature")
+        .or_else(|| tx.pointer("/transaction/signatures/0"))
+        .and_then(|s| s.as_str())
+}
+
+fn tx_failed(tx: &serde_json::Value) -> bool {
+    let failed = |v: Option<&serde_json::Value>| v.is_some_and(|e| !e.is_null());
+    failed(tx.get("transactionError")) || failed(tx.pointer("/meta/err"))
+}
+
+/// Every account the transaction touched: `accountData` in enhanced
+/// payloads, `accountKeys` (plain or parsed) in raw ones.
+fn tx_accounts(tx: &serde_json::Value) -> Vec<&str> {
+    let mut accounts: Vec<&str> = Vec::new();
+    for entry in tx.get("accountData").and_then(|a| a.as_array()).into_iter().flatten() {
+        accounts.extend(entry.get("account").and_then(|a| a.as_str()));
+    }
+    let keys = tx.pointer("/transaction/message/accountKeys").and_then(|k| k.as_array());
+    for key in keys.into_iter().flatten() {
+        accounts.extend(key.as_str().or_else(|| key.get("pubkey")?.as_str()));
+    }
+    accounts.sort_unstable();
+    accounts.dedup();
+    accounts
+}
+
+// --- GET /api/wallet/events ---
+
+#[utoipa::path(
+    get,
+    path = "/api/wallet/events",
+    tag = "wallet",
+    responses(
+        (status = 200, description = "Server-sent `asset` events as the wallet's mints and burns \
+                                      land on-chain", content_type = "text/event-stream"),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+    )
+)]
+pub async fn wallet_events(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, ApiError> {
+    let wallet = require_wallet(&state, &headers)?;
+    let events = state.asset_events.events.subscribe();
+    let closing = state.asset_events.closing.subscribe();
+
+    let stream = futures::stream::unfold(
+        (events, closing, wallet),
+        |(mut events, mut closing, wallet)| async move {
+            loop {
+                let received = tokio::select! {
+                    received = events.recv() => received,
+                    _ = closing.wait_for(|closed| *closed) => return None,
+                };
+                match received {
+                    Ok(event) if event.wallet == wallet => {
+                        let sse = Event::default().event("asset").json_data(&event);
+                        return Some((sse, (events, closing, wallet)));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(n)) => {
+                        log::warn!("Event stream for {wallet} missed {n} events")
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    );
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,152 @@
+use crate::config::AuthSettings;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap};
+use axum::middleware::Next;
+use axum::response::Response;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::Arc;
+use subtle::{Choice, ConstantTimeEq};
+
+pub struct AuthConfig {
+    /// SHA-256 of each key, so checking one takes the same time whatever it
+    /// is and however long.
+    keys: Vec<[u8; 32]>,
+}
+
+impl AuthConfig {
+    /// Build from `[auth] api_keys` (or GAME_API_KEYS).
+    /// Returns None (auth disabled) if no keys are configured.
+    pub fn from_config(settings: &AuthSettings) -> Option<Self> {
+        Self::from_keys(&settings.api_keys)
+    }
+
+    /// Build from `[auth] admin_keys` (or GAME_ADMIN_KEYS), the only keys
+    /// admin endpoints take. Returns None (admin disabled) if there are none.
+    pub fn admin_from_config(settings: &AuthSettings) -> Option<Self> {
+        Self::from_keys(&settings.admin_keys)
+    }
+
+    fn from_keys(keys: &[String]) -> Option<Self> {
+        let keys: HashSet<String> = keys
+            .iter()
+            .map(|k| k.trim())
+            .filter(|k| !k.is_empty())
+            .map(|k| k.to_string())
+            .collect();
+        if keys.is_empty() {
+            return None;
+        }
+        let keys = keys.iter().map(|k| Sha256::digest(k.as_bytes()).into()).collect();
+        Some(AuthConfig { keys })
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether `key` is one of ours. Compares against every key in
+    /// constant time, so timing doesn't tell how close a guess came.
+    fn is_known(&self, key: &str) -> bool {
+        let digest: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        let known = self
+            .keys
+            .iter()
+            .fold(Choice::from(0), |known, k| known | k.ct_eq(&digest));
+        known.into()
+    }
+
+    /// Check the request headers for a known key.
+    pub fn authorize(&self, headers: &HeaderMap) -> Result<(), ApiError> {
+        match extract_key(headers) {
+            Some(key) if self.is_known(key) => Ok(()),
+            Some(_) => Err(err(ErrorCode::Unauthorized, "Invalid API key")),
+            None => Err(err(ErrorCode::Unauthorized, "Missing API key")),
+        }
+    }
+}
+
+/// Admin endpoints are only reachable when admin keys are configured, and
+/// always require one — even if the route is later exposed over GET. Player
+/// API keys don't open them: the frontend hands those out.
+pub fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    match &state.admin_auth {
+        Some(admin) => admin.authorize(headers),
+        None => Err(err(
+            ErrorCode::Unauthorized,
+            "Admin endpoints are disabled (set GAME_ADMIN_KEYS to enable)",
+        )),
+    }
+}
+
+/// Accepts either `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+fn extract_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(value) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+    {
+        if let Some(token) = value.strip_prefix("Bearer ") {
+            return Some(token.trim());
+        }
+    }
+    headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim())
+}
+
+/// Middleware: when auth is configured, every mutating request (anything other
+/// than GET/HEAD/OPTIONS/TRACE) must carry a valid API key. Admin keys pass
+/// too, so admin requests need only the one key.
+pub async fn require_api_key(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if let Some(auth) = &state.auth {
+        let admin = || {
+            let admin = state.admin_auth.as_ref();
+            admin.is_some_and(|a| a.authorize(req.headers()).is_ok())
+        };
+        if !req.method().is_safe() && !admin() {
+            auth.authorize(req.headers())?;
+        }
+    }
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bearer(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, format!("Bearer {key}").parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn admin_keys_are_kept_apart_from_player_keys() {
+        let settings = AuthSettings {
+            api_keys: vec!["player".into()],
+            admin_keys: vec!["admin".into()],
+            ..AuthSettings::default()
+        };
+        let players = AuthConfig::from_config(&settings).expect("player keys");
+        let admin = AuthConfig::admin_from_config(&settings).expect("admin keys");
+        assert!(players.authorize(&bearer("player")).is_ok());
+        assert!(admin.authorize(&bearer("player")).is_err());
+        assert!(admin.authorize(&bearer("admin")).is_ok());
+    }
+
+    #[test]
+    fn no_admin_keys_disables_admin() {
+        let settings = AuthSettings {
+            api_keys: vec!["player".into()],
+            ..AuthSettings::default()
+        };
+        assert!(AuthConfig::admin_from_config(&settings).is_none());
+    }
+}
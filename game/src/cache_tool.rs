@@ -0,0 +1,213 @@
+//! `game cache-tool`: check the card cache against the crafted images on
+//! disk and, with `--fix`, repair what it can. Safe to run while the server is
+//! up; POST /api/admin/reload-content afterwards so it drops stale copies.
+
+use crate::card_cache::{compute_crafted_card_id, CardCache};
+use crate::config::Config;
+use crate::crafted_art::disk_path;
+use crate::image_variants;
+use crate::storage;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Files newer than this may belong to a render whose cache row isn't
+/// written yet, so they are never treated as orphans.
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(600);
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Apply repairs (relink, re-key, delete orphans) instead of only reporting
+    #[arg(long)]
+    fix: bool,
+}
+
+#[derive(Default)]
+struct Report {
+    cards: usize,
+    impossible: usize,
+    ok: usize,
+    /// Cards whose image is gone and couldn't be relinked.
+    missing_image: Vec<String>,
+    /// (card id, new image path)
+    relinked: Vec<(String, String)>,
+    /// (stored id, id recomputed from the recipe)
+    wrong_id: Vec<(String, String)>,
+    /// Cards without a recipe, so their id can't be checked.
+    no_recipe: usize,
+    orphans: Vec<PathBuf>,
+}
+
+pub fn run(args: Args) -> Result<(), String> {
+    let config = Config::load()?;
+    let paths = &config.paths;
+    let cache = CardCache::new(storage::open(&config)?.cards, &config.card_cache);
+    let crafted_dir = paths.crafted_dir();
+    let files = list_files(&crafted_dir);
+
+    let mut report = Report::default();
+    let mut referenced: HashSet<PathBuf> = HashSet::new();
+
+    for mut card in cache.all_entries() {
+        report.cards += 1;
+        if card.impossible {
+            report.impossible += 1;
+            continue;
+        }
+
+        match &card.recipe {
+            Some(recipe) => {
+                let materials: Vec<&str> =
+                    recipe.material_ids.iter().map(|s| s.as_str()).collect();
+                let expected = compute_crafted_card_id(&materials, recipe.intent_id.as_deref());
+                if expected != card.id {
+                    report.wrong_id.push((card.id.clone(), expected));
+                }
+            }
+            None => report.no_recipe += 1,
+        }
+
+        // Replaced art stays on disk for whatever still links to it
+        for old in &card.previous_art {
+            referenced.extend(disk_path(&paths.cards_dir, &old.image_path));
+        }
+
+        let image = disk_path(&paths.cards_dir, &card.image_path);
+        if image.as_ref().is_some_and(|p| p.is_file()) {
+            referenced.extend(image);
+            report.ok += 1;
+            continue;
+        }
+        match find_legacy_image(&files, &card.id) {
+            Some(found) => {
+                let name = found.file_name().unwrap_or_default().to_string_lossy();
+                let image_path = format!("/cards/crafted/{name}");
+                report.relinked.push((card.id.clone(), image_path.clone()));
+                referenced.insert(found.clone());
+                if args.fix {
+                    card.image_path = image_path;
+                    cache.insert(card.id.clone(), card);
+                }
+            }
+            None => report.missing_image.push(card.id.clone()),
+        }
+    }
+
+    if args.fix {
+        for (old, new) in &report.wrong_id {
+            rekey(&cache, old, new);
+        }
+    }
+
+    report.orphans = files
+        .iter()
+        .filter(|f| !referenced.iter().any(|r| is_variant_of(f, r)) && is_old(f))
+        .cloned()
+        .collect();
+    if args.fix {
+        for orphan in &report.orphans {
+            if let Err(e) = std::fs::remove_file(orphan) {
+                eprintln!("Failed to remove {}: {e}", orphan.display());
+            }
+        }
+    }
+
+    report.print(args.fix);
+    Ok(())
+}
+
+/// Move a card to the id its recipe hashes to, unless that id is taken.
+fn rekey(cache: &CardCache, old: &str, new: &str) {
+    if cache.get(new).is_some() {
+        eprintln!("Not re-keying {old}: {new} already exists");
+        return;
+    }
+    if let Some(card) = cache.get(old) {
+        cache.insert(new.to_string(), card);
+        cache.remove(old);
+    }
+}
+
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+fn is_old(file: &Path) -> bool {
+    std::fs::metadata(file)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age >= ORPHAN_MIN_AGE)
+}
+
+/// Before content-hash naming, renders were saved as `<name>-<card id>.png`.
+fn find_legacy_image<'a>(files: &'a [PathBuf], card_id: &str) -> Option<&'a PathBuf> {
+    let suffix = format!("-{card_id}.png");
+    files.iter().find(|f| {
+        f.file_name()
+            .is_some_and(|n| n.to_string_lossy().ends_with(&suffix))
+    })
+}
+
+/// `file` is `original` itself or one of its WebP/smaller-size variants.
+fn is_variant_of(file: &Path, original: &Path) -> bool {
+    file == original
+        || image_variants::variants().any(|(size, format)| {
+            file == image_variants::variant_path(original, size, format, false)
+        })
+}
+
+impl Report {
+    fn print(&self, fixed: bool) {
+        let action = |done: &'static str, todo: &'static str| if fixed { done } else { todo };
+
+        println!("Card cache report");
+        println!("  {} cards ({} impossible)", self.cards, self.impossible);
+        println!("  {} with images on disk", self.ok);
+        println!("  {} without a recipe (id not checkable)", self.no_recipe);
+
+        println!(
+            "  {} image paths {}",
+            self.relinked.len(),
+            action("relinked", "can be relinked")
+        );
+        for (id, path) in &self.relinked {
+            println!("    {id} → {path}");
+        }
+        println!("  {} cards missing images", self.missing_image.len());
+        for id in &self.missing_image {
+            println!("    {id}");
+        }
+        println!(
+            "  {} ids don't match their recipe{}",
+            self.wrong_id.len(),
+            action(" (re-keyed)", "")
+        );
+        for (old, new) in &self.wrong_id {
+            println!("    {old} → {new}");
+        }
+        println!(
+            "  {} orphaned files{}",
+            self.orphans.len(),
+            action(" (removed)", "")
+        );
+        for orphan in &self.orphans {
+            println!("    {}", orphan.display());
+        }
+
+        let has_repairs =
+            !(self.relinked.is_empty() && self.wrong_id.is_empty() && self.orphans.is_empty());
+        if !fixed && has_repairs {
+            println!("Run again with --fix to apply.");
+        }
+    }
+}
@@ -1,67 +1,209 @@
-use ab_glyph::{FontRef, PxScale};
+use ab_glyph::PxScale;
 use image::imageops::FilterType;
 use image::{DynamicImage, GenericImageView, ImageReader, Rgba, RgbaImage};
-use imageproc::drawing::{draw_text_mut, text_size};
+use imageproc::drawing::{draw_filled_circle_mut, draw_filled_rect_mut};
+use imageproc::rect::Rect;
+use qrcode::{EcLevel, QrCode};
+use crate::card_fonts::CardFonts;
+use crate::card_svg;
+use crate::card_template::{BackStyle, CardLocale, CardTemplate, Color, Renderer, StampWords};
+use crate::image_variants::Size;
+use crate::packs::Rarity;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::io::Cursor;
+use std::sync::OnceLock;
+use utoipa::ToSchema;
 
-#[derive(Deserialize, Default, Clone, PartialEq)]
+#[derive(Deserialize, Default, Clone, PartialEq, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum CardKind {
     #[default]
     Material,
     Intent,
+    /// Made by combining other cards.
+    Crafted,
 }
 
 pub const CARD_W: u32 = 750;
 pub const CARD_H: u32 = 1050;
 
-// Content area inside the frame's ornate border
-const CONTENT_X: i32 = 56;
-const CONTENT_Y: i32 = 80;
-const CONTENT_W: u32 = 638;
+// Wrapped names: line height as a share of the text size, and the space
+// above and below the two lines
+const NAME_LINE_SPACING: f32 = 1.1;
+const NAME_PAD: u32 = 8;
 
-// Name banner overlaid on top of the art
-const NAME_BANNER_H: u32 = 80;
-const BANNER_R: u32 = 8;
+// Category fit strip: badges, and the gaps around them
+const FIT_SHOWN: usize = 3;
+pub const FIT_BADGE_R: i32 = 20;
+pub const FIT_GAP: i32 = 10;
+const FIT_LABEL_PX: f32 = 24.0;
+const FIT_MIN_LABEL_PX: f32 = 16.0;
+pub const FIT_SCORE_PX: f32 = 24.0;
 
-// Material card colors (warm gold)
-const COLOR_BANNER: Rgba<u8> = Rgba([30, 20, 12, 190]);
-const COLOR_NAME: Rgba<u8> = Rgba([220, 195, 130, 255]);
+// Share of the rarity gem's size taken by its rim
+pub const GEM_EDGE: f32 = 0.15;
 
-// Intent card colors (cool purple/silver)
-const COLOR_INTENT_BANNER: Rgba<u8> = Rgba([20, 12, 35, 200]);
-const COLOR_INTENT_NAME: Rgba<u8> = Rgba([180, 160, 220, 255]);
+/// Minted cards' QR codes: pixels per module, and the white border around
+/// the code, in modules.
+const QR_MODULE_PX: i32 = 3;
+const QR_QUIET: i32 = 2;
+
+/// Minted cards' stamp: height of the pill, the set symbol's radius, and
+/// the text sizes in the symbol and beside it.
+const STAMP_H: u32 = 40;
+const STAMP_SYMBOL_R: i32 = 15;
+const STAMP_SET_PX: f32 = 18.0;
+const STAMP_PX: f32 = 22.0;
+
+/// Card back: how far the logo's edges fade into the background, and the
+/// season marker's size.
+const BACK_LOGO_FADE: f32 = 60.0;
+const BACK_SEASON_H: u32 = 60;
+const BACK_SEASON_PX: f32 = 36.0;
+
+// Foil: rainbow bands running diagonally, screened over the card
+const FOIL_BANDS: f32 = 3.0;
+const FOIL_MIN: f32 = 0.10;
+const FOIL_MAX: f32 = 0.32;
+const SPARKLES: usize = 18;
+const SPARKLE_MIN_R: f32 = 10.0;
+const SPARKLE_MAX_R: f32 = 26.0;
+// Sparkles stay this far in from the card's edges, clear of most frames
+const SPARKLE_INSET_X: f32 = 56.0;
+const SPARKLE_INSET_Y: f32 = 80.0;
 
 /// Brightness threshold below which frame pixels are treated as transparent.
 const BLACK_THRESHOLD: u16 = 30;
 
-static FONT_BYTES: &[u8] = include_bytes!("../assets/Cinzel-Bold.ttf");
+pub static FONT_BYTES: &[u8] = include_bytes!("../assets/Cinzel-Bold.ttf");
 static FRAME_BYTES: &[u8] = include_bytes!("../assets/card-frame.png");
 static FRAME_INTENT_BYTES: &[u8] = include_bytes!("../assets/card-frame-intent.png");
+static FRAME_CRAFTED_BYTES: &[u8] = include_bytes!("../assets/card-frame-crafted.png");
+static LOGO_BYTES: &[u8] = include_bytes!("../../logo.png");
+
+/// What goes on a card besides its name, art, kind and rarity.
+#[derive(Default)]
+pub struct CardOptions<'a> {
+    /// Holographic foil finish.
+    pub foil: bool,
+    /// Category fit scores, 1-10; the best are printed along the bottom.
+    pub fit: Option<&'a HashMap<String, u32>>,
+    /// Link printed as a QR code in the corner.
+    pub qr_url: Option<&'a str>,
+    /// Set symbol and provenance, for a minted copy.
+    pub stamp: Option<&'a Stamp>,
+    /// Smaller sizes to render along with the card.
+    pub sizes: &'a [Size],
+    /// Language to print the card in; English when unset.
+    pub locale: Option<&'a CardLocale>,
+    /// What to print for each category in `fit`, in the locale's language.
+    /// Categories left out print as they are.
+    pub category_names: Option<&'a HashMap<String, String>>,
+}
+
+impl CardOptions<'_> {
+    /// The fonts the card's text is set in: the locale's, or the template's.
+    pub fn fonts<'t>(&'t self, template: &'t CardTemplate) -> &'t CardFonts {
+        match self.locale.and_then(|locale| locale.font_set.as_deref()) {
+            Some(fonts) => fonts,
+            None => &template.fonts,
+        }
+    }
+
+    pub fn break_anywhere(&self) -> bool {
+        self.locale.is_some_and(|locale| locale.break_anywhere)
+    }
+
+    /// `category` as printed on the card.
+    pub fn category_name<'c>(&'c self, category: &'c str) -> &'c str {
+        self.category_names.and_then(|names| names.get(category)).map_or(category, String::as_str)
+    }
+}
+
+/// A minted copy's set symbol, and which copy it is.
+#[derive(Deserialize, ToSchema)]
+pub struct Stamp {
+    /// Set code printed in the symbol: the season.
+    pub set: String,
+    pub provenance: Provenance,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Provenance {
+    /// The copy minted when the card was discovered.
+    FirstDiscovery,
+    /// The nth copy minted.
+    Edition(u64),
+}
+
+impl Provenance {
+    fn label(&self, words: &StampWords) -> String {
+        match self {
+            Provenance::FirstDiscovery => words.first_discovery.clone(),
+            Provenance::Edition(n) => words.edition.replace("{n}", &n.to_string()),
+        }
+    }
+}
 
+/// A rendered card as a PNG, and scaled down to each size asked for.
+pub struct RenderedCard {
+    pub png: Vec<u8>,
+    pub sizes: Vec<(Size, Vec<u8>)>,
+}
+
+#[tracing::instrument(name = "card.render", skip(template, image_bytes, kind, options))]
 pub fn render_card(
+    template: &CardTemplate,
     name: &str,
     image_bytes: &[u8],
     kind: &CardKind,
-) -> Result<Vec<u8>, String> {
-    let font = FontRef::try_from_slice(FONT_BYTES).map_err(|e| format!("font error: {e}"))?;
+    rarity: Rarity,
+    options: &CardOptions,
+) -> Result<RenderedCard, String> {
+    let mut card = match template.renderer {
+        Renderer::Raster => render_raster(template, name, image_bytes, kind, rarity, options)?,
+        Renderer::Svg => {
+            let svg = card_svg::compose(template, name, image_bytes, kind, rarity, options, false)?;
+            card_svg::rasterize(&svg, options.fonts(template))?
+        }
+    };
 
-    // Load the appropriate frame for the card kind
-    let frame_bytes = match kind {
-        CardKind::Intent => FRAME_INTENT_BYTES,
-        CardKind::Material => FRAME_BYTES,
+    finish(&mut card, template, options)?;
+
+    // Encode to PNG, then each smaller size from the same pixels
+    let card = DynamicImage::ImageRgba8(card);
+    let encode = |img: &DynamicImage| {
+        let mut buf = Cursor::new(Vec::new());
+        img.write_to(&mut buf, image::ImageFormat::Png)
+            .map(|()| buf.into_inner())
+            .map_err(|e| format!("png encode error: {e}"))
+    };
+    let mut rendered = RenderedCard {
+        png: encode(&card)?,
+        sizes: Vec::new(),
     };
+    for &size in options.sizes {
+        if let Some((w, h)) = size.dimensions() {
+            let scaled = card.resize_exact(w, h, FilterType::Lanczos3);
+            rendered.sizes.push((size, encode(&scaled)?));
+        }
+    }
+    Ok(rendered)
+}
 
-    // Resize the frame, making its black interior transparent
-    let mut frame_img = ImageReader::new(Cursor::new(frame_bytes))
-        .with_guessed_format()
-        .map_err(|e| format!("frame format error: {e}"))?
-        .decode()
-        .map_err(|e| format!("frame decode error: {e}"))?
-        .resize_exact(CARD_W, CARD_H, FilterType::Lanczos3)
-        .to_rgba8();
-    remove_black_background(&mut frame_img);
+/// Draw the card pixel by pixel.
+fn render_raster(
+    template: &CardTemplate,
+    name: &str,
+    image_bytes: &[u8],
+    kind: &CardKind,
+    rarity: Rarity,
+    options: &CardOptions,
+) -> Result<RgbaImage, String> {
+    let fonts = options.fonts(template);
+    let frame_img = load_frame(template, kind)?;
 
     // 1. Draw art as full card background
     let art_img = ImageReader::new(Cursor::new(image_bytes))
@@ -72,48 +214,365 @@ pub fn render_card(
     let mut card = resize_cover(&art_img, CARD_W, CARD_H);
 
     // 2. Overlay the ornate frame on top (black interior is now transparent)
-    alpha_overlay(&mut card, &frame_img);
+    alpha_overlay(&mut card, frame_img);
 
     // 3. Draw semi-transparent name banner over the top of the art
-    let (banner_color, name_color) = match kind {
-        CardKind::Intent => (COLOR_INTENT_BANNER, COLOR_INTENT_NAME),
-        CardKind::Material => (COLOR_BANNER, COLOR_NAME),
-    };
+    let style = template.style(kind);
+    let (banner_color, name_color) = (Rgba(style.banner), Rgba(style.name));
+    let layout = layout_name(template, fonts, name, options.break_anywhere());
 
-    let banner_y = CONTENT_Y;
     draw_rounded_rect(
         &mut card,
-        CONTENT_X,
-        banner_y,
-        CONTENT_W,
-        NAME_BANNER_H,
-        BANNER_R,
+        template.content_x,
+        template.content_y,
+        template.content_w,
+        layout.banner_h,
+        template.banner_radius,
         banner_color,
     );
 
-    // 4. Draw name text (centered in banner)
-    let max_name_w = CONTENT_W - 40;
-    let mut name_px = 60.0_f32;
-    loop {
-        let (tw, _) = text_size(PxScale::from(name_px), &font, name);
-        if tw <= max_name_w || name_px <= 22.0 {
-            break;
+    // 4. Draw name text, each line centered in the banner
+    let name_scale = PxScale::from(layout.px);
+    for (line, cy) in layout.lines.iter().zip(layout.centers) {
+        let (line_w, line_h) = fonts.text_size(name_scale, line);
+        let line_x = template.content_x + (template.content_w as i32 - line_w as i32) / 2;
+        let line_y = cy - line_h as i32 / 2;
+        fonts.draw_text(&mut card, name_color, line_x, line_y, name_scale, line);
+    }
+
+    // 5. Print the categories the card fits best along the bottom
+    draw_fit_strip(&mut card, template, options, banner_color, name_color);
+
+    // 6. Set the rarity gem into the bottom of the frame
+    draw_gem(&mut card, template, template.gem(rarity));
+    Ok(card)
+}
+
+/// The back of a card: the collection logo in the template's frame, with
+/// the season below it when there is one. Drawn pixel by pixel whatever the
+/// template's renderer.
+#[tracing::instrument(name = "card.render_back", skip(template))]
+pub fn render_card_back(template: &CardTemplate, season: Option<&str>) -> Result<Vec<u8>, String> {
+    let back = &template.back;
+    let mut card = RgbaImage::from_pixel(CARD_W, CARD_H, Rgba(back.color));
+
+    // Logo across the content width, faded into the background
+    let logo = ImageReader::new(Cursor::new(back.logo_bytes.as_deref().unwrap_or(LOGO_BYTES)))
+        .with_guessed_format()
+        .map_err(|e| format!("logo format error: {e}"))?
+        .decode()
+        .map_err(|e| format!("logo decode error: {e}"))?
+        .resize(template.content_w, CARD_H, FilterType::Lanczos3)
+        .to_rgba8();
+    let logo_x = template.content_x + (template.content_w as i32 - logo.width() as i32) / 2;
+    for (x, y, px) in logo.enumerate_pixels() {
+        let (cx, cy) = (logo_x + x as i32, back.logo_y + y as i32);
+        if cx < 0 || cy < 0 || cx >= CARD_W as i32 || cy >= CARD_H as i32 {
+            continue;
+        }
+        let edge = x.min(y).min(logo.width() - 1 - x).min(logo.height() - 1 - y);
+        let a = (edge as f32 / BACK_LOGO_FADE).min(1.0) * px[3] as f32 / 255.0;
+        let dst = card.get_pixel_mut(cx as u32, cy as u32);
+        for c in 0..3 {
+            dst[c] = (px[c] as f32 * a + dst[c] as f32 * (1.0 - a)).round() as u8;
         }
-        name_px -= 2.0;
     }
-    let name_scale = PxScale::from(name_px);
-    let (name_w, name_h) = text_size(name_scale, &font, name);
-    let name_x = CONTENT_X + (CONTENT_W as i32 - name_w as i32) / 2;
-    let name_y = banner_y + (NAME_BANNER_H as i32 - name_h as i32) / 2;
-    draw_text_mut(&mut card, name_color, name_x, name_y, name_scale, &font, name);
 
-    // Encode to PNG
+    alpha_overlay(&mut card, load_back_frame(back)?);
+
+    // Season marker
+    if let Some(season) = season {
+        let fonts = &template.fonts;
+        let (x, w) = (template.content_x, template.content_w);
+        let r = template.banner_radius;
+        draw_rounded_rect(&mut card, x, back.season_y, w, BACK_SEASON_H, r, Rgba(back.banner));
+        let label = format!("Season {season}");
+        let max_w = w.saturating_sub(40);
+        let px = shrink_to_fit(fonts, &[&label], BACK_SEASON_PX, FIT_MIN_LABEL_PX, max_w);
+        let scale = PxScale::from(px);
+        let (label_w, label_h) = fonts.text_size(scale, &label);
+        let label_x = x + (w as i32 - label_w as i32) / 2;
+        let label_y = back.season_y + (BACK_SEASON_H as i32 - label_h as i32) / 2;
+        fonts.draw_text(&mut card, Rgba(back.text), label_x, label_y, scale, &label);
+    }
+
+    let mut buf = Cursor::new(Vec::new());
+    card.write_to(&mut buf, image::ImageFormat::Png)
+        .map_err(|e| format!("png encode error: {e}"))?;
+    Ok(buf.into_inner())
+}
+
+/// Foil, then the QR code and stamp, over everything else.
+fn finish(
+    card: &mut RgbaImage,
+    template: &CardTemplate,
+    options: &CardOptions,
+) -> Result<(), String> {
+    if options.foil {
+        apply_foil(card);
+    }
+    if let Some(url) = options.qr_url {
+        draw_qr(card, template, url)?;
+    }
+    if let Some(stamp) = options.stamp {
+        draw_stamp(card, template, options, stamp);
+    }
+    Ok(())
+}
+
+/// A card PNG as already rendered, with `options`' foil, QR code and stamp
+/// added. Fit scores and sizes are ignored.
+pub fn finish_png(
+    png: &[u8],
+    template: &CardTemplate,
+    options: &CardOptions,
+) -> Result<Vec<u8>, String> {
+    let mut card = image::load_from_memory(png)
+        .map_err(|e| format!("image decode error: {e}"))?
+        .to_rgba8();
+    finish(&mut card, template, options)?;
     let mut buf = Cursor::new(Vec::new());
     card.write_to(&mut buf, image::ImageFormat::Png)
         .map_err(|e| format!("png encode error: {e}"))?;
     Ok(buf.into_inner())
 }
 
+/// A QR code of `url`, black on white, with its bottom-right corner at the
+/// template's `qr_x`, `qr_y`.
+fn draw_qr(card: &mut RgbaImage, template: &CardTemplate, url: &str) -> Result<(), String> {
+    let code = QrCode::with_error_correction_level(url, EcLevel::M)
+        .map_err(|e| format!("qr code error: {e}"))?;
+    let modules = code.width() as i32;
+    let size = (modules + 2 * QR_QUIET) * QR_MODULE_PX;
+    let (x, y) = (template.qr_x - size, template.qr_y - size);
+    let white = Rgba([255, 255, 255, 255]);
+    draw_rounded_rect(card, x, y, size as u32, size as u32, QR_MODULE_PX as u32, white);
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color == qrcode::Color::Dark {
+            let (col, row) = (i as i32 % modules + QR_QUIET, i as i32 / modules + QR_QUIET);
+            let module = Rect::at(x + col * QR_MODULE_PX, y + row * QR_MODULE_PX)
+                .of_size(QR_MODULE_PX as u32, QR_MODULE_PX as u32);
+            draw_filled_rect_mut(card, module, Rgba([0, 0, 0, 255]));
+        }
+    }
+    Ok(())
+}
+
+/// The set symbol, a disc with the set code in it, then the provenance,
+/// on a pill whose bottom-left corner is at the template's `stamp_x`,
+/// `stamp_y`, worded in `options`' locale.
+fn draw_stamp(card: &mut RgbaImage, template: &CardTemplate, options: &CardOptions, stamp: &Stamp) {
+    let fonts = options.fonts(template);
+    let pad = (STAMP_H as i32 - 2 * STAMP_SYMBOL_R) / 2;
+    let english = StampWords::default();
+    let label = stamp.provenance.label(options.locale.map_or(&english, |locale| &locale.stamp));
+    let scale = PxScale::from(STAMP_PX);
+    let (label_w, label_h) = fonts.text_size(scale, &label);
+    let w = (pad + 2 * STAMP_SYMBOL_R + FIT_GAP + label_w as i32 + 2 * pad) as u32;
+    let (x, y) = (template.stamp_x, template.stamp_y - STAMP_H as i32);
+    let half = STAMP_H / 2;
+    draw_rounded_rect(card, x, y, w, STAMP_H, half, Rgba(template.stamp_bg));
+
+    let (cx, cy) = (x + pad + STAMP_SYMBOL_R, y + half as i32);
+    draw_filled_circle_mut(card, (cx, cy), STAMP_SYMBOL_R, Rgba(template.stamp_text));
+    let max_w = (2 * STAMP_SYMBOL_R - 6) as u32;
+    let set_px = shrink_to_fit(fonts, &[&stamp.set], STAMP_SET_PX, FIT_MIN_LABEL_PX / 2.0, max_w);
+    let set_scale = PxScale::from(set_px);
+    let (set_w, set_h) = fonts.text_size(set_scale, &stamp.set);
+    let (set_x, set_y) = (cx - set_w as i32 / 2, cy - set_h as i32 / 2);
+    let set_color = Rgba([template.stamp_bg[0], template.stamp_bg[1], template.stamp_bg[2], 255]);
+    fonts.draw_text(card, set_color, set_x, set_y, set_scale, &stamp.set);
+
+    let label_x = cx + STAMP_SYMBOL_R + FIT_GAP;
+    let label_y = cy - label_h as i32 / 2;
+    fonts.draw_text(card, Rgba(template.stamp_text), label_x, label_y, scale, &label);
+}
+
+/// Where the back of a card from `season` is served, for NFT metadata and
+/// wallet listings.
+pub fn card_back_path(season: &str) -> String {
+    let mut path = "/api/card-back?season=".to_string();
+    for b in season.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            path.push(b as char);
+        } else {
+            path.push_str(&format!("%{b:02X}"));
+        }
+    }
+    path
+}
+
+/// The template's frame for `kind`, or the built-in one, scaled to the card
+/// with its black interior made transparent. Made on first use and kept
+/// with the template.
+pub fn load_frame<'a>(
+    template: &'a CardTemplate,
+    kind: &CardKind,
+) -> Result<&'a RgbaImage, String> {
+    let style = template.style(kind);
+    cached_frame(&style.frame_image, || {
+        decode_frame(style.frame_bytes.as_deref().unwrap_or(match kind {
+            CardKind::Intent => FRAME_INTENT_BYTES,
+            CardKind::Material => FRAME_BYTES,
+            CardKind::Crafted => FRAME_CRAFTED_BYTES,
+        }))
+    })
+}
+
+/// `load_frame` encoded as a PNG, kept the same way.
+pub fn load_frame_png<'a>(
+    template: &'a CardTemplate,
+    kind: &CardKind,
+) -> Result<&'a [u8], String> {
+    let cell = &template.style(kind).frame_png;
+    if let Some(png) = cell.get() {
+        return Ok(png);
+    }
+    let mut png = Cursor::new(Vec::new());
+    load_frame(template, kind)?
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| format!("png encode error: {e}"))?;
+    Ok(cell.get_or_init(|| png.into_inner()))
+}
+
+/// The back's frame, or the material frame, like `load_frame`.
+pub fn load_back_frame(back: &BackStyle) -> Result<&RgbaImage, String> {
+    cached_frame(&back.frame_image, || {
+        decode_frame(back.frame_bytes.as_deref().unwrap_or(FRAME_BYTES))
+    })
+}
+
+/// `cell`'s frame, made with `make` the first time. Two renders racing to
+/// make it both do the work; one result is kept.
+fn cached_frame(
+    cell: &OnceLock<RgbaImage>,
+    make: impl FnOnce() -> Result<RgbaImage, String>,
+) -> Result<&RgbaImage, String> {
+    if let Some(frame) = cell.get() {
+        return Ok(frame);
+    }
+    let frame = make()?;
+    Ok(cell.get_or_init(|| frame))
+}
+
+fn decode_frame(frame_bytes: &[u8]) -> Result<RgbaImage, String> {
+    let mut frame = ImageReader::new(Cursor::new(frame_bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("frame format error: {e}"))?
+        .decode()
+        .map_err(|e| format!("frame decode error: {e}"))?
+        .resize_exact(CARD_W, CARD_H, FilterType::Lanczos3)
+        .to_rgba8();
+    remove_black_background(&mut frame);
+    Ok(frame)
+}
+
+/// How a card's name is set in its banner.
+pub struct NameLayout {
+    /// One line, or two for a long name.
+    pub lines: Vec<String>,
+    pub px: f32,
+    /// The template's banner height, or taller to hold two lines.
+    pub banner_h: u32,
+    /// Where each line is centered vertically.
+    pub centers: Vec<i32>,
+}
+
+/// Set `name` on one line, shrinking it to fit. A name of several words
+/// that would have to shrink below the template's `wrap_px` goes on two
+/// lines instead, broken to keep the lines even, in a taller banner. With
+/// `break_anywhere`, a name without spaces can break between any two
+/// characters.
+pub fn layout_name(
+    template: &CardTemplate,
+    fonts: &CardFonts,
+    name: &str,
+    break_anywhere: bool,
+) -> NameLayout {
+    let max_w = template.content_w.saturating_sub(40);
+    let single_px = shrink_to_fit(fonts, &[name], template.name_px, template.min_name_px, max_w);
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let breaks: Vec<(String, String)> = if words.len() >= 2 {
+        (1..words.len()).map(|i| (words[..i].join(" "), words[i..].join(" "))).collect()
+    } else if break_anywhere {
+        let name = name.trim();
+        let at = name.char_indices().skip(1).map(|(i, _)| i);
+        at.map(|i| (name[..i].to_string(), name[i..].to_string())).collect()
+    } else {
+        Vec::new()
+    };
+    if single_px >= template.wrap_px || breaks.is_empty() {
+        return NameLayout {
+            lines: vec![name.to_string()],
+            px: single_px,
+            banner_h: template.banner_h,
+            centers: vec![template.content_y + template.banner_h as i32 / 2],
+        };
+    }
+
+    // Break where the longer of the two lines is shortest
+    let scale = PxScale::from(template.wrap_px);
+    let (first, second) = breaks
+        .into_iter()
+        .min_by_key(|(a, b)| fonts.text_size(scale, a).0.max(fonts.text_size(scale, b).0))
+        .expect("a name that wraps has a break");
+    let start_px = template.wrap_px.max(template.min_name_px);
+    let px = shrink_to_fit(fonts, &[&first, &second], start_px, template.min_name_px, max_w);
+    let line_h = (px * NAME_LINE_SPACING).round() as i32;
+    let banner_h = template.banner_h.max(2 * line_h as u32 + 2 * NAME_PAD);
+    let mid = template.content_y + banner_h as i32 / 2;
+    NameLayout {
+        lines: vec![first, second],
+        px,
+        banner_h,
+        centers: vec![mid - line_h / 2, mid + line_h / 2],
+    }
+}
+
+/// The largest size from `px` down to `min_px`, in steps of 2, at which
+/// every one of `lines` fits in `max_w`.
+fn shrink_to_fit(fonts: &CardFonts, lines: &[&str], mut px: f32, min_px: f32, max_w: u32) -> f32 {
+    loop {
+        let widest = lines.iter().map(|line| fonts.text_size(PxScale::from(px), line).0).max();
+        if widest.unwrap_or(0) <= max_w || px <= min_px {
+            return px;
+        }
+        px = (px - 2.0).max(min_px);
+    }
+}
+
+/// Up to `FIT_SHOWN` of the best-scoring categories in `fit`, best first,
+/// with scores held to 1-10.
+pub fn top_fits(fit: &HashMap<String, u32>) -> Vec<(&str, u32)> {
+    let mut top: Vec<(&str, u32)> =
+        fit.iter().map(|(category, score)| (category.as_str(), (*score).clamp(1, 10))).collect();
+    // Highest score first, ties by name so the same scores always print alike
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    top.truncate(FIT_SHOWN);
+    top
+}
+
+/// Width of each category's slot in the fit strip, and the room for its name.
+pub fn fit_slot(template: &CardTemplate, shown: usize) -> (i32, u32) {
+    let slot_w = template.content_w as i32 / shown.max(1) as i32;
+    (slot_w, (slot_w - 2 * FIT_BADGE_R - 3 * FIT_GAP).max(0) as u32)
+}
+
+/// `category` as it fits in `max_w`: shrunk, then cut short if it still
+/// doesn't fit. Returns the text and its size.
+pub fn fit_label(fonts: &CardFonts, category: &str, max_w: u32) -> (String, f32) {
+    let mut label_px = FIT_LABEL_PX;
+    while fonts.text_size(PxScale::from(label_px), category).0 > max_w
+        && label_px > FIT_MIN_LABEL_PX
+    {
+        label_px -= 1.0;
+    }
+    let scale = PxScale::from(label_px);
+    let mut label = category.to_string();
+    while fonts.text_size(scale, &label).0 > max_w && label.pop().is_some() {}
+    (label.trim_end().to_string(), label_px)
+}
+
 /// Make near-black pixels in the frame transparent so the art shows through.
 fn remove_black_background(img: &mut RgbaImage) {
     for pixel in img.pixels_mut() {
@@ -229,6 +688,172 @@ fn draw_rounded_rect(
     }
 }
 
+/// Give a rendered card a holographic foil finish: diagonal rainbow bands
+/// and scattered sparkles. Where the sparkles fall depends on the card, so
+/// applying it to the same card always gives the same image.
+pub fn apply_foil(card: &mut RgbaImage) {
+    let (w, h) = card.dimensions();
+    let span = (w + h) as f32;
+    for (x, y, px) in card.enumerate_pixels_mut() {
+        // Position along the diagonal, 0 at top-left to 1 at bottom-right
+        let t = (x + y) as f32 / span;
+        let band = (t * FOIL_BANDS * std::f32::consts::TAU).sin() * 0.5 + 0.5;
+        let strength = FOIL_MIN + (FOIL_MAX - FOIL_MIN) * band * band;
+        let hue = (t * FOIL_BANDS + (x as f32 / w as f32) * 0.25).fract();
+        let tint = hue_rgb(hue);
+        for c in 0..3 {
+            // Screen blend, so dark areas pick up the color and light ones stay light
+            let d = px[c] as f32 / 255.0;
+            let screened = 1.0 - (1.0 - d) * (1.0 - tint[c]);
+            px[c] = ((d + (screened - d) * strength) * 255.0).round() as u8;
+        }
+    }
+
+    let mut seed = card
+        .pixels()
+        .step_by(997)
+        .fold(0x9E37_79B9_u32, |acc, p| acc.rotate_left(5) ^ u32::from_le_bytes(p.0));
+    let mut next = move || {
+        // xorshift32
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32
+    };
+    for _ in 0..SPARKLES {
+        let cx = SPARKLE_INSET_X + next() * (w as f32 - 2.0 * SPARKLE_INSET_X);
+        let cy = SPARKLE_INSET_Y + next() * (h as f32 - 2.0 * SPARKLE_INSET_Y);
+        let r = SPARKLE_MIN_R + next() * (SPARKLE_MAX_R - SPARKLE_MIN_R);
+        draw_sparkle(card, cx, cy, r);
+    }
+}
+
+/// Fully saturated color for `hue` in 0..1, as 0..1 channels.
+fn hue_rgb(hue: f32) -> [f32; 3] {
+    let h = hue * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    match h as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
+
+/// Lighten a four-pointed star of radius `r` centered on (cx, cy): long
+/// arms along the axes, short ones on the diagonals, and a soft glow.
+fn draw_sparkle(img: &mut RgbaImage, cx: f32, cy: f32, r: f32) {
+    let x0 = (cx - r).max(0.0) as u32;
+    let y0 = (cy - r).max(0.0) as u32;
+    let x1 = ((cx + r) as u32).min(img.width() - 1);
+    let y1 = ((cy + r) as u32).min(img.height() - 1);
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let dx = (x as f32 - cx).abs();
+            let dy = (y as f32 - cy).abs();
+            let dist = (dx * dx + dy * dy).sqrt();
+            if dist > r {
+                continue;
+            }
+            let fade = 1.0 - dist / r;
+            // Arms thin out toward their tips
+            let axis = (1.0 - dx.min(dy) / (1.5 * fade + 0.5)).max(0.0) * fade;
+            let diagonal = (1.0 - (dx - dy).abs() / (fade + 0.5)).max(0.0) * fade * fade * 0.5;
+            let glow = fade.powi(4) * 0.6;
+            let light = axis.max(diagonal).max(glow).min(1.0);
+            let px = img.get_pixel_mut(x, y);
+            for c in 0..3 {
+                px[c] = (px[c] as f32 + (255.0 - px[c] as f32) * light).round() as u8;
+            }
+        }
+    }
+}
+
+/// Draw up to `FIT_SHOWN` of the best-scoring categories in `fit` in a strip
+/// over the bottom of the art, each as a badge holding its 1-10 score next
+/// to the category's name. Draws nothing when there are no scores.
+fn draw_fit_strip(
+    card: &mut RgbaImage,
+    template: &CardTemplate,
+    options: &CardOptions,
+    strip_color: Rgba<u8>,
+    text_color: Rgba<u8>,
+) {
+    let no_fit = HashMap::new();
+    let top = top_fits(options.fit.unwrap_or(&no_fit));
+    if top.is_empty() {
+        return;
+    }
+    let fonts = options.fonts(template);
+
+    draw_rounded_rect(
+        card,
+        template.content_x,
+        template.fit_strip_y,
+        template.content_w,
+        template.fit_strip_h,
+        template.banner_radius,
+        strip_color,
+    );
+    let (slot_w, max_label_w) = fit_slot(template, top.len());
+    let cy = template.fit_strip_y + template.fit_strip_h as i32 / 2;
+    for (i, (category, score)) in top.into_iter().enumerate() {
+        let (label, label_px) = fit_label(fonts, options.category_name(category), max_label_w);
+        let label_scale = PxScale::from(label_px);
+        let (label_w, label_h) = fonts.text_size(label_scale, &label);
+
+        let chip_w = 2 * FIT_BADGE_R + FIT_GAP + label_w as i32;
+        let x = template.content_x + slot_w * i as i32 + (slot_w - chip_w) / 2;
+        let badge = match score {
+            8.. => template.fit_high,
+            5..=7 => template.fit_mid,
+            _ => template.fit_low,
+        };
+        draw_filled_circle_mut(card, (x + FIT_BADGE_R, cy), FIT_BADGE_R, Rgba(badge));
+        let score_scale = PxScale::from(FIT_SCORE_PX);
+        let score = score.to_string();
+        let (score_w, score_h) = fonts.text_size(score_scale, &score);
+        let score_x = x + FIT_BADGE_R - score_w as i32 / 2;
+        let score_y = cy - score_h as i32 / 2;
+        let score_color = Rgba(template.fit_score);
+        fonts.draw_text(card, score_color, score_x, score_y, score_scale, &score);
+
+        let label_x = x + 2 * FIT_BADGE_R + FIT_GAP;
+        let label_y = cy - label_h as i32 / 2;
+        fonts.draw_text(card, text_color, label_x, label_y, label_scale, &label);
+    }
+}
+
+/// Draw a faceted diamond gem where `template` places it: an `edge`-colored
+/// rim, the upper-left facet `lit` and the rest `body`.
+fn draw_gem(img: &mut RgbaImage, template: &CardTemplate, [body, lit, edge]: [Color; 3]) {
+    let (cx, cy) = (template.gem_x, template.gem_y);
+    let (rx, ry) = (template.gem_rx.max(1), template.gem_ry.max(1));
+    for y in cy - ry..=cy + ry {
+        for x in cx - rx..=cx + rx {
+            if x < 0 || y < 0 || x >= img.width() as i32 || y >= img.height() as i32 {
+                continue;
+            }
+            let dx = (x - cx) as f32 / rx as f32;
+            let dy = (y - cy) as f32 / ry as f32;
+            let d = dx.abs() + dy.abs();
+            if d > 1.0 {
+                continue;
+            }
+            let color = if d > 1.0 - GEM_EDGE {
+                edge
+            } else if dx <= 0.0 && dy <= 0.0 {
+                lit
+            } else {
+                body
+            };
+            img.put_pixel(x as u32, y as u32, Rgba(color));
+        }
+    }
+}
+
 fn corner_inside(px: u32, py: u32, cx: f64, cy: f64, r: f64) -> bool {
     let dx = px as f64 + 0.5 - cx;
     let dy = py as f64 + 0.5 - cy;
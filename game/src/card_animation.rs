@@ -0,0 +1,146 @@
+//! Animated cards: a card as rendered, looping with a warm glow breathing
+//! around its edge and motes of light drifting up over it, as an APNG. The
+//! first frame is the still card; every later one is only the light, on a
+//! transparent canvas drawn over it, so the file stays near the PNG's size.
+
+use image::RgbaImage;
+use png::{BlendOp, DisposeOp};
+
+/// Frames in one loop, and how long each shows.
+const FRAMES: u32 = 24;
+const FRAME_MS: u16 = 80;
+
+/// Glow color, and how far it reaches in from the edge and how bright it
+/// gets, at full card width.
+const LIGHT: [u8; 3] = [255, 222, 160];
+const GLOW_PX: f32 = 36.0;
+const GLOW_MAX: f32 = 0.55;
+
+/// Motes: how many, their radius range at full card width, and how far up
+/// the card each drifts over its fade in and out.
+const MOTES: usize = 16;
+const MOTE_MIN_R: f32 = 3.0;
+const MOTE_MAX_R: f32 = 7.0;
+const MOTE_RISE: f32 = 0.3;
+
+/// `card` as a looping APNG.
+pub fn animate(card: &RgbaImage) -> Result<Vec<u8>, String> {
+    let (w, h) = card.dimensions();
+    let scale = w as f32 / crate::card::CARD_W as f32;
+    let motes = place_motes(card, scale);
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, w, h);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(FRAMES, 0).map_err(apng_error)?;
+    encoder.set_frame_delay(FRAME_MS, 1000).map_err(apng_error)?;
+    let mut writer = encoder.write_header().map_err(apng_error)?;
+
+    // The still card stays on the canvas; each later frame is drawn over
+    // it and cleared back to it
+    writer.set_blend_op(BlendOp::Source).map_err(apng_error)?;
+    writer.set_dispose_op(DisposeOp::None).map_err(apng_error)?;
+    writer.write_image_data(card.as_raw()).map_err(apng_error)?;
+    writer.set_blend_op(BlendOp::Over).map_err(apng_error)?;
+    writer.set_dispose_op(DisposeOp::Previous).map_err(apng_error)?;
+    for frame in 1..FRAMES {
+        let light = draw_light(w, h, scale, &motes, frame as f32 / FRAMES as f32);
+        writer.write_image_data(light.as_raw()).map_err(apng_error)?;
+    }
+    writer.finish().map_err(apng_error)?;
+    Ok(png)
+}
+
+fn apng_error(e: png::EncodingError) -> String {
+    format!("apng error: {e}")
+}
+
+/// A mote's start, size, and where in the loop it is at the start.
+struct Mote {
+    x: f32,
+    y: f32,
+    r: f32,
+    phase: f32,
+}
+
+/// Motes scattered over the art, the same ones every time for a card.
+fn place_motes(card: &RgbaImage, scale: f32) -> Vec<Mote> {
+    let (w, h) = (card.width() as f32, card.height() as f32);
+    let mut seed = card
+        .pixels()
+        .step_by(991)
+        .fold(0x85EB_CA6B_u32, |acc, p| acc.rotate_left(7) ^ u32::from_le_bytes(p.0));
+    let mut next = move || {
+        // xorshift32
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32
+    };
+    (0..MOTES)
+        .map(|_| Mote {
+            x: w * (0.1 + 0.8 * next()),
+            // Low enough to rise without leaving the card
+            y: h * (0.35 + 0.55 * next()),
+            r: scale * (MOTE_MIN_R + next() * (MOTE_MAX_R - MOTE_MIN_R)),
+            phase: next(),
+        })
+        .collect()
+}
+
+/// The light at `t` (0..1 through the loop) on a transparent canvas. It
+/// all breathes together, from nothing at the start of the loop, so the
+/// still card is where each loop begins and ends.
+fn draw_light(w: u32, h: u32, scale: f32, motes: &[Mote], t: f32) -> RgbaImage {
+    let pulse = 0.5 - 0.5 * (t * std::f32::consts::TAU).cos();
+    let mut light = RgbaImage::new(w, h);
+    let mut lighten = |x: u32, y: u32, a: f32| {
+        let px = light.get_pixel_mut(x, y);
+        let a = (a.clamp(0.0, 1.0) * 255.0).round() as u8;
+        if a > px[3] {
+            *px = image::Rgba([LIGHT[0], LIGHT[1], LIGHT[2], a]);
+        }
+    };
+
+    // Edge glow: only the band along the edges is visited
+    let reach = GLOW_PX * scale;
+    let band = reach.ceil() as u32;
+    for y in 0..h {
+        let inside = y >= band && y < h.saturating_sub(band);
+        let spans = if inside {
+            [0..band.min(w), w.saturating_sub(band)..w]
+        } else {
+            [0..w, w..w]
+        };
+        for x in spans.into_iter().flatten() {
+            let edge = x.min(y).min(w - 1 - x).min(h - 1 - y) as f32;
+            let falloff = (1.0 - edge / reach).max(0.0);
+            lighten(x, y, falloff * falloff * GLOW_MAX * pulse);
+        }
+    }
+
+    // Motes, each fading in low and out high over its own cycle
+    for mote in motes {
+        let cycle = (t + mote.phase).fract();
+        let cy = mote.y - cycle * MOTE_RISE * h as f32;
+        let fade = (cycle * std::f32::consts::PI).sin() * pulse;
+        let glow_r = mote.r * 2.5;
+        let (x0, y0) = ((mote.x - glow_r).max(0.0) as u32, (cy - glow_r).max(0.0) as u32);
+        let x1 = ((mote.x + glow_r) as u32).min(w - 1);
+        let y1 = ((cy + glow_r) as u32).min(h - 1);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let dist = ((x as f32 - mote.x).powi(2) + (y as f32 - cy).powi(2)).sqrt();
+                // A bright core in a soft halo
+                let a = if dist <= mote.r {
+                    1.0
+                } else {
+                    (1.0 - (dist - mote.r) / (glow_r - mote.r)).max(0.0).powi(2) * 0.6
+                };
+                lighten(x, y, a * fade);
+            }
+        }
+    }
+    light
+}
@@ -1,7 +1,13 @@
+use crate::config::CardCacheSettings;
+use crate::migrate;
+use crate::storage::CardStore;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CachedCard {
@@ -14,41 +20,543 @@ pub struct CachedCard {
     pub discovered: bool,
     #[serde(default)]
     pub impossible: bool,
+    /// The inputs that produced this card. Absent for entries cached before
+    /// provenance was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipe: Option<Recipe>,
+    /// Unix seconds when a player first discovered the card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovered_at: Option<u64>,
+    /// Unix seconds when the card was first generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    /// Successful combines that produced this card, cache hits included.
+    #[serde(default)]
+    pub times_crafted: u64,
+    /// The generation server's `combine_version` when it made this verdict.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub combine_version: Option<String>,
+    /// Wallet that discovered the card, when it was discovered signed in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovered_by: Option<String>,
+    /// Art the card had before its current `image_path`, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_art: Vec<ArtVersion>,
+    /// The art `image_path` was rendered from, kept so the card can be
+    /// rendered again. Absent for cards rendered before art was kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub art_path: Option<String>,
+}
+
+/// Art a card had until it was replaced. The file stays where it was, so
+/// anything still pointing at it (an NFT's metadata, a game in progress)
+/// keeps working.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ArtVersion {
+    pub image_path: String,
+    /// Unix seconds when it was replaced.
+    pub replaced_at: u64,
+}
+
+/// Input card ids for a crafted card, in the same form used to derive its id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recipe {
+    /// Sorted material (or crafted) card ids.
+    pub material_ids: Vec<String>,
+    pub intent_id: Option<String>,
+}
+
+impl Recipe {
+    pub fn new(material_ids: &[&str], intent_id: Option<&str>) -> Self {
+        let mut material_ids: Vec<String> = material_ids.iter().map(|id| id.to_string()).collect();
+        material_ids.sort();
+        Recipe {
+            material_ids,
+            intent_id: intent_id.map(|id| id.to_string()),
+        }
+    }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+/// Crafted cards (and impossible combinations) keyed by crafted card id: a
+/// bounded in-memory layer over the configured `CardStore`. Storage errors
+/// are logged and read as a miss, so a flaky database degrades to
+/// regenerating cards rather than failing requests.
 pub struct CardCache {
-    entries: HashMap<String, CachedCard>,
+    store: Arc<dyn CardStore>,
+    /// Recently read cards, so hot combinations skip the database.
+    hot: Mutex<HotCache>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    max_impossible: usize,
+    /// 0 keeps impossible verdicts until the combine version changes.
+    impossible_ttl_secs: u64,
+    /// Latest `combine_version` reported by the generation server.
+    combine_version: Mutex<Option<String>>,
+    /// Recipes for async-image combines, held until finalize writes the card.
+    pending_recipes: Mutex<HashMap<String, Recipe>>,
 }
 
 impl CardCache {
-    pub fn load(path: &Path) -> Self {
-        match std::fs::read_to_string(path) {
-            Ok(data) => serde_json::from_str(&data).unwrap_or_default(),
-            Err(_) => Self::default(),
+    pub fn new(store: Arc<dyn CardStore>, settings: &CardCacheSettings) -> Self {
+        let cache = CardCache {
+            store,
+            hot: Mutex::new(HotCache::new(settings.hot_entries)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            max_impossible: settings.max_impossible,
+            impossible_ttl_secs: settings.impossible_ttl_secs,
+            combine_version: Mutex::new(None),
+            pending_recipes: Mutex::new(HashMap::new()),
+        };
+        cache.prune_impossible();
+        cache
+    }
+
+    /// Import a legacy `card-cache.json`, then rename it to `*.imported` so it
+    /// isn't imported again. Returns the number of cards imported.
+    pub fn import_json(&self, path: &Path) -> Result<usize, String> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let legacy = serde_json::from_str(&data)
+            .map_err(|e| e.to_string())
+            .and_then(CacheDump::from_json)
+            .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+
+        let summary = self.store.merge(dump_cards(legacy.entries), ConflictStrategy::Overwrite)?;
+        self.store.add_mint_counts(&legacy.mint_counts)?;
+        self.prune_impossible();
+
+        let mut done = path.as_os_str().to_owned();
+        done.push(".imported");
+        if let Err(e) = std::fs::rename(path, &done) {
+            log::warn!("Imported {} but could not rename it: {e}", path.display());
+        }
+        Ok(summary.imported)
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedCard> {
+        if let Some(card) = self.hot.lock().unwrap().get(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(card);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let card = self.store.get(key).unwrap_or_else(|e| {
+            log::error!("Card cache read failed for {key}: {e}");
+            None
+        })?;
+        self.hot.lock().unwrap().put(card.clone());
+        Some(card)
+    }
+
+    pub fn insert(&self, key: String, mut card: CachedCard) {
+        card.id = key;
+        if let Err(e) = self.store.put(&card) {
+            log::error!("Card cache write failed for {}: {e}", card.id);
+            self.hot.lock().unwrap().remove(&card.id);
+            return;
+        }
+        let impossible = card.impossible;
+        self.hot.lock().unwrap().put(card);
+        if impossible {
+            self.prune_impossible();
         }
     }
 
-    pub fn save(&self, path: &Path) {
-        if let Some(parent) = path.parent() {
-            let _ = std::fs::create_dir_all(parent);
+    pub fn remove(&self, key: &str) {
+        if let Err(e) = self.store.remove(key) {
+            log::error!("Card cache delete failed for {key}: {e}");
         }
-        if let Ok(data) = serde_json::to_string_pretty(self) {
-            let _ = std::fs::write(path, data);
+        self.hot.lock().unwrap().remove(key);
+    }
+
+    /// Whether a cached result should be generated again instead of used.
+    /// Discovered cards are canon and never are. Anything else is when it
+    /// doesn't carry the combine version the generation server runs now (it
+    /// came from another model or prompt, or predates versioning), and "not
+    /// possible" verdicts also once they are older than `impossible_ttl_secs`.
+    pub fn needs_recheck(&self, card: &CachedCard) -> bool {
+        if card.discovered {
+            return false;
         }
+        let current = self.combine_version.lock().unwrap();
+        let outdated = current
+            .as_ref()
+            .is_some_and(|v| card.combine_version.as_ref() != Some(v));
+        let expired = card.impossible
+            && self.impossible_ttl_secs > 0
+            && card
+                .created_at
+                .is_none_or(|t| now_secs().saturating_sub(t) >= self.impossible_ttl_secs);
+        outdated || expired
     }
 
-    pub fn get(&self, key: &str) -> Option<&CachedCard> {
-        self.entries.get(key)
+    /// Delete the undiscovered cards and "not possible" verdicts that
+    /// `needs_recheck` would regenerate anyway because they don't carry
+    /// `current_version`. Returns their ids.
+    pub fn remove_outdated(&self, current_version: &str) -> Result<Vec<String>, String> {
+        let removed = self.store.remove_outdated(current_version)?;
+        self.forget(&removed);
+        Ok(removed)
     }
 
-    pub fn insert(&mut self, key: String, card: CachedCard) {
-        self.entries.insert(key, card);
+    pub fn combine_version(&self) -> Option<String> {
+        self.combine_version.lock().unwrap().clone()
     }
 
-    pub fn all_entries(&self) -> impl Iterator<Item = (&String, &CachedCard)> {
-        self.entries.iter()
+    pub fn set_combine_version(&self, version: &str) {
+        let mut current = self.combine_version.lock().unwrap();
+        if current.as_deref() != Some(version) {
+            log::info!("Generation server combine version is {version}");
+            *current = Some(version.to_string());
+        }
+    }
+
+    /// Drop the oldest impossible entries beyond `max_impossible`.
+    fn prune_impossible(&self) {
+        if self.max_impossible == 0 {
+            return;
+        }
+        match self.store.prune_impossible(self.max_impossible) {
+            Ok(removed) if !removed.is_empty() => {
+                let mut hot = self.hot.lock().unwrap();
+                for id in &removed {
+                    hot.remove(id);
+                }
+                log::info!("Pruned {} old impossible combinations", removed.len());
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to prune impossible combinations: {e}"),
+        }
+    }
+
+    /// Every cached card and mint count.
+    pub fn export(&self) -> Result<CacheDump, String> {
+        let entries = self
+            .store
+            .all()?
+            .into_iter()
+            .map(|card| (card.id.clone(), card))
+            .collect();
+        Ok(CacheDump {
+            schema_version: migrate::CACHE_DUMP_VERSION,
+            entries,
+            mint_counts: self.store.mint_counts()?,
+        })
     }
+
+    /// Merge a dump's cards in one transaction. Mint counts are left alone:
+    /// they describe mints made through the server that exported them.
+    pub fn merge(
+        &self,
+        dump: CacheDump,
+        strategy: ConflictStrategy,
+    ) -> Result<MergeSummary, String> {
+        let summary = self.store.merge(dump_cards(dump.entries), strategy)?;
+        self.clear_hot();
+        self.prune_impossible();
+        Ok(summary)
+    }
+
+    /// Forget the in-memory copies, e.g. after scripts edited the database.
+    /// Other servers sharing the store are told to do the same.
+    pub fn clear_hot(&self) {
+        if let Err(e) = self.store.invalidate_all() {
+            log::error!("Failed to invalidate shared card copies: {e}");
+        }
+        self.forget(&[]);
+    }
+
+    /// Drop the in-memory copies of `ids` (all of them when empty) because
+    /// another server changed them.
+    pub fn forget(&self, ids: &[String]) {
+        let mut hot = self.hot.lock().unwrap();
+        if ids.is_empty() {
+            hot.clear();
+        }
+        for id in ids {
+            hot.remove(id);
+        }
+    }
+
+    pub fn stats(&self) -> Result<CacheStats, String> {
+        let counts = self.store.counts()?;
+        let combine_versions = self
+            .store
+            .version_counts()?
+            .into_iter()
+            .map(|(v, n)| (v.unwrap_or_else(|| "untagged".to_string()), n))
+            .collect();
+        let hot = self.hot.lock().unwrap();
+        Ok(CacheStats {
+            hot_entries: hot.entries.len(),
+            hot_capacity: hot.capacity,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            counts,
+            max_impossible: self.max_impossible,
+            combine_version: self.combine_version(),
+            combine_versions,
+        })
+    }
+
+    pub fn all_entries(&self) -> Vec<CachedCard> {
+        self.store.all().unwrap_or_else(|e| {
+            log::error!("{e}");
+            Vec::new()
+        })
+    }
+
+    /// The page of cards `query` asks for and how many match in all, read
+    /// on the blocking pool.
+    pub fn list(
+        &self,
+        query: CardQuery,
+    ) -> impl Future<Output = Result<(Vec<CachedCard>, u64), String>> {
+        let store = self.store.clone();
+        async move {
+            tokio::task::spawn_blocking(move || store.list(&query))
+                .await
+                .map_err(|e| e.to_string())?
+        }
+    }
+
+    /// Id and name of every possible card, read on the blocking pool.
+    pub fn names(&self) -> impl Future<Output = Result<Vec<(String, String)>, String>> {
+        let store = self.store.clone();
+        async move {
+            tokio::task::spawn_blocking(move || store.names())
+                .await
+                .map_err(|e| e.to_string())?
+        }
+    }
+
+    /// Discovered, finished crafted cards (the pool packs draw from).
+    pub fn discovered_cards(&self) -> Vec<CachedCard> {
+        self.store.discovered().unwrap_or_else(|e| {
+            log::error!("{e}");
+            Vec::new()
+        })
+    }
+
+    /// Cheap query used by the readiness check.
+    pub fn ping(&self) -> Result<(), String> {
+        self.store.ping()
+    }
+
+    /// Count another combine that produced `card_id` from the cache.
+    pub fn record_craft(&self, card_id: &str) {
+        if let Err(e) = self.store.record_craft(card_id) {
+            log::error!("Failed to record craft of {card_id}: {e}");
+        }
+        self.hot.lock().unwrap().remove(card_id);
+    }
+
+    pub fn record_mint(&self, card_id: &str) {
+        if let Err(e) = self.store.record_mint(card_id) {
+            log::error!("Failed to record mint of {card_id}: {e}");
+        }
+    }
+
+    pub fn mint_count(&self, card_id: &str) -> u64 {
+        self.store.mint_count(card_id).unwrap_or(0)
+    }
+
+    /// Take the lowest edition number of `card_id` a mint that never landed
+    /// gave back, else the next one, or `None` once `cap` of them have been
+    /// issued.
+    pub fn issue_edition(&self, card_id: &str, cap: Option<u64>) -> Result<Option<u64>, String> {
+        self.store.issue_edition(card_id, cap)
+    }
+
+    pub fn editions(&self, card_id: &str) -> u64 {
+        self.store.editions(card_id).unwrap_or(0)
+    }
+
+    pub fn set_pending_recipe(&self, key: String, recipe: Recipe) {
+        self.pending_recipes.lock().unwrap().insert(key, recipe);
+    }
+
+    pub fn take_pending_recipe(&self, key: &str) -> Option<Recipe> {
+        self.pending_recipes.lock().unwrap().remove(key)
+    }
+}
+
+/// Dump entries are keyed by id; the key wins over any `id` in the body.
+fn dump_cards(entries: HashMap<String, CachedCard>) -> Vec<CachedCard> {
+    entries
+        .into_iter()
+        .map(|(key, mut card)| {
+            card.id = key;
+            card
+        })
+        .collect()
+}
+
+/// Reported by `GET /api/admin/card-cache`.
+#[derive(Serialize)]
+pub struct CacheStats {
+    pub hot_entries: usize,
+    pub hot_capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    #[serde(flatten)]
+    pub counts: CardCounts,
+    /// 0 means unlimited.
+    pub max_impossible: usize,
+    /// What the generation server runs now, once known.
+    pub combine_version: Option<String>,
+    /// Entries per combine version that produced them.
+    pub combine_versions: BTreeMap<String, u64>,
+}
+
+/// A page of the possible (not impossible) cards, ordered by name
+/// (case-insensitive) then id.
+pub struct CardQuery {
+    /// Skip cards nobody has discovered yet.
+    pub discovered: bool,
+    /// Case-insensitive substring of the name.
+    pub name: Option<String>,
+    pub descending: bool,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl CardQuery {
+    /// `name` as a `LIKE` pattern, escaped with backslashes.
+    pub fn name_pattern(&self) -> Option<String> {
+        self.name.as_ref().map(|name| {
+            let escaped = name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            format!("%{escaped}%")
+        })
+    }
+}
+
+/// Entry counts straight from the store.
+#[derive(Serialize)]
+pub struct CardCounts {
+    pub entries: u64,
+    pub discovered: u64,
+    /// Generated (e.g. by the scripts) but not yet crafted by a player.
+    pub undiscovered: u64,
+    pub impossible_entries: u64,
+    /// Cards and impossible entries created in the last hour / day.
+    pub created_last_hour: u64,
+    pub created_last_day: u64,
+}
+
+impl CardCounts {
+    pub fn new(entries: u64, discovered: u64, impossible: u64, hour: u64, day: u64) -> Self {
+        CardCounts {
+            entries,
+            discovered,
+            undiscovered: entries.saturating_sub(discovered + impossible),
+            impossible_entries: impossible,
+            created_last_hour: hour,
+            created_last_day: day,
+        }
+    }
+}
+
+/// A bounded least-recently-used map of cards by id.
+struct HotCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (CachedCard, u64)>,
+    /// Last-use tick → id, oldest first.
+    recency: BTreeMap<u64, String>,
+}
+
+impl HotCache {
+    fn new(capacity: usize) -> Self {
+        HotCache {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<CachedCard> {
+        self.tick += 1;
+        let (card, used) = self.entries.get_mut(key)?;
+        self.recency.remove(used);
+        *used = self.tick;
+        self.recency.insert(self.tick, key.to_string());
+        Some(card.clone())
+    }
+
+    fn put(&mut self, card: CachedCard) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        let key = card.id.clone();
+        if let Some((_, used)) = self.entries.insert(key.clone(), (card, self.tick)) {
+            self.recency.remove(&used);
+        }
+        self.recency.insert(self.tick, key);
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((_, used)) = self.entries.remove(key) {
+            self.recency.remove(&used);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+}
+
+/// The whole cache as JSON: the pre-SQLite `card-cache.json` layout, also
+/// used by the admin export/import endpoints.
+#[derive(Serialize, Deserialize)]
+pub struct CacheDump {
+    /// See `migrate::CACHE_DUMP_VERSION`; absent in `card-cache.json`.
+    #[serde(default)]
+    pub schema_version: u32,
+    pub entries: HashMap<String, CachedCard>,
+    #[serde(default)]
+    pub mint_counts: HashMap<String, u64>,
+}
+
+impl CacheDump {
+    /// Parse a dump of any known version, upgrading it to the current one.
+    pub fn from_json(value: serde_json::Value) -> Result<Self, String> {
+        let value = migrate::cache_dump(value)?;
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+}
+
+/// What to do when an imported card id is already cached.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    #[default]
+    KeepExisting,
+    Overwrite,
+}
+
+#[derive(Serialize)]
+pub struct MergeSummary {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+/// Current time as Unix seconds, for `created_at` and `discovered_at`.
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
 }
 
 /// Compute the ID for a base card: SHA-256 of lowercase name, first 12 hex chars.
@@ -70,3 +578,35 @@ pub fn compute_crafted_card_id(material_ids: &[&str], intent_id: Option<&str>) -
     let hash = Sha256::digest(key.as_bytes());
     format!("{:x}", hash)[..12].to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crafted_card_id_ignores_material_order() {
+        let id = compute_crafted_card_id(&["fire", "water"], None);
+        assert_eq!(id.len(), 12);
+        assert_eq!(id, compute_crafted_card_id(&["water", "fire"], None));
+        let expected = format!("{:x}", Sha256::digest(b"fire+water"));
+        assert_eq!(id, expected[..12]);
+    }
+
+    #[test]
+    fn crafted_card_id_depends_on_the_intent() {
+        let plain = compute_crafted_card_id(&["stone", "wood"], None);
+        let tool = compute_crafted_card_id(&["stone", "wood"], Some("tool"));
+        assert_ne!(plain, tool);
+        let expected = format!("{:x}", Sha256::digest(b"stone+wood+[tool]"));
+        assert_eq!(tool, expected[..12]);
+        // An intent isn't just another material
+        assert_ne!(tool, compute_crafted_card_id(&["stone", "tool", "wood"], None));
+    }
+
+    #[test]
+    fn recipe_sorts_its_materials() {
+        let recipe = Recipe::new(&["wood", "stone"], Some("tool"));
+        assert_eq!(recipe, Recipe::new(&["stone", "wood"], Some("tool")));
+        assert_eq!(recipe.material_ids, ["stone", "wood"]);
+    }
+}
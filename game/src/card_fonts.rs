@@ -0,0 +1,184 @@
+//! The fonts card text is set in: Cinzel, then fallbacks (listed in
+//! card-templates.json) for the characters it lacks, such as CJK, Greek or
+//! Cyrillic. Each character is drawn with the first font that has it. A
+//! locale can put its own fonts ahead of them all (see `preferring`).
+
+use crate::card::FONT_BYTES;
+use ab_glyph::{Font, FontArc, FontVec, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+use resvg::usvg::fontdb;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Family name inside Cinzel-Bold.ttf.
+pub const PRIMARY_FAMILY: &str = "Cinzel";
+
+pub struct CardFonts {
+    /// Cinzel first, then the fallbacks in order.
+    fonts: Vec<FontArc>,
+    /// Where each font was read from, Cinzel's left empty.
+    paths: Vec<PathBuf>,
+    /// The same fonts, for the SVG renderer.
+    pub database: Arc<fontdb::Database>,
+    /// Family names of the fonts, in order.
+    pub families: Vec<String>,
+}
+
+impl Default for CardFonts {
+    /// Just Cinzel.
+    fn default() -> Self {
+        CardFonts::new(&[]).expect("the built-in font loads")
+    }
+}
+
+impl CardFonts {
+    /// Cinzel followed by the fonts in `fallbacks`. A missing file is
+    /// skipped, so the list can name fonts that only some hosts have
+    /// installed; a file that isn't a font is an error. Font collections
+    /// (.ttc) use their first font.
+    pub fn new(fallbacks: &[PathBuf]) -> Result<Self, String> {
+        let mut fonts = CardFonts {
+            fonts: Vec::new(),
+            paths: Vec::new(),
+            database: Arc::new(fontdb::Database::new()),
+            families: Vec::new(),
+        };
+        fonts.push(PathBuf::new(), FONT_BYTES.to_vec())?;
+        for path in fallbacks {
+            if let Some(bytes) = read_font(path)? {
+                fonts.push(path.clone(), bytes)?;
+            }
+        }
+        Ok(fonts)
+    }
+
+    /// These fonts with the ones in `preferred` ahead of them all, Cinzel
+    /// included, for a locale set in its own typeface. Fonts already loaded
+    /// here are shared rather than read again; missing files are skipped as
+    /// in `new`.
+    pub fn preferring(&self, preferred: &[PathBuf]) -> Result<Self, String> {
+        let mut fonts = CardFonts {
+            fonts: Vec::new(),
+            paths: Vec::new(),
+            database: self.database.clone(),
+            families: Vec::new(),
+        };
+        for path in preferred {
+            if let Some(i) = self.paths.iter().position(|loaded| loaded == path) {
+                fonts.fonts.push(self.fonts[i].clone());
+                fonts.paths.push(path.clone());
+                fonts.families.extend(self.families.get(i).cloned());
+            } else if let Some(bytes) = read_font(path)? {
+                fonts.push(path.clone(), bytes)?;
+            }
+        }
+        for (i, font) in self.fonts.iter().enumerate() {
+            if !fonts.paths.contains(&self.paths[i]) {
+                fonts.fonts.push(font.clone());
+                fonts.paths.push(self.paths[i].clone());
+                fonts.families.extend(self.families.get(i).cloned());
+            }
+        }
+        Ok(fonts)
+    }
+
+    /// Load the font in `bytes` after the others.
+    fn push(&mut self, path: PathBuf, bytes: Vec<u8>) -> Result<(), String> {
+        let name = if path.as_os_str().is_empty() {
+            PRIMARY_FAMILY.to_string()
+        } else {
+            path.display().to_string()
+        };
+        let font = FontVec::try_from_vec_and_index(bytes.clone(), 0)
+            .map_err(|e| format!("Failed to load font {name}: {e}"))?;
+        let database = Arc::make_mut(&mut self.database);
+        let ids = database.load_font_source(fontdb::Source::Binary(Arc::new(bytes)));
+        let family = ids.first().and_then(|id| database.face(*id));
+        if let Some((family, _)) = family.and_then(|face| face.families.first()) {
+            self.families.push(family.clone());
+        }
+        self.fonts.push(FontArc::new(font));
+        self.paths.push(path);
+        Ok(())
+    }
+
+    /// Cinzel, or a locale's first font, whose metrics the layout follows.
+    pub fn primary(&self) -> &FontArc {
+        &self.fonts[0]
+    }
+
+    /// Width and height of `text`, as imageproc's `text_size` measures them.
+    pub fn text_size(&self, scale: PxScale, text: &str) -> (u32, u32) {
+        self.runs(text).iter().fold((0, 0), |(w, h), (font, run)| {
+            let (run_w, run_h) = text_size(scale, *font, run);
+            (w + run_w, h.max(run_h))
+        })
+    }
+
+    /// Draw `text` with its top-left at (x, y), every run on Cinzel's
+    /// baseline.
+    pub fn draw_text(
+        &self,
+        img: &mut RgbaImage,
+        color: Rgba<u8>,
+        x: i32,
+        y: i32,
+        scale: PxScale,
+        text: &str,
+    ) {
+        let baseline = y as f32 + self.primary().as_scaled(scale).ascent();
+        let mut x = x;
+        for (font, run) in self.runs(text) {
+            let run_y = (baseline - font.as_scaled(scale).ascent()).round() as i32;
+            draw_text_mut(img, color, x, run_y, scale, font, run);
+            x += text_size(scale, font, run).0 as i32;
+        }
+    }
+
+    /// `text` split into runs that share a font: for each character, the
+    /// first font that has it, or the primary font when none do.
+    fn runs<'a>(&'a self, text: &'a str) -> Vec<(&'a FontArc, &'a str)> {
+        let mut runs: Vec<(&FontArc, &str)> = Vec::new();
+        let mut start = 0;
+        let mut current: Option<&FontArc> = None;
+        for (i, c) in text.char_indices() {
+            let font = self.font_for(c);
+            match current {
+                Some(prev) if std::ptr::eq(prev, font) => {}
+                Some(prev) => {
+                    runs.push((prev, &text[start..i]));
+                    start = i;
+                    current = Some(font);
+                }
+                None => current = Some(font),
+            }
+        }
+        if let Some(font) = current {
+            runs.push((font, &text[start..]));
+        }
+        runs
+    }
+
+    fn font_for(&self, c: char) -> &FontArc {
+        if c.is_whitespace() {
+            return self.primary();
+        }
+        self.fonts
+            .iter()
+            .find(|font| font.glyph_id(c).0 != 0)
+            .unwrap_or(self.primary())
+    }
+}
+
+/// The font file at `path`, or None when there's no such file.
+fn read_font(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::warn!("Font {} not found, skipping it", path.display());
+            Ok(None)
+        }
+        Err(e) => Err(format!("Failed to read font {}: {e}", path.display())),
+    }
+}
@@ -0,0 +1,191 @@
+//! Cards composed as SVG: the art and frame embedded as images, and the
+//! name, fit strip and gem as real text and shapes. Rasterized with resvg
+//! for templates that ask for it, and exported as-is for print, where the
+//! text stays sharp at any size. Laid out exactly like the raster renderer.
+
+use crate::card::{
+    self, CardKind, CardOptions, CARD_H, CARD_W, FIT_BADGE_R, FIT_GAP, FIT_SCORE_PX, GEM_EDGE,
+};
+use crate::card_fonts::{CardFonts, PRIMARY_FAMILY};
+use crate::card_template::{CardTemplate, Color};
+use crate::packs::Rarity;
+use ab_glyph::{Font, PxScale};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::RgbaImage;
+use resvg::{tiny_skia, usvg};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// The card as an SVG document. `embed_font` includes Cinzel, so the
+/// document looks right wherever it's opened; fallback fonts are named but
+/// not embedded.
+pub fn compose(
+    template: &CardTemplate,
+    name: &str,
+    image_bytes: &[u8],
+    kind: &CardKind,
+    rarity: Rarity,
+    options: &CardOptions,
+    embed_font: bool,
+) -> Result<String, String> {
+    let fonts = options.fonts(template);
+    let style = template.style(kind);
+    let art_mime = image::guess_format(image_bytes)
+        .map_err(|e| format!("image format error: {e}"))?
+        .to_mime_type();
+    let frame = card::load_frame_png(template, kind)?;
+
+    let families: Vec<String> =
+        fonts.families.iter().map(|family| format!("'{}'", escape(family))).collect();
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{CARD_W}" height="{CARD_H}" viewBox="0 0 {CARD_W} {CARD_H}" font-family="{}" font-weight="700">"#,
+        families.join(", ")
+    );
+    if embed_font {
+        let _ = write!(
+            svg,
+            "<style>@font-face{{font-family:'{PRIMARY_FAMILY}';font-weight:700;\
+             src:url(data:font/ttf;base64,{})}}</style>",
+            STANDARD.encode(card::FONT_BYTES)
+        );
+    }
+
+    // Art covering the card, then the frame over it
+    let _ = write!(
+        svg,
+        r#"<image width="{CARD_W}" height="{CARD_H}" preserveAspectRatio="xMidYMid slice" href="data:{art_mime};base64,{}"/>"#,
+        STANDARD.encode(image_bytes)
+    );
+    let _ = write!(
+        svg,
+        r#"<image width="{CARD_W}" height="{CARD_H}" href="data:image/png;base64,{}"/>"#,
+        STANDARD.encode(frame)
+    );
+
+    // Name banner
+    let (x, w, r) = (template.content_x, template.content_w, template.banner_radius);
+    let layout = card::layout_name(template, fonts, name, options.break_anywhere());
+    rect(&mut svg, x, template.content_y, w, layout.banner_h, r, style.banner);
+    let name_cx = x + w as i32 / 2;
+    let name_em = em(fonts, layout.px);
+    for (line, cy) in layout.lines.iter().zip(layout.centers) {
+        text(&mut svg, name_cx, cy, name_em, "middle", style.name, line);
+    }
+
+    // Category fit strip
+    let no_fit = HashMap::new();
+    let top = card::top_fits(options.fit.unwrap_or(&no_fit));
+    if !top.is_empty() {
+        rect(&mut svg, x, template.fit_strip_y, w, template.fit_strip_h, r, style.banner);
+        let (slot_w, max_label_w) = card::fit_slot(template, top.len());
+        let cy = template.fit_strip_y + template.fit_strip_h as i32 / 2;
+        for (i, (category, score)) in top.into_iter().enumerate() {
+            let printed = options.category_name(category);
+            let (label, label_px) = card::fit_label(fonts, printed, max_label_w);
+            let scale = PxScale::from(label_px);
+            let (label_w, _) = fonts.text_size(scale, &label);
+            let chip_w = 2 * FIT_BADGE_R + FIT_GAP + label_w as i32;
+            let chip_x = x + slot_w * i as i32 + (slot_w - chip_w) / 2;
+            let badge = match score {
+                8.. => template.fit_high,
+                5..=7 => template.fit_mid,
+                _ => template.fit_low,
+            };
+            let _ = write!(
+                svg,
+                r#"<circle cx="{}" cy="{cy}" r="{FIT_BADGE_R}" {}/>"#,
+                chip_x + FIT_BADGE_R,
+                fill(badge)
+            );
+            let score = score.to_string();
+            let badge_cx = chip_x + FIT_BADGE_R;
+            let score_em = em(fonts, FIT_SCORE_PX);
+            text(&mut svg, badge_cx, cy, score_em, "middle", template.fit_score, &score);
+            let label_x = chip_x + 2 * FIT_BADGE_R + FIT_GAP;
+            text(&mut svg, label_x, cy, em(fonts, label_px), "start", style.name, &label);
+        }
+    }
+
+    // Rarity gem: rim, body, and the lit upper-left facet
+    let [body, lit, edge] = template.gem(rarity);
+    let (cx, cy) = (template.gem_x as f32, template.gem_y as f32);
+    let (rx, ry) = (template.gem_rx.max(1) as f32, template.gem_ry.max(1) as f32);
+    let inner = 1.0 - GEM_EDGE;
+    let diamond = |k: f32| {
+        [(cx, cy - ry * k), (cx + rx * k, cy), (cx, cy + ry * k), (cx - rx * k, cy)]
+    };
+    polygon(&mut svg, &diamond(1.0), edge);
+    polygon(&mut svg, &diamond(inner), body);
+    polygon(&mut svg, &[(cx - rx * inner, cy), (cx, cy - ry * inner), (cx, cy)], lit);
+
+    svg.push_str("</svg>");
+    Ok(svg)
+}
+
+/// Draw an SVG document made by `compose` at card size, with `fonts` only,
+/// so rendering never depends on what's installed.
+pub fn rasterize(svg: &str, fonts: &CardFonts) -> Result<RgbaImage, String> {
+    let options = usvg::Options {
+        font_family: fonts.families.first().map_or(PRIMARY_FAMILY, String::as_str).to_string(),
+        fontdb: fonts.database.clone(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| format!("svg parse error: {e}"))?;
+    let mut pixmap =
+        tiny_skia::Pixmap::new(CARD_W, CARD_H).ok_or_else(|| "pixmap error".to_string())?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+    // tiny-skia keeps premultiplied alpha
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    RgbaImage::from_raw(CARD_W, CARD_H, pixels).ok_or_else(|| "pixmap size error".to_string())
+}
+
+/// SVG font size matching the raster renderer's `px`, which ab_glyph takes
+/// as ascent to descent rather than the em.
+fn em(fonts: &CardFonts, px: f32) -> f32 {
+    let font = fonts.primary();
+    let units_per_em = font.units_per_em().unwrap_or(1000.0);
+    px * units_per_em / font.height_unscaled()
+}
+
+fn fill([r, g, b, a]: Color) -> String {
+    format!(r#"fill="rgb({r},{g},{b})" fill-opacity="{:.3}""#, a as f32 / 255.0)
+}
+
+fn rect(svg: &mut String, x: i32, y: i32, w: u32, h: u32, r: u32, color: Color) {
+    let _ = write!(
+        svg,
+        r#"<rect x="{x}" y="{y}" width="{w}" height="{h}" rx="{r}" {}/>"#,
+        fill(color)
+    );
+}
+
+/// `content` centered vertically on `y`; `anchor` is where `x` falls on it.
+fn text(svg: &mut String, x: i32, y: i32, size: f32, anchor: &str, color: Color, content: &str) {
+    let _ = write!(
+        svg,
+        r#"<text x="{x}" y="{y}" font-size="{size:.1}" text-anchor="{anchor}" dominant-baseline="central" {}>{}</text>"#,
+        fill(color),
+        escape(content)
+    );
+}
+
+fn polygon(svg: &mut String, points: &[(f32, f32)], color: Color) {
+    let points: Vec<String> = points.iter().map(|(x, y)| format!("{x:.1},{y:.1}")).collect();
+    let _ = write!(svg, r#"<polygon points="{}" {}/>"#, points.join(" "), fill(color));
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,402 @@
+//! Card layout templates: where the art, name banner, fit strip and rarity
+//! gem sit on a card, their colors, the frame for each kind of card, and
+//! how the card back looks, and how cards in other languages are printed.
+//! Read from card-templates.json, so the cards can be reskinned without a
+//! rebuild. Any setting a template leaves out keeps the built-in look.
+
+use crate::card::{self, CardKind, CARD_H, CARD_W};
+use crate::card_fonts::CardFonts;
+use crate::packs::Rarity;
+use image::{ImageReader, RgbaImage};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// The template the game renders its cards with.
+pub const DEFAULT_TEMPLATE: &str = "default";
+
+/// RGBA.
+pub type Color = [u8; 4];
+
+/// How a template's cards are drawn.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    /// Drawn pixel by pixel.
+    #[default]
+    Raster,
+    /// Composed as SVG, then rasterized, for smoother text and shapes.
+    Svg,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct CardTemplate {
+    pub renderer: Renderer,
+    /// Art area inside the frame's border: left edge, top edge and width.
+    pub content_x: i32,
+    pub content_y: i32,
+    pub content_w: u32,
+    /// Height of the name banner across the top of the art.
+    pub banner_h: u32,
+    /// Corner radius of the name banner and the fit strip.
+    pub banner_radius: u32,
+    /// Names start this size and shrink until they fit, down to `min_name_px`.
+    pub name_px: f32,
+    pub min_name_px: f32,
+    /// A name of several words goes on two lines rather than shrink below
+    /// this size.
+    pub wrap_px: f32,
+    pub material: KindStyle,
+    pub intent: KindStyle,
+    pub crafted: KindStyle,
+    /// Top edge and height of the category fit strip.
+    pub fit_strip_y: i32,
+    pub fit_strip_h: u32,
+    /// Fit badges for scores of 8-10, 5-7 and 1-4, and the score on them.
+    pub fit_high: Color,
+    pub fit_mid: Color,
+    pub fit_low: Color,
+    pub fit_score: Color,
+    /// Center of the rarity gem, and its half width and half height.
+    pub gem_x: i32,
+    pub gem_y: i32,
+    pub gem_rx: i32,
+    pub gem_ry: i32,
+    /// Gem colors for each rarity: body, lit facet, edge.
+    pub gem_common: [Color; 3],
+    pub gem_rare: [Color; 3],
+    pub gem_legendary: [Color; 3],
+    /// Where the bottom-right corner of a minted card's QR code goes.
+    pub qr_x: i32,
+    pub qr_y: i32,
+    /// Where the bottom-left corner of a minted card's set and edition
+    /// stamp goes, and its colors.
+    pub stamp_x: i32,
+    pub stamp_y: i32,
+    pub stamp_bg: Color,
+    pub stamp_text: Color,
+    pub back: BackStyle,
+    /// Shared by every template, from the file's `fallback_fonts`.
+    #[serde(skip)]
+    pub fonts: Arc<CardFonts>,
+}
+
+impl Default for CardTemplate {
+    fn default() -> Self {
+        CardTemplate {
+            renderer: Renderer::Raster,
+            content_x: 56,
+            content_y: 80,
+            content_w: 638,
+            banner_h: 80,
+            banner_radius: 8,
+            name_px: 60.0,
+            min_name_px: 22.0,
+            wrap_px: 40.0,
+            // Warm gold
+            material: KindStyle::new([30, 20, 12, 190], [220, 195, 130, 255]),
+            // Cool purple/silver
+            intent: KindStyle::new([20, 12, 35, 200], [180, 160, 220, 255]),
+            // Emerald/silver
+            crafted: KindStyle::new([10, 30, 24, 200], [150, 220, 190, 255]),
+            fit_strip_y: CARD_H as i32 - 200,
+            fit_strip_h: 60,
+            fit_high: [80, 185, 105, 255],
+            fit_mid: [220, 165, 55, 255],
+            fit_low: [135, 135, 145, 255],
+            fit_score: [20, 14, 8, 255],
+            gem_x: CARD_W as i32 / 2,
+            gem_y: CARD_H as i32 - 85,
+            gem_rx: 24,
+            gem_ry: 34,
+            gem_common: [[170, 175, 185, 255], [230, 232, 238, 255], [60, 62, 70, 255]],
+            gem_rare: [[40, 95, 210, 255], [130, 180, 255, 255], [15, 30, 80, 255]],
+            gem_legendary: [[225, 140, 25, 255], [255, 215, 120, 255], [90, 45, 5, 255]],
+            qr_x: 650,
+            qr_y: CARD_H as i32 - 220,
+            stamp_x: 70,
+            stamp_y: CARD_H as i32 - 220,
+            stamp_bg: [30, 20, 12, 200],
+            stamp_text: [220, 195, 130, 255],
+            back: BackStyle::default(),
+            fonts: Arc::new(CardFonts::default()),
+        }
+    }
+}
+
+impl CardTemplate {
+    pub fn style(&self, kind: &CardKind) -> &KindStyle {
+        match kind {
+            CardKind::Material => &self.material,
+            CardKind::Intent => &self.intent,
+            CardKind::Crafted => &self.crafted,
+        }
+    }
+
+    pub fn gem(&self, rarity: Rarity) -> [Color; 3] {
+        match rarity {
+            Rarity::Common => self.gem_common,
+            Rarity::Rare => self.gem_rare,
+            Rarity::Legendary => self.gem_legendary,
+        }
+    }
+}
+
+/// How one kind of card looks.
+#[derive(Deserialize)]
+pub struct KindStyle {
+    pub banner: Color,
+    pub name: Color,
+    /// Frame image, scaled to the card, with its near-black interior cut away
+    /// for the art. The built-in frame when unset.
+    #[serde(default)]
+    pub frame: Option<PathBuf>,
+    /// `frame`'s contents, read when the templates are loaded.
+    #[serde(skip)]
+    pub frame_bytes: Option<Vec<u8>>,
+    /// The frame ready to draw, made once; see `card::load_frame`.
+    #[serde(skip)]
+    pub frame_image: OnceLock<RgbaImage>,
+    /// `frame_image` as a PNG, for SVG cards.
+    #[serde(skip)]
+    pub frame_png: OnceLock<Vec<u8>>,
+}
+
+impl KindStyle {
+    fn new(banner: Color, name: Color) -> Self {
+        KindStyle {
+            banner,
+            name,
+            frame: None,
+            frame_bytes: None,
+            frame_image: OnceLock::new(),
+            frame_png: OnceLock::new(),
+        }
+    }
+}
+
+/// How the back of a card looks: the collection logo over a plain field,
+/// in a frame, with the season the card belongs to below.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BackStyle {
+    /// Behind the logo, inside the frame.
+    pub color: Color,
+    /// Collection logo, scaled to the content width with its edges faded
+    /// into `color`. The game's logo when unset.
+    pub logo: Option<PathBuf>,
+    #[serde(skip)]
+    pub logo_bytes: Option<Vec<u8>>,
+    /// Top edge of the logo.
+    pub logo_y: i32,
+    /// Top edge of the season banner, and its colors.
+    pub season_y: i32,
+    pub banner: Color,
+    pub text: Color,
+    /// Frame image, like `KindStyle::frame`. The material frame when unset.
+    pub frame: Option<PathBuf>,
+    #[serde(skip)]
+    pub frame_bytes: Option<Vec<u8>>,
+    #[serde(skip)]
+    pub frame_image: OnceLock<RgbaImage>,
+}
+
+impl Default for BackStyle {
+    fn default() -> Self {
+        BackStyle {
+            // The logo's own night purple
+            color: [18, 15, 32, 255],
+            logo: None,
+            logo_bytes: None,
+            logo_y: 150,
+            season_y: CARD_H as i32 - 220,
+            banner: [30, 20, 12, 190],
+            text: [220, 195, 130, 255],
+            frame: None,
+            frame_bytes: None,
+            frame_image: OnceLock::new(),
+        }
+    }
+}
+
+/// How cards in one language are printed, from the file's `locales`.
+/// Anything a locale leaves out is printed as in English.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct CardLocale {
+    /// Fonts to set the card's text in, ahead of Cinzel and the fallbacks,
+    /// such as one drawn for the language's script.
+    pub fonts: Vec<PathBuf>,
+    /// `fonts` loaded ahead of the shared ones, when there are any.
+    #[serde(skip)]
+    pub font_set: Option<Arc<CardFonts>>,
+    /// Names with no spaces, as in Japanese or Chinese, may wrap between
+    /// any two characters.
+    pub break_anywhere: bool,
+    /// Wording of a minted card's stamp.
+    pub stamp: StampWords,
+}
+
+/// A stamp's provenance in one language.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct StampWords {
+    pub first_discovery: String,
+    /// `{n}` is replaced with the edition.
+    pub edition: String,
+}
+
+impl Default for StampWords {
+    fn default() -> Self {
+        StampWords {
+            first_discovery: "1st Discovery".to_string(),
+            edition: "Edition {n}".to_string(),
+        }
+    }
+}
+
+/// A template's name and a season.
+type BackKey = (String, Option<String>);
+
+/// Every template, by name. There is always a `default`.
+pub struct CardTemplates {
+    templates: HashMap<String, Arc<CardTemplate>>,
+    /// Locales, by lowercase tag.
+    locales: HashMap<String, Arc<CardLocale>>,
+    /// Card backs already drawn, by template and season. Dropped with the
+    /// templates on reload.
+    backs: Mutex<HashMap<BackKey, Vec<u8>>>,
+}
+
+impl CardTemplates {
+    pub fn get(&self, name: &str) -> Option<Arc<CardTemplate>> {
+        self.templates.get(name).cloned()
+    }
+
+    /// The template the game renders its cards with.
+    pub fn default_template(&self) -> Arc<CardTemplate> {
+        self.templates[DEFAULT_TEMPLATE].clone()
+    }
+
+    /// The locale a BCP 47 tag such as `pt-BR` asks for: the whole tag if
+    /// there's a locale for it, else its language. None prints in English.
+    pub fn locale(&self, tag: &str) -> Option<Arc<CardLocale>> {
+        let tag = tag.trim().to_lowercase();
+        let language = tag.split(['-', '_']).next().unwrap_or_default();
+        self.locales.get(&tag).or_else(|| self.locales.get(language)).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    /// The back of `name`'s cards as a PNG, if it's been drawn.
+    pub fn cached_back(&self, name: &str, season: Option<&str>) -> Option<Vec<u8>> {
+        let key = (name.to_string(), season.map(str::to_string));
+        self.backs.lock().unwrap().get(&key).cloned()
+    }
+
+    /// Keep a back drawn with `template` (see `card::render_card_back`) for
+    /// `cached_back`, unless the templates were reloaded while it was drawn.
+    pub fn store_back(
+        &self,
+        name: &str,
+        season: Option<&str>,
+        template: &Arc<CardTemplate>,
+        png: Vec<u8>,
+    ) {
+        if self.get(name).is_some_and(|current| Arc::ptr_eq(&current, template)) {
+            let key = (name.to_string(), season.map(str::to_string));
+            self.backs.lock().unwrap().insert(key, png);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TemplateFile {
+    /// Fonts for characters Cinzel lacks, tried in order.
+    #[serde(default)]
+    fallback_fonts: Vec<PathBuf>,
+    templates: HashMap<String, CardTemplate>,
+    #[serde(default)]
+    locales: HashMap<String, CardLocale>,
+}
+
+/// Read and validate card-templates.json, along with the frames and fonts
+/// it names.
+pub fn load_card_templates(path: &Path) -> Result<CardTemplates, String> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let file: TemplateFile = serde_json::from_str(&data)
+        .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+    if !file.templates.contains_key(DEFAULT_TEMPLATE) {
+        return Err(format!("{} needs a {DEFAULT_TEMPLATE:?} template", path.display()));
+    }
+
+    let fonts = CardFonts::new(&file.fallback_fonts)
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    let fonts = Arc::new(fonts);
+
+    let mut templates = HashMap::new();
+    for (name, mut template) in file.templates {
+        template.fonts = fonts.clone();
+        let invalid = |why: String| format!("{}: template {name:?} {why}", path.display());
+        let right = template.content_x as i64 + template.content_w as i64;
+        if template.content_x < 0 || template.content_w == 0 || right > CARD_W as i64 {
+            return Err(invalid("has a content box outside the card".into()));
+        }
+        if template.min_name_px <= 0.0 || template.min_name_px > template.name_px {
+            return Err(invalid("needs 0 < min_name_px <= name_px".into()));
+        }
+        for style in [&mut template.material, &mut template.intent, &mut template.crafted] {
+            if let Some(frame) = &style.frame {
+                style.frame_bytes = Some(read_image(frame).map_err(&invalid)?);
+            }
+        }
+        let back = &mut template.back;
+        if let Some(frame) = &back.frame {
+            back.frame_bytes = Some(read_image(frame).map_err(&invalid)?);
+        }
+        if let Some(logo) = &back.logo {
+            back.logo_bytes = Some(read_image(logo).map_err(&invalid)?);
+        }
+
+        // Scale and key the frames now rather than on the first render
+        for kind in [CardKind::Material, CardKind::Intent, CardKind::Crafted] {
+            card::load_frame(&template, &kind).map_err(&invalid)?;
+        }
+        card::load_back_frame(&template.back).map_err(&invalid)?;
+        templates.insert(name, Arc::new(template));
+    }
+
+    let mut locales = HashMap::new();
+    for (tag, mut locale) in file.locales {
+        let invalid = |why: String| format!("{}: locale {tag:?} {why}", path.display());
+        if !locale.stamp.edition.contains("{n}") {
+            return Err(invalid("needs {n} in its stamp's edition".into()));
+        }
+        if !locale.fonts.is_empty() {
+            locale.font_set = Some(Arc::new(fonts.preferring(&locale.fonts).map_err(invalid)?));
+        }
+        locales.insert(tag.to_lowercase(), Arc::new(locale));
+    }
+    Ok(CardTemplates {
+        templates,
+        locales,
+        backs: Mutex::new(HashMap::new()),
+    })
+}
+
+/// The bytes of the image at `path`, once they're known to decode.
+fn read_image(path: &Path) -> Result<Vec<u8>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("image {}: {e}", path.display()))?;
+    ImageReader::new(Cursor::new(&bytes))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())
+        .and_then(|reader| reader.decode().map_err(|e| e.to_string()))
+        .map_err(|e| format!("image {}: {e}", path.display()))?;
+    Ok(bytes)
+}
@@ -0,0 +1,279 @@
+//! Crafted cards claimed as NFTs. Each claim names the proof it was made
+//! with (a game the wallet crafted the card in, or a voucher), and a proof
+//! mints at most one card.
+
+use crate::card_cache::now_secs;
+use crate::config::ClaimSettings;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use crate::solana::SolanaConfig;
+use crate::storage::ClaimStore;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+pub struct Claim {
+    /// `game:<game id>:<card id>` or `voucher:<code>`.
+    pub proof: String,
+    pub wallet: String,
+    pub card_id: String,
+    /// Asset the mint transaction creates.
+    pub asset_address: String,
+    pub claimed_at: u64,
+}
+
+/// Admin-issued right to claim a card without having crafted it.
+pub struct ClaimVoucher {
+    pub code: String,
+    pub card_id: String,
+    /// When set, only this wallet may redeem it.
+    pub wallet: Option<String>,
+    pub created_at: u64,
+}
+
+/// A limit of `ClaimSettings` a new claim would go over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimLimit {
+    PerCard,
+    PerDay,
+}
+
+/// The window `ClaimSettings::per_wallet_per_day` counts claims in.
+pub const CLAIM_DAY_SECS: u64 = 86_400;
+
+/// How long a claim's transaction might still land. Its proof stays spent
+/// until then; after that, a claim whose asset never appeared can be retried.
+const PENDING_SECS: u64 = 150;
+
+/// The proof that lets `wallet` claim `card_id`: `voucher` when given,
+/// otherwise a game owned by the wallet in which it crafted the card.
+pub async fn proof_for(
+    state: &AppState,
+    wallet: &str,
+    card_id: &str,
+    game_id: Option<&str>,
+    voucher: Option<&str>,
+) -> Result<String, ApiError> {
+    if let Some(code) = voucher {
+        let voucher = state
+            .claims
+            .voucher(code)
+            .map_err(|e| err(ErrorCode::StorageError, e))?
+            .ok_or_else(|| err(ErrorCode::ClaimNotAllowed, "Unknown claim voucher"))?;
+        if voucher.card_id != card_id {
+            return Err(err(ErrorCode::ClaimNotAllowed, "Voucher is for a different card"));
+        }
+        if voucher.wallet.as_deref().is_some_and(|w| w != wallet) {
+            return Err(err(ErrorCode::ClaimNotAllowed, "Voucher is for a different wallet"));
+        }
+        return Ok(format!("voucher:{code}"));
+    }
+
+    let game_id = game_id.ok_or_else(|| {
+        err(
+            ErrorCode::ClaimNotAllowed,
+            "Claims need the game the card was crafted in, or a voucher",
+        )
+    })?;
+    let games = state.games.read().await;
+    let game = games
+        .get(game_id)
+        .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+    let player = &game.players[0];
+    if player.wallet.as_deref() != Some(wallet) {
+        return Err(err(ErrorCode::ClaimNotAllowed, "Game was not played by this wallet"));
+    }
+    if !player.crafted.iter().any(|id| id == card_id) {
+        return Err(err(ErrorCode::ClaimNotAllowed, "Card was not crafted in this game"));
+    }
+    Ok(format!("game:{game_id}:{card_id}"))
+}
+
+/// Check that `proof` hasn't minted a card yet and `wallet` is within its
+/// claim limits. Returns the asset of an earlier claim on this proof that
+/// never landed, which the new claim replaces. The limits are checked again
+/// when the claim is recorded, since other claims may land in between.
+pub fn check(
+    state: &AppState,
+    solana: &SolanaConfig,
+    wallet: &str,
+    card_id: &str,
+    proof: &str,
+) -> Result<Option<String>, ApiError> {
+    let store = |e| err(ErrorCode::StorageError, e);
+    if let Some(previous) = state.claims.claim(proof).map_err(store)? {
+        if previous.wallet != wallet {
+            return Err(err(ErrorCode::ClaimNotAllowed, "Already claimed by another wallet"));
+        }
+        let asset = Pubkey::from_str(&previous.asset_address)
+            .map_err(|e| err(ErrorCode::StorageError, format!("Bad claimed asset: {e}")))?;
+        if solana
+            .account_exists(&asset)
+            .map_err(|e| err(ErrorCode::SolanaError, e))?
+        {
+            return Err(err(ErrorCode::ClaimNotAllowed, "Card already claimed"));
+        }
+        let age = now_secs().saturating_sub(previous.claimed_at);
+        if age < PENDING_SECS {
+            return Err(err(
+                ErrorCode::ClaimNotAllowed,
+                format!(
+                    "An earlier claim may still land; retry in {}s",
+                    PENDING_SECS - age
+                ),
+            )
+            .with_details(serde_json::json!({ "asset_address": previous.asset_address })));
+        }
+        // Retrying a claim that never landed doesn't count against the limits
+        return Ok(Some(previous.asset_address));
+    }
+
+    check_limits(state.claims.as_ref(), state.claim_limits, wallet, card_id, now_secs())?;
+    Ok(None)
+}
+
+/// Record `claim`, taking over the proof's claim on `replacing` (an asset
+/// that never landed) or, for a new claim, reserving one of the wallet's
+/// claims in the same store transaction that checks its limits.
+pub fn record(state: &AppState, claim: &Claim, replacing: Option<&str>) -> Result<(), ApiError> {
+    let recorded = match replacing {
+        Some(previous) => state.claims.record_claim(claim, Some(previous)).map(Ok),
+        None => state.claims.reserve_claim(claim, state.claim_limits),
+    }
+    .map_err(|e| err(ErrorCode::StorageError, e))?;
+    match recorded {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(err(
+            ErrorCode::ClaimNotAllowed,
+            "Card is being claimed by another request",
+        )),
+        Err(limit) => Err(limit_error(state.claim_limits, limit)),
+    }
+}
+
+/// The limit a wallet with `of_card` claims of a card and `in_day` claims
+/// in the last day would go over with another claim of that card.
+pub fn limit_reached(limits: ClaimSettings, of_card: u64, in_day: u64) -> Option<ClaimLimit> {
+    if limits.per_wallet_per_card > 0 && of_card >= limits.per_wallet_per_card {
+        Some(ClaimLimit::PerCard)
+    } else if limits.per_wallet_per_day > 0 && in_day >= limits.per_wallet_per_day {
+        Some(ClaimLimit::PerDay)
+    } else {
+        None
+    }
+}
+
+fn limit_error(limits: ClaimSettings, limit: ClaimLimit) -> ApiError {
+    let message = match limit {
+        ClaimLimit::PerCard => format!(
+            "Claim limit reached ({} per card per wallet)",
+            limits.per_wallet_per_card
+        ),
+        ClaimLimit::PerDay => format!(
+            "Claim limit reached ({} per wallet per day)",
+            limits.per_wallet_per_day
+        ),
+    };
+    err(ErrorCode::ClaimLimitReached, message)
+}
+
+/// Check that a new claim of `card_id` by `wallet` at `now` is within `limits`.
+fn check_limits(
+    claims: &dyn ClaimStore,
+    limits: ClaimSettings,
+    wallet: &str,
+    card_id: &str,
+    now: u64,
+) -> Result<(), ApiError> {
+    let store = |e| err(ErrorCode::StorageError, e);
+    let of_card = claims.claim_count(wallet, Some(card_id), 0).map_err(store)?;
+    let day_ago = now.saturating_sub(CLAIM_DAY_SECS);
+    let in_day = claims.claim_count(wallet, None, day_ago).map_err(store)?;
+    match limit_reached(limits, of_card, in_day) {
+        Some(limit) => Err(limit_error(limits, limit)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite_store::SqliteCardStore;
+    use std::path::Path;
+
+    const DAY: u64 = 86_400;
+
+    fn claim(store: &SqliteCardStore, n: u32, card_id: &str, claimed_at: u64) {
+        let claim = Claim {
+            proof: format!("voucher:{n}"),
+            wallet: "wallet".to_string(),
+            card_id: card_id.to_string(),
+            asset_address: format!("asset{n}"),
+            claimed_at,
+        };
+        assert!(store.record_claim(&claim, None).unwrap());
+    }
+
+    fn rejected(result: Result<(), ApiError>) -> bool {
+        result.is_err_and(|e| e.code == ErrorCode::ClaimLimitReached)
+    }
+
+    #[test]
+    fn a_wallet_claims_each_card_only_so_often() {
+        let store = SqliteCardStore::open(Path::new(":memory:")).expect("opens");
+        let limits = ClaimSettings {
+            per_wallet_per_card: 2,
+            per_wallet_per_day: 0,
+        };
+        let check = |card_id| check_limits(&store, limits, "wallet", card_id, 10 * DAY);
+        claim(&store, 1, "card", 0);
+        assert!(check("card").is_ok());
+        claim(&store, 2, "card", DAY);
+        assert!(rejected(check("card")));
+        // Other cards and other wallets aren't held back
+        assert!(check("other").is_ok());
+        assert!(check_limits(&store, limits, "someone", "card", 10 * DAY).is_ok());
+    }
+
+    #[test]
+    fn a_wallet_claims_only_so_many_cards_a_day() {
+        let store = SqliteCardStore::open(Path::new(":memory:")).expect("opens");
+        let limits = ClaimSettings {
+            per_wallet_per_card: 0,
+            per_wallet_per_day: 2,
+        };
+        let now = 10 * DAY;
+        claim(&store, 1, "a", now - DAY - 1);
+        claim(&store, 2, "b", now - 60);
+        assert!(check_limits(&store, limits, "wallet", "c", now).is_ok());
+        claim(&store, 3, "c", now - 30);
+        assert!(rejected(check_limits(&store, limits, "wallet", "d", now)));
+        // The oldest claim in the window ages out
+        assert!(check_limits(&store, limits, "wallet", "d", now - 60 + DAY + 1).is_ok());
+    }
+
+    #[test]
+    fn reserving_a_claim_rechecks_the_limits() {
+        let store = SqliteCardStore::open(Path::new(":memory:")).expect("opens");
+        let limits = ClaimSettings {
+            per_wallet_per_card: 1,
+            per_wallet_per_day: 0,
+        };
+        let new_claim = |n: u32| Claim {
+            proof: format!("game:{n}:card"),
+            wallet: "wallet".to_string(),
+            card_id: "card".to_string(),
+            asset_address: format!("asset{n}"),
+            claimed_at: DAY,
+        };
+        // Both passed the early check; only the first to record gets the claim
+        assert!(check_limits(&store, limits, "wallet", "card", DAY).is_ok());
+        assert_eq!(store.reserve_claim(&new_claim(1), limits), Ok(Ok(true)));
+        assert_eq!(store.reserve_claim(&new_claim(2), limits), Ok(Err(ClaimLimit::PerCard)));
+        // A spent proof isn't taken twice
+        let unlimited = ClaimSettings {
+            per_wallet_per_card: 0,
+            per_wallet_per_day: 0,
+        };
+        assert_eq!(store.reserve_claim(&new_claim(1), unlimited), Ok(Ok(false)));
+    }
+}
@@ -0,0 +1,76 @@
+use crate::config::CorsSettings;
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+pub struct CorsConfig {
+    /// `None` means any origin (`*`).
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+}
+
+impl CorsConfig {
+    /// Build from the `[cors]` section (or the CORS_ALLOWED_* env vars).
+    /// Returns None if no origins are configured, in which case only
+    /// same-origin requests work.
+    pub fn from_config(settings: &CorsSettings) -> Option<Self> {
+        let origins = &settings.allowed_origins;
+        if origins.is_empty() {
+            return None;
+        }
+
+        Some(CorsConfig {
+            allowed_origins: if origins.iter().any(|o| o == "*") {
+                None
+            } else {
+                Some(origins.clone())
+            },
+            allowed_methods: settings.allowed_methods.clone(),
+            allowed_headers: settings.allowed_headers.clone(),
+        })
+    }
+
+    /// Build the tower-http layer. Invalid entries are logged and skipped.
+    pub fn layer(&self) -> CorsLayer {
+        let origin = match &self.allowed_origins {
+            None => AllowOrigin::any(),
+            Some(origins) => AllowOrigin::list(origins.iter().filter_map(|o| {
+                HeaderValue::from_str(o)
+                    .map_err(|e| log::warn!("Ignoring invalid CORS origin '{o}': {e}"))
+                    .ok()
+            })),
+        };
+
+        let methods: Vec<Method> = self
+            .allowed_methods
+            .iter()
+            .filter_map(|m| {
+                Method::from_bytes(m.to_uppercase().as_bytes())
+                    .map_err(|e| log::warn!("Ignoring invalid CORS method '{m}': {e}"))
+                    .ok()
+            })
+            .collect();
+
+        let headers: Vec<HeaderName> = self
+            .allowed_headers
+            .iter()
+            .filter_map(|h| {
+                HeaderName::from_bytes(h.as_bytes())
+                    .map_err(|e| log::warn!("Ignoring invalid CORS header '{h}': {e}"))
+                    .ok()
+            })
+            .collect();
+
+        CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(AllowMethods::list(methods))
+            .allow_headers(AllowHeaders::list(headers))
+    }
+
+    pub fn describe_origins(&self) -> String {
+        match &self.allowed_origins {
+            None => "*".to_string(),
+            Some(origins) => origins.join(", "),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Rendered crafted cards on disk. Files are named by the SHA-256 of their
+//! contents, so identical renders share one file and a card's name never
+//! leaks into (or goes stale in) its URL. The cache's `image_path` is the
+//! card → file mapping.
+
+use crate::card::{self, CardKind, CardOptions, RenderedCard};
+use crate::card_cache::CachedCard;
+use crate::card_template::CardTemplate;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use crate::image_variants::{self, Format};
+use crate::packs::Rarity;
+use axum::body::Bytes;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Hex chars of the content hash used in filenames.
+const HASH_LEN: usize = 16;
+
+/// Store a rendered card under `crafted_dir`, with the sizes rendered
+/// alongside it, and return its `/cards/crafted/` URL. An identical file
+/// already on disk is reused as-is.
+pub fn save(crafted_dir: &Path, card: RenderedCard) -> Result<String, String> {
+    let RenderedCard { png, sizes } = card;
+    let hash = format!("{:x}", Sha256::digest(&png));
+    let filename = format!("{}.png", &hash[..HASH_LEN]);
+    let disk_path = crafted_dir.join(&filename);
+    let serve_path = format!("/cards/crafted/{filename}");
+
+    if disk_path.is_file() {
+        log::debug!("Reusing identical render {filename}");
+        return Ok(serve_path);
+    }
+
+    std::fs::create_dir_all(crafted_dir).map_err(|e| format!("create dir error: {e}"))?;
+    for (size, bytes) in sizes {
+        write_new(&image_variants::variant_path(&disk_path, size, Format::Png, false), &bytes)?;
+    }
+    write_new(&disk_path, &png)?;
+
+    // WebP and the other sizes aren't needed to answer, so build them in the background
+    tokio::task::spawn_blocking(move || image_variants::write_all(&disk_path, &png));
+    Ok(serve_path)
+}
+
+/// Keep the art a crafted card is rendered from under `cards_dir/art`, so
+/// the card can be rendered again when its template changes, and return its
+/// `/cards/art/` URL. Named by content like renders.
+pub fn save_art(cards_dir: &Path, art: &[u8]) -> Result<String, String> {
+    let ext = image::guess_format(art)
+        .ok()
+        .and_then(|format| format.extensions_str().first().copied())
+        .unwrap_or("png");
+    let hash = format!("{:x}", Sha256::digest(art));
+    let filename = format!("{}.{ext}", &hash[..HASH_LEN]);
+    let art_dir = cards_dir.join("art");
+    let disk_path = art_dir.join(&filename);
+    if !disk_path.is_file() {
+        std::fs::create_dir_all(&art_dir).map_err(|e| format!("create dir error: {e}"))?;
+        write_new(&disk_path, art)?;
+    }
+    Ok(format!("/cards/art/{filename}"))
+}
+
+/// Keep `art` as crafted card `card_id`'s, then render the card, titled
+/// `name`, from it on the render queue and save it. Returns the `/cards/art/`
+/// and `/cards/crafted/` URLs.
+pub async fn keep_and_render(
+    state: &AppState,
+    card_id: &str,
+    name: &str,
+    art: Bytes,
+) -> Result<(String, String), ApiError> {
+    // Keep the art so the card can be rendered again with a new template
+    let art_path = save_art(&state.paths.cards_dir, &art)
+        .map_err(|e| err(ErrorCode::StorageError, format!("File write error: {e}")))?;
+
+    let rarity = Rarity::of(state, card_id, true);
+    let template = state.card_templates.read().await.default_template();
+    let name = name.to_string();
+    let card = state
+        .render
+        .run(move || {
+            let options = CardOptions {
+                sizes: &image_variants::RENDERED_SIZES,
+                ..Default::default()
+            };
+            card::render_card(&template, &name, &art, &CardKind::Crafted, rarity, &options)
+        })
+        .await?
+        .map_err(|e| err(ErrorCode::RenderFailed, format!("Card render error: {e}")))?;
+
+    // Save to disk, named by content so identical renders share a file
+    let serve_path = save(&state.paths.crafted_dir(), card)
+        .map_err(|e| err(ErrorCode::StorageError, format!("File write error: {e}")))?;
+    Ok((art_path, serve_path))
+}
+
+/// Render `card` again from its kept art with `template`, and save it like a
+/// new render. Returns its `/cards/crafted/` URL.
+pub fn render_again(
+    cards_dir: &Path,
+    card: &CachedCard,
+    template: &CardTemplate,
+    rarity: Rarity,
+) -> Result<String, String> {
+    let art_path = card.art_path.as_ref().ok_or("the card's art wasn't kept")?;
+    let art_file = disk_path(cards_dir, art_path)
+        .ok_or_else(|| format!("{art_path} is not under /cards"))?;
+    let art = std::fs::read(&art_file).map_err(|e| format!("{}: {e}", art_file.display()))?;
+    let options = CardOptions {
+        sizes: &image_variants::RENDERED_SIZES,
+        ..Default::default()
+    };
+    let rendered =
+        card::render_card(template, &card.name, &art, &CardKind::Crafted, rarity, &options)?;
+    save(&cards_dir.join("crafted"), rendered)
+}
+
+/// The card at `image_path` finished with `options`' foil, QR code and
+/// stamp, for the one asset `asset`, saved under `cards_dir/minted`. Returns
+/// its `/cards/minted/` URL.
+pub fn save_minted(
+    cards_dir: &Path,
+    image_path: &str,
+    asset: &str,
+    template: &CardTemplate,
+    options: &CardOptions,
+) -> Result<String, String> {
+    let original = disk_path(cards_dir, image_path)
+        .ok_or_else(|| format!("{image_path} is not under /cards"))?;
+    let png = std::fs::read(&original).map_err(|e| format!("{}: {e}", original.display()))?;
+    let png = card::finish_png(&png, template, options)?;
+    let minted_dir = cards_dir.join("minted");
+    std::fs::create_dir_all(&minted_dir).map_err(|e| format!("create dir error: {e}"))?;
+    write_new(&minted_dir.join(format!("{asset}.png")), &png)?;
+    Ok(format!("/cards/minted/{asset}.png"))
+}
+
+/// Delete the image `save_minted` made for `asset`, if there is one.
+pub fn remove_minted(cards_dir: &Path, asset: &str) -> Result<(), String> {
+    match std::fs::remove_file(cards_dir.join("minted").join(format!("{asset}.png"))) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("remove error: {e}")),
+        _ => Ok(()),
+    }
+}
+
+/// Write `bytes` to `path` in one step, so readers never see a partial file.
+fn write_new(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp, bytes).map_err(|e| format!("write error: {e}"))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("rename error: {e}"))
+}
+
+/// `/cards/crafted/x.png` → `<cards_dir>/crafted/x.png`.
+pub fn disk_path(cards_dir: &Path, image_path: &str) -> Option<PathBuf> {
+    let rel = image_path.strip_prefix("/cards/")?;
+    Some(cards_dir.join(rel))
+}
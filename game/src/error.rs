@@ -0,0 +1,176 @@
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Machine-readable error codes. Clients should branch on these, not on `message`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    // Malformed or invalid requests
+    InvalidRequest,
+    InvalidCardIndex,
+    InvalidBoardPosition,
+    InvalidCombination,
+    InvalidWallet,
+    // Game rules
+    GameNotFound,
+    GameOver,
+    NotYourTurn,
+    NotBotGame,
+    AlreadyPlaced,
+    CellAlreadyOwned,
+    CardNotPlaceable,
+    // Cards and ownership
+    CardNotFound,
+    CardNotOwned,
+    CardSoulbound,
+    CombinationImpossible,
+    ClaimNotAllowed,
+    ClaimLimitReached,
+    CardSoldOut,
+    NotEnoughEssence,
+    PaymentNotVerified,
+    PackLimitReached,
+    PackNotFound,
+    PackAlreadyOpened,
+    TradeNotFound,
+    TradeNotOpen,
+    TransactionWouldFail,
+    AirdropNotFound,
+    AttestationInProgress,
+    InvalidArt,
+    ArtTooLarge,
+    ArtRejected,
+    NotDiscoverer,
+    // Access control
+    Unauthorized,
+    WalletSessionRequired,
+    RateLimited,
+    // Upstream services
+    GenerationUnavailable,
+    SolanaNotConfigured,
+    SolanaError,
+    // Server-side failures
+    RenderFailed,
+    RenderBusy,
+    StorageError,
+    ShuttingDown,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidRequest
+            | ErrorCode::InvalidCardIndex
+            | ErrorCode::InvalidBoardPosition
+            | ErrorCode::InvalidCombination
+            | ErrorCode::InvalidWallet
+            | ErrorCode::GameOver
+            | ErrorCode::NotYourTurn
+            | ErrorCode::NotBotGame
+            | ErrorCode::AlreadyPlaced
+            | ErrorCode::CellAlreadyOwned
+            | ErrorCode::CardNotPlaceable
+            | ErrorCode::CardNotOwned
+            | ErrorCode::CardSoulbound
+            | ErrorCode::InvalidArt => StatusCode::BAD_REQUEST,
+            ErrorCode::GameNotFound
+            | ErrorCode::CardNotFound
+            | ErrorCode::TradeNotFound
+            | ErrorCode::PackNotFound
+            | ErrorCode::AirdropNotFound => StatusCode::NOT_FOUND,
+            ErrorCode::TradeNotOpen
+            | ErrorCode::CardSoldOut
+            | ErrorCode::PackAlreadyOpened
+            | ErrorCode::AttestationInProgress => StatusCode::CONFLICT,
+            ErrorCode::ArtTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::CombinationImpossible
+            | ErrorCode::TransactionWouldFail
+            | ErrorCode::ArtRejected => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::ClaimNotAllowed
+            | ErrorCode::ClaimLimitReached
+            | ErrorCode::PackLimitReached
+            | ErrorCode::NotDiscoverer => StatusCode::FORBIDDEN,
+            ErrorCode::NotEnoughEssence | ErrorCode::PaymentNotVerified => {
+                StatusCode::PAYMENT_REQUIRED
+            }
+            ErrorCode::Unauthorized | ErrorCode::WalletSessionRequired => {
+                StatusCode::UNAUTHORIZED
+            }
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::GenerationUnavailable | ErrorCode::SolanaError => StatusCode::BAD_GATEWAY,
+            ErrorCode::SolanaNotConfigured | ErrorCode::RenderBusy | ErrorCode::ShuttingDown => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            ErrorCode::RenderFailed | ErrorCode::StorageError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// JSON error body shared by every endpoint: `{code, message, details}`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    #[serde(skip)]
+    retry_after: Option<Duration>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+            details: None,
+            retry_after: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Ask the client to come back after `retry_after`, in a Retry-After
+    /// header.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    pub fn rate_limited(scope: &str, retry_after: Duration) -> Self {
+        let secs = retry_after.as_secs().max(1);
+        let mut e = ApiError::new(
+            ErrorCode::RateLimited,
+            format!("Rate limit exceeded for {scope}, retry in {secs}s"),
+        )
+        .with_details(serde_json::json!({ "scope": scope, "retry_after_secs": secs }));
+        e.retry_after = Some(Duration::from_secs(secs));
+        e
+    }
+}
+
+/// Shorthand used throughout the handlers.
+pub fn err(code: ErrorCode, message: impl Into<String>) -> ApiError {
+    ApiError::new(code, message)
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.code.status();
+        match self.retry_after {
+            Some(retry_after) => (
+                status,
+                [(header::RETRY_AFTER, retry_after.as_secs().to_string())],
+                Json(self),
+            )
+                .into_response(),
+            None => (status, Json(self)).into_response(),
+        }
+    }
+}
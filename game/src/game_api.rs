@@ -1,91 +1,506 @@
-use crate::card;
-use crate::card::CardKind;
-use crate::card_cache::{self, CachedCard};
-use crate::game_state::{CraftedCard, GameMode, GamePhase, GameState, HandCard, PlacedCard};
+use crate::card_cache::{self, ArtVersion, CachedCard, CardCache, CardQuery};
+use crate::crafted_art;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::game_state::{
+    Attestation, BaseCard, CraftedCard, GameMode, GamePhase, GameState, HandCard, PlacedCard,
+};
 use crate::generate::AppState;
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use crate::image_variants;
+use crate::indexer;
+use crate::search;
+use crate::solana_api::require_solana;
+use crate::telemetry::SendTraced;
+use crate::wallet_auth::{require_game_owner, require_wallet};
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
 use axum::Json;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct NftCardSelection {
     pub mint_address: String,
     pub card_id: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct NewGameRequest {
     pub mode: GameMode,
-    #[serde(default)]
-    pub wallet_address: Option<String>,
+    /// Needs a wallet session; the game is tied to the signed-in wallet.
     #[serde(default)]
     pub nft_cards: Vec<NftCardSelection>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CombineRequest {
     pub card_indices: Vec<usize>,
     #[serde(default)]
     pub async_image: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct FinalizeCombineRequest {
+    /// `cache_key` of the card `combine` left waiting on its image.
     pub cache_key: String,
-    pub name: String,
-    pub description: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct PlaceRequest {
     pub hand_index: usize,
     pub row: usize,
     pub col: usize,
 }
 
-#[derive(Serialize)]
-pub struct ApiError {
-    pub error: String,
+/// Default and maximum page sizes for `/api/cards`.
+const CARDS_PER_PAGE: usize = 50;
+const MAX_CARDS_PER_PAGE: usize = 200;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CardListKind {
+    Material,
+    Intent,
+    Crafted,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CardListSort {
+    /// Alphabetical by name (case-insensitive).
+    #[default]
+    Name,
+    NameDesc,
+    /// Materials, then intents, then crafted; by name within each.
+    Kind,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListCardsQuery {
+    /// Only cards of this kind.
+    pub kind: Option<CardListKind>,
+    /// Skip crafted cards nobody has discovered yet.
+    #[serde(default)]
+    pub discovered: bool,
+    /// Case-insensitive substring match on the card name.
+    pub q: Option<String>,
+    #[serde(default)]
+    pub sort: CardListSort,
+    /// 1-based page number.
+    pub page: Option<usize>,
+    /// Cards per page (default 50, max 200).
+    pub per_page: Option<usize>,
+}
+
+/// One entry in the `/api/cards` listing — a base card or a crafted card.
+#[derive(Serialize, ToSchema)]
+pub struct CardSummary {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// "material", "intent", or "crafted".
+    pub kind: String,
+    pub image_path: String,
+    /// Always true for base cards.
+    pub discovered: bool,
+}
+
+impl CardSummary {
+    fn base(card: &BaseCard) -> Self {
+        CardSummary {
+            id: card.id.clone(),
+            name: card.name.clone(),
+            description: card.description.clone(),
+            kind: card.kind.clone(),
+            image_path: card.image_path.clone(),
+            discovered: true,
+        }
+    }
+
+    fn crafted(card: CachedCard) -> Self {
+        CardSummary {
+            id: card.id,
+            name: card.name,
+            description: card.description,
+            kind: "crafted".to_string(),
+            image_path: card.image_path,
+            discovered: card.discovered,
+        }
+    }
+
+    fn kind_rank(&self) -> u8 {
+        match self.kind.as_str() {
+            "material" => 0,
+            "intent" => 1,
+            _ => 2,
+        }
+    }
 }
 
-fn err(status: StatusCode, msg: impl Into<String>) -> (StatusCode, Json<ApiError>) {
-    (status, Json(ApiError { error: msg.into() }))
+impl CardListSort {
+    fn cmp(self, a: &CardSummary, b: &CardSummary) -> std::cmp::Ordering {
+        let by_name = |a: &CardSummary, b: &CardSummary| {
+            a.name
+                .to_lowercase()
+                .cmp(&b.name.to_lowercase())
+                .then_with(|| a.id.cmp(&b.id))
+        };
+        match self {
+            CardListSort::Name => by_name(a, b),
+            CardListSort::NameDesc => by_name(b, a),
+            CardListSort::Kind => a
+                .kind_rank()
+                .cmp(&b.kind_rank())
+                .then_with(|| by_name(a, b)),
+        }
+    }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/cards",
+    tag = "cards",
+    params(ListCardsQuery),
+    responses(
+        (status = 200, description = "One page of base and crafted cards", body = crate::openapi::CardListResponse),
+        (status = 400, description = "Invalid query parameters", body = ApiError),
+    )
+)]
 pub async fn list_cards(
     State(state): State<Arc<AppState>>,
-) -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "cards": state.base_cards,
+    query: Result<Query<ListCardsQuery>, QueryRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(query) = query.map_err(|e| err(ErrorCode::InvalidRequest, e.body_text()))?;
+    let per_page = query
+        .per_page
+        .unwrap_or(CARDS_PER_PAGE)
+        .clamp(1, MAX_CARDS_PER_PAGE);
+    let page = query.page.unwrap_or(1).max(1);
+    let offset = (page - 1).saturating_mul(per_page);
+    let sort = query.sort;
+
+    // Base cards are few and in memory; crafted cards are paged by the store
+    let needle = query.q.as_deref().map(str::to_lowercase);
+    let mut base: Vec<CardSummary> = state
+        .base_cards
+        .read()
+        .await
+        .iter()
+        .filter(|c| match query.kind {
+            None => true,
+            Some(CardListKind::Material) => c.kind == "material",
+            Some(CardListKind::Intent) => c.kind == "intent",
+            Some(CardListKind::Crafted) => false,
+        })
+        .filter(|c| {
+            needle
+                .as_deref()
+                .is_none_or(|n| c.name.to_lowercase().contains(n))
+        })
+        .map(CardSummary::base)
+        .collect();
+    base.sort_by(|a, b| sort.cmp(a, b));
+
+    // A crafted card's place in the listing is its place among the crafted
+    // cards plus the base cards sorting ahead of it, so the ones on this page
+    // are among the `per_page + base.len()` from `offset - base.len()` on
+    let skip = offset.saturating_sub(base.len());
+    let (crafted, crafted_total) = match query.kind {
+        None | Some(CardListKind::Crafted) => {
+            let list = state.card_cache.read().await.list(CardQuery {
+                discovered: query.discovered,
+                name: query.q.clone(),
+                descending: matches!(sort, CardListSort::NameDesc),
+                offset: skip,
+                limit: offset.saturating_add(per_page) - skip,
+            });
+            list.await.map_err(|e| err(ErrorCode::StorageError, e))?
+        }
+        Some(_) => (Vec::new(), 0),
+    };
+    let total = base.len() + crafted_total as usize;
+    let crafted: Vec<CardSummary> = crafted.into_iter().map(CardSummary::crafted).collect();
+
+    // Base cards sorting ahead of the first crafted card read come before
+    // this page; the rest merge in with the crafted cards
+    let ahead = match crafted.first() {
+        _ if skip == 0 => 0,
+        Some(first) => base.iter().filter(|b| sort.cmp(b, first).is_lt()).count(),
+        None => base.len(),
+    };
+    let mut cards: Vec<CardSummary> = base.into_iter().skip(ahead).chain(crafted).collect();
+    cards.sort_by(|a, b| sort.cmp(a, b));
+    let page_cards: Vec<CardSummary> = cards
+        .into_iter()
+        .skip(offset - (skip + ahead))
+        .take(per_page)
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "cards": page_cards,
+        "total": total,
+        "page": page,
+        "per_page": per_page,
+    })))
+}
+
+/// An input to a crafted card's recipe.
+#[derive(Serialize, ToSchema)]
+pub struct RecipeInput {
+    pub id: String,
+    /// Empty if the input card is no longer known.
+    pub name: String,
+    pub kind: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RecipeDetail {
+    pub materials: Vec<RecipeInput>,
+    pub intent: Option<RecipeInput>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CardDetail {
+    #[serde(flatten)]
+    pub card: CardSummary,
+    /// How a crafted card was made. Null for base cards and for crafted
+    /// cards cached before provenance was recorded.
+    pub recipe: Option<RecipeDetail>,
+    /// Unix seconds when the card was first discovered, if known.
+    pub discovered_at: Option<u64>,
+    /// Unix seconds when the card was first generated, if known.
+    pub created_at: Option<u64>,
+    /// Wallet that discovered the card, when a wallet did. Only it can
+    /// upload replacement art.
+    pub discovered_by: Option<String>,
+    /// Images the card had before its art was replaced, oldest first.
+    pub previous_art: Vec<ArtVersion>,
+    /// Combines that have produced this card. Zero for base cards.
+    pub times_crafted: u64,
+    /// NFTs minted of this card through this server.
+    pub mint_count: u64,
+    /// Edition numbers handed out so far, including mints still pending.
+    pub editions: u64,
+    /// Most editions that will ever be minted, if the card is capped.
+    pub supply_cap: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/cards/{id}",
+    tag = "cards",
+    params(("id" = String, Path, description = "Card id")),
+    responses(
+        (status = 200, description = "Card with recipe provenance", body = CardDetail),
+        (status = 404, description = "Card not found", body = ApiError),
+    )
+)]
+pub async fn get_card(
+    State(state): State<Arc<AppState>>,
+    Path(card_id): Path<String>,
+) -> Result<Json<CardDetail>, ApiError> {
+    let base_cards = state.base_cards.read().await;
+    let cache = state.card_cache.read().await;
+    let mint_count = cache.mint_count(&card_id);
+    let editions = cache.editions(&card_id);
+
+    if let Some(base) = base_cards.iter().find(|b| b.id == card_id) {
+        return Ok(Json(CardDetail {
+            card: CardSummary {
+                id: base.id.clone(),
+                name: base.name.clone(),
+                description: base.description.clone(),
+                kind: base.kind.clone(),
+                image_path: base.image_path.clone(),
+                discovered: true,
+            },
+            recipe: None,
+            discovered_at: None,
+            created_at: None,
+            discovered_by: None,
+            previous_art: Vec::new(),
+            times_crafted: 0,
+            mint_count,
+            editions,
+            supply_cap: state.supply.cap(&card_id, false),
+        }));
+    }
+
+    let cached = cache
+        .get(&card_id)
+        .filter(|c| !c.impossible)
+        .ok_or_else(|| err(ErrorCode::CardNotFound, "Card not found"))?;
+
+    // Inputs may be base cards or other crafted cards
+    let input = |id: &str| {
+        if let Some(base) = base_cards.iter().find(|b| b.id == id) {
+            RecipeInput {
+                id: id.to_string(),
+                name: base.name.clone(),
+                kind: base.kind.clone(),
+            }
+        } else {
+            RecipeInput {
+                id: id.to_string(),
+                name: cache.get(id).map(|c| c.name.clone()).unwrap_or_default(),
+                kind: "crafted".to_string(),
+            }
+        }
+    };
+    let recipe = cached.recipe.as_ref().map(|r| RecipeDetail {
+        materials: r.material_ids.iter().map(|id| input(id)).collect(),
+        intent: r.intent_id.as_deref().map(input),
+    });
+
+    Ok(Json(CardDetail {
+        card: CardSummary {
+            id: card_id.clone(),
+            name: cached.name.clone(),
+            description: cached.description.clone(),
+            kind: "crafted".to_string(),
+            image_path: cached.image_path.clone(),
+            discovered: cached.discovered,
+        },
+        recipe,
+        discovered_at: cached.discovered_at,
+        created_at: cached.created_at,
+        discovered_by: cached.discovered_by.clone(),
+        previous_art: cached.previous_art.clone(),
+        times_crafted: cached.times_crafted,
+        mint_count,
+        editions,
+        supply_cap: state.supply.cap(&card_id, true),
     }))
 }
 
+/// Default and maximum result counts for `/api/cards/search`.
+const SEARCH_LIMIT: usize = 20;
+const MAX_SEARCH_LIMIT: usize = 100;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchCardsQuery {
+    /// Search text; matches name substrings and tolerates small typos.
+    pub q: String,
+    /// Maximum results (default 20, max 100).
+    pub limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/cards/search",
+    tag = "cards",
+    params(SearchCardsQuery),
+    responses(
+        (status = 200, description = "Matching cards, best match first", body = crate::openapi::CardSearchResponse),
+        (status = 400, description = "Missing or empty query", body = ApiError),
+    )
+)]
+pub async fn search_cards(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<SearchCardsQuery>, QueryRejection>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Query(query) = query.map_err(|e| err(ErrorCode::InvalidRequest, e.body_text()))?;
+    if query.q.trim().is_empty() {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            "Search query must not be empty",
+        ));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+
+    // Names are ranked here, typos included; only the best matches are read in full
+    let base_cards = state.base_cards.read().await;
+    let crafted_names = state.card_cache.read().await.names();
+    let crafted_names = crafted_names
+        .await
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    let names = base_cards
+        .iter()
+        .map(|c| (&c.id, &c.name))
+        .chain(crafted_names.iter().map(|(id, name)| (id, name)));
+    let mut hits: Vec<(usize, &String, &String)> = names
+        .filter_map(|(id, name)| search::match_rank(&query.q, name).map(|rank| (rank, id, name)))
+        .collect();
+    hits.sort_by(|(ra, _, a), (rb, _, b)| {
+        ra.cmp(rb)
+            .then_with(|| a.len().cmp(&b.len()))
+            .then_with(|| a.cmp(b))
+    });
+    let cache = state.card_cache.read().await;
+    let results: Vec<CardSummary> = hits
+        .into_iter()
+        .filter_map(|(_, id, _)| match base_cards.iter().find(|b| &b.id == id) {
+            Some(base) => Some(CardSummary::base(base)),
+            None => cache
+                .get(id)
+                .filter(|c| !c.impossible)
+                .map(CardSummary::crafted),
+        })
+        .take(limit)
+        .collect();
+
+    Ok(Json(serde_json::json!({ "results": results })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/game/new",
+    tag = "game",
+    request_body = NewGameRequest,
+    responses(
+        (status = 200, description = "Newly created game", body = GameState),
+        (status = 400, description = "Invalid wallet or NFT selection", body = ApiError),
+        (status = 401, description = "NFT cards selected without a wallet session", body = ApiError),
+        (status = 502, description = "Failed to verify NFT ownership", body = ApiError),
+        (status = 503, description = "Server is shutting down", body = ApiError),
+    )
+)]
 pub async fn new_game(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<NewGameRequest>,
-) -> Result<Json<GameState>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<GameState>, ApiError> {
+    if state.shutting_down.load(Ordering::Relaxed) {
+        return Err(err(ErrorCode::ShuttingDown, "Server is shutting down"));
+    }
+    // Signed-in players' games belong to their wallet
+    let wallet = crate::wallet_auth::session_wallet(&state, &headers);
     let id = uuid::Uuid::new_v4().to_string();
-    let mut game = GameState::new(id.clone(), req.mode, &state.categories, &state.base_cards);
+    let base_cards = state.base_cards.read().await;
+    let mut game = GameState::new(
+        id.clone(),
+        req.mode,
+        &state.categories.read().await,
+        &base_cards,
+    );
 
     // If player has NFT cards selected, verify and add them to hand
     if !req.nft_cards.is_empty() {
         if req.nft_cards.len() > 4 {
-            return Err(err(StatusCode::BAD_REQUEST, "Max 4 NFT cards"));
+            return Err(err(ErrorCode::InvalidRequest, "Max 4 NFT cards"));
         }
 
-        // Verify ownership if Solana is configured
-        if let (Some(wallet), Some(solana)) = (&req.wallet_address, state.solana.as_deref()) {
-            let owned = solana
-                .query_owned_cards(wallet)
+        // Only the signed-in wallet's own NFTs can be played
+        let wallet = require_wallet(&state, &headers)?;
+        if let Some(solana) = state.solana.as_deref() {
+            let owned = indexer::owned_cards(&state, solana, &wallet)
                 .await
-                .map_err(|e| err(StatusCode::BAD_GATEWAY, e))?;
+                .map_err(|e| err(ErrorCode::SolanaError, e))?;
 
             for nft in &req.nft_cards {
-                if !owned.iter().any(|o| o.mint_address == nft.mint_address && o.card_id == nft.card_id) {
+                if !owned
+                    .iter()
+                    .any(|o| o.mint_address == nft.mint_address && o.card_id == nft.card_id)
+                {
                     return Err(err(
-                        StatusCode::BAD_REQUEST,
+                        ErrorCode::CardNotOwned,
                         format!("NFT {} not owned by wallet", nft.mint_address),
                     ));
                 }
@@ -97,7 +512,7 @@ pub async fn new_game(
         let mut nft_hand_cards = Vec::new();
         for nft in &req.nft_cards {
             // Check base cards first
-            if let Some(base) = state.base_cards.iter().find(|b| b.id == nft.card_id) {
+            if let Some(base) = base_cards.iter().find(|b| b.id == nft.card_id) {
                 let mut hc = HandCard::from_base(base);
                 hc.nft_mint = Some(nft.mint_address.clone());
                 nft_hand_cards.push(hc);
@@ -122,51 +537,86 @@ pub async fn new_game(
         }
     }
 
-    // Set wallet on player state
-    if let Some(wallet) = req.wallet_address {
-        game.players[0].wallet = Some(wallet);
-    }
+    game.players[0].wallet = wallet;
 
-    state.games.write().await.insert(id, game.clone());
+    state.games_mut().await.insert(id, game.clone());
     Ok(Json(game))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/game/{id}",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    responses(
+        (status = 200, description = "Current game state", body = GameState),
+        (status = 404, description = "Game not found", body = ApiError),
+    )
+)]
 pub async fn get_game(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<GameState>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<GameState>, ApiError> {
     let games = state.games.read().await;
     match games.get(&id) {
         Some(game) => Ok(Json(game.clone())),
-        None => Err(err(StatusCode::NOT_FOUND, "Game not found")),
+        None => Err(err(ErrorCode::GameNotFound, "Game not found")),
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/game/{id}/combine",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    request_body = CombineRequest,
+    responses(
+        (status = 200, description = "Crafted card and updated game", body = crate::openapi::CombineResponse),
+        (status = 400, description = "Invalid selection or game over", body = ApiError),
+        (status = 401, description = "Game belongs to a wallet that isn't signed in", body = ApiError),
+        (status = 404, description = "Game not found", body = ApiError),
+        (status = 422, description = "Combination is impossible", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Generation server unavailable", body = ApiError),
+    )
+)]
 pub async fn combine(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    headers: HeaderMap,
     Json(req): Json<CombineRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let (game, player_idx) = {
         let games = state.games.read().await;
         let game = games
             .get(&id)
-            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Game not found"))?;
+            .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+        require_game_owner(&state, &headers, game)?;
         if game.phase == GamePhase::GameOver {
-            return Err(err(StatusCode::BAD_REQUEST, "Game is over"));
+            return Err(err(ErrorCode::GameOver, "Game is over"));
         }
         (game.clone(), game.current_player)
     };
 
+    if let Some(wallet) = &game.players[player_idx].wallet {
+        state
+            .rate_limiter
+            .check_wallet(wallet)
+            .map_err(|retry_after| ApiError::rate_limited("wallet", retry_after))?;
+    }
+
     let hand = &game.players[player_idx].hand;
 
     // Validate indices
     if req.card_indices.len() < 2 || req.card_indices.len() > 4 {
-        return Err(err(StatusCode::BAD_REQUEST, "Select 2-4 cards to combine"));
+        return Err(err(
+            ErrorCode::InvalidCombination,
+            "Select 2-4 cards to combine",
+        ));
     }
     for &idx in &req.card_indices {
         if idx >= hand.len() {
-            return Err(err(StatusCode::BAD_REQUEST, "Invalid card index"));
+            return Err(err(ErrorCode::InvalidCardIndex, "Invalid card index"));
         }
     }
 
@@ -182,12 +632,15 @@ pub async fn combine(
 
     if material_like_count < 1 {
         return Err(err(
-            StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidCombination,
             "Need at least 1 material card",
         ));
     }
     if intent_count > 1 {
-        return Err(err(StatusCode::BAD_REQUEST, "At most 1 intent allowed"));
+        return Err(err(
+            ErrorCode::InvalidCombination,
+            "At most 1 intent allowed",
+        ));
     }
 
     // Build cache key from card IDs
@@ -201,34 +654,33 @@ pub async fn combine(
         .find(|c| c.kind == "intent")
         .map(|c| c.id.as_str());
     let key = card_cache::compute_crafted_card_id(&material_ids, intent_id);
+    let recipe = card_cache::Recipe::new(&material_ids, intent_id);
 
-    // Check cache
+    // Check cache (stale "not possible" verdicts fall through to be judged again)
     {
-        let mut cache = state.card_cache.write().await;
-        if let Some(cached) = cache.get(&key).cloned() {
+        let cache = state.card_cache.write().await;
+        if let Some(cached) = cache.get(&key).filter(|c| !cache.needs_recheck(c)) {
             if cached.impossible {
                 return Err(err(
-                    StatusCode::UNPROCESSABLE_ENTITY,
+                    ErrorCode::CombinationImpossible,
                     "Combination not possible",
                 ));
             }
             let is_new = !cached.discovered;
-            if is_new {
-                // Mark as discovered
+            if is_new || cached.recipe.is_none() {
+                // Mark as discovered, backfilling provenance for older entries
                 let mut updated = cached.clone();
-                updated.discovered = true;
+                if is_new {
+                    updated.discovered = true;
+                    updated.discovered_at = Some(card_cache::now_secs());
+                    updated.discovered_by = game.players[player_idx].wallet.clone();
+                }
+                updated.recipe.get_or_insert(recipe);
                 cache.insert(key.clone(), updated);
-                cache.save(std::path::Path::new("cards/card-cache.json"));
             }
-            return finish_combine(
-                &state,
-                &id,
-                player_idx,
-                &req.card_indices,
-                &cached,
-                is_new,
-            )
-            .await;
+            cache.record_craft(&key);
+            return finish_combine(&state, &id, player_idx, &req.card_indices, &cached, is_new)
+                .await;
         }
     }
 
@@ -253,35 +705,40 @@ pub async fn combine(
         .client
         .post(format!("{}/combine", state.generation_url))
         .json(&serde_json::json!({ "cards": combine_cards }))
-        .send()
+        .send_traced(tracing::info_span!("generation.combine"))
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Generation server error: {e}")))?;
+        .map_err(|e| {
+            err(
+                ErrorCode::GenerationUnavailable,
+                format!("Generation server error: {e}"),
+            )
+        })?;
 
     if !combine_resp.status().is_success() {
         let body = combine_resp.text().await.unwrap_or_default();
         return Err(err(
-            StatusCode::BAD_GATEWAY,
+            ErrorCode::GenerationUnavailable,
             format!("Combination failed: {body}"),
         ));
     }
 
-    let combined: serde_json::Value = combine_resp
-        .json()
-        .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Parse error: {e}")))?;
-
-    let card_name = combined["name"]
-        .as_str()
-        .unwrap_or("Unknown")
-        .to_string();
-    let card_desc = combined["description"]
-        .as_str()
-        .unwrap_or("")
-        .to_string();
+    let combined: serde_json::Value = combine_resp.json().await.map_err(|e| {
+        err(
+            ErrorCode::GenerationUnavailable,
+            format!("Parse error: {e}"),
+        )
+    })?;
+
+    let card_name = combined["name"].as_str().unwrap_or("Unknown").to_string();
+    let card_desc = combined["description"].as_str().unwrap_or("").to_string();
+    let combine_version = combined["combine_version"].as_str().map(str::to_string);
+    if let Some(version) = &combine_version {
+        state.card_cache.read().await.set_combine_version(version);
+    }
 
     // Check for "Not possible" — cache it so we don't retry
     if card_name.to_lowercase().contains("not possible") {
-        let mut cache = state.card_cache.write().await;
+        let cache = state.card_cache.write().await;
         cache.insert(
             key.clone(),
             CachedCard {
@@ -291,21 +748,34 @@ pub async fn combine(
                 id: key,
                 discovered: false,
                 impossible: true,
+                recipe: Some(recipe),
+                discovered_at: None,
+                created_at: Some(card_cache::now_secs()),
+                times_crafted: 0,
+                combine_version,
+                discovered_by: None,
+                previous_art: Vec::new(),
+                art_path: None,
             },
         );
-        cache.save(std::path::Path::new("cards/card-cache.json"));
         return Err(err(
-            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::CombinationImpossible,
             "Combination not possible",
         ));
     }
 
     // If async_image requested, return early with name/desc before image generation
     if req.async_image {
-        let mut games = state.games.write().await;
+        state
+            .card_cache
+            .write()
+            .await
+            .set_pending_recipe(key.clone(), recipe);
+
+        let mut games = state.games_mut().await;
         let game = games
             .get_mut(&id)
-            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Game not found"))?;
+            .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
 
         // Remove used cards from hand (highest index first)
         let mut sorted_indices: Vec<usize> = req.card_indices.to_vec();
@@ -317,6 +787,7 @@ pub async fn combine(
         }
 
         // Add crafted card with empty image_path (pending)
+        game.players[player_idx].crafted.push(key.clone());
         game.players[player_idx].hand.push(HandCard {
             name: card_name.clone(),
             description: card_desc.clone(),
@@ -325,6 +796,7 @@ pub async fn combine(
             id: key.clone(),
             nft_mint: None,
         });
+        state.game_events.publish(game);
 
         return Ok(Json(serde_json::json!({
             "game": game.clone(),
@@ -346,42 +818,31 @@ pub async fn combine(
             "name": card_name,
             "description": card_desc,
         }))
-        .send()
+        .send_traced(tracing::info_span!("generation.generate_image"))
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Image generation error: {e}")))?;
+        .map_err(|e| {
+            err(
+                ErrorCode::GenerationUnavailable,
+                format!("Image generation error: {e}"),
+            )
+        })?;
 
     if !image_resp.status().is_success() {
-        return Err(err(StatusCode::BAD_GATEWAY, "Image generation failed"));
+        return Err(err(
+            ErrorCode::GenerationUnavailable,
+            "Image generation failed",
+        ));
     }
 
-    let art_bytes = image_resp
-        .bytes()
-        .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Image read error: {e}")))?;
+    let art_bytes = image_resp.bytes().await.map_err(|e| {
+        err(
+            ErrorCode::GenerationUnavailable,
+            format!("Image read error: {e}"),
+        )
+    })?;
 
-    // Render the card
-    let png = card::render_card(&card_name, &art_bytes, &CardKind::Material)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, format!("Card render error: {e}")))?;
-
-    // Save to disk — use card ID for unique filename
-    let safe_name = card_name
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == ' ' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>()
-        .replace(' ', "-");
-    let filename = format!("{safe_name}-{key}.png");
-    let disk_path = format!("cards/crafted/{filename}");
-    let serve_path = format!("/cards/crafted/{filename}");
-
-    let _ = std::fs::create_dir_all("cards/crafted");
-    std::fs::write(&disk_path, &png)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, format!("File write error: {e}")))?;
+    let (art_path, serve_path) =
+        crafted_art::keep_and_render(&state, &key, &card_name, art_bytes).await?;
 
     let cached = CachedCard {
         name: card_name,
@@ -390,13 +851,20 @@ pub async fn combine(
         id: key.clone(),
         discovered: true,
         impossible: false,
+        recipe: Some(recipe),
+        discovered_at: Some(card_cache::now_secs()),
+        created_at: Some(card_cache::now_secs()),
+        times_crafted: 1,
+        combine_version,
+        discovered_by: game.players[player_idx].wallet.clone(),
+        previous_art: Vec::new(),
+        art_path: Some(art_path),
     };
 
     // Save to cache
     {
-        let mut cache = state.card_cache.write().await;
+        let cache = state.card_cache.write().await;
         cache.insert(key, cached.clone());
-        cache.save(std::path::Path::new("cards/card-cache.json"));
     }
 
     finish_combine(&state, &id, player_idx, &req.card_indices, &cached, true).await
@@ -409,11 +877,11 @@ async fn finish_combine(
     card_indices: &[usize],
     cached: &CachedCard,
     is_new: bool,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
-    let mut games = state.games.write().await;
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut games = state.games_mut().await;
     let game = games
         .get_mut(game_id)
-        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Game not found"))?;
+        .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
 
     // Remove used cards from hand (highest index first to avoid shifting)
     let mut sorted_indices: Vec<usize> = card_indices.to_vec();
@@ -424,99 +892,89 @@ async fn finish_combine(
         }
     }
 
-    // Add crafted card to hand
+    // Add crafted card to hand, in foil for its first discovery
+    let image_path = if is_new {
+        image_variants::foil_url(&cached.image_path)
+    } else {
+        cached.image_path.clone()
+    };
+    game.players[player_idx].crafted.push(cached.id.clone());
     game.players[player_idx].hand.push(HandCard {
         name: cached.name.clone(),
         description: cached.description.clone(),
         kind: "crafted".to_string(),
-        image_path: cached.image_path.clone(),
+        image_path: image_path.clone(),
         id: cached.id.clone(),
         nft_mint: None,
     });
+    state.game_events.publish(game);
 
     Ok(Json(serde_json::json!({
         "game": game.clone(),
         "crafted_card": {
             "name": cached.name,
             "description": cached.description,
-            "image_path": cached.image_path,
+            "image_path": image_path,
         },
         "is_new": is_new,
     })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/game/{id}/finalize-combine",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    request_body = FinalizeCombineRequest,
+    responses(
+        (status = 200, description = "Rendered card image", body = crate::openapi::FinalizeCombineResponse),
+        (status = 401, description = "Game belongs to a wallet that isn't signed in", body = ApiError),
+        (status = 404, description = "Game not found, or no card in hand waiting on an image", body = ApiError),
+        (status = 502, description = "Generation server unavailable", body = ApiError),
+    )
+)]
 pub async fn finalize_combine(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    headers: HeaderMap,
     Json(req): Json<FinalizeCombineRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
-    // Generate image
-    let image_resp = state
-        .client
-        .post(format!("{}/generate-image", state.generation_url))
-        .json(&serde_json::json!({
-            "name": req.name,
-            "description": req.description,
-        }))
-        .send()
-        .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Image generation error: {e}")))?;
-
-    if !image_resp.status().is_success() {
-        return Err(err(StatusCode::BAD_GATEWAY, "Image generation failed"));
-    }
-
-    let art_bytes = image_resp
-        .bytes()
-        .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Image read error: {e}")))?;
-
-    // Render the card
-    let png = card::render_card(&req.name, &art_bytes, &CardKind::Material)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, format!("Card render error: {e}")))?;
-
-    // Save to disk — use card ID for unique filename
-    let safe_name = req
-        .name
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == ' ' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>()
-        .replace(' ', "-");
-    let filename = format!("{safe_name}-{}.png", req.cache_key);
-    let disk_path = format!("cards/crafted/{filename}");
-    let serve_path = format!("/cards/crafted/{filename}");
-
-    let _ = std::fs::create_dir_all("cards/crafted");
-    std::fs::write(&disk_path, &png)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, format!("File write error: {e}")))?;
-
-    let cached = CachedCard {
-        name: req.name.clone(),
-        description: req.description.clone(),
-        image_path: serve_path.clone(),
-        id: req.cache_key.clone(),
-        discovered: true,
-        impossible: false,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    // Only a card `combine` left waiting in this hand, named as it was then
+    let (pending, discovered_by) = {
+        let games = state.games.read().await;
+        let game = games
+            .get(&id)
+            .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+        require_game_owner(&state, &headers, game)?;
+        let player = &game.players[game.current_player];
+        let pending = player
+            .hand
+            .iter()
+            .find(|c| c.id == req.cache_key && c.kind == "crafted" && c.image_path.is_empty())
+            .cloned()
+            .ok_or_else(|| {
+                err(
+                    ErrorCode::CardNotFound,
+                    "No card in hand is waiting on an image",
+                )
+            })?;
+        (pending, player.wallet.clone())
     };
 
-    // Save to cache
-    {
-        let mut cache = state.card_cache.write().await;
-        cache.insert(req.cache_key.clone(), cached);
-        cache.save(std::path::Path::new("cards/card-cache.json"));
-    }
+    // Another game may have rendered this card meanwhile; then its image will do
+    let rendered = rendered_card(&*state.card_cache.read().await, &req.cache_key);
+    let serve_path = match rendered {
+        Some(cached) => cached.image_path,
+        None => render_pending(&state, pending, discovered_by).await?,
+    };
 
-    // Update the pending card's image_path in the player's hand
-    let mut games = state.games.write().await;
+    // Update the pending card's image_path in the player's hand. Only a first
+    // discovery is left pending, so it gets the foil
+    let serve_path = image_variants::foil_url(&serve_path);
+    let mut games = state.games_mut().await;
     let game = games
         .get_mut(&id)
-        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Game not found"))?;
+        .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
 
     let player_idx = game.current_player;
     for card in &mut game.players[player_idx].hand {
@@ -525,6 +983,7 @@ pub async fn finalize_combine(
             break;
         }
     }
+    state.game_events.publish(game);
 
     Ok(Json(serde_json::json!({
         "game": game.clone(),
@@ -532,45 +991,162 @@ pub async fn finalize_combine(
     })))
 }
 
+/// `card_id`'s cache entry, if it is a card with an image.
+fn rendered_card(cache: &CardCache, card_id: &str) -> Option<CachedCard> {
+    cache
+        .get(card_id)
+        .filter(|c| !c.impossible && !cache.needs_recheck(c))
+}
+
+/// Generate and render the image of `pending`, a first discovery left
+/// waiting in a hand, and cache the card. Returns its image path, which is
+/// another request's if that one cached the card first.
+async fn render_pending(
+    state: &AppState,
+    pending: HandCard,
+    discovered_by: Option<String>,
+) -> Result<String, ApiError> {
+    let image_resp = state
+        .client
+        .post(format!("{}/generate-image", state.generation_url))
+        .json(&serde_json::json!({
+            "name": pending.name,
+            "description": pending.description,
+        }))
+        .send_traced(tracing::info_span!("generation.generate_image"))
+        .await
+        .map_err(|e| {
+            err(
+                ErrorCode::GenerationUnavailable,
+                format!("Image generation error: {e}"),
+            )
+        })?;
+
+    if !image_resp.status().is_success() {
+        return Err(err(
+            ErrorCode::GenerationUnavailable,
+            "Image generation failed",
+        ));
+    }
+
+    let art_bytes = image_resp.bytes().await.map_err(|e| {
+        err(
+            ErrorCode::GenerationUnavailable,
+            format!("Image read error: {e}"),
+        )
+    })?;
+
+    let (art_path, serve_path) =
+        crafted_art::keep_and_render(state, &pending.id, &pending.name, art_bytes).await?;
+
+    let cache = state.card_cache.write().await;
+    if let Some(existing) = rendered_card(&cache, &pending.id) {
+        return Ok(existing.image_path);
+    }
+    let cached = CachedCard {
+        name: pending.name,
+        description: pending.description,
+        image_path: serve_path.clone(),
+        id: pending.id.clone(),
+        discovered: true,
+        impossible: false,
+        recipe: cache.take_pending_recipe(&pending.id),
+        discovered_at: Some(card_cache::now_secs()),
+        created_at: Some(card_cache::now_secs()),
+        times_crafted: 1,
+        combine_version: cache.combine_version(),
+        discovered_by,
+        previous_art: Vec::new(),
+        art_path: Some(art_path),
+    };
+    cache.insert(pending.id, cached);
+    Ok(serve_path)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/game/{id}/place",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    request_body = PlaceRequest,
+    responses(
+        (status = 200, description = "Placement outcome, including any judgment", body = crate::openapi::PlaceResponse),
+        (status = 400, description = "Invalid placement", body = ApiError),
+        (status = 401, description = "Game belongs to a wallet that isn't signed in", body = ApiError),
+        (status = 404, description = "Game not found", body = ApiError),
+        (status = 502, description = "Judge unavailable", body = ApiError),
+    )
+)]
 pub async fn place(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    headers: HeaderMap,
     Json(req): Json<PlaceRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let (game, player_idx) = {
         let games = state.games.read().await;
         let game = games
             .get(&id)
-            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Game not found"))?;
+            .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+        require_game_owner(&state, &headers, game)?;
         if game.phase == GamePhase::GameOver {
-            return Err(err(StatusCode::BAD_REQUEST, "Game is over"));
+            return Err(err(ErrorCode::GameOver, "Game is over"));
         }
         if game.has_placed {
-            return Err(err(StatusCode::BAD_REQUEST, "Already placed a card this turn"));
+            return Err(err(
+                ErrorCode::AlreadyPlaced,
+                "Already placed a card this turn",
+            ));
         }
         (game.clone(), game.current_player)
     };
 
     if req.row >= 3 || req.col >= 3 {
-        return Err(err(StatusCode::BAD_REQUEST, "Invalid board position"));
+        return Err(err(
+            ErrorCode::InvalidBoardPosition,
+            "Invalid board position",
+        ));
     }
     if req.hand_index >= game.players[player_idx].hand.len() {
-        return Err(err(StatusCode::BAD_REQUEST, "Invalid card index"));
+        return Err(err(ErrorCode::InvalidCardIndex, "Invalid card index"));
     }
 
     let hand_card = &game.players[player_idx].hand[req.hand_index];
     if hand_card.kind != "crafted" {
         return Err(err(
-            StatusCode::BAD_REQUEST,
+            ErrorCode::CardNotPlaceable,
             "Only crafted cards can be placed",
         ));
     }
 
-    let crafted = CraftedCard {
-        name: hand_card.name.clone(),
-        description: hand_card.description.clone(),
-        image_path: hand_card.image_path.clone(),
-        id: hand_card.id.clone(),
+    // The hand is only trusted as far as the card cache backs it: the card must
+    // be a real, finished crafted card, and the cached copy is what gets judged
+    let crafted = {
+        let cache = state.card_cache.read().await;
+        let cached = cache.get(&hand_card.id).ok_or_else(|| {
+            err(
+                ErrorCode::CardNotPlaceable,
+                "Card is not a known crafted card",
+            )
+        })?;
+        if cached.impossible {
+            return Err(err(
+                ErrorCode::CardNotPlaceable,
+                "Card is an impossible combination",
+            ));
+        }
+        if cached.image_path.is_empty() {
+            return Err(err(
+                ErrorCode::CardNotPlaceable,
+                "Card image is still being generated",
+            ));
+        }
+        CraftedCard {
+            name: cached.name.clone(),
+            description: cached.description.clone(),
+            image_path: cached.image_path.clone(),
+            id: hand_card.id.clone(),
+        }
     };
     let cell = &game.board[req.row][req.col];
 
@@ -579,7 +1155,10 @@ pub async fn place(
     // Check if cell is occupied by opponent
     if let Some(placed) = &cell.card {
         if placed.owner == player_idx {
-            return Err(err(StatusCode::BAD_REQUEST, "You already own this cell"));
+            return Err(err(
+                ErrorCode::CellAlreadyOwned,
+                "You already own this cell",
+            ));
         }
 
         // Contest! Call judge
@@ -597,24 +1176,28 @@ pub async fn place(
                     "description": crafted.description,
                 },
             }))
-            .send()
+            .send_traced(tracing::info_span!("generation.judge"))
             .await
-            .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Judge error: {e}")))?;
+            .map_err(|e| {
+                err(
+                    ErrorCode::GenerationUnavailable,
+                    format!("Judge error: {e}"),
+                )
+            })?;
 
         if !judge_resp.status().is_success() {
-            return Err(err(StatusCode::BAD_GATEWAY, "Judge call failed"));
+            return Err(err(ErrorCode::GenerationUnavailable, "Judge call failed"));
         }
 
-        let judge_result: serde_json::Value = judge_resp
-            .json()
-            .await
-            .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Judge parse error: {e}")))?;
+        let judge_result: serde_json::Value = judge_resp.json().await.map_err(|e| {
+            err(
+                ErrorCode::GenerationUnavailable,
+                format!("Judge parse error: {e}"),
+            )
+        })?;
 
         let winner = judge_result["winner"].as_str().unwrap_or("a");
-        let reason = judge_result["reason"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
+        let reason = judge_result["reason"].as_str().unwrap_or("").to_string();
 
         judgment = Some(serde_json::json!({
             "winner": winner,
@@ -639,7 +1222,7 @@ pub async fn place(
     }
 
     // Place the card
-    let mut games = state.games.write().await;
+    let mut games = state.games_mut().await;
     let game = games.get_mut(&id).unwrap();
 
     // If replacing an opponent's card, decrease their score
@@ -658,6 +1241,7 @@ pub async fn place(
     game.players[player_idx].score += 1;
     game.has_placed = true;
     game.check_winner();
+    state.game_events.publish(game);
 
     Ok(Json(serde_json::json!({
         "result": if judgment.is_some() { "conquered" } else { "placed" },
@@ -666,34 +1250,49 @@ pub async fn place(
     })))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct DiscardRequest {
     pub card_indices: Vec<usize>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/game/{id}/discard",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    request_body = DiscardRequest,
+    responses(
+        (status = 200, description = "Updated game", body = GameState),
+        (status = 400, description = "Invalid discard", body = ApiError),
+        (status = 401, description = "Game belongs to a wallet that isn't signed in", body = ApiError),
+        (status = 404, description = "Game not found", body = ApiError),
+    )
+)]
 pub async fn discard(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    headers: HeaderMap,
     Json(req): Json<DiscardRequest>,
-) -> Result<Json<GameState>, (StatusCode, Json<ApiError>)> {
-    let mut games = state.games.write().await;
+) -> Result<Json<GameState>, ApiError> {
+    let mut games = state.games_mut().await;
     let game = games
         .get_mut(&id)
-        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Game not found"))?;
+        .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+    require_game_owner(&state, &headers, game)?;
 
     if game.phase == GamePhase::GameOver {
-        return Err(err(StatusCode::BAD_REQUEST, "Game is over"));
+        return Err(err(ErrorCode::GameOver, "Game is over"));
     }
 
     if req.card_indices.is_empty() || req.card_indices.len() > 3 {
-        return Err(err(StatusCode::BAD_REQUEST, "Discard 1-3 cards"));
+        return Err(err(ErrorCode::InvalidRequest, "Discard 1-3 cards"));
     }
 
     let player_idx = game.current_player;
     let hand_len = game.players[player_idx].hand.len();
     for &idx in &req.card_indices {
         if idx >= hand_len {
-            return Err(err(StatusCode::BAD_REQUEST, "Invalid card index"));
+            return Err(err(ErrorCode::InvalidCardIndex, "Invalid card index"));
         }
     }
 
@@ -704,28 +1303,139 @@ pub async fn discard(
     for idx in sorted {
         game.players[player_idx].hand.remove(idx);
     }
+    state.game_events.publish(game);
 
     Ok(Json(game.clone()))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/game/{id}/end-turn",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    responses(
+        (status = 200, description = "Updated game", body = GameState),
+        (status = 400, description = "Game over", body = ApiError),
+        (status = 401, description = "Game belongs to a wallet that isn't signed in", body = ApiError),
+        (status = 404, description = "Game not found", body = ApiError),
+    )
+)]
 pub async fn end_turn(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<GameState>, (StatusCode, Json<ApiError>)> {
-    let mut games = state.games.write().await;
+    headers: HeaderMap,
+) -> Result<Json<GameState>, ApiError> {
+    let mut games = state.games_mut().await;
     let game = games
         .get_mut(&id)
-        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Game not found"))?;
+        .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+    require_game_owner(&state, &headers, game)?;
 
     if game.phase == GamePhase::GameOver {
-        return Err(err(StatusCode::BAD_REQUEST, "Game is over"));
+        return Err(err(ErrorCode::GameOver, "Game is over"));
     }
 
-    game.advance_turn(&state.base_cards);
+    game.advance_turn(&state.base_cards.read().await);
+    state.game_events.publish(game);
 
     Ok(Json(game.clone()))
 }
 
+/// Write a finished game's result on-chain as a memo (see
+/// `GameState::result_record`). Attesting again returns the first record.
+#[utoipa::path(
+    post,
+    path = "/api/game/{id}/attest",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    responses(
+        (status = 200, description = "On-chain result record", body = Attestation),
+        (status = 400, description = "Game not over yet, or not a wallet game", body = ApiError),
+        (status = 401, description = "Game belongs to a wallet that isn't signed in", body = ApiError),
+        (status = 404, description = "Game not found", body = ApiError),
+        (status = 409, description = "Game is being attested", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana or attestation not configured", body = ApiError),
+    )
+)]
+pub async fn attest(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<Attestation>, ApiError> {
+    let solana = require_solana(&state)?;
+    if !solana.attest_results {
+        return Err(err(
+            ErrorCode::SolanaNotConfigured,
+            "Result attestation is off ([solana] attest_results)",
+        ));
+    }
+
+    // Marked as attesting under the lock, so only one request sends the memo
+    let record = {
+        let mut games = state.games_mut().await;
+        let game = games
+            .get_mut(&id)
+            .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+        if game.players[0].wallet.is_none() {
+            return Err(err(
+                ErrorCode::InvalidRequest,
+                "Only wallet games can be attested",
+            ));
+        }
+        require_game_owner(&state, &headers, game)?;
+        if game.phase != GamePhase::GameOver {
+            return Err(err(ErrorCode::InvalidRequest, "Game is not over yet"));
+        }
+        if let Some(attestation) = &game.attestation {
+            return Ok(Json(attestation.clone()));
+        }
+        if game.attesting {
+            return Err(err(
+                ErrorCode::AttestationInProgress,
+                "Game is being attested",
+            ));
+        }
+        game.attesting = true;
+        game.result_record()
+    };
+
+    // Sent and recorded in a task of its own, so a client going away can't
+    // leave the game marked as attesting
+    let task = tokio::spawn(send_attestation(state.clone(), id, record));
+    let attestation = task
+        .await
+        .map_err(|e| err(ErrorCode::SolanaError, format!("Attestation task failed: {e}")))??;
+    Ok(Json(attestation))
+}
+
+/// Send `record` as game `id`'s memo and store the attestation, clearing the
+/// game's attesting mark either way.
+async fn send_attestation(
+    state: Arc<AppState>,
+    id: String,
+    record: String,
+) -> Result<Attestation, ApiError> {
+    let solana = require_solana(&state)?;
+    let sent = solana.send_memo(&record);
+    let mut games = state.games_mut().await;
+    let game = games
+        .get_mut(&id)
+        .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+    game.attesting = false;
+    let signature = sent.map_err(|e| err(ErrorCode::SolanaError, e))?;
+    log::info!("Attested game {id} ({signature})");
+    let attestation = game.attestation.insert(Attestation {
+        record,
+        signature,
+        cluster: solana.cluster.name().to_string(),
+    });
+    let attestation = attestation.clone();
+    state.game_events.publish(game);
+    Ok(attestation)
+}
+
 fn build_board_data(game: &GameState) -> Vec<Vec<serde_json::Value>> {
     game.board
         .iter()
@@ -761,23 +1471,38 @@ fn build_hand_data(game: &GameState, player: usize) -> Vec<serde_json::Value> {
 }
 
 /// Phase 1: Bot decides which cards to combine
+#[utoipa::path(
+    post,
+    path = "/api/game/{id}/bot-combine",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    responses(
+        (status = 200, description = "Bot combine outcome", body = crate::openapi::BotTurnResponse),
+        (status = 400, description = "Not a bot game or not the bot's turn", body = ApiError),
+        (status = 401, description = "Game belongs to a wallet that isn't signed in", body = ApiError),
+        (status = 404, description = "Game not found", body = ApiError),
+        (status = 502, description = "Generation server unavailable", body = ApiError),
+    )
+)]
 pub async fn bot_combine(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
     let game = {
         let games = state.games.read().await;
         let game = games
             .get(&id)
-            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Game not found"))?;
+            .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+        require_game_owner(&state, &headers, game)?;
         if game.mode != GameMode::Bot {
-            return Err(err(StatusCode::BAD_REQUEST, "Not a bot game"));
+            return Err(err(ErrorCode::NotBotGame, "Not a bot game"));
         }
         if game.current_player != 1 {
-            return Err(err(StatusCode::BAD_REQUEST, "Not bot's turn"));
+            return Err(err(ErrorCode::NotYourTurn, "Not bot's turn"));
         }
         if game.phase == GamePhase::GameOver {
-            return Err(err(StatusCode::BAD_REQUEST, "Game is over"));
+            return Err(err(ErrorCode::GameOver, "Game is over"));
         }
         game.clone()
     };
@@ -794,25 +1519,33 @@ pub async fn bot_combine(
             "bot_score": game.players[1].score,
             "player_score": game.players[0].score,
         }))
-        .send()
+        .send_traced(tracing::info_span!("generation.bot_combine"))
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Bot combine error: {e}")))?;
+        .map_err(|e| {
+            err(
+                ErrorCode::GenerationUnavailable,
+                format!("Bot combine error: {e}"),
+            )
+        })?;
 
     if !resp.status().is_success() {
         // LLM failed — skip turn
-        let mut games = state.games.write().await;
+        let mut games = state.games_mut().await;
         let game = games.get_mut(&id).unwrap();
-        game.advance_turn(&state.base_cards);
+        game.advance_turn(&state.base_cards.read().await);
+        state.game_events.publish(game);
         return Ok(Json(serde_json::json!({
             "result": "bot_failed",
             "game": game.clone(),
         })));
     }
 
-    let bot_result: serde_json::Value = resp
-        .json()
-        .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Parse error: {e}")))?;
+    let bot_result: serde_json::Value = resp.json().await.map_err(|e| {
+        err(
+            ErrorCode::GenerationUnavailable,
+            format!("Parse error: {e}"),
+        )
+    })?;
 
     let combine_indices: Vec<usize> = bot_result["combine"]
         .as_array()
@@ -825,6 +1558,7 @@ pub async fn bot_combine(
     let combine_result = combine(
         State(state.clone()),
         Path(id.clone()),
+        headers,
         Json(CombineRequest {
             card_indices: combine_indices,
             async_image: false,
@@ -836,9 +1570,10 @@ pub async fn bot_combine(
         Ok(result) => Ok(result),
         Err(_) => {
             // Combination failed — skip turn
-            let mut games = state.games.write().await;
+            let mut games = state.games_mut().await;
             let game = games.get_mut(&id).unwrap();
-            game.advance_turn(&state.base_cards);
+            game.advance_turn(&state.base_cards.read().await);
+            state.game_events.publish(game);
             Ok(Json(serde_json::json!({
                 "result": "bot_failed",
                 "game": game.clone(),
@@ -848,23 +1583,38 @@ pub async fn bot_combine(
 }
 
 /// Phase 2: Bot decides where to place a crafted card (or skip)
+#[utoipa::path(
+    post,
+    path = "/api/game/{id}/bot-place",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    responses(
+        (status = 200, description = "Bot placement outcome", body = crate::openapi::BotTurnResponse),
+        (status = 400, description = "Not a bot game or not the bot's turn", body = ApiError),
+        (status = 401, description = "Game belongs to a wallet that isn't signed in", body = ApiError),
+        (status = 404, description = "Game not found", body = ApiError),
+        (status = 502, description = "Generation server unavailable", body = ApiError),
+    )
+)]
 pub async fn bot_place(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
     let game = {
         let games = state.games.read().await;
         let game = games
             .get(&id)
-            .ok_or_else(|| err(StatusCode::NOT_FOUND, "Game not found"))?;
+            .ok_or_else(|| err(ErrorCode::GameNotFound, "Game not found"))?;
+        require_game_owner(&state, &headers, game)?;
         if game.mode != GameMode::Bot {
-            return Err(err(StatusCode::BAD_REQUEST, "Not a bot game"));
+            return Err(err(ErrorCode::NotBotGame, "Not a bot game"));
         }
         if game.current_player != 1 {
-            return Err(err(StatusCode::BAD_REQUEST, "Not bot's turn"));
+            return Err(err(ErrorCode::NotYourTurn, "Not bot's turn"));
         }
         if game.phase == GamePhase::GameOver {
-            return Err(err(StatusCode::BAD_REQUEST, "Game is over"));
+            return Err(err(ErrorCode::GameOver, "Game is over"));
         }
         game.clone()
     };
@@ -873,9 +1623,10 @@ pub async fn bot_place(
     let has_crafted = game.players[1].hand.iter().any(|c| c.kind == "crafted");
     if !has_crafted {
         // Nothing to place — end turn
-        let mut games = state.games.write().await;
+        let mut games = state.games_mut().await;
         let game = games.get_mut(&id).unwrap();
-        game.advance_turn(&state.base_cards);
+        game.advance_turn(&state.base_cards.read().await);
+        state.game_events.publish(game);
         return Ok(Json(serde_json::json!({
             "result": "bot_skipped_place",
             "game": game.clone(),
@@ -894,33 +1645,42 @@ pub async fn bot_place(
             "bot_score": game.players[1].score,
             "player_score": game.players[0].score,
         }))
-        .send()
+        .send_traced(tracing::info_span!("generation.bot_place"))
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Bot place error: {e}")))?;
+        .map_err(|e| {
+            err(
+                ErrorCode::GenerationUnavailable,
+                format!("Bot place error: {e}"),
+            )
+        })?;
 
     if !resp.status().is_success() {
         // LLM failed — end turn
-        let mut games = state.games.write().await;
+        let mut games = state.games_mut().await;
         let game = games.get_mut(&id).unwrap();
-        game.advance_turn(&state.base_cards);
+        game.advance_turn(&state.base_cards.read().await);
+        state.game_events.publish(game);
         return Ok(Json(serde_json::json!({
             "result": "bot_failed",
             "game": game.clone(),
         })));
     }
 
-    let bot_result: serde_json::Value = resp
-        .json()
-        .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Parse error: {e}")))?;
+    let bot_result: serde_json::Value = resp.json().await.map_err(|e| {
+        err(
+            ErrorCode::GenerationUnavailable,
+            format!("Parse error: {e}"),
+        )
+    })?;
 
     let skip = bot_result["skip"].as_bool().unwrap_or(false);
 
     if skip {
         // Bot chose to save its crafted cards — end turn
-        let mut games = state.games.write().await;
+        let mut games = state.games_mut().await;
         let game = games.get_mut(&id).unwrap();
-        game.advance_turn(&state.base_cards);
+        game.advance_turn(&state.base_cards.read().await);
+        state.game_events.publish(game);
         return Ok(Json(serde_json::json!({
             "result": "bot_skipped_place",
             "game": game.clone(),
@@ -935,6 +1695,7 @@ pub async fn bot_place(
     let place_result = place(
         State(state.clone()),
         Path(id.clone()),
+        headers,
         Json(PlaceRequest {
             hand_index,
             row: target_row.min(2),
@@ -946,11 +1707,12 @@ pub async fn bot_place(
     match place_result {
         Ok(mut result) => {
             // End bot's turn after placing
-            let mut games = state.games.write().await;
+            let mut games = state.games_mut().await;
             let game = games.get_mut(&id).unwrap();
             if game.phase != GamePhase::GameOver {
-                game.advance_turn(&state.base_cards);
+                game.advance_turn(&state.base_cards.read().await);
             }
+            state.game_events.publish(game);
             if let Some(obj) = result.0.as_object_mut() {
                 obj.insert(
                     "game".to_string(),
@@ -961,9 +1723,10 @@ pub async fn bot_place(
         }
         Err(_) => {
             // Place failed — end turn (bot keeps the card)
-            let mut games = state.games.write().await;
+            let mut games = state.games_mut().await;
             let game = games.get_mut(&id).unwrap();
-            game.advance_turn(&state.base_cards);
+            game.advance_turn(&state.base_cards.read().await);
+            state.game_events.publish(game);
             Ok(Json(serde_json::json!({
                 "result": "bot_skipped_place",
                 "game": game.clone(),
@@ -0,0 +1,158 @@
+//! Live game updates. Every change to a game is sent, as the whole game, to
+//! the `/api/game/{id}/ws` sockets watching it, so clients don't have to poll
+//! `GET /api/game/{id}`. With a `GameFeed` (Redis), servers pass their
+//! updates to each other, so a socket on any server sees every move.
+
+use crate::error::{err, ApiError, ErrorCode};
+use crate::game_state::GameState;
+use crate::generate::AppState;
+use crate::storage::GameFeed;
+use axum::extract::ws::{Message, Utf8Bytes, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::response::Response;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
+
+/// A game as it stood after a change.
+#[derive(Clone)]
+pub struct GameUpdate {
+    game_id: String,
+    json: Utf8Bytes,
+    /// Came in over the feed, so isn't passed back out.
+    remote: bool,
+}
+
+/// Fan-out of game updates to the sockets on this server.
+pub struct GameEvents {
+    events: broadcast::Sender<GameUpdate>,
+    closing: watch::Sender<bool>,
+    /// Updates to games on other servers arrive here too.
+    shared: bool,
+}
+
+impl GameEvents {
+    pub fn new(shared: bool) -> Self {
+        GameEvents {
+            events: broadcast::channel(256).0,
+            closing: watch::channel(false).0,
+            shared,
+        }
+    }
+
+    /// Tell the game's sockets about its new state. Call with the games lock
+    /// still held, so updates go out in the order they were made.
+    pub fn publish(&self, game: &GameState) {
+        if self.events.receiver_count() == 0 {
+            return;
+        }
+        match serde_json::to_string(game) {
+            Ok(json) => self.send(game.id.clone(), json, false),
+            Err(e) => log::warn!("Failed to serialize game {}: {e}", game.id),
+        }
+    }
+
+    /// Pass on an update another server published to the feed.
+    pub fn forward(&self, game_id: String, json: String) {
+        self.send(game_id, json, true);
+    }
+
+    fn send(&self, game_id: String, json: String, remote: bool) {
+        // No receivers just means nobody is watching
+        let _ = self.events.send(GameUpdate {
+            game_id,
+            json: json.into(),
+            remote,
+        });
+    }
+
+    /// Close every open socket, so graceful shutdown doesn't wait on them.
+    pub fn close(&self) {
+        self.closing.send_replace(true);
+    }
+}
+
+/// Publish this server's updates to `feed`, in order. Runs for the life of
+/// the server.
+pub async fn relay(state: Arc<AppState>, feed: Arc<dyn GameFeed>) {
+    let mut events = state.game_events.events.subscribe();
+    loop {
+        match events.recv().await {
+            Ok(update) if !update.remote => feed.publish_game(&update.game_id, &update.json),
+            Ok(_) => {}
+            Err(RecvError::Lagged(n)) => log::warn!("Game feed missed {n} updates"),
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+// --- GET /api/game/{id}/ws ---
+
+#[utoipa::path(
+    get,
+    path = "/api/game/{id}/ws",
+    tag = "game",
+    params(("id" = String, Path, description = "Game id")),
+    responses(
+        (status = 101, description = "WebSocket carrying the game, as `GET /api/game/{id}` returns \
+                                      it, now and after every change"),
+        (status = 404, description = "Game not found", body = ApiError),
+    )
+)]
+pub async fn game_socket(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    // Subscribed before reading the game, so no change slips in between
+    let events = state.game_events.events.subscribe();
+    let closing = state.game_events.closing.subscribe();
+    let current = state
+        .games
+        .read()
+        .await
+        .get(&id)
+        .and_then(|game| serde_json::to_string(game).ok());
+    // Another server may hold it; its next change arrives over the feed
+    if current.is_none() && !state.game_events.shared {
+        return Err(err(ErrorCode::GameNotFound, "Game not found"));
+    }
+    Ok(ws.on_upgrade(move |socket| watch_game(socket, id, current, events, closing)))
+}
+
+async fn watch_game(
+    mut socket: WebSocket,
+    id: String,
+    current: Option<String>,
+    mut events: broadcast::Receiver<GameUpdate>,
+    mut closing: watch::Receiver<bool>,
+) {
+    if let Some(json) = current {
+        if socket.send(Message::Text(json.into())).await.is_err() {
+            return;
+        }
+    }
+    loop {
+        let received = tokio::select! {
+            received = events.recv() => received,
+            // Clients only listen; anything but a close is ignored
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            _ = closing.wait_for(|closed| *closed) => break,
+        };
+        match received {
+            Ok(update) if update.game_id == id => {
+                if socket.send(Message::Text(update.json)).await.is_err() {
+                    return;
+                }
+            }
+            Ok(_) => {}
+            // Each update is the whole game, so the next one catches the socket up
+            Err(RecvError::Lagged(n)) => log::warn!("Socket for game {id} missed {n} updates"),
+            Err(RecvError::Closed) => return,
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
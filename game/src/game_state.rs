@@ -1,9 +1,14 @@
 use crate::card_cache;
+use crate::migrate;
 use rand::Rng;
 use rand::seq::{IndexedRandom, SliceRandom};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BaseCard {
     pub name: String,
     pub description: String,
@@ -13,7 +18,7 @@ pub struct BaseCard {
 }
 
 /// A card in a player's hand — can be a base card or a crafted card.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct HandCard {
     pub name: String,
     pub description: String,
@@ -24,7 +29,7 @@ pub struct HandCard {
     pub nft_mint: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CraftedCard {
     pub name: String,
     pub description: String,
@@ -32,41 +37,55 @@ pub struct CraftedCard {
     pub id: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BoardCell {
     pub category: String,
     pub card: Option<PlacedCard>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PlacedCard {
     pub card: CraftedCard,
     pub owner: usize, // 0 or 1
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PlayerState {
     pub hand: Vec<HandCard>,
     pub score: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub wallet: Option<String>,
+    /// Ids of every card this player crafted, kept after the card leaves
+    /// the hand. A wallet may claim these as NFTs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crafted: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum GamePhase {
     Playing,
     GameOver,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum GameMode {
     Pvp,
     Bot,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A finished game's result as written on-chain by `/api/game/{id}/attest`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Attestation {
+    /// Memo text: `alchemaybe:result:v1:<game id>:<player 0>:<player 1>:<winner>:<hash>`.
+    pub record: String,
+    /// Transaction carrying the memo.
+    pub signature: String,
+    pub cluster: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct GameState {
     pub id: String,
     pub mode: GameMode,
@@ -76,6 +95,12 @@ pub struct GameState {
     pub players: [PlayerState; 2],
     pub winner: Option<usize>,
     pub has_placed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>,
+    /// Set while `/api/game/{id}/attest` is writing the result, so only one
+    /// request writes it.
+    #[serde(skip)]
+    pub attesting: bool,
 }
 
 const HAND_SIZE: usize = 7;
@@ -139,16 +164,55 @@ impl GameState {
                     hand: hand0,
                     score: 0,
                     wallet: None,
+                    crafted: Vec::new(),
                 },
                 PlayerState {
                     hand: hand1,
                     score: 0,
                     wallet: None,
+                    crafted: Vec::new(),
                 },
             ],
             winner: None,
             has_placed: false,
+            attestation: None,
+            attesting: false,
+        }
+    }
+
+    /// The memo recording this game's result. Players are wallets, or `bot` /
+    /// `guest`; the winner is `0`, `1`, or `none`. The hash is SHA-256 (hex)
+    /// over the game id, the scores, and each board cell as
+    /// `category=card id@owner`, one per line, so anyone holding the final
+    /// game state can check it.
+    pub fn result_record(&self) -> String {
+        let player = |i: usize| match (&self.players[i].wallet, &self.mode) {
+            (Some(wallet), _) => wallet.as_str(),
+            (None, GameMode::Bot) if i == 1 => "bot",
+            (None, _) => "guest",
+        };
+        let winner = self.winner.map_or("none".to_string(), |w| w.to_string());
+
+        let mut digest = Sha256::new();
+        digest.update(format!(
+            "{}\n{} {}\n",
+            self.id, self.players[0].score, self.players[1].score
+        ));
+        for cell in self.board.iter().flatten() {
+            let card = cell
+                .card
+                .as_ref()
+                .map_or(String::new(), |p| format!("{}@{}", p.card.id, p.owner));
+            digest.update(format!("{}={card}\n", cell.category));
         }
+
+        format!(
+            "alchemaybe:result:v1:{}:{}:{}:{winner}:{:x}",
+            self.id,
+            player(0),
+            player(1),
+            digest.finalize()
+        )
     }
 
     /// Draw random base cards until hand has HAND_SIZE cards.
@@ -195,6 +259,35 @@ fn draw_random_card<'a>(base_cards: &'a [BaseCard], rng: &mut rand::rngs::Thread
     }
 }
 
+/// Read and validate cards.json. Games need at least one material to draw from.
+pub fn load_base_cards(path: &Path) -> Result<Vec<BaseCard>, String> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let json: serde_json::Value = serde_json::from_str(&data)
+        .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+    let base_cards = build_base_cards(&json);
+    if !base_cards.iter().any(|c| c.kind == "material") {
+        return Err(format!("{} defines no materials", path.display()));
+    }
+    Ok(base_cards)
+}
+
+/// Read and validate categories.json. Each board needs 9 distinct categories.
+pub fn load_categories(path: &Path) -> Result<Vec<String>, String> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let categories: Vec<String> = serde_json::from_str(&data)
+        .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+    if categories.len() < 9 {
+        return Err(format!(
+            "{} needs at least 9 categories, found {}",
+            path.display(),
+            categories.len()
+        ));
+    }
+    Ok(categories)
+}
+
 pub fn build_base_cards(cards_json: &serde_json::Value) -> Vec<BaseCard> {
     let mut base_cards = Vec::new();
 
@@ -226,3 +319,78 @@ pub fn build_base_cards(cards_json: &serde_json::Value) -> Vec<BaseCard> {
 
     base_cards
 }
+
+/// Load games persisted by a previous run, upgrading older file layouts.
+/// A missing file yields no games. Anything unreadable is logged and the
+/// file is copied aside first, since the next save would overwrite it.
+pub fn load_games(path: &Path) -> HashMap<String, GameState> {
+    let Ok(data) = std::fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+    let file = serde_json::from_str(&data)
+        .map_err(|e| e.to_string())
+        .and_then(migrate::games)
+        .and_then(|v| {
+            serde_json::from_value::<GamesFile<HashMap<String, serde_json::Value>>>(v)
+                .map_err(|e| e.to_string())
+        });
+    let file = match file {
+        Ok(file) => file,
+        Err(e) => {
+            log::error!("Unreadable games file {}: {e}", path.display());
+            set_aside(path);
+            return HashMap::new();
+        }
+    };
+
+    let mut games = HashMap::new();
+    let mut unreadable = 0;
+    for (id, game) in file.games {
+        match serde_json::from_value(game) {
+            Ok(game) => {
+                games.insert(id, game);
+            }
+            Err(e) => {
+                log::error!("Skipping unreadable saved game {id}: {e}");
+                unreadable += 1;
+            }
+        }
+    }
+    if unreadable > 0 {
+        set_aside(path);
+    }
+    games
+}
+
+/// On-disk layout of the games file (see `migrate::GAMES_VERSION`). Loading
+/// keeps the games raw so one bad game doesn't sink the rest.
+#[derive(Serialize, Deserialize)]
+struct GamesFile<G> {
+    schema_version: u32,
+    games: G,
+}
+
+/// Copy `path` to `<path>.unreadable-<unix secs>` so it can be recovered by hand.
+fn set_aside(path: &Path) {
+    let mut copy = path.as_os_str().to_owned();
+    copy.push(format!(".unreadable-{}", card_cache::now_secs()));
+    match std::fs::copy(path, &copy) {
+        Ok(_) => log::warn!("Kept a copy of {} at {}", path.display(), copy.to_string_lossy()),
+        Err(e) => log::error!("Failed to copy {} aside: {e}", path.display()),
+    }
+}
+
+/// Write all games to disk, via a temp file so a crash mid-write can't truncate it.
+pub fn save_games(path: &Path, games: &HashMap<String, GameState>) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("create dir error: {e}"))?;
+    }
+    let file = GamesFile {
+        schema_version: migrate::GAMES_VERSION,
+        games,
+    };
+    let data = serde_json::to_string(&file).map_err(|e| format!("serialize error: {e}"))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, data).map_err(|e| format!("write error: {e}"))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("rename error: {e}"))
+}
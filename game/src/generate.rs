@@ -1,44 +1,210 @@
-use crate::card::{self, CardKind};
+use crate::airdrop::Airdrops;
+use crate::assets::AssetEvents;
+use crate::auth::AuthConfig;
+use crate::card::{self, CardKind, CardOptions, Stamp};
+use crate::card_animation;
+use crate::card_svg;
 use crate::card_cache::CardCache;
+use crate::card_template::{CardTemplates, DEFAULT_TEMPLATE};
+use crate::config::{
+    ArtUploadSettings, ClaimSettings, IndexerSettings, PathSettings, RewardSettings, SupplySettings,
+};
+use crate::error::{err, ApiError, ErrorCode};
+use crate::game_events::GameEvents;
 use crate::game_state::{BaseCard, GameState};
+use crate::packs::{Pack, Rarity};
+use crate::persist::GamesPersister;
+use crate::storage::{AssetStore, ClaimStore, GameStore, OwnershipStore, TradeStore};
+use crate::rate_limit::RateLimiter;
+use crate::render_queue::RenderQueue;
 use crate::solana::SolanaConfig;
-use axum::extract::State;
-use axum::http::{header, StatusCode};
+use crate::telemetry::SendTraced;
+use crate::wallet_auth::WalletSessions;
+use axum::extract::{Query, State};
+use axum::http::header;
 use axum::response::IntoResponse;
 use axum::Json;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{RwLock, RwLockWriteGuard};
+use utoipa::{IntoParams, ToSchema};
 
 pub struct AppState {
     pub generation_url: String,
     pub client: reqwest::Client,
+    pub paths: PathSettings,
+    /// Mutate through `games_mut` so changes get persisted.
     pub games: RwLock<HashMap<String, GameState>>,
+    pub games_persister: GamesPersister,
+    pub game_events: GameEvents,
+    pub game_store: Arc<dyn GameStore>,
+    pub claims: Arc<dyn ClaimStore>,
+    pub claim_limits: ClaimSettings,
+    pub assets: Arc<dyn AssetStore>,
+    pub asset_events: AssetEvents,
+    pub rewards: RewardSettings,
+    pub supply: SupplySettings,
+    pub trades: Arc<dyn TradeStore>,
+    pub ownership: Arc<dyn OwnershipStore>,
+    pub indexer: IndexerSettings,
     pub card_cache: RwLock<CardCache>,
-    pub base_cards: Vec<BaseCard>,
-    pub categories: Vec<String>,
+    /// Reloadable via POST /api/admin/reload-content.
+    pub base_cards: RwLock<Vec<BaseCard>>,
+    pub categories: RwLock<Vec<String>>,
+    pub packs: RwLock<Vec<Pack>>,
+    pub card_templates: RwLock<CardTemplates>,
+    pub airdrops: Airdrops,
     pub solana: Option<Arc<SolanaConfig>>,
+    pub auth: Option<AuthConfig>,
+    pub admin_auth: Option<AuthConfig>,
+    pub wallet_sessions: WalletSessions,
+    pub rate_limiter: RateLimiter,
+    pub render: RenderQueue,
+    pub art_upload: ArtUploadSettings,
+    /// Set once a shutdown signal arrives; new games are refused from then on.
+    pub shutting_down: AtomicBool,
 }
 
-#[derive(Deserialize)]
+impl AppState {
+    /// Write access to the games, marking them for the next background save.
+    pub async fn games_mut(&self) -> RwLockWriteGuard<'_, HashMap<String, GameState>> {
+        let games = self.games.write().await;
+        // Marked while the lock is held, so the flush that picks this up
+        // can't snapshot the games before this change lands
+        self.games_persister.mark_dirty();
+        games
+    }
+}
+
+/// How often to ask the generation server for its combine version.
+const COMBINE_VERSION_POLL: Duration = Duration::from_secs(300);
+
+/// Keep the card cache's idea of the generation server's combine version
+/// current, so "not possible" verdicts from an older model or prompt get
+/// re-judged even before a cache miss reports the new version. Runs for the
+/// life of the server.
+pub async fn track_combine_version(state: Arc<AppState>) {
+    let mut ticker = tokio::time::interval(COMBINE_VERSION_POLL);
+    loop {
+        ticker.tick().await;
+        match fetch_combine_version(&state).await {
+            Ok(version) => state.card_cache.read().await.set_combine_version(&version),
+            Err(e) => log::debug!("Could not fetch combine version: {e}"),
+        }
+    }
+}
+
+pub async fn fetch_combine_version(state: &AppState) -> Result<String, String> {
+    let body: serde_json::Value = state
+        .client
+        .get(format!("{}/version", state.generation_url))
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    body["combine_version"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "no combine_version in response".to_string())
+}
+
+#[derive(Deserialize, ToSchema)]
 pub struct CardRequest {
     pub name: String,
     pub description: String,
     #[serde(default)]
     pub kind: CardKind,
+    /// Sets the gem on the frame.
+    #[serde(default)]
+    pub rarity: Rarity,
+    /// Render with a holographic foil finish.
+    #[serde(default)]
+    pub foil: bool,
+    /// How well the card fits each category, 1-10, as the scorer rates it.
+    /// The best three are printed along the bottom of the card.
+    #[serde(default)]
+    pub category_scores: HashMap<String, u32>,
+    /// Card template to lay the card out with, from card-templates.json.
+    #[serde(default = "default_template")]
+    pub template: String,
+    #[serde(default)]
+    pub format: CardFormat,
+    /// Link to print as a QR code in the corner, such as the card's minted
+    /// asset on an explorer. Only drawn on PNGs.
+    #[serde(default)]
+    pub qr_url: Option<String>,
+    /// Set symbol and edition to stamp on, as on a minted card. Only drawn
+    /// on PNGs.
+    #[serde(default)]
+    pub stamp: Option<Stamp>,
+    /// Language to print the card in, as a tag such as `ja` or `pt-BR`: the
+    /// fonts, name wrapping and stamp wording of that locale in
+    /// card-templates.json. A locale it doesn't list prints as English.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// The card's text in `locale`, printed in place of `name` and the
+    /// category names. The art is still drawn from `name` and `description`,
+    /// so every language's copy of a card shares it.
+    #[serde(default)]
+    pub translation: Option<CardTranslation>,
 }
 
-#[derive(Serialize)]
-pub struct CardError {
-    reason: String,
+#[derive(Deserialize, ToSchema)]
+pub struct CardTranslation {
+    pub name: String,
+    /// Printed names of the categories in `category_scores`, by category.
+    #[serde(default)]
+    pub categories: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CardFormat {
+    #[default]
+    Png,
+    /// The card as an SVG document with its font embedded, for print. Foil
+    /// isn't drawn.
+    Svg,
+    /// The PNG looping with a glowing edge and drifting motes of light.
+    Apng,
+}
+
+fn default_template() -> String {
+    DEFAULT_TEMPLATE.to_string()
 }
 
+#[utoipa::path(
+    post,
+    path = "/generate-card",
+    tag = "cards",
+    request_body = CardRequest,
+    responses(
+        (status = 200, description = "Rendered card (image/svg+xml when `format` is svg, image/apng when apng)", content_type = "image/png"),
+        (status = 400, description = "Unknown card template", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 500, description = "Card rendering failed", body = ApiError),
+        (status = 502, description = "Generation server unavailable", body = ApiError),
+    )
+)]
 pub async fn generate_card(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CardRequest>,
-) -> Result<impl IntoResponse, (StatusCode, Json<CardError>)> {
+) -> Result<impl IntoResponse, ApiError> {
     log::info!("Generating card '{}'", req.name);
+    let (template, locale) = {
+        let templates = state.card_templates.read().await;
+        let template = templates.get(&req.template).ok_or_else(|| {
+            err(ErrorCode::InvalidRequest, format!("Unknown card template '{}'", req.template))
+        })?;
+        (template, req.locale.as_deref().and_then(|tag| templates.locale(tag)))
+    };
 
     // Call generation server for art
     let gen_url = format!("{}/generate-image", state.generation_url);
@@ -48,50 +214,161 @@ pub async fn generate_card(
         .json(&serde_json::json!({
             "name": req.name,
             "description": req.description,
+            // Crafted art is drawn like materials; only the frame differs
             "kind": if req.kind == CardKind::Intent { "intent" } else { "material" },
         }))
-        .send()
+        .send_traced(tracing::info_span!("generation.generate_image"))
         .await
         .map_err(|e| {
             log::error!("Generation server request failed: {e}");
-            (
-                StatusCode::BAD_GATEWAY,
-                Json(CardError {
-                    reason: format!("generation server error: {e}"),
-                }),
+            err(
+                ErrorCode::GenerationUnavailable,
+                format!("generation server error: {e}"),
             )
         })?
         .error_for_status()
         .map_err(|e| {
             log::error!("Generation server returned error: {e}");
-            (
-                StatusCode::BAD_GATEWAY,
-                Json(CardError {
-                    reason: format!("generation server error: {e}"),
-                }),
+            err(
+                ErrorCode::GenerationUnavailable,
+                format!("generation server error: {e}"),
             )
         })?
         .bytes()
         .await
         .map_err(|e| {
             log::error!("Failed to read generation response: {e}");
-            (
-                StatusCode::BAD_GATEWAY,
-                Json(CardError {
-                    reason: format!("generation server error: {e}"),
-                }),
+            err(
+                ErrorCode::GenerationUnavailable,
+                format!("generation server error: {e}"),
             )
         })?;
 
     // Render the card
-    let png = card::render_card(&req.name, &art_bytes, &req.kind).map_err(|e| {
-        log::error!("Card rendering failed: {e}");
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(CardError { reason: e }),
-        )
-    })?;
-
-    log::info!("Card '{}' rendered ({} bytes)", req.name, png.len());
-    Ok(([(header::CONTENT_TYPE, "image/png")], png))
+    let name = req.name.clone();
+    let (content_type, body) = state
+        .render
+        .run(move || {
+            let translation = req.translation.as_ref();
+            let printed_name = translation.map_or(&req.name, |translation| &translation.name);
+            let options = CardOptions {
+                foil: req.foil,
+                fit: Some(&req.category_scores),
+                qr_url: req.qr_url.as_deref(),
+                stamp: req.stamp.as_ref(),
+                locale: locale.as_deref(),
+                category_names: translation.map(|translation| &translation.categories),
+                ..Default::default()
+            };
+            match req.format {
+                CardFormat::Svg => card_svg::compose(
+                    &template,
+                    printed_name,
+                    &art_bytes,
+                    &req.kind,
+                    req.rarity,
+                    &options,
+                    true,
+                )
+                .map(|svg| ("image/svg+xml", svg.into_bytes())),
+                CardFormat::Png | CardFormat::Apng => {
+                    let card = card::render_card(
+                        &template,
+                        printed_name,
+                        &art_bytes,
+                        &req.kind,
+                        req.rarity,
+                        &options,
+                    )?;
+                    if let CardFormat::Apng = req.format {
+                        let still = image::load_from_memory(&card.png)
+                            .map_err(|e| format!("image decode error: {e}"))?;
+                        card_animation::animate(&still.to_rgba8())
+                            .map(|apng| ("image/apng", apng))
+                    } else {
+                        Ok(("image/png", card.png))
+                    }
+                }
+            }
+        })
+        .await?
+        .map_err(|e| {
+            log::error!("Card rendering failed: {e}");
+            err(ErrorCode::RenderFailed, e)
+        })?;
+
+    log::info!("Card '{name}' rendered as {content_type} ({} bytes)", body.len());
+    Ok(([(header::CONTENT_TYPE, content_type)], body))
+}
+
+/// How long clients may reuse a card back; they only change on a content
+/// reload.
+const CARD_BACK_MAX_AGE: &str = "public, max-age=3600";
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CardBackQuery {
+    /// Season to mark the back with (default: the current season). Backs have
+    /// no season marker when Solana is off.
+    pub season: Option<String>,
+    /// Card template to draw the back with (default "default").
+    #[serde(default = "default_template")]
+    pub template: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/card-back",
+    tag = "cards",
+    params(CardBackQuery),
+    responses(
+        (status = 200, description = "Card back, for face-down cards and NFT metadata", content_type = "image/png"),
+        (status = 400, description = "Unknown card template or season", body = ApiError),
+        (status = 500, description = "Card rendering failed", body = ApiError),
+    )
+)]
+pub async fn card_back(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CardBackQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let season = match (&query.season, &state.solana) {
+        (None, None) => None,
+        (None, Some(solana)) => Some(solana.seasons[0].name.as_str()),
+        (Some(season), solana) => {
+            let known = solana.iter().flat_map(|s| &s.seasons).any(|s| s.name == *season);
+            if !known {
+                return Err(err(ErrorCode::InvalidRequest, format!("Unknown season '{season}'")));
+            }
+            Some(season.as_str())
+        }
+    };
+
+    let (template, cached) = {
+        let templates = state.card_templates.read().await;
+        let template = templates.get(&query.template).ok_or_else(|| {
+            err(ErrorCode::InvalidRequest, format!("Unknown card template '{}'", query.template))
+        })?;
+        (template, templates.cached_back(&query.template, season))
+    };
+    let png = match cached {
+        Some(png) => png,
+        None => {
+            let (drawn_with, drawn_season) = (template.clone(), season.map(str::to_string));
+            let png = state
+                .render
+                .run(move || card::render_card_back(&drawn_with, drawn_season.as_deref()))
+                .await?
+                .map_err(|e| {
+                    log::error!("Card back rendering failed: {e}");
+                    err(ErrorCode::RenderFailed, e)
+                })?;
+            let templates = state.card_templates.read().await;
+            templates.store_back(&query.template, season, &template, png.clone());
+            png
+        }
+    };
+    Ok((
+        [(header::CONTENT_TYPE, "image/png"), (header::CACHE_CONTROL, CARD_BACK_MAX_AGE)],
+        png,
+    ))
 }
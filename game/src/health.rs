@@ -0,0 +1,145 @@
+use crate::generate::AppState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a single dependency check may take before it counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Serialize)]
+pub struct Liveness {
+    status: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct DependencyStatus {
+    /// "ok", "error", or "skipped" (dependency not configured).
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl DependencyStatus {
+    fn from_result(result: Result<(), String>, start: Instant) -> Self {
+        let latency_ms = Some(start.elapsed().as_millis() as u64);
+        match result {
+            Ok(()) => DependencyStatus {
+                status: "ok",
+                latency_ms,
+                error: None,
+            },
+            Err(e) => DependencyStatus {
+                status: "error",
+                latency_ms,
+                error: Some(e),
+            },
+        }
+    }
+
+    fn skipped() -> Self {
+        DependencyStatus {
+            status: "skipped",
+            latency_ms: None,
+            error: None,
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.status != "error"
+    }
+}
+
+#[derive(Serialize)]
+pub struct Checks {
+    generation: DependencyStatus,
+    card_cache: DependencyStatus,
+    solana: DependencyStatus,
+}
+
+#[derive(Serialize)]
+pub struct Readiness {
+    /// "ready", "not_ready", or "shutting_down".
+    status: &'static str,
+    checks: Checks,
+}
+
+/// GET /healthz — the process is up and serving requests. No dependency checks.
+pub async fn healthz() -> Json<Liveness> {
+    Json(Liveness { status: "ok" })
+}
+
+/// GET /readyz — 200 when every configured dependency responds, 503 otherwise.
+pub async fn readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Readiness>) {
+    let (generation, card_cache, solana) = tokio::join!(
+        check_generation(&state),
+        check_card_cache(&state),
+        check_solana(&state),
+    );
+    let checks = Checks {
+        generation,
+        card_cache,
+        solana,
+    };
+
+    let healthy = checks.generation.is_ok() && checks.card_cache.is_ok() && checks.solana.is_ok();
+    let status = if state.shutting_down.load(Ordering::Relaxed) {
+        "shutting_down"
+    } else if healthy {
+        "ready"
+    } else {
+        "not_ready"
+    };
+    let code = if status == "ready" {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(Readiness { status, checks }))
+}
+
+async fn check_generation(state: &AppState) -> DependencyStatus {
+    let start = Instant::now();
+    let result = state
+        .client
+        .get(format!("{}/healthz", state.generation_url))
+        .timeout(CHECK_TIMEOUT)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map(|_| ())
+        .map_err(|e| format!("generation server unreachable: {e}"));
+    DependencyStatus::from_result(result, start)
+}
+
+/// New discoveries write art under the cards directory and a row to the cache
+/// database, so both must accept writes.
+async fn check_card_cache(state: &AppState) -> DependencyStatus {
+    let start = Instant::now();
+    let probe = state.paths.cards_dir.join(".readyz-probe");
+    let result = match std::fs::write(&probe, b"ok").and_then(|_| std::fs::remove_file(&probe)) {
+        Ok(()) => state.card_cache.read().await.ping(),
+        Err(e) => Err(format!("cards directory not writable: {e}")),
+    };
+    DependencyStatus::from_result(result, start)
+}
+
+async fn check_solana(state: &AppState) -> DependencyStatus {
+    let Some(solana) = state.solana.clone() else {
+        return DependencyStatus::skipped();
+    };
+    let start = Instant::now();
+    // RpcClient is blocking, so run it off the async workers
+    let check = tokio::task::spawn_blocking(move || solana.check_health());
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(format!("health check panicked: {e}")),
+        Err(_) => Err("Solana RPC timed out".to_string()),
+    };
+    DependencyStatus::from_result(result, start)
+}
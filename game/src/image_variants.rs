@@ -0,0 +1,174 @@
+//! Lighter renditions of card PNGs for hand and board views: a WebP of the
+//! full card, and board (375×525), thumbnail (250×350) and hand (150×210)
+//! sizes in both formats, stored next to the original as `<stem>.webp`,
+//! `<stem>-board.png`, `<stem>-thumb.webp` and so on. Foil copies (first
+//! discoveries, foil pack pulls) are built on first request, as
+//! `<stem>-foil.png` and so on, and so are animated APNGs (`<stem>.apng`).
+
+use crate::card::{self, CARD_H, CARD_W};
+use crate::card_animation;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+pub const THUMB_W: u32 = CARD_W / 3;
+pub const THUMB_H: u32 = CARD_H / 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Size {
+    #[default]
+    Full,
+    /// Half size, for cards on the board.
+    Board,
+    Thumb,
+    /// One-fifth size, for cards in hand.
+    Hand,
+}
+
+/// Sizes a freshly rendered card is scaled to along with the render itself,
+/// so the views that show it never wait on a resize.
+pub const RENDERED_SIZES: [Size; 2] = [Size::Board, Size::Hand];
+
+const SIZES: [Size; 4] = [Size::Full, Size::Board, Size::Thumb, Size::Hand];
+const FORMATS: [Format; 2] = [Format::Png, Format::Webp];
+
+impl Size {
+    /// Width and height, or `None` for the card as rendered.
+    pub fn dimensions(self) -> Option<(u32, u32)> {
+        match self {
+            Size::Full => None,
+            Size::Board => Some((CARD_W / 2, CARD_H / 2)),
+            Size::Thumb => Some((THUMB_W, THUMB_H)),
+            Size::Hand => Some((CARD_W / 5, CARD_H / 5)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Png,
+    Webp,
+    /// Looping animation over the card; see `card_animation`.
+    Apng,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Webp => "webp",
+            Format::Apng => "apng",
+        }
+    }
+}
+
+/// URL of the foil copy of the card at `image_path`.
+pub fn foil_url(image_path: &str) -> String {
+    if image_path.is_empty() {
+        return String::new();
+    }
+    format!("{image_path}?foil=true")
+}
+
+/// URL of the animated copy of the card at `image_path`, which may already
+/// have a query (a foil URL).
+pub fn animated_url(image_path: &str) -> String {
+    if image_path.is_empty() {
+        return String::new();
+    }
+    let separator = if image_path.contains('?') { '&' } else { '?' };
+    format!("{image_path}{separator}format=apng")
+}
+
+/// Where a variant of `original` (a `.png`) lives.
+pub fn variant_path(original: &Path, size: Size, format: Format, foil: bool) -> PathBuf {
+    let stem = original
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let finish = if foil { "-foil" } else { "" };
+    let suffix = match size {
+        Size::Full => "",
+        Size::Board => "-board",
+        Size::Thumb => "-thumb",
+        Size::Hand => "-hand",
+    };
+    original.with_file_name(format!("{stem}{finish}{suffix}.{}", format.extension()))
+}
+
+/// Every variant besides the original PNG, foils aside.
+pub fn variants() -> impl Iterator<Item = (Size, Format)> {
+    SIZES
+        .into_iter()
+        .flat_map(|size| FORMATS.map(|format| (size, format)))
+        .filter(|&variant| variant != (Size::Full, Format::Png))
+}
+
+/// Write every variant of a freshly rendered card that isn't on disk yet
+/// (the render's own sizes are saved with it). Failures are only logged:
+/// the PNG is already saved and missing variants are rebuilt on request.
+pub fn write_all(original: &Path, png: &[u8]) {
+    let img = match image::load_from_memory_with_format(png, ImageFormat::Png) {
+        Ok(img) => img,
+        Err(e) => {
+            log::warn!("Skipping variants for {}: {e}", original.display());
+            return;
+        }
+    };
+    for (size, format) in variants() {
+        let path = variant_path(original, size, format, false);
+        if path.exists() {
+            continue;
+        }
+        if let Err(e) = write_variant(&img, &path, size, format) {
+            log::warn!("Failed to write {}: {e}", path.display());
+        }
+    }
+}
+
+/// Build one variant from the original on disk if it doesn't exist yet
+/// (base card art, cards rendered before variants were written, and foils).
+pub fn ensure(original: &Path, size: Size, format: Format, foil: bool) -> Result<PathBuf, String> {
+    let path = variant_path(original, size, format, foil);
+    if path.exists() {
+        return Ok(path);
+    }
+    let mut img = image::open(original).map_err(|e| format!("{}: {e}", original.display()))?;
+    if foil {
+        let mut rgba = img.to_rgba8();
+        card::apply_foil(&mut rgba);
+        img = DynamicImage::ImageRgba8(rgba);
+    }
+    write_variant(&img, &path, size, format)?;
+    Ok(path)
+}
+
+fn write_variant(img: &DynamicImage, path: &Path, size: Size, format: Format) -> Result<(), String> {
+    let img = match size.dimensions() {
+        None => img.clone(),
+        Some((w, h)) => img.resize(w, h, FilterType::Lanczos3),
+    };
+    let bytes = match format {
+        Format::Png => encode(&img, ImageFormat::Png)?,
+        Format::Webp => encode(&img, ImageFormat::WebP)?,
+        Format::Apng => card_animation::animate(&img.to_rgba8())?,
+    };
+
+    // Concurrent requests may build the same variant; never expose a partial file
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp, &bytes).map_err(|e| format!("write error: {e}"))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("rename error: {e}"))
+}
+
+fn encode(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    img.write_to(&mut Cursor::new(&mut bytes), format)
+        .map_err(|e| format!("encode error: {e}"))?;
+    Ok(bytes)
+}
@@ -0,0 +1,131 @@
+//! Local index of which wallet holds each of our cards, so ownership checks
+//! in new games and wallet combines are answered from the database instead
+//! of waiting on DAS. Our own mints and burns update it as they happen; a
+//! Helius webhook delivery touching an indexed card (a transfer, say) makes
+//! its holder's index untrusted until DAS is asked again. In the background,
+//! wallets checked recently are reconciled with DAS before their index goes
+//! stale, and anything else falls back to DAS on its next check.
+
+use crate::assets::{AssetStatus, TrackedAsset};
+use crate::card_cache::now_secs;
+use crate::generate::AppState;
+use crate::solana::{OwnedCard, SolanaConfig};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the background reconciler looks for wallets to refresh.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+/// Wallets refreshed per pass, so one pass can't use up the DAS quota.
+const RECONCILE_BATCH: usize = 20;
+
+/// `wallet`'s cards, from the index while DAS confirmed it within
+/// `max_age_secs`, otherwise from DAS (which also refreshes the index).
+pub async fn owned_cards(
+    state: &AppState,
+    solana: &SolanaConfig,
+    wallet: &str,
+) -> Result<Vec<OwnedCard>, String> {
+    if !state.indexer.enabled {
+        return solana.query_owned_cards(wallet).await;
+    }
+    let now = now_secs();
+    let indexed = state.ownership.owned_cards(wallet, now).unwrap_or_else(|e| {
+        log::warn!("Ownership index unreadable for {wallet}: {e}");
+        None
+    });
+    match indexed {
+        Some((reconciled_at, cards))
+            if now.saturating_sub(reconciled_at) < state.indexer.max_age_secs =>
+        {
+            Ok(cards)
+        }
+        Some((_, cards)) => reconcile(state, solana, wallet).await.or_else(|e| {
+            log::warn!("DAS failed for {wallet}, using its stale index: {e}");
+            Ok(cards)
+        }),
+        None => reconcile(state, solana, wallet).await,
+    }
+}
+
+/// Ask DAS for `wallet`'s cards and make them its index.
+async fn reconcile(
+    state: &AppState,
+    solana: &SolanaConfig,
+    wallet: &str,
+) -> Result<Vec<OwnedCard>, String> {
+    let started = now_secs();
+    let cards = solana.fetch_owned_cards(wallet).await?;
+    if let Err(e) = state.ownership.reconcile_wallet(wallet, &cards, started) {
+        log::warn!("Failed to index {wallet}'s cards: {e}");
+    }
+    Ok(cards)
+}
+
+/// Index our own mints and burns once they're on-chain: a confirmed asset is
+/// held by its wallet, a burned one by nobody.
+pub fn record(state: &AppState, solana: &SolanaConfig, assets: &[TrackedAsset]) {
+    if !state.indexer.enabled {
+        return;
+    }
+    let season = &solana.seasons[0];
+    for asset in assets {
+        let card = OwnedCard {
+            mint_address: asset.asset_address.clone(),
+            card_id: asset.card_id.clone(),
+            name: String::new(),
+            image: String::new(),
+            metadata_uri: String::new(),
+            collection: season.collection.to_string(),
+            season: season.name.clone(),
+            edition: None,
+            soulbound: solana.is_soulbound(&asset.card_id),
+        };
+        let result = match asset.status {
+            AssetStatus::Confirmed => state.ownership.index_mint(&asset.wallet, &card, now_secs()),
+            AssetStatus::Burned => state.ownership.index_burn(&asset.wallet, &card, now_secs()),
+            AssetStatus::Pending | AssetStatus::Burning => continue,
+        };
+        if let Err(e) = result {
+            log::warn!("Failed to index {}: {e}", asset.asset_address);
+        }
+    }
+}
+
+/// Stop trusting the index of whoever holds `mint`, which an on-chain
+/// transaction we didn't build just touched.
+pub fn forget_holder(state: &AppState, mint: &str) {
+    if !state.indexer.enabled {
+        return;
+    }
+    if let Err(e) = state.ownership.forget_holder(mint) {
+        log::warn!("Failed to mark the holder of {mint} stale: {e}");
+    }
+}
+
+/// Keep recently checked wallets reconciled with DAS, halfway through
+/// `max_age_secs`, so their checks never have to wait on it.
+pub async fn run(state: Arc<AppState>) {
+    let Some(solana) = state.solana.clone().filter(|_| state.indexer.enabled) else {
+        return;
+    };
+    let settings = state.indexer;
+    let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = now_secs();
+        let before = now.saturating_sub(settings.max_age_secs / 2);
+        let active_since = now.saturating_sub(settings.active_secs);
+        let wallets = match state.ownership.stale_wallets(before, active_since, RECONCILE_BATCH) {
+            Ok(wallets) => wallets,
+            Err(e) => {
+                log::warn!("Failed to list wallets to reconcile: {e}");
+                continue;
+            }
+        };
+        for wallet in wallets {
+            if let Err(e) = reconcile(&state, &solana, &wallet).await {
+                log::warn!("Failed to reconcile {wallet} with DAS: {e}");
+            }
+        }
+    }
+}
@@ -1,102 +1,417 @@
+mod admin;
+mod airdrop;
+mod art_upload;
+mod assets;
+mod auth;
+mod cache_tool;
+mod claims;
 mod card;
+mod card_animation;
 mod card_cache;
+mod card_fonts;
+mod card_svg;
+mod card_template;
+mod config;
+mod cors;
+mod crafted_art;
+mod error;
 mod game_api;
+mod game_events;
 mod game_state;
 mod generate;
+mod health;
+mod image_variants;
+mod indexer;
+mod migrate;
+mod openapi;
+mod packs;
+mod persist;
+#[cfg(feature = "postgres")]
+mod postgres_store;
+mod print;
+mod rate_limit;
+#[cfg(feature = "redis")]
+mod redis_shared;
+mod render_batch;
+mod render_queue;
+mod rerender;
+mod rpc;
+mod search;
 mod solana;
 mod solana_api;
+mod sqlite_store;
+mod static_files;
+mod storage;
+mod telemetry;
+mod tls;
+mod trades;
+mod wallet_auth;
 
+use axum::extract::DefaultBodyLimit;
+use axum::middleware;
 use axum::routing::{get, post};
-use axum::{Json, Router};
+use axum::Router;
+use clap::{Parser, Subcommand};
 use card_cache::CardCache;
 use generate::AppState;
-use game_state::build_base_cards;
-use serde::Serialize;
-use std::collections::HashMap;
+use game_state::{load_base_cards, load_categories};
+use persist::GamesPersister;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tower_http::services::ServeDir;
+use tower_http::trace::TraceLayer;
 
-#[derive(Serialize)]
-struct Status {
-    status: &'static str,
+/// Card cache dumps can be far larger than axum's 2 MB default.
+const IMPORT_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Resolves on Ctrl+C or SIGTERM, after flagging the server as shutting down.
+async fn shutdown_signal(state: Arc<AppState>) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    log::info!("Shutdown signal received; refusing new games and draining in-flight requests");
+    state.shutting_down.store(true, Ordering::Relaxed);
+    state.asset_events.close();
+    state.game_events.close();
+}
+
+/// Write any game changes the background saver hasn't picked up yet. The card
+/// cache writes through.
+async fn flush_state(state: &AppState) {
+    match state.games_persister.flush(state).await {
+        Ok(0) => log::info!("Games already saved"),
+        Ok(n) => log::info!("Saved {n} changed games"),
+        Err(e) => log::error!("Failed to save games: {e}"),
+    }
 }
 
-async fn status() -> Json<Status> {
-    Json(Status { status: "ok" })
+#[derive(Parser)]
+#[command(name = "game", about = "Alchemaybe game server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check the card cache against crafted images on disk
+    CacheTool(cache_tool::Args),
+    /// Render crafted cards again from their kept art with the current templates
+    Rerender(rerender::Args),
+    /// Mint a card to a list of wallets on a running server
+    Airdrop(airdrop::Args),
 }
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    if let Some(command) = Cli::parse().command {
+        let result = match command {
+            Command::CacheTool(args) => cache_tool::run(args),
+            Command::Rerender(args) => rerender::run(args),
+            Command::Airdrop(args) => airdrop::run_cli(args).await,
+        };
+        if let Err(e) = result {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let telemetry = telemetry::init();
 
-    let generation_url =
-        std::env::var("GENERATION_URL").expect("GENERATION_URL env var is required");
+    let config = config::Config::load().unwrap_or_else(|e| panic!("{e}"));
+    let paths = config.paths.clone();
+
+    let generation_url = config.game.generation_url.clone();
     log::info!("Using generation server at {generation_url}");
 
     // Load cards.json
-    let cards_data = std::fs::read_to_string("cards.json").expect("Failed to read cards.json");
-    let cards_json: serde_json::Value =
-        serde_json::from_str(&cards_data).expect("Failed to parse cards.json");
-    let base_cards = build_base_cards(&cards_json);
+    let base_cards = load_base_cards(&paths.cards_json).unwrap_or_else(|e| panic!("{e}"));
     log::info!("Loaded {} base cards", base_cards.len());
 
     // Load categories.json
-    let cats_data =
-        std::fs::read_to_string("categories.json").expect("Failed to read categories.json");
-    let categories: Vec<String> =
-        serde_json::from_str(&cats_data).expect("Failed to parse categories.json");
+    let categories = load_categories(&paths.categories_json).unwrap_or_else(|e| panic!("{e}"));
     log::info!("Loaded {} categories", categories.len());
 
-    // Load card cache
-    let card_cache = CardCache::load(std::path::Path::new("cards/card-cache.json"));
+    // Load packs.json
+    let packs = packs::load_packs(&paths.packs_json).unwrap_or_else(|e| panic!("{e}"));
+    log::info!("Loaded {} packs", packs.len());
+
+    // Load card-templates.json
+    let card_templates = card_template::load_card_templates(&paths.card_templates_json)
+        .unwrap_or_else(|e| panic!("{e}"));
+    log::info!("Loaded {} card templates", card_templates.len());
+
+    // Open storage and the card cache, migrating the old JSON file if one is still around
+    let stores = storage::open(&config).unwrap_or_else(|e| panic!("{e}"));
+    let card_cache = CardCache::new(stores.cards, &config.card_cache);
+    let legacy_cache = paths.cards_dir.join("card-cache.json");
+    if legacy_cache.exists() {
+        match card_cache.import_json(&legacy_cache) {
+            Ok(n) => log::info!("Imported {} into card cache ({n} cards)", legacy_cache.display()),
+            Err(e) => log::error!("Failed to import legacy card cache: {e}"),
+        }
+    }
+
+    // Restore games saved by the last run
+    let games = stores.games.load().unwrap_or_else(|e| panic!("Failed to load games: {e}"));
+    if !games.is_empty() {
+        log::info!("Restored {} saved games", games.len());
+    }
 
     // Load Solana config
-    let solana_config = solana::SolanaConfig::from_env().map(std::sync::Arc::new);
-    if solana_config.is_some() {
+    let solana_config = solana::SolanaConfig::from_config(
+        &config.solana,
+        &config.game.public_base_url,
+        &paths.cards_dir,
+    )
+    .map(std::sync::Arc::new);
+    if let Some(solana) = &solana_config {
+        tokio::task::block_in_place(|| solana.validate())
+            .unwrap_or_else(|e| panic!("Invalid Solana config: {e}"));
         log::info!("Solana integration enabled");
     } else {
-        log::info!("Solana integration not configured (set SOLANA_KEYPAIR_PATH, SOLANA_RPC_URL, HELIUS_API_KEY, COLLECTION_ADDRESS to enable)");
+        log::info!("Solana integration not configured (fill in [solana] or set SOLANA_KEYPAIR_PATH, SOLANA_RPC_URL, HELIUS_API_KEY, COLLECTION_ADDRESS to enable)");
+    }
+
+    // Load API key auth
+    let auth = auth::AuthConfig::from_config(&config.auth);
+    if let Some(auth) = &auth {
+        log::info!("API key auth enabled ({} keys)", auth.key_count());
+    } else {
+        log::info!("API key auth disabled ([auth] api_keys or GAME_API_KEYS to require keys on mutating endpoints)");
+    }
+    let admin_auth = auth::AuthConfig::admin_from_config(&config.auth);
+    if let Some(admin) = &admin_auth {
+        log::info!("Admin endpoints enabled ({} keys)", admin.key_count());
+    } else {
+        log::info!("Admin endpoints disabled ([auth] admin_keys or GAME_ADMIN_KEYS to enable)");
+    }
+
+    // Load CORS policy
+    let cors_config = cors::CorsConfig::from_config(&config.cors);
+    if let Some(cors) = &cors_config {
+        log::info!("CORS enabled for origins: {}", cors.describe_origins());
     }
 
     let state = Arc::new(AppState {
         generation_url,
         client: reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(180))
+            .timeout(std::time::Duration::from_secs(
+                config.game.generation_timeout_secs,
+            ))
             .build()
             .expect("failed to build HTTP client"),
-        games: RwLock::new(HashMap::new()),
+        paths: paths.clone(),
+        games_persister: GamesPersister::new(
+            config.game.games_flush_secs,
+            config.game.games_flush_after,
+            &games,
+        ),
+        game_events: game_events::GameEvents::new(stores.game_feed.is_some()),
+        games: RwLock::new(games),
+        game_store: stores.games,
+        claims: stores.claims,
+        claim_limits: config.claims,
+        assets: stores.assets,
+        asset_events: assets::AssetEvents::new(),
+        rewards: config.rewards,
+        supply: config.supply.clone(),
+        trades: stores.trades,
+        ownership: stores.ownership,
+        indexer: config.indexer,
         card_cache: RwLock::new(card_cache),
-        base_cards,
-        categories,
+        base_cards: RwLock::new(base_cards),
+        categories: RwLock::new(categories),
+        packs: RwLock::new(packs),
+        card_templates: RwLock::new(card_templates),
+        airdrops: airdrop::Airdrops::default(),
         solana: solana_config,
+        auth,
+        admin_auth,
+        wallet_sessions: wallet_auth::WalletSessions::from_config(&config.auth, stores.sessions),
+        rate_limiter: rate_limit::RateLimiter::from_config(
+            &config.rate_limit,
+            stores.buckets,
+        ),
+        render: render_queue::RenderQueue::from_config(&config.render),
+        art_upload: config.art_upload,
+        shutting_down: AtomicBool::new(false),
     });
+    log::info!("Rendering cards on {} workers", state.render.worker_count());
+
+    // Save games in the background so handlers never wait on disk
+    tokio::spawn(GamesPersister::run(state.clone()));
+
+    // Track which model/prompt the generation server judges combines with
+    tokio::spawn(generate::track_combine_version(state.clone()));
+
+    // Send cards in expired trades back out of escrow
+    tokio::spawn(trades::expire_trades(state.clone()));
+
+    // Finish minting pack cards that failed, or refund them
+    tokio::spawn(packs::retry_mints(state.clone()));
 
-    let app = Router::new()
-        .route("/status", get(status))
+    // Give editions held by wallet mints that never landed to the next mint
+    tokio::spawn(assets::release_editions(state.clone()));
+
+    // Keep recently used wallets' ownership index in step with DAS
+    tokio::spawn(indexer::run(state.clone()));
+
+    // Drop in-memory copies of cards that other servers changed
+    if let Some(events) = stores.card_events {
+        let state = state.clone();
+        std::thread::spawn(move || {
+            events.listen(&|ids| state.card_cache.blocking_read().forget(&ids));
+        });
+    }
+
+    // Share game updates with the sockets on other servers
+    if let Some(feed) = stores.game_feed {
+        tokio::spawn(game_events::relay(state.clone(), feed.clone()));
+        let state = state.clone();
+        std::thread::spawn(move || {
+            feed.listen_games(&|game_id, json| state.game_events.forward(game_id, json));
+        });
+    }
+
+    // Expensive endpoints (LLM combines, image generation, Solana) get a per-IP rate limit
+    let limited = Router::new()
         .route("/generate-card", post(generate::generate_card))
+        .route("/api/cards/print", post(print::print_cards))
+        .route("/api/game/{id}/combine", post(game_api::combine))
+        .route("/api/game/{id}/attest", post(game_api::attest))
+        .route(
+            "/api/cards/{id}/art",
+            post(art_upload::upload_art).layer(DefaultBodyLimit::max(config.art_upload.max_bytes)),
+        )
+        // Solana wallet endpoints
+        .route("/api/wallet/nonce", post(wallet_auth::wallet_nonce))
+        .route("/api/wallet/login", post(wallet_auth::wallet_login))
+        .route("/api/wallet/logout", post(wallet_auth::wallet_logout))
+        .route("/api/wallet/cards", post(solana_api::wallet_cards))
+        .route("/api/wallet/claim", post(solana_api::wallet_claim))
+        .route("/api/wallet/combine", post(solana_api::wallet_combine))
+        .route("/api/wallet/transfer", post(solana_api::wallet_transfer))
+        .route("/api/wallet/burn", post(solana_api::wallet_burn))
+        .route("/api/wallet/burn/confirm", post(solana_api::wallet_burn_confirm))
+        .route("/api/wallet/trades", post(trades::wallet_trades))
+        .route("/api/wallet/trade/propose", post(trades::trade_propose))
+        .route("/api/wallet/trade/accept", post(trades::trade_accept))
+        .route("/api/wallet/trade/cancel", post(trades::trade_cancel))
+        .route("/api/wallet/pack/buy", post(solana_api::wallet_pack_buy))
+        .route("/api/wallet/pack/confirm", post(solana_api::wallet_pack_confirm))
+        .route("/api/wallet/pack/{purchase_id}", get(packs::pack_status))
+        .route("/api/wallet/submit-tx", post(solana_api::wallet_submit_tx))
+        .route("/api/wallet/refresh-tx", post(solana_api::wallet_refresh_tx))
+        .route("/api/wallet/events", get(assets::wallet_events))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit_by_ip,
+        ));
+
+    let mut app = Router::new()
+        .route("/status", get(health::healthz))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route("/openapi.json", get(openapi::openapi_json))
         .route("/api/cards", get(game_api::list_cards))
+        .route("/api/cards/search", get(game_api::search_cards))
+        .route("/api/cards/{id}", get(game_api::get_card))
+        .route("/api/card-back", get(generate::card_back))
+        .route("/api/packs", get(packs::list_packs))
+        .route("/api/packs/verify/{purchase_id}", get(packs::verify_pack))
         .route("/api/game/new", post(game_api::new_game))
         .route("/api/game/{id}", get(game_api::get_game))
-        .route("/api/game/{id}/combine", post(game_api::combine))
+        .route("/api/game/{id}/ws", get(game_events::game_socket))
         .route("/api/game/{id}/finalize-combine", post(game_api::finalize_combine))
         .route("/api/game/{id}/place", post(game_api::place))
         .route("/api/game/{id}/discard", post(game_api::discard))
         .route("/api/game/{id}/end-turn", post(game_api::end_turn))
         .route("/api/game/{id}/bot-combine", post(game_api::bot_combine))
         .route("/api/game/{id}/bot-place", post(game_api::bot_place))
-        // Solana wallet endpoints
-        .route("/api/wallet/cards", post(solana_api::wallet_cards))
-        .route("/api/wallet/claim", post(solana_api::wallet_claim))
-        .route("/api/wallet/combine", post(solana_api::wallet_combine))
-        .route("/api/wallet/pack/buy", post(solana_api::wallet_pack_buy))
-        .route("/api/wallet/pack/confirm", post(solana_api::wallet_pack_confirm))
-        .route("/api/wallet/submit-tx", post(solana_api::wallet_submit_tx))
-        .nest_service("/cards", ServeDir::new("cards"))
-        .fallback_service(ServeDir::new("game/static"))
-        .with_state(state);
+        .route("/api/admin/reload-content", post(admin::reload_content))
+        .route("/api/admin/card-cache", get(admin::card_cache_stats))
+        .route("/api/admin/card-cache/export", get(admin::export_card_cache))
+        .route("/api/admin/card-cache/invalidate", post(admin::invalidate_card_cache))
+        .route("/api/admin/claim-vouchers", post(admin::issue_claim_vouchers))
+        .route("/api/admin/airdrop", post(airdrop::start_airdrop))
+        .route("/api/admin/airdrop/{id}", get(airdrop::airdrop_status))
+        .route("/api/admin/render-batch", post(render_batch::render_batch))
+        .route(
+            "/api/admin/card-cache/import",
+            post(admin::import_card_cache).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .merge(limited)
+        .nest_service("/cards", static_files::cards(state.clone()))
+        .fallback_service(static_files::frontend(&paths.static_dir))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_api_key,
+        ))
+        // Helius can't send our API key; the handler checks its own header
+        .route("/api/webhooks/helius", post(assets::helius_webhook))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::request_span))
+        .with_state(state.clone());
+
+    // CORS wraps everything (including auth rejections) so browsers can read error responses
+    if let Some(cors) = &cors_config {
+        app = app.layer(cors.layer());
+    }
+
+    let listen_addr = &config.game.listen_addr;
+    let make_service = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
+    if let (Some(cert), Some(key)) = (&config.game.tls_cert, &config.game.tls_key) {
+        let tls_config = tls::load(cert, key).await.unwrap_or_else(|e| panic!("{e}"));
+        let listener = std::net::TcpListener::bind(listen_addr)
+            .and_then(|l| l.set_nonblocking(true).map(|_| l))
+            .unwrap_or_else(|e| panic!("Failed to bind {listen_addr}: {e}"));
+        log::info!("Game server listening on {} (HTTPS)", listener.local_addr().unwrap());
+
+        let handle = axum_server::Handle::new();
+        tokio::spawn({
+            let handle = handle.clone();
+            let state = state.clone();
+            async move {
+                shutdown_signal(state).await;
+                handle.graceful_shutdown(None);
+            }
+        });
+        axum_server::from_tcp_rustls(listener, tls_config)
+            .handle(handle)
+            .serve(make_service)
+            .await
+            .unwrap();
+    } else {
+        let listener = tokio::net::TcpListener::bind(listen_addr)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to bind {listen_addr}: {e}"));
+        log::info!("Game server listening on {}", listener.local_addr().unwrap());
+        axum::serve(listener, make_service)
+            .with_graceful_shutdown(shutdown_signal(state.clone()))
+            .await
+            .unwrap();
+    }
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3001").await.unwrap();
-    log::info!("Game server listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    flush_state(&state).await;
+    telemetry.shutdown();
+    log::info!("Game server stopped");
 }
@@ -0,0 +1,144 @@
+//! Versioned JSON files. Files the server writes (saved games, card cache
+//! dumps) carry a `schema_version`; on load, older layouts are upgraded one
+//! step at a time before deserializing. Files from before versioning count
+//! as version 0.
+
+use serde_json::{json, Map, Value};
+
+/// Current `games.json` layout: `{"schema_version": 1, "games": {id: game}}`.
+pub const GAMES_VERSION: u32 = 1;
+/// Current card cache dump layout: `{"schema_version": 1, "entries": {...},
+/// "mint_counts": {...}}`.
+pub const CACHE_DUMP_VERSION: u32 = 1;
+
+/// Upgrades from version `i` to `i + 1`, indexed by `i`.
+type Step = fn(Value) -> Result<Value, String>;
+
+const GAMES_STEPS: &[Step] = &[games_v0_to_v1];
+const CACHE_DUMP_STEPS: &[Step] = &[cache_dump_v0_to_v1];
+
+/// Bring a parsed `games.json` up to `GAMES_VERSION`.
+pub fn games(value: Value) -> Result<Value, String> {
+    upgrade(value, GAMES_STEPS, "games file")
+}
+
+/// Bring a parsed card cache dump up to `CACHE_DUMP_VERSION`.
+pub fn cache_dump(value: Value) -> Result<Value, String> {
+    upgrade(value, CACHE_DUMP_STEPS, "card cache dump")
+}
+
+/// The file's `schema_version`, or 0 when it predates versioning.
+fn version_of(value: &Value) -> u64 {
+    value
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0)
+}
+
+fn upgrade(mut value: Value, steps: &[Step], what: &str) -> Result<Value, String> {
+    let version = version_of(&value);
+    if version > steps.len() as u64 {
+        return Err(format!(
+            "{what} has schema_version {version}, newer than this server understands ({})",
+            steps.len()
+        ));
+    }
+    for (i, step) in steps.iter().enumerate().skip(version as usize) {
+        value = step(value).map_err(|e| format!("upgrading {what} to version {}: {e}", i + 1))?;
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("schema_version".into(), json!(i + 1));
+        }
+    }
+    Ok(value)
+}
+
+/// v0 was the bare `{id: game}` map.
+fn games_v0_to_v1(value: Value) -> Result<Value, String> {
+    if !value.is_object() {
+        return Err("expected an object of games".into());
+    }
+    Ok(json!({ "games": value }))
+}
+
+/// v0 entries (the old `card-cache.json`) predate creation times and craft
+/// counts. Use the discovery time as the creation time, and count every
+/// possible card as crafted once, since that is how it got into the cache.
+fn cache_dump_v0_to_v1(mut value: Value) -> Result<Value, String> {
+    let entries = value
+        .get_mut("entries")
+        .and_then(Value::as_object_mut)
+        .ok_or("missing \"entries\" object")?;
+    for entry in entries.values_mut().filter_map(Value::as_object_mut) {
+        fill_provenance(entry);
+    }
+    Ok(value)
+}
+
+fn fill_provenance(entry: &mut Map<String, Value>) {
+    if !entry.contains_key("created_at") {
+        if let Some(discovered_at) = entry.get("discovered_at").cloned() {
+            entry.insert("created_at".into(), discovered_at);
+        }
+    }
+    let impossible = entry
+        .get("impossible")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    if !entry.contains_key("times_crafted") && !impossible {
+        entry.insert("times_crafted".into(), json!(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unversioned_games_are_wrapped() {
+        let old = json!({ "g1": { "turn": 3 } });
+        let upgraded = games(old).unwrap();
+        assert_eq!(
+            upgraded,
+            json!({ "schema_version": 1, "games": { "g1": { "turn": 3 } } })
+        );
+    }
+
+    #[test]
+    fn current_files_load_unchanged() {
+        let current = json!({ "schema_version": 1, "games": { "g1": { "turn": 3 } } });
+        assert_eq!(games(current.clone()).unwrap(), current);
+    }
+
+    #[test]
+    fn unversioned_cache_dumps_get_provenance() {
+        let old = json!({
+            "entries": {
+                "c1": { "discovered_at": 5 },
+                "c2": { "impossible": true },
+                "c3": { "discovered_at": 5, "created_at": 2, "times_crafted": 4 },
+            },
+            "mint_counts": { "c1": 1 },
+        });
+        let upgraded = cache_dump(old).unwrap();
+        assert_eq!(upgraded["schema_version"], 1);
+        let entries = &upgraded["entries"];
+        assert_eq!(
+            entries["c1"],
+            json!({ "discovered_at": 5, "created_at": 5, "times_crafted": 1 })
+        );
+        assert_eq!(entries["c2"], json!({ "impossible": true }));
+        assert_eq!(
+            entries["c3"],
+            json!({ "discovered_at": 5, "created_at": 2, "times_crafted": 4 })
+        );
+        assert_eq!(upgraded["mint_counts"], json!({ "c1": 1 }));
+    }
+
+    #[test]
+    fn files_it_cant_read_are_refused() {
+        let newer = json!({ "schema_version": 2, "games": {} });
+        assert!(games(newer).unwrap_err().contains("newer"));
+        // Rather than upgraded into an empty cache
+        assert!(cache_dump(json!({ "entries": [] })).is_err());
+    }
+}
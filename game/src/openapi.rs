@@ -0,0 +1,378 @@
+//! OpenAPI document for the game server, served at `/openapi.json`.
+//!
+//! Most handlers build their responses with `serde_json::json!`, so the
+//! response shapes below are documentation-only mirrors of those bodies.
+//! Keep them in sync when a handler's response changes.
+#![allow(dead_code)]
+
+use crate::error::{ApiError, ErrorCode};
+use crate::game_api::CardSummary;
+use crate::game_state::GameState;
+use crate::packs::{Pack, PackMint, PackSlot, Rarity};
+use crate::trades::Trade;
+use axum::Json;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::{OpenApi, ToSchema};
+
+#[derive(Serialize, ToSchema)]
+pub struct CardListResponse {
+    pub cards: Vec<CardSummary>,
+    /// Matching cards across all pages.
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CardSearchResponse {
+    pub results: Vec<CardSummary>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CombinedCard {
+    pub name: String,
+    pub description: String,
+    /// Absent while the image is still pending.
+    pub image_path: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CombineResponse {
+    pub game: GameState,
+    pub crafted_card: CombinedCard,
+    pub is_new: bool,
+    /// Set when `async_image` was requested and the art is still generating.
+    /// Call `finalize-combine` with `cache_key` to render it.
+    pub image_pending: Option<bool>,
+    pub cache_key: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FinalizeCombineResponse {
+    pub game: GameState,
+    pub image_path: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Judgment {
+    /// "a" (defender) or "b" (attacker).
+    pub winner: String,
+    pub reason: String,
+    pub defender: String,
+    pub attacker: String,
+    pub category: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PlaceResponse {
+    /// "placed", "conquered", or "defended".
+    pub result: String,
+    pub judgment: Option<Judgment>,
+    pub game: GameState,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BotTurnResponse {
+    /// "bot_failed" or "bot_skipped_place" when the bot gave up; otherwise the
+    /// fields of the underlying combine or place response are returned.
+    pub result: Option<String>,
+    pub game: GameState,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WalletCard {
+    pub mint_address: String,
+    pub card_id: String,
+    /// Season of the collection the card was minted into.
+    pub season: String,
+    /// Back of the card, marked with its season.
+    pub card_back: String,
+    /// Which of the card's mints this is; absent on cards minted before
+    /// editions were stamped.
+    pub edition: Option<u64>,
+    /// Frozen for good: can be burned or combined, not transferred or traded.
+    pub soulbound: bool,
+    pub name: String,
+    pub description: String,
+    pub image_path: String,
+    pub kind: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WalletCardsResponse {
+    /// Solana cluster the server is on ("devnet" or "mainnet-beta").
+    pub cluster: String,
+    pub cards: Vec<WalletCard>,
+    /// Earned by burning cards; spendable on packs.
+    pub essence: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MintedCard {
+    pub card_id: String,
+    pub name: String,
+    pub description: String,
+    pub image_path: String,
+    /// Which of the card's mints this is, starting at 1.
+    pub edition: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MintTxResponse {
+    /// Solana cluster the server is on.
+    pub cluster: String,
+    /// Base64-encoded transaction for the wallet to sign and submit.
+    pub transaction: String,
+    pub asset_address: String,
+    pub card: MintedCard,
+    /// Only present on wallet combines.
+    pub is_new: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TransferTxResponse {
+    /// Solana cluster the server is on.
+    pub cluster: String,
+    /// Base64-encoded transaction for the wallet to sign and submit.
+    pub transaction: String,
+    pub asset_address: String,
+    pub card_id: String,
+    pub recipient: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BurnTxResponse {
+    /// Solana cluster the server is on.
+    pub cluster: String,
+    /// Base64-encoded transaction for the wallet to sign and submit.
+    pub transaction: String,
+    /// Essence credited once the burn lands.
+    pub essence: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BurnConfirmResponse {
+    /// Assets found burned by this call.
+    pub burned: Vec<String>,
+    /// Essence those burns credited.
+    pub essence_earned: u64,
+    pub essence: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TradeListResponse {
+    /// Wallet that holds offered cards while trades are open.
+    pub escrow: String,
+    pub trades: Vec<Trade>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TradeTxResponse {
+    /// Solana cluster the server is on.
+    pub cluster: String,
+    /// Base64-encoded transaction for the wallet to sign and submit.
+    pub transaction: String,
+    pub trade: Trade,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TradeCancelResponse {
+    pub trade: Trade,
+    /// Offered cards sent back to the proposer from escrow.
+    pub returned: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PackCard {
+    pub card_id: String,
+    pub name: String,
+    pub description: String,
+    pub image_path: String,
+    pub kind: String,
+    pub rarity: Rarity,
+    pub soulbound: bool,
+    /// Minted with a foil finish; `image_path` shows it.
+    pub foil: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PackListResponse {
+    pub packs: Vec<Pack>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PackBuyResponse {
+    /// Solana cluster the server is on.
+    pub cluster: String,
+    /// Base64-encoded payment transaction for the wallet to sign. Absent
+    /// when the pack was paid with essence.
+    pub payment_transaction: Option<String>,
+    /// Pass to `/api/wallet/pack/confirm` to open the pack.
+    pub purchase_id: String,
+    /// SHA-256 (hex) of the server seed revealed when the pack is opened.
+    pub commitment: String,
+    /// SHA-256 (hex) of the cards the pack can draw.
+    pub pool_hash: String,
+    pub wallet_address: String,
+    pub price_sol: f64,
+    /// Price when paying with essence (0 when that isn't allowed).
+    pub price_essence: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MintResult {
+    pub signature: String,
+    pub asset_address: String,
+    pub card_id: String,
+    pub edition: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PackConfirmResponse {
+    /// Solana cluster the server is on.
+    pub cluster: String,
+    pub purchase_id: String,
+    /// Hashes to the commitment given at purchase.
+    pub server_seed: String,
+    pub client_seed: String,
+    /// The cards drawn, in order.
+    pub cards: Vec<PackCard>,
+    pub minted: Vec<MintResult>,
+    /// Cards drawn that reached their supply cap (or were removed) since the
+    /// pack was bought, so weren't minted and were refunded.
+    pub sold_out: Vec<String>,
+    /// Cards whose mint failed; they are retried in the background, and
+    /// refunded if they never succeed.
+    pub retrying: Vec<String>,
+    /// Essence credited for the cards in `sold_out`.
+    pub refunded_essence: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PackStatusResponse {
+    pub purchase_id: String,
+    pub pack_id: String,
+    pub opened_at: Option<u64>,
+    /// Essence credited for each card that can't be minted.
+    pub refund_essence: u64,
+    /// Each drawn card's mint, in draw order. Empty until opened.
+    pub cards: Vec<PackMint>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PackVerifyResponse {
+    pub purchase_id: String,
+    pub pack_id: String,
+    pub wallet_address: String,
+    pub bought_at: u64,
+    pub commitment: String,
+    pub pool_hash: String,
+    /// Card ids the pack could draw, by rarity, each list sorted.
+    pub pool: BTreeMap<Rarity, Vec<String>>,
+    pub slots: Vec<PackSlot>,
+    /// The rest is null (or empty) until the pack is opened.
+    pub opened_at: Option<u64>,
+    pub server_seed: Option<String>,
+    pub client_seed: Option<String>,
+    /// The SOL payment it was opened with. Null for essence purchases.
+    pub payment_signature: Option<String>,
+    pub cards: Vec<String>,
+    /// Whether the seeds reproduce `cards`.
+    pub verified: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SubmitTxResponse {
+    /// Solana cluster the server is on.
+    pub cluster: String,
+    pub signature: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RefreshTxResponse {
+    /// Solana cluster the server is on.
+    pub cluster: String,
+    /// Base64 transaction for the wallet to sign, in place of the expired one.
+    pub transaction: String,
+    pub asset_address: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NonceResponse {
+    /// Solana cluster the server is on.
+    pub cluster: String,
+    pub nonce: String,
+    /// Sign exactly these bytes (UTF-8) with the wallet.
+    pub message: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LoginResponse {
+    /// Send as `X-Wallet-Session` on wallet requests and wallet-linked games.
+    pub session_token: String,
+    pub wallet_address: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Alchemaybe Game Server",
+        description = "Game state, card crafting, and Solana wallet endpoints. \
+                       Errors use the shared `ApiError` body; branch on `code`."
+    ),
+    paths(
+        crate::game_api::list_cards,
+        crate::game_api::search_cards,
+        crate::game_api::get_card,
+        crate::game_api::new_game,
+        crate::game_api::get_game,
+        crate::game_events::game_socket,
+        crate::game_api::combine,
+        crate::game_api::finalize_combine,
+        crate::game_api::place,
+        crate::game_api::discard,
+        crate::game_api::end_turn,
+        crate::game_api::bot_combine,
+        crate::game_api::bot_place,
+        crate::game_api::attest,
+        crate::generate::generate_card,
+        crate::generate::card_back,
+        crate::print::print_cards,
+        crate::art_upload::upload_art,
+        crate::wallet_auth::wallet_nonce,
+        crate::wallet_auth::wallet_login,
+        crate::wallet_auth::wallet_logout,
+        crate::solana_api::wallet_cards,
+        crate::solana_api::wallet_claim,
+        crate::solana_api::wallet_combine,
+        crate::solana_api::wallet_transfer,
+        crate::solana_api::wallet_burn,
+        crate::solana_api::wallet_burn_confirm,
+        crate::trades::wallet_trades,
+        crate::trades::trade_propose,
+        crate::trades::trade_accept,
+        crate::trades::trade_cancel,
+        crate::packs::list_packs,
+        crate::packs::verify_pack,
+        crate::solana_api::wallet_pack_buy,
+        crate::solana_api::wallet_pack_confirm,
+        crate::packs::pack_status,
+        crate::solana_api::wallet_submit_tx,
+        crate::solana_api::wallet_refresh_tx,
+        crate::assets::wallet_events,
+    ),
+    components(schemas(ApiError, ErrorCode)),
+    tags(
+        (name = "game", description = "Game lifecycle and turns"),
+        (name = "cards", description = "Base cards and card rendering"),
+        (name = "wallet", description = "Solana NFT wallet actions (requires Solana config)"),
+    )
+)]
+pub struct ApiDoc;
+
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
@@ -0,0 +1,857 @@
+//! The pack catalog: what's on sale at `/api/wallet/pack/buy`, what each
+//! pack costs, and how its cards are drawn. Read from `packs.json` at startup
+//! and on `/api/admin/reload-content`.
+
+use crate::assets::{self, AssetStatus, TrackedAsset};
+use crate::card_cache::{now_secs, CardCache};
+use crate::config::SupplySettings;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use crate::solana::{Finish, SolanaConfig};
+use crate::wallet_auth::require_wallet;
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Tries at minting a pack card, counting the one made on opening, before
+/// the buyer is refunded instead.
+const MAX_MINT_ATTEMPTS: u32 = 5;
+/// Wait before retrying a failed mint, doubled after each further failure.
+const RETRY_DELAY_SECS: u64 = 60;
+/// How long a card is left to whoever is minting it (the request opening the
+/// pack, or a retry) before another may try.
+const MINT_LEASE_SECS: u64 = 300;
+/// How often the retrier looks for cards to mint.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// Cards retried per pass.
+const RETRY_BATCH: usize = 20;
+
+/// How hard a card is to pull. Base cards are common, crafted cards rare,
+/// and crafted cards with a supply cap legendary.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Rarity {
+    #[default]
+    Common,
+    Rare,
+    Legendary,
+}
+
+impl Rarity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Rarity::Common => "common",
+            Rarity::Rare => "rare",
+            Rarity::Legendary => "legendary",
+        }
+    }
+
+    /// The rarity of `card_id`, a crafted card or a base one.
+    pub fn of(state: &AppState, card_id: &str, crafted: bool) -> Self {
+        if crafted {
+            Rarity::of_crafted(&state.supply, card_id)
+        } else {
+            Rarity::Common
+        }
+    }
+
+    /// The rarity of the crafted card `card_id`: legendary when its supply
+    /// is capped.
+    pub fn of_crafted(supply: &SupplySettings, card_id: &str) -> Self {
+        if supply.cap(card_id, true).is_some() {
+            Rarity::Legendary
+        } else {
+            Rarity::Rare
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PackSlot {
+    /// Cards this slot draws.
+    #[serde(default = "one")]
+    pub count: usize,
+    /// Relative chance of each rarity for every card the slot draws.
+    pub weights: BTreeMap<Rarity, u32>,
+    /// Mint this slot's cards soulbound (non-transferable).
+    #[serde(default)]
+    pub soulbound: bool,
+    /// Mint this slot's cards with a foil finish.
+    #[serde(default)]
+    pub foil: bool,
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Pack {
+    /// Passed as `pack_type` to `/api/wallet/pack/buy`.
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 0 means it can only be bought with essence.
+    #[serde(default)]
+    pub price_lamports: u64,
+    /// 0 means it can only be bought with SOL.
+    #[serde(default)]
+    pub price_essence: u64,
+    pub slots: Vec<PackSlot>,
+    /// Packs of this kind one wallet may ever buy. 0 means no limit.
+    #[serde(default)]
+    pub per_wallet: u64,
+    /// Packs of this kind one wallet may buy in any 24 hours. 0 means no
+    /// limit.
+    #[serde(default)]
+    pub per_wallet_per_day: u64,
+    /// Essence credited for each of the pack's cards that can't be minted.
+    /// 0 means the card's share of `price_essence`, rounded up, which is
+    /// nothing for a pack only sold for SOL.
+    #[serde(default)]
+    pub refund_essence: u64,
+}
+
+impl Pack {
+    /// Essence credited in place of one of this pack's cards.
+    pub fn refund_per_card(&self) -> u64 {
+        if self.refund_essence > 0 {
+            return self.refund_essence;
+        }
+        let cards = self.slots.iter().map(|s| s.count).sum::<usize>().max(1);
+        self.price_essence.div_ceil(cards as u64)
+    }
+}
+
+#[derive(Deserialize)]
+struct PackCatalog {
+    packs: Vec<Pack>,
+}
+
+/// Read and validate packs.json.
+pub fn load_packs(path: &std::path::Path) -> Result<Vec<Pack>, String> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let catalog: PackCatalog = serde_json::from_str(&data)
+        .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+
+    let mut ids = HashSet::new();
+    for pack in &catalog.packs {
+        let invalid = |why: &str| format!("{}: pack {:?} {why}", path.display(), pack.id);
+        if pack.id.is_empty() || !ids.insert(pack.id.as_str()) {
+            return Err(invalid("needs a unique, non-empty id"));
+        }
+        if pack.price_lamports == 0 && pack.price_essence == 0 {
+            return Err(invalid("has no price"));
+        }
+        if pack.slots.iter().map(|s| s.count).sum::<usize>() == 0 {
+            return Err(invalid("has no cards"));
+        }
+        if pack.slots.iter().any(|s| s.weights.values().sum::<u32>() == 0) {
+            return Err(invalid("has a slot with no rarity weights"));
+        }
+    }
+    Ok(catalog.packs)
+}
+
+/// A pack bought by a wallet: the commitment made when it was bought, and
+/// the cards drawn once it's opened.
+pub struct PackPurchase {
+    pub id: String,
+    pub wallet: String,
+    pub pack_id: String,
+    /// Hex. Kept secret until the pack is opened; its SHA-256 is the
+    /// commitment shown at purchase.
+    pub server_seed: String,
+    /// The pack's slots and the cards it could draw, as they were when it was
+    /// bought.
+    pub slots: Vec<PackSlot>,
+    pub pool: Pool,
+    pub bought_at: u64,
+    /// Buyer's contribution to the draw, given when opening it.
+    pub client_seed: Option<String>,
+    /// Card ids drawn, in order. Empty until opened.
+    pub cards: Vec<String>,
+    pub opened_at: Option<u64>,
+    /// Essence credited for each card that can't be minted, as it was when
+    /// the pack was bought.
+    pub refund_essence: u64,
+    /// Lamports the buyer has to pay before opening it. 0 when it was paid
+    /// for in essence.
+    pub price_lamports: u64,
+    /// The transaction that paid `price_lamports`, checked when opening it.
+    pub payment_signature: Option<String>,
+}
+
+impl PackPurchase {
+    /// A purchase of `pack`, to be paid `price_lamports` (0 if it was paid
+    /// for in essence).
+    pub fn new(wallet: &str, pack: &Pack, pool: Pool, price_lamports: u64) -> Self {
+        let seed: [u8; 32] = rand::random();
+        PackPurchase {
+            id: uuid::Uuid::new_v4().to_string(),
+            wallet: wallet.to_string(),
+            pack_id: pack.id.clone(),
+            server_seed: seed.iter().map(|b| format!("{b:02x}")).collect(),
+            slots: pack.slots.clone(),
+            pool,
+            bought_at: now_secs(),
+            client_seed: None,
+            cards: Vec::new(),
+            opened_at: None,
+            refund_essence: pack.refund_per_card(),
+            price_lamports,
+            payment_signature: None,
+        }
+    }
+
+    pub fn commitment(&self) -> String {
+        format!("{:x}", Sha256::digest(self.server_seed.as_bytes()))
+    }
+}
+
+/// How far minting one of an opened pack's cards has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MintStatus {
+    /// Being minted by the request that opened the pack.
+    Pending,
+    Minted,
+    /// The last try failed; it will be tried again.
+    Failed,
+    /// It couldn't be minted (sold out, removed, or out of tries), so the
+    /// buyer was credited essence instead.
+    Refunded,
+}
+
+impl MintStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MintStatus::Pending => "pending",
+            MintStatus::Minted => "minted",
+            MintStatus::Failed => "failed",
+            MintStatus::Refunded => "refunded",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(MintStatus::Pending),
+            "minted" => Some(MintStatus::Minted),
+            "failed" => Some(MintStatus::Failed),
+            "refunded" => Some(MintStatus::Refunded),
+            _ => None,
+        }
+    }
+}
+
+/// One card drawn from an opened pack, on its way to the buyer's wallet.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PackMint {
+    #[serde(skip)]
+    pub purchase_id: String,
+    /// Position among the pack's drawn cards.
+    pub slot: usize,
+    #[serde(skip)]
+    pub wallet: String,
+    pub card_id: String,
+    pub soulbound: bool,
+    pub foil: bool,
+    pub status: MintStatus,
+    /// Taken before the first try, so retries keep the same number.
+    pub edition: Option<u64>,
+    pub attempts: u32,
+    pub asset_address: Option<String>,
+    pub signature: Option<String>,
+    /// Why the last try failed, or why the card was refunded.
+    pub error: Option<String>,
+    /// Essence credited in the card's place.
+    pub refund: u64,
+    /// When it may next be tried.
+    #[serde(skip)]
+    pub retry_at: u64,
+    pub updated_at: u64,
+}
+
+impl PackMint {
+    /// The `slot`th card drawn from `purchase`, by `drawn_by`, left to the
+    /// request opening the pack for a while.
+    pub fn new(purchase: &PackPurchase, slot: usize, card_id: &str, drawn_by: &PackSlot) -> Self {
+        let now = now_secs();
+        PackMint {
+            purchase_id: purchase.id.clone(),
+            slot,
+            wallet: purchase.wallet.clone(),
+            card_id: card_id.to_string(),
+            soulbound: drawn_by.soulbound,
+            foil: drawn_by.foil,
+            status: MintStatus::Pending,
+            edition: None,
+            attempts: 0,
+            asset_address: None,
+            signature: None,
+            error: None,
+            refund: 0,
+            retry_at: now + MINT_LEASE_SECS,
+            updated_at: now,
+        }
+    }
+}
+
+/// Card ids packs can draw, by rarity, each list sorted.
+pub type Pool = BTreeMap<Rarity, Vec<String>>;
+
+/// SHA-256 (hex) of the pool written one rarity per line, in rarity order:
+/// `common:id,id,...`.
+pub fn pool_hash(pool: &Pool) -> String {
+    let lines: Vec<String> = pool
+        .iter()
+        .map(|(rarity, ids)| format!("{}:{}", rarity.as_str(), ids.join(",")))
+        .collect();
+    format!("{:x}", Sha256::digest(lines.join("\n").as_bytes()))
+}
+
+/// Draw a pack's card ids from `pool`, determined entirely by the seeds. Each
+/// slot picks a rarity by weight among those with cards in the pool, then one
+/// of those cards; a slot with none of its rarities in the pool draws a
+/// common card instead. Every pick is a `roll`, so anyone holding the seeds
+/// can repeat the draw.
+pub fn draw(slots: &[PackSlot], pool: &Pool, server_seed: &str, client_seed: &str) -> Vec<String> {
+    let commons = pool.get(&Rarity::Common).map_or(&[][..], Vec::as_slice);
+    let mut rolls = 0u64;
+    let mut roll = |n: usize| {
+        let hash = Sha256::digest(format!("{server_seed}:{client_seed}:{rolls}").as_bytes());
+        rolls += 1;
+        let value = u64::from_be_bytes(hash[..8].try_into().expect("8 bytes"));
+        (value % n as u64) as usize
+    };
+
+    let mut drawn = Vec::new();
+    for slot in slots {
+        let available: Vec<(&[String], u32)> = slot
+            .weights
+            .iter()
+            .filter_map(|(rarity, &weight)| {
+                let ids = pool.get(rarity).filter(|ids| !ids.is_empty() && weight > 0)?;
+                Some((ids.as_slice(), weight))
+            })
+            .collect();
+        let total: u32 = available.iter().map(|(_, weight)| weight).sum();
+
+        for _ in 0..slot.count {
+            let ids = if total == 0 {
+                commons
+            } else {
+                let mut picked = roll(total as usize) as u32;
+                let mut ids = available[0].0;
+                for &(candidates, weight) in &available {
+                    if picked < weight {
+                        ids = candidates;
+                        break;
+                    }
+                    picked -= weight;
+                }
+                ids
+            };
+            if !ids.is_empty() {
+                drawn.push(ids[roll(ids.len())].clone());
+            }
+        }
+    }
+    drawn
+}
+
+/// The slot each card `draw` returns for `slots` was drawn by.
+pub fn draw_slots(slots: &[PackSlot]) -> Vec<&PackSlot> {
+    slots
+        .iter()
+        .flat_map(|slot| std::iter::repeat_n(slot, slot.count))
+        .collect()
+}
+
+/// Every card packs can draw right now. Sold-out cards are left out.
+pub async fn current_pool(state: &AppState) -> Result<Pool, ApiError> {
+    let base_cards = state.base_cards.read().await;
+    let cache = state.card_cache.read().await;
+    let mut pool = Pool::new();
+
+    for base in base_cards.iter() {
+        if in_supply(state, &cache, &base.id, false) {
+            let rarity = Rarity::of(state, &base.id, false);
+            pool.entry(rarity).or_default().push(base.id.clone());
+        }
+    }
+    for crafted in cache.discovered_cards() {
+        if !in_supply(state, &cache, &crafted.id, true) {
+            continue;
+        }
+        let rarity = Rarity::of(state, &crafted.id, true);
+        pool.entry(rarity).or_default().push(crafted.id);
+    }
+    if pool.get(&Rarity::Common).is_none_or(Vec::is_empty) {
+        return Err(err(ErrorCode::CardSoldOut, "Every base card has sold out"));
+    }
+    for ids in pool.values_mut() {
+        ids.sort_unstable();
+        ids.dedup();
+    }
+    Ok(pool)
+}
+
+/// A drawn card, for minting and showing to the buyer.
+pub struct DrawnCard {
+    pub card_id: String,
+    pub name: String,
+    pub description: String,
+    pub image_path: String,
+    pub kind: String,
+    pub rarity: Rarity,
+    /// Whether `[supply] crafted_cap` applies to it.
+    pub crafted: bool,
+}
+
+/// Look up `card_id` among the base and crafted cards. None if it has been
+/// removed since the pack was bought.
+pub async fn drawn_card(state: &AppState, pool: &Pool, card_id: &str) -> Option<DrawnCard> {
+    let rarity = pool
+        .iter()
+        .find(|(_, ids)| ids.binary_search_by(|id| id.as_str().cmp(card_id)).is_ok())
+        .map_or(Rarity::Common, |(rarity, _)| *rarity);
+    if let Some(base) = state.base_cards.read().await.iter().find(|b| b.id == card_id) {
+        return Some(DrawnCard {
+            card_id: base.id.clone(),
+            name: base.name.clone(),
+            description: base.description.clone(),
+            image_path: base.image_path.clone(),
+            kind: base.kind.clone(),
+            rarity,
+            crafted: false,
+        });
+    }
+    let crafted = state.card_cache.read().await.get(card_id)?;
+    Some(DrawnCard {
+        card_id: crafted.id,
+        name: crafted.name,
+        description: crafted.description,
+        image_path: crafted.image_path,
+        kind: "crafted".to_string(),
+        rarity,
+        crafted: true,
+    })
+}
+
+/// Whether `card_id` can still be minted under its supply cap.
+fn in_supply(state: &AppState, cache: &CardCache, card_id: &str, crafted: bool) -> bool {
+    state
+        .supply
+        .cap(card_id, crafted)
+        .is_none_or(|cap| cache.editions(card_id) < cap)
+}
+
+/// Record a purchase of `pack`, committing to the seed its cards will be
+/// drawn with. Bought with essence, the price is taken in the same step;
+/// bought with SOL, its `price_lamports` is owed before it can be opened.
+pub fn record_purchase(
+    state: &AppState,
+    wallet: &str,
+    pack: &Pack,
+    pool: Pool,
+    pay_with_essence: bool,
+) -> Result<PackPurchase, ApiError> {
+    let store = |e: String| err(ErrorCode::StorageError, e);
+    let (lamports, essence) =
+        if pay_with_essence { (0, pack.price_essence) } else { (pack.price_lamports, 0) };
+    let purchase = PackPurchase::new(wallet, pack, pool, lamports);
+    if !state.assets.record_pack_purchase(&purchase, essence).map_err(store)? {
+        let balance = state.assets.essence(wallet).map_err(store)?;
+        return Err(err(ErrorCode::NotEnoughEssence, "Not enough essence for this pack")
+            .with_details(serde_json::json!({ "price": essence, "essence": balance })));
+    }
+    if pay_with_essence {
+        log::info!("{wallet} paid {essence} essence for a {} pack", pack.id);
+    }
+    Ok(purchase)
+}
+
+/// Refuse the purchase if `wallet` has bought as many of `pack` as it may.
+pub fn check_limits(state: &AppState, wallet: &str, pack: &Pack) -> Result<(), ApiError> {
+    let store = |e: String| err(ErrorCode::StorageError, e);
+    let day_ago = now_secs().saturating_sub(86_400);
+    for (limit, since, per) in [
+        (pack.per_wallet, 0, "per wallet"),
+        (pack.per_wallet_per_day, day_ago, "per wallet per day"),
+    ] {
+        if limit > 0
+            && state
+                .assets
+                .pack_purchases(wallet, &pack.id, since)
+                .map_err(store)?
+                >= limit
+        {
+            return Err(err(
+                ErrorCode::PackLimitReached,
+                format!("Purchase limit reached ({limit} {} {per})", pack.name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Why a try at minting a pack card didn't mint it.
+enum MintFailure {
+    /// It can never be minted.
+    Gone(String),
+    Failed(String),
+}
+
+/// One try at minting `mint` to its buyer, taking its edition first if it
+/// has none. `card` is the drawn card, or `None` if it no longer exists. A
+/// card that can't ever be minted, or fails its last try, is refunded; one
+/// that fails otherwise is left for the retrier. `announce` tells the
+/// wallet's event streams about the mint, for mints made after the pack was
+/// opened.
+pub async fn fulfill(
+    state: &AppState,
+    solana: &SolanaConfig,
+    card: Option<&DrawnCard>,
+    mut mint: PackMint,
+    announce: bool,
+) -> PackMint {
+    mint.attempts += 1;
+    mint.updated_at = now_secs();
+    match try_mint(state, solana, card, &mut mint).await {
+        Ok((signature, asset_address)) => {
+            log::info!("Minted {} -> {asset_address} (sig: {signature})", mint.card_id);
+            // Opening the pack reports its own mints, so those are already
+            // confirmed; later ones settle so the wallet hears of them
+            let status = if announce { AssetStatus::Pending } else { AssetStatus::Confirmed };
+            let tracked = |status| {
+                let mut asset =
+                    TrackedAsset::new(&asset_address, &mint.card_id, &mint.wallet, status);
+                asset.signature = Some(signature.clone());
+                asset
+            };
+            assets::track(state, &[tracked(status)]);
+            // Settling counts the mint
+            if announce {
+                if let Err(e) = assets::settle(state, solana, tracked(status), &signature).await {
+                    log::warn!("Failed to settle {asset_address}: {}", e.message);
+                }
+            } else {
+                state.card_cache.write().await.record_mint(&mint.card_id);
+            }
+            mint.status = MintStatus::Minted;
+            mint.asset_address = Some(asset_address);
+            mint.signature = Some(signature);
+            mint.error = None;
+        }
+        Err(MintFailure::Gone(why)) => return refund(state, mint, why),
+        Err(MintFailure::Failed(e)) if mint.attempts >= MAX_MINT_ATTEMPTS => {
+            return refund(state, mint, e)
+        }
+        Err(MintFailure::Failed(e)) => {
+            log::warn!(
+                "Minting pack card {} to {} failed (attempt {}): {e}",
+                mint.card_id,
+                mint.wallet,
+                mint.attempts
+            );
+            mint.status = MintStatus::Failed;
+            mint.error = Some(e);
+            mint.retry_at = mint.updated_at + (RETRY_DELAY_SECS << (mint.attempts - 1));
+        }
+    }
+    if let Err(e) = state.assets.update_pack_mint(&mint) {
+        log::error!("Failed to record pack card {} of {}: {e}", mint.slot, mint.purchase_id);
+    }
+    mint
+}
+
+async fn try_mint(
+    state: &AppState,
+    solana: &SolanaConfig,
+    card: Option<&DrawnCard>,
+    mint: &mut PackMint,
+) -> Result<(String, String), MintFailure> {
+    let Some(card) = card else {
+        return Err(MintFailure::Gone("Card no longer exists".to_string()));
+    };
+    let recipient = Pubkey::from_str(&mint.wallet)
+        .map_err(|e| MintFailure::Gone(format!("Invalid wallet: {e}")))?;
+    let edition = match mint.edition {
+        Some(edition) => edition,
+        None => {
+            let cap = state.supply.cap(&mint.card_id, card.crafted);
+            let cache = state.card_cache.read().await;
+            let edition = cache.issue_edition(&mint.card_id, cap).map_err(MintFailure::Failed)?;
+            let edition = edition.ok_or_else(|| MintFailure::Gone("Sold out".to_string()))?;
+            mint.edition = Some(edition);
+            edition
+        }
+    };
+    let metadata_uri = solana
+        .ensure_metadata_json(
+            &mint.card_id,
+            &card.name,
+            &card.description,
+            &card.image_path,
+            Finish::new(mint.foil, Rarity::of(state, &mint.card_id, card.crafted)),
+            None,
+        )
+        .map_err(MintFailure::Failed)?;
+    solana
+        .server_mint(&mint.card_id, edition, mint.soulbound, &card.name, &metadata_uri, &recipient)
+        .map_err(MintFailure::Failed)
+}
+
+/// Credit the buyer essence in place of a card that won't be minted.
+fn refund(state: &AppState, mut mint: PackMint, why: String) -> PackMint {
+    mint.status = MintStatus::Refunded;
+    mint.error = Some(why);
+    match state.assets.refund_pack_mint(&mint) {
+        Ok(Some(refund)) => {
+            mint.refund = refund;
+            log::warn!(
+                "Refunded {} {refund} essence for pack card {} ({})",
+                mint.wallet,
+                mint.card_id,
+                mint.error.as_deref().unwrap_or_default()
+            );
+        }
+        // Another server got there first
+        Ok(None) => {}
+        Err(e) => {
+            log::error!("Failed to refund pack card {} of {}: {e}", mint.slot, mint.purchase_id);
+            mint.status = MintStatus::Failed;
+        }
+    }
+    mint
+}
+
+/// Keep trying pack cards whose mint failed, or whose opening request never
+/// finished, until each is minted or refunded.
+pub async fn retry_mints(state: Arc<AppState>) {
+    let Some(solana) = state.solana.clone() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(RETRY_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = now_secs();
+        let due = match state.assets.due_pack_mints(now, now + MINT_LEASE_SECS, RETRY_BATCH) {
+            Ok(due) => due,
+            Err(e) => {
+                log::warn!("Failed to list pack cards to mint: {e}");
+                continue;
+            }
+        };
+        for mint in due {
+            let purchase = match state.assets.pack_purchase(&mint.purchase_id) {
+                Ok(Some(purchase)) => purchase,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::warn!("Failed to read pack purchase {}: {e}", mint.purchase_id);
+                    continue;
+                }
+            };
+            let card = drawn_card(&state, &purchase.pool, &mint.card_id).await;
+            fulfill(&state, &solana, card.as_ref(), mint, true).await;
+        }
+    }
+}
+
+// --- GET /api/packs ---
+
+#[utoipa::path(
+    get,
+    path = "/api/packs",
+    tag = "wallet",
+    responses(
+        (status = 200, description = "Packs on sale", body = crate::openapi::PackListResponse),
+    )
+)]
+pub async fn list_packs(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let packs = state.packs.read().await;
+    Json(serde_json::json!({ "packs": *packs }))
+}
+
+// --- GET /api/packs/verify/{purchase_id} ---
+
+#[utoipa::path(
+    get,
+    path = "/api/packs/verify/{purchase_id}",
+    tag = "wallet",
+    params(("purchase_id" = String, Path, description = "From `/api/wallet/pack/buy`")),
+    responses(
+        (status = 200, description = "The purchase's commitment, and once opened, its seeds and \
+                                      cards", body = crate::openapi::PackVerifyResponse),
+        (status = 404, description = "Purchase not found", body = ApiError),
+    )
+)]
+pub async fn verify_pack(
+    State(state): State<Arc<AppState>>,
+    Path(purchase_id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let purchase = state
+        .assets
+        .pack_purchase(&purchase_id)
+        .map_err(|e| err(ErrorCode::StorageError, e))?
+        .ok_or_else(|| err(ErrorCode::PackNotFound, "Pack purchase not found"))?;
+
+    // The seed only comes out once the draw can no longer change
+    let opened = purchase.opened_at.is_some();
+    let verified = purchase.client_seed.as_deref().filter(|_| opened).map(|client_seed| {
+        draw(&purchase.slots, &purchase.pool, &purchase.server_seed, client_seed) == purchase.cards
+    });
+
+    Ok(Json(serde_json::json!({
+        "purchase_id": purchase.id,
+        "pack_id": purchase.pack_id,
+        "wallet_address": purchase.wallet,
+        "bought_at": purchase.bought_at,
+        "commitment": purchase.commitment(),
+        "pool_hash": pool_hash(&purchase.pool),
+        "pool": purchase.pool,
+        "slots": purchase.slots,
+        "opened_at": purchase.opened_at,
+        "server_seed": opened.then_some(&purchase.server_seed),
+        "client_seed": purchase.client_seed,
+        "payment_signature": purchase.payment_signature,
+        "cards": purchase.cards,
+        "verified": verified,
+    })))
+}
+
+// --- GET /api/wallet/pack/{purchase_id} ---
+
+#[utoipa::path(
+    get,
+    path = "/api/wallet/pack/{purchase_id}",
+    tag = "wallet",
+    params(("purchase_id" = String, Path, description = "From `/api/wallet/pack/buy`")),
+    responses(
+        (status = 200, description = "Where each of the pack's cards is in being minted", body = crate::openapi::PackStatusResponse),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 404, description = "Purchase not found", body = ApiError),
+    )
+)]
+pub async fn pack_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(purchase_id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let wallet = require_wallet(&state, &headers)?;
+    let store = |e: String| err(ErrorCode::StorageError, e);
+    let purchase = state
+        .assets
+        .pack_purchase(&purchase_id)
+        .map_err(store)?
+        .filter(|p| p.wallet == wallet)
+        .ok_or_else(|| err(ErrorCode::PackNotFound, "Pack purchase not found"))?;
+    let cards = state.assets.pack_mints(&purchase.id).map_err(store)?;
+
+    Ok(Json(serde_json::json!({
+        "purchase_id": purchase.id,
+        "pack_id": purchase.pack_id,
+        "opened_at": purchase.opened_at,
+        "refund_essence": purchase.refund_essence,
+        "cards": cards,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(entries: &[(Rarity, &[&str])]) -> Pool {
+        entries
+            .iter()
+            .map(|(rarity, ids)| (*rarity, ids.iter().map(|id| id.to_string()).collect()))
+            .collect()
+    }
+
+    fn slot(count: usize, weights: &[(Rarity, u32)]) -> PackSlot {
+        PackSlot {
+            count,
+            weights: weights.iter().copied().collect(),
+            soulbound: false,
+            foil: false,
+        }
+    }
+
+    #[test]
+    fn draw_is_repeatable_from_the_seeds() {
+        let pool = pool(&[(Rarity::Common, &["a", "b", "c"]), (Rarity::Rare, &["x", "y"])]);
+        let slots = [slot(5, &[(Rarity::Common, 1), (Rarity::Rare, 1)])];
+        let drawn = draw(&slots, &pool, "server", "client");
+        assert_eq!(drawn.len(), 5);
+        assert_eq!(drawn, draw(&slots, &pool, "server", "client"));
+        assert_ne!(drawn, draw(&slots, &pool, "server", "other client"));
+    }
+
+    #[test]
+    fn draw_follows_the_slot_weights() {
+        let pool = pool(&[(Rarity::Common, &["a", "b"]), (Rarity::Rare, &["x", "y"])]);
+        let slots = [slot(10, &[(Rarity::Common, 3), (Rarity::Rare, 1)])];
+        let (mut rare, mut total) = (0, 0);
+        let mut seen = HashSet::new();
+        for i in 0..400 {
+            for id in draw(&slots, &pool, "server", &i.to_string()) {
+                rare += usize::from(id == "x" || id == "y");
+                total += 1;
+                seen.insert(id);
+            }
+        }
+        let share = rare as f64 / total as f64;
+        assert!((0.22..0.28).contains(&share), "rare share {share}");
+        assert_eq!(seen.len(), 4, "every card in the pool gets drawn");
+    }
+
+    #[test]
+    fn draw_falls_back_to_commons() {
+        let pool = pool(&[(Rarity::Common, &["a"]), (Rarity::Rare, &[])]);
+        let slots = [slot(2, &[(Rarity::Rare, 1), (Rarity::Legendary, 5)])];
+        assert_eq!(draw(&slots, &pool, "server", "client"), ["a", "a"]);
+    }
+
+    #[test]
+    fn pool_hash_covers_each_rarity_line_in_order() {
+        let pool = pool(&[(Rarity::Rare, &["x"]), (Rarity::Common, &["a", "b"])]);
+        let expected = format!("{:x}", Sha256::digest(b"common:a,b\nrare:x"));
+        assert_eq!(pool_hash(&pool), expected);
+
+        let mut swapped = pool.clone();
+        swapped.insert(Rarity::Rare, vec!["y".to_string()]);
+        assert_ne!(pool_hash(&swapped), expected);
+    }
+
+    #[test]
+    fn commitment_is_the_server_seed_hash() {
+        let pack = Pack {
+            id: "starter".to_string(),
+            name: "Starter".to_string(),
+            description: String::new(),
+            price_lamports: 1_000,
+            price_essence: 0,
+            slots: vec![slot(1, &[(Rarity::Common, 1)])],
+            per_wallet: 0,
+            per_wallet_per_day: 0,
+            refund_essence: 0,
+        };
+        let purchase = PackPurchase::new("wallet", &pack, Pool::new(), pack.price_lamports);
+        assert_eq!(purchase.server_seed.len(), 64);
+        let expected = format!("{:x}", Sha256::digest(purchase.server_seed.as_bytes()));
+        assert_eq!(purchase.commitment(), expected);
+        assert_eq!(purchase.refund_essence, 0);
+    }
+}
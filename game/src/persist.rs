@@ -0,0 +1,116 @@
+use crate::game_state::GameState;
+use crate::generate::AppState;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+
+/// Background persistence for games. Handlers only bump a change counter (via
+/// `AppState::games_mut`); a background task snapshots the games and hands the
+/// ones whose content changed to the `GameStore` every `interval`, or sooner
+/// once `flush_after` changes pile up, so no request ever waits on storage.
+/// Saving is one way: the games are only read back on the next start, even
+/// from a store other servers share, so each game must stay on one server.
+pub struct GamesPersister {
+    pending: AtomicU32,
+    wake: Notify,
+    flush_after: u32,
+    interval: Duration,
+    /// Content hash of each game as last saved. Locking it also serializes
+    /// writers, so the periodic flush and the shutdown flush never overlap.
+    saved: Mutex<HashMap<String, u64>>,
+}
+
+impl GamesPersister {
+    /// `games` are the ones just loaded from the store, so they count as saved.
+    pub fn new(interval_secs: u64, flush_after: u32, games: &HashMap<String, GameState>) -> Self {
+        GamesPersister {
+            pending: AtomicU32::new(0),
+            wake: Notify::new(),
+            flush_after: flush_after.max(1),
+            interval: Duration::from_secs(interval_secs.max(1)),
+            saved: Mutex::new(content_hashes(games)),
+        }
+    }
+
+    /// Record a change to the games map.
+    pub fn mark_dirty(&self) {
+        let pending = self.pending.fetch_add(1, Ordering::Relaxed) + 1;
+        if pending == self.flush_after {
+            self.wake.notify_one();
+        }
+    }
+
+    /// Flush on the interval, or early when enough changes are pending. Runs
+    /// for the life of the server.
+    pub async fn run(state: Arc<AppState>) {
+        let persister = &state.games_persister;
+        let mut ticker = tokio::time::interval(persister.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = persister.wake.notified() => {}
+            }
+            if let Err(e) = persister.flush(&state).await {
+                log::error!("Failed to save games: {e}");
+            }
+        }
+    }
+
+    /// Save the games whose content changed since the last save. Returns how
+    /// many were saved (0 when there was nothing to do).
+    pub async fn flush(&self, state: &AppState) -> Result<usize, String> {
+        let mut saved = self.saved.lock().await;
+        let changes = self.pending.swap(0, Ordering::Relaxed);
+        if changes == 0 {
+            return Ok(0);
+        }
+
+        let games = state.games.read().await.clone();
+        let store = state.game_store.clone();
+        let previous = saved.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let hashes = content_hashes(&games);
+            let changed: Vec<String> = hashes
+                .iter()
+                .filter(|(id, hash)| previous.get(*id) != Some(hash))
+                .map(|(id, _)| id.clone())
+                .collect();
+            if !changed.is_empty() {
+                store.save(&games, &changed)?;
+            }
+            Ok((hashes, changed.len()))
+        })
+        .await
+        .map_err(|e| format!("save task failed: {e}"))
+        .and_then(|r| r);
+
+        match result {
+            Ok((hashes, count)) => {
+                *saved = hashes;
+                Ok(count)
+            }
+            Err(e) => {
+                // Keep the changes pending so the next tick retries
+                self.pending.fetch_add(changes, Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+}
+
+fn content_hashes(games: &HashMap<String, GameState>) -> HashMap<String, u64> {
+    games
+        .iter()
+        .map(|(id, game)| {
+            let mut hasher = DefaultHasher::new();
+            serde_json::to_string(game)
+                .unwrap_or_default()
+                .hash(&mut hasher);
+            (id.clone(), hasher.finish())
+        })
+        .collect()
+}
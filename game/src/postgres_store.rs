@@ -0,0 +1,1442 @@
+//! Postgres backend (the `postgres` feature). One database holds the card
+//! cache and mint counts, shared live by several game servers, and keeps
+//! each server's games across restarts (games themselves aren't shared).
+
+use crate::assets::{AssetStatus, TrackedAsset};
+use crate::card_cache::{
+    now_secs, CachedCard, CardCounts, CardQuery, ConflictStrategy, MergeSummary,
+};
+use crate::claims::{self, Claim, ClaimLimit, ClaimVoucher};
+use crate::config::ClaimSettings;
+use crate::packs::{MintStatus, PackMint, PackPurchase};
+use crate::game_state::GameState;
+use crate::solana::OwnedCard;
+use crate::storage::{AssetStore, CardStore, ClaimStore, GameStore, OwnershipStore, TradeStore};
+use crate::trades::{Trade, TradeStatus};
+use postgres::{Client, GenericClient, NoTls, Row};
+use r2d2_postgres::r2d2::Pool;
+use r2d2_postgres::PostgresConnectionManager;
+use std::collections::HashMap;
+
+/// Applied in order on connect; `schema_migrations` records how many ran.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE cards (
+        id            TEXT PRIMARY KEY,
+        name          TEXT NOT NULL,
+        description   TEXT NOT NULL,
+        image_path    TEXT NOT NULL,
+        discovered    BOOLEAN NOT NULL DEFAULT FALSE,
+        impossible    BOOLEAN NOT NULL DEFAULT FALSE,
+        recipe        TEXT,
+        discovered_at BIGINT,
+        created_at    BIGINT,
+        times_crafted BIGINT NOT NULL DEFAULT 0
+    );
+    CREATE INDEX cards_name ON cards (lower(name));
+    CREATE INDEX cards_discovered ON cards (discovered);
+    CREATE INDEX cards_impossible ON cards (impossible, created_at);
+    CREATE TABLE mint_counts (
+        card_id TEXT PRIMARY KEY,
+        count   BIGINT NOT NULL
+    );
+    CREATE TABLE games (
+        id         TEXT PRIMARY KEY,
+        state      TEXT NOT NULL,
+        updated_at BIGINT NOT NULL
+    );
+    ",
+    "ALTER TABLE cards ADD COLUMN combine_version TEXT;",
+    "
+    CREATE TABLE claims (
+        proof         TEXT PRIMARY KEY,
+        wallet        TEXT NOT NULL,
+        card_id       TEXT NOT NULL,
+        asset_address TEXT NOT NULL,
+        claimed_at    BIGINT NOT NULL
+    );
+    CREATE INDEX claims_wallet ON claims (wallet, claimed_at);
+    CREATE TABLE claim_vouchers (
+        code       TEXT PRIMARY KEY,
+        card_id    TEXT NOT NULL,
+        wallet     TEXT,
+        created_at BIGINT NOT NULL
+    );
+    ",
+    "
+    CREATE TABLE tracked_assets (
+        asset_address TEXT PRIMARY KEY,
+        card_id       TEXT NOT NULL,
+        wallet        TEXT NOT NULL,
+        status        TEXT NOT NULL,
+        signature     TEXT,
+        updated_at    BIGINT NOT NULL
+    );
+    CREATE INDEX tracked_assets_wallet ON tracked_assets (wallet);
+    ",
+    "
+    ALTER TABLE tracked_assets ADD COLUMN reward BIGINT NOT NULL DEFAULT 0;
+    CREATE TABLE essence_balances (
+        wallet  TEXT PRIMARY KEY,
+        balance BIGINT NOT NULL
+    );
+    ",
+    "
+    CREATE TABLE trades (
+        id           TEXT PRIMARY KEY,
+        proposer     TEXT NOT NULL,
+        counterparty TEXT NOT NULL,
+        offered      TEXT NOT NULL,
+        requested    TEXT NOT NULL,
+        status       TEXT NOT NULL,
+        created_at   BIGINT NOT NULL,
+        expires_at   BIGINT NOT NULL,
+        updated_at   BIGINT NOT NULL
+    );
+    CREATE INDEX trades_proposer ON trades (proposer, created_at);
+    CREATE INDEX trades_counterparty ON trades (counterparty, created_at);
+    CREATE INDEX trades_expiry ON trades (status, expires_at);
+    ",
+    "
+    ALTER TABLE tracked_assets ADD COLUMN mint_tx TEXT;
+    ALTER TABLE tracked_assets ADD COLUMN asset_seed TEXT;
+    CREATE INDEX claims_asset ON claims (asset_address);
+    ",
+    "
+    CREATE TABLE editions (
+        card_id TEXT PRIMARY KEY,
+        issued  BIGINT NOT NULL
+    );
+    ",
+    "
+    CREATE TABLE pack_purchases (
+        wallet    TEXT NOT NULL,
+        pack_id   TEXT NOT NULL,
+        bought_at BIGINT NOT NULL
+    );
+    CREATE INDEX pack_purchases_wallet ON pack_purchases (wallet, pack_id, bought_at);
+    ",
+    "
+    ALTER TABLE pack_purchases ADD COLUMN id TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN server_seed TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN slots TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN pool TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN client_seed TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN cards TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN opened_at BIGINT;
+    CREATE UNIQUE INDEX pack_purchases_id ON pack_purchases (id);
+    ",
+    "
+    CREATE TABLE owned_cards (
+        mint_address TEXT PRIMARY KEY,
+        wallet       TEXT NOT NULL,
+        card_id      TEXT NOT NULL,
+        name         TEXT NOT NULL,
+        collection   TEXT NOT NULL,
+        season       TEXT NOT NULL,
+        edition      BIGINT,
+        soulbound    BOOLEAN NOT NULL,
+        burned       BOOLEAN NOT NULL DEFAULT FALSE,
+        updated_at   BIGINT NOT NULL
+    );
+    CREATE INDEX owned_cards_wallet ON owned_cards (wallet);
+    CREATE TABLE indexed_wallets (
+        wallet        TEXT PRIMARY KEY,
+        reconciled_at BIGINT NOT NULL,
+        asked_at      BIGINT NOT NULL
+    );
+    CREATE INDEX indexed_wallets_reconciled ON indexed_wallets (reconciled_at);
+    ",
+    "
+    ALTER TABLE pack_purchases ADD COLUMN refund_essence BIGINT NOT NULL DEFAULT 0;
+    CREATE TABLE pack_mints (
+        purchase_id   TEXT NOT NULL,
+        slot          BIGINT NOT NULL,
+        wallet        TEXT NOT NULL,
+        card_id       TEXT NOT NULL,
+        soulbound     BOOLEAN NOT NULL,
+        status        TEXT NOT NULL,
+        edition       BIGINT,
+        attempts      INTEGER NOT NULL,
+        asset_address TEXT,
+        signature     TEXT,
+        error         TEXT,
+        refund        BIGINT NOT NULL DEFAULT 0,
+        retry_at      BIGINT NOT NULL,
+        updated_at    BIGINT NOT NULL,
+        PRIMARY KEY (purchase_id, slot)
+    );
+    CREATE INDEX pack_mints_due ON pack_mints (status, retry_at);
+    ",
+    "
+    ALTER TABLE pack_mints ADD COLUMN foil BOOLEAN NOT NULL DEFAULT FALSE;
+    ",
+    "
+    ALTER TABLE cards ADD COLUMN discovered_by TEXT;
+    ALTER TABLE cards ADD COLUMN previous_art TEXT;
+    ",
+    "
+    ALTER TABLE cards ADD COLUMN art_path TEXT;
+    ",
+    "
+    ALTER TABLE pack_purchases ADD COLUMN price_lamports BIGINT NOT NULL DEFAULT 0;
+    ALTER TABLE pack_purchases ADD COLUMN payment_signature TEXT;
+    CREATE UNIQUE INDEX pack_purchases_payment ON pack_purchases (payment_signature);
+    ",
+    "
+    CREATE TABLE edition_holds (
+        card_id       TEXT NOT NULL,
+        edition       BIGINT NOT NULL,
+        asset_address TEXT UNIQUE,
+        expires_at    BIGINT NOT NULL,
+        PRIMARY KEY (card_id, edition)
+    );
+    CREATE INDEX edition_holds_expiry ON edition_holds (expires_at);
+    ",
+    "
+    ALTER TABLE trades ADD COLUMN escrow_tx TEXT;
+    ",
+    "
+    ALTER TABLE tracked_assets ADD COLUMN crafted BOOLEAN NOT NULL DEFAULT FALSE;
+    ",
+];
+
+const TRADE_COLUMNS: &str = "id, proposer, counterparty, offered, requested, status, created_at, \
+     expires_at, updated_at, escrow_tx";
+
+/// Held while migrating so servers starting together don't race.
+const MIGRATION_LOCK: i64 = 0x616c_6368;
+/// Advisory lock class taken, with a hash of the wallet, while a new claim
+/// counts the wallet's claims, so two claims of one wallet take turns.
+const CLAIM_LOCK: i32 = 0x636c_6d73;
+
+const PACK_COLUMNS: &str = "id, wallet, pack_id, server_seed, slots, pool, bought_at, client_seed, \
+     cards, opened_at, refund_essence, price_lamports, payment_signature";
+
+const PACK_MINT_COLUMNS: &str = "purchase_id, slot, wallet, card_id, soulbound, status, edition, \
+     attempts, asset_address, signature, error, refund, retry_at, updated_at, foil";
+
+const CARD_COLUMNS: &str = "id, name, description, image_path, discovered, impossible, recipe, \
+     discovered_at, created_at, times_crafted, combine_version, discovered_by, previous_art, \
+     art_path";
+
+pub struct PgStore {
+    /// `None` only while being dropped.
+    pool: Option<Pool<PostgresConnectionManager<NoTls>>>,
+}
+
+impl PgStore {
+    /// Open `pool_size` connections to `url` and bring the schema up to date.
+    pub fn connect(url: &str, pool_size: u32) -> Result<Self, String> {
+        let config = url
+            .parse()
+            .map_err(|e| format!("Invalid Postgres URL: {e}"))?;
+        let manager = PostgresConnectionManager::new(config, NoTls);
+        let pool = tokio::task::block_in_place(|| {
+            Pool::builder().max_size(pool_size.max(1)).build(manager)
+        })
+        .map_err(|e| format!("Failed to connect to Postgres: {e}"))?;
+        let store = PgStore { pool: Some(pool) };
+        store.with(migrate)?;
+        Ok(store)
+    }
+
+    /// Run `f` on a pooled connection, waiting for one to come free. The
+    /// client is blocking, so this steps off the async worker. Connections
+    /// that dropped are replaced by the pool.
+    fn with<T>(
+        &self,
+        f: impl FnOnce(&mut Client) -> Result<T, postgres::Error>,
+    ) -> Result<T, String> {
+        tokio::task::block_in_place(|| {
+            let pool = self.pool.as_ref().expect("pool is open until dropped");
+            let mut client = pool.get().map_err(|e| format!("No Postgres connection: {e}"))?;
+            f(&mut client).map_err(|e| e.to_string())
+        })
+    }
+
+    fn query_cards(&self, filter: &str) -> Result<Vec<CachedCard>, String> {
+        let sql = format!("SELECT {CARD_COLUMNS} FROM cards {filter}");
+        let rows = self.with(|c| c.query(&sql, &[]))?;
+        Ok(rows.iter().map(card_from_row).collect())
+    }
+
+    fn query_claim(&self, filter: &str, value: &str) -> Result<Option<Claim>, String> {
+        let sql = format!(
+            "SELECT proof, wallet, card_id, asset_address, claimed_at FROM claims WHERE {filter}"
+        );
+        let row = self.with(|c| c.query_opt(&sql, &[&value]))?;
+        Ok(row.map(|r| Claim {
+            proof: r.get(0),
+            wallet: r.get(1),
+            card_id: r.get(2),
+            asset_address: r.get(3),
+            claimed_at: r.get::<_, i64>(4) as u64,
+        }))
+    }
+}
+
+impl Drop for PgStore {
+    /// Closing the blocking clients drives a runtime of their own, which
+    /// would panic on an async worker.
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            tokio::task::block_in_place(|| drop(pool));
+        }
+    }
+}
+
+/// Claims by `wallet` at or after `since`, optionally only of `card_id`.
+fn count_claims(
+    c: &mut impl GenericClient,
+    wallet: &str,
+    card_id: Option<&str>,
+    since: u64,
+) -> Result<u64, postgres::Error> {
+    let row = c.query_one(
+        "SELECT COUNT(*) FROM claims
+         WHERE wallet = $1 AND claimed_at >= $2
+           AND ($3::TEXT IS NULL OR card_id = $3)",
+        &[&wallet, &(since as i64), &card_id],
+    )?;
+    Ok(row.get::<_, i64>(0) as u64)
+}
+
+fn migrate(client: &mut Client) -> Result<(), postgres::Error> {
+    client.execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK])?;
+    let result = (|| {
+        client.batch_execute(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER NOT NULL)",
+        )?;
+        let version: i32 = client
+            .query_one("SELECT COALESCE(MAX(version), 0) FROM schema_migrations", &[])?
+            .get(0);
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let mut tx = client.transaction()?;
+            tx.batch_execute(migration)?;
+            tx.execute(
+                "INSERT INTO schema_migrations (version) VALUES ($1)",
+                &[&(i as i32 + 1)],
+            )?;
+            tx.commit()?;
+        }
+        Ok(())
+    })();
+    client.execute("SELECT pg_advisory_unlock($1)", &[&MIGRATION_LOCK])?;
+    result
+}
+
+impl ClaimStore for PgStore {
+    fn claim(&self, proof: &str) -> Result<Option<Claim>, String> {
+        self.query_claim("proof = $1", proof)
+    }
+
+    fn claim_of_asset(&self, address: &str) -> Result<Option<Claim>, String> {
+        self.query_claim("asset_address = $1", address)
+    }
+
+    fn record_claim(&self, claim: &Claim, replacing: Option<&str>) -> Result<bool, String> {
+        let claimed_at = claim.claimed_at as i64;
+        let written = self.with(|c| match replacing {
+            None => c.execute(
+                "INSERT INTO claims (proof, wallet, card_id, asset_address, claimed_at)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (proof) DO NOTHING",
+                &[
+                    &claim.proof,
+                    &claim.wallet,
+                    &claim.card_id,
+                    &claim.asset_address,
+                    &claimed_at,
+                ],
+            ),
+            Some(previous) => c.execute(
+                "UPDATE claims SET wallet = $2, asset_address = $3, claimed_at = $4
+                 WHERE proof = $1 AND asset_address = $5",
+                &[
+                    &claim.proof,
+                    &claim.wallet,
+                    &claim.asset_address,
+                    &claimed_at,
+                    &previous,
+                ],
+            ),
+        })?;
+        Ok(written > 0)
+    }
+
+    fn reserve_claim(
+        &self,
+        claim: &Claim,
+        limits: ClaimSettings,
+    ) -> Result<Result<bool, ClaimLimit>, String> {
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            tx.execute(
+                "SELECT pg_advisory_xact_lock($1, hashtext($2))",
+                &[&CLAIM_LOCK, &claim.wallet],
+            )?;
+            let of_card = count_claims(&mut tx, &claim.wallet, Some(&claim.card_id), 0)?;
+            let day_ago = claim.claimed_at.saturating_sub(claims::CLAIM_DAY_SECS);
+            let in_day = count_claims(&mut tx, &claim.wallet, None, day_ago)?;
+            if let Some(limit) = claims::limit_reached(limits, of_card, in_day) {
+                return Ok(Err(limit));
+            }
+            let written = tx.execute(
+                "INSERT INTO claims (proof, wallet, card_id, asset_address, claimed_at)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (proof) DO NOTHING",
+                &[
+                    &claim.proof,
+                    &claim.wallet,
+                    &claim.card_id,
+                    &claim.asset_address,
+                    &(claim.claimed_at as i64),
+                ],
+            )?;
+            tx.commit()?;
+            Ok(Ok(written > 0))
+        })
+    }
+
+    fn claim_count(
+        &self,
+        wallet: &str,
+        card_id: Option<&str>,
+        since: u64,
+    ) -> Result<u64, String> {
+        self.with(|c| count_claims(c, wallet, card_id, since))
+    }
+
+    fn put_voucher(&self, voucher: &ClaimVoucher) -> Result<(), String> {
+        self.with(|c| {
+            c.execute(
+                "INSERT INTO claim_vouchers (code, card_id, wallet, created_at)
+                 VALUES ($1, $2, $3, $4)",
+                &[
+                    &voucher.code,
+                    &voucher.card_id,
+                    &voucher.wallet,
+                    &(voucher.created_at as i64),
+                ],
+            )
+        })
+        .map(|_| ())
+    }
+
+    fn voucher(&self, code: &str) -> Result<Option<ClaimVoucher>, String> {
+        let row = self.with(|c| {
+            c.query_opt(
+                "SELECT code, card_id, wallet, created_at FROM claim_vouchers WHERE code = $1",
+                &[&code],
+            )
+        })?;
+        Ok(row.map(|r| ClaimVoucher {
+            code: r.get(0),
+            card_id: r.get(1),
+            wallet: r.get(2),
+            created_at: r.get::<_, i64>(3) as u64,
+        }))
+    }
+}
+
+impl AssetStore for PgStore {
+    fn track_assets(&self, assets: &[TrackedAsset]) -> Result<(), String> {
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            for asset in assets {
+                tx.execute(
+                    "INSERT INTO tracked_assets
+                     (asset_address, card_id, wallet, status, signature, reward, mint_tx,
+                      asset_seed, updated_at, crafted)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                     ON CONFLICT (asset_address) DO UPDATE SET
+                       card_id = EXCLUDED.card_id, wallet = EXCLUDED.wallet,
+                       status = EXCLUDED.status, signature = EXCLUDED.signature,
+                       reward = EXCLUDED.reward, mint_tx = EXCLUDED.mint_tx,
+                       asset_seed = EXCLUDED.asset_seed, updated_at = EXCLUDED.updated_at,
+                       crafted = EXCLUDED.crafted",
+                    &[
+                        &asset.asset_address,
+                        &asset.card_id,
+                        &asset.wallet,
+                        &asset.status.as_str(),
+                        &asset.signature,
+                        &(asset.reward as i64),
+                        &asset.mint_tx,
+                        &asset.asset_seed,
+                        &(asset.updated_at as i64),
+                        &asset.crafted,
+                    ],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+
+    fn tracked_asset(&self, address: &str) -> Result<Option<TrackedAsset>, String> {
+        let row = self.with(|c| {
+            c.query_opt(
+                "SELECT asset_address, card_id, wallet, status, signature, reward, mint_tx,
+                        asset_seed, updated_at, crafted
+                 FROM tracked_assets WHERE asset_address = $1",
+                &[&address],
+            )
+        })?;
+        Ok(row.map(|r| TrackedAsset {
+            asset_address: r.get(0),
+            card_id: r.get(1),
+            wallet: r.get(2),
+            status: AssetStatus::parse(r.get(3)).unwrap_or(AssetStatus::Pending),
+            signature: r.get(4),
+            reward: r.get::<_, i64>(5) as u64,
+            mint_tx: r.get(6),
+            asset_seed: r.get(7),
+            updated_at: r.get::<_, i64>(8) as u64,
+            crafted: r.get(9),
+        }))
+    }
+
+    fn settle_asset(
+        &self,
+        address: &str,
+        from: AssetStatus,
+        to: AssetStatus,
+        signature: &str,
+        at: u64,
+    ) -> Result<bool, String> {
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            let updated = tx.execute(
+                "UPDATE tracked_assets SET status = $3, signature = $4, updated_at = $5
+                 WHERE asset_address = $1 AND status = $2",
+                &[&address, &from.as_str(), &to.as_str(), &signature, &(at as i64)],
+            )?;
+            if updated > 0 && to == AssetStatus::Burned {
+                tx.execute(
+                    "INSERT INTO essence_balances (wallet, balance)
+                     SELECT wallet, reward FROM tracked_assets
+                     WHERE asset_address = $1 AND reward > 0
+                     ON CONFLICT (wallet) DO UPDATE
+                       SET balance = essence_balances.balance + EXCLUDED.balance",
+                    &[&address],
+                )?;
+            }
+            if updated > 0 && to == AssetStatus::Confirmed {
+                tx.execute("DELETE FROM edition_holds WHERE asset_address = $1", &[&address])?;
+            }
+            if updated > 0 && from == AssetStatus::Pending && to == AssetStatus::Confirmed {
+                tx.execute(
+                    "INSERT INTO mint_counts (card_id, count)
+                     SELECT card_id, 1 FROM tracked_assets WHERE asset_address = $1
+                     ON CONFLICT (card_id) DO UPDATE SET count = mint_counts.count + 1",
+                    &[&address],
+                )?;
+            }
+            tx.commit()?;
+            Ok(updated > 0)
+        })
+    }
+
+    fn hold_edition(
+        &self,
+        card_id: &str,
+        cap: Option<u64>,
+        asset: &str,
+        expires_at: u64,
+    ) -> Result<Option<u64>, String> {
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            let Some(edition) = take_edition(&mut tx, card_id, cap)? else {
+                return Ok(None);
+            };
+            tx.execute(
+                "INSERT INTO edition_holds (card_id, edition, asset_address, expires_at)
+                 VALUES ($1, $2, $3, $4)",
+                &[&card_id, &(edition as i64), &asset, &(expires_at as i64)],
+            )?;
+            tx.commit()?;
+            Ok(Some(edition))
+        })
+    }
+
+    fn expired_edition_holds(&self, now: u64, limit: usize) -> Result<Vec<String>, String> {
+        let rows = self.with(|c| {
+            c.query(
+                "SELECT asset_address FROM edition_holds
+                 WHERE asset_address IS NOT NULL AND expires_at <= $1
+                 ORDER BY expires_at LIMIT $2",
+                &[&(now as i64), &(limit as i64)],
+            )
+        })?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
+    }
+
+    fn end_edition_hold(&self, asset: &str, minted: bool) -> Result<(), String> {
+        let sql = if minted {
+            "DELETE FROM edition_holds WHERE asset_address = $1"
+        } else {
+            "UPDATE edition_holds SET asset_address = NULL WHERE asset_address = $1"
+        };
+        self.with(|c| c.execute(sql, &[&asset])).map(|_| ())
+    }
+
+    fn essence(&self, wallet: &str) -> Result<u64, String> {
+        let row = self.with(|c| {
+            c.query_opt(
+                "SELECT balance FROM essence_balances WHERE wallet = $1",
+                &[&wallet],
+            )
+        })?;
+        Ok(row.map_or(0, |r| r.get::<_, i64>(0) as u64))
+    }
+
+    fn record_pack_purchase(&self, purchase: &PackPurchase, essence: u64) -> Result<bool, String> {
+        let slots = serde_json::to_string(&purchase.slots).map_err(|e| e.to_string())?;
+        let pool = serde_json::to_string(&purchase.pool).map_err(|e| e.to_string())?;
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            if essence > 0 {
+                let paid = tx.execute(
+                    "UPDATE essence_balances SET balance = balance - $2
+                     WHERE wallet = $1 AND balance >= $2",
+                    &[&purchase.wallet, &(essence as i64)],
+                )? > 0;
+                if !paid {
+                    return Ok(false);
+                }
+            }
+            tx.execute(
+                "INSERT INTO pack_purchases
+                     (id, wallet, pack_id, server_seed, slots, pool, bought_at, refund_essence,
+                      price_lamports)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                &[
+                    &purchase.id,
+                    &purchase.wallet,
+                    &purchase.pack_id,
+                    &purchase.server_seed,
+                    &slots,
+                    &pool,
+                    &(purchase.bought_at as i64),
+                    &(purchase.refund_essence as i64),
+                    &(purchase.price_lamports as i64),
+                ],
+            )?;
+            tx.commit()?;
+            Ok(true)
+        })
+    }
+
+    fn pack_purchase(&self, id: &str) -> Result<Option<PackPurchase>, String> {
+        let row = self.with(|c| {
+            c.query_opt(&format!("SELECT {PACK_COLUMNS} FROM pack_purchases WHERE id = $1"), &[&id])
+        })?;
+        Ok(row.as_ref().map(purchase_from_row))
+    }
+
+    fn pack_payment_used(&self, signature: &str) -> Result<bool, String> {
+        let row = self.with(|c| {
+            c.query_opt("SELECT 1 FROM pack_purchases WHERE payment_signature = $1", &[&signature])
+        })?;
+        Ok(row.is_some())
+    }
+
+    fn open_pack(
+        &self,
+        id: &str,
+        client_seed: &str,
+        payment_signature: Option<&str>,
+        mints: &[PackMint],
+        at: u64,
+    ) -> Result<bool, String> {
+        let cards: Vec<&str> = mints.iter().map(|m| m.card_id.as_str()).collect();
+        let cards = serde_json::to_string(&cards).map_err(|e| e.to_string())?;
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            let opened = tx.execute(
+                "UPDATE pack_purchases
+                 SET client_seed = $2, cards = $3, opened_at = $4, payment_signature = $5
+                 WHERE id = $1 AND opened_at IS NULL",
+                &[&id, &client_seed, &cards, &(at as i64), &payment_signature],
+            )? > 0;
+            if opened {
+                for mint in mints {
+                    tx.execute(
+                        &format!(
+                            "INSERT INTO pack_mints ({PACK_MINT_COLUMNS})
+                             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14,
+                                     $15)"
+                        ),
+                        &[
+                            &mint.purchase_id,
+                            &(mint.slot as i64),
+                            &mint.wallet,
+                            &mint.card_id,
+                            &mint.soulbound,
+                            &mint.status.as_str(),
+                            &mint.edition.map(|e| e as i64),
+                            &(mint.attempts as i32),
+                            &mint.asset_address,
+                            &mint.signature,
+                            &mint.error,
+                            &(mint.refund as i64),
+                            &(mint.retry_at as i64),
+                            &(mint.updated_at as i64),
+                            &mint.foil,
+                        ],
+                    )?;
+                }
+            }
+            tx.commit()?;
+            Ok(opened)
+        })
+    }
+
+    fn pack_purchases(&self, wallet: &str, pack_id: &str, since: u64) -> Result<u64, String> {
+        let row = self.with(|c| {
+            c.query_one(
+                "SELECT COUNT(*) FROM pack_purchases
+                 WHERE wallet = $1 AND pack_id = $2 AND bought_at >= $3",
+                &[&wallet, &pack_id, &(since as i64)],
+            )
+        })?;
+        Ok(row.get::<_, i64>(0) as u64)
+    }
+
+    fn pack_mints(&self, purchase_id: &str) -> Result<Vec<PackMint>, String> {
+        let rows = self.with(|c| {
+            c.query(
+                &format!(
+                    "SELECT {PACK_MINT_COLUMNS} FROM pack_mints WHERE purchase_id = $1
+                     ORDER BY slot"
+                ),
+                &[&purchase_id],
+            )
+        })?;
+        Ok(rows.iter().map(pack_mint_from_row).collect())
+    }
+
+    fn update_pack_mint(&self, mint: &PackMint) -> Result<(), String> {
+        self.with(|c| {
+            c.execute(
+                "UPDATE pack_mints SET status = $3, edition = $4, attempts = $5,
+                     asset_address = $6, signature = $7, error = $8, retry_at = $9,
+                     updated_at = $10
+                 WHERE purchase_id = $1 AND slot = $2 AND status IN ('pending', 'failed')",
+                &[
+                    &mint.purchase_id,
+                    &(mint.slot as i64),
+                    &mint.status.as_str(),
+                    &mint.edition.map(|e| e as i64),
+                    &(mint.attempts as i32),
+                    &mint.asset_address,
+                    &mint.signature,
+                    &mint.error,
+                    &(mint.retry_at as i64),
+                    &(mint.updated_at as i64),
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn refund_pack_mint(&self, mint: &PackMint) -> Result<Option<u64>, String> {
+        let refund = self.with(|c| {
+            let mut tx = c.transaction()?;
+            let refund: Option<i64> = tx
+                .query_opt(
+                    "UPDATE pack_mints SET status = 'refunded', edition = $3, attempts = $4,
+                         error = $5, updated_at = $6,
+                         refund = (SELECT refund_essence FROM pack_purchases WHERE id = $1)
+                     WHERE purchase_id = $1 AND slot = $2 AND status IN ('pending', 'failed')
+                     RETURNING refund",
+                    &[
+                        &mint.purchase_id,
+                        &(mint.slot as i64),
+                        &mint.edition.map(|e| e as i64),
+                        &(mint.attempts as i32),
+                        &mint.error,
+                        &(mint.updated_at as i64),
+                    ],
+                )?
+                .map(|r| r.get(0));
+            if let Some(amount) = refund.filter(|&a| a > 0) {
+                tx.execute(
+                    "INSERT INTO essence_balances (wallet, balance) VALUES ($1, $2)
+                     ON CONFLICT (wallet) DO UPDATE
+                       SET balance = essence_balances.balance + EXCLUDED.balance",
+                    &[&mint.wallet, &amount],
+                )?;
+            }
+            tx.commit()?;
+            Ok(refund)
+        })?;
+        Ok(refund.map(|a| a as u64))
+    }
+
+    fn due_pack_mints(&self, now: u64, until: u64, limit: usize) -> Result<Vec<PackMint>, String> {
+        let rows = self.with(|c| {
+            c.query(
+                &format!(
+                    "UPDATE pack_mints SET retry_at = $2
+                     WHERE (purchase_id, slot) IN (
+                         SELECT purchase_id, slot FROM pack_mints
+                         WHERE status IN ('pending', 'failed') AND retry_at <= $1
+                         ORDER BY retry_at LIMIT $3
+                         FOR UPDATE SKIP LOCKED)
+                     RETURNING {PACK_MINT_COLUMNS}"
+                ),
+                &[&(now as i64), &(until as i64), &(limit as i64)],
+            )
+        })?;
+        Ok(rows.iter().map(pack_mint_from_row).collect())
+    }
+}
+
+impl TradeStore for PgStore {
+    fn put_trade(&self, trade: &Trade) -> Result<(), String> {
+        let offered = serde_json::to_string(&trade.offered).map_err(|e| e.to_string())?;
+        let requested = serde_json::to_string(&trade.requested).map_err(|e| e.to_string())?;
+        self.with(|c| {
+            c.execute(
+                &format!(
+                    "INSERT INTO trades ({TRADE_COLUMNS})
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
+                ),
+                &[
+                    &trade.id,
+                    &trade.proposer,
+                    &trade.counterparty,
+                    &offered,
+                    &requested,
+                    &trade.status.as_str(),
+                    &(trade.created_at as i64),
+                    &(trade.expires_at as i64),
+                    &(trade.updated_at as i64),
+                    &trade.escrow_tx,
+                ],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn trade(&self, id: &str) -> Result<Option<Trade>, String> {
+        let row = self.with(|c| {
+            c.query_opt(&format!("SELECT {TRADE_COLUMNS} FROM trades WHERE id = $1"), &[&id])
+        })?;
+        Ok(row.as_ref().map(trade_from_row))
+    }
+
+    fn trades_for(&self, wallet: &str, limit: usize) -> Result<Vec<Trade>, String> {
+        let rows = self.with(|c| {
+            c.query(
+                &format!(
+                    "SELECT {TRADE_COLUMNS} FROM trades WHERE proposer = $1 OR counterparty = $1
+                     ORDER BY created_at DESC LIMIT $2"
+                ),
+                &[&wallet, &(limit as i64)],
+            )
+        })?;
+        Ok(rows.iter().map(trade_from_row).collect())
+    }
+
+    fn live_trades(&self, wallet: &str) -> Result<Vec<Trade>, String> {
+        let rows = self.with(|c| {
+            c.query(
+                &format!(
+                    "SELECT {TRADE_COLUMNS} FROM trades
+                     WHERE proposer = $1 AND status IN ('open', 'accepted')"
+                ),
+                &[&wallet],
+            )
+        })?;
+        Ok(rows.iter().map(trade_from_row).collect())
+    }
+
+    fn expired_trades(&self, now: u64) -> Result<Vec<Trade>, String> {
+        let rows = self.with(|c| {
+            c.query(
+                &format!(
+                    "SELECT {TRADE_COLUMNS} FROM trades
+                     WHERE status IN ('open', 'accepted') AND expires_at <= $1"
+                ),
+                &[&(now as i64)],
+            )
+        })?;
+        Ok(rows.iter().map(trade_from_row).collect())
+    }
+
+    fn set_trade_status(
+        &self,
+        id: &str,
+        from: TradeStatus,
+        to: TradeStatus,
+        at: u64,
+    ) -> Result<bool, String> {
+        let updated = self.with(|c| {
+            c.execute(
+                "UPDATE trades SET status = $3, updated_at = $4 WHERE id = $1 AND status = $2",
+                &[&id, &from.as_str(), &to.as_str(), &(at as i64)],
+            )
+        })?;
+        Ok(updated > 0)
+    }
+
+    fn set_trade_escrow(&self, id: &str, signature: &str) -> Result<(), String> {
+        self.with(|c| {
+            c.execute(
+                "UPDATE trades SET escrow_tx = $2 WHERE id = $1 AND escrow_tx IS NULL",
+                &[&id, &signature],
+            )
+        })?;
+        Ok(())
+    }
+}
+
+/// The lowest edition of `card_id` a mint that never landed gave back, else
+/// the next one unless `cap` of them have been issued.
+fn take_edition(
+    client: &mut impl postgres::GenericClient,
+    card_id: &str,
+    cap: Option<u64>,
+) -> Result<Option<u64>, postgres::Error> {
+    let released = client.query_opt(
+        "DELETE FROM edition_holds WHERE card_id = $1 AND edition = (
+           SELECT edition FROM edition_holds
+           WHERE card_id = $1 AND asset_address IS NULL
+           ORDER BY edition LIMIT 1 FOR UPDATE SKIP LOCKED)
+         RETURNING edition",
+        &[&card_id],
+    )?;
+    if let Some(row) = released {
+        return Ok(Some(row.get::<_, i64>(0) as u64));
+    }
+    let cap = cap.map(|n| n as i64);
+    let issued = client.query_opt(
+        "INSERT INTO editions (card_id, issued) VALUES ($1, 1)
+         ON CONFLICT (card_id) DO UPDATE SET issued = editions.issued + 1
+         WHERE $2::BIGINT IS NULL OR editions.issued < $2
+         RETURNING issued",
+        &[&card_id, &cap],
+    )?;
+    Ok(issued.map(|r| r.get::<_, i64>(0) as u64))
+}
+
+/// Insert or update an indexed card unless it was burned, or (with `newer`)
+/// changed after `at`.
+fn upsert_owned(
+    client: &mut impl postgres::GenericClient,
+    wallet: &str,
+    card: &OwnedCard,
+    at: u64,
+    newer: bool,
+) -> Result<u64, postgres::Error> {
+    let guard = if newer { "AND owned_cards.updated_at < excluded.updated_at" } else { "" };
+    client.execute(
+        &format!(
+            "INSERT INTO owned_cards
+             (mint_address, wallet, card_id, name, collection, season, edition, soulbound,
+              updated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             ON CONFLICT (mint_address) DO UPDATE SET
+                wallet = excluded.wallet, card_id = excluded.card_id, name = excluded.name,
+                collection = excluded.collection, season = excluded.season,
+                edition = COALESCE(excluded.edition, owned_cards.edition),
+                soulbound = excluded.soulbound, updated_at = excluded.updated_at
+             WHERE NOT owned_cards.burned {guard}"
+        ),
+        &[
+            &card.mint_address,
+            &wallet,
+            &card.card_id,
+            &card.name,
+            &card.collection,
+            &card.season,
+            &card.edition.map(|e| e as i64),
+            &card.soulbound,
+            &(at as i64),
+        ],
+    )
+}
+
+impl OwnershipStore for PgStore {
+    fn owned_cards(&self, wallet: &str, at: u64) -> Result<Option<(u64, Vec<OwnedCard>)>, String> {
+        let reconciled_at = self.with(|c| {
+            c.query_opt(
+                "UPDATE indexed_wallets SET asked_at = $2 WHERE wallet = $1
+                 RETURNING reconciled_at",
+                &[&wallet, &(at as i64)],
+            )
+        })?;
+        let Some(reconciled_at) = reconciled_at.map(|r| r.get::<_, i64>(0) as u64) else {
+            return Ok(None);
+        };
+        let rows = self.with(|c| {
+            c.query(
+                "SELECT mint_address, card_id, name, collection, season, edition, soulbound
+                 FROM owned_cards WHERE wallet = $1 AND NOT burned",
+                &[&wallet],
+            )
+        })?;
+        let cards = rows
+            .iter()
+            .map(|r| OwnedCard {
+                mint_address: r.get(0),
+                card_id: r.get(1),
+                name: r.get(2),
+                image: String::new(),
+                metadata_uri: String::new(),
+                collection: r.get(3),
+                season: r.get(4),
+                edition: r.get::<_, Option<i64>>(5).map(|e| e as u64),
+                soulbound: r.get(6),
+            })
+            .collect();
+        Ok(Some((reconciled_at, cards)))
+    }
+
+    fn reconcile_wallet(&self, wallet: &str, cards: &[OwnedCard], at: u64) -> Result<(), String> {
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            for card in cards {
+                upsert_owned(&mut tx, wallet, card, at, true)?;
+            }
+            // Whatever DAS didn't list and nothing touched since has moved on
+            tx.execute(
+                "DELETE FROM owned_cards WHERE wallet = $1 AND NOT burned AND updated_at < $2",
+                &[&wallet, &(at as i64)],
+            )?;
+            tx.execute(
+                "INSERT INTO indexed_wallets (wallet, reconciled_at, asked_at)
+                 VALUES ($1, $2, $2)
+                 ON CONFLICT (wallet) DO UPDATE SET reconciled_at = excluded.reconciled_at",
+                &[&wallet, &(at as i64)],
+            )?;
+            tx.commit()
+        })
+    }
+
+    fn index_mint(&self, wallet: &str, card: &OwnedCard, at: u64) -> Result<(), String> {
+        self.with(|c| upsert_owned(c, wallet, card, at, false))?;
+        Ok(())
+    }
+
+    fn index_burn(&self, wallet: &str, card: &OwnedCard, at: u64) -> Result<(), String> {
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            upsert_owned(&mut tx, wallet, card, at, false)?;
+            tx.execute(
+                "UPDATE owned_cards SET burned = TRUE WHERE mint_address = $1",
+                &[&card.mint_address],
+            )?;
+            tx.commit()
+        })
+    }
+
+    fn forget_holder(&self, mint: &str) -> Result<(), String> {
+        self.with(|c| {
+            c.execute(
+                "UPDATE indexed_wallets SET reconciled_at = 0
+                 WHERE wallet = (SELECT wallet FROM owned_cards WHERE mint_address = $1)",
+                &[&mint],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn stale_wallets(
+        &self,
+        before: u64,
+        active_since: u64,
+        limit: usize,
+    ) -> Result<Vec<String>, String> {
+        let rows = self.with(|c| {
+            c.query(
+                "SELECT wallet FROM indexed_wallets WHERE reconciled_at < $1 AND asked_at >= $2
+                 ORDER BY reconciled_at LIMIT $3",
+                &[&(before as i64), &(active_since as i64), &(limit as i64)],
+            )
+        })?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
+    }
+}
+
+fn trade_from_row(row: &Row) -> Trade {
+    let offered: String = row.get(3);
+    let requested: String = row.get(4);
+    Trade {
+        id: row.get(0),
+        proposer: row.get(1),
+        counterparty: row.get(2),
+        offered: serde_json::from_str(&offered).unwrap_or_default(),
+        requested: serde_json::from_str(&requested).unwrap_or_default(),
+        status: TradeStatus::parse(row.get(5)).unwrap_or(TradeStatus::Cancelled),
+        created_at: row.get::<_, i64>(6) as u64,
+        expires_at: row.get::<_, i64>(7) as u64,
+        updated_at: row.get::<_, i64>(8) as u64,
+        escrow_tx: row.get(9),
+    }
+}
+
+fn purchase_from_row(row: &Row) -> PackPurchase {
+    let slots: String = row.get(4);
+    let pool: String = row.get(5);
+    let cards: Option<String> = row.get(8);
+    PackPurchase {
+        id: row.get(0),
+        wallet: row.get(1),
+        pack_id: row.get(2),
+        server_seed: row.get(3),
+        slots: serde_json::from_str(&slots).unwrap_or_default(),
+        pool: serde_json::from_str(&pool).unwrap_or_default(),
+        bought_at: row.get::<_, i64>(6) as u64,
+        client_seed: row.get(7),
+        cards: cards.and_then(|c| serde_json::from_str(&c).ok()).unwrap_or_default(),
+        opened_at: row.get::<_, Option<i64>>(9).map(|t| t as u64),
+        refund_essence: row.get::<_, i64>(10) as u64,
+        price_lamports: row.get::<_, i64>(11) as u64,
+        payment_signature: row.get(12),
+    }
+}
+
+fn pack_mint_from_row(row: &Row) -> PackMint {
+    PackMint {
+        purchase_id: row.get(0),
+        slot: row.get::<_, i64>(1) as usize,
+        wallet: row.get(2),
+        card_id: row.get(3),
+        soulbound: row.get(4),
+        foil: row.get(14),
+        status: MintStatus::parse(row.get(5)).unwrap_or(MintStatus::Failed),
+        edition: row.get::<_, Option<i64>>(6).map(|e| e as u64),
+        attempts: row.get::<_, i32>(7) as u32,
+        asset_address: row.get(8),
+        signature: row.get(9),
+        error: row.get(10),
+        refund: row.get::<_, i64>(11) as u64,
+        retry_at: row.get::<_, i64>(12) as u64,
+        updated_at: row.get::<_, i64>(13) as u64,
+    }
+}
+
+fn card_from_row(row: &Row) -> CachedCard {
+    let recipe: Option<String> = row.get(6);
+    CachedCard {
+        id: row.get(0),
+        name: row.get(1),
+        description: row.get(2),
+        image_path: row.get(3),
+        discovered: row.get(4),
+        impossible: row.get(5),
+        recipe: recipe.and_then(|r| serde_json::from_str(&r).ok()),
+        discovered_at: row.get::<_, Option<i64>>(7).map(|t| t as u64),
+        created_at: row.get::<_, Option<i64>>(8).map(|t| t as u64),
+        times_crafted: row.get::<_, i64>(9) as u64,
+        combine_version: row.get(10),
+        discovered_by: row.get(11),
+        previous_art: row
+            .get::<_, Option<String>>(12)
+            .and_then(|art| serde_json::from_str(&art).ok())
+            .unwrap_or_default(),
+        art_path: row.get(13),
+    }
+}
+
+/// Returns the number of rows written (0 when `KeepExisting` skipped it).
+fn write_card(
+    client: &mut impl postgres::GenericClient,
+    card: &CachedCard,
+    strategy: ConflictStrategy,
+) -> Result<u64, postgres::Error> {
+    let on_conflict = match strategy {
+        ConflictStrategy::KeepExisting => "DO NOTHING",
+        ConflictStrategy::Overwrite => {
+            "DO UPDATE SET name = excluded.name, description = excluded.description,
+                 image_path = excluded.image_path, discovered = excluded.discovered,
+                 impossible = excluded.impossible, recipe = excluded.recipe,
+                 discovered_at = excluded.discovered_at, created_at = excluded.created_at,
+                 times_crafted = excluded.times_crafted,
+                 combine_version = excluded.combine_version,
+                 discovered_by = excluded.discovered_by, previous_art = excluded.previous_art,
+                 art_path = excluded.art_path"
+        }
+    };
+    let recipe = card
+        .recipe
+        .as_ref()
+        .and_then(|r| serde_json::to_string(r).ok());
+    let previous_art = (!card.previous_art.is_empty())
+        .then(|| serde_json::to_string(&card.previous_art).ok())
+        .flatten();
+    client.execute(
+        &format!(
+            "INSERT INTO cards ({CARD_COLUMNS})
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+             ON CONFLICT (id) {on_conflict}"
+        ),
+        &[
+            &card.id,
+            &card.name,
+            &card.description,
+            &card.image_path,
+            &card.discovered,
+            &card.impossible,
+            &recipe,
+            &card.discovered_at.map(|t| t as i64),
+            &card.created_at.map(|t| t as i64),
+            &(card.times_crafted as i64),
+            &card.combine_version,
+            &card.discovered_by,
+            &previous_art,
+            &card.art_path,
+        ],
+    )
+}
+
+impl CardStore for PgStore {
+    fn get(&self, key: &str) -> Result<Option<CachedCard>, String> {
+        let row = self.with(|c| {
+            c.query_opt(&format!("SELECT {CARD_COLUMNS} FROM cards WHERE id = $1"), &[&key])
+        })?;
+        Ok(row.as_ref().map(card_from_row))
+    }
+
+    fn put(&self, card: &CachedCard) -> Result<(), String> {
+        self.with(|c| write_card(c, card, ConflictStrategy::Overwrite))
+            .map(|_| ())
+    }
+
+    fn remove(&self, key: &str) -> Result<(), String> {
+        self.with(|c| c.execute("DELETE FROM cards WHERE id = $1", &[&key]))
+            .map(|_| ())
+    }
+
+    fn all(&self) -> Result<Vec<CachedCard>, String> {
+        self.query_cards("")
+    }
+
+    fn discovered(&self) -> Result<Vec<CachedCard>, String> {
+        self.query_cards("WHERE discovered AND NOT impossible AND image_path != ''")
+    }
+
+    fn list(&self, query: &CardQuery) -> Result<(Vec<CachedCard>, u64), String> {
+        let discovered = if query.discovered { "AND discovered" } else { "" };
+        let filter = format!(
+            "WHERE NOT impossible AND ($1::TEXT IS NULL OR lower(name) LIKE lower($1) ESCAPE '\\')
+             {discovered}"
+        );
+        let order = if query.descending { "DESC" } else { "ASC" };
+        let pattern = query.name_pattern();
+        let (limit, offset) = (query.limit as i64, query.offset as i64);
+        let (total, rows) = self.with(|c| {
+            let total = c.query_one(&format!("SELECT COUNT(*) FROM cards {filter}"), &[&pattern])?;
+            let rows = c.query(
+                &format!(
+                    "SELECT {CARD_COLUMNS} FROM cards {filter}
+                     ORDER BY lower(name) {order}, id {order} LIMIT $2 OFFSET $3"
+                ),
+                &[&pattern, &limit, &offset],
+            )?;
+            Ok((total.get::<_, i64>(0), rows))
+        })?;
+        Ok((rows.iter().map(card_from_row).collect(), total as u64))
+    }
+
+    fn names(&self) -> Result<Vec<(String, String)>, String> {
+        let rows = self.with(|c| c.query("SELECT id, name FROM cards WHERE NOT impossible", &[]))?;
+        Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+    }
+
+    fn merge(
+        &self,
+        cards: Vec<CachedCard>,
+        strategy: ConflictStrategy,
+    ) -> Result<MergeSummary, String> {
+        self.with(|c| {
+            let mut summary = MergeSummary {
+                imported: 0,
+                skipped: 0,
+            };
+            let mut tx = c.transaction()?;
+            for card in &cards {
+                match write_card(&mut tx, card, strategy)? {
+                    0 => summary.skipped += 1,
+                    _ => summary.imported += 1,
+                }
+            }
+            tx.commit()?;
+            Ok(summary)
+        })
+    }
+
+    fn counts(&self) -> Result<CardCounts, String> {
+        let now = now_secs() as i64;
+        let row = self.with(|c| {
+            c.query_one(
+                "SELECT COUNT(*),
+                        COUNT(*) FILTER (WHERE discovered AND NOT impossible),
+                        COUNT(*) FILTER (WHERE impossible),
+                        COUNT(*) FILTER (WHERE created_at >= $1),
+                        COUNT(*) FILTER (WHERE created_at >= $2)
+                 FROM cards",
+                &[&(now - 3600), &(now - 86_400)],
+            )
+        })?;
+        let n = |i| row.get::<_, i64>(i) as u64;
+        Ok(CardCounts::new(n(0), n(1), n(2), n(3), n(4)))
+    }
+
+    fn version_counts(&self) -> Result<HashMap<Option<String>, u64>, String> {
+        let rows = self.with(|c| {
+            c.query("SELECT combine_version, COUNT(*) FROM cards GROUP BY combine_version", &[])
+        })?;
+        Ok(rows
+            .iter()
+            .map(|r| (r.get(0), r.get::<_, i64>(1) as u64))
+            .collect())
+    }
+
+    fn remove_outdated(&self, version: &str) -> Result<Vec<String>, String> {
+        let rows = self.with(|c| {
+            c.query(
+                "DELETE FROM cards
+                 WHERE NOT discovered AND combine_version IS DISTINCT FROM $1
+                 RETURNING id",
+                &[&version],
+            )
+        })?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
+    }
+
+    fn prune_impossible(&self, max: usize) -> Result<Vec<String>, String> {
+        let rows = self.with(|c| {
+            c.query(
+                "DELETE FROM cards WHERE id IN (
+                     SELECT id FROM cards WHERE impossible
+                     ORDER BY created_at NULLS FIRST
+                     LIMIT GREATEST(0, (SELECT COUNT(*) FROM cards WHERE impossible) - $1)
+                 ) RETURNING id",
+                &[&(max as i64)],
+            )
+        })?;
+        Ok(rows.iter().map(|r| r.get(0)).collect())
+    }
+
+    fn record_craft(&self, card_id: &str) -> Result<(), String> {
+        self.with(|c| {
+            c.execute(
+                "UPDATE cards SET times_crafted = times_crafted + 1 WHERE id = $1",
+                &[&card_id],
+            )
+        })
+        .map(|_| ())
+    }
+
+    fn record_mint(&self, card_id: &str) -> Result<(), String> {
+        self.with(|c| {
+            c.execute(
+                "INSERT INTO mint_counts (card_id, count) VALUES ($1, 1)
+                 ON CONFLICT (card_id) DO UPDATE SET count = mint_counts.count + 1",
+                &[&card_id],
+            )
+        })
+        .map(|_| ())
+    }
+
+    fn add_mint_counts(&self, counts: &HashMap<String, u64>) -> Result<(), String> {
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            for (card_id, count) in counts {
+                tx.execute(
+                    "INSERT INTO mint_counts (card_id, count) VALUES ($1, $2)
+                     ON CONFLICT (card_id)
+                     DO UPDATE SET count = mint_counts.count + excluded.count",
+                    &[card_id, &(*count as i64)],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+
+    fn mint_count(&self, card_id: &str) -> Result<u64, String> {
+        let row = self.with(|c| {
+            c.query_opt("SELECT count FROM mint_counts WHERE card_id = $1", &[&card_id])
+        })?;
+        Ok(row.map_or(0, |r| r.get::<_, i64>(0) as u64))
+    }
+
+    fn mint_counts(&self) -> Result<HashMap<String, u64>, String> {
+        let rows = self.with(|c| c.query("SELECT card_id, count FROM mint_counts", &[]))?;
+        Ok(rows
+            .iter()
+            .map(|r| (r.get(0), r.get::<_, i64>(1) as u64))
+            .collect())
+    }
+
+    fn issue_edition(&self, card_id: &str, cap: Option<u64>) -> Result<Option<u64>, String> {
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            let edition = take_edition(&mut tx, card_id, cap)?;
+            tx.commit()?;
+            Ok(edition)
+        })
+    }
+
+    fn editions(&self, card_id: &str) -> Result<u64, String> {
+        let row = self.with(|c| {
+            c.query_opt(
+                "SELECT issued - (SELECT COUNT(*) FROM edition_holds
+                                  WHERE card_id = $1 AND asset_address IS NULL)
+                 FROM editions WHERE card_id = $1",
+                &[&card_id],
+            )
+        })?;
+        Ok(row.map_or(0, |r| r.get::<_, i64>(0) as u64))
+    }
+
+    fn ping(&self) -> Result<(), String> {
+        self.with(|c| c.batch_execute("SELECT 1"))
+            .map_err(|e| format!("card cache unavailable: {e}"))
+    }
+}
+
+impl GameStore for PgStore {
+    fn load(&self) -> Result<HashMap<String, GameState>, String> {
+        let rows = self.with(|c| c.query("SELECT id, state FROM games", &[]))?;
+        let mut games = HashMap::new();
+        for row in rows {
+            let id: String = row.get(0);
+            match serde_json::from_str(row.get(1)) {
+                Ok(game) => {
+                    games.insert(id, game);
+                }
+                Err(e) => log::warn!("Skipping unreadable saved game {id}: {e}"),
+            }
+        }
+        Ok(games)
+    }
+
+    fn save(&self, games: &HashMap<String, GameState>, changed: &[String]) -> Result<(), String> {
+        let now = now_secs() as i64;
+        let rows: Vec<(&String, String)> = changed
+            .iter()
+            .filter_map(|id| {
+                let game = games.get(id)?;
+                Some((id, serde_json::to_string(game).ok()?))
+            })
+            .collect();
+        self.with(|c| {
+            let mut tx = c.transaction()?;
+            for (id, state) in &rows {
+                tx.execute(
+                    "INSERT INTO games (id, state, updated_at) VALUES ($1, $2, $3)
+                     ON CONFLICT (id) DO UPDATE
+                     SET state = excluded.state, updated_at = excluded.updated_at",
+                    &[id, state, &now],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+}
@@ -0,0 +1,214 @@
+//! Print-and-play sheets: cards laid out on A4 or Letter pages at poker size
+//! (63×88 mm), edge to edge, with cut lines running out to the page edges.
+//! Cards go in as JPEGs at their rendered size, which is 300 DPI at that
+//! size.
+
+use crate::crafted_art;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::Json;
+use image::codecs::jpeg::JpegEncoder;
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Most cards in one PDF: a 60-card deck with room to spare.
+const MAX_PRINT_CARDS: usize = 90;
+
+/// Points per millimetre.
+const PT_PER_MM: f32 = 72.0 / 25.4;
+const CARD_W_MM: f32 = 63.0;
+const CARD_H_MM: f32 = 88.0;
+/// Smallest margin left around the grid, for printers that can't print to
+/// the edge.
+const MIN_MARGIN_MM: f32 = 5.0;
+const CUT_LINE_PT: f32 = 0.3;
+const CUT_LINE_GRAY: f32 = 0.6;
+const JPEG_QUALITY: u8 = 90;
+
+#[derive(Deserialize, Default, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Paper {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl Paper {
+    /// Width and height in points.
+    fn size(self) -> (f32, f32) {
+        match self {
+            Paper::A4 => (210.0 * PT_PER_MM, 297.0 * PT_PER_MM),
+            Paper::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PrintRequest {
+    /// Cards to print, base or crafted, in order; repeat an id for copies.
+    pub card_ids: Vec<String>,
+    #[serde(default)]
+    pub paper: Paper,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/cards/print",
+    tag = "cards",
+    request_body = PrintRequest,
+    responses(
+        (status = 200, description = "Print-and-play PDF, nine cards to a page", content_type = "application/pdf"),
+        (status = 400, description = "No cards, or more than 90", body = ApiError),
+        (status = 404, description = "A card doesn't exist or has no image yet", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 500, description = "PDF rendering failed", body = ApiError),
+    )
+)]
+pub async fn print_cards(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<PrintRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if req.card_ids.is_empty() || req.card_ids.len() > MAX_PRINT_CARDS {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("Print between 1 and {MAX_PRINT_CARDS} cards"),
+        ));
+    }
+
+    // Each card's image on disk
+    let mut images = Vec::with_capacity(req.card_ids.len());
+    {
+        let base_cards = state.base_cards.read().await;
+        let cache = state.card_cache.read().await;
+        for id in &req.card_ids {
+            let image_path = match base_cards.iter().find(|b| b.id == *id) {
+                Some(base) => base.image_path.clone(),
+                None => cache
+                    .get(id)
+                    .filter(|c| !c.impossible)
+                    .map(|c| c.image_path)
+                    .unwrap_or_default(),
+            };
+            let disk_path = crafted_art::disk_path(&state.paths.cards_dir, &image_path)
+                .filter(|path| path.is_file())
+                .ok_or_else(|| {
+                    err(ErrorCode::CardNotFound, format!("Card '{id}' not found or has no image"))
+                })?;
+            images.push(disk_path);
+        }
+    }
+
+    let pdf = state.render.run(move || build_pdf(&images, req.paper)).await?.map_err(|e| {
+        log::error!("Print PDF failed: {e}");
+        err(ErrorCode::RenderFailed, e)
+    })?;
+    log::info!("Printed {} cards ({} bytes)", req.card_ids.len(), pdf.len());
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"alchemaybe-cards.pdf\""),
+        ],
+        pdf,
+    ))
+}
+
+/// Lay `images` out in a centered grid, as many to a page as fit.
+fn build_pdf(images: &[PathBuf], paper: Paper) -> Result<Vec<u8>, String> {
+    let (page_w, page_h) = paper.size();
+    let (card_w, card_h) = (CARD_W_MM * PT_PER_MM, CARD_H_MM * PT_PER_MM);
+    let margin = MIN_MARGIN_MM * PT_PER_MM;
+    let cols = ((page_w - 2.0 * margin) / card_w).floor().max(1.0) as usize;
+    let rows = ((page_h - 2.0 * margin) / card_h).floor().max(1.0) as usize;
+    let left = (page_w - cols as f32 * card_w) / 2.0;
+    // PDF y runs up from the bottom of the page
+    let top = page_h - (page_h - rows as f32 * card_h) / 2.0;
+
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let tree_id = alloc.bump();
+    let mut pdf = Pdf::new();
+
+    // One image per distinct card, however many copies are printed
+    let mut xobjects: HashMap<&PathBuf, (Ref, String)> = HashMap::new();
+    for path in images {
+        if xobjects.contains_key(path) {
+            continue;
+        }
+        let img = image::open(path).map_err(|e| format!("{}: {e}", path.display()))?.to_rgb8();
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY)
+            .encode_image(&img)
+            .map_err(|e| format!("jpeg encode error: {e}"))?;
+        let id = alloc.bump();
+        let mut xobject = pdf.image_xobject(id, &jpeg);
+        xobject.filter(Filter::DctDecode);
+        xobject.width(img.width() as i32);
+        xobject.height(img.height() as i32);
+        xobject.color_space().device_rgb();
+        xobject.bits_per_component(8);
+        xobject.finish();
+        xobjects.insert(path, (id, format!("Card{}", xobjects.len())));
+    }
+
+    let per_page = cols * rows;
+    let mut page_ids = Vec::new();
+    for sheet in images.chunks(per_page) {
+        let page_id = alloc.bump();
+        let content_id = alloc.bump();
+        page_ids.push(page_id);
+
+        // Cut lines first, across the whole page, so the cards cover them
+        // and only the stubs in the margins show
+        let mut content = Content::new();
+        content.set_line_width(CUT_LINE_PT);
+        content.set_stroke_gray(CUT_LINE_GRAY);
+        let used_rows = sheet.len().div_ceil(cols);
+        let used_cols = sheet.len().min(cols);
+        for col in 0..=used_cols {
+            let x = left + col as f32 * card_w;
+            content.move_to(x, 0.0).line_to(x, page_h);
+        }
+        for row in 0..=used_rows {
+            let y = top - row as f32 * card_h;
+            content.move_to(0.0, y).line_to(page_w, y);
+        }
+        content.stroke();
+
+        for (i, path) in sheet.iter().enumerate() {
+            let (col, row) = (i % cols, i / cols);
+            let x = left + col as f32 * card_w;
+            let y = top - (row + 1) as f32 * card_h;
+            content.save_state();
+            content.transform([card_w, 0.0, 0.0, card_h, x, y]);
+            content.x_object(Name(xobjects[path].1.as_bytes()));
+            content.restore_state();
+        }
+        pdf.stream(content_id, &content.finish());
+
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, page_w, page_h));
+        page.parent(tree_id);
+        page.contents(content_id);
+        let mut resources = page.resources();
+        let mut names = resources.x_objects();
+        for path in sheet.iter().collect::<HashSet<_>>() {
+            let (id, name) = &xobjects[path];
+            names.pair(Name(name.as_bytes()), *id);
+        }
+        names.finish();
+        resources.finish();
+        page.finish();
+    }
+
+    pdf.catalog(catalog_id).pages(tree_id);
+    let page_count = page_ids.len() as i32;
+    pdf.pages(tree_id).kids(page_ids).count(page_count);
+    Ok(pdf.finish())
+}
@@ -0,0 +1,252 @@
+use crate::config::RateLimitSettings;
+use crate::error::ApiError;
+use crate::generate::AppState;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Most keys a limiter tracks. A new key past this first prunes fully
+/// refilled buckets, then the least recently used tenth if none were.
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+#[derive(Clone, Copy)]
+pub struct BucketConfig {
+    /// Sustained refill rate. 0 disables the limit.
+    pub per_minute: u32,
+    /// Maximum tokens a bucket can hold (burst size).
+    pub burst: u32,
+}
+
+impl BucketConfig {
+    fn refill_per_sec(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+/// Buckets kept outside this process, so every server behind a load
+/// balancer draws from the same tokens.
+pub trait SharedBuckets: Send + Sync {
+    /// Take one token from the bucket named `key`. The inner result is the
+    /// limiter's verdict; the outer error means the store was unreachable.
+    fn take(&self, key: &str, config: BucketConfig) -> Result<Result<(), Duration>, String>;
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+struct Limiter<K> {
+    /// Prefix for shared bucket names.
+    name: &'static str,
+    config: BucketConfig,
+    buckets: Mutex<HashMap<K, Bucket>>,
+    shared: Option<Arc<dyn SharedBuckets>>,
+}
+
+impl<K: Hash + Eq + Display> Limiter<K> {
+    fn new(
+        name: &'static str,
+        config: BucketConfig,
+        shared: Option<Arc<dyn SharedBuckets>>,
+    ) -> Self {
+        Self {
+            name,
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            shared,
+        }
+    }
+
+    /// Take one token for `key`. On rejection, returns how long until a token is available.
+    fn check(&self, key: K) -> Result<(), Duration> {
+        if self.config.per_minute == 0 {
+            return Ok(());
+        }
+        if let Some(shared) = &self.shared {
+            match shared.take(&format!("{}:{key}", self.name), self.config) {
+                Ok(verdict) => return verdict,
+                // Fall back to this server's own buckets rather than failing open
+                Err(e) => log::warn!("Shared rate limit unavailable: {e}"),
+            }
+        }
+        let burst = self.config.burst.max(1) as f64;
+        let rate = self.config.refill_per_sec();
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_KEYS && !buckets.contains_key(&key) {
+            // Drop buckets that have fully refilled — they carry no state worth keeping
+            buckets
+                .retain(|_, b| b.tokens + now.duration_since(b.last).as_secs_f64() * rate < burst);
+            if buckets.len() >= MAX_TRACKED_KEYS {
+                // Flooded with keys: forget the least recently used, so
+                // memory stays bounded
+                let mut lasts: Vec<Instant> = buckets.values().map(|b| b.last).collect();
+                let (_, &mut cutoff, _) = lasts.select_nth_unstable(MAX_TRACKED_KEYS / 10);
+                buckets.retain(|_, b| b.last > cutoff);
+            }
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            last: now,
+        });
+        bucket.tokens =
+            (bucket.tokens + now.duration_since(bucket.last).as_secs_f64() * rate).min(burst);
+        bucket.last = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+/// Token-bucket limits for expensive endpoints, keyed by client IP and by wallet.
+pub struct RateLimiter {
+    ip: Limiter<IpAddr>,
+    wallet: Limiter<String>,
+    trust_forwarded: bool,
+}
+
+impl RateLimiter {
+    /// Build from the `[rate_limit]` section (or the RATE_LIMIT_* env vars).
+    /// With `shared`, buckets live there and the local ones are a fallback.
+    pub fn from_config(
+        settings: &RateLimitSettings,
+        shared: Option<Arc<dyn SharedBuckets>>,
+    ) -> Self {
+        let ip = BucketConfig {
+            per_minute: settings.ip_per_minute,
+            burst: settings.ip_burst,
+        };
+        let wallet = BucketConfig {
+            per_minute: settings.wallet_per_minute,
+            burst: settings.wallet_burst,
+        };
+        let trust_forwarded = settings.trust_forwarded;
+        log::info!(
+            "Rate limits: {}/min (burst {}) per IP, {}/min (burst {}) per wallet",
+            ip.per_minute,
+            ip.burst,
+            wallet.per_minute,
+            wallet.burst
+        );
+        RateLimiter {
+            ip: Limiter::new("ip", ip, shared.clone()),
+            wallet: Limiter::new("wallet", wallet, shared),
+            trust_forwarded,
+        }
+    }
+
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.ip.check(ip)
+    }
+
+    pub fn check_wallet(&self, wallet: &str) -> Result<(), Duration> {
+        self.wallet.check(wallet.to_string())
+    }
+
+    pub fn client_ip(&self, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+        if self.trust_forwarded {
+            let forwarded = headers
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .and_then(|v| v.trim().parse().ok());
+            if let Some(ip) = forwarded {
+                return ip;
+            }
+        }
+        peer.ip()
+    }
+}
+
+/// Middleware: per-IP token bucket for expensive routes.
+pub async fn limit_by_ip(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let ip = state.rate_limiter.client_ip(req.headers(), peer);
+    match state.rate_limiter.check_ip(ip) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => {
+            log::warn!("Rate limited {ip} on {}", req.uri().path());
+            ApiError::rate_limited("ip", retry_after).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_minute: u32, burst: u32) -> Limiter<String> {
+        Limiter::new("test", BucketConfig { per_minute, burst }, None)
+    }
+
+    #[test]
+    fn a_bucket_runs_out_after_its_burst() {
+        let limiter = limiter(60, 2);
+        assert!(limiter.check("a".to_string()).is_ok());
+        assert!(limiter.check("a".to_string()).is_ok());
+        let retry_after = limiter.check("a".to_string()).unwrap_err();
+        assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(1));
+        // Other keys have buckets of their own
+        assert!(limiter.check("b".to_string()).is_ok());
+    }
+
+    #[test]
+    fn a_bucket_refills_at_its_rate_up_to_its_burst() {
+        let limiter = limiter(60, 2);
+        for _ in 0..2 {
+            limiter.check("a".to_string()).unwrap();
+        }
+        let rewind = |by: Duration| {
+            let mut buckets = limiter.buckets.lock().unwrap();
+            let bucket = buckets.get_mut("a").expect("tracked");
+            bucket.last -= by;
+        };
+        // A token comes back each second...
+        rewind(Duration::from_secs(1));
+        assert!(limiter.check("a".to_string()).is_ok());
+        assert!(limiter.check("a".to_string()).is_err());
+        // ...but a minute idle refills only the burst
+        rewind(Duration::from_secs(60));
+        assert!(limiter.check("a".to_string()).is_ok());
+        assert!(limiter.check("a".to_string()).is_ok());
+        assert!(limiter.check("a".to_string()).is_err());
+    }
+
+    #[test]
+    fn tracked_keys_stay_under_the_cap() {
+        let limiter = limiter(1, 1);
+        for i in 0..MAX_TRACKED_KEYS + 100 {
+            limiter.check(i.to_string()).unwrap();
+        }
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(buckets.len() <= MAX_TRACKED_KEYS);
+        // The newest keys are the ones kept
+        assert!(buckets.contains_key(&(MAX_TRACKED_KEYS + 99).to_string()));
+    }
+
+    #[test]
+    fn no_rate_means_no_limit() {
+        let limiter = limiter(0, 1);
+        for _ in 0..100 {
+            assert!(limiter.check("a".to_string()).is_ok());
+        }
+    }
+}
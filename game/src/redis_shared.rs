@@ -0,0 +1,466 @@
+//! Redis layer (the `redis` feature) for running several game servers behind
+//! a load balancer: a shared read cache in front of the card store, shared
+//! rate-limit buckets and wallet sessions, a channel telling every server
+//! which cards went stale so they drop their in-memory copies, and one
+//! carrying game updates to every server's sockets.
+
+use crate::card_cache::{CachedCard, CardCounts, CardQuery, ConflictStrategy, MergeSummary};
+use crate::rate_limit::{BucketConfig, SharedBuckets};
+use crate::storage::{CardEvents, CardStore, GameFeed};
+use crate::wallet_auth::{LoginLimits, LoginRejected, SharedSessions};
+use r2d2::Pool;
+use redis::{Client, Commands, Connection, Script};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const CARD_PREFIX: &str = "alchemaybe:card:";
+const BUCKET_PREFIX: &str = "alchemaybe:bucket:";
+const EVENTS_CHANNEL: &str = "alchemaybe:card-events";
+const GAMES_CHANNEL: &str = "alchemaybe:game-events";
+const LOGIN_PREFIX: &str = "alchemaybe:login:";
+/// Sorted sets of waiting nonces scored by expiry: one per wallet and one
+/// for all of them.
+const WALLET_LOGINS_PREFIX: &str = "alchemaybe:wallet-logins:";
+const LOGINS: &str = "alchemaybe:logins";
+const SESSION_PREFIX: &str = "alchemaybe:session:";
+
+/// Token bucket stored as a hash. Uses the Redis clock so servers with
+/// skewed clocks agree, and returns the wait in seconds ("0" when a token
+/// was taken) as a string because Lua numbers come back truncated.
+const TAKE_TOKEN: &str = r"
+    local t = redis.call('TIME')
+    local now = tonumber(t[1]) + tonumber(t[2]) / 1000000
+    local rate, burst = tonumber(ARGV[1]), tonumber(ARGV[2])
+    local b = redis.call('HMGET', KEYS[1], 'tokens', 'last')
+    local tokens = tonumber(b[1]) or burst
+    local last = tonumber(b[2]) or now
+    tokens = math.min(burst, tokens + (now - last) * rate)
+    local wait = 0
+    if tokens >= 1 then tokens = tokens - 1 else wait = (1 - tokens) / rate end
+    redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'last', tostring(now))
+    redis.call('EXPIRE', KEYS[1], math.ceil(burst / rate) + 1)
+    return tostring(wait)
+";
+
+/// Holds a login unless the set of all logins (KEYS[3]) is full after
+/// dropping expired nonces, first dropping the oldest of the wallet's set
+/// (KEYS[2]) while that one is full. Returns the limit hit and the seconds
+/// until its first login runs out, or an empty limit when held.
+const HOLD_LOGIN: &str = r"
+    local t = redis.call('TIME')
+    local now = tonumber(t[1]) + tonumber(t[2]) / 1000000
+    local ttl = tonumber(ARGV[3])
+    redis.call('ZREMRANGEBYSCORE', KEYS[2], '-inf', now)
+    redis.call('ZREMRANGEBYSCORE', KEYS[3], '-inf', now)
+    while redis.call('ZCARD', KEYS[2]) >= tonumber(ARGV[4]) do
+        local oldest = redis.call('ZPOPMIN', KEYS[2])[1]
+        redis.call('DEL', ARGV[6] .. oldest)
+        redis.call('ZREM', KEYS[3], oldest)
+    end
+    if redis.call('ZCARD', KEYS[3]) >= tonumber(ARGV[5]) then
+        local first = redis.call('ZRANGE', KEYS[3], 0, 0, 'WITHSCORES')
+        return {'logins', tostring(tonumber(first[2]) - now)}
+    end
+    redis.call('SET', KEYS[1], ARGV[2], 'EX', ttl)
+    for _, key in ipairs({KEYS[2], KEYS[3]}) do
+        redis.call('ZADD', key, now + ttl, ARGV[1])
+        redis.call('EXPIRE', key, ttl)
+    end
+    return {'', '0'}
+";
+
+/// A login waiting for its signature, under `LOGIN_PREFIX` + nonce.
+#[derive(Serialize, Deserialize)]
+struct PendingLogin {
+    wallet: String,
+    message: String,
+}
+
+/// Published on `EVENTS_CHANNEL` after a server writes cards.
+#[derive(Serialize, Deserialize)]
+struct CardsChanged {
+    /// Lets the sender skip its own notices.
+    from: String,
+    /// Empty means every card.
+    ids: Vec<String>,
+}
+
+/// Published on `GAMES_CHANNEL` after a server changes a game.
+#[derive(Serialize, Deserialize)]
+struct GameChanged {
+    from: String,
+    game_id: String,
+    /// The game as JSON.
+    game: String,
+}
+
+pub struct Redis {
+    /// Opens the pub/sub connections, which are held for as long as they
+    /// listen rather than borrowed from the pool.
+    client: Client,
+    pool: Pool<Client>,
+    take_token: Script,
+    hold_login: Script,
+    instance: String,
+}
+
+impl Redis {
+    pub fn connect(url: &str, pool_size: u32) -> Result<Self, String> {
+        let client = Client::open(url).map_err(|e| format!("Invalid Redis URL: {e}"))?;
+        let pool = tokio::task::block_in_place(|| {
+            Pool::builder()
+                .max_size(pool_size.max(1))
+                .connection_timeout(Duration::from_secs(5))
+                .build(client.clone())
+        })
+        .map_err(|e| format!("Failed to connect to Redis: {e}"))?;
+        let redis = Redis {
+            client,
+            pool,
+            take_token: Script::new(TAKE_TOKEN),
+            hold_login: Script::new(HOLD_LOGIN),
+            instance: uuid::Uuid::new_v4().to_string(),
+        };
+        Ok(redis)
+    }
+
+    /// Run `f` on a pooled connection, waiting for one to come free. The
+    /// client is blocking, so this steps off the async worker. Connections
+    /// that dropped are replaced by the pool.
+    fn with<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> redis::RedisResult<T>,
+    ) -> Result<T, String> {
+        tokio::task::block_in_place(|| {
+            let mut conn = self.pool.get().map_err(|e| format!("No Redis connection: {e}"))?;
+            f(&mut conn).map_err(|e| e.to_string())
+        })
+    }
+
+    fn publish_message(&self, channel: &str, msg: &impl Serialize, what: &str) {
+        let Ok(payload) = serde_json::to_string(msg) else {
+            return;
+        };
+        if let Err(e) = self.with(|c| c.publish::<_, _, ()>(channel, payload)) {
+            log::warn!("Failed to publish {what}: {e}");
+        }
+    }
+
+    fn publish(&self, ids: Vec<String>) {
+        let msg = CardsChanged {
+            from: self.instance.clone(),
+            ids,
+        };
+        self.publish_message(EVENTS_CHANNEL, &msg, "card changes");
+    }
+
+    fn listen_once(&self, on_change: &dyn Fn(Vec<String>)) -> redis::RedisResult<()> {
+        let mut conn = self.client.get_connection()?;
+        let mut pubsub = conn.as_pubsub();
+        pubsub.subscribe(EVENTS_CHANNEL)?;
+        // Whatever changed while we weren't listening is unknown
+        on_change(Vec::new());
+        loop {
+            let payload: String = pubsub.get_message()?.get_payload()?;
+            match serde_json::from_str::<CardsChanged>(&payload) {
+                Ok(msg) if msg.from != self.instance => on_change(msg.ids),
+                Ok(_) => {}
+                Err(e) => log::warn!("Ignoring malformed card event: {e}"),
+            }
+        }
+    }
+
+    fn listen_games_once(&self, on_update: &dyn Fn(String, String)) -> redis::RedisResult<()> {
+        let mut conn = self.client.get_connection()?;
+        let mut pubsub = conn.as_pubsub();
+        pubsub.subscribe(GAMES_CHANNEL)?;
+        loop {
+            let payload: String = pubsub.get_message()?.get_payload()?;
+            match serde_json::from_str::<GameChanged>(&payload) {
+                Ok(msg) if msg.from != self.instance => on_update(msg.game_id, msg.game),
+                Ok(_) => {}
+                Err(e) => log::warn!("Ignoring malformed game event: {e}"),
+            }
+        }
+    }
+}
+
+impl SharedBuckets for Redis {
+    fn take(&self, key: &str, config: BucketConfig) -> Result<Result<(), Duration>, String> {
+        let rate = config.per_minute as f64 / 60.0;
+        let wait: String = self.with(|c| {
+            self.take_token
+                .key(format!("{BUCKET_PREFIX}{key}"))
+                .arg(rate)
+                .arg(config.burst.max(1))
+                .invoke(c)
+        })?;
+        match wait.parse::<f64>() {
+            Ok(secs) if secs > 0.0 => Ok(Err(Duration::from_secs_f64(secs))),
+            Ok(_) => Ok(Ok(())),
+            Err(_) => Err(format!("unexpected rate limit reply {wait:?}")),
+        }
+    }
+}
+
+impl CardEvents for Redis {
+    fn listen(&self, on_change: &dyn Fn(Vec<String>)) {
+        loop {
+            if let Err(e) = self.listen_once(on_change) {
+                log::warn!("Card event subscription dropped, retrying: {e}");
+            }
+            std::thread::sleep(Duration::from_secs(5));
+        }
+    }
+}
+
+impl GameFeed for Redis {
+    fn publish_game(&self, game_id: &str, json: &str) {
+        let msg = GameChanged {
+            from: self.instance.clone(),
+            game_id: game_id.to_string(),
+            game: json.to_string(),
+        };
+        self.publish_message(GAMES_CHANNEL, &msg, "game update");
+    }
+
+    fn listen_games(&self, on_update: &dyn Fn(String, String)) {
+        loop {
+            if let Err(e) = self.listen_games_once(on_update) {
+                log::warn!("Game event subscription dropped, retrying: {e}");
+            }
+            std::thread::sleep(Duration::from_secs(5));
+        }
+    }
+}
+
+impl SharedSessions for Redis {
+    fn hold_login(
+        &self,
+        nonce: &str,
+        wallet: &str,
+        message: &str,
+        limits: LoginLimits,
+    ) -> Result<Result<(), LoginRejected>, String> {
+        let login = PendingLogin {
+            wallet: wallet.to_string(),
+            message: message.to_string(),
+        };
+        let payload = serde_json::to_string(&login).map_err(|e| e.to_string())?;
+        let (limit, wait): (String, String) = self.with(|c| {
+            self.hold_login
+                .key(format!("{LOGIN_PREFIX}{nonce}"))
+                .key(format!("{WALLET_LOGINS_PREFIX}{wallet}"))
+                .key(LOGINS)
+                .arg(nonce)
+                .arg(payload)
+                .arg(limits.ttl.as_secs().max(1))
+                .arg(limits.per_wallet)
+                .arg(limits.total)
+                .arg(LOGIN_PREFIX)
+                .invoke(c)
+        })?;
+        let limit = match limit.as_str() {
+            "" => return Ok(Ok(())),
+            "logins" => "logins",
+            _ => return Err(format!("unexpected login limit reply {limit:?}")),
+        };
+        let secs = wait.parse::<f64>().map_err(|_| format!("unexpected login wait {wait:?}"))?;
+        Ok(Err((limit, Duration::from_secs_f64(secs.max(0.0)))))
+    }
+
+    fn take_login(&self, nonce: &str) -> Result<Option<(String, String)>, String> {
+        let payload: Option<String> =
+            self.with(|c| c.get_del(format!("{LOGIN_PREFIX}{nonce}")))?;
+        let Some(payload) = payload else {
+            return Ok(None);
+        };
+        let login: PendingLogin = serde_json::from_str(&payload).map_err(|e| e.to_string())?;
+        self.with(|c| {
+            redis::pipe()
+                .zrem(format!("{WALLET_LOGINS_PREFIX}{}", login.wallet), nonce)
+                .zrem(LOGINS, nonce)
+                .query::<()>(c)
+        })?;
+        Ok(Some((login.wallet, login.message)))
+    }
+
+    fn open_session(&self, token: &str, wallet: &str, ttl: Duration) -> Result<(), String> {
+        let key = format!("{SESSION_PREFIX}{token}");
+        self.with(|c| c.set_ex::<_, _, ()>(key, wallet, ttl.as_secs()))
+    }
+
+    fn session_wallet(&self, token: &str) -> Result<Option<String>, String> {
+        self.with(|c| c.get(format!("{SESSION_PREFIX}{token}")))
+    }
+
+    fn end_session(&self, token: &str) -> Result<(), String> {
+        self.with(|c| c.del::<_, ()>(format!("{SESSION_PREFIX}{token}")))
+    }
+}
+
+/// Read-through Redis cache over another `CardStore`. Writes go to the inner
+/// store first, then evict the Redis copy and tell the other servers.
+pub struct RedisCardStore {
+    inner: Arc<dyn CardStore>,
+    redis: Arc<Redis>,
+    ttl_secs: u64,
+}
+
+impl RedisCardStore {
+    pub fn new(inner: Arc<dyn CardStore>, redis: Arc<Redis>, ttl_secs: u64) -> Self {
+        RedisCardStore {
+            inner,
+            redis,
+            ttl_secs: ttl_secs.max(1),
+        }
+    }
+
+    fn cached(&self, key: &str) -> Option<CachedCard> {
+        let json: Option<String> = self
+            .redis
+            .with(|c| c.get(format!("{CARD_PREFIX}{key}")))
+            .map_err(|e| log::warn!("Redis card read failed for {key}: {e}"))
+            .ok()
+            .flatten();
+        json.and_then(|j| serde_json::from_str(&j).ok())
+    }
+
+    fn evict(&self, ids: Vec<String>) {
+        if ids.is_empty() {
+            return;
+        }
+        let keys: Vec<String> = ids.iter().map(|id| format!("{CARD_PREFIX}{id}")).collect();
+        if let Err(e) = self.redis.with(|c| c.del::<_, ()>(&keys)) {
+            log::warn!("Failed to evict cards from Redis: {e}");
+        }
+        self.redis.publish(ids);
+    }
+}
+
+impl CardStore for RedisCardStore {
+    fn get(&self, key: &str) -> Result<Option<CachedCard>, String> {
+        if let Some(card) = self.cached(key) {
+            return Ok(Some(card));
+        }
+        let card = self.inner.get(key)?;
+        if let Some(json) = card.as_ref().and_then(|c| serde_json::to_string(c).ok()) {
+            let key = format!("{CARD_PREFIX}{key}");
+            if let Err(e) = self.redis.with(|c| c.set_ex::<_, _, ()>(&key, json, self.ttl_secs)) {
+                log::warn!("Redis card write failed for {key}: {e}");
+            }
+        }
+        Ok(card)
+    }
+
+    fn put(&self, card: &CachedCard) -> Result<(), String> {
+        self.inner.put(card)?;
+        self.evict(vec![card.id.clone()]);
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<(), String> {
+        self.inner.remove(key)?;
+        self.evict(vec![key.to_string()]);
+        Ok(())
+    }
+
+    fn all(&self) -> Result<Vec<CachedCard>, String> {
+        self.inner.all()
+    }
+
+    fn discovered(&self) -> Result<Vec<CachedCard>, String> {
+        self.inner.discovered()
+    }
+
+    fn list(&self, query: &CardQuery) -> Result<(Vec<CachedCard>, u64), String> {
+        self.inner.list(query)
+    }
+
+    fn names(&self) -> Result<Vec<(String, String)>, String> {
+        self.inner.names()
+    }
+
+    fn merge(
+        &self,
+        cards: Vec<CachedCard>,
+        strategy: ConflictStrategy,
+    ) -> Result<MergeSummary, String> {
+        let ids = cards.iter().map(|c| c.id.clone()).collect();
+        let summary = self.inner.merge(cards, strategy)?;
+        self.evict(ids);
+        Ok(summary)
+    }
+
+    fn counts(&self) -> Result<CardCounts, String> {
+        self.inner.counts()
+    }
+
+    fn version_counts(&self) -> Result<HashMap<Option<String>, u64>, String> {
+        self.inner.version_counts()
+    }
+
+    fn remove_outdated(&self, version: &str) -> Result<Vec<String>, String> {
+        let removed = self.inner.remove_outdated(version)?;
+        self.evict(removed.clone());
+        Ok(removed)
+    }
+
+    fn prune_impossible(&self, max: usize) -> Result<Vec<String>, String> {
+        let removed = self.inner.prune_impossible(max)?;
+        self.evict(removed.clone());
+        Ok(removed)
+    }
+
+    fn record_craft(&self, card_id: &str) -> Result<(), String> {
+        self.inner.record_craft(card_id)?;
+        self.evict(vec![card_id.to_string()]);
+        Ok(())
+    }
+
+    fn record_mint(&self, card_id: &str) -> Result<(), String> {
+        self.inner.record_mint(card_id)
+    }
+
+    fn add_mint_counts(&self, counts: &HashMap<String, u64>) -> Result<(), String> {
+        self.inner.add_mint_counts(counts)
+    }
+
+    fn mint_count(&self, card_id: &str) -> Result<u64, String> {
+        self.inner.mint_count(card_id)
+    }
+
+    fn mint_counts(&self) -> Result<HashMap<String, u64>, String> {
+        self.inner.mint_counts()
+    }
+
+    fn issue_edition(&self, card_id: &str, cap: Option<u64>) -> Result<Option<u64>, String> {
+        self.inner.issue_edition(card_id, cap)
+    }
+
+    fn editions(&self, card_id: &str) -> Result<u64, String> {
+        self.inner.editions(card_id)
+    }
+
+    fn ping(&self) -> Result<(), String> {
+        self.inner.ping()?;
+        self.redis
+            .with(|c| redis::cmd("PING").query::<()>(c))
+            .map_err(|e| format!("redis unavailable: {e}"))
+    }
+
+    fn invalidate_all(&self) -> Result<(), String> {
+        self.inner.invalidate_all()?;
+        let keys: Vec<String> = self.redis.with(|c| {
+            c.scan_match::<_, String>(format!("{CARD_PREFIX}*"))
+                .map(|iter| iter.collect())
+        })?;
+        for chunk in keys.chunks(500) {
+            self.redis.with(|c| c.del::<_, ()>(chunk))?;
+        }
+        self.redis.publish(Vec::new());
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+//! Re-rendering crafted cards in bulk, e.g. after a template change, from
+//! each card's kept art. Cards rendered before art was kept have theirs drawn
+//! again by the generation server, which seeds image generation so the same
+//! card gets the same art back. A few cards are in flight at once so
+//! players' renders still get workers.
+
+use crate::auth::require_admin;
+use crate::card_cache::CachedCard;
+use crate::card_template::CardTemplate;
+use crate::crafted_art;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use crate::packs::Rarity;
+use crate::telemetry::SendTraced;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::Json;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Default and most cards re-rendered by one request.
+const BATCH_LIMIT: usize = 100;
+const MAX_BATCH_LIMIT: usize = 500;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RenderBatchRequest {
+    /// Crafted cards to re-render. Empty means every crafted card, a page of
+    /// `limit` at a time.
+    pub card_ids: Vec<String>,
+    /// Card template to render with (default: the default template).
+    pub template: Option<String>,
+    /// When paging through every card, start after this id (the previous
+    /// response's `next`).
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+    /// Cards in flight at once (default: half the render workers).
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct RenderBatchSummary {
+    pub rendered: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub cards: Vec<BatchCard>,
+    /// Pass as `after` to carry on through every card; absent on the last
+    /// page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BatchCard {
+    pub card_id: String,
+    pub status: BatchStatus,
+    /// The card's image after the batch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Rendered,
+    Failed,
+    /// Not a crafted card in the cache, or its art was uploaded before art
+    /// was kept and can't be drawn again.
+    Skipped,
+}
+
+/// POST /api/admin/render-batch — re-render crafted cards with the current
+/// template and point the cache at the new images. The old files stay until
+/// `cache-tool --fix` clears them out, so games in progress keep showing
+/// them meanwhile.
+pub async fn render_batch(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<RenderBatchRequest>,
+) -> Result<Json<RenderBatchSummary>, ApiError> {
+    require_admin(&state, &headers)?;
+    let limit = req.limit.unwrap_or(BATCH_LIMIT);
+    if req.card_ids.len() > MAX_BATCH_LIMIT || limit == 0 || limit > MAX_BATCH_LIMIT {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("Re-render between 1 and {MAX_BATCH_LIMIT} cards per request"),
+        ));
+    }
+    let template = {
+        let templates = state.card_templates.read().await;
+        match &req.template {
+            Some(name) => templates.get(name).ok_or_else(|| {
+                err(ErrorCode::InvalidRequest, format!("Unknown card template '{name}'"))
+            })?,
+            None => templates.default_template(),
+        }
+    };
+
+    // The cards asked for, or the next page of every crafted card
+    let (cards, next) = {
+        let cache = state.card_cache.read().await;
+        if req.card_ids.is_empty() {
+            let mut all: Vec<CachedCard> = cache
+                .all_entries()
+                .into_iter()
+                .filter(|c| !c.impossible && req.after.as_ref().is_none_or(|a| c.id > *a))
+                .collect();
+            all.sort_by(|a, b| a.id.cmp(&b.id));
+            let next = (all.len() > limit).then(|| all[limit - 1].id.clone());
+            all.truncate(limit);
+            (all.into_iter().map(|c| (c.id.clone(), Some(c))).collect(), next)
+        } else {
+            let cards = req
+                .card_ids
+                .iter()
+                .map(|id| (id.clone(), cache.get(id).filter(|c| !c.impossible)))
+                .collect::<Vec<_>>();
+            (cards, None)
+        }
+    };
+
+    let workers = state.render.worker_count();
+    let concurrency = req.concurrency.unwrap_or(workers.div_ceil(2)).clamp(1, workers);
+    let results: Vec<BatchCard> = stream::iter(cards)
+        .map(|(card_id, card)| {
+            let state = state.clone();
+            let template = template.clone();
+            async move {
+                // Uploaded art from before art was kept can't be drawn again
+                let Some(card) =
+                    card.filter(|c| c.art_path.is_some() || c.previous_art.is_empty())
+                else {
+                    return BatchCard {
+                        card_id,
+                        status: BatchStatus::Skipped,
+                        image_path: None,
+                        error: None,
+                    };
+                };
+                match rerender(&state, &card, template).await {
+                    Ok(image_path) => BatchCard {
+                        card_id,
+                        status: BatchStatus::Rendered,
+                        image_path: Some(image_path),
+                        error: None,
+                    },
+                    Err(e) => {
+                        log::warn!("Re-rendering {card_id} failed: {}", e.message);
+                        BatchCard {
+                            card_id,
+                            status: BatchStatus::Failed,
+                            image_path: Some(card.image_path),
+                            error: Some(e.message),
+                        }
+                    }
+                }
+            }
+        })
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    let count = |status: BatchStatus| results.iter().filter(|c| c.status == status).count();
+    let summary = RenderBatchSummary {
+        rendered: count(BatchStatus::Rendered),
+        failed: count(BatchStatus::Failed),
+        skipped: count(BatchStatus::Skipped),
+        cards: results,
+        next,
+    };
+    log::info!(
+        "Re-rendered {} cards ({} failed, {} skipped)",
+        summary.rendered,
+        summary.failed,
+        summary.skipped
+    );
+    Ok(Json(summary))
+}
+
+/// Render `card` again with `template` from its kept art or, for cards
+/// rendered before art was kept, art drawn again (and kept from now on).
+/// Points the cache at the result and returns the new image path.
+async fn rerender(
+    state: &AppState,
+    card: &CachedCard,
+    template: Arc<CardTemplate>,
+) -> Result<String, ApiError> {
+    let mut card = card.clone();
+    let kept_art = card.art_path.clone();
+    if kept_art.is_none() {
+        let art_bytes = state
+            .client
+            .post(format!("{}/generate-image", state.generation_url))
+            .json(&serde_json::json!({
+                "name": card.name,
+                "description": card.description,
+            }))
+            .send_traced(tracing::info_span!("generation.generate_image"))
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| {
+                err(ErrorCode::GenerationUnavailable, format!("Image generation error: {e}"))
+            })?
+            .bytes()
+            .await
+            .map_err(|e| {
+                err(ErrorCode::GenerationUnavailable, format!("Image read error: {e}"))
+            })?;
+        let art_path = crafted_art::save_art(&state.paths.cards_dir, &art_bytes)
+            .map_err(|e| err(ErrorCode::StorageError, format!("File write error: {e}")))?;
+        card.art_path = Some(art_path);
+    }
+
+    let rarity = Rarity::of(state, &card.id, true);
+    let cards_dir = state.paths.cards_dir.clone();
+    let (card_id, art_path) = (card.id.clone(), card.art_path.clone());
+    let serve_path = state
+        .render
+        .run(move || crafted_art::render_again(&cards_dir, &card, &template, rarity))
+        .await?
+        .map_err(|e| err(ErrorCode::RenderFailed, format!("Card render error: {e}")))?;
+
+    // Re-read under the write lock so a craft counted, or art uploaded,
+    // meanwhile isn't lost
+    let cache = state.card_cache.write().await;
+    if let Some(mut current) = cache.get(&card_id) {
+        if current.art_path != kept_art {
+            return Err(err(ErrorCode::RenderFailed, "Art was replaced while rendering"));
+        }
+        current.image_path = serve_path.clone();
+        current.art_path = art_path;
+        cache.insert(card_id, current);
+    }
+    Ok(serve_path)
+}
@@ -0,0 +1,159 @@
+//! Card rendering off the async runtime. Renders run on tokio's blocking
+//! pool, a few at a time, so the Lanczos resizes and pixel loops never stall
+//! the request handlers. Requests queue for a free worker; once too many are
+//! waiting, new ones are refused with RENDER_BUSY rather than piling up.
+//! Builds of the same file take turns, so a burst of requests for one
+//! missing file renders it once.
+
+use crate::config::RenderSettings;
+use crate::error::{err, ApiError, ErrorCode};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedMutexGuard, Semaphore};
+
+/// Files being built, each with the turn its builders wait for.
+type Turns = Arc<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>>;
+
+/// How long a client turned away is asked to wait.
+const BUSY_RETRY_AFTER: Duration = Duration::from_secs(2);
+
+pub struct RenderQueue {
+    workers: Arc<Semaphore>,
+    worker_count: usize,
+    max_queued: usize,
+    /// Renders waiting or running.
+    pending: Arc<AtomicUsize>,
+    building: Turns,
+}
+
+impl RenderQueue {
+    pub fn from_config(settings: &RenderSettings) -> Self {
+        let worker_count = match settings.workers {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        RenderQueue {
+            workers: Arc::new(Semaphore::new(worker_count)),
+            worker_count,
+            max_queued: settings.max_queued,
+            pending: Arc::new(AtomicUsize::new(0)),
+            building: Arc::default(),
+        }
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.worker_count
+    }
+
+    /// Run `render` on a blocking thread once a worker is free, in the
+    /// caller's tracing span. Refused with RENDER_BUSY when the queue is
+    /// full. A render whose request goes away still finishes, holding its
+    /// worker until it does.
+    pub async fn run<T, F>(&self, render: F) -> Result<T, ApiError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let pending = Pending::enter(&self.pending);
+        if pending.count > self.worker_count + self.max_queued {
+            return Err(err(ErrorCode::RenderBusy, "Too many cards rendering, try again shortly")
+                .with_details(serde_json::json!({ "max_queued": self.max_queued }))
+                .with_retry_after(BUSY_RETRY_AFTER));
+        }
+        let worker = self
+            .workers
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| err(ErrorCode::RenderFailed, e.to_string()))?;
+        let span = tracing::Span::current();
+        tokio::task::spawn_blocking(move || {
+            let _held = (worker, pending);
+            span.in_scope(render)
+        })
+        .await
+        .map_err(|e| err(ErrorCode::RenderFailed, format!("Render task failed: {e}")))
+    }
+
+    /// Wait for the turn to build `path`. Whoever held it before may have
+    /// built the file already, so check again before rendering it.
+    pub async fn building(&self, path: &Path) -> Building {
+        let turn = self
+            .building
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone();
+        Building {
+            turn: Some(turn.lock_owned().await),
+            path: path.to_path_buf(),
+            building: self.building.clone(),
+        }
+    }
+}
+
+/// The turn to build one file, passed on when dropped.
+pub struct Building {
+    turn: Option<OwnedMutexGuard<()>>,
+    path: PathBuf,
+    building: Turns,
+}
+
+impl Drop for Building {
+    fn drop(&mut self) {
+        let mut building = self.building.lock().unwrap();
+        self.turn = None;
+        // Nobody else waiting (the map holds the last reference)
+        if building.get(&self.path).is_some_and(|turn| Arc::strong_count(turn) == 1) {
+            building.remove(&self.path);
+        }
+    }
+}
+
+/// One render counted in `pending` until dropped.
+struct Pending {
+    pending: Arc<AtomicUsize>,
+    /// Renders pending, this one included, when it arrived.
+    count: usize,
+}
+
+impl Pending {
+    fn enter(pending: &Arc<AtomicUsize>) -> Self {
+        let count = pending.fetch_add(1, Ordering::SeqCst) + 1;
+        Pending {
+            pending: pending.clone(),
+            count,
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn builds_of_one_file_take_turns() {
+        let queue = RenderQueue::from_config(&RenderSettings::default());
+        let path = Path::new("card.png");
+        let first = queue.building(path).await;
+        // Another file isn't held up
+        drop(queue.building(Path::new("other.png")).await);
+        let second = queue.building(path);
+        tokio::pin!(second);
+        let waited = tokio::time::timeout(Duration::from_millis(10), &mut second).await;
+        assert!(waited.is_err());
+        drop(first);
+        drop(second.await);
+        assert!(queue.building.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+//! `game rerender`: render every crafted card again from its kept art with
+//! the current card templates, so frame and layout changes reach cards made
+//! before them. Safe to run while the server is up; POST
+//! /api/admin/reload-content afterwards so it drops stale copies.
+
+use crate::card_cache::{CachedCard, CardCache};
+use crate::card_template::load_card_templates;
+use crate::config::Config;
+use crate::crafted_art;
+use crate::packs::Rarity;
+use crate::storage;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Card template to render with (default: the default template)
+    #[arg(long)]
+    template: Option<String>,
+    /// Cards rendered at once (default: one per CPU)
+    #[arg(long, default_value_t = 0)]
+    jobs: usize,
+    /// Only report which cards would be re-rendered
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Default)]
+struct Report {
+    rendered: usize,
+    /// Rendered to an image the card already had.
+    unchanged: usize,
+    /// Cards whose art was replaced while they rendered; left as they were.
+    changed: Vec<String>,
+    /// (card id, error)
+    failed: Vec<(String, String)>,
+}
+
+pub fn run(args: Args) -> Result<(), String> {
+    let config = Config::load()?;
+    let paths = &config.paths;
+    let cache = CardCache::new(storage::open(&config)?.cards, &config.card_cache);
+    let templates = load_card_templates(&paths.card_templates_json)?;
+    let template = match &args.template {
+        Some(name) => templates
+            .get(name)
+            .ok_or_else(|| format!("Unknown card template '{name}'"))?,
+        None => templates.default_template(),
+    };
+
+    let (cards, no_art): (Vec<CachedCard>, Vec<CachedCard>) = cache
+        .all_entries()
+        .into_iter()
+        .filter(|c| !c.impossible)
+        .partition(|c| c.art_path.is_some());
+    println!("{} cards to re-render", cards.len());
+    println!("  {} without kept art (use POST /api/admin/render-batch)", no_art.len());
+    for card in &no_art {
+        println!("    {}", card.id);
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let jobs = match args.jobs {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    // Saving a render writes its other sizes on tokio's blocking pool
+    let runtime = tokio::runtime::Handle::current();
+    let next = AtomicUsize::new(0);
+    let report = Mutex::new(Report::default());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(cards.len()) {
+            scope.spawn(|| {
+                let _runtime = runtime.enter();
+                while let Some(card) = cards.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let rarity = Rarity::of_crafted(&config.supply, &card.id);
+                    let result =
+                        crafted_art::render_again(&paths.cards_dir, card, &template, rarity)
+                            .map(|image_path| point_at(&cache, card, image_path));
+                    let mut report = report.lock().unwrap_or_else(|e| e.into_inner());
+                    match result {
+                        Ok(Update::Rendered) => report.rendered += 1,
+                        Ok(Update::Unchanged) => report.unchanged += 1,
+                        Ok(Update::Changed) => report.changed.push(card.id.clone()),
+                        Err(e) => report.failed.push((card.id.clone(), e)),
+                    }
+                }
+            });
+        }
+    });
+
+    report.into_inner().unwrap_or_else(|e| e.into_inner()).print();
+    Ok(())
+}
+
+enum Update {
+    Rendered,
+    Unchanged,
+    Changed,
+}
+
+/// Point `card` at its new render, unless its art changed since it was read.
+fn point_at(cache: &CardCache, card: &CachedCard, image_path: String) -> Update {
+    let Some(mut current) = cache.get(&card.id) else {
+        return Update::Changed;
+    };
+    if current.art_path != card.art_path {
+        return Update::Changed;
+    }
+    if current.image_path == image_path {
+        return Update::Unchanged;
+    }
+    current.image_path = image_path;
+    cache.insert(card.id.clone(), current);
+    Update::Rendered
+}
+
+impl Report {
+    fn print(&self) {
+        println!("  {} re-rendered", self.rendered);
+        println!("  {} unchanged", self.unchanged);
+        println!("  {} skipped, their art was replaced meanwhile", self.changed.len());
+        for id in &self.changed {
+            println!("    {id}");
+        }
+        println!("  {} failed", self.failed.len());
+        for (id, e) in &self.failed {
+            println!("    {id}: {e}");
+        }
+        if self.rendered > 0 {
+            println!("Old images stay until `cache-tool --fix` removes them.");
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Solana RPC that rides out a flaky node. Each call goes to the first
+//! healthy endpoint, in priority order (`rpc_url`, then `fallback_rpc_urls`).
+//! Transient failures (unreachable, overloaded, or behind) are retried with
+//! backoff, then the next endpoint is tried, all within a time budget. An
+//! endpoint that keeps failing is passed over for a while, so later calls
+//! don't wait on it first. The client is blocking, so every call, retries
+//! and backoff included, steps off the async worker it was made from.
+
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_custom_error::{
+    JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE, JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+    JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+};
+use solana_client::rpc_request::RpcError;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use std::sync::Mutex;
+use tokio::runtime::RuntimeFlavor;
+use std::time::{Duration, Instant};
+
+/// Wait before the first retry on an endpoint, doubled after each.
+const BACKOFF: Duration = Duration::from_millis(250);
+/// How long an endpoint that failed every try is passed over.
+const COOLDOWN: Duration = Duration::from_secs(30);
+/// JSON-RPC "internal error", which overloaded nodes return.
+const JSON_RPC_INTERNAL_ERROR: i64 = -32603;
+
+/// `ClientError` is large, so it's boxed to keep results small.
+pub type RpcResult<T> = Result<T, Box<ClientError>>;
+
+pub struct Rpc {
+    endpoints: Vec<Endpoint>,
+    /// Extra tries on one endpoint before moving to the next.
+    retries: u32,
+    /// No new try starts once a call has taken this long.
+    budget: Duration,
+}
+
+struct Endpoint {
+    client: RpcClient,
+    /// Host only: the URL may carry an API key.
+    host: String,
+    /// Passed over until then, after failing every try.
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    fn is_up(&self, now: Instant) -> bool {
+        self.down_until.lock().unwrap().is_none_or(|until| now >= until)
+    }
+}
+
+impl Rpc {
+    /// Endpoints for `urls`, highest priority first. Each request gives up
+    /// after `timeout`.
+    pub fn new(
+        urls: &[String],
+        commitment: CommitmentConfig,
+        timeout: Duration,
+        retries: u32,
+        budget: Duration,
+    ) -> Self {
+        let endpoints = urls
+            .iter()
+            .map(|url| Endpoint {
+                client: RpcClient::new_with_timeout_and_commitment(url, timeout, commitment),
+                host: reqwest::Url::parse(url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(str::to_string))
+                    .unwrap_or_else(|| "rpc".to_string()),
+                down_until: Mutex::new(None),
+            })
+            .collect();
+        Rpc {
+            endpoints,
+            retries,
+            budget,
+        }
+    }
+
+    pub fn commitment(&self) -> CommitmentConfig {
+        self.endpoints[0].client.commitment()
+    }
+
+    /// Every endpoint's client and host, in priority order.
+    pub fn endpoints(&self) -> impl Iterator<Item = (&RpcClient, &str)> {
+        self.endpoints.iter().map(|e| (&e.client, e.host.as_str()))
+    }
+
+    /// Run `f` on the endpoints until it succeeds, fails for good, or the
+    /// budget runs out. `method` names the call in logs.
+    pub fn call<T>(
+        &self,
+        method: &str,
+        f: impl Fn(&RpcClient) -> RpcResult<T>,
+    ) -> RpcResult<T> {
+        self.call_with(method, |client, _| f(client))
+    }
+
+    /// Send `tx` and wait for it to be confirmed. A retry first checks
+    /// whether an earlier try landed after all, and otherwise sends the same
+    /// transaction again, so it can't land twice.
+    pub fn send_and_confirm(&self, tx: &VersionedTransaction) -> RpcResult<Signature> {
+        let signature = tx.signatures[0];
+        self.call_with("sendTransaction", |client, retry| {
+            if retry {
+                if let Some(landed) = client.get_signature_status(&signature)? {
+                    return landed
+                        .map(|()| signature)
+                        .map_err(|e| Box::new(ClientErrorKind::from(e).into()));
+                }
+            }
+            Ok(client.send_and_confirm_transaction(tx)?)
+        })
+    }
+
+    /// `f` is told whether an earlier try was made.
+    fn call_with<T>(
+        &self,
+        method: &str,
+        f: impl Fn(&RpcClient, bool) -> RpcResult<T>,
+    ) -> RpcResult<T> {
+        // The worker's other tasks move to another thread meanwhile. Outside
+        // the runtime (a blocking task, a CLI thread) this just runs `f`.
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| self.try_endpoints(method, f))
+            }
+            _ => self.try_endpoints(method, f),
+        }
+    }
+
+    fn try_endpoints<T>(
+        &self,
+        method: &str,
+        f: impl Fn(&RpcClient, bool) -> RpcResult<T>,
+    ) -> RpcResult<T> {
+        let started = Instant::now();
+        // Healthy endpoints first, keeping priority order within each group
+        let (up, down): (Vec<&Endpoint>, Vec<&Endpoint>) =
+            self.endpoints.iter().partition(|e| e.is_up(started));
+
+        let mut tries = 0;
+        let mut last_error = None;
+        'endpoints: for endpoint in up.into_iter().chain(down) {
+            for attempt in 0..=self.retries {
+                if tries > 0 && started.elapsed() >= self.budget {
+                    break 'endpoints;
+                }
+                if attempt > 0 {
+                    std::thread::sleep(BACKOFF * 2u32.pow(attempt - 1));
+                }
+                let result = f(&endpoint.client, tries > 0);
+                tries += 1;
+                match result {
+                    Ok(value) => {
+                        *endpoint.down_until.lock().unwrap() = None;
+                        return Ok(value);
+                    }
+                    Err(e) if is_transient(&e) => {
+                        log::warn!(
+                            "Solana RPC {method} on {} failed (try {tries}): {e}",
+                            endpoint.host
+                        );
+                        last_error = Some(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            *endpoint.down_until.lock().unwrap() = Some(Instant::now() + COOLDOWN);
+        }
+        Err(last_error.expect("a call makes at least one try"))
+    }
+}
+
+/// Whether another try (or another endpoint) might do better: the node was
+/// unreachable, overloaded or behind, rather than the request being wrong.
+fn is_transient(e: &ClientError) -> bool {
+    match e.kind() {
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) | ClientErrorKind::Middleware(_) => {
+            true
+        }
+        ClientErrorKind::RpcError(RpcError::RpcRequestError(_)) => true,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => matches!(
+            *code,
+            JSON_RPC_INTERNAL_ERROR
+                | JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+                | JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE
+                | JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET
+                | JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unreachable() -> Rpc {
+        // Nothing listens there, so every try fails
+        Rpc::new(
+            &["http://127.0.0.1:1".to_string()],
+            CommitmentConfig::confirmed(),
+            Duration::from_secs(1),
+            2,
+            Duration::from_secs(5),
+        )
+    }
+
+    #[test]
+    fn retries_dont_hold_up_the_runtime() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let rpc = unreachable();
+            let started = Instant::now();
+            let call = tokio::spawn(async move { rpc.call("getHealth", |c| Ok(c.get_health()?)) });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let ticked = started.elapsed();
+            assert!(call.await.unwrap().is_err());
+            assert!(ticked < BACKOFF, "runtime stalled for {ticked:?}");
+        });
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn calls_work_from_the_blocking_pool() {
+        let rpc = unreachable();
+        let call = move || rpc.call("getHealth", |c| Ok(c.get_health()?));
+        let result = tokio::task::spawn_blocking(call).await;
+        assert!(result.expect("doesn't panic").is_err());
+    }
+}
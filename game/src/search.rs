@@ -0,0 +1,59 @@
+//! Name matching for `/api/cards/search`.
+
+/// Rank how well `name` matches `query` (both compared case-insensitively).
+/// Lower is better; `None` means no match.
+///
+/// 0 exact, 1 prefix, 2 word prefix, 3 substring, then 4 + edit distance for
+/// typos against the whole name or any single word ("katan" → "Steel Katana").
+pub fn match_rank(query: &str, name: &str) -> Option<usize> {
+    let query = query.trim().to_lowercase();
+    let name = name.to_lowercase();
+    if query.is_empty() {
+        return None;
+    }
+    if name == query {
+        return Some(0);
+    }
+    if name.starts_with(&query) {
+        return Some(1);
+    }
+    if name.split_whitespace().any(|w| w.starts_with(&query)) {
+        return Some(2);
+    }
+    if name.contains(&query) {
+        return Some(3);
+    }
+
+    let allowed = max_typos(&query);
+    std::iter::once(name.as_str())
+        .chain(name.split_whitespace())
+        .map(|candidate| edit_distance(&query, candidate))
+        .min()
+        .filter(|&d| d <= allowed)
+        .map(|d| 4 + d)
+}
+
+/// Short queries tolerate fewer typos, or everything would match.
+fn max_typos(query: &str) -> usize {
+    match query.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = substitute.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
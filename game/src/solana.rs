@@ -1,22 +1,299 @@
-use mpl_core::instructions::{BurnV1Builder, CreateV1Builder};
-use mpl_core::types::{Attribute, Attributes, Plugin, PluginAuthorityPair};
+use crate::card;
+use crate::config::{SolanaCluster, SolanaCommitment, SolanaSettings};
+use crate::image_variants;
+use crate::packs::Rarity;
+use crate::rpc::Rpc;
+use mpl_core::accounts::BaseAssetV1;
+use mpl_core::errors::MplCoreError;
+use mpl_core::instructions::{BurnV1Builder, CreateV1Builder, TransferV1Builder};
+use mpl_core::types::{
+    Attribute, Attributes, Key, PermanentBurnDelegate, PermanentFreezeDelegate, Plugin,
+    PluginAuthority, PluginAuthorityPair, UpdateAuthority,
+};
 use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
+use sha2::{Digest, Sha256};
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_client::rpc_request::RpcRequest;
+use solana_compute_budget_interface::ComputeBudgetInstruction;
 use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::message::{v0, VersionedMessage};
 use solana_sdk::pubkey::Pubkey;
-use solana_sdk::signature::Keypair;
+use solana_sdk::signature::{keypair_from_seed, Keypair, Signature};
 use solana_sdk::signer::Signer;
-use solana_sdk::transaction::Transaction;
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use solana_system_interface::instruction::SystemInstruction;
+use solana_system_interface::program as system_program;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEVNET_GENESIS_HASH: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
+const MAINNET_GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+/// Largest page `getAssetsByOwner` serves.
+const DAS_PAGE_LIMIT: usize = 1000;
+/// DAS pages requested at once for big wallets.
+const DAS_CONCURRENCY: usize = 4;
+/// How long a card's fetched off-chain metadata is reused.
+const METADATA_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long before a failed metadata fetch is tried again.
+const METADATA_RETRY: Duration = Duration::from_secs(5 * 60);
+const METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+/// Larger metadata documents are ignored.
+const METADATA_MAX_BYTES: usize = 64 * 1024;
+/// Entries kept in `metadata_cache` before it's emptied.
+const METADATA_CACHE_MAX: usize = 10_000;
+/// Transactions touching an asset looked through for a trade's escrow.
+const ESCROW_SEARCH_LIMIT: usize = 25;
+/// SPL Memo program (v2).
+const MEMO_PROGRAM_ID: &str = "MemoSq4gqABAXKb96qnH8TjnLGX7tZEwRskEA4mmR9s";
+/// mpl-core errors that mean the signer doesn't control the asset.
+const CORE_INVALID_AUTHORITY: u32 = MplCoreError::InvalidAuthority as u32;
+const CORE_NO_APPROVALS: u32 = MplCoreError::NoApprovals as u32;
+const CORE_ASSET_FROZEN: u32 = MplCoreError::AssetIsFrozen as u32;
 
 pub struct SolanaConfig {
-    pub rpc_client: RpcClient,
+    pub rpc: Rpc,
     pub server_keypair: Arc<Keypair>,
+    /// Collection new cards are minted into: the current season's.
     pub collection_pubkey: Pubkey,
+    /// Every collection whose cards count as ours, current season first.
+    pub seasons: Vec<Season>,
     pub public_base_url: String,
     pub helius_api_key: String,
+    /// Which network everything here talks to. Wallet responses carry it so
+    /// clients can tell environments apart.
+    pub cluster: SolanaCluster,
+    /// Helius DAS endpoint, without the api key.
+    pub das_url: String,
+    /// Upper bound on assets read per wallet query.
+    pub das_max_assets: usize,
+    /// Zero disables `owned_cache`.
+    pub das_cache_ttl: Duration,
+    /// Expected `Authorization` on Helius webhook deliveries.
+    pub webhook_auth: Option<String>,
+    /// Whether `/api/game/{id}/attest` is on.
+    pub attest_results: bool,
+    /// Whether mint and combine transactions are simulated before use.
+    pub simulate_transactions: bool,
+    owned_cache: Mutex<HashMap<String, OwnedEntry>>,
+    /// Off-chain metadata by URI, for cards we don't have locally.
+    metadata_cache: Mutex<HashMap<String, MetadataEntry>>,
     pub http_client: reqwest::Client,
+    pub metadata_dir: PathBuf,
+    /// Priority fee in micro-lamports per compute unit (0 = none).
+    pub compute_unit_price: u64,
+    /// Compute units to request (0 = runtime default).
+    pub compute_unit_limit: u32,
+    /// Cards minted soulbound wherever they come from.
+    soulbound_cards: HashSet<String>,
+}
+
+/// A transaction minting a new asset, for the wallet to sign.
+pub struct MintTx {
+    /// Base64, partially signed.
+    pub transaction: String,
+    pub asset_address: String,
+    /// Derives the asset's keypair, so the transaction can be signed again
+    /// with a fresh blockhash.
+    pub asset_seed: String,
+}
+
+/// The card a mint transaction creates.
+pub struct NewCard<'a> {
+    pub card_id: &'a str,
+    pub edition: u64,
+    pub name: &'a str,
+    pub metadata_uri: &'a str,
+    /// From `SolanaConfig::new_asset_seed`; derives the asset's keypair.
+    pub asset_seed: &'a str,
+}
+
+/// How a card's art shows in its NFT metadata.
+#[derive(Clone, Copy, Default)]
+pub struct Finish {
+    pub foil: bool,
+    /// Also links the art looping with light, as `animation_url`.
+    pub animated: bool,
+}
+
+impl Finish {
+    /// Foil and legendary cards are animated.
+    pub fn new(foil: bool, rarity: Rarity) -> Self {
+        Finish {
+            foil,
+            animated: foil || rarity == Rarity::Legendary,
+        }
+    }
+}
+
+/// Why simulation says a transaction would fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxFailure {
+    /// The fee payer can't cover the fee or the new asset's rent.
+    InsufficientSol,
+    AssetFrozen,
+    /// The wallet doesn't own (or no longer owns) a card it would spend.
+    WrongOwner,
+    Other,
+}
+
+impl TxFailure {
+    pub fn describe(self) -> &'static str {
+        match self {
+            TxFailure::InsufficientSol => "Not enough SOL in the wallet to pay for this transaction",
+            TxFailure::AssetFrozen => "A card in this transaction is frozen",
+            TxFailure::WrongOwner => "A card in this transaction is not owned by this wallet",
+            TxFailure::Other => "Transaction would fail",
+        }
+    }
+}
+
+/// A transaction that couldn't be built, or that was built but would fail.
+#[derive(Debug)]
+pub enum TxError {
+    Solana(String),
+    WouldFail {
+        failure: TxFailure,
+        /// The runtime's error.
+        error: String,
+        logs: Vec<String>,
+    },
+}
+
+impl From<String> for TxError {
+    fn from(e: String) -> Self {
+        TxError::Solana(e)
+    }
+}
+
+/// A payment that couldn't be checked, or that was checked and doesn't pay.
+#[derive(Debug)]
+pub enum PaymentError {
+    Solana(String),
+    /// It hasn't landed, failed, or doesn't pay enough to the server.
+    Unpaid(String),
+}
+
+impl From<String> for PaymentError {
+    fn from(e: String) -> Self {
+        PaymentError::Solana(e)
+    }
+}
+
+/// Lamports `tx` transfers from `payer`, one of its signers, to `recipient`
+/// with System Program transfers at the top level.
+fn paid_lamports(tx: &VersionedTransaction, payer: &Pubkey, recipient: &Pubkey) -> u64 {
+    let message = &tx.message;
+    let keys = message.static_account_keys();
+    let signers = &keys[..(message.header().num_required_signatures as usize).min(keys.len())];
+    if !signers.contains(payer) {
+        return 0;
+    }
+    message
+        .instructions()
+        .iter()
+        .filter(|ix| keys.get(ix.program_id_index as usize) == Some(&system_program::ID))
+        .filter_map(|ix| {
+            let SystemInstruction::Transfer { lamports } = bincode::deserialize(&ix.data).ok()?
+            else {
+                return None;
+            };
+            let from = keys.get(*ix.accounts.first()? as usize)?;
+            let to = keys.get(*ix.accounts.get(1)? as usize)?;
+            (from == payer && to == recipient).then_some(lamports)
+        })
+        .fold(0, u64::saturating_add)
+}
+
+/// Whether `signer` signed `tx` and it carries every one of `ixs`, among
+/// any others the wallet added (priority fees, say).
+fn carries_all(tx: &VersionedTransaction, signer: &Pubkey, ixs: &[Instruction]) -> bool {
+    let message = &tx.message;
+    let keys = message.static_account_keys();
+    let signers = &keys[..(message.header().num_required_signatures as usize).min(keys.len())];
+    if !signers.contains(signer) {
+        return false;
+    }
+    ixs.iter().all(|ix| {
+        message.instructions().iter().any(|compiled| {
+            keys.get(compiled.program_id_index as usize) == Some(&ix.program_id)
+                && compiled.data == ix.data
+                && compiled.accounts.len() == ix.accounts.len()
+                && compiled
+                    .accounts
+                    .iter()
+                    .zip(&ix.accounts)
+                    .all(|(i, meta)| keys.get(*i as usize) == Some(&meta.pubkey))
+        })
+    })
+}
+
+/// Make sense of a failed simulation of `message`.
+fn tx_failure(message: &VersionedMessage, error: &TransactionError, logs: &[String]) -> TxFailure {
+    // System transfers inside a CPI fail with the calling program's index
+    if logs.iter().any(|l| l.contains("insufficient lamports")) {
+        return TxFailure::InsufficientSol;
+    }
+    match error {
+        TransactionError::InsufficientFundsForFee
+        | TransactionError::InsufficientFundsForRent { .. }
+        // A fee payer that was never funded doesn't exist
+        | TransactionError::AccountNotFound => TxFailure::InsufficientSol,
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            let program = message
+                .instructions()
+                .get(*index as usize)
+                .and_then(|ix| message.static_account_keys().get(ix.program_id_index as usize));
+            if program != Some(&mpl_core::ID) {
+                return TxFailure::Other;
+            }
+            match *code {
+                CORE_ASSET_FROZEN => TxFailure::AssetFrozen,
+                CORE_INVALID_AUTHORITY | CORE_NO_APPROVALS => TxFailure::WrongOwner,
+                _ => TxFailure::Other,
+            }
+        }
+        _ => TxFailure::Other,
+    }
+}
+
+/// A wallet's cards as of when they were fetched. `None` marks a wallet
+/// forgotten at that time.
+type OwnedEntry = (Instant, Option<Vec<OwnedCard>>);
+
+/// A card's off-chain metadata as of when it was fetched. `None` marks a
+/// failed fetch.
+type MetadataEntry = (Instant, Option<CardMetadata>);
+
+/// The parts of a card's off-chain metadata JSON (see `ensure_metadata_json`)
+/// needed to show it.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CardMetadata {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Absolute URL.
+    #[serde(default)]
+    pub image: String,
+}
+
+pub struct Season {
+    pub name: String,
+    pub collection: Pubkey,
+}
+
+/// A Core asset and the collection it belongs to, which instructions acting
+/// on it have to name.
+#[derive(Debug, Clone, Copy)]
+pub struct CoreAsset {
+    pub address: Pubkey,
+    pub collection: Pubkey,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,20 +302,55 @@ pub struct OwnedCard {
     pub card_id: String,
     pub name: String,
     pub image: String,
+    /// The asset's off-chain metadata JSON, as DAS reports it.
+    #[serde(default)]
+    pub metadata_uri: String,
+    #[serde(default)]
+    pub collection: String,
+    /// Season of `collection`.
+    #[serde(default)]
+    pub season: String,
+    /// Which of the card's mints this is, for cards minted with one.
+    #[serde(default)]
+    pub edition: Option<u64>,
+    /// Frozen for good: it can be burned but not transferred.
+    #[serde(default)]
+    pub soulbound: bool,
 }
 
-/// Extract card_id from a DAS item's plugins.attributes.data.attribute_list
-fn extract_card_id(item: &serde_json::Value) -> Option<String> {
+impl OwnedCard {
+    pub fn core_asset(&self) -> Result<CoreAsset, String> {
+        let address = Pubkey::from_str(&self.mint_address)
+            .map_err(|e| format!("Invalid mint {}: {e}", self.mint_address))?;
+        let collection = Pubkey::from_str(&self.collection)
+            .map_err(|e| format!("Invalid collection {}: {e}", self.collection))?;
+        Ok(CoreAsset {
+            address,
+            collection,
+        })
+    }
+}
+
+/// Extract `key` from a DAS item's plugins.attributes.data.attribute_list
+fn extract_attribute(item: &serde_json::Value, key: &str) -> Option<String> {
     item.get("plugins")?
         .get("attributes")?
         .get("data")?
         .get("attribute_list")?
         .as_array()?
         .iter()
-        .find(|a| a.get("key").and_then(|k| k.as_str()) == Some("card_id"))
+        .find(|a| a.get("key").and_then(|k| k.as_str()) == Some(key))
         .and_then(|a| a.get("value")?.as_str().map(|s| s.to_string()))
 }
 
+/// Whether a DAS item is permanently frozen.
+fn is_soulbound(item: &serde_json::Value) -> bool {
+    item.get("plugins")
+        .and_then(|p| p.get("permanent_freeze_delegate"))
+        .and_then(|f| f.get("data")?.get("frozen")?.as_bool())
+        .unwrap_or(false)
+}
+
 fn extract_name(item: &serde_json::Value) -> String {
     item.get("content")
         .and_then(|c| c.get("metadata"))
@@ -48,27 +360,37 @@ fn extract_name(item: &serde_json::Value) -> String {
         .to_string()
 }
 
-fn is_in_collection(item: &serde_json::Value, collection: &str) -> bool {
-    item.get("grouping")
-        .and_then(|g| g.as_array())
-        .map(|groups| {
-            groups.iter().any(|g| {
-                g.get("group_key").and_then(|k| k.as_str()) == Some("collection")
-                    && g.get("group_value").and_then(|v| v.as_str()) == Some(collection)
-            })
-        })
-        .unwrap_or(false)
+fn extract_json_uri(item: &serde_json::Value) -> String {
+    item.get("content")
+        .and_then(|c| c.get("json_uri"))
+        .and_then(|u| u.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+fn collection_of(item: &serde_json::Value) -> Option<&str> {
+    item.get("grouping")?
+        .as_array()?
+        .iter()
+        .find(|g| g.get("group_key").and_then(|k| k.as_str()) == Some("collection"))?
+        .get("group_value")?
+        .as_str()
 }
 
 impl SolanaConfig {
-    /// Load Solana config from environment variables. Returns None if not configured.
-    pub fn from_env() -> Option<Self> {
-        let keypair_path = std::env::var("SOLANA_KEYPAIR_PATH").ok()?;
-        let rpc_url = std::env::var("SOLANA_RPC_URL").ok()?;
-        let helius_api_key = std::env::var("HELIUS_API_KEY").ok()?;
-        let collection_address = std::env::var("COLLECTION_ADDRESS").ok()?;
-        let public_base_url =
-            std::env::var("PUBLIC_BASE_URL").unwrap_or_else(|_| "http://localhost:3001".into());
+    /// Build from the `[solana]` section. Returns None if not fully configured.
+    /// NFT metadata JSON is written under `cards_dir/metadata`.
+    pub fn from_config(
+        settings: &SolanaSettings,
+        public_base_url: &str,
+        cards_dir: &Path,
+    ) -> Option<Self> {
+        let keypair_path = settings.keypair_path.clone()?;
+        let rpc_url = settings.rpc_url.clone()?;
+        let helius_api_key = settings.helius_api_key.clone()?;
+        let collection_address = settings.collection_address.clone()?;
+        let public_base_url = public_base_url.to_string();
+        let metadata_dir = cards_dir.join("metadata");
 
         let keypair_data = std::fs::read_to_string(&keypair_path)
             .unwrap_or_else(|e| panic!("Failed to read keypair at {keypair_path}: {e}"));
@@ -79,78 +401,428 @@ impl SolanaConfig {
 
         let collection_pubkey = Pubkey::from_str(&collection_address)
             .unwrap_or_else(|e| panic!("Invalid collection address {collection_address}: {e}"));
+        let mut seasons = vec![Season {
+            name: settings.season.clone(),
+            collection: collection_pubkey,
+        }];
+        let mut past: Vec<_> = settings.past_collections.iter().collect();
+        past.sort();
+        for (name, address) in past {
+            let collection = Pubkey::from_str(address).unwrap_or_else(|e| {
+                panic!("Invalid collection address {address} for season {name}: {e}")
+            });
+            if seasons.iter().any(|s| s.name == *name || s.collection == collection) {
+                panic!("Season {name} ({address}) is configured twice");
+            }
+            seasons.push(Season {
+                name: name.clone(),
+                collection,
+            });
+        }
 
-        let rpc_client = RpcClient::new_with_commitment(&rpc_url, CommitmentConfig::confirmed());
+        let commitment = match settings.commitment {
+            SolanaCommitment::Processed => CommitmentConfig::processed(),
+            SolanaCommitment::Confirmed => CommitmentConfig::confirmed(),
+            SolanaCommitment::Finalized => CommitmentConfig::finalized(),
+        };
+        let mut rpc_urls = vec![rpc_url];
+        rpc_urls.extend(settings.fallback_rpc_urls.iter().cloned());
+        let rpc = Rpc::new(
+            &rpc_urls,
+            commitment,
+            Duration::from_secs(settings.rpc_timeout_secs),
+            settings.rpc_retries,
+            Duration::from_secs(settings.rpc_budget_secs),
+        );
+        let das_url = settings.das_url.clone().unwrap_or_else(|| {
+            match settings.cluster {
+                SolanaCluster::Devnet => "https://devnet.helius-rpc.com",
+                SolanaCluster::MainnetBeta => "https://mainnet.helius-rpc.com",
+            }
+            .to_string()
+        });
         let http_client = reqwest::Client::new();
 
-        log::info!("Solana config loaded: collection={collection_address}");
+        log::info!(
+            "Solana config loaded: cluster={}, collection={collection_address} (season {}, \
+             {} past), compute_unit_price={}, compute_unit_limit={}",
+            settings.cluster.name(),
+            settings.season,
+            seasons.len() - 1,
+            settings.compute_unit_price,
+            settings.compute_unit_limit
+        );
 
         Some(SolanaConfig {
-            rpc_client,
+            rpc,
             server_keypair: Arc::new(server_keypair),
             collection_pubkey,
+            seasons,
             public_base_url,
             helius_api_key,
+            cluster: settings.cluster,
+            das_url,
+            das_max_assets: settings.das_max_assets,
+            das_cache_ttl: Duration::from_secs(settings.das_cache_ttl_secs),
+            webhook_auth: settings.webhook_auth.clone().filter(|a| !a.is_empty()),
+            attest_results: settings.attest_results,
+            simulate_transactions: settings.simulate_transactions,
+            owned_cache: Mutex::new(HashMap::new()),
+            metadata_cache: Mutex::new(HashMap::new()),
             http_client,
+            metadata_dir,
+            compute_unit_price: settings.compute_unit_price,
+            compute_unit_limit: settings.compute_unit_limit,
+            soulbound_cards: settings.soulbound_cards.iter().cloned().collect(),
         })
     }
 
-    /// Query owned NFT cards for a wallet using Helius DAS API.
+    /// Whether every mint of `card_id` is soulbound.
+    pub fn is_soulbound(&self, card_id: &str) -> bool {
+        self.soulbound_cards.contains(card_id)
+    }
+
+    /// The plugins a card asset is created with: Attributes with its card id
+    /// and edition, and for soulbound cards (asked for, or in
+    /// `soulbound_cards`) a permanent freeze. A permanent burn delegate held
+    /// by the owner overrides the freeze for burns, so soulbound cards can
+    /// still be combined and burned for essence.
+    fn card_plugins(
+        &self,
+        card_id: &str,
+        edition: u64,
+        soulbound: bool,
+    ) -> Vec<PluginAuthorityPair> {
+        let soulbound = soulbound || self.is_soulbound(card_id);
+        let mut plugins = vec![PluginAuthorityPair {
+            plugin: Plugin::Attributes(Attributes {
+                attribute_list: vec![
+                    Attribute {
+                        key: "card_id".to_string(),
+                        value: card_id.to_string(),
+                    },
+                    Attribute {
+                        key: "edition".to_string(),
+                        value: edition.to_string(),
+                    },
+                ],
+            }),
+            authority: None,
+        }];
+        if soulbound {
+            plugins.push(PluginAuthorityPair {
+                plugin: Plugin::PermanentFreezeDelegate(PermanentFreezeDelegate { frozen: true }),
+                authority: Some(PluginAuthority::UpdateAuthority),
+            });
+            plugins.push(PluginAuthorityPair {
+                plugin: Plugin::PermanentBurnDelegate(PermanentBurnDelegate {}),
+                authority: Some(PluginAuthority::Owner),
+            });
+        }
+        plugins
+    }
+
+    /// Season whose collection is `collection`, if it's one of ours.
+    pub fn season_of(&self, collection: &str) -> Option<&Season> {
+        self.seasons.iter().find(|s| s.collection.to_string() == collection)
+    }
+
+    /// Check that the RPC node is on the configured cluster and that every
+    /// season's collection exists there. Only logs when the node can't be reached, so
+    /// a flaky RPC doesn't keep the server down.
+    pub fn validate(&self) -> Result<(), String> {
+        let expected = match self.cluster {
+            SolanaCluster::Devnet => DEVNET_GENESIS_HASH,
+            SolanaCluster::MainnetBeta => MAINNET_GENESIS_HASH,
+        };
+        // Every endpoint, since a fallback on another cluster would only show
+        // up once the primary fails
+        let mut reachable = false;
+        for (client, host) in self.rpc.endpoints() {
+            match client.get_genesis_hash() {
+                Ok(hash) if hash.to_string() == expected => reachable = true,
+                Ok(hash) => {
+                    return Err(format!(
+                        "Solana RPC {host} is not on {} (genesis hash {hash}); check [solana] \
+                         cluster, rpc_url and fallback_rpc_urls",
+                        self.cluster.name()
+                    ))
+                }
+                Err(e) => log::warn!("Could not check Solana cluster, RPC {host} unreachable: {e}"),
+            }
+        }
+        if !reachable {
+            return Ok(());
+        }
+
+        for season in &self.seasons {
+            let collection = season.collection;
+            let account = match self.rpc.call("getAccountInfo", |c| {
+                Ok(c.get_account_with_commitment(&collection, c.commitment())?)
+            }) {
+                Ok(response) => response.value,
+                Err(e) => {
+                    log::warn!("Could not check collection {collection}, RPC unreachable: {e}");
+                    return Ok(());
+                }
+            };
+            match account {
+                None => {
+                    return Err(format!(
+                        "Collection {collection} (season {}) does not exist on {}",
+                        season.name,
+                        self.cluster.name()
+                    ))
+                }
+                Some(account)
+                    if account.owner != mpl_core::ID
+                        || account.data.first() != Some(&(Key::CollectionV1 as u8)) =>
+                {
+                    return Err(format!("{collection} is not a Metaplex Core collection"))
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Compute budget instructions to put ahead of every transaction's own.
+    fn compute_budget_ixs(&self) -> Vec<Instruction> {
+        let mut ixs = Vec::new();
+        if self.compute_unit_limit > 0 {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(self.compute_unit_limit));
+        }
+        if self.compute_unit_price > 0 {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_price(self.compute_unit_price));
+        }
+        ixs
+    }
+
+    /// Build a v0 transaction paid by `payer` with the compute budget
+    /// prepended, signed by whichever of `signers` it needs. Signatures for
+    /// anyone else (usually the wallet) are left blank for them to fill in.
+    fn build_tx(
+        &self,
+        instructions: Vec<Instruction>,
+        payer: &Pubkey,
+        signers: &[&Keypair],
+    ) -> Result<VersionedTransaction, String> {
+        let mut ixs = self.compute_budget_ixs();
+        ixs.extend(instructions);
+
+        let recent_blockhash = self
+            .rpc
+            .call("getLatestBlockhash", |c| Ok(c.get_latest_blockhash()?))
+            .map_err(|e| format!("Failed to get blockhash: {e}"))?;
+        let message = v0::Message::try_compile(payer, &ixs, &[], recent_blockhash)
+            .map_err(|e| format!("Failed to compile message: {e}"))?;
+        sign_tx(VersionedMessage::V0(message), signers)
+    }
+
+    /// Keypair of a new asset, derived from the server's key so only `seed`
+    /// has to be kept to sign for it again.
+    fn asset_keypair(&self, seed: &str) -> Result<Keypair, String> {
+        let hash = Sha256::new()
+            .chain_update(self.server_keypair.to_bytes())
+            .chain_update(b"asset:")
+            .chain_update(seed.as_bytes())
+            .finalize();
+        keypair_from_seed(&hash).map_err(|e| format!("Failed to derive asset keypair: {e}"))
+    }
+
+    /// Address of the asset a `build_mint_tx` or `build_burn_and_mint_tx`
+    /// with `seed` creates, known before the transaction is built.
+    pub fn asset_address(&self, seed: &str) -> Result<Pubkey, String> {
+        Ok(self.asset_keypair(seed)?.pubkey())
+    }
+
+    /// Seed for a new asset's keypair.
+    pub fn new_asset_seed() -> String {
+        uuid::Uuid::new_v4().simple().to_string()
+    }
+
+    /// Sign a mint from `build_mint_tx` or `build_burn_and_mint_tx` again
+    /// with a fresh blockhash, for a wallet that took too long to sign it.
+    /// The asset address stays the same, so at most one of the two can land.
+    #[tracing::instrument(name = "solana.refresh_mint_tx", skip_all)]
+    pub fn refresh_mint_tx(&self, transaction: &str, asset_seed: &str) -> Result<String, TxError> {
+        let mut message = decode_tx(transaction)?.message;
+        let recent_blockhash = self
+            .rpc
+            .call("getLatestBlockhash", |c| Ok(c.get_latest_blockhash()?))
+            .map_err(|e| format!("Failed to get blockhash: {e}"))?;
+        message.set_recent_blockhash(recent_blockhash);
+        let asset_keypair = self.asset_keypair(asset_seed)?;
+        let tx = sign_tx(message, &[&*self.server_keypair, &asset_keypair])?;
+        self.simulate(&tx)?;
+        Ok(encode_tx(&tx)?)
+    }
+
+    /// Simulate `tx` against the current chain state. Signatures aren't
+    /// checked, since the wallet hasn't signed yet. Lets the transaction
+    /// through when simulation is off or the RPC call itself fails.
+    #[tracing::instrument(name = "solana.simulate", skip_all)]
+    fn simulate(&self, tx: &VersionedTransaction) -> Result<(), TxError> {
+        if !self.simulate_transactions {
+            return Ok(());
+        }
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: false,
+            commitment: Some(self.rpc.commitment()),
+            ..Default::default()
+        };
+        let result = match self.rpc.call("simulateTransaction", |c| {
+            Ok(c.simulate_transaction_with_config(tx, config.clone())?)
+        }) {
+            Ok(response) => response.value,
+            Err(e) => {
+                log::warn!("Could not simulate transaction: {e}");
+                return Ok(());
+            }
+        };
+        let Some(error) = result.err else {
+            return Ok(());
+        };
+        let logs = result.logs.unwrap_or_default();
+        let failure = tx_failure(&tx.message, &error, &logs);
+        log::info!("Transaction would fail ({failure:?}): {error}");
+        Err(TxError::WouldFail {
+            failure,
+            error: error.to_string(),
+            logs,
+        })
+    }
+
+    /// Owned NFT cards for a wallet, reusing an answer younger than
+    /// `das_cache_ttl`.
+    #[tracing::instrument(name = "solana.query_owned_cards", skip(self))]
     pub async fn query_owned_cards(&self, wallet: &str) -> Result<Vec<OwnedCard>, String> {
-        let wallet_pubkey =
-            Pubkey::from_str(wallet).map_err(|e| format!("Invalid wallet address: {e}"))?;
+        if let Some((fetched, Some(cards))) = self.owned_cache.lock().unwrap().get(wallet) {
+            if fetched.elapsed() < self.das_cache_ttl {
+                return Ok(cards.clone());
+            }
+        }
 
-        let rpc_url = format!(
-            "https://devnet.helius-rpc.com/?api-key={}",
-            self.helius_api_key
-        );
+        let started = Instant::now();
+        let cards = self.fetch_owned_cards(wallet).await?;
+        if !self.das_cache_ttl.is_zero() {
+            let mut cache = self.owned_cache.lock().unwrap();
+            cache.retain(|_, (fetched, _)| fetched.elapsed() < self.das_cache_ttl);
+            // Don't overwrite a newer answer, or a forget from a mint or
+            // burn that finished while we were fetching
+            if cache.get(wallet).is_none_or(|(at, _)| *at < started) {
+                cache.insert(wallet.to_string(), (started, Some(cards.clone())));
+            }
+        }
+        Ok(cards)
+    }
 
-        let body = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": "alchemaybe",
-            "method": "getAssetsByOwner",
-            "params": {
-                "ownerAddress": wallet_pubkey.to_string(),
-                "page": 1,
-                "limit": 1000
+    /// Forget the cached cards of `wallet` after a transaction that changed
+    /// what it owns.
+    pub fn forget_owned_cards(&self, wallet: &str) {
+        if !self.das_cache_ttl.is_zero() {
+            let mut cache = self.owned_cache.lock().unwrap();
+            cache.insert(wallet.to_string(), (Instant::now(), None));
+        }
+    }
+
+    /// The off-chain metadata at `uri`, for showing a card this server
+    /// doesn't know (say, one crafted on another instance). Answers are
+    /// reused for `METADATA_TTL`, and failures for `METADATA_RETRY`.
+    #[tracing::instrument(name = "solana.card_metadata", skip(self))]
+    pub async fn card_metadata(&self, uri: &str) -> Option<CardMetadata> {
+        if let Some((fetched, metadata)) = self.metadata_cache.lock().unwrap().get(uri) {
+            let ttl = if metadata.is_some() { METADATA_TTL } else { METADATA_RETRY };
+            if fetched.elapsed() < ttl {
+                return metadata.clone();
             }
-        });
+        }
 
+        let metadata = self
+            .fetch_card_metadata(uri)
+            .await
+            .inspect_err(|e| log::warn!("Failed to fetch card metadata from {uri}: {e}"))
+            .ok();
+        let mut cache = self.metadata_cache.lock().unwrap();
+        if cache.len() >= METADATA_CACHE_MAX {
+            cache.clear();
+        }
+        cache.insert(uri.to_string(), (Instant::now(), metadata.clone()));
+        metadata
+    }
+
+    async fn fetch_card_metadata(&self, uri: &str) -> Result<CardMetadata, String> {
+        let url = reqwest::Url::parse(uri).map_err(|e| format!("Invalid URI: {e}"))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Unsupported scheme {}", url.scheme()));
+        }
         let resp = self
             .http_client
-            .post(&rpc_url)
-            .json(&body)
+            .get(url)
+            .timeout(METADATA_TIMEOUT)
             .send()
             .await
-            .map_err(|e| format!("DAS request failed: {e}"))?;
-
+            .map_err(|e| format!("Request failed: {e}"))?;
         if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(format!("DAS returned {status}: {body}"));
+            return Err(format!("Returned {}", resp.status()));
         }
+        if resp.content_length().is_some_and(|len| len > METADATA_MAX_BYTES as u64) {
+            return Err("Too large".to_string());
+        }
+        let body = resp.bytes().await.map_err(|e| format!("Read failed: {e}"))?;
+        if body.len() > METADATA_MAX_BYTES {
+            return Err("Too large".to_string());
+        }
+        let mut metadata: CardMetadata =
+            serde_json::from_slice(&body).map_err(|e| format!("Parse error: {e}"))?;
+        if !metadata.image.starts_with("https://") && !metadata.image.starts_with("http://") {
+            metadata.image.clear();
+        }
+        Ok(metadata)
+    }
 
-        let das_resp: serde_json::Value = resp
-            .json()
-            .await
-            .map_err(|e| format!("DAS parse error: {e}"))?;
+    /// Query owned NFT cards for a wallet using Helius DAS API. When the
+    /// first page is full, the rest are fetched `DAS_CONCURRENCY` at a time
+    /// until one comes back short, or `das_max_assets` have been read.
+    /// Owned NFT cards for a wallet, straight from DAS.
+    pub async fn fetch_owned_cards(&self, wallet: &str) -> Result<Vec<OwnedCard>, String> {
+        let wallet_pubkey =
+            Pubkey::from_str(wallet).map_err(|e| format!("Invalid wallet address: {e}"))?;
 
-        let items = das_resp
-            .get("result")
-            .and_then(|r| r.get("items"))
-            .and_then(|i| i.as_array())
-            .cloned()
-            .unwrap_or_default();
+        let max_pages = self.das_max_assets.div_ceil(DAS_PAGE_LIMIT).max(1);
+        let mut items = Vec::new();
+        let mut next_page = 1;
+        let mut done = false;
+        while !done && next_page <= max_pages {
+            // Most wallets fit on one page, so only fan out after a full one
+            let window = if next_page == 1 { 1 } else { DAS_CONCURRENCY };
+            let last = (next_page + window - 1).min(max_pages);
+            let pages = futures::future::try_join_all(
+                (next_page..=last).map(|page| self.fetch_das_page(&wallet_pubkey, page)),
+            )
+            .await?;
+            for page in pages {
+                done |= page.len() < DAS_PAGE_LIMIT;
+                items.extend(page);
+            }
+            next_page = last + 1;
+        }
+        if !done {
+            log::warn!(
+                "Wallet {wallet} holds more than {} assets; only those were read",
+                max_pages * DAS_PAGE_LIMIT
+            );
+        }
 
-        let collection_str = self.collection_pubkey.to_string();
         let mut cards = Vec::new();
 
         for item in &items {
-            if !is_in_collection(item, &collection_str) {
+            let Some(season) = collection_of(item).and_then(|c| self.season_of(c)) else {
                 continue;
-            }
+            };
 
-            let card_id = match extract_card_id(item) {
+            let card_id = match extract_attribute(item, "card_id") {
                 Some(id) if !id.is_empty() => id,
                 _ => continue,
             };
@@ -163,121 +835,238 @@ impl SolanaConfig {
                 card_id,
                 name,
                 image: String::new(),
+                metadata_uri: extract_json_uri(item),
+                collection: season.collection.to_string(),
+                season: season.name.clone(),
+                edition: extract_attribute(item, "edition").and_then(|e| e.parse().ok()),
+                soulbound: is_soulbound(item),
             });
         }
 
         Ok(cards)
     }
 
-    /// Build a mint transaction for a single card. Server partial-signs.
-    /// Returns (base64 serialized transaction, new asset pubkey string).
-    pub fn build_mint_tx(
+    /// One page (1-based) of `getAssetsByOwner`.
+    async fn fetch_das_page(
         &self,
-        card_id: &str,
-        name: &str,
-        metadata_uri: &str,
-        recipient: &Pubkey,
-    ) -> Result<(String, String), String> {
-        let asset_keypair = Keypair::new();
-        let asset_pubkey = asset_keypair.pubkey();
+        owner: &Pubkey,
+        page: usize,
+    ) -> Result<Vec<serde_json::Value>, String> {
+        let separator = if self.das_url.contains('?') { '&' } else { '?' };
+        let rpc_url = format!("{}{separator}api-key={}", self.das_url, self.helius_api_key);
 
-        let create_ix = CreateV1Builder::new()
-            .asset(asset_pubkey)
-            .collection(Some(self.collection_pubkey))
-            .authority(Some(self.server_keypair.pubkey()))
-            .payer(recipient.clone())
-            .owner(Some(recipient.clone()))
-            .name(name.to_string())
-            .uri(metadata_uri.to_string())
-            .plugins(vec![PluginAuthorityPair {
-                plugin: Plugin::Attributes(Attributes {
-                    attribute_list: vec![Attribute {
-                        key: "card_id".to_string(),
-                        value: card_id.to_string(),
-                    }],
-                }),
-                authority: None,
-            }])
-            .instruction();
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": "alchemaybe",
+            "method": "getAssetsByOwner",
+            "params": {
+                "ownerAddress": owner.to_string(),
+                "page": page,
+                "limit": DAS_PAGE_LIMIT
+            }
+        });
 
-        let recent_blockhash = self
-            .rpc_client
-            .get_latest_blockhash()
-            .map_err(|e| format!("Failed to get blockhash: {e}"))?;
+        let resp = self
+            .http_client
+            .post(&rpc_url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("DAS request failed: {e}"))?;
 
-        let mut tx = Transaction::new_with_payer(&[create_ix], Some(recipient));
-        tx.partial_sign(&[&*self.server_keypair, &asset_keypair], recent_blockhash);
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("DAS returned {status}: {body}"));
+        }
+
+        let das_resp: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| format!("DAS parse error: {e}"))?;
+        if let Some(error) = das_resp.get("error") {
+            return Err(format!("DAS error on page {page}: {error}"));
+        }
 
-        let serialized = bincode::serialize(&tx)
-            .map_err(|e| format!("Failed to serialize tx: {e}"))?;
-        let b64 = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &serialized);
+        Ok(das_resp
+            .get("result")
+            .and_then(|r| r.get("items"))
+            .and_then(|i| i.as_array())
+            .cloned()
+            .unwrap_or_default())
+    }
 
-        Ok((b64, asset_pubkey.to_string()))
+    /// Build a mint transaction for a single card. Server partial-signs.
+    /// Returns the transaction, or why it would fail.
+    #[tracing::instrument(name = "solana.build_mint_tx", skip_all, fields(card_id = card.card_id, edition = card.edition))]
+    pub fn build_mint_tx(&self, card: &NewCard, recipient: &Pubkey) -> Result<MintTx, TxError> {
+        self.mint_tx(Vec::new(), card, recipient)
     }
 
     /// Build an atomic burn+mint transaction: burns N input NFTs, mints 1 new one.
-    /// Server partial-signs. Returns (base64 tx, new asset pubkey string).
+    /// Server partial-signs. Returns the transaction, or why it would fail.
+    #[tracing::instrument(name = "solana.build_burn_and_mint_tx", skip_all, fields(burns = burn_assets.len(), card_id = card.card_id, edition = card.edition))]
     pub fn build_burn_and_mint_tx(
         &self,
-        burn_mints: &[Pubkey],
-        new_card_id: &str,
-        new_name: &str,
-        new_metadata_uri: &str,
+        burn_assets: &[CoreAsset],
+        card: &NewCard,
         owner: &Pubkey,
-    ) -> Result<(String, String), String> {
-        let mut instructions = Vec::new();
-
-        // Burn instructions for each input NFT
-        for mint in burn_mints {
-            let burn_ix = BurnV1Builder::new()
-                .asset(*mint)
-                .collection(Some(self.collection_pubkey))
-                .payer(*owner)
-                .authority(Some(*owner))
-                .instruction();
-            instructions.push(burn_ix);
-        }
+    ) -> Result<MintTx, TxError> {
+        self.mint_tx(self.burn_ixs(burn_assets, owner), card, owner)
+    }
 
-        // Create instruction for the new NFT
-        let asset_keypair = Keypair::new();
+    /// `instructions`, then one creating `card` for `owner`, who pays.
+    fn mint_tx(
+        &self,
+        mut instructions: Vec<Instruction>,
+        card: &NewCard,
+        owner: &Pubkey,
+    ) -> Result<MintTx, TxError> {
+        let asset_keypair = self.asset_keypair(card.asset_seed)?;
         let asset_pubkey = asset_keypair.pubkey();
 
         let create_ix = CreateV1Builder::new()
             .asset(asset_pubkey)
             .collection(Some(self.collection_pubkey))
             .authority(Some(self.server_keypair.pubkey()))
-            .payer(owner.clone())
-            .owner(Some(owner.clone()))
-            .name(new_name.to_string())
-            .uri(new_metadata_uri.to_string())
-            .plugins(vec![PluginAuthorityPair {
-                plugin: Plugin::Attributes(Attributes {
-                    attribute_list: vec![Attribute {
-                        key: "card_id".to_string(),
-                        value: new_card_id.to_string(),
-                    }],
-                }),
-                authority: None,
-            }])
+            .payer(*owner)
+            .owner(Some(*owner))
+            .name(card.name.to_string())
+            .uri(card.metadata_uri.to_string())
+            .plugins(self.card_plugins(card.card_id, card.edition, false))
             .instruction();
         instructions.push(create_ix);
 
-        let recent_blockhash = self
-            .rpc_client
-            .get_latest_blockhash()
-            .map_err(|e| format!("Failed to get blockhash: {e}"))?;
+        let tx = self.build_tx(
+            instructions,
+            owner,
+            &[&*self.server_keypair, &asset_keypair],
+        )?;
+        self.simulate(&tx)?;
 
-        let mut tx = Transaction::new_with_payer(&instructions, Some(owner));
-        tx.partial_sign(&[&*self.server_keypair, &asset_keypair], recent_blockhash);
+        Ok(MintTx {
+            transaction: encode_tx(&tx)?,
+            asset_address: asset_pubkey.to_string(),
+            asset_seed: card.asset_seed.to_string(),
+        })
+    }
 
-        let serialized = bincode::serialize(&tx)
-            .map_err(|e| format!("Failed to serialize tx: {e}"))?;
-        let b64 = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &serialized);
+    /// Burn instructions for each of `owner`'s `assets`.
+    fn burn_ixs(&self, assets: &[CoreAsset], owner: &Pubkey) -> Vec<Instruction> {
+        assets
+            .iter()
+            .map(|asset| {
+                BurnV1Builder::new()
+                    .asset(asset.address)
+                    .collection(Some(asset.collection))
+                    .payer(*owner)
+                    .authority(Some(*owner))
+                    .instruction()
+            })
+            .collect()
+    }
+
+    /// Build a transaction burning `owner`'s `assets`. Only the owner signs.
+    #[tracing::instrument(name = "solana.build_burn_tx", skip(self))]
+    pub fn build_burn_tx(&self, assets: &[CoreAsset], owner: &Pubkey) -> Result<String, String> {
+        let tx = self.build_tx(self.burn_ixs(assets, owner), owner, &[])?;
+        encode_tx(&tx)
+    }
+
+    /// Transfer instruction moving `asset` from `owner` to `recipient`,
+    /// with `payer` covering any rent.
+    fn transfer_ix(
+        &self,
+        asset: &CoreAsset,
+        owner: &Pubkey,
+        recipient: &Pubkey,
+        payer: &Pubkey,
+    ) -> Instruction {
+        TransferV1Builder::new()
+            .asset(asset.address)
+            .collection(Some(asset.collection))
+            .payer(*payer)
+            .authority(Some(*owner))
+            .new_owner(*recipient)
+            .instruction()
+    }
+
+    /// Build a transfer of `asset` from `owner` to `recipient`. Only the
+    /// owner signs: the collection has no plugins needing our authority.
+    #[tracing::instrument(name = "solana.build_transfer_tx", skip(self))]
+    pub fn build_transfer_tx(
+        &self,
+        asset: &CoreAsset,
+        owner: &Pubkey,
+        recipient: &Pubkey,
+    ) -> Result<String, String> {
+        let transfer_ix = self.transfer_ix(asset, owner, recipient, owner);
+        let tx = self.build_tx(vec![transfer_ix], owner, &[])?;
+        encode_tx(&tx)
+    }
 
-        Ok((b64, asset_pubkey.to_string()))
+    /// Wallet holding cards offered in open trades: the server's own.
+    pub fn escrow(&self) -> Pubkey {
+        self.server_keypair.pubkey()
+    }
+
+    /// Build a transaction moving `owner`'s `assets` into escrow. Only the
+    /// owner signs.
+    #[tracing::instrument(name = "solana.build_escrow_tx", skip(self))]
+    pub fn build_escrow_tx(&self, assets: &[CoreAsset], owner: &Pubkey) -> Result<String, String> {
+        let escrow = self.escrow();
+        let ixs = assets
+            .iter()
+            .map(|asset| self.transfer_ix(asset, owner, &escrow, owner))
+            .collect();
+        let tx = self.build_tx(ixs, owner, &[])?;
+        encode_tx(&tx)
+    }
+
+    /// Build the swap settling a trade: `offered` goes from escrow to `taker`
+    /// and `requested` from `taker` to `proposer`, all or nothing. The taker
+    /// pays; the server partial-signs for the escrow.
+    #[tracing::instrument(name = "solana.build_trade_tx", skip(self))]
+    pub fn build_trade_tx(
+        &self,
+        offered: &[CoreAsset],
+        requested: &[CoreAsset],
+        proposer: &Pubkey,
+        taker: &Pubkey,
+    ) -> Result<String, String> {
+        let escrow = self.escrow();
+        let mut ixs: Vec<Instruction> = offered
+            .iter()
+            .map(|asset| self.transfer_ix(asset, &escrow, taker, taker))
+            .collect();
+        ixs.extend(requested.iter().map(|asset| self.transfer_ix(asset, taker, proposer, taker)));
+        let tx = self.build_tx(ixs, taker, &[&*self.server_keypair])?;
+        encode_tx(&tx)
+    }
+
+    /// Send `assets` from escrow back to `owner` (server pays). Returns the
+    /// transaction signature.
+    #[tracing::instrument(name = "solana.return_from_escrow", skip(self))]
+    pub fn return_from_escrow(
+        &self,
+        assets: &[CoreAsset],
+        owner: &Pubkey,
+    ) -> Result<String, String> {
+        let escrow = self.escrow();
+        let ixs = assets
+            .iter()
+            .map(|asset| self.transfer_ix(asset, &escrow, owner, &escrow))
+            .collect();
+        let tx = self.build_tx(ixs, &escrow, &[&*self.server_keypair])?;
+        let sig = self
+            .rpc
+            .send_and_confirm(&tx)
+            .map_err(|e| format!("Escrow return failed: {e}"))?;
+        Ok(sig.to_string())
     }
 
     /// Build a SOL payment transaction from buyer to server. Buyer signs.
+    #[tracing::instrument(name = "solana.build_payment_tx", skip(self))]
     pub fn build_payment_tx(
         &self,
         price_lamports: u64,
@@ -289,24 +1078,127 @@ impl SolanaConfig {
             price_lamports,
         );
 
-        let recent_blockhash = self
-            .rpc_client
-            .get_latest_blockhash()
-            .map_err(|e| format!("Failed to get blockhash: {e}"))?;
-
-        let mut tx = Transaction::new_with_payer(&[transfer_ix], Some(buyer));
         // Only buyer signs — no server signature needed for a simple transfer
-        tx.partial_sign(&[] as &[&Keypair], recent_blockhash);
+        let tx = self.build_tx(vec![transfer_ix], buyer, &[])?;
 
-        let serialized = bincode::serialize(&tx)
-            .map_err(|e| format!("Failed to serialize tx: {e}"))?;
-        Ok(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &serialized))
+        encode_tx(&tx)
+    }
+
+    /// Check that the transaction `signature` landed, and that `payer`
+    /// signed it and paid the server at least `lamports`, the way
+    /// `build_payment_tx` has it.
+    #[tracing::instrument(name = "solana.verify_payment", skip(self))]
+    pub fn verify_payment(
+        &self,
+        signature: &str,
+        payer: &Pubkey,
+        lamports: u64,
+    ) -> Result<(), PaymentError> {
+        let sig = Signature::from_str(signature)
+            .map_err(|e| PaymentError::Unpaid(format!("Invalid payment signature: {e}")))?;
+        let landed = self
+            .landed_tx(&sig)
+            .map_err(|e| format!("Failed to look up payment {sig}: {e}"))?;
+        if landed.is_null() {
+            return Err(PaymentError::Unpaid(format!("Payment {sig} hasn't landed yet")));
+        }
+        if !landed["meta"]["err"].is_null() {
+            return Err(PaymentError::Unpaid(format!("Payment {sig} failed")));
+        }
+        let tx = landed["transaction"][0]
+            .as_str()
+            .ok_or_else(|| format!("Payment {sig} came back without its transaction"))
+            .and_then(decode_tx)?;
+        let paid = paid_lamports(&tx, payer, &self.server_keypair.pubkey());
+        if paid < lamports {
+            return Err(PaymentError::Unpaid(format!(
+                "Payment {sig} pays {paid} lamports from {payer}, not {lamports}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Confirmed, not just processed: processed transactions can still be
+    /// dropped.
+    fn landed_commitment(&self) -> CommitmentConfig {
+        match self.rpc.commitment() {
+            c if c.is_at_least_confirmed() => c,
+            _ => CommitmentConfig::confirmed(),
+        }
+    }
+
+    /// `getTransaction` for `sig`: null until it lands, then the transaction
+    /// (base64) and its `meta`.
+    fn landed_tx(&self, sig: &Signature) -> Result<serde_json::Value, String> {
+        let params = serde_json::json!([sig.to_string(), {
+            "encoding": "base64",
+            "commitment": self.landed_commitment().commitment,
+            "maxSupportedTransactionVersion": 0,
+        }]);
+        self.rpc
+            .call("getTransaction", |c| Ok(c.send(RpcRequest::GetTransaction, params.clone())?))
+            .map_err(|e| e.to_string())
+    }
+
+    /// The transaction, landed since `since` (unix secs), in which `owner`
+    /// signed the transfers `build_escrow_tx` builds for `assets`. Looks
+    /// through the latest transactions touching the first asset.
+    #[tracing::instrument(name = "solana.find_escrow_tx", skip(self))]
+    pub fn find_escrow_tx(
+        &self,
+        assets: &[CoreAsset],
+        owner: &Pubkey,
+        since: u64,
+    ) -> Result<Option<String>, String> {
+        let Some(first) = assets.first() else {
+            return Ok(None);
+        };
+        let escrow = self.escrow();
+        let transfers: Vec<Instruction> = assets
+            .iter()
+            .map(|asset| self.transfer_ix(asset, owner, &escrow, owner))
+            .collect();
+        let config = || GetConfirmedSignaturesForAddress2Config {
+            limit: Some(ESCROW_SEARCH_LIMIT),
+            commitment: Some(self.landed_commitment()),
+            ..Default::default()
+        };
+        let recent = self
+            .rpc
+            .call("getSignaturesForAddress", |c| {
+                Ok(c.get_signatures_for_address_with_config(&first.address, config())?)
+            })
+            .map_err(|e| format!("Failed to list transactions of {}: {e}", first.address))?;
+        // Newest first
+        for status in recent {
+            if status.block_time.is_some_and(|t| (t.max(0) as u64) < since) {
+                break;
+            }
+            if status.err.is_some() {
+                continue;
+            }
+            let sig = Signature::from_str(&status.signature).map_err(|e| e.to_string())?;
+            let landed = self
+                .landed_tx(&sig)
+                .map_err(|e| format!("Failed to look up {sig}: {e}"))?;
+            let Some(tx) = landed["transaction"][0].as_str() else {
+                continue;
+            };
+            if carries_all(&decode_tx(tx)?, owner, &transfers) {
+                return Ok(Some(status.signature));
+            }
+        }
+        Ok(None)
     }
 
     /// Mint a card fully server-side (server pays). Returns tx signature and asset pubkey.
+    /// With `soulbound`, the card is minted non-transferable whatever `soulbound_cards` says.
+    #[tracing::instrument(name = "solana.server_mint", skip(self, metadata_uri))]
     pub fn server_mint(
         &self,
         card_id: &str,
+        edition: u64,
+        soulbound: bool,
         name: &str,
         metadata_uri: &str,
         recipient: &Pubkey,
@@ -322,67 +1214,105 @@ impl SolanaConfig {
             .owner(Some(*recipient))
             .name(name.to_string())
             .uri(metadata_uri.to_string())
-            .plugins(vec![PluginAuthorityPair {
-                plugin: Plugin::Attributes(Attributes {
-                    attribute_list: vec![Attribute {
-                        key: "card_id".to_string(),
-                        value: card_id.to_string(),
-                    }],
-                }),
-                authority: None,
-            }])
+            .plugins(self.card_plugins(card_id, edition, soulbound))
             .instruction();
 
-        let recent_blockhash = self
-            .rpc_client
-            .get_latest_blockhash()
-            .map_err(|e| format!("Failed to get blockhash: {e}"))?;
-
-        let tx = Transaction::new_signed_with_payer(
-            &[create_ix],
-            Some(&self.server_keypair.pubkey()),
+        let tx = self.build_tx(
+            vec![create_ix],
+            &self.server_keypair.pubkey(),
             &[&*self.server_keypair, &asset_keypair],
-            recent_blockhash,
-        );
+        )?;
 
         let sig = self
-            .rpc_client
-            .send_and_confirm_transaction(&tx)
+            .rpc
+            .send_and_confirm(&tx)
             .map_err(|e| format!("Mint failed: {e}"))?;
 
         Ok((sig.to_string(), asset_pubkey.to_string()))
     }
 
+    /// Write `memo` on-chain from the server wallet (server pays). Returns the
+    /// transaction signature.
+    #[tracing::instrument(name = "solana.send_memo", skip(self))]
+    pub fn send_memo(&self, memo: &str) -> Result<String, String> {
+        let server = self.server_keypair.pubkey();
+        let memo_ix = Instruction::new_with_bytes(
+            Pubkey::from_str(MEMO_PROGRAM_ID).expect("valid memo program id"),
+            memo.as_bytes(),
+            vec![AccountMeta::new_readonly(server, true)],
+        );
+        let tx = self.build_tx(vec![memo_ix], &server, &[&*self.server_keypair])?;
+        let sig = self
+            .rpc
+            .send_and_confirm(&tx)
+            .map_err(|e| format!("Memo failed: {e}"))?;
+        Ok(sig.to_string())
+    }
+
     /// Ensure metadata JSON file exists for a card. Returns the public URI.
+    /// Foil copies get their own file, showing the foil image, as do single
+    /// assets with an image of their own (`asset`). Animated cards link the
+    /// APNG variant of the image too.
     pub fn ensure_metadata_json(
         &self,
         card_id: &str,
         name: &str,
         description: &str,
         image_path: &str,
+        finish: Finish,
+        asset: Option<&str>,
     ) -> Result<String, String> {
-        let dir = "cards/metadata";
-        let _ = std::fs::create_dir_all(dir);
+        let _ = std::fs::create_dir_all(&self.metadata_dir);
 
-        let filename = format!("{card_id}.json");
-        let disk_path = format!("{dir}/{filename}");
+        let (filename, image_path) = match asset {
+            Some(asset) => (format!("{asset}.json"), image_path.to_string()),
+            None if finish.foil => {
+                (format!("{card_id}-foil.json"), image_variants::foil_url(image_path))
+            }
+            None => (format!("{card_id}.json"), image_path.to_string()),
+        };
+        let disk_path = self.metadata_dir.join(&filename);
         let public_uri = format!("{}/cards/metadata/{filename}", self.public_base_url);
 
         // Build image URL from the serve path
-        let image_url = if image_path.starts_with("http") {
-            image_path.to_string()
-        } else {
+        let served_here = !image_path.starts_with("http");
+        let image_url = if served_here {
             format!("{}{image_path}", self.public_base_url)
+        } else {
+            image_path
         };
 
-        let metadata = serde_json::json!({
+        // The back of the card, from the season it's minted into
+        let back_url =
+            format!("{}{}", self.public_base_url, card::card_back_path(&self.seasons[0].name));
+
+        let mut attributes = vec![serde_json::json!({ "trait_type": "card_id", "value": card_id })];
+        if finish.foil {
+            attributes.push(serde_json::json!({ "trait_type": "finish", "value": "foil" }));
+        }
+        let mut files = vec![
+            serde_json::json!({ "uri": image_url, "type": "image/png" }),
+            serde_json::json!({ "uri": back_url, "type": "image/png" }),
+        ];
+        // Only our own images have animated variants
+        let animation_url = (finish.animated && served_here)
+            .then(|| image_variants::animated_url(&image_url));
+        if let Some(url) = &animation_url {
+            files.push(serde_json::json!({ "uri": url, "type": "image/apng" }));
+        }
+        let mut metadata = serde_json::json!({
             "name": name,
             "description": description,
             "image": image_url,
-            "attributes": [
-                { "trait_type": "card_id", "value": card_id }
-            ]
+            "attributes": attributes,
+            "properties": {
+                "category": "image",
+                "files": files,
+            },
         });
+        if let Some(url) = animation_url {
+            metadata["animation_url"] = serde_json::json!(url);
+        }
 
         let data = serde_json::to_string_pretty(&metadata)
             .map_err(|e| format!("JSON serialize error: {e}"))?;
@@ -392,22 +1322,166 @@ impl SolanaConfig {
         Ok(public_uri)
     }
 
+    /// Delete the metadata JSON of the single asset `asset`, if there is one.
+    pub fn remove_metadata_json(&self, asset: &str) -> Result<(), String> {
+        match std::fs::remove_file(self.metadata_dir.join(format!("{asset}.json"))) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to remove metadata: {e}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether an account (e.g. a freshly minted asset) exists yet.
+    pub fn account_exists(&self, address: &Pubkey) -> Result<bool, String> {
+        self.rpc
+            .call("getAccountInfo", |c| {
+                Ok(c.get_account_with_commitment(address, c.commitment())?)
+            })
+            .map(|r| r.value.is_some())
+            .map_err(|e| format!("Failed to look up {address}: {e}"))
+    }
+
+    /// Current owner of a Core asset and the asset itself, or None if it's
+    /// burned or never existed.
+    pub fn asset_owner(&self, asset: &Pubkey) -> Result<Option<(Pubkey, CoreAsset)>, String> {
+        let account = self
+            .rpc
+            .call("getAccountInfo", |c| {
+                Ok(c.get_account_with_commitment(asset, c.commitment())?)
+            })
+            .map_err(|e| format!("Failed to look up {asset}: {e}"))?
+            .value;
+        match account {
+            Some(account)
+                if account.owner == mpl_core::ID
+                    && account.data.first() == Some(&(Key::AssetV1 as u8)) =>
+            {
+                let base = BaseAssetV1::from_bytes(&account.data)
+                    .map_err(|e| format!("{asset} is not a Core asset: {e}"))?;
+                let UpdateAuthority::Collection(collection) = base.update_authority else {
+                    return Err(format!("{asset} is not in a collection"));
+                };
+                let core_asset = CoreAsset {
+                    address: *asset,
+                    collection,
+                };
+                Ok(Some((base.owner, core_asset)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Ping the RPC nodes, healthy while any of them is. Blocking, like the
+    /// rest of the RPC calls here.
+    #[tracing::instrument(name = "solana.check_health", skip_all)]
+    pub fn check_health(&self) -> Result<(), String> {
+        self.rpc
+            .call("getHealth", |c| Ok(c.get_health()?))
+            .map_err(|e| format!("Solana RPC unhealthy: {e}"))
+    }
+
     /// Submit a fully-signed transaction to the network.
+    #[tracing::instrument(name = "solana.submit_transaction", skip_all)]
     pub fn submit_transaction(&self, signed_tx_base64: &str) -> Result<String, String> {
-        let bytes = base64::Engine::decode(
-            &base64::engine::general_purpose::STANDARD,
-            signed_tx_base64,
-        )
-        .map_err(|e| format!("Base64 decode error: {e}"))?;
-
-        let tx: Transaction = bincode::deserialize(&bytes)
-            .map_err(|e| format!("Transaction deserialize error: {e}"))?;
+        let tx = decode_tx(signed_tx_base64)?;
 
         let sig = self
-            .rpc_client
-            .send_and_confirm_transaction(&tx)
+            .rpc
+            .send_and_confirm(&tx)
             .map_err(|e| format!("Transaction failed: {e}"))?;
 
         Ok(sig.to_string())
     }
 }
+
+/// Sign `message` by whichever of `signers` it needs, leaving the other
+/// signatures blank.
+fn sign_tx(
+    message: VersionedMessage,
+    signers: &[&Keypair],
+) -> Result<VersionedTransaction, String> {
+    let required = message.header().num_required_signatures as usize;
+    let mut signatures = vec![Signature::default(); required];
+    let message_bytes = message.serialize();
+    for signer in signers {
+        let pubkey = signer.pubkey();
+        let slot = message.static_account_keys()[..required]
+            .iter()
+            .position(|k| *k == pubkey)
+            .ok_or_else(|| format!("{pubkey} is not a signer of this transaction"))?;
+        signatures[slot] = signer.sign_message(&message_bytes);
+    }
+
+    Ok(VersionedTransaction {
+        signatures,
+        message,
+    })
+}
+
+fn decode_tx(tx_base64: &str) -> Result<VersionedTransaction, String> {
+    let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, tx_base64)
+        .map_err(|e| format!("Base64 decode error: {e}"))?;
+    // Legacy transactions deserialize as versioned ones too
+    bincode::deserialize(&bytes).map_err(|e| format!("Transaction deserialize error: {e}"))
+}
+
+fn encode_tx(tx: &VersionedTransaction) -> Result<String, String> {
+    let serialized =
+        bincode::serialize(tx).map_err(|e| format!("Failed to serialize tx: {e}"))?;
+    Ok(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &serialized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::Hash;
+    use solana_system_interface::instruction::transfer;
+
+    fn payment(payer: &Keypair, ixs: &[Instruction]) -> VersionedTransaction {
+        let message = v0::Message::try_compile(&payer.pubkey(), ixs, &[], Hash::default())
+            .expect("compiles");
+        sign_tx(VersionedMessage::V0(message), &[payer]).expect("signs")
+    }
+
+    #[test]
+    fn paid_lamports_counts_transfers_from_payer_to_recipient() {
+        let payer = Keypair::new();
+        let server = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let tx = payment(
+            &payer,
+            &[
+                transfer(&payer.pubkey(), &server, 600),
+                transfer(&payer.pubkey(), &other, 1_000),
+                transfer(&payer.pubkey(), &server, 400),
+            ],
+        );
+        assert_eq!(paid_lamports(&tx, &payer.pubkey(), &server), 1_000);
+        assert_eq!(paid_lamports(&tx, &payer.pubkey(), &other), 1_000);
+    }
+
+    #[test]
+    fn paid_lamports_needs_the_payer_to_sign() {
+        let payer = Keypair::new();
+        let server = Pubkey::new_unique();
+        let tx = payment(&payer, &[transfer(&payer.pubkey(), &server, 1_000)]);
+        assert_eq!(paid_lamports(&tx, &Pubkey::new_unique(), &server), 0);
+    }
+
+    #[test]
+    fn carries_all_needs_every_instruction_from_the_signer() {
+        let owner = Keypair::new();
+        let escrow = Pubkey::new_unique();
+        let wanted = [
+            transfer(&owner.pubkey(), &escrow, 1),
+            transfer(&owner.pubkey(), &escrow, 2),
+        ];
+        let extra = transfer(&owner.pubkey(), &Pubkey::new_unique(), 3);
+        let tx = payment(&owner, &[wanted[1].clone(), extra, wanted[0].clone()]);
+        assert!(carries_all(&tx, &owner.pubkey(), &wanted));
+        assert!(!carries_all(&tx, &Pubkey::new_unique(), &wanted));
+        let partial = payment(&owner, &wanted[..1]);
+        assert!(!carries_all(&partial, &owner.pubkey(), &wanted));
+    }
+}
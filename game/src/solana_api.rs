@@ -1,71 +1,113 @@
+use crate::assets::{self, AssetStatus, TrackedAsset};
 use crate::card;
-use crate::card::CardKind;
+use crate::card::{CardOptions, Provenance, Stamp};
 use crate::card_cache::{self, CachedCard};
+use crate::claims::{self, Claim};
+use crate::crafted_art;
+use crate::error::{err, ApiError, ErrorCode};
 use crate::game_state::HandCard;
 use crate::generate::AppState;
+use crate::image_variants;
+use crate::indexer;
+use crate::packs::{self, MintStatus, PackMint, Rarity};
+use crate::solana::{
+    CardMetadata, Finish, MintTx, NewCard, OwnedCard, PaymentError, SolanaConfig, TxError,
+};
+use crate::telemetry::SendTraced;
+use crate::wallet_auth::require_wallet;
 use axum::extract::State;
-use axum::http::StatusCode;
+use axum::http::HeaderMap;
 use axum::Json;
-use rand::Rng;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
+use utoipa::ToSchema;
 
-#[derive(Serialize)]
-pub struct ApiError {
-    pub error: String,
-}
-
-fn err(status: StatusCode, msg: impl Into<String>) -> (StatusCode, Json<ApiError>) {
-    (status, Json(ApiError { error: msg.into() }))
-}
-
-fn require_solana(state: &AppState) -> Result<&crate::solana::SolanaConfig, (StatusCode, Json<ApiError>)> {
+pub fn require_solana(state: &AppState) -> Result<&crate::solana::SolanaConfig, ApiError> {
     state.solana.as_deref().ok_or_else(|| {
         err(
-            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::SolanaNotConfigured,
             "Solana integration not configured",
         )
     })
 }
 
-// --- POST /api/wallet/cards ---
+pub fn check_wallet_rate(state: &AppState, wallet: &str) -> Result<(), ApiError> {
+    state
+        .rate_limiter
+        .check_wallet(wallet)
+        .map_err(|retry_after| ApiError::rate_limited("wallet", retry_after))
+}
 
-#[derive(Deserialize)]
-pub struct WalletCardsRequest {
-    pub wallet_address: String,
+/// A transaction that would fail is the wallet's to fix: say why, with the
+/// simulation logs for anyone digging further.
+pub fn tx_error(e: TxError) -> ApiError {
+    match e {
+        TxError::Solana(e) => err(ErrorCode::SolanaError, e),
+        TxError::WouldFail {
+            failure,
+            error,
+            logs,
+        } => err(ErrorCode::TransactionWouldFail, format!("{} ({error})", failure.describe()))
+            .with_details(serde_json::json!({ "reason": failure, "logs": logs })),
+    }
 }
 
+// --- POST /api/wallet/cards ---
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/cards",
+    tag = "wallet",
+    responses(
+        (status = 200, description = "Alchemaybe NFTs owned by the wallet", body = crate::openapi::WalletCardsResponse),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana RPC error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
 pub async fn wallet_cards(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<WalletCardsRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
     let solana = require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
 
-    log::info!("Querying cards for wallet: {}", req.wallet_address);
+    log::info!("Querying cards for wallet: {wallet}");
     let owned = solana
-        .query_owned_cards(&req.wallet_address)
+        .query_owned_cards(&wallet)
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, e))?;
+        .map_err(|e| err(ErrorCode::SolanaError, e))?;
     log::info!("Found {} owned cards", owned.len());
 
     // Enrich with card cache and base card data
     let cache = state.card_cache.read().await;
+    let base_cards = state.base_cards.read().await;
     let mut cards = Vec::new();
+    // Cards we don't know, e.g. minted by another server, by their index
+    let mut foreign = Vec::new();
     for card in &owned {
-        let base = state.base_cards.iter().find(|b| b.id == card.card_id);
+        let base = base_cards.iter().find(|b| b.id == card.card_id);
         let cached = cache.get(&card.card_id);
         let (name, description, image_path, kind) = if let Some(b) = base {
             (b.name.as_str(), b.description.as_str(), b.image_path.as_str(), b.kind.as_str())
-        } else if let Some(c) = cached {
+        } else if let Some(c) = &cached {
             (c.name.as_str(), c.description.as_str(), c.image_path.as_str(), "crafted")
         } else {
+            foreign.push(cards.len());
             (card.name.as_str(), "", "", "crafted")
         };
         cards.push(serde_json::json!({
             "mint_address": card.mint_address,
             "card_id": card.card_id,
+            "season": card.season,
+            "card_back": card::card_back_path(&card.season),
+            "edition": card.edition,
+            "soulbound": card.soulbound,
             "name": name,
             "description": description,
             "image_path": image_path,
@@ -73,116 +115,235 @@ pub async fn wallet_cards(
         }));
     }
 
-    Ok(Json(serde_json::json!({ "cards": cards })))
-}
-
-fn determine_card_kind(state: &AppState, card_id: &str) -> &'static str {
-    if state.base_cards.iter().any(|b| b.id == card_id) {
-        let base = state.base_cards.iter().find(|b| b.id == card_id).unwrap();
-        if base.kind == "intent" {
-            "intent"
-        } else {
-            "material"
+    drop(cache);
+    drop(base_cards);
+    // Fall back to their off-chain metadata, fetching each URI once
+    let uris: HashSet<&str> = foreign
+        .iter()
+        .map(|&i| owned[i].metadata_uri.as_str())
+        .filter(|uri| !uri.is_empty())
+        .collect();
+    let fetched: HashMap<&str, CardMetadata> = futures::future::join_all(
+        uris.into_iter()
+            .map(|uri| async move { solana.card_metadata(uri).await.map(|m| (uri, m)) }),
+    )
+    .await
+    .into_iter()
+    .flatten()
+    .collect();
+    for i in foreign {
+        let Some(metadata) = fetched.get(owned[i].metadata_uri.as_str()) else {
+            continue;
+        };
+        let card = &mut cards[i];
+        if !metadata.name.is_empty() {
+            card["name"] = metadata.name.clone().into();
         }
-    } else {
-        "crafted"
+        card["description"] = metadata.description.clone().into();
+        card["image_path"] = metadata.image.clone().into();
     }
+
+    let essence = state
+        .assets
+        .essence(&wallet)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+
+    Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
+        "cards": cards,
+        "essence": essence,
+    })))
 }
 
 // --- POST /api/wallet/claim ---
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ClaimRequest {
-    pub wallet_address: String,
     pub card_id: String,
-    #[allow(dead_code)]
+    /// Game in which the signed-in wallet crafted the card.
     #[serde(default)]
     pub game_id: Option<String>,
+    /// Admin-issued voucher, instead of a game.
+    #[serde(default)]
+    pub voucher: Option<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/wallet/claim",
+    tag = "wallet",
+    request_body = ClaimRequest,
+    responses(
+        (status = 200, description = "Unsigned mint transaction", body = crate::openapi::MintTxResponse),
+        (status = 400, description = "Invalid wallet", body = ApiError),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 403, description = "No proof for this claim, already claimed, or over the claim limits", body = ApiError),
+        (status = 404, description = "Card or game not found", body = ApiError),
+        (status = 422, description = "Card is impossible, or the mint would fail (see `details.reason`)", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
 pub async fn wallet_claim(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<ClaimRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let solana = require_solana(&state)?;
-    let recipient = Pubkey::from_str(&req.wallet_address)
-        .map_err(|e| err(StatusCode::BAD_REQUEST, format!("Invalid wallet: {e}")))?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let recipient = Pubkey::from_str(&wallet)
+        .map_err(|e| err(ErrorCode::InvalidWallet, format!("Invalid wallet: {e}")))?;
 
     // Verify the card exists in cache
     let cache = state.card_cache.read().await;
     let cached = cache
         .get(&req.card_id)
-        .ok_or_else(|| err(StatusCode::NOT_FOUND, "Card not found in cache"))?;
+        .ok_or_else(|| err(ErrorCode::CardNotFound, "Card not found in cache"))?;
 
     if cached.impossible {
-        return Err(err(StatusCode::BAD_REQUEST, "Cannot claim impossible card"));
+        return Err(err(ErrorCode::CombinationImpossible, "Cannot claim impossible card"));
     }
+    drop(cache);
 
-    // Ensure metadata JSON exists
-    let metadata_uri = solana
-        .ensure_metadata_json(&req.card_id, &cached.name, &cached.description, &cached.image_path)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
-
-    // Build mint transaction
-    let (tx_base64, asset_pubkey) = solana
-        .build_mint_tx(&req.card_id, &cached.name, &metadata_uri, &recipient)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    // Only mint with proof the wallet earned the card, once per proof
+    let proof = claims::proof_for(
+        &state,
+        &wallet,
+        &req.card_id,
+        req.game_id.as_deref(),
+        req.voucher.as_deref(),
+    )
+    .await?;
+    let replacing = claims::check(&state, solana, &wallet, &req.card_id, &proof)?;
+    let asset = new_asset(&state, solana, &req.card_id, &cached.image_path, false).await?;
+    let edition = asset.edition;
+
+    let build = || -> Result<MintTx, ApiError> {
+        // Ensure metadata JSON exists
+        let metadata_uri = solana
+            .ensure_metadata_json(
+                &req.card_id,
+                &cached.name,
+                &cached.description,
+                &asset.image_path,
+                Finish::new(false, Rarity::of(&state, &req.card_id, true)),
+                Some(&asset.address),
+            )
+            .map_err(|e| err(ErrorCode::StorageError, e))?;
+
+        // Build mint transaction
+        solana
+            .build_mint_tx(
+                &NewCard {
+                    card_id: &req.card_id,
+                    edition,
+                    name: &cached.name,
+                    metadata_uri: &metadata_uri,
+                    asset_seed: &asset.seed,
+                },
+                &recipient,
+            )
+            .map_err(tx_error)
+    };
+    let mint = build().inspect_err(|_| release_edition(&state, solana, &asset))?;
+
+    let claim = Claim {
+        proof,
+        wallet,
+        card_id: req.card_id.clone(),
+        asset_address: mint.asset_address.clone(),
+        claimed_at: card_cache::now_secs(),
+    };
+    claims::record(&state, &claim, replacing.as_deref())
+        .inspect_err(|_| release_edition(&state, solana, &asset))?;
+    log::info!("Claim {} -> {} ({})", claim.card_id, claim.wallet, claim.proof);
+    assets::track(&state, &[TrackedAsset::minting(&mint, &claim.card_id, &claim.wallet)]);
 
     Ok(Json(serde_json::json!({
-        "transaction": tx_base64,
-        "asset_address": asset_pubkey,
+        "cluster": solana.cluster.name(),
+        "transaction": mint.transaction,
+        "asset_address": mint.asset_address,
         "card": {
             "card_id": req.card_id,
             "name": cached.name,
             "description": cached.description,
             "image_path": cached.image_path,
+            "edition": edition,
         }
     })))
 }
 
 // --- POST /api/wallet/combine ---
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct WalletCombineRequest {
-    pub wallet_address: String,
     pub mint_addresses: Vec<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/wallet/combine",
+    tag = "wallet",
+    request_body = WalletCombineRequest,
+    responses(
+        (status = 200, description = "Unsigned burn+mint transaction", body = crate::openapi::MintTxResponse),
+        (status = 400, description = "Invalid selection or cards not owned", body = ApiError),
+        (status = 422, description = "Combination is impossible, or the transaction would fail (see `details.reason`)", body = ApiError),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Generation or Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
 pub async fn wallet_combine(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<WalletCombineRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let solana = require_solana(&state)?;
-    let owner = Pubkey::from_str(&req.wallet_address)
-        .map_err(|e| err(StatusCode::BAD_REQUEST, format!("Invalid wallet: {e}")))?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let owner = Pubkey::from_str(&wallet)
+        .map_err(|e| err(ErrorCode::InvalidWallet, format!("Invalid wallet: {e}")))?;
 
     if req.mint_addresses.len() < 2 || req.mint_addresses.len() > 4 {
-        return Err(err(StatusCode::BAD_REQUEST, "Select 2-4 cards to combine"));
+        return Err(err(ErrorCode::InvalidCombination, "Select 2-4 cards to combine"));
+    }
+    let mut seen = HashSet::new();
+    if let Some(mint) = req.mint_addresses.iter().find(|m| !seen.insert(m.as_str())) {
+        return Err(err(
+            ErrorCode::InvalidCombination,
+            format!("Card {mint} selected more than once"),
+        ));
     }
 
-    // Verify ownership and get card_ids via DAS
-    let owned = solana
-        .query_owned_cards(&req.wallet_address)
+    // Verify ownership and get card_ids from the ownership index
+    let owned = indexer::owned_cards(&state, solana, &wallet)
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, e))?;
+        .map_err(|e| err(ErrorCode::SolanaError, e))?;
 
     let mut selected_cards: Vec<(String, String)> = Vec::new(); // (mint, card_id)
+    let mut burn_assets = Vec::new();
     for mint_addr in &req.mint_addresses {
         let owned_card = owned
             .iter()
             .find(|c| c.mint_address == *mint_addr)
-            .ok_or_else(|| err(StatusCode::BAD_REQUEST, format!("Card {mint_addr} not owned")))?;
+            .ok_or_else(|| err(ErrorCode::CardNotOwned, format!("Card {mint_addr} not owned")))?;
         selected_cards.push((mint_addr.clone(), owned_card.card_id.clone()));
+        burn_assets.push(owned_card.core_asset().map_err(|e| err(ErrorCode::SolanaError, e))?);
     }
 
     // Look up card details from cache and base cards
     let cache = state.card_cache.read().await;
+    let base_cards = state.base_cards.read().await;
     let mut hand_cards: Vec<HandCard> = Vec::new();
     for (_mint, card_id) in &selected_cards {
         // Check base cards first
-        if let Some(base) = state.base_cards.iter().find(|b| b.id == *card_id) {
+        if let Some(base) = base_cards.iter().find(|b| b.id == *card_id) {
             hand_cards.push(HandCard::from_base(base));
-        } else if let Some(cached) = cache.get(card_id) {
+        } else if let Some(cached) = cache.get(card_id).filter(|c| !c.impossible) {
             hand_cards.push(HandCard {
                 name: cached.name.clone(),
                 description: cached.description.clone(),
@@ -192,7 +353,7 @@ pub async fn wallet_combine(
                 nft_mint: None,
             });
         } else {
-            return Err(err(StatusCode::NOT_FOUND, format!("Card {card_id} not found")));
+            return Err(err(ErrorCode::CardNotFound, format!("Card {card_id} not found")));
         }
     }
     drop(cache);
@@ -204,10 +365,10 @@ pub async fn wallet_combine(
         .count();
     let intent_count = hand_cards.iter().filter(|c| c.kind == "intent").count();
     if material_like_count < 1 {
-        return Err(err(StatusCode::BAD_REQUEST, "Need at least 1 material"));
+        return Err(err(ErrorCode::InvalidCombination, "Need at least 1 material"));
     }
     if intent_count > 1 {
-        return Err(err(StatusCode::BAD_REQUEST, "At most 1 intent"));
+        return Err(err(ErrorCode::InvalidCombination, "At most 1 intent"));
     }
 
     // Compute cache key
@@ -221,43 +382,81 @@ pub async fn wallet_combine(
         .find(|c| c.kind == "intent")
         .map(|c| c.id.as_str());
     let key = card_cache::compute_crafted_card_id(&material_ids, intent_id);
-
-    // Check cache
-    {
-        let cache = state.card_cache.read().await;
-        if let Some(cached) = cache.get(&key) {
-            if cached.impossible {
-                return Err(err(StatusCode::UNPROCESSABLE_ENTITY, "Combination not possible"));
+    let recipe = card_cache::Recipe::new(&material_ids, intent_id);
+
+    // Check cache (stale "not possible" verdicts fall through to be judged again)
+    let hit = {
+        let cache = state.card_cache.write().await;
+        match cache.get(&key).filter(|c| !cache.needs_recheck(c)) {
+            Some(cached) if cached.impossible => {
+                return Err(err(ErrorCode::CombinationImpossible, "Combination not possible"));
+            }
+            Some(cached) => {
+                // Discovery and the craft count wait for the transaction to
+                // land; only the recipe of older entries is backfilled now
+                if cached.recipe.is_none() {
+                    let mut updated = cached.clone();
+                    updated.recipe = Some(recipe.clone());
+                    cache.insert(key.clone(), updated);
+                }
+                let is_new = !cached.discovered;
+                Some((cached, is_new))
             }
+            None => None,
+        }
+    };
+    if let Some((cached, is_new)) = hit {
+        // Build burn+mint tx, in foil for the card's first discovery
+        let image_path = if is_new {
+            image_variants::foil_url(&cached.image_path)
+        } else {
+            cached.image_path.clone()
+        };
+        let asset = new_asset(&state, solana, &key, &cached.image_path, is_new).await?;
+        let edition = asset.edition;
 
-            // Build burn+mint tx
+        let build = || -> Result<MintTx, ApiError> {
             let metadata_uri = solana
-                .ensure_metadata_json(&key, &cached.name, &cached.description, &cached.image_path)
-                .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
-
-            let burn_pubkeys: Vec<Pubkey> = req
-                .mint_addresses
-                .iter()
-                .map(|a| Pubkey::from_str(a))
-                .collect::<Result<_, _>>()
-                .map_err(|e| err(StatusCode::BAD_REQUEST, format!("Invalid mint: {e}")))?;
-
-            let (tx_base64, asset_pubkey) = solana
-                .build_burn_and_mint_tx(&burn_pubkeys, &key, &cached.name, &metadata_uri, &owner)
-                .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
-
-            return Ok(Json(serde_json::json!({
-                "transaction": tx_base64,
-                "asset_address": asset_pubkey,
-                "card": {
-                    "card_id": key,
-                    "name": cached.name,
-                    "description": cached.description,
-                    "image_path": cached.image_path,
-                },
-                "is_new": !cached.discovered,
-            })));
-        }
+                .ensure_metadata_json(
+                    &key,
+                    &cached.name,
+                    &cached.description,
+                    &asset.image_path,
+                    Finish::new(is_new, Rarity::of(&state, &key, true)),
+                    Some(&asset.address),
+                )
+                .map_err(|e| err(ErrorCode::StorageError, e))?;
+
+            solana
+                .build_burn_and_mint_tx(
+                    &burn_assets,
+                    &NewCard {
+                        card_id: &key,
+                        edition,
+                        name: &cached.name,
+                        metadata_uri: &metadata_uri,
+                        asset_seed: &asset.seed,
+                    },
+                    &owner,
+                )
+                .map_err(tx_error)
+        };
+        let mint = build().inspect_err(|_| release_edition(&state, solana, &asset))?;
+        track_combine(&state, &wallet, &selected_cards, &mint, &key);
+
+        return Ok(Json(serde_json::json!({
+            "cluster": solana.cluster.name(),
+            "transaction": mint.transaction,
+            "asset_address": mint.asset_address,
+            "card": {
+                "card_id": key,
+                "name": cached.name,
+                "description": cached.description,
+                "image_path": image_path,
+                "edition": edition,
+            },
+            "is_new": is_new,
+        })));
     }
 
     // Cache miss — call generation server
@@ -277,25 +476,29 @@ pub async fn wallet_combine(
         .client
         .post(format!("{}/combine", state.generation_url))
         .json(&serde_json::json!({ "cards": combine_cards }))
-        .send()
+        .send_traced(tracing::info_span!("generation.combine"))
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Generation error: {e}")))?;
+        .map_err(|e| err(ErrorCode::GenerationUnavailable, format!("Generation error: {e}")))?;
 
     if !combine_resp.status().is_success() {
         let body = combine_resp.text().await.unwrap_or_default();
-        return Err(err(StatusCode::BAD_GATEWAY, format!("Combination failed: {body}")));
+        return Err(err(ErrorCode::GenerationUnavailable, format!("Combination failed: {body}")));
     }
 
     let combined: serde_json::Value = combine_resp
         .json()
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Parse error: {e}")))?;
+        .map_err(|e| err(ErrorCode::GenerationUnavailable, format!("Parse error: {e}")))?;
 
     let card_name = combined["name"].as_str().unwrap_or("Unknown").to_string();
     let card_desc = combined["description"].as_str().unwrap_or("").to_string();
+    let combine_version = combined["combine_version"].as_str().map(str::to_string);
+    if let Some(version) = &combine_version {
+        state.card_cache.read().await.set_combine_version(version);
+    }
 
     if card_name.to_lowercase().contains("not possible") {
-        let mut cache = state.card_cache.write().await;
+        let cache = state.card_cache.write().await;
         cache.insert(
             key.clone(),
             CachedCard {
@@ -305,10 +508,17 @@ pub async fn wallet_combine(
                 id: key,
                 discovered: false,
                 impossible: true,
+                recipe: Some(recipe),
+                discovered_at: None,
+                created_at: Some(card_cache::now_secs()),
+                times_crafted: 0,
+                combine_version,
+                discovered_by: None,
+                previous_art: Vec::new(),
+                art_path: None,
             },
         );
-        cache.save(std::path::Path::new("cards/card-cache.json"));
-        return Err(err(StatusCode::UNPROCESSABLE_ENTITY, "Combination not possible"));
+        return Err(err(ErrorCode::CombinationImpossible, "Combination not possible"));
     }
 
     // Generate image
@@ -319,270 +529,818 @@ pub async fn wallet_combine(
             "name": card_name,
             "description": card_desc,
         }))
-        .send()
+        .send_traced(tracing::info_span!("generation.generate_image"))
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Image error: {e}")))?;
+        .map_err(|e| err(ErrorCode::GenerationUnavailable, format!("Image error: {e}")))?;
 
     if !image_resp.status().is_success() {
-        return Err(err(StatusCode::BAD_GATEWAY, "Image generation failed"));
+        return Err(err(ErrorCode::GenerationUnavailable, "Image generation failed"));
     }
 
     let art_bytes = image_resp
         .bytes()
         .await
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, format!("Image read error: {e}")))?;
-
-    let png = card::render_card(&card_name, &art_bytes, &CardKind::Material)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, format!("Render error: {e}")))?;
+        .map_err(|e| err(ErrorCode::GenerationUnavailable, format!("Image read error: {e}")))?;
 
-    let safe_name = card_name
-        .chars()
-        .map(|c| if c.is_alphanumeric() || c == ' ' || c == '-' { c } else { '_' })
-        .collect::<String>()
-        .replace(' ', "-");
-    let filename = format!("{safe_name}-{key}.png");
-    let disk_path = format!("cards/crafted/{filename}");
-    let serve_path = format!("/cards/crafted/{filename}");
-
-    let _ = std::fs::create_dir_all("cards/crafted");
-    std::fs::write(&disk_path, &png)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, format!("Write error: {e}")))?;
+    let (art_path, serve_path) =
+        crafted_art::keep_and_render(&state, &key, &card_name, art_bytes).await?;
 
     let cached = CachedCard {
         name: card_name.clone(),
         description: card_desc.clone(),
         image_path: serve_path.clone(),
         id: key.clone(),
-        discovered: true,
+        discovered: false,
         impossible: false,
+        recipe: Some(recipe),
+        discovered_at: None,
+        created_at: Some(card_cache::now_secs()),
+        times_crafted: 0,
+        combine_version,
+        discovered_by: None,
+        previous_art: Vec::new(),
+        art_path: Some(art_path),
     };
 
-    {
-        let mut cache = state.card_cache.write().await;
-        cache.insert(key.clone(), cached);
-        cache.save(std::path::Path::new("cards/card-cache.json"));
-    }
-
-    // Build burn+mint tx
-    let metadata_uri = solana
-        .ensure_metadata_json(&key, &card_name, &card_desc, &serve_path)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
-
-    let burn_pubkeys: Vec<Pubkey> = req
-        .mint_addresses
-        .iter()
-        .map(|a| Pubkey::from_str(a))
-        .collect::<Result<_, _>>()
-        .map_err(|e| err(StatusCode::BAD_REQUEST, format!("Invalid mint: {e}")))?;
+    state.card_cache.write().await.insert(key.clone(), cached);
 
-    let (tx_base64, asset_pubkey) = solana
-        .build_burn_and_mint_tx(&burn_pubkeys, &key, &card_name, &metadata_uri, &owner)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    // Build burn+mint tx, in foil since this is the card's first discovery
+    let asset = new_asset(&state, solana, &key, &serve_path, true).await?;
+    let edition = asset.edition;
+    let build = || -> Result<MintTx, ApiError> {
+        let metadata_uri = solana
+            .ensure_metadata_json(
+                &key,
+                &card_name,
+                &card_desc,
+                &asset.image_path,
+                Finish::new(true, Rarity::of(&state, &key, true)),
+                Some(&asset.address),
+            )
+            .map_err(|e| err(ErrorCode::StorageError, e))?;
+
+        solana
+            .build_burn_and_mint_tx(
+                &burn_assets,
+                &NewCard {
+                    card_id: &key,
+                    edition,
+                    name: &card_name,
+                    metadata_uri: &metadata_uri,
+                    asset_seed: &asset.seed,
+                },
+                &owner,
+            )
+            .map_err(tx_error)
+    };
+    let mint = build().inspect_err(|_| release_edition(&state, solana, &asset))?;
+    track_combine(&state, &wallet, &selected_cards, &mint, &key);
 
     Ok(Json(serde_json::json!({
-        "transaction": tx_base64,
-        "asset_address": asset_pubkey,
+        "cluster": solana.cluster.name(),
+        "transaction": mint.transaction,
+        "asset_address": mint.asset_address,
         "card": {
             "card_id": key,
             "name": card_name,
             "description": card_desc,
-            "image_path": serve_path,
+            "image_path": image_variants::foil_url(&serve_path),
+            "edition": edition,
         },
         "is_new": true,
     })))
 }
 
-// --- POST /api/wallet/pack/buy ---
+/// Refusal to move a soulbound card, which would only fail on-chain.
+pub fn soulbound_error(card: &OwnedCard) -> ApiError {
+    err(
+        ErrorCode::CardSoulbound,
+        format!("Card {} is soulbound and can't be transferred", card.mint_address),
+    )
+    .with_details(serde_json::json!({ "mint_address": card.mint_address }))
+}
 
-#[derive(Deserialize)]
-pub struct PackBuyRequest {
-    pub wallet_address: String,
-    pub pack_type: String, // "starter" or "premium"
+/// An asset about to be minted, the edition held for it, and its own copy of
+/// the card image.
+struct NewAsset {
+    seed: String,
+    address: String,
+    edition: u64,
+    image_path: String,
 }
 
-pub async fn wallet_pack_buy(
+/// How long a wallet mint holds its edition: as long as its transaction can
+/// be signed again, plus the life of the last blockhash and some slack.
+const EDITION_HOLD_SECS: u64 = MINT_REFRESH_SECS + 10 * 60;
+
+/// A fresh asset address holding an edition of `card_id`, refused once the
+/// card's supply cap is reached, and the card at `image_path` as that
+/// asset's copy: a QR code to it on the explorer and the season's set symbol
+/// with its provenance stamped on, in foil for the card's first discovery.
+/// Falls back to the shared image when the copy can't be made, so a mint
+/// never fails over artwork. Give the edition back with `release_edition` if
+/// the mint isn't handed out.
+async fn new_asset(
+    state: &AppState,
+    solana: &SolanaConfig,
+    card_id: &str,
+    image_path: &str,
+    first_discovery: bool,
+) -> Result<NewAsset, ApiError> {
+    let seed = SolanaConfig::new_asset_seed();
+    let address = solana
+        .asset_address(&seed)
+        .map_err(|e| err(ErrorCode::SolanaError, e))?
+        .to_string();
+    let cap = state.supply.cap(card_id, true);
+    let expires_at = card_cache::now_secs() + EDITION_HOLD_SECS;
+    let edition = state
+        .assets
+        .hold_edition(card_id, cap, &address, expires_at)
+        .map_err(|e| err(ErrorCode::StorageError, e))?
+        .ok_or_else(|| {
+            let minted = cap.unwrap_or(0);
+            err(ErrorCode::CardSoldOut, format!("All {minted} of card {card_id} have been minted"))
+                .with_details(serde_json::json!({ "card_id": card_id, "cap": cap }))
+        })?;
+
+    let explorer_url = solana.cluster.explorer_url(&address);
+    let stamp = Stamp {
+        set: solana.seasons[0].name.clone(),
+        provenance: if first_discovery {
+            Provenance::FirstDiscovery
+        } else {
+            Provenance::Edition(edition)
+        },
+    };
+    let template = state.card_templates.read().await.default_template();
+    let cards_dir = state.paths.cards_dir.clone();
+    let (shared, asset) = (image_path.to_string(), address.clone());
+    let minted = state
+        .render
+        .run(move || {
+            let options = CardOptions {
+                foil: first_discovery,
+                qr_url: Some(&explorer_url),
+                stamp: Some(&stamp),
+                ..Default::default()
+            };
+            crafted_art::save_minted(&cards_dir, &shared, &asset, &template, &options)
+        })
+        .await
+        .map_err(|e| e.message)
+        .and_then(|saved| saved);
+    let image_path = minted.unwrap_or_else(|e| {
+        log::warn!("Minting {address} with the shared card image: {e}");
+        if first_discovery {
+            image_variants::foil_url(image_path)
+        } else {
+            image_path.to_string()
+        }
+    });
+    Ok(NewAsset {
+        seed,
+        address,
+        edition,
+        image_path,
+    })
+}
+
+/// Give the edition held for `asset` back and delete its files, when its
+/// transaction couldn't be built or handed out. Only logged on failure: the
+/// hold runs out anyway.
+fn release_edition(state: &AppState, solana: &SolanaConfig, asset: &NewAsset) {
+    if let Err(e) = assets::discard_mint(state, solana, &asset.address) {
+        log::warn!("Failed to discard the mint of {}: {e}", asset.address);
+    }
+}
+
+/// The burned cards and the new one of a wallet combine, until the
+/// transaction lands.
+fn track_combine(
+    state: &AppState,
+    wallet: &str,
+    burned: &[(String, String)],
+    mint: &MintTx,
+    card_id: &str,
+) {
+    let mut tracked: Vec<TrackedAsset> = burned
+        .iter()
+        .map(|(mint, burned_id)| TrackedAsset::new(mint, burned_id, wallet, AssetStatus::Burning))
+        .collect();
+    let mut minted = TrackedAsset::minting(mint, card_id, wallet);
+    minted.crafted = true;
+    tracked.push(minted);
+    assets::track(state, &tracked);
+}
+
+// --- POST /api/wallet/transfer ---
+
+#[derive(Deserialize, ToSchema)]
+pub struct TransferRequest {
+    pub mint_address: String,
+    /// Wallet receiving the card.
+    pub recipient: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/transfer",
+    tag = "wallet",
+    request_body = TransferRequest,
+    responses(
+        (status = 200, description = "Unsigned transfer transaction", body = crate::openapi::TransferTxResponse),
+        (status = 400, description = "Invalid recipient, or card not owned or soulbound", body = ApiError),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn wallet_transfer(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<PackBuyRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+    headers: HeaderMap,
+    Json(req): Json<TransferRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
     let solana = require_solana(&state)?;
-    let buyer = Pubkey::from_str(&req.wallet_address)
-        .map_err(|e| err(StatusCode::BAD_REQUEST, format!("Invalid wallet: {e}")))?;
-
-    // Pack config
-    let (base_count, crafted_count, price_lamports) = match req.pack_type.as_str() {
-        "starter" => (2, 1, 10_000_000u64),   // 0.01 SOL
-        "premium" => (3, 2, 15_000_000u64),    // 0.015 SOL
-        _ => return Err(err(StatusCode::BAD_REQUEST, "Invalid pack type")),
-    };
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let owner = Pubkey::from_str(&wallet)
+        .map_err(|e| err(ErrorCode::InvalidWallet, format!("Invalid wallet: {e}")))?;
+    let recipient = Pubkey::from_str(&req.recipient)
+        .map_err(|e| err(ErrorCode::InvalidWallet, format!("Invalid recipient: {e}")))?;
+    if recipient == owner {
+        return Err(err(ErrorCode::InvalidWallet, "Cannot send a card to yourself"));
+    }
+    let owned = solana
+        .query_owned_cards(&wallet)
+        .await
+        .map_err(|e| err(ErrorCode::SolanaError, e))?;
+    let card = owned
+        .iter()
+        .find(|c| c.mint_address == req.mint_address)
+        .ok_or_else(|| {
+            err(ErrorCode::CardNotOwned, format!("Card {} not owned", req.mint_address))
+        })?;
+    if card.soulbound {
+        return Err(soulbound_error(card));
+    }
 
-    let mut pack_cards: Vec<(String, String, String)> = Vec::new(); // (card_id, name, metadata_uri)
-    let mut pack_display: Vec<serde_json::Value> = Vec::new();
+    let asset = card.core_asset().map_err(|e| err(ErrorCode::SolanaError, e))?;
+    let tx_base64 = solana
+        .build_transfer_tx(&asset, &owner, &recipient)
+        .map_err(|e| err(ErrorCode::SolanaError, e))?;
+    log::info!("Transfer {} ({}) {wallet} -> {}", card.mint_address, card.card_id, req.recipient);
 
-    // Pre-select random indices before any await points (ThreadRng is !Send)
-    let base_selections: Vec<usize> = {
-        let mut rng = rand::rng();
-        (0..base_count)
-            .map(|_| rng.random_range(0..state.base_cards.len()))
-            .collect()
-    };
+    Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
+        "transaction": tx_base64,
+        "asset_address": card.mint_address,
+        "card_id": card.card_id,
+        "recipient": req.recipient,
+    })))
+}
 
-    // Select random base cards
-    for idx in &base_selections {
-        let base = &state.base_cards[*idx];
-        let metadata_uri = solana
-            .ensure_metadata_json(&base.id, &base.name, &base.description, &base.image_path)
-            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
-        pack_cards.push((base.id.clone(), base.name.clone(), metadata_uri));
-        pack_display.push(serde_json::json!({
-            "card_id": base.id,
-            "name": base.name,
-            "description": base.description,
-            "image_path": base.image_path,
-            "kind": base.kind,
-        }));
+// --- POST /api/wallet/burn ---
+
+/// Most cards one burn transaction takes.
+const MAX_BURN: usize = 10;
+/// How long a burn transaction we handed out might still land. Its cards
+/// can't be put in another burn until then.
+const BURN_PENDING_SECS: u64 = 150;
+
+#[derive(Deserialize, ToSchema)]
+pub struct BurnRequest {
+    pub mint_addresses: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/burn",
+    tag = "wallet",
+    request_body = BurnRequest,
+    responses(
+        (status = 200, description = "Unsigned burn transaction and the essence it earns", body = crate::openapi::BurnTxResponse),
+        (status = 400, description = "Invalid selection or cards not owned", body = ApiError),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn wallet_burn(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<BurnRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let solana = require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let owner = Pubkey::from_str(&wallet)
+        .map_err(|e| err(ErrorCode::InvalidWallet, format!("Invalid wallet: {e}")))?;
+
+    let mut mints = req.mint_addresses.clone();
+    mints.sort();
+    mints.dedup();
+    if mints.is_empty() || mints.len() != req.mint_addresses.len() || mints.len() > MAX_BURN {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("Select 1-{MAX_BURN} different cards to burn"),
+        ));
     }
 
-    // Select random discovered crafted card from cache
-    {
-        let cache = state.card_cache.read().await;
-        let discovered: Vec<CachedCard> = cache
-            .all_entries()
-            .filter(|(_, c)| c.discovered && !c.impossible && !c.image_path.is_empty())
-            .map(|(_, c)| c.clone())
-            .collect();
-
-        let crafted_selections: Vec<Option<usize>> = {
-            let mut rng = rand::rng();
-            (0..crafted_count)
-                .map(|_| {
-                    if discovered.is_empty() {
-                        None
-                    } else {
-                        Some(rng.random_range(0..discovered.len()))
-                    }
-                })
-                .collect()
+    let owned = solana
+        .query_owned_cards(&wallet)
+        .await
+        .map_err(|e| err(ErrorCode::SolanaError, e))?;
+    let base_cards = state.base_cards.read().await;
+    let mut burned = Vec::new();
+    let mut burn_assets = Vec::new();
+    for mint in &req.mint_addresses {
+        let card = owned
+            .iter()
+            .find(|c| c.mint_address == *mint)
+            .ok_or_else(|| err(ErrorCode::CardNotOwned, format!("Card {mint} not owned")))?;
+        // A burn or combine still in flight could otherwise earn twice
+        let tracked = state
+            .assets
+            .tracked_asset(mint)
+            .map_err(|e| err(ErrorCode::StorageError, e))?;
+        if tracked.is_some_and(|t| {
+            t.status == AssetStatus::Burning
+                && card_cache::now_secs() < t.updated_at + BURN_PENDING_SECS
+        }) {
+            return Err(err(
+                ErrorCode::InvalidRequest,
+                format!("Card {mint} is already being burned"),
+            ));
+        }
+        let mut asset = TrackedAsset::new(mint, &card.card_id, &wallet, AssetStatus::Burning);
+        asset.reward = if base_cards.iter().any(|b| b.id == card.card_id) {
+            state.rewards.essence_per_base_card
+        } else {
+            state.rewards.essence_per_crafted_card
         };
+        burned.push(asset);
+        burn_assets.push(card.core_asset().map_err(|e| err(ErrorCode::SolanaError, e))?);
+    }
+    drop(base_cards);
 
-        for selection in &crafted_selections {
-            if let Some(idx) = selection {
-                let crafted = &discovered[*idx];
-                let metadata_uri = solana
-                    .ensure_metadata_json(
-                        &crafted.id,
-                        &crafted.name,
-                        &crafted.description,
-                        &crafted.image_path,
-                    )
-                    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
-                pack_cards.push((crafted.id.clone(), crafted.name.clone(), metadata_uri));
-                pack_display.push(serde_json::json!({
-                    "card_id": crafted.id,
-                    "name": crafted.name,
-                    "description": crafted.description,
-                    "image_path": crafted.image_path,
-                    "kind": "crafted",
-                }));
-            } else {
-                // No crafted cards available; add another base card
-                let fallback_idx = {
-                    let mut rng = rand::rng();
-                    rng.random_range(0..state.base_cards.len())
-                };
-                let base = &state.base_cards[fallback_idx];
-                let metadata_uri = solana
-                    .ensure_metadata_json(&base.id, &base.name, &base.description, &base.image_path)
-                    .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
-                pack_cards.push((base.id.clone(), base.name.clone(), metadata_uri));
-                pack_display.push(serde_json::json!({
-                    "card_id": base.id,
-                    "name": base.name,
-                    "description": base.description,
-                    "image_path": base.image_path,
-                    "kind": base.kind,
-                }));
-            }
+    let tx_base64 = solana
+        .build_burn_tx(&burn_assets, &owner)
+        .map_err(|e| err(ErrorCode::SolanaError, e))?;
+
+    let essence: u64 = burned.iter().map(|a| a.reward).sum();
+    assets::track(&state, &burned);
+    log::info!("Burn of {} cards for {essence} essence by {wallet}", burned.len());
+
+    Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
+        "transaction": tx_base64,
+        "essence": essence,
+    })))
+}
+
+// --- POST /api/wallet/burn/confirm ---
+
+#[derive(Deserialize, ToSchema)]
+pub struct BurnConfirmRequest {
+    pub mint_addresses: Vec<String>,
+    /// Signature from `/api/wallet/submit-tx`.
+    pub signature: String,
+}
+
+/// Credit burns that have landed, for servers without the Helius webhook (or
+/// clients that don't want to wait for it). Burns already settled are skipped.
+#[utoipa::path(
+    post,
+    path = "/api/wallet/burn/confirm",
+    tag = "wallet",
+    request_body = BurnConfirmRequest,
+    responses(
+        (status = 200, description = "Burns found on-chain and the essence they credited", body = crate::openapi::BurnConfirmResponse),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn wallet_burn_confirm(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<BurnConfirmRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let solana = require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+
+    let mut burned = Vec::new();
+    let mut essence_earned = 0;
+    for mint in req.mint_addresses.iter().take(MAX_BURN) {
+        let tracked = state
+            .assets
+            .tracked_asset(mint)
+            .map_err(|e| err(ErrorCode::StorageError, e))?;
+        let Some(asset) = tracked.filter(|a| a.wallet == wallet) else {
+            continue;
+        };
+        if asset.status != AssetStatus::Burning {
+            continue;
+        }
+        let pubkey = Pubkey::from_str(mint)
+            .map_err(|e| err(ErrorCode::InvalidRequest, format!("Invalid mint: {e}")))?;
+        if solana.account_exists(&pubkey).map_err(|e| err(ErrorCode::SolanaError, e))? {
+            continue;
+        }
+        let reward = asset.reward;
+        if assets::settle(&state, solana, asset, &req.signature).await? {
+            essence_earned += reward;
+            burned.push(mint.clone());
         }
     }
+    let essence = state
+        .assets
+        .essence(&wallet)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+
+    Ok(Json(serde_json::json!({
+        "burned": burned,
+        "essence_earned": essence_earned,
+        "essence": essence,
+    })))
+}
 
-    // Build payment transaction (user signs this one)
-    let payment_tx = solana
-        .build_payment_tx(price_lamports, &buyer)
-        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+// --- POST /api/wallet/pack/buy ---
+
+#[derive(Deserialize, ToSchema)]
+pub struct PackBuyRequest {
+    /// Id of a pack from `GET /api/packs`.
+    pub pack_type: String,
+    /// Pay with essence from burned cards instead of SOL.
+    #[serde(default)]
+    pub pay_with_essence: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/pack/buy",
+    tag = "wallet",
+    request_body = PackBuyRequest,
+    responses(
+        (status = 200, description = "Payment transaction and the commitment to the pack's draw", body = crate::openapi::PackBuyResponse),
+        (status = 400, description = "Invalid wallet or pack type", body = ApiError),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 402, description = "Not enough essence", body = ApiError),
+        (status = 403, description = "Over the pack's purchase limits", body = ApiError),
+        (status = 409, description = "Every base card has sold out", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn wallet_pack_buy(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<PackBuyRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let solana = require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let buyer = Pubkey::from_str(&wallet)
+        .map_err(|e| err(ErrorCode::InvalidWallet, format!("Invalid wallet: {e}")))?;
+
+    let pack = state
+        .packs
+        .read()
+        .await
+        .iter()
+        .find(|p| p.id == req.pack_type)
+        .cloned()
+        .ok_or_else(|| err(ErrorCode::InvalidRequest, "Invalid pack type"))?;
+    packs::check_limits(&state, &wallet, &pack)?;
+
+    // Fix what the pack can draw before payment, so it can't change after
+    let pool = packs::current_pool(&state).await?;
+
+    // Build payment transaction (user signs this one), or take essence as
+    // the purchase is recorded
+    let price_essence = pack.price_essence;
+    let price_lamports = pack.price_lamports;
+    let payment_tx = if req.pay_with_essence {
+        if price_essence == 0 {
+            return Err(err(ErrorCode::InvalidRequest, "This pack can't be bought with essence"));
+        }
+        None
+    } else {
+        if price_lamports == 0 {
+            return Err(err(ErrorCode::InvalidRequest, "This pack can only be bought with essence"));
+        }
+        let tx = solana
+            .build_payment_tx(price_lamports, &buyer)
+            .map_err(|e| err(ErrorCode::SolanaError, e))?;
+        Some(tx)
+    };
+    let purchase = packs::record_purchase(&state, &wallet, &pack, pool, req.pay_with_essence)?;
 
     Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
         "payment_transaction": payment_tx,
-        "cards": pack_display,
-        "pack_cards": pack_cards.iter().map(|(id, name, uri)| {
-            serde_json::json!({"card_id": id, "name": name, "metadata_uri": uri})
-        }).collect::<Vec<_>>(),
-        "wallet_address": req.wallet_address,
+        "purchase_id": purchase.id,
+        "commitment": purchase.commitment(),
+        "pool_hash": packs::pool_hash(&purchase.pool),
+        "wallet_address": wallet,
         "price_sol": price_lamports as f64 / 1_000_000_000.0,
+        "price_essence": price_essence,
     })))
 }
 
 // --- POST /api/wallet/pack/confirm ---
 
-#[derive(Deserialize)]
+/// Longest `client_seed` accepted.
+const MAX_CLIENT_SEED_LEN: usize = 128;
+
+#[derive(Deserialize, ToSchema)]
 pub struct PackConfirmRequest {
+    /// From `/api/wallet/pack/buy`.
+    pub purchase_id: String,
+    /// The signed payment transaction, for packs bought with SOL. It has to
+    /// have landed, and opens only this pack. Ignored for essence purchases.
     pub payment_signature: String,
-    pub wallet_address: String,
-    pub pack_cards: Vec<PackCardInfo>,
-}
-
-#[derive(Deserialize)]
-pub struct PackCardInfo {
-    pub card_id: String,
-    pub name: String,
-    pub metadata_uri: String,
+    /// Buyer's contribution to the draw. Defaults to `payment_signature`.
+    #[serde(default)]
+    pub client_seed: Option<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/wallet/pack/confirm",
+    tag = "wallet",
+    request_body = PackConfirmRequest,
+    responses(
+        (status = 200, description = "Drawn and minted pack cards, and the revealed seeds", body = crate::openapi::PackConfirmResponse),
+        (status = 400, description = "Invalid wallet or client seed", body = ApiError),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 402, description = "Payment not verified", body = ApiError),
+        (status = 404, description = "Purchase not found", body = ApiError),
+        (status = 409, description = "Pack already opened", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
 pub async fn wallet_pack_confirm(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<PackConfirmRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let solana = require_solana(&state)?;
-    let recipient = Pubkey::from_str(&req.wallet_address)
-        .map_err(|e| err(StatusCode::BAD_REQUEST, format!("Invalid wallet: {e}")))?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let buyer = Pubkey::from_str(&wallet)
+        .map_err(|e| err(ErrorCode::InvalidWallet, format!("Invalid wallet: {e}")))?;
+
+    let purchase = state
+        .assets
+        .pack_purchase(&req.purchase_id)
+        .map_err(|e| err(ErrorCode::StorageError, e))?
+        .filter(|p| p.wallet == wallet)
+        .ok_or_else(|| err(ErrorCode::PackNotFound, "Pack purchase not found"))?;
+    let already_opened = || err(ErrorCode::PackAlreadyOpened, "Pack already opened");
+    if purchase.opened_at.is_some() {
+        return Err(already_opened());
+    }
+    let client_seed = req.client_seed.unwrap_or_else(|| req.payment_signature.clone());
+    if client_seed.is_empty() || client_seed.len() > MAX_CLIENT_SEED_LEN {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("client_seed must be 1-{MAX_CLIENT_SEED_LEN} bytes"),
+        ));
+    }
 
-    // TODO: optionally verify payment_signature landed on-chain
+    // Nothing is drawn until a SOL purchase is paid for, and each payment
+    // opens one pack
+    let payment_signature = if purchase.price_lamports > 0 {
+        let used = state
+            .assets
+            .pack_payment_used(&req.payment_signature)
+            .map_err(|e| err(ErrorCode::StorageError, e))?;
+        if used {
+            return Err(err(
+                ErrorCode::PaymentNotVerified,
+                "This payment already opened another pack",
+            ));
+        }
+        solana
+            .verify_payment(&req.payment_signature, &buyer, purchase.price_lamports)
+            .map_err(|e| match e {
+                PaymentError::Solana(e) => err(ErrorCode::SolanaError, e),
+                PaymentError::Unpaid(e) => err(ErrorCode::PaymentNotVerified, e),
+            })?;
+        Some(req.payment_signature.as_str())
+    } else {
+        None
+    };
 
-    // Mint each card server-side
+    // The draw is fixed by the committed seed and the buyer's; record it,
+    // with a mint for each card, before minting so the pack can only be
+    // opened once and every card it owes is remembered
+    let card_ids =
+        packs::draw(&purchase.slots, &purchase.pool, &purchase.server_seed, &client_seed);
+    let mints: Vec<PackMint> = card_ids
+        .iter()
+        .zip(packs::draw_slots(&purchase.slots))
+        .enumerate()
+        .map(|(slot, (card_id, drawn_by))| PackMint::new(&purchase, slot, card_id, drawn_by))
+        .collect();
+    let opened = state
+        .assets
+        .open_pack(&purchase.id, &client_seed, payment_signature, &mints, card_cache::now_secs())
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    if !opened {
+        return Err(already_opened());
+    }
+    log::info!("{wallet} opened {} pack {}", purchase.pack_id, purchase.id);
+
+    // Mint each card server-side. Cards that sold out (or were removed)
+    // since the buy are refunded; failed mints are retried in the background
+    let mut cards = Vec::new();
     let mut minted = Vec::new();
-    for card in &req.pack_cards {
-        let (sig, asset_pubkey) = solana
-            .server_mint(&card.card_id, &card.name, &card.metadata_uri, &recipient)
-            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, e))?;
-        log::info!("Minted {} -> {} (sig: {})", card.name, asset_pubkey, sig);
-        minted.push(serde_json::json!({
-            "signature": sig,
-            "asset_address": asset_pubkey,
-        }));
+    let mut sold_out = Vec::new();
+    let mut retrying = Vec::new();
+    let mut refunded_essence = 0;
+    for mint in mints {
+        let card = packs::drawn_card(&state, &purchase.pool, &mint.card_id).await;
+        if let Some(card) = &card {
+            cards.push(serde_json::json!({
+                "card_id": card.card_id,
+                "name": card.name,
+                "description": card.description,
+                "image_path": if mint.foil {
+                    image_variants::foil_url(&card.image_path)
+                } else {
+                    card.image_path.clone()
+                },
+                "kind": card.kind,
+                "rarity": card.rarity,
+                "soulbound": mint.soulbound || solana.is_soulbound(&card.card_id),
+                "foil": mint.foil,
+            }));
+        }
+        let mint = packs::fulfill(&state, solana, card.as_ref(), mint, false).await;
+        match mint.status {
+            MintStatus::Minted => minted.push(serde_json::json!({
+                "signature": mint.signature,
+                "asset_address": mint.asset_address,
+                "card_id": mint.card_id,
+                "edition": mint.edition,
+            })),
+            MintStatus::Refunded => {
+                refunded_essence += mint.refund;
+                sold_out.push(mint.card_id);
+            }
+            MintStatus::Pending | MintStatus::Failed => retrying.push(mint.card_id),
+        }
     }
+    solana.forget_owned_cards(&wallet);
 
     Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
+        "purchase_id": purchase.id,
+        "server_seed": purchase.server_seed,
+        "client_seed": client_seed,
+        "cards": cards,
         "minted": minted,
+        "sold_out": sold_out,
+        "retrying": retrying,
+        "refunded_essence": refunded_essence,
     })))
 }
 
 // --- POST /api/wallet/submit-tx ---
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SubmitTxRequest {
     pub signed_transaction: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/wallet/submit-tx",
+    tag = "wallet",
+    request_body = SubmitTxRequest,
+    responses(
+        (status = 200, description = "Submitted transaction signature", body = crate::openapi::SubmitTxResponse),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
 pub async fn wallet_submit_tx(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<SubmitTxRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ApiError>)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     let solana = require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
 
     let signature = solana
         .submit_transaction(&req.signed_transaction)
-        .map_err(|e| err(StatusCode::BAD_GATEWAY, e))?;
+        .map_err(|e| err(ErrorCode::SolanaError, e))?;
+    solana.forget_owned_cards(&wallet);
 
     Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
         "signature": signature,
     })))
 }
+
+// --- POST /api/wallet/refresh-tx ---
+
+/// How long after a mint is handed out it can still be signed again.
+const MINT_REFRESH_SECS: u64 = 60 * 60;
+
+#[derive(Deserialize, ToSchema)]
+pub struct RefreshTxRequest {
+    /// `asset_address` from `/api/wallet/claim` or `/api/wallet/combine`.
+    pub asset_address: String,
+}
+
+/// Sign a claim or combine transaction again with a fresh blockhash, for a
+/// wallet that took too long to sign the first one. It mints the same asset,
+/// so at most one of them can land.
+#[utoipa::path(
+    post,
+    path = "/api/wallet/refresh-tx",
+    tag = "wallet",
+    request_body = RefreshTxRequest,
+    responses(
+        (status = 200, description = "The same mint with a fresh blockhash", body = crate::openapi::RefreshTxResponse),
+        (status = 400, description = "Already minted, or handed out too long ago", body = ApiError),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 403, description = "The claim was retried with another transaction", body = ApiError),
+        (status = 404, description = "No pending mint of that asset for this wallet", body = ApiError),
+        (status = 422, description = "The transaction would fail (see `details.reason`)", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn wallet_refresh_tx(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<RefreshTxRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let solana = require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let store = |e| err(ErrorCode::StorageError, e);
+    let not_found =
+        || err(ErrorCode::CardNotFound, "No pending mint of that asset for this wallet");
+
+    let asset = state
+        .assets
+        .tracked_asset(&req.asset_address)
+        .map_err(store)?
+        .filter(|a| a.wallet == wallet)
+        .ok_or_else(not_found)?;
+    let (Some(mint_tx), Some(seed)) = (&asset.mint_tx, &asset.asset_seed) else {
+        return Err(not_found());
+    };
+    let address = Pubkey::from_str(&asset.asset_address)
+        .map_err(|e| err(ErrorCode::StorageError, format!("Bad tracked asset: {e}")))?;
+    if asset.status != AssetStatus::Pending
+        || solana.account_exists(&address).map_err(|e| err(ErrorCode::SolanaError, e))?
+    {
+        return Err(err(ErrorCode::InvalidRequest, "Already minted"));
+    }
+    if card_cache::now_secs() >= asset.updated_at + MINT_REFRESH_SECS {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            "Transaction was handed out too long ago; start again",
+        ));
+    }
+
+    let tx_base64 = solana.refresh_mint_tx(mint_tx, seed).map_err(tx_error)?;
+
+    // Keep the claim from being retried while this one might still land
+    if let Some(claim) = state.claims.claim_of_asset(&asset.asset_address).map_err(store)? {
+        let claim = Claim {
+            claimed_at: card_cache::now_secs(),
+            ..claim
+        };
+        if !state.claims.record_claim(&claim, Some(&asset.asset_address)).map_err(store)? {
+            return Err(err(
+                ErrorCode::ClaimNotAllowed,
+                "This claim was retried with another transaction",
+            ));
+        }
+    }
+    log::info!("Refreshed mint of {} for {wallet}", asset.asset_address);
+
+    Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
+        "transaction": tx_base64,
+        "asset_address": asset.asset_address,
+    })))
+}
@@ -0,0 +1,1587 @@
+use crate::assets::{AssetStatus, TrackedAsset};
+use crate::card_cache::{
+    now_secs, CachedCard, CardCounts, CardQuery, ConflictStrategy, MergeSummary,
+};
+use crate::claims::{self, Claim, ClaimLimit, ClaimVoucher};
+use crate::config::ClaimSettings;
+use crate::packs::{MintStatus, PackMint, PackPurchase};
+use crate::solana::OwnedCard;
+use crate::storage::{AssetStore, CardStore, ClaimStore, OwnershipStore, TradeStore};
+use crate::trades::{Trade, TradeStatus};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS cards (
+        id            TEXT PRIMARY KEY,
+        name          TEXT NOT NULL,
+        description   TEXT NOT NULL,
+        image_path    TEXT NOT NULL,
+        discovered    INTEGER NOT NULL DEFAULT 0,
+        impossible    INTEGER NOT NULL DEFAULT 0,
+        recipe        TEXT,
+        discovered_at INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS cards_name ON cards (name COLLATE NOCASE);
+    CREATE INDEX IF NOT EXISTS cards_discovered ON cards (discovered);
+    CREATE TABLE IF NOT EXISTS mint_counts (
+        card_id TEXT PRIMARY KEY,
+        count   INTEGER NOT NULL
+    );
+";
+
+/// Applied in order on open; `PRAGMA user_version` records how many ran.
+const MIGRATIONS: &[&str] = &[
+    "
+    ALTER TABLE cards ADD COLUMN created_at INTEGER;
+    ALTER TABLE cards ADD COLUMN times_crafted INTEGER NOT NULL DEFAULT 0;
+    ",
+    "CREATE INDEX IF NOT EXISTS cards_impossible ON cards (impossible, created_at);",
+    "ALTER TABLE cards ADD COLUMN combine_version TEXT;",
+    "
+    CREATE TABLE claims (
+        proof         TEXT PRIMARY KEY,
+        wallet        TEXT NOT NULL,
+        card_id       TEXT NOT NULL,
+        asset_address TEXT NOT NULL,
+        claimed_at    INTEGER NOT NULL
+    );
+    CREATE INDEX claims_wallet ON claims (wallet, claimed_at);
+    CREATE TABLE claim_vouchers (
+        code       TEXT PRIMARY KEY,
+        card_id    TEXT NOT NULL,
+        wallet     TEXT,
+        created_at INTEGER NOT NULL
+    );
+    ",
+    "
+    CREATE TABLE tracked_assets (
+        asset_address TEXT PRIMARY KEY,
+        card_id       TEXT NOT NULL,
+        wallet        TEXT NOT NULL,
+        status        TEXT NOT NULL,
+        signature     TEXT,
+        updated_at    INTEGER NOT NULL
+    );
+    CREATE INDEX tracked_assets_wallet ON tracked_assets (wallet);
+    ",
+    "
+    ALTER TABLE tracked_assets ADD COLUMN reward INTEGER NOT NULL DEFAULT 0;
+    CREATE TABLE essence_balances (
+        wallet  TEXT PRIMARY KEY,
+        balance INTEGER NOT NULL
+    );
+    ",
+    "
+    CREATE TABLE trades (
+        id           TEXT PRIMARY KEY,
+        proposer     TEXT NOT NULL,
+        counterparty TEXT NOT NULL,
+        offered      TEXT NOT NULL,
+        requested    TEXT NOT NULL,
+        status       TEXT NOT NULL,
+        created_at   INTEGER NOT NULL,
+        expires_at   INTEGER NOT NULL,
+        updated_at   INTEGER NOT NULL
+    );
+    CREATE INDEX trades_proposer ON trades (proposer, created_at);
+    CREATE INDEX trades_counterparty ON trades (counterparty, created_at);
+    CREATE INDEX trades_expiry ON trades (status, expires_at);
+    ",
+    "
+    ALTER TABLE tracked_assets ADD COLUMN mint_tx TEXT;
+    ALTER TABLE tracked_assets ADD COLUMN asset_seed TEXT;
+    CREATE INDEX claims_asset ON claims (asset_address);
+    ",
+    "
+    CREATE TABLE editions (
+        card_id TEXT PRIMARY KEY,
+        issued  INTEGER NOT NULL
+    );
+    ",
+    "
+    CREATE TABLE pack_purchases (
+        wallet    TEXT NOT NULL,
+        pack_id   TEXT NOT NULL,
+        bought_at INTEGER NOT NULL
+    );
+    CREATE INDEX pack_purchases_wallet ON pack_purchases (wallet, pack_id, bought_at);
+    ",
+    "
+    ALTER TABLE pack_purchases ADD COLUMN id TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN server_seed TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN slots TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN pool TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN client_seed TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN cards TEXT;
+    ALTER TABLE pack_purchases ADD COLUMN opened_at INTEGER;
+    CREATE UNIQUE INDEX pack_purchases_id ON pack_purchases (id);
+    ",
+    "
+    CREATE TABLE owned_cards (
+        mint_address TEXT PRIMARY KEY,
+        wallet       TEXT NOT NULL,
+        card_id      TEXT NOT NULL,
+        name         TEXT NOT NULL,
+        collection   TEXT NOT NULL,
+        season       TEXT NOT NULL,
+        edition      INTEGER,
+        soulbound    INTEGER NOT NULL,
+        burned       INTEGER NOT NULL DEFAULT 0,
+        updated_at   INTEGER NOT NULL
+    );
+    CREATE INDEX owned_cards_wallet ON owned_cards (wallet);
+    CREATE TABLE indexed_wallets (
+        wallet        TEXT PRIMARY KEY,
+        reconciled_at INTEGER NOT NULL,
+        asked_at      INTEGER NOT NULL
+    );
+    CREATE INDEX indexed_wallets_reconciled ON indexed_wallets (reconciled_at);
+    ",
+    "
+    ALTER TABLE pack_purchases ADD COLUMN refund_essence INTEGER NOT NULL DEFAULT 0;
+    CREATE TABLE pack_mints (
+        purchase_id   TEXT NOT NULL,
+        slot          INTEGER NOT NULL,
+        wallet        TEXT NOT NULL,
+        card_id       TEXT NOT NULL,
+        soulbound     INTEGER NOT NULL,
+        status        TEXT NOT NULL,
+        edition       INTEGER,
+        attempts      INTEGER NOT NULL,
+        asset_address TEXT,
+        signature     TEXT,
+        error         TEXT,
+        refund        INTEGER NOT NULL DEFAULT 0,
+        retry_at      INTEGER NOT NULL,
+        updated_at    INTEGER NOT NULL,
+        PRIMARY KEY (purchase_id, slot)
+    );
+    CREATE INDEX pack_mints_due ON pack_mints (status, retry_at);
+    ",
+    "
+    ALTER TABLE pack_mints ADD COLUMN foil INTEGER NOT NULL DEFAULT 0;
+    ",
+    "
+    ALTER TABLE cards ADD COLUMN discovered_by TEXT;
+    ALTER TABLE cards ADD COLUMN previous_art TEXT;
+    ",
+    "
+    ALTER TABLE cards ADD COLUMN art_path TEXT;
+    ",
+    "
+    ALTER TABLE pack_purchases ADD COLUMN price_lamports INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE pack_purchases ADD COLUMN payment_signature TEXT;
+    CREATE UNIQUE INDEX pack_purchases_payment ON pack_purchases (payment_signature);
+    ",
+    "
+    CREATE TABLE edition_holds (
+        card_id       TEXT NOT NULL,
+        edition       INTEGER NOT NULL,
+        asset_address TEXT UNIQUE,
+        expires_at    INTEGER NOT NULL,
+        PRIMARY KEY (card_id, edition)
+    );
+    CREATE INDEX edition_holds_expiry ON edition_holds (expires_at);
+    ",
+    "
+    ALTER TABLE trades ADD COLUMN escrow_tx TEXT;
+    ",
+    "
+    ALTER TABLE tracked_assets ADD COLUMN crafted INTEGER NOT NULL DEFAULT 0;
+    ",
+];
+
+const TRADE_COLUMNS: &str = "id, proposer, counterparty, offered, requested, status, created_at, \
+     expires_at, updated_at, escrow_tx";
+
+const PACK_COLUMNS: &str = "id, wallet, pack_id, server_seed, slots, pool, bought_at, client_seed, \
+     cards, opened_at, refund_essence, price_lamports, payment_signature";
+
+const PACK_MINT_COLUMNS: &str = "purchase_id, slot, wallet, card_id, soulbound, status, edition, \
+     attempts, asset_address, signature, error, refund, retry_at, updated_at, foil";
+
+const CARD_COLUMNS: &str = "id, name, description, image_path, discovered, impossible, recipe, \
+     discovered_at, created_at, times_crafted, combine_version, discovered_by, previous_art, \
+     art_path";
+
+/// Crafted cards (and impossible combinations) keyed by crafted card id, in
+/// SQLite. Every insert is its own small write, so concurrent games never
+/// rewrite the whole cache and a crash can't leave it half-written.
+pub struct SqliteCardStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteCardStore {
+    /// Open (or create) the cache database at `path`.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open card cache {}: {e}", path.display()))?;
+        // WAL lets the generation scripts write while the server reads
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|_| conn.busy_timeout(Duration::from_secs(5)))
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .and_then(|_| migrate(&conn))
+            .map_err(|e| format!("Failed to initialize card cache: {e}"))?;
+        Ok(SqliteCardStore {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn query_trades(
+        &self,
+        filter: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<Trade>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare(&format!("SELECT {TRADE_COLUMNS} FROM trades {filter}"))
+            .and_then(|mut stmt| stmt.query_map(params, trade_from_row)?.collect())
+            .map_err(|e| e.to_string())
+    }
+
+    fn query_claim(&self, filter: &str, value: &str) -> Result<Option<Claim>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!(
+                "SELECT proof, wallet, card_id, asset_address, claimed_at FROM claims
+                 WHERE {filter}"
+            ),
+            [value],
+            |r| {
+                Ok(Claim {
+                    proof: r.get(0)?,
+                    wallet: r.get(1)?,
+                    card_id: r.get(2)?,
+                    asset_address: r.get(3)?,
+                    claimed_at: r.get::<_, i64>(4)? as u64,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    fn query_cards(&self, filter: &str) -> Result<Vec<CachedCard>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare(&format!("SELECT {CARD_COLUMNS} FROM cards {filter}"))
+            .and_then(|mut stmt| stmt.query_map([], card_from_row)?.collect())
+            .map_err(|e| format!("Card cache scan failed: {e}"))
+    }
+}
+
+impl CardStore for SqliteCardStore {
+    fn get(&self, key: &str) -> Result<Option<CachedCard>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {CARD_COLUMNS} FROM cards WHERE id = ?1"),
+            [key],
+            card_from_row,
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    fn put(&self, card: &CachedCard) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        write_card(&conn, card, ConflictStrategy::Overwrite)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn remove(&self, key: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM cards WHERE id = ?1", [key])
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn all(&self) -> Result<Vec<CachedCard>, String> {
+        self.query_cards("")
+    }
+
+    fn discovered(&self) -> Result<Vec<CachedCard>, String> {
+        self.query_cards("WHERE discovered = 1 AND impossible = 0 AND image_path != ''")
+    }
+
+    fn list(&self, query: &CardQuery) -> Result<(Vec<CachedCard>, u64), String> {
+        let discovered = if query.discovered { "AND discovered = 1" } else { "" };
+        let filter = format!(
+            "WHERE impossible = 0 AND (?1 IS NULL OR name LIKE ?1 ESCAPE '\\') {discovered}"
+        );
+        let order = if query.descending { "DESC" } else { "ASC" };
+        let pattern = query.name_pattern();
+        let conn = self.conn.lock().unwrap();
+        let total = conn
+            .query_row(&format!("SELECT COUNT(*) FROM cards {filter}"), [&pattern], |r| {
+                r.get::<_, i64>(0)
+            })
+            .map_err(|e| format!("Card count failed: {e}"))?;
+        let cards = conn
+            .prepare(&format!(
+                "SELECT {CARD_COLUMNS} FROM cards {filter}
+                 ORDER BY name COLLATE NOCASE {order}, id {order} LIMIT ?2 OFFSET ?3"
+            ))
+            .and_then(|mut stmt| {
+                stmt.query_map(
+                    params![pattern, query.limit as i64, query.offset as i64],
+                    card_from_row,
+                )?
+                .collect()
+            })
+            .map_err(|e| format!("Card list failed: {e}"))?;
+        Ok((cards, total as u64))
+    }
+
+    fn names(&self) -> Result<Vec<(String, String)>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare("SELECT id, name FROM cards WHERE impossible = 0")
+            .and_then(|mut stmt| stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?.collect())
+            .map_err(|e| format!("Card name scan failed: {e}"))
+    }
+
+    fn merge(
+        &self,
+        cards: Vec<CachedCard>,
+        strategy: ConflictStrategy,
+    ) -> Result<MergeSummary, String> {
+        let mut summary = MergeSummary {
+            imported: 0,
+            skipped: 0,
+        };
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for card in &cards {
+            match write_card(&tx, card, strategy).map_err(|e| e.to_string())? {
+                0 => summary.skipped += 1,
+                _ => summary.imported += 1,
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(summary)
+    }
+
+    fn counts(&self) -> Result<CardCounts, String> {
+        let now = now_secs() as i64;
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(discovered = 1 AND impossible = 0), 0),
+                    COALESCE(SUM(impossible = 1), 0),
+                    COALESCE(SUM(created_at >= ?1), 0),
+                    COALESCE(SUM(created_at >= ?2), 0)
+             FROM cards",
+            params![now - 3600, now - 86_400],
+            |r| {
+                let n = |i| r.get::<_, i64>(i).map(|n| n as u64);
+                Ok(CardCounts::new(n(0)?, n(1)?, n(2)?, n(3)?, n(4)?))
+            },
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    fn version_counts(&self) -> Result<HashMap<Option<String>, u64>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare("SELECT combine_version, COUNT(*) FROM cards GROUP BY combine_version")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |r| Ok((r.get(0)?, r.get::<_, i64>(1)? as u64)))?
+                    .collect()
+            })
+            .map_err(|e| e.to_string())
+    }
+
+    fn remove_outdated(&self, version: &str) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare(
+            "DELETE FROM cards
+             WHERE discovered = 0 AND (combine_version IS NULL OR combine_version != ?1)
+             RETURNING id",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([version], |r| r.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    fn prune_impossible(&self, max: usize) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare(
+            "DELETE FROM cards WHERE id IN (
+                 SELECT id FROM cards WHERE impossible = 1
+                 ORDER BY created_at
+                 LIMIT max(0, (SELECT COUNT(*) FROM cards WHERE impossible = 1) - ?1)
+             ) RETURNING id",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([max as i64], |r| r.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    fn record_craft(&self, card_id: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE cards SET times_crafted = times_crafted + 1 WHERE id = ?1",
+            [card_id],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    fn record_mint(&self, card_id: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO mint_counts (card_id, count) VALUES (?1, 1)
+             ON CONFLICT (card_id) DO UPDATE SET count = count + 1",
+            [card_id],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    fn add_mint_counts(&self, counts: &HashMap<String, u64>) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for (card_id, count) in counts {
+            tx.execute(
+                "INSERT INTO mint_counts (card_id, count) VALUES (?1, ?2)
+                 ON CONFLICT (card_id) DO UPDATE SET count = count + excluded.count",
+                params![card_id, *count as i64],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn mint_count(&self, card_id: &str) -> Result<u64, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT count FROM mint_counts WHERE card_id = ?1",
+            [card_id],
+            |r| r.get::<_, i64>(0),
+        )
+        .optional()
+        .map(|n| n.unwrap_or(0) as u64)
+        .map_err(|e| e.to_string())
+    }
+
+    fn mint_counts(&self) -> Result<HashMap<String, u64>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare("SELECT card_id, count FROM mint_counts")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |r| Ok((r.get(0)?, r.get::<_, i64>(1)? as u64)))?
+                    .collect()
+            })
+            .map_err(|e| format!("Failed to read mint counts: {e}"))
+    }
+
+    fn issue_edition(&self, card_id: &str, cap: Option<u64>) -> Result<Option<u64>, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let edition = take_edition(&tx, card_id, cap).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(edition)
+    }
+
+    fn editions(&self, card_id: &str) -> Result<u64, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT issued - (SELECT COUNT(*) FROM edition_holds
+                              WHERE card_id = ?1 AND asset_address IS NULL)
+             FROM editions WHERE card_id = ?1",
+            [card_id],
+            |r| r.get::<_, i64>(0),
+        )
+        .optional()
+        .map(|n| n.unwrap_or(0) as u64)
+        .map_err(|e| e.to_string())
+    }
+
+    fn ping(&self) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT 1", [], |_| Ok(()))
+            .map_err(|e| format!("card cache unavailable: {e}"))
+    }
+}
+
+impl ClaimStore for SqliteCardStore {
+    fn claim(&self, proof: &str) -> Result<Option<Claim>, String> {
+        self.query_claim("proof = ?1", proof)
+    }
+
+    fn claim_of_asset(&self, address: &str) -> Result<Option<Claim>, String> {
+        self.query_claim("asset_address = ?1", address)
+    }
+
+    fn record_claim(&self, claim: &Claim, replacing: Option<&str>) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        let written = match replacing {
+            None => conn.execute(
+                "INSERT OR IGNORE INTO claims (proof, wallet, card_id, asset_address, claimed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    claim.proof,
+                    claim.wallet,
+                    claim.card_id,
+                    claim.asset_address,
+                    claim.claimed_at as i64
+                ],
+            ),
+            Some(previous) => conn.execute(
+                "UPDATE claims SET wallet = ?2, asset_address = ?3, claimed_at = ?4
+                 WHERE proof = ?1 AND asset_address = ?5",
+                params![
+                    claim.proof,
+                    claim.wallet,
+                    claim.asset_address,
+                    claim.claimed_at as i64,
+                    previous
+                ],
+            ),
+        };
+        written.map(|n| n > 0).map_err(|e| e.to_string())
+    }
+
+    fn reserve_claim(
+        &self,
+        claim: &Claim,
+        limits: ClaimSettings,
+    ) -> Result<Result<bool, ClaimLimit>, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let reserve = |tx: &rusqlite::Transaction| -> rusqlite::Result<Result<bool, ClaimLimit>> {
+            let of_card = count_claims(tx, &claim.wallet, Some(&claim.card_id), 0)?;
+            let day_ago = claim.claimed_at.saturating_sub(claims::CLAIM_DAY_SECS);
+            let in_day = count_claims(tx, &claim.wallet, None, day_ago)?;
+            if let Some(limit) = claims::limit_reached(limits, of_card, in_day) {
+                return Ok(Err(limit));
+            }
+            let written = tx.execute(
+                "INSERT OR IGNORE INTO claims (proof, wallet, card_id, asset_address, claimed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    claim.proof,
+                    claim.wallet,
+                    claim.card_id,
+                    claim.asset_address,
+                    claim.claimed_at as i64
+                ],
+            )?;
+            Ok(Ok(written > 0))
+        };
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let reserved = reserve(&tx).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(reserved)
+    }
+
+    fn claim_count(
+        &self,
+        wallet: &str,
+        card_id: Option<&str>,
+        since: u64,
+    ) -> Result<u64, String> {
+        let conn = self.conn.lock().unwrap();
+        count_claims(&conn, wallet, card_id, since).map_err(|e| e.to_string())
+    }
+
+    fn put_voucher(&self, voucher: &ClaimVoucher) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO claim_vouchers (code, card_id, wallet, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                voucher.code,
+                voucher.card_id,
+                voucher.wallet,
+                voucher.created_at as i64
+            ],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    fn voucher(&self, code: &str) -> Result<Option<ClaimVoucher>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT code, card_id, wallet, created_at FROM claim_vouchers WHERE code = ?1",
+            [code],
+            |r| {
+                Ok(ClaimVoucher {
+                    code: r.get(0)?,
+                    card_id: r.get(1)?,
+                    wallet: r.get(2)?,
+                    created_at: r.get::<_, i64>(3)? as u64,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+}
+
+impl AssetStore for SqliteCardStore {
+    fn track_assets(&self, assets: &[TrackedAsset]) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for asset in assets {
+            tx.execute(
+                "INSERT OR REPLACE INTO tracked_assets
+                 (asset_address, card_id, wallet, status, signature, reward, mint_tx,
+                  asset_seed, updated_at, crafted)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    asset.asset_address,
+                    asset.card_id,
+                    asset.wallet,
+                    asset.status.as_str(),
+                    asset.signature,
+                    asset.reward as i64,
+                    asset.mint_tx,
+                    asset.asset_seed,
+                    asset.updated_at as i64,
+                    asset.crafted
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn tracked_asset(&self, address: &str) -> Result<Option<TrackedAsset>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT asset_address, card_id, wallet, status, signature, reward, mint_tx,
+                    asset_seed, updated_at, crafted
+             FROM tracked_assets WHERE asset_address = ?1",
+            [address],
+            |r| {
+                let status: String = r.get(3)?;
+                Ok(TrackedAsset {
+                    asset_address: r.get(0)?,
+                    card_id: r.get(1)?,
+                    wallet: r.get(2)?,
+                    status: AssetStatus::parse(&status).unwrap_or(AssetStatus::Pending),
+                    signature: r.get(4)?,
+                    reward: r.get::<_, i64>(5)? as u64,
+                    mint_tx: r.get(6)?,
+                    asset_seed: r.get(7)?,
+                    updated_at: r.get::<_, i64>(8)? as u64,
+                    crafted: r.get(9)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    fn settle_asset(
+        &self,
+        address: &str,
+        from: AssetStatus,
+        to: AssetStatus,
+        signature: &str,
+        at: u64,
+    ) -> Result<bool, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let settle = |tx: &rusqlite::Transaction| -> rusqlite::Result<bool> {
+            let updated = tx.execute(
+                "UPDATE tracked_assets SET status = ?3, signature = ?4, updated_at = ?5
+                 WHERE asset_address = ?1 AND status = ?2",
+                params![address, from.as_str(), to.as_str(), signature, at as i64],
+            )?;
+            if updated > 0 && to == AssetStatus::Burned {
+                tx.execute(
+                    "INSERT INTO essence_balances (wallet, balance)
+                     SELECT wallet, reward FROM tracked_assets
+                     WHERE asset_address = ?1 AND reward > 0
+                     ON CONFLICT (wallet) DO UPDATE SET balance = balance + excluded.balance",
+                    [address],
+                )?;
+            }
+            if updated > 0 && to == AssetStatus::Confirmed {
+                tx.execute("DELETE FROM edition_holds WHERE asset_address = ?1", [address])?;
+            }
+            if updated > 0 && from == AssetStatus::Pending && to == AssetStatus::Confirmed {
+                tx.execute(
+                    "INSERT INTO mint_counts (card_id, count)
+                     SELECT card_id, 1 FROM tracked_assets WHERE asset_address = ?1
+                     ON CONFLICT (card_id) DO UPDATE SET count = count + 1",
+                    [address],
+                )?;
+            }
+            Ok(updated > 0)
+        };
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let settled = settle(&tx).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(settled)
+    }
+
+    fn hold_edition(
+        &self,
+        card_id: &str,
+        cap: Option<u64>,
+        asset: &str,
+        expires_at: u64,
+    ) -> Result<Option<u64>, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let hold = |tx: &rusqlite::Transaction| -> rusqlite::Result<Option<u64>> {
+            let Some(edition) = take_edition(tx, card_id, cap)? else {
+                return Ok(None);
+            };
+            tx.execute(
+                "INSERT INTO edition_holds (card_id, edition, asset_address, expires_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![card_id, edition as i64, asset, expires_at as i64],
+            )?;
+            Ok(Some(edition))
+        };
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let edition = hold(&tx).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(edition)
+    }
+
+    fn expired_edition_holds(&self, now: u64, limit: usize) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare(
+            "SELECT asset_address FROM edition_holds
+             WHERE asset_address IS NOT NULL AND expires_at <= ?1
+             ORDER BY expires_at LIMIT ?2",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map(params![now as i64, limit as i64], |r| r.get(0))?
+                .collect()
+        })
+        .map_err(|e| e.to_string())
+    }
+
+    fn end_edition_hold(&self, asset: &str, minted: bool) -> Result<(), String> {
+        let sql = if minted {
+            "DELETE FROM edition_holds WHERE asset_address = ?1"
+        } else {
+            "UPDATE edition_holds SET asset_address = NULL WHERE asset_address = ?1"
+        };
+        let conn = self.conn.lock().unwrap();
+        conn.execute(sql, [asset]).map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    fn essence(&self, wallet: &str) -> Result<u64, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT balance FROM essence_balances WHERE wallet = ?1",
+            [wallet],
+            |r| r.get::<_, i64>(0),
+        )
+        .optional()
+        .map(|b| b.unwrap_or(0) as u64)
+        .map_err(|e| e.to_string())
+    }
+
+    fn record_pack_purchase(&self, purchase: &PackPurchase, essence: u64) -> Result<bool, String> {
+        let slots = serde_json::to_string(&purchase.slots).map_err(|e| e.to_string())?;
+        let pool = serde_json::to_string(&purchase.pool).map_err(|e| e.to_string())?;
+        let mut conn = self.conn.lock().unwrap();
+        let record = |tx: &rusqlite::Transaction| -> rusqlite::Result<bool> {
+            if essence > 0 {
+                let paid = tx.execute(
+                    "UPDATE essence_balances SET balance = balance - ?2
+                     WHERE wallet = ?1 AND balance >= ?2",
+                    params![purchase.wallet, essence as i64],
+                )? > 0;
+                if !paid {
+                    return Ok(false);
+                }
+            }
+            tx.execute(
+                "INSERT INTO pack_purchases
+                     (id, wallet, pack_id, server_seed, slots, pool, bought_at, refund_essence,
+                      price_lamports)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    purchase.id,
+                    purchase.wallet,
+                    purchase.pack_id,
+                    purchase.server_seed,
+                    slots,
+                    pool,
+                    purchase.bought_at as i64,
+                    purchase.refund_essence as i64,
+                    purchase.price_lamports as i64
+                ],
+            )?;
+            Ok(true)
+        };
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let recorded = record(&tx).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(recorded)
+    }
+
+    fn pack_purchase(&self, id: &str) -> Result<Option<PackPurchase>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {PACK_COLUMNS} FROM pack_purchases WHERE id = ?1"),
+            [id],
+            purchase_from_row,
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    fn pack_payment_used(&self, signature: &str) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT 1 FROM pack_purchases WHERE payment_signature = ?1",
+            [signature],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|used| used.is_some())
+        .map_err(|e| e.to_string())
+    }
+
+    fn open_pack(
+        &self,
+        id: &str,
+        client_seed: &str,
+        payment_signature: Option<&str>,
+        mints: &[PackMint],
+        at: u64,
+    ) -> Result<bool, String> {
+        let cards: Vec<&str> = mints.iter().map(|m| m.card_id.as_str()).collect();
+        let cards = serde_json::to_string(&cards).map_err(|e| e.to_string())?;
+        let mut conn = self.conn.lock().unwrap();
+        let open = |tx: &rusqlite::Transaction| -> rusqlite::Result<bool> {
+            let opened = tx.execute(
+                "UPDATE pack_purchases
+                 SET client_seed = ?2, cards = ?3, opened_at = ?4, payment_signature = ?5
+                 WHERE id = ?1 AND opened_at IS NULL",
+                params![id, client_seed, cards, at as i64, payment_signature],
+            )? > 0;
+            if opened {
+                for mint in mints {
+                    tx.execute(
+                        &format!(
+                            "INSERT INTO pack_mints ({PACK_MINT_COLUMNS})
+                             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14,
+                                     ?15)"
+                        ),
+                        params![
+                            mint.purchase_id,
+                            mint.slot as i64,
+                            mint.wallet,
+                            mint.card_id,
+                            mint.soulbound,
+                            mint.status.as_str(),
+                            mint.edition.map(|e| e as i64),
+                            mint.attempts,
+                            mint.asset_address,
+                            mint.signature,
+                            mint.error,
+                            mint.refund as i64,
+                            mint.retry_at as i64,
+                            mint.updated_at as i64,
+                            mint.foil
+                        ],
+                    )?;
+                }
+            }
+            Ok(opened)
+        };
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let opened = open(&tx).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(opened)
+    }
+
+    fn pack_purchases(&self, wallet: &str, pack_id: &str, since: u64) -> Result<u64, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM pack_purchases
+             WHERE wallet = ?1 AND pack_id = ?2 AND bought_at >= ?3",
+            params![wallet, pack_id, since as i64],
+            |r| r.get::<_, i64>(0),
+        )
+        .map(|n| n as u64)
+        .map_err(|e| e.to_string())
+    }
+
+    fn pack_mints(&self, purchase_id: &str) -> Result<Vec<PackMint>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare(&format!(
+            "SELECT {PACK_MINT_COLUMNS} FROM pack_mints WHERE purchase_id = ?1 ORDER BY slot"
+        ))
+        .and_then(|mut stmt| stmt.query_map([purchase_id], pack_mint_from_row)?.collect())
+        .map_err(|e| e.to_string())
+    }
+
+    fn update_pack_mint(&self, mint: &PackMint) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE pack_mints SET status = ?3, edition = ?4, attempts = ?5, asset_address = ?6,
+                 signature = ?7, error = ?8, retry_at = ?9, updated_at = ?10
+             WHERE purchase_id = ?1 AND slot = ?2 AND status IN ('pending', 'failed')",
+            params![
+                mint.purchase_id,
+                mint.slot as i64,
+                mint.status.as_str(),
+                mint.edition.map(|e| e as i64),
+                mint.attempts,
+                mint.asset_address,
+                mint.signature,
+                mint.error,
+                mint.retry_at as i64,
+                mint.updated_at as i64
+            ],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    fn refund_pack_mint(&self, mint: &PackMint) -> Result<Option<u64>, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let refund = |tx: &rusqlite::Transaction| -> rusqlite::Result<Option<i64>> {
+            let refund: Option<i64> = tx
+                .query_row(
+                    "UPDATE pack_mints SET status = 'refunded', edition = ?3, attempts = ?4,
+                         error = ?5, updated_at = ?6,
+                         refund = (SELECT refund_essence FROM pack_purchases WHERE id = ?1)
+                     WHERE purchase_id = ?1 AND slot = ?2 AND status IN ('pending', 'failed')
+                     RETURNING refund",
+                    params![
+                        mint.purchase_id,
+                        mint.slot as i64,
+                        mint.edition.map(|e| e as i64),
+                        mint.attempts,
+                        mint.error,
+                        mint.updated_at as i64
+                    ],
+                    |r| r.get(0),
+                )
+                .optional()?;
+            if let Some(amount) = refund.filter(|&a| a > 0) {
+                tx.execute(
+                    "INSERT INTO essence_balances (wallet, balance) VALUES (?1, ?2)
+                     ON CONFLICT (wallet) DO UPDATE SET balance = balance + excluded.balance",
+                    params![mint.wallet, amount],
+                )?;
+            }
+            Ok(refund)
+        };
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let refunded = refund(&tx).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(refunded.map(|a| a as u64))
+    }
+
+    fn due_pack_mints(&self, now: u64, until: u64, limit: usize) -> Result<Vec<PackMint>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare(&format!(
+            "UPDATE pack_mints SET retry_at = ?2
+             WHERE (purchase_id, slot) IN (
+                 SELECT purchase_id, slot FROM pack_mints
+                 WHERE status IN ('pending', 'failed') AND retry_at <= ?1
+                 ORDER BY retry_at LIMIT ?3)
+             RETURNING {PACK_MINT_COLUMNS}"
+        ))
+        .and_then(|mut stmt| {
+            stmt.query_map(params![now as i64, until as i64, limit as i64], pack_mint_from_row)?
+                .collect()
+        })
+        .map_err(|e| e.to_string())
+    }
+}
+
+impl TradeStore for SqliteCardStore {
+    fn put_trade(&self, trade: &Trade) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            &format!(
+                "INSERT INTO trades ({TRADE_COLUMNS})
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+            ),
+            params![
+                trade.id,
+                trade.proposer,
+                trade.counterparty,
+                serde_json::to_string(&trade.offered).map_err(|e| e.to_string())?,
+                serde_json::to_string(&trade.requested).map_err(|e| e.to_string())?,
+                trade.status.as_str(),
+                trade.created_at as i64,
+                trade.expires_at as i64,
+                trade.updated_at as i64,
+                trade.escrow_tx
+            ],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    fn trade(&self, id: &str) -> Result<Option<Trade>, String> {
+        Ok(self.query_trades("WHERE id = ?1", &[&id])?.pop())
+    }
+
+    fn trades_for(&self, wallet: &str, limit: usize) -> Result<Vec<Trade>, String> {
+        self.query_trades(
+            "WHERE proposer = ?1 OR counterparty = ?1 ORDER BY created_at DESC LIMIT ?2",
+            &[&wallet, &(limit as i64)],
+        )
+    }
+
+    fn live_trades(&self, wallet: &str) -> Result<Vec<Trade>, String> {
+        self.query_trades(
+            "WHERE proposer = ?1 AND status IN ('open', 'accepted')",
+            &[&wallet],
+        )
+    }
+
+    fn expired_trades(&self, now: u64) -> Result<Vec<Trade>, String> {
+        self.query_trades(
+            "WHERE status IN ('open', 'accepted') AND expires_at <= ?1",
+            &[&(now as i64)],
+        )
+    }
+
+    fn set_trade_status(
+        &self,
+        id: &str,
+        from: TradeStatus,
+        to: TradeStatus,
+        at: u64,
+    ) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE trades SET status = ?3, updated_at = ?4 WHERE id = ?1 AND status = ?2",
+            params![id, from.as_str(), to.as_str(), at as i64],
+        )
+        .map(|n| n > 0)
+        .map_err(|e| e.to_string())
+    }
+
+    fn set_trade_escrow(&self, id: &str, signature: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE trades SET escrow_tx = ?2 WHERE id = ?1 AND escrow_tx IS NULL",
+            params![id, signature],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Claims by `wallet` at or after `since`, optionally only of `card_id`.
+fn count_claims(
+    conn: &Connection,
+    wallet: &str,
+    card_id: Option<&str>,
+    since: u64,
+) -> rusqlite::Result<u64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM claims
+         WHERE wallet = ?1 AND claimed_at >= ?2 AND (?3 IS NULL OR card_id = ?3)",
+        params![wallet, since as i64, card_id],
+        |r| r.get::<_, i64>(0),
+    )
+    .map(|n| n as u64)
+}
+
+/// The lowest edition of `card_id` a mint that never landed gave back, else
+/// the next one unless `cap` of them have been issued.
+fn take_edition(
+    conn: &Connection,
+    card_id: &str,
+    cap: Option<u64>,
+) -> rusqlite::Result<Option<u64>> {
+    let released = conn
+        .query_row(
+            "DELETE FROM edition_holds WHERE card_id = ?1 AND edition = (
+               SELECT MIN(edition) FROM edition_holds
+               WHERE card_id = ?1 AND asset_address IS NULL)
+             RETURNING edition",
+            [card_id],
+            |r| r.get::<_, i64>(0),
+        )
+        .optional()?;
+    if let Some(edition) = released {
+        return Ok(Some(edition as u64));
+    }
+    conn.query_row(
+        "INSERT INTO editions (card_id, issued) VALUES (?1, 1)
+         ON CONFLICT (card_id) DO UPDATE SET issued = issued + 1
+         WHERE ?2 IS NULL OR issued < ?2
+         RETURNING issued",
+        params![card_id, cap.map(|n| n as i64)],
+        |r| r.get::<_, i64>(0),
+    )
+    .optional()
+    .map(|n| n.map(|n| n as u64))
+}
+
+/// Insert or update an indexed card unless it was burned, or (with `newer`)
+/// changed after `at`.
+fn upsert_owned(
+    tx: &rusqlite::Transaction,
+    wallet: &str,
+    card: &OwnedCard,
+    at: u64,
+    newer: bool,
+) -> rusqlite::Result<usize> {
+    let guard = if newer { "AND owned_cards.updated_at < excluded.updated_at" } else { "" };
+    tx.execute(
+        &format!(
+            "INSERT INTO owned_cards
+             (mint_address, wallet, card_id, name, collection, season, edition, soulbound,
+              updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT (mint_address) DO UPDATE SET
+                wallet = excluded.wallet, card_id = excluded.card_id, name = excluded.name,
+                collection = excluded.collection, season = excluded.season,
+                edition = COALESCE(excluded.edition, owned_cards.edition),
+                soulbound = excluded.soulbound, updated_at = excluded.updated_at
+             WHERE owned_cards.burned = 0 {guard}"
+        ),
+        params![
+            card.mint_address,
+            wallet,
+            card.card_id,
+            card.name,
+            card.collection,
+            card.season,
+            card.edition.map(|e| e as i64),
+            card.soulbound,
+            at as i64
+        ],
+    )
+}
+
+impl OwnershipStore for SqliteCardStore {
+    fn owned_cards(&self, wallet: &str, at: u64) -> Result<Option<(u64, Vec<OwnedCard>)>, String> {
+        let conn = self.conn.lock().unwrap();
+        let reconciled_at: Option<i64> = conn
+            .query_row(
+                "UPDATE indexed_wallets SET asked_at = ?2 WHERE wallet = ?1
+                 RETURNING reconciled_at",
+                params![wallet, at as i64],
+                |r| r.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some(reconciled_at) = reconciled_at else {
+            return Ok(None);
+        };
+        let cards = conn
+            .prepare(
+                "SELECT mint_address, card_id, name, collection, season, edition, soulbound
+                 FROM owned_cards WHERE wallet = ?1 AND burned = 0",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([wallet], |r| {
+                    Ok(OwnedCard {
+                        mint_address: r.get(0)?,
+                        card_id: r.get(1)?,
+                        name: r.get(2)?,
+                        image: String::new(),
+                        metadata_uri: String::new(),
+                        collection: r.get(3)?,
+                        season: r.get(4)?,
+                        edition: r.get::<_, Option<i64>>(5)?.map(|e| e as u64),
+                        soulbound: r.get(6)?,
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            .map_err(|e| e.to_string())?;
+        Ok(Some((reconciled_at as u64, cards)))
+    }
+
+    fn reconcile_wallet(&self, wallet: &str, cards: &[OwnedCard], at: u64) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let reconcile = |tx: &rusqlite::Transaction| -> rusqlite::Result<()> {
+            for card in cards {
+                upsert_owned(tx, wallet, card, at, true)?;
+            }
+            // Whatever DAS didn't list and nothing touched since has moved on
+            tx.execute(
+                "DELETE FROM owned_cards WHERE wallet = ?1 AND burned = 0 AND updated_at < ?2",
+                params![wallet, at as i64],
+            )?;
+            tx.execute(
+                "INSERT INTO indexed_wallets (wallet, reconciled_at, asked_at)
+                 VALUES (?1, ?2, ?2)
+                 ON CONFLICT (wallet) DO UPDATE SET reconciled_at = excluded.reconciled_at",
+                params![wallet, at as i64],
+            )?;
+            Ok(())
+        };
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        reconcile(&tx).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn index_mint(&self, wallet: &str, card: &OwnedCard, at: u64) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        upsert_owned(&tx, wallet, card, at, false).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn index_burn(&self, wallet: &str, card: &OwnedCard, at: u64) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        upsert_owned(&tx, wallet, card, at, false)
+            .and_then(|_| {
+                tx.execute(
+                    "UPDATE owned_cards SET burned = 1 WHERE mint_address = ?1",
+                    [&card.mint_address],
+                )
+            })
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    fn forget_holder(&self, mint: &str) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE indexed_wallets SET reconciled_at = 0
+             WHERE wallet = (SELECT wallet FROM owned_cards WHERE mint_address = ?1)",
+            [mint],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    fn stale_wallets(
+        &self,
+        before: u64,
+        active_since: u64,
+        limit: usize,
+    ) -> Result<Vec<String>, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare(
+            "SELECT wallet FROM indexed_wallets WHERE reconciled_at < ?1 AND asked_at >= ?2
+             ORDER BY reconciled_at LIMIT ?3",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map(params![before as i64, active_since as i64, limit as i64], |r| r.get(0))?
+                .collect()
+        })
+        .map_err(|e| e.to_string())
+    }
+}
+
+fn trade_from_row(row: &Row) -> rusqlite::Result<Trade> {
+    let offered: String = row.get(3)?;
+    let requested: String = row.get(4)?;
+    let status: String = row.get(5)?;
+    Ok(Trade {
+        id: row.get(0)?,
+        proposer: row.get(1)?,
+        counterparty: row.get(2)?,
+        offered: serde_json::from_str(&offered).unwrap_or_default(),
+        requested: serde_json::from_str(&requested).unwrap_or_default(),
+        status: TradeStatus::parse(&status).unwrap_or(TradeStatus::Cancelled),
+        created_at: row.get::<_, i64>(6)? as u64,
+        expires_at: row.get::<_, i64>(7)? as u64,
+        updated_at: row.get::<_, i64>(8)? as u64,
+        escrow_tx: row.get(9)?,
+    })
+}
+
+fn purchase_from_row(row: &Row) -> rusqlite::Result<PackPurchase> {
+    let slots: String = row.get(4)?;
+    let pool: String = row.get(5)?;
+    let cards: Option<String> = row.get(8)?;
+    Ok(PackPurchase {
+        id: row.get(0)?,
+        wallet: row.get(1)?,
+        pack_id: row.get(2)?,
+        server_seed: row.get(3)?,
+        slots: serde_json::from_str(&slots).unwrap_or_default(),
+        pool: serde_json::from_str(&pool).unwrap_or_default(),
+        bought_at: row.get::<_, i64>(6)? as u64,
+        client_seed: row.get(7)?,
+        cards: cards.and_then(|c| serde_json::from_str(&c).ok()).unwrap_or_default(),
+        opened_at: row.get::<_, Option<i64>>(9)?.map(|t| t as u64),
+        refund_essence: row.get::<_, i64>(10)? as u64,
+        price_lamports: row.get::<_, i64>(11)? as u64,
+        payment_signature: row.get(12)?,
+    })
+}
+
+fn pack_mint_from_row(row: &Row) -> rusqlite::Result<PackMint> {
+    let status: String = row.get(5)?;
+    Ok(PackMint {
+        purchase_id: row.get(0)?,
+        slot: row.get::<_, i64>(1)? as usize,
+        wallet: row.get(2)?,
+        card_id: row.get(3)?,
+        soulbound: row.get(4)?,
+        foil: row.get(14)?,
+        status: MintStatus::parse(&status).unwrap_or(MintStatus::Failed),
+        edition: row.get::<_, Option<i64>>(6)?.map(|e| e as u64),
+        attempts: row.get(7)?,
+        asset_address: row.get(8)?,
+        signature: row.get(9)?,
+        error: row.get(10)?,
+        refund: row.get::<_, i64>(11)? as u64,
+        retry_at: row.get::<_, i64>(12)? as u64,
+        updated_at: row.get::<_, i64>(13)? as u64,
+    })
+}
+
+fn card_from_row(row: &Row) -> rusqlite::Result<CachedCard> {
+    let recipe: Option<String> = row.get(6)?;
+    Ok(CachedCard {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        image_path: row.get(3)?,
+        discovered: row.get(4)?,
+        impossible: row.get(5)?,
+        recipe: recipe.and_then(|r| serde_json::from_str(&r).ok()),
+        discovered_at: row.get::<_, Option<i64>>(7)?.map(|t| t as u64),
+        created_at: row.get::<_, Option<i64>>(8)?.map(|t| t as u64),
+        times_crafted: row.get::<_, i64>(9)? as u64,
+        combine_version: row.get(10)?,
+        discovered_by: row.get(11)?,
+        previous_art: row
+            .get::<_, Option<String>>(12)?
+            .and_then(|art| serde_json::from_str(&art).ok())
+            .unwrap_or_default(),
+        art_path: row.get(13)?,
+    })
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        conn.execute_batch(&format!(
+            "BEGIN; {migration} PRAGMA user_version = {}; COMMIT;",
+            i + 1
+        ))?;
+    }
+    Ok(())
+}
+
+/// Returns the number of rows written (0 when `KeepExisting` skipped it).
+fn write_card(
+    conn: &Connection,
+    card: &CachedCard,
+    strategy: ConflictStrategy,
+) -> rusqlite::Result<usize> {
+    let verb = match strategy {
+        ConflictStrategy::KeepExisting => "INSERT OR IGNORE",
+        ConflictStrategy::Overwrite => "INSERT OR REPLACE",
+    };
+    let recipe = card
+        .recipe
+        .as_ref()
+        .and_then(|r| serde_json::to_string(r).ok());
+    let previous_art = (!card.previous_art.is_empty())
+        .then(|| serde_json::to_string(&card.previous_art).ok())
+        .flatten();
+    conn.execute(
+        &format!(
+            "{verb} INTO cards ({CARD_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)"
+        ),
+        params![
+            card.id,
+            card.name,
+            card.description,
+            card.image_path,
+            card.discovered,
+            card.impossible,
+            recipe,
+            card.discovered_at.map(|t| t as i64),
+            card.created_at.map(|t| t as i64),
+            card.times_crafted as i64,
+            card.combine_version,
+            card.discovered_by,
+            previous_art,
+            card.art_path,
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> SqliteCardStore {
+        SqliteCardStore::open(Path::new(":memory:")).expect("opens")
+    }
+
+    fn burning(store: &SqliteCardStore, address: &str, reward: u64) {
+        let mut asset = TrackedAsset::new(address, "card", "wallet", AssetStatus::Burning);
+        asset.reward = reward;
+        store.track_assets(&[asset]).expect("tracks");
+    }
+
+    #[test]
+    fn settling_a_burn_credits_its_reward_once() {
+        let store = store();
+        burning(&store, "asset", 7);
+        let settle =
+            || store.settle_asset("asset", AssetStatus::Burning, AssetStatus::Burned, "sig", 1);
+        assert!(settle().unwrap());
+        assert!(!settle().unwrap());
+        assert_eq!(store.essence("wallet").unwrap(), 7);
+    }
+
+    #[test]
+    fn settling_a_burn_back_to_confirmed_credits_nothing() {
+        let store = store();
+        burning(&store, "asset", 7);
+        assert!(store
+            .settle_asset("asset", AssetStatus::Burning, AssetStatus::Confirmed, "sig", 1)
+            .unwrap());
+        assert_eq!(store.essence("wallet").unwrap(), 0);
+        let asset = store.tracked_asset("asset").unwrap().expect("tracked");
+        assert_eq!(asset.status, AssetStatus::Confirmed);
+    }
+
+    #[test]
+    fn a_landed_mint_counts_once() {
+        let store = store();
+        let asset = TrackedAsset::new("asset", "card", "wallet", AssetStatus::Pending);
+        store.track_assets(&[asset]).expect("tracks");
+        let settle =
+            || store.settle_asset("asset", AssetStatus::Pending, AssetStatus::Confirmed, "sig", 1);
+        assert!(settle().unwrap());
+        assert!(!settle().unwrap());
+        assert_eq!(store.mint_count("card").unwrap(), 1);
+    }
+
+    fn card(id: &str, name: &str, discovered: bool, impossible: bool) -> CachedCard {
+        CachedCard {
+            name: name.to_string(),
+            description: String::new(),
+            image_path: String::new(),
+            id: id.to_string(),
+            discovered,
+            impossible,
+            recipe: None,
+            discovered_at: None,
+            created_at: None,
+            times_crafted: 0,
+            combine_version: None,
+            discovered_by: None,
+            previous_art: Vec::new(),
+            art_path: None,
+        }
+    }
+
+    fn listed(store: &SqliteCardStore, query: CardQuery) -> (Vec<String>, u64) {
+        let (cards, total) = store.list(&query).unwrap();
+        (cards.into_iter().map(|c| c.id).collect(), total)
+    }
+
+    fn query(name: Option<&str>, descending: bool, offset: usize, limit: usize) -> CardQuery {
+        CardQuery {
+            discovered: false,
+            name: name.map(str::to_string),
+            descending,
+            offset,
+            limit,
+        }
+    }
+
+    #[test]
+    fn listing_filters_sorts_and_pages_cards() {
+        let store = store();
+        for c in [
+            card("a", "apple", true, false),
+            card("b", "Banana", true, false),
+            card("c", "cherry", false, false),
+            card("d", "apricot", true, true),
+            card("e", "50%_off", true, false),
+        ] {
+            store.put(&c).unwrap();
+        }
+        // Names sort without regard to case; impossible entries never list
+        assert_eq!(listed(&store, query(None, false, 0, 10)).0, ["e", "a", "b", "c"]);
+        assert_eq!(listed(&store, query(None, true, 0, 10)).0, ["c", "b", "a", "e"]);
+        let mut discovered = query(None, false, 0, 10);
+        discovered.discovered = true;
+        assert_eq!(listed(&store, discovered), (vec!["e".into(), "a".into(), "b".into()], 3));
+        // Name filters are case-insensitive substrings, wildcards taken literally
+        assert_eq!(listed(&store, query(Some("AN"), false, 0, 10)).0, ["b"]);
+        assert_eq!(listed(&store, query(Some("%_"), false, 0, 10)).0, ["e"]);
+        assert_eq!(listed(&store, query(Some("_"), false, 0, 10)).0, ["e"]);
+    }
+
+    #[test]
+    fn pages_end_where_the_cards_do() {
+        let store = store();
+        for (id, name) in [("a", "a"), ("b", "b"), ("c", "c")] {
+            store.put(&card(id, name, true, false)).unwrap();
+        }
+        assert_eq!(listed(&store, query(None, false, 0, 2)), (vec!["a".into(), "b".into()], 3));
+        assert_eq!(listed(&store, query(None, false, 2, 2)), (vec!["c".into()], 3));
+        assert_eq!(listed(&store, query(None, false, 3, 2)), (vec![], 3));
+        assert_eq!(listed(&store, query(None, false, 0, 0)), (vec![], 3));
+    }
+
+    fn trade(id: &str, expires_at: u64) -> Trade {
+        Trade {
+            id: id.to_string(),
+            proposer: "alice".to_string(),
+            counterparty: "bob".to_string(),
+            offered: vec!["a1".to_string()],
+            requested: vec!["b1".to_string()],
+            status: TradeStatus::Open,
+            created_at: 0,
+            expires_at,
+            updated_at: 0,
+            escrow_tx: None,
+        }
+    }
+
+    #[test]
+    fn trade_moves_only_from_the_status_it_is_in() {
+        let store = store();
+        store.put_trade(&trade("t", 100)).unwrap();
+        let (open, accepted) = (TradeStatus::Open, TradeStatus::Accepted);
+        assert!(store.set_trade_status("t", open, accepted, 1).unwrap());
+        // A second accept, or a cancel from open, lost the race
+        assert!(!store.set_trade_status("t", open, accepted, 2).unwrap());
+        assert!(!store.set_trade_status("t", open, TradeStatus::Cancelled, 2).unwrap());
+        assert!(store.set_trade_status("t", accepted, TradeStatus::Completed, 3).unwrap());
+        let t = store.trade("t").unwrap().expect("stored");
+        assert_eq!((t.status, t.updated_at), (TradeStatus::Completed, 3));
+        assert!(store.live_trades("alice").unwrap().is_empty());
+    }
+
+    #[test]
+    fn escrow_transaction_is_recorded_once() {
+        let store = store();
+        store.put_trade(&trade("t", 100)).unwrap();
+        store.set_trade_escrow("t", "first").unwrap();
+        store.set_trade_escrow("t", "second").unwrap();
+        let t = store.trade("t").unwrap().expect("stored");
+        assert_eq!(t.escrow_tx.as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn only_live_trades_expire() {
+        let store = store();
+        store.put_trade(&trade("open", 100)).unwrap();
+        store.put_trade(&trade("cancelled", 100)).unwrap();
+        store.put_trade(&trade("later", 200)).unwrap();
+        store
+            .set_trade_status("cancelled", TradeStatus::Open, TradeStatus::Cancelled, 1)
+            .unwrap();
+        let expired: Vec<String> =
+            store.expired_trades(150).unwrap().into_iter().map(|t| t.id).collect();
+        assert_eq!(expired, ["open"]);
+    }
+}
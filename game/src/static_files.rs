@@ -0,0 +1,192 @@
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use crate::image_variants::{self, Format, Size};
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Query, Request, State};
+use axum::http::{header, HeaderValue, Method, StatusCode, Uri};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use serde::Deserialize;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tower_http::compression::CompressionLayer;
+use tower_http::services::ServeDir;
+use tower_http::set_header::SetResponseHeaderLayer;
+
+/// Crafted card art is named by content hash, so a URL never changes content.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// Base card art and NFT metadata rarely change.
+const CARDS: &str = "public, max-age=3600";
+/// Frontend JS/CSS/HTML change on deploy, so always revalidate (cheap with ETags).
+const REVALIDATE: &str = "no-cache";
+
+/// `/cards`: crafted art cached forever, everything else for an hour. Card
+/// PNGs also accept `?size=board|thumb|hand`, `?format=webp|apng` and `?foil=true`.
+pub fn cards(state: Arc<AppState>) -> Router {
+    let dir = &state.paths.cards_dir;
+    Router::new()
+        .nest_service("/crafted", serve(&dir.join("crafted"), IMMUTABLE))
+        .fallback_service(serve(dir, CARDS))
+        .layer(middleware::from_fn_with_state(state.clone(), variants))
+}
+
+/// The frontend (index.html, app.js, docs).
+pub fn frontend(dir: &Path) -> Router {
+    serve(dir, REVALIDATE)
+}
+
+/// ServeDir with ETags, a Cache-Control policy, and gzip/brotli for text
+/// assets (PNGs are already compressed and skipped by the default predicate).
+fn serve(dir: &Path, cache_control: &'static str) -> Router {
+    Router::new()
+        .fallback_service(ServeDir::new(dir))
+        .layer(middleware::from_fn(etag))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(cache_control),
+        ))
+        .layer(CompressionLayer::new())
+}
+
+#[derive(Deserialize)]
+struct VariantQuery {
+    #[serde(default)]
+    size: Size,
+    #[serde(default)]
+    format: Format,
+    #[serde(default)]
+    foil: bool,
+}
+
+/// Point a card PNG request with `size`/`format`/`foil` at that variant's file,
+/// building it first if it doesn't exist. Builds count against the client's
+/// IP limit and take a render worker; concurrent requests for the same
+/// missing variant build it once. Anything that can't be resolved falls
+/// through to the original.
+async fn variants(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if req.uri().query().is_none() || !req.uri().path().ends_with(".png") {
+        return next.run(req).await;
+    }
+    let query = match Query::<VariantQuery>::try_from_uri(req.uri()) {
+        Ok(Query(query)) => query,
+        Err(e) => return err(ErrorCode::InvalidRequest, e.body_text()).into_response(),
+    };
+    if query.size == Size::Full && query.format == Format::Png && !query.foil {
+        return next.run(req).await;
+    }
+
+    // Only plain relative paths; ServeDir would refuse anything else anyway
+    let rel = Path::new(req.uri().path().trim_start_matches('/'));
+    if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+        return next.run(req).await;
+    }
+    let original = state.paths.cards_dir.join(rel);
+    if !original.is_file() {
+        return next.run(req).await;
+    }
+
+    let mut variant =
+        image_variants::variant_path(&original, query.size, query.format, query.foil);
+    if !variant.exists() {
+        let ip = state.rate_limiter.client_ip(req.headers(), peer);
+        variant = match build(&state, ip, original, query).await {
+            Ok(Ok(variant)) => variant,
+            Ok(Err(e)) => {
+                log::warn!("Serving original; variant failed: {e}");
+                return next.run(req).await;
+            }
+            Err(e) => return e.into_response(),
+        };
+    }
+
+    let file_name = variant.file_name().unwrap_or_default().to_string_lossy();
+    let path = req.uri().path();
+    let dir_part = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+    if let Ok(uri) = format!("{dir_part}{file_name}").parse::<Uri>() {
+        *req.uri_mut() = uri;
+    }
+    next.run(req).await
+}
+
+/// Build a missing variant on the render queue, once per file however many
+/// requests ask for it at the same time.
+async fn build(
+    state: &AppState,
+    ip: IpAddr,
+    original: PathBuf,
+    query: VariantQuery,
+) -> Result<Result<PathBuf, String>, ApiError> {
+    if let Err(retry_after) = state.rate_limiter.check_ip(ip) {
+        log::warn!("Rate limited {ip} building {}", original.display());
+        return Err(ApiError::rate_limited("ip", retry_after));
+    }
+    let variant = image_variants::variant_path(&original, query.size, query.format, query.foil);
+    let _turn = state.render.building(&variant).await;
+    state
+        .render
+        .run(move || image_variants::ensure(&original, query.size, query.format, query.foil))
+        .await
+}
+
+/// Add a weak ETag derived from the file's size and modification time, and
+/// answer `If-None-Match` with 304. Runs inside compression so Content-Length
+/// still describes the file on disk.
+async fn etag(req: Request, next: Next) -> Response {
+    let is_read = matches!(*req.method(), Method::GET | Method::HEAD);
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+    let mut resp = next.run(req).await;
+    if !is_read || resp.status() != StatusCode::OK {
+        return resp;
+    }
+
+    let headers = resp.headers();
+    let (Some(len), Some(modified)) = (
+        headers.get(header::CONTENT_LENGTH),
+        headers.get(header::LAST_MODIFIED),
+    ) else {
+        return resp;
+    };
+    let mut hasher = DefaultHasher::new();
+    modified.as_bytes().hash(&mut hasher);
+    let tag = format!(
+        "W/\"{}-{:x}\"",
+        len.to_str().unwrap_or("0"),
+        hasher.finish()
+    );
+    let Ok(tag) = HeaderValue::from_str(&tag) else {
+        return resp;
+    };
+
+    let matched = if_none_match
+        .as_ref()
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim() == "*" || v.split(',').any(|t| weak_eq(t.trim(), &tag)));
+    if matched {
+        let mut not_modified = Response::new(Body::empty());
+        *not_modified.status_mut() = StatusCode::NOT_MODIFIED;
+        if let Some(modified) = resp.headers().get(header::LAST_MODIFIED) {
+            not_modified
+                .headers_mut()
+                .insert(header::LAST_MODIFIED, modified.clone());
+        }
+        not_modified.headers_mut().insert(header::ETAG, tag);
+        return not_modified;
+    }
+
+    resp.headers_mut().insert(header::ETAG, tag);
+    resp
+}
+
+/// Weak comparison (RFC 9110 §8.8.3.2): ignore the `W/` prefix on both sides.
+fn weak_eq(a: &str, b: &HeaderValue) -> bool {
+    let b = b.to_str().unwrap_or("");
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
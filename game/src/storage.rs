@@ -0,0 +1,349 @@
+//! Persistence backends. By default cards, NFT claims, tracked NFTs and
+//! trades live in SQLite and games in a JSON file; with the `postgres`
+//! feature and `[storage] backend = "postgres"`, all of it goes to a Postgres
+//! database that several game servers can share. With the `redis` feature
+//! and `[redis] url`, a Redis server sits in front of the card store and
+//! carries shared rate limits and cache invalidations.
+
+use crate::assets::{AssetStatus, TrackedAsset};
+use crate::card_cache::{CachedCard, CardCounts, CardQuery, ConflictStrategy, MergeSummary};
+use crate::claims::{Claim, ClaimLimit, ClaimVoucher};
+use crate::config::{ClaimSettings, Config, StorageBackend};
+use crate::game_state::{load_games, save_games, GameState};
+use crate::packs::{PackMint, PackPurchase};
+use crate::rate_limit::SharedBuckets;
+use crate::solana::OwnedCard;
+use crate::sqlite_store::SqliteCardStore;
+use crate::trades::{Trade, TradeStatus};
+use crate::wallet_auth::SharedSessions;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Durable storage for crafted cards and mint counts. `CardCache` puts an
+/// in-memory layer in front of this.
+pub trait CardStore: Send + Sync {
+    fn get(&self, key: &str) -> Result<Option<CachedCard>, String>;
+    /// Insert or replace `card` (keyed by `card.id`).
+    fn put(&self, card: &CachedCard) -> Result<(), String>;
+    fn remove(&self, key: &str) -> Result<(), String>;
+    fn all(&self) -> Result<Vec<CachedCard>, String>;
+    /// Discovered, finished crafted cards (the pool packs draw from).
+    fn discovered(&self) -> Result<Vec<CachedCard>, String>;
+    /// The page of cards `query` asks for, and how many match in all.
+    fn list(&self, query: &CardQuery) -> Result<(Vec<CachedCard>, u64), String>;
+    /// Id and name of every possible card.
+    fn names(&self) -> Result<Vec<(String, String)>, String>;
+    /// Write many cards at once, all or nothing.
+    fn merge(
+        &self,
+        cards: Vec<CachedCard>,
+        strategy: ConflictStrategy,
+    ) -> Result<MergeSummary, String>;
+    fn counts(&self) -> Result<CardCounts, String>;
+    /// Entries per `combine_version` (`None` for untagged ones).
+    fn version_counts(&self) -> Result<HashMap<Option<String>, u64>, String>;
+    /// Delete undiscovered cards and impossible entries not tagged with
+    /// `version`, returning their ids.
+    fn remove_outdated(&self, version: &str) -> Result<Vec<String>, String>;
+    /// Delete the oldest impossible entries beyond `max`, returning their ids.
+    fn prune_impossible(&self, max: usize) -> Result<Vec<String>, String>;
+    fn record_craft(&self, card_id: &str) -> Result<(), String>;
+    fn record_mint(&self, card_id: &str) -> Result<(), String>;
+    /// Add `counts` onto the stored mint counts.
+    fn add_mint_counts(&self, counts: &HashMap<String, u64>) -> Result<(), String>;
+    fn mint_count(&self, card_id: &str) -> Result<u64, String>;
+    fn mint_counts(&self) -> Result<HashMap<String, u64>, String>;
+    /// Take the lowest edition number of `card_id` a mint that never landed
+    /// gave back, else the next one, or `None` once `cap` of them have been
+    /// issued.
+    fn issue_edition(&self, card_id: &str, cap: Option<u64>) -> Result<Option<u64>, String>;
+    /// How many editions of `card_id` are taken.
+    fn editions(&self, card_id: &str) -> Result<u64, String>;
+    /// Cheap query used by the readiness check.
+    fn ping(&self) -> Result<(), String>;
+    /// Drop any copies held outside the store itself (the database may have
+    /// been edited behind its back).
+    fn invalidate_all(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Claims of crafted cards as NFTs, and the vouchers that allow them.
+pub trait ClaimStore: Send + Sync {
+    fn claim(&self, proof: &str) -> Result<Option<Claim>, String>;
+    /// The claim that minted (or is minting) `address`.
+    fn claim_of_asset(&self, address: &str) -> Result<Option<Claim>, String>;
+    /// Record `claim` if its proof is unused, or with `replacing`, take over
+    /// the proof's claim if it still names that asset. Returns whether the
+    /// write won, so two servers can't both spend a proof.
+    fn record_claim(&self, claim: &Claim, replacing: Option<&str>) -> Result<bool, String>;
+    /// Record `claim`, a new one, if its proof is unused and its wallet is
+    /// within `limits`, counting the wallet's claims and writing in one
+    /// transaction so concurrent claims can't both slip under a limit.
+    /// Returns whether the write won, or the limit it would go over.
+    fn reserve_claim(
+        &self,
+        claim: &Claim,
+        limits: ClaimSettings,
+    ) -> Result<Result<bool, ClaimLimit>, String>;
+    /// Claims by `wallet` at or after `since`, optionally only of `card_id`.
+    fn claim_count(&self, wallet: &str, card_id: Option<&str>, since: u64)
+        -> Result<u64, String>;
+    fn put_voucher(&self, voucher: &ClaimVoucher) -> Result<(), String>;
+    fn voucher(&self, code: &str) -> Result<Option<ClaimVoucher>, String>;
+}
+
+/// NFTs we minted or burned, whether that has landed on-chain yet, the
+/// essence wallets earned from burns, and the packs they bought.
+pub trait AssetStore: Send + Sync {
+    /// Insert or replace each asset (keyed by address).
+    fn track_assets(&self, assets: &[TrackedAsset]) -> Result<(), String>;
+    fn tracked_asset(&self, address: &str) -> Result<Option<TrackedAsset>, String>;
+    /// Move the asset from `from` to `to`, crediting its `reward` to its
+    /// wallet when it becomes burned, keeping its held edition for good when
+    /// it becomes confirmed, and counting its card's mint when it landed from
+    /// pending. Returns whether it was still in `from`, so only one delivery
+    /// of a webhook settles it.
+    fn settle_asset(
+        &self,
+        address: &str,
+        from: AssetStatus,
+        to: AssetStatus,
+        signature: &str,
+        at: u64,
+    ) -> Result<bool, String>;
+    /// Take an edition of `card_id` as `CardStore::issue_edition` does, held
+    /// for the mint of `asset` until `expires_at`.
+    fn hold_edition(
+        &self,
+        card_id: &str,
+        cap: Option<u64>,
+        asset: &str,
+        expires_at: u64,
+    ) -> Result<Option<u64>, String>;
+    /// Up to `limit` assets whose edition hold ran out by `now`.
+    fn expired_edition_holds(&self, now: u64, limit: usize) -> Result<Vec<String>, String>;
+    /// End the hold on `asset`'s edition: kept for good if it was minted,
+    /// otherwise given to the next mint of the card.
+    fn end_edition_hold(&self, asset: &str, minted: bool) -> Result<(), String>;
+    fn essence(&self, wallet: &str) -> Result<u64, String>;
+    /// Record `purchase`, taking `essence` from its wallet in the same
+    /// transaction. Returns false, changing nothing, if the wallet doesn't
+    /// have that much.
+    fn record_pack_purchase(&self, purchase: &PackPurchase, essence: u64) -> Result<bool, String>;
+    fn pack_purchase(&self, id: &str) -> Result<Option<PackPurchase>, String>;
+    /// Whether a pack was opened with the payment `signature`.
+    fn pack_payment_used(&self, signature: &str) -> Result<bool, String>;
+    /// Record the client seed, payment and drawn cards of an unopened
+    /// purchase, with a mint to fulfill for each card. Returns whether it was
+    /// still unopened, so a pack is only opened once. Fails if another pack
+    /// was opened with the same payment.
+    fn open_pack(
+        &self,
+        id: &str,
+        client_seed: &str,
+        payment_signature: Option<&str>,
+        mints: &[PackMint],
+        at: u64,
+    ) -> Result<bool, String>;
+    /// Packs of `pack_id` bought by `wallet` at or after `since`.
+    fn pack_purchases(&self, wallet: &str, pack_id: &str, since: u64) -> Result<u64, String>;
+    /// The mints of an opened pack's cards, in draw order.
+    fn pack_mints(&self, purchase_id: &str) -> Result<Vec<PackMint>, String>;
+    /// Save a try at minting a pack card, unless it was minted or refunded
+    /// meanwhile.
+    fn update_pack_mint(&self, mint: &PackMint) -> Result<(), String>;
+    /// Mark a pack card refunded and credit its purchase's `refund_essence`
+    /// to the wallet. Returns the essence credited, or `None` if it was
+    /// already minted or refunded.
+    fn refund_pack_mint(&self, mint: &PackMint) -> Result<Option<u64>, String>;
+    /// Take up to `limit` pending or failed pack cards due by `now`, putting
+    /// them off until `until` so no one else tries them meanwhile.
+    fn due_pack_mints(&self, now: u64, until: u64, limit: usize)
+        -> Result<Vec<PackMint>, String>;
+}
+
+/// Which wallet holds each of our cards: a local copy of DAS, kept current
+/// from our own mints and burns and reconciled with DAS now and then.
+pub trait OwnershipStore: Send + Sync {
+    /// `wallet`'s indexed cards and when DAS last confirmed them, or `None`
+    /// if it never has. Notes that the wallet was asked about at `at`.
+    fn owned_cards(&self, wallet: &str, at: u64)
+        -> Result<Option<(u64, Vec<OwnedCard>)>, String>;
+    /// Make `cards`, a DAS answer fetched at `at`, the wallet's holdings.
+    /// Changes recorded after `at` win over it, and burned cards stay burned.
+    fn reconcile_wallet(&self, wallet: &str, cards: &[OwnedCard], at: u64) -> Result<(), String>;
+    /// Record that `wallet` holds `card`, newly minted to it.
+    fn index_mint(&self, wallet: &str, card: &OwnedCard, at: u64) -> Result<(), String>;
+    /// Record that `card`, last held by `wallet`, was burned.
+    fn index_burn(&self, wallet: &str, card: &OwnedCard, at: u64) -> Result<(), String>;
+    /// Stop trusting the index of whichever wallet holds `mint` (it may have
+    /// moved) until DAS is asked again.
+    fn forget_holder(&self, mint: &str) -> Result<(), String>;
+    /// Wallets asked about since `active_since` whose index DAS last
+    /// confirmed before `before`, least recently confirmed first.
+    fn stale_wallets(&self, before: u64, active_since: u64, limit: usize)
+        -> Result<Vec<String>, String>;
+}
+
+/// Card trades between wallets.
+pub trait TradeStore: Send + Sync {
+    fn put_trade(&self, trade: &Trade) -> Result<(), String>;
+    fn trade(&self, id: &str) -> Result<Option<Trade>, String>;
+    /// Trades `wallet` proposed or was offered, newest first.
+    fn trades_for(&self, wallet: &str, limit: usize) -> Result<Vec<Trade>, String>;
+    /// Open or accepted trades `wallet` proposed.
+    fn live_trades(&self, wallet: &str) -> Result<Vec<Trade>, String>;
+    /// Open or accepted trades that expired by `now`.
+    fn expired_trades(&self, now: u64) -> Result<Vec<Trade>, String>;
+    /// Move the trade from `from` to `to`. Returns whether it was still in
+    /// `from`, so only one request (or server) moves it.
+    fn set_trade_status(
+        &self,
+        id: &str,
+        from: TradeStatus,
+        to: TradeStatus,
+        at: u64,
+    ) -> Result<bool, String>;
+    /// Record the proposer's transaction that put the trade's cards in
+    /// escrow, unless one is recorded already.
+    fn set_trade_escrow(&self, id: &str, signature: &str) -> Result<(), String>;
+}
+
+/// Notices from other servers that cards they wrote are stale here.
+pub trait CardEvents: Send + Sync {
+    /// Block forever, calling `on_change` with each batch of changed card ids
+    /// (empty means all of them).
+    fn listen(&self, on_change: &dyn Fn(Vec<String>));
+}
+
+/// A channel passing game updates between servers, so a game's sockets on
+/// any server see its changes.
+pub trait GameFeed: Send + Sync {
+    /// Send `json`, the game as it now stands, to the other servers.
+    fn publish_game(&self, game_id: &str, json: &str);
+    /// Block forever, calling `on_update` with each (game id, json) another
+    /// server published.
+    fn listen_games(&self, on_update: &dyn Fn(String, String));
+}
+
+/// Durable storage for in-progress games.
+pub trait GameStore: Send + Sync {
+    fn load(&self) -> Result<HashMap<String, GameState>, String>;
+    /// Persist `changed` (ids into `games`). Stores shared between servers
+    /// must leave every other game alone, since it may belong to another
+    /// server.
+    fn save(&self, games: &HashMap<String, GameState>, changed: &[String]) -> Result<(), String>;
+}
+
+/// The whole games map in one JSON file, rewritten on every save.
+pub struct JsonGameStore {
+    path: PathBuf,
+}
+
+impl GameStore for JsonGameStore {
+    fn load(&self) -> Result<HashMap<String, GameState>, String> {
+        Ok(load_games(&self.path))
+    }
+
+    fn save(&self, games: &HashMap<String, GameState>, _changed: &[String]) -> Result<(), String> {
+        save_games(&self.path, games)
+    }
+}
+
+pub struct Stores {
+    pub cards: Arc<dyn CardStore>,
+    pub games: Arc<dyn GameStore>,
+    pub claims: Arc<dyn ClaimStore>,
+    pub assets: Arc<dyn AssetStore>,
+    pub trades: Arc<dyn TradeStore>,
+    pub ownership: Arc<dyn OwnershipStore>,
+    /// Set when servers share rate limits, wallet sessions, card
+    /// invalidations and game updates.
+    pub buckets: Option<Arc<dyn SharedBuckets>>,
+    pub sessions: Option<Arc<dyn SharedSessions>>,
+    pub card_events: Option<Arc<dyn CardEvents>>,
+    pub game_feed: Option<Arc<dyn GameFeed>>,
+}
+
+/// Open the configured backend, plus Redis when it's configured.
+pub fn open(config: &Config) -> Result<Stores, String> {
+    let stores = open_backend(config)?;
+    match config.redis.url.as_deref() {
+        None => Ok(stores),
+        #[cfg(feature = "redis")]
+        Some(url) => {
+            let redis = Arc::new(crate::redis_shared::Redis::connect(url, config.redis.pool_size)?);
+            Ok(Stores {
+                cards: Arc::new(crate::redis_shared::RedisCardStore::new(
+                    stores.cards,
+                    redis.clone(),
+                    config.redis.card_ttl_secs,
+                )),
+                games: stores.games,
+                claims: stores.claims,
+                assets: stores.assets,
+                trades: stores.trades,
+                ownership: stores.ownership,
+                buckets: Some(redis.clone()),
+                sessions: Some(redis.clone()),
+                card_events: Some(redis.clone()),
+                game_feed: Some(redis),
+            })
+        }
+        #[cfg(not(feature = "redis"))]
+        Some(_) => {
+            Err("[redis] url needs a game server built with --features redis".to_string())
+        }
+    }
+}
+
+fn open_backend(config: &Config) -> Result<Stores, String> {
+    match config.storage.backend {
+        StorageBackend::Sqlite => {
+            let store = Arc::new(SqliteCardStore::open(&config.paths.card_cache)?);
+            Ok(Stores {
+                cards: store.clone(),
+                games: Arc::new(JsonGameStore {
+                    path: config.paths.games.clone(),
+                }),
+                claims: store.clone(),
+                assets: store.clone(),
+                trades: store.clone(),
+                ownership: store,
+                buckets: None,
+                sessions: None,
+                card_events: None,
+                game_feed: None,
+            })
+        }
+        #[cfg(feature = "postgres")]
+        StorageBackend::Postgres => {
+            let url = config
+                .storage
+                .postgres_url
+                .as_deref()
+                .ok_or("[storage] backend = \"postgres\" needs postgres_url (or DATABASE_URL)")?;
+            let pool_size = config.storage.postgres_pool_size;
+            let store = Arc::new(crate::postgres_store::PgStore::connect(url, pool_size)?);
+            Ok(Stores {
+                cards: store.clone(),
+                games: store.clone(),
+                claims: store.clone(),
+                assets: store.clone(),
+                trades: store.clone(),
+                ownership: store,
+                buckets: None,
+                sessions: None,
+                card_events: None,
+                game_feed: None,
+            })
+        }
+        #[cfg(not(feature = "postgres"))]
+        StorageBackend::Postgres => Err(
+            "[storage] backend = \"postgres\" needs a game server built with --features postgres"
+                .to_string(),
+        ),
+    }
+}
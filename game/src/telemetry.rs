@@ -0,0 +1,95 @@
+use axum::extract::Request;
+use axum::http::HeaderMap;
+use opentelemetry::global;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing::{Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+const SERVICE_NAME: &str = "alchemaybe-game";
+
+/// Holds the OTLP exporter (if any) so pending spans can be flushed on exit.
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+/// Install the global subscriber. Logs go to stderr filtered by RUST_LOG; spans
+/// are also exported over OTLP/HTTP when OTEL_EXPORTER_OTLP_ENDPOINT is set.
+/// Existing `log::` calls are bridged into tracing.
+pub fn init() -> Telemetry {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok().and_then(|endpoint| {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .build()
+            .map_err(|e| eprintln!("Failed to build OTLP exporter for {endpoint}: {e}"))
+            .ok()?;
+        Some(
+            SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+                .build(),
+        )
+    });
+    let otel_layer = provider
+        .as_ref()
+        .map(|p| tracing_opentelemetry::layer().with_tracer(p.tracer(SERVICE_NAME)));
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel_layer)
+        .init();
+
+    if provider.is_some() {
+        log::info!("OTLP trace export enabled");
+    }
+    Telemetry { provider }
+}
+
+impl Telemetry {
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider {
+            if let Err(e) = provider.shutdown() {
+                log::warn!("Failed to flush traces: {e}");
+            }
+        }
+    }
+}
+
+/// Root span for an incoming request, parented to the caller's `traceparent`
+/// header if present.
+pub fn request_span(req: &Request) -> Span {
+    let parent = global::get_text_map_propagator(|p| p.extract(&HeaderExtractor(req.headers())));
+    let span = tracing::info_span!("request", method = %req.method(), path = %req.uri().path());
+    span.set_parent(parent);
+    span
+}
+
+/// Extension for outgoing requests to the generation server: sends inside
+/// `span` and forwards the trace context so its spans (and the Ollama calls
+/// under them) join this trace.
+pub trait SendTraced {
+    fn send_traced(
+        self,
+        span: Span,
+    ) -> impl std::future::Future<Output = reqwest::Result<reqwest::Response>> + Send;
+}
+
+impl SendTraced for reqwest::RequestBuilder {
+    async fn send_traced(self, span: Span) -> reqwest::Result<reqwest::Response> {
+        let mut headers = HeaderMap::new();
+        let cx = span.context();
+        global::get_text_map_propagator(|p| {
+            p.inject_context(&cx, &mut HeaderInjector(&mut headers))
+        });
+        self.headers(headers).send().instrument(span).await
+    }
+}
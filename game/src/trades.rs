@@ -0,0 +1,578 @@
+//! Card-for-card trades between wallets. Proposing a trade moves the
+//! proposer's offered cards into escrow (the server's wallet). The
+//! counterparty accepts by signing one transaction that sends their cards to
+//! the proposer and the escrowed ones to them, so either both sides move or
+//! neither does. Cancelled and expired trades get their cards sent back.
+
+use crate::card_cache::now_secs;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::generate::AppState;
+use crate::solana::{CoreAsset, SolanaConfig};
+use crate::solana_api::{check_wallet_rate, require_solana, soulbound_error};
+use crate::wallet_auth::require_wallet;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Most cards on each side of a trade.
+const MAX_TRADE_CARDS: usize = 5;
+const DEFAULT_TRADE_SECS: u64 = 24 * 60 * 60;
+const MAX_TRADE_SECS: u64 = 7 * 24 * 60 * 60;
+/// How long a transaction we handed out might still land. A trade can't be
+/// closed while its escrow or accept transaction might.
+const PENDING_SECS: u64 = 150;
+/// How far a block's timestamp may run behind our clock.
+const BLOCK_TIME_SLACK: u64 = 30;
+/// How often to look for expired trades.
+const EXPIRY_SWEEP: Duration = Duration::from_secs(60);
+/// Most trades returned by `/api/wallet/trades`.
+const LIST_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeStatus {
+    /// Waiting on the counterparty.
+    Open,
+    /// Swap transaction handed to the counterparty, not seen on-chain yet.
+    Accepted,
+    Completed,
+    Cancelled,
+    Expired,
+}
+
+impl TradeStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TradeStatus::Open => "open",
+            TradeStatus::Accepted => "accepted",
+            TradeStatus::Completed => "completed",
+            TradeStatus::Cancelled => "cancelled",
+            TradeStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "open" => Some(TradeStatus::Open),
+            "accepted" => Some(TradeStatus::Accepted),
+            "completed" => Some(TradeStatus::Completed),
+            "cancelled" => Some(TradeStatus::Cancelled),
+            "expired" => Some(TradeStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct Trade {
+    pub id: String,
+    pub proposer: String,
+    pub counterparty: String,
+    /// Proposer's cards (mint addresses), held in escrow while open.
+    pub offered: Vec<String>,
+    /// Counterparty's cards asked for in return.
+    pub requested: Vec<String>,
+    pub status: TradeStatus,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub updated_at: u64,
+    /// The proposer's transaction that put the offered cards in escrow,
+    /// once found.
+    pub escrow_tx: Option<String>,
+}
+
+impl Trade {
+    /// An accept transaction for it might still land.
+    fn accept_pending(&self) -> bool {
+        self.status == TradeStatus::Accepted && now_secs() < self.updated_at + PENDING_SECS
+    }
+
+    fn is_live(&self) -> bool {
+        matches!(self.status, TradeStatus::Open | TradeStatus::Accepted)
+    }
+}
+
+fn not_open(trade: &Trade, message: &str) -> ApiError {
+    err(ErrorCode::TradeNotOpen, message)
+        .with_details(serde_json::json!({ "status": trade.status }))
+}
+
+fn load(state: &AppState, id: &str) -> Result<Trade, ApiError> {
+    state
+        .trades
+        .trade(id)
+        .map_err(|e| err(ErrorCode::StorageError, e))?
+        .ok_or_else(|| err(ErrorCode::TradeNotFound, format!("Trade {id} not found")))
+}
+
+fn set_status(state: &AppState, trade: &mut Trade, to: TradeStatus) -> Result<bool, ApiError> {
+    let at = now_secs();
+    let won = state
+        .trades
+        .set_trade_status(&trade.id, trade.status, to, at)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    if won {
+        trade.status = to;
+        trade.updated_at = at;
+    }
+    Ok(won)
+}
+
+/// 1 to `MAX_TRADE_CARDS` distinct mints.
+fn check_mints(mints: &[String], side: &str) -> Result<(), ApiError> {
+    let mut distinct = mints.to_vec();
+    distinct.sort();
+    distinct.dedup();
+    if distinct.is_empty() || distinct.len() != mints.len() || mints.len() > MAX_TRADE_CARDS {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("{side} must be 1-{MAX_TRADE_CARDS} different cards"),
+        ));
+    }
+    for mint in mints {
+        Pubkey::from_str(mint)
+            .map_err(|e| err(ErrorCode::InvalidRequest, format!("Invalid mint: {e}")))?;
+    }
+    Ok(())
+}
+
+fn parse_wallet(wallet: &str) -> Result<Pubkey, ApiError> {
+    Pubkey::from_str(wallet)
+        .map_err(|e| err(ErrorCode::InvalidWallet, format!("Invalid wallet: {e}")))
+}
+
+/// Fail unless `wallet` holds every one of `mints` and none is soulbound,
+/// returning them.
+async fn require_owned(
+    solana: &SolanaConfig,
+    wallet: &str,
+    mints: &[String],
+) -> Result<Vec<CoreAsset>, ApiError> {
+    let owned = solana
+        .query_owned_cards(wallet)
+        .await
+        .map_err(|e| err(ErrorCode::SolanaError, e))?;
+    mints
+        .iter()
+        .map(|mint| {
+            let card = owned.iter().find(|c| c.mint_address == *mint).ok_or_else(|| {
+                err(ErrorCode::CardNotOwned, format!("Card {mint} not owned by {wallet}"))
+            })?;
+            if card.soulbound {
+                return Err(soulbound_error(card));
+            }
+            card.core_asset().map_err(|e| err(ErrorCode::SolanaError, e))
+        })
+        .collect()
+}
+
+/// The trade's offered cards that are in escrow right now, whoever sent them.
+fn held_in_escrow(solana: &SolanaConfig, trade: &Trade) -> Result<Vec<CoreAsset>, ApiError> {
+    let escrow = solana.escrow();
+    let mut held = Vec::new();
+    for mint in &trade.offered {
+        let mint = parse_wallet(mint)?;
+        let found = solana.asset_owner(&mint).map_err(|e| err(ErrorCode::SolanaError, e))?;
+        held.extend(found.filter(|(owner, _)| *owner == escrow).map(|(_, asset)| asset));
+    }
+    Ok(held)
+}
+
+/// The trade's offered cards if the proposer's escrow transaction put all of
+/// them in escrow, else none. Cards that got there another way (another
+/// trade's, say) aren't this trade's to hand out or send back. The
+/// transaction, once found, is kept on the trade.
+fn in_escrow(
+    state: &AppState,
+    solana: &SolanaConfig,
+    trade: &mut Trade,
+) -> Result<Vec<CoreAsset>, ApiError> {
+    let held = held_in_escrow(solana, trade)?;
+    if held.len() < trade.offered.len() {
+        return Ok(Vec::new());
+    }
+    if trade.escrow_tx.is_none() {
+        let proposer = parse_wallet(&trade.proposer)?;
+        let since = trade.created_at.saturating_sub(BLOCK_TIME_SLACK);
+        let Some(signature) = solana
+            .find_escrow_tx(&held, &proposer, since)
+            .map_err(|e| err(ErrorCode::SolanaError, e))?
+        else {
+            return Ok(Vec::new());
+        };
+        state
+            .trades
+            .set_trade_escrow(&trade.id, &signature)
+            .map_err(|e| err(ErrorCode::StorageError, e))?;
+        trade.escrow_tx = Some(signature);
+    }
+    Ok(held)
+}
+
+/// Run `f`, which makes blocking RPC calls, on the blocking pool.
+async fn on_solana<T: Send + 'static>(
+    state: &Arc<AppState>,
+    f: impl FnOnce(&AppState, &SolanaConfig) -> Result<T, ApiError> + Send + 'static,
+) -> Result<T, ApiError> {
+    let state = state.clone();
+    tokio::task::spawn_blocking(move || f(&state, require_solana(&state)?))
+        .await
+        .map_err(|e| err(ErrorCode::SolanaError, format!("Solana task failed: {e}")))?
+}
+
+/// Mark an accepted trade completed once escrow no longer holds its cards:
+/// only the swap moves them out while the trade is live.
+fn resolve(state: &AppState, solana: &SolanaConfig, trade: &mut Trade) -> Result<(), ApiError> {
+    if trade.status != TradeStatus::Accepted || !held_in_escrow(solana, trade)?.is_empty() {
+        return Ok(());
+    }
+    if set_status(state, trade, TradeStatus::Completed)? {
+        log::info!("Trade {} completed", trade.id);
+        solana.forget_owned_cards(&trade.proposer);
+        solana.forget_owned_cards(&trade.counterparty);
+    }
+    Ok(())
+}
+
+/// End a live trade as `to` and send whatever is in escrow back to the
+/// proposer. Returns the cards sent back.
+fn close(
+    state: &AppState,
+    solana: &SolanaConfig,
+    trade: &mut Trade,
+    to: TradeStatus,
+) -> Result<Vec<String>, ApiError> {
+    let (from, since) = (trade.status, trade.updated_at);
+    let held = in_escrow(state, solana, trade)?;
+    if held.len() < trade.offered.len() && now_secs() < trade.created_at + PENDING_SECS {
+        return Err(not_open(trade, "The escrow transaction may still land, try again shortly"));
+    }
+    if !set_status(state, trade, to)? {
+        return Err(not_open(trade, "Trade changed while closing it, try again"));
+    }
+    if held.is_empty() {
+        return Ok(Vec::new());
+    }
+    let proposer = parse_wallet(&trade.proposer)?;
+    if let Err(e) = solana.return_from_escrow(&held, &proposer) {
+        // Leave it live so it can be closed again
+        if let Err(revert) = state.trades.set_trade_status(&trade.id, to, from, since) {
+            log::error!("Trade {} stuck {} with cards in escrow: {revert}", trade.id, to.as_str());
+        }
+        return Err(err(ErrorCode::SolanaError, e));
+    }
+    solana.forget_owned_cards(&trade.proposer);
+    Ok(held.iter().map(|a| a.address.to_string()).collect())
+}
+
+/// Return escrowed cards of trades past their expiry. Runs for the life of
+/// the server.
+pub async fn expire_trades(state: Arc<AppState>) {
+    if state.solana.is_none() {
+        return;
+    }
+    let mut ticker = tokio::time::interval(EXPIRY_SWEEP);
+    loop {
+        ticker.tick().await;
+        let trades = match state.trades.expired_trades(now_secs()) {
+            Ok(trades) => trades,
+            Err(e) => {
+                log::warn!("Failed to list expired trades: {e}");
+                continue;
+            }
+        };
+        for mut trade in trades {
+            let id = trade.id.clone();
+            let expired = on_solana(&state, move |state, solana| {
+                resolve(state, solana, &mut trade)?;
+                if !trade.is_live() || trade.accept_pending() {
+                    return Ok(None);
+                }
+                close(state, solana, &mut trade, TradeStatus::Expired).map(Some)
+            })
+            .await;
+            match expired {
+                Ok(Some(returned)) => {
+                    log::info!("Trade {id} expired, {} cards returned", returned.len())
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to expire trade {id}: {}", e.message),
+            }
+        }
+    }
+}
+
+// --- POST /api/wallet/trades ---
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/trades",
+    tag = "wallet",
+    responses(
+        (status = 200, description = "Trades the wallet proposed or was offered, newest first", body = crate::openapi::TradeListResponse),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn wallet_trades(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let solana = require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+
+    let mut trades = state
+        .trades
+        .trades_for(&wallet, LIST_LIMIT)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    let trades = on_solana(&state, move |state, solana| {
+        for trade in &mut trades {
+            resolve(state, solana, trade)?;
+        }
+        Ok(trades)
+    })
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "escrow": solana.escrow().to_string(),
+        "trades": trades,
+    })))
+}
+
+// --- POST /api/wallet/trade/propose ---
+
+#[derive(Deserialize, ToSchema)]
+pub struct TradeProposeRequest {
+    /// Wallet asked to trade.
+    pub counterparty: String,
+    /// Mints of the signed-in wallet's cards to give.
+    pub offered: Vec<String>,
+    /// Mints of the counterparty's cards wanted in return.
+    pub requested: Vec<String>,
+    /// Defaults to a day; at most a week.
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/trade/propose",
+    tag = "wallet",
+    request_body = TradeProposeRequest,
+    responses(
+        (status = 200, description = "The trade, and an unsigned transaction moving the offered cards into escrow", body = crate::openapi::TradeTxResponse),
+        (status = 400, description = "Invalid selection, or cards not owned or soulbound", body = ApiError),
+        (status = 401, description = "Wallet not signed in", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn trade_propose(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<TradeProposeRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let solana = require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let proposer = parse_wallet(&wallet)?;
+    let counterparty = parse_wallet(&req.counterparty)?;
+    if counterparty == proposer || counterparty == solana.escrow() {
+        return Err(err(ErrorCode::InvalidWallet, "Cannot trade with that wallet"));
+    }
+    check_mints(&req.offered, "offered")?;
+    check_mints(&req.requested, "requested")?;
+
+    // Escrow can't tell two trades' copies of a card apart
+    let live = state
+        .trades
+        .live_trades(&wallet)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    if let Some(mint) = req.offered.iter().find(|m| live.iter().any(|t| t.offered.contains(m))) {
+        return Err(err(
+            ErrorCode::InvalidRequest,
+            format!("Card {mint} is already offered in another trade"),
+        ));
+    }
+    let offered = require_owned(solana, &wallet, &req.offered).await?;
+    require_owned(solana, &req.counterparty, &req.requested).await?;
+
+    let tx_base64 = on_solana(&state, move |_, solana| {
+        solana.build_escrow_tx(&offered, &proposer).map_err(|e| err(ErrorCode::SolanaError, e))
+    })
+    .await?;
+
+    let now = now_secs();
+    let ttl = req.expires_in_secs.unwrap_or(DEFAULT_TRADE_SECS).clamp(PENDING_SECS, MAX_TRADE_SECS);
+    let trade = Trade {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        proposer: wallet,
+        counterparty: req.counterparty,
+        offered: req.offered,
+        requested: req.requested,
+        status: TradeStatus::Open,
+        created_at: now,
+        expires_at: now + ttl,
+        updated_at: now,
+        escrow_tx: None,
+    };
+    state
+        .trades
+        .put_trade(&trade)
+        .map_err(|e| err(ErrorCode::StorageError, e))?;
+    log::info!(
+        "Trade {} proposed: {} cards from {} for {} from {}",
+        trade.id,
+        trade.offered.len(),
+        trade.proposer,
+        trade.requested.len(),
+        trade.counterparty
+    );
+
+    Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
+        "transaction": tx_base64,
+        "trade": trade,
+    })))
+}
+
+// --- POST /api/wallet/trade/accept ---
+
+#[derive(Deserialize, ToSchema)]
+pub struct TradeRequest {
+    pub trade_id: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/trade/accept",
+    tag = "wallet",
+    request_body = TradeRequest,
+    responses(
+        (status = 200, description = "The trade, and an unsigned swap transaction for the counterparty", body = crate::openapi::TradeTxResponse),
+        (status = 400, description = "Requested cards no longer owned, or soulbound", body = ApiError),
+        (status = 401, description = "Wallet not signed in, or not the counterparty", body = ApiError),
+        (status = 404, description = "Trade not found", body = ApiError),
+        (status = 409, description = "Trade not open, expired, or not in escrow yet", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn trade_accept(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<TradeRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let solana = require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let mut trade = load(&state, &req.trade_id)?;
+    if trade.counterparty != wallet {
+        return Err(err(
+            ErrorCode::WalletSessionRequired,
+            "This trade was offered to a different wallet",
+        ));
+    }
+
+    let (mut trade, offered) = on_solana(&state, move |state, solana| {
+        resolve(state, solana, &mut trade)?;
+        if trade.accept_pending() {
+            return Err(not_open(&trade, "Trade is already being accepted"));
+        }
+        if !trade.is_live() {
+            return Err(not_open(&trade, "Trade is no longer open"));
+        }
+        if now_secs() >= trade.expires_at {
+            return Err(not_open(&trade, "Trade has expired"));
+        }
+        let offered = in_escrow(state, solana, &mut trade)?;
+        if offered.len() != trade.offered.len() {
+            return Err(not_open(&trade, "Offered cards are not in escrow yet"));
+        }
+        Ok((trade, offered))
+    })
+    .await?;
+    let requested = require_owned(solana, &wallet, &trade.requested).await?;
+
+    let (proposer, taker) = (parse_wallet(&trade.proposer)?, parse_wallet(&wallet)?);
+    let tx_base64 = on_solana(&state, move |_, solana| {
+        solana
+            .build_trade_tx(&offered, &requested, &proposer, &taker)
+            .map_err(|e| err(ErrorCode::SolanaError, e))
+    })
+    .await?;
+    if !set_status(&state, &mut trade, TradeStatus::Accepted)? {
+        return Err(not_open(&trade, "Trade changed while accepting it, try again"));
+    }
+    log::info!("Trade {} accepted by {wallet}", trade.id);
+
+    Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
+        "transaction": tx_base64,
+        "trade": trade,
+    })))
+}
+
+// --- POST /api/wallet/trade/cancel ---
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/trade/cancel",
+    tag = "wallet",
+    request_body = TradeRequest,
+    responses(
+        (status = 200, description = "Cancelled trade and the cards sent back from escrow", body = crate::openapi::TradeCancelResponse),
+        (status = 401, description = "Wallet not signed in, or not part of the trade", body = ApiError),
+        (status = 404, description = "Trade not found", body = ApiError),
+        (status = 409, description = "Trade already closed or being accepted", body = ApiError),
+        (status = 429, description = "Rate limited", body = ApiError),
+        (status = 502, description = "Solana error", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn trade_cancel(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<TradeRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    require_solana(&state)?;
+    let wallet = require_wallet(&state, &headers)?;
+    check_wallet_rate(&state, &wallet)?;
+    let mut trade = load(&state, &req.trade_id)?;
+    if trade.proposer != wallet && trade.counterparty != wallet {
+        return Err(err(
+            ErrorCode::WalletSessionRequired,
+            "This trade belongs to different wallets",
+        ));
+    }
+
+    let (trade, returned) = on_solana(&state, move |state, solana| {
+        resolve(state, solana, &mut trade)?;
+        if trade.accept_pending() {
+            return Err(not_open(&trade, "Trade is being accepted"));
+        }
+        if !trade.is_live() {
+            return Err(not_open(&trade, "Trade is no longer open"));
+        }
+        let returned = close(state, solana, &mut trade, TradeStatus::Cancelled)?;
+        Ok((trade, returned))
+    })
+    .await?;
+    log::info!("Trade {} cancelled by {wallet}, {} cards returned", trade.id, returned.len());
+
+    Ok(Json(serde_json::json!({
+        "trade": trade,
+        "returned": returned,
+    })))
+}
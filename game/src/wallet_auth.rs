@@ -0,0 +1,431 @@
+//! Sign-In-With-Solana: the wallet signs a one-time message from the server
+//! and gets back a session token. Wallet endpoints and wallet-linked games
+//! act for the session's wallet, never for an address named in the request.
+
+use crate::config::AuthSettings;
+use crate::error::{err, ApiError, ErrorCode};
+use crate::game_state::GameState;
+use crate::generate::AppState;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::Json;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// Header carrying the token from `/api/wallet/login`.
+const SESSION_HEADER: &str = "x-wallet-session";
+
+/// How many logins can wait for a signature, and for how long.
+#[derive(Clone, Copy)]
+pub struct LoginLimits {
+    /// How long the wallet has to sign a login message.
+    pub ttl: Duration,
+    /// Past this, a wallet's oldest login is dropped for its newest.
+    pub per_wallet: usize,
+    /// Across all wallets.
+    pub total: usize,
+}
+
+const LOGIN_LIMITS: LoginLimits = LoginLimits {
+    ttl: Duration::from_secs(300),
+    per_wallet: 5,
+    total: 10_000,
+};
+
+/// A login refused for having too many waiting: which limit, and how long
+/// until a login under it runs out.
+pub type LoginRejected = (&'static str, Duration);
+
+/// Logins and sessions kept outside this process, so a wallet signed in on
+/// one server behind a load balancer is signed in on all of them.
+pub trait SharedSessions: Send + Sync {
+    /// Hold the login `message` for `wallet` under `nonce`, within `limits`,
+    /// dropping the wallet's oldest if it has too many. The inner result is
+    /// the verdict; the outer error means the store was unreachable.
+    fn hold_login(
+        &self,
+        nonce: &str,
+        wallet: &str,
+        message: &str,
+        limits: LoginLimits,
+    ) -> Result<Result<(), LoginRejected>, String>;
+    /// Remove the login held under `nonce`, returning (wallet, message).
+    fn take_login(&self, nonce: &str) -> Result<Option<(String, String)>, String>;
+    fn open_session(&self, token: &str, wallet: &str, ttl: Duration) -> Result<(), String>;
+    fn session_wallet(&self, token: &str) -> Result<Option<String>, String>;
+    fn end_session(&self, token: &str) -> Result<(), String>;
+}
+
+struct PendingLogin {
+    wallet: String,
+    message: String,
+    expires: Instant,
+}
+
+struct Session {
+    wallet: String,
+    expires: Instant,
+}
+
+/// Outstanding login messages and live sessions. They live in `shared` when
+/// servers share them; otherwise they're in memory and only exist on the
+/// server that issued them. Sticky routing on the game id doesn't cover
+/// `/api/wallet/*`, so then the load balancer has to keep each client on one
+/// server.
+pub struct WalletSessions {
+    ttl: Duration,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+    sessions: Mutex<HashMap<String, Session>>,
+    shared: Option<Arc<dyn SharedSessions>>,
+}
+
+impl WalletSessions {
+    pub fn from_config(settings: &AuthSettings, shared: Option<Arc<dyn SharedSessions>>) -> Self {
+        WalletSessions {
+            ttl: Duration::from_secs(settings.wallet_session_ttl_secs.max(60)),
+            pending: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+            shared,
+        }
+    }
+
+    /// Start a login for `wallet`, returning (nonce, message to sign). Anyone
+    /// can start one for any wallet, so a wallet with too many waiting loses
+    /// its oldest rather than being refused; only every wallet together
+    /// having too many gets a 429.
+    fn begin(
+        &self,
+        wallet: &str,
+        domain: &str,
+        uri: &str,
+        cluster: &str,
+    ) -> Result<(String, String), ApiError> {
+        let nonce = uuid::Uuid::new_v4().simple().to_string();
+        let message = format!(
+            "{domain} wants you to sign in with your Solana account:\n{wallet}\n\n\
+             Sign in to Alchemaybe.\n\n\
+             URI: {uri}\nVersion: 1\nChain ID: {cluster}\nNonce: {nonce}"
+        );
+        let held = match &self.shared {
+            Some(shared) => shared
+                .hold_login(&nonce, wallet, &message, LOGIN_LIMITS)
+                .map_err(|e| err(ErrorCode::StorageError, e))?,
+            None => self.hold_login(&nonce, wallet, &message),
+        };
+        held.map_err(|(limit, retry_after)| ApiError::rate_limited(limit, retry_after))?;
+        Ok((nonce, message))
+    }
+
+    fn hold_login(&self, nonce: &str, wallet: &str, message: &str) -> Result<(), LoginRejected> {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.expires > now);
+        let until_first_expires = |logins: Vec<Instant>| {
+            logins.into_iter().min().map_or(LOGIN_LIMITS.ttl, |e| e - now)
+        };
+        let mut of_wallet: Vec<(Instant, String)> = pending
+            .iter()
+            .filter(|(_, p)| p.wallet == wallet)
+            .map(|(nonce, p)| (p.expires, nonce.clone()))
+            .collect();
+        if of_wallet.len() >= LOGIN_LIMITS.per_wallet {
+            of_wallet.sort();
+            for (_, nonce) in &of_wallet[..=of_wallet.len() - LOGIN_LIMITS.per_wallet] {
+                pending.remove(nonce);
+            }
+        }
+        if pending.len() >= LOGIN_LIMITS.total {
+            let all = pending.values().map(|p| p.expires).collect();
+            return Err(("logins", until_first_expires(all)));
+        }
+        pending.insert(
+            nonce.to_string(),
+            PendingLogin {
+                wallet: wallet.to_string(),
+                message: message.to_string(),
+                expires: now + LOGIN_LIMITS.ttl,
+            },
+        );
+        Ok(())
+    }
+
+    /// Check `signature` over the message issued with `nonce` and open a
+    /// session. The nonce is spent either way.
+    fn finish(&self, wallet: &str, nonce: &str, signature: &Signature) -> Result<String, ApiError> {
+        let store = |e| err(ErrorCode::StorageError, e);
+        let denied = |message: &str| err(ErrorCode::WalletSessionRequired, message);
+        let login = match &self.shared {
+            Some(shared) => shared.take_login(nonce).map_err(store)?,
+            None => self
+                .pending
+                .lock()
+                .unwrap()
+                .remove(nonce)
+                .filter(|p| p.expires > Instant::now())
+                .map(|p| (p.wallet, p.message)),
+        };
+        let (login_wallet, message) =
+            login.ok_or_else(|| denied("Login nonce is unknown or expired"))?;
+        if login_wallet != wallet {
+            return Err(denied("Nonce was issued to a different wallet"));
+        }
+        let pubkey = Pubkey::from_str(wallet).map_err(|e| denied(&format!("Invalid wallet: {e}")))?;
+        if !signature.verify(pubkey.as_ref(), message.as_bytes()) {
+            return Err(denied("Signature does not match the wallet"));
+        }
+
+        let token = format!(
+            "{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        );
+        if let Some(shared) = &self.shared {
+            shared.open_session(&token, wallet, self.ttl).map_err(store)?;
+            return Ok(token);
+        }
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| s.expires > now);
+        sessions.insert(
+            token.clone(),
+            Session {
+                wallet: login_wallet,
+                expires: now + self.ttl,
+            },
+        );
+        Ok(token)
+    }
+
+    fn wallet_for(&self, token: &str) -> Option<String> {
+        if let Some(shared) = &self.shared {
+            return shared
+                .session_wallet(token)
+                .map_err(|e| log::warn!("Failed to look up a wallet session: {e}"))
+                .ok()
+                .flatten();
+        }
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .get(token)
+            .filter(|s| s.expires > Instant::now())
+            .map(|s| s.wallet.clone())
+    }
+
+    fn end(&self, token: &str) {
+        if let Some(shared) = &self.shared {
+            if let Err(e) = shared.end_session(token) {
+                log::warn!("Failed to end a wallet session: {e}");
+            }
+            return;
+        }
+        self.sessions.lock().unwrap().remove(token);
+    }
+}
+
+fn session_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+}
+
+/// The wallet signed in on this request, if any.
+pub fn session_wallet(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    session_token(headers).and_then(|t| state.wallet_sessions.wallet_for(t))
+}
+
+/// The wallet signed in on this request, or 401.
+pub fn require_wallet(state: &AppState, headers: &HeaderMap) -> Result<String, ApiError> {
+    session_wallet(state, headers).ok_or_else(|| {
+        err(
+            ErrorCode::WalletSessionRequired,
+            "Sign in with your wallet first (POST /api/wallet/nonce, then /api/wallet/login)",
+        )
+    })
+}
+
+/// Games started with a wallet only take moves from that wallet's session.
+pub fn require_game_owner(
+    state: &AppState,
+    headers: &HeaderMap,
+    game: &GameState,
+) -> Result<(), ApiError> {
+    let Some(owner) = &game.players[0].wallet else {
+        return Ok(());
+    };
+    if require_wallet(state, headers)? != *owner {
+        return Err(err(
+            ErrorCode::WalletSessionRequired,
+            "This game belongs to a different wallet",
+        ));
+    }
+    Ok(())
+}
+
+// --- POST /api/wallet/nonce ---
+
+#[derive(Deserialize, ToSchema)]
+pub struct NonceRequest {
+    pub wallet_address: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/nonce",
+    tag = "wallet",
+    request_body = NonceRequest,
+    responses(
+        (status = 200, description = "Message for the wallet to sign", body = crate::openapi::NonceResponse),
+        (status = 400, description = "Invalid wallet", body = ApiError),
+        (status = 429, description = "Rate limited, or too many logins waiting for a signature", body = ApiError),
+        (status = 503, description = "Solana not configured", body = ApiError),
+    )
+)]
+pub async fn wallet_nonce(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<NonceRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let solana = state.solana.as_deref().ok_or_else(|| {
+        err(
+            ErrorCode::SolanaNotConfigured,
+            "Solana integration not configured",
+        )
+    })?;
+    // Not the wallet's rate limit: the caller needn't be the wallet, and
+    // the route's per-IP limit already applies
+    Pubkey::from_str(&req.wallet_address)
+        .map_err(|e| err(ErrorCode::InvalidWallet, format!("Invalid wallet: {e}")))?;
+
+    let uri = solana.public_base_url.as_str();
+    let domain = uri.split_once("://").map_or(uri, |(_, rest)| rest);
+    let domain = domain.split('/').next().unwrap_or(domain);
+    let (nonce, message) =
+        state
+            .wallet_sessions
+            .begin(&req.wallet_address, domain, uri, solana.cluster.name())?;
+
+    Ok(Json(serde_json::json!({
+        "cluster": solana.cluster.name(),
+        "nonce": nonce,
+        "message": message,
+        "expires_in_secs": LOGIN_LIMITS.ttl.as_secs(),
+    })))
+}
+
+// --- POST /api/wallet/login ---
+
+#[derive(Deserialize, ToSchema)]
+pub struct LoginRequest {
+    pub wallet_address: String,
+    pub nonce: String,
+    /// Base64 ed25519 signature over the `message` from `/api/wallet/nonce`.
+    pub signature: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/login",
+    tag = "wallet",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Session token", body = crate::openapi::LoginResponse),
+        (status = 400, description = "Malformed signature", body = ApiError),
+        (status = 401, description = "Bad signature or expired nonce", body = ApiError),
+    )
+)]
+pub async fn wallet_login(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<LoginRequest>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &req.signature)
+        .map_err(|e| err(ErrorCode::InvalidRequest, format!("Invalid signature: {e}")))?;
+    let signature = Signature::try_from(bytes.as_slice())
+        .map_err(|_| err(ErrorCode::InvalidRequest, "Signature must be 64 bytes"))?;
+
+    let token = state
+        .wallet_sessions
+        .finish(&req.wallet_address, &req.nonce, &signature)?;
+    log::info!("Wallet signed in: {}", req.wallet_address);
+
+    Ok(Json(serde_json::json!({
+        "session_token": token,
+        "wallet_address": req.wallet_address,
+        "expires_in_secs": state.wallet_sessions.ttl.as_secs(),
+    })))
+}
+
+// --- POST /api/wallet/logout ---
+
+#[utoipa::path(
+    post,
+    path = "/api/wallet/logout",
+    tag = "wallet",
+    responses((status = 204, description = "Session ended")),
+)]
+pub async fn wallet_logout(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> axum::http::StatusCode {
+    if let Some(token) = session_token(&headers) {
+        state.wallet_sessions.end(token);
+    }
+    axum::http::StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn begin(sessions: &WalletSessions, wallet: &str) -> Result<String, &'static str> {
+        let nonce = uuid::Uuid::new_v4().simple().to_string();
+        sessions.hold_login(&nonce, wallet, "Sign in").map(|_| nonce).map_err(|(limit, _)| limit)
+    }
+
+    fn waiting(sessions: &WalletSessions, nonce: &str) -> bool {
+        sessions.pending.lock().unwrap().contains_key(nonce)
+    }
+
+    #[test]
+    fn a_wallets_oldest_login_makes_way_for_its_newest() {
+        let sessions = WalletSessions::from_config(&AuthSettings::default(), None);
+        let nonces: Vec<String> = (0..LOGIN_LIMITS.per_wallet)
+            .map(|_| {
+                std::thread::sleep(Duration::from_millis(1));
+                begin(&sessions, "alice").unwrap()
+            })
+            .collect();
+        let bob = begin(&sessions, "bob").unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        let newest = begin(&sessions, "alice").unwrap();
+        assert!(!waiting(&sessions, &nonces[0]));
+        assert!(nonces[1..].iter().all(|n| waiting(&sessions, n)));
+        assert!(waiting(&sessions, &newest));
+        assert!(waiting(&sessions, &bob));
+    }
+
+    #[test]
+    fn spent_nonces_are_gone() {
+        let sessions = WalletSessions::from_config(&AuthSettings::default(), None);
+        let nonce = begin(&sessions, "alice").unwrap();
+        let bad_signature = Signature::default();
+        assert!(sessions.finish("alice", &nonce, &bad_signature).is_err());
+        assert!(!waiting(&sessions, &nonce));
+        assert!(sessions.finish("alice", &nonce, &bad_signature).is_err());
+    }
+
+    #[test]
+    fn pending_logins_are_capped_overall() {
+        let sessions = WalletSessions::from_config(&AuthSettings::default(), None);
+        for i in 0..LOGIN_LIMITS.total {
+            begin(&sessions, &format!("wallet{i}")).unwrap();
+        }
+        assert_eq!(begin(&sessions, "alice"), Err("logins"));
+    }
+}
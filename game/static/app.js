@@ -6,13 +6,29 @@ let selectedHandIndices = new Set();
 
 async function api(method, path, body) {
     const opts = { method, headers: { 'Content-Type': 'application/json' } };
+    const apiKey = localStorage.getItem('apiKey');
+    if (apiKey) opts.headers['Authorization'] = `Bearer ${apiKey}`;
+    if (walletSession) opts.headers['X-Wallet-Session'] = walletSession;
     if (body) opts.body = JSON.stringify(body);
     const resp = await fetch(path, opts);
     const data = await resp.json();
-    if (!resp.ok) throw new Error(data.error || data.reason || 'Request failed');
+    if (!resp.ok) {
+        const err = new Error(data.message || 'Request failed');
+        err.code = data.code;
+        err.details = data.details;
+        throw err;
+    }
     return data;
 }
 
+// Small WebP rendition of a card image, for hand, board, and collection views
+function thumbUrl(path) {
+    if (!path || !path.startsWith('/cards/')) return path;
+    const [file, query] = path.split('?');
+    if (!file.endsWith('.png')) return path;
+    return `${file}?${query ? `${query}&` : ''}size=thumb&format=webp`;
+}
+
 // --- Screen management ---
 
 function showScreen(id) {
@@ -120,18 +136,15 @@ async function startGame(mode) {
     try {
         // Offer NFT selection if wallet connected and has cards
         let nftCards = [];
-        let walletAddr = null;
         if (walletPublicKey && ownedCards.length > 0) {
             const selection = await showNftSelection();
             if (selection && selection.length > 0) {
                 nftCards = selection;
-                walletAddr = walletPublicKey;
             }
         }
 
         showLoading('Creating game...');
         const body = { mode };
-        if (walletAddr) body.wallet_address = walletAddr;
         if (nftCards.length > 0) body.nft_cards = nftCards;
         gameState = await api('POST', '/api/game/new', body);
         gameId = gameState.id;
@@ -179,7 +192,7 @@ function renderBoard() {
             if (cell.card) {
                 const img = document.createElement('img');
                 img.className = 'cell-card';
-                img.src = cell.card.card.image_path;
+                img.src = thumbUrl(cell.card.card.image_path);
                 img.alt = cell.card.card.name;
                 div.appendChild(img);
 
@@ -224,7 +237,7 @@ function renderHand() {
         div.onclick = () => toggleHandCard(i);
 
         const img = document.createElement('img');
-        img.src = card.image_path;
+        img.src = thumbUrl(card.image_path);
         img.alt = card.name;
         div.appendChild(img);
 
@@ -332,6 +345,14 @@ async function doCombine() {
         }
     } catch (e) {
         hideLoading();
+        if (e.code && e.code !== 'COMBINATION_IMPOSSIBLE') {
+            showOverlay(`
+                <h2>Failed</h2>
+                <p>${e.message}</p>
+                <button onclick="hideOverlay()">Dismiss</button>
+            `);
+            return;
+        }
         showOverlay(`
             <h2>Fizzled!</h2>
             <p>The combination didn't work.</p>
@@ -358,8 +379,6 @@ function showCardPending(card, cacheKey) {
         try {
             const result = await api('POST', `/api/game/${gameId}/finalize-combine`, {
                 cache_key: cacheKey,
-                name: card.name,
-                description: card.description,
             });
             gameState = result.game;
 
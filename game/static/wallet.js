@@ -1,7 +1,10 @@
 // --- Wallet State ---
 let walletPublicKey = null;
+let walletSession = null;  // token from /api/wallet/login, sent as X-Wallet-Session
 let ownedCards = [];
+let walletEssence = 0;  // earned by burning cards, spendable on packs
 let burnedMints = new Set(JSON.parse(localStorage.getItem('burnedMints') || '[]'));
+let assetEvents = null;  // AbortController for the /api/wallet/events stream
 
 // --- Phantom Wallet Connection ---
 
@@ -23,7 +26,10 @@ async function connectWallet() {
 
     try {
         const resp = await phantom.connect();
-        walletPublicKey = resp.publicKey.toString();
+        const address = resp.publicKey.toString();
+        await signIn(phantom, address);
+        walletPublicKey = address;
+        listenForAssetEvents();
         updateWalletUI();
         await refreshOwnedCards();
     } catch (e) {
@@ -31,7 +37,27 @@ async function connectWallet() {
     }
 }
 
+// Prove wallet ownership by signing a one-time server message
+async function signIn(phantom, address) {
+    const { message, nonce } = await api('POST', '/api/wallet/nonce', {
+        wallet_address: address,
+    });
+    const { signature } = await phantom.signMessage(new TextEncoder().encode(message), 'utf8');
+    const login = await api('POST', '/api/wallet/login', {
+        wallet_address: address,
+        nonce,
+        signature: btoa(String.fromCharCode(...signature)),
+    });
+    walletSession = login.session_token;
+}
+
 async function disconnectWallet() {
+    if (walletSession) {
+        try { await api('POST', '/api/wallet/logout'); } catch (_) {}
+    }
+    walletSession = null;
+    if (assetEvents) assetEvents.abort();
+    assetEvents = null;
     const phantom = getPhantom();
     if (phantom) {
         try { await phantom.disconnect(); } catch (_) {}
@@ -65,9 +91,7 @@ function updateWalletUI() {
 async function refreshOwnedCards() {
     if (!walletPublicKey) return;
     try {
-        const data = await api('POST', '/api/wallet/cards', {
-            wallet_address: walletPublicKey,
-        });
+        const data = await api('POST', '/api/wallet/cards');
         const fresh = data.cards || [];
         // If DAS no longer returns a burned mint, it's fully indexed — stop filtering it
         for (const mint of burnedMints) {
@@ -77,12 +101,53 @@ async function refreshOwnedCards() {
         }
         localStorage.setItem('burnedMints', JSON.stringify([...burnedMints]));
         ownedCards = fresh.filter(c => !burnedMints.has(c.mint_address));
+        walletEssence = data.essence || 0;
     } catch (e) {
         console.error('Failed to fetch owned cards:', e);
         ownedCards = [];
     }
 }
 
+// --- On-chain Confirmations ---
+
+// Server-sent events for this wallet's mints and burns as they land. Read
+// with fetch rather than EventSource so the session header can be sent.
+async function listenForAssetEvents() {
+    if (assetEvents) assetEvents.abort();
+    const controller = new AbortController();
+    assetEvents = controller;
+    try {
+        const resp = await fetch('/api/wallet/events', {
+            headers: { 'X-Wallet-Session': walletSession },
+            signal: controller.signal,
+        });
+        if (!resp.ok) return;
+        const reader = resp.body.pipeThrough(new TextDecoderStream()).getReader();
+        let buffer = '';
+        for (;;) {
+            const { value, done } = await reader.read();
+            if (done) break;
+            buffer += value;
+            const messages = buffer.split('\n\n');
+            buffer = messages.pop();
+            for (const message of messages) {
+                const data = message.split('\n').find(l => l.startsWith('data:'));
+                if (data) await onAssetEvent(JSON.parse(data.slice(5)));
+            }
+        }
+    } catch (e) {
+        if (e.name !== 'AbortError') console.error('Asset event stream failed:', e);
+    }
+}
+
+async function onAssetEvent(event) {
+    console.log(`NFT ${event.asset_address} ${event.status}`);
+    await refreshOwnedCards();
+    if (document.getElementById('collection-screen')?.classList.contains('active')) {
+        renderCollection();
+    }
+}
+
 // --- Transaction Signing ---
 
 async function signAndSubmitTransaction(base64Tx) {
@@ -93,7 +158,7 @@ async function signAndSubmitTransaction(base64Tx) {
     const bytes = Uint8Array.from(atob(base64Tx), c => c.charCodeAt(0));
 
     // Deserialize as a versioned transaction
-    const tx = solanaWeb3.Transaction.from(bytes);
+    const tx = solanaWeb3.VersionedTransaction.deserialize(bytes);
 
     // User signs via Phantom
     const signed = await phantom.signTransaction(tx);
@@ -110,6 +175,21 @@ async function signAndSubmitTransaction(base64Tx) {
     return result.signature;
 }
 
+// Sign and submit a claim or combine. If the wallet took so long that its
+// blockhash expired, have the server sign the same mint again and retry once.
+async function signAndSubmitMint(data) {
+    try {
+        return await signAndSubmitTransaction(data.transaction);
+    } catch (e) {
+        if (!/blockhash|block height exceeded|expir/i.test(e.message)) throw e;
+        const fresh = await api('POST', '/api/wallet/refresh-tx', {
+            asset_address: data.asset_address,
+        });
+        showLoading('Transaction expired, confirm again in Phantom...');
+        return await signAndSubmitTransaction(fresh.transaction);
+    }
+}
+
 // --- Claim Card as NFT ---
 
 async function claimCard(cardId, gameId) {
@@ -125,14 +205,13 @@ async function claimCard(cardId, gameId) {
     try {
         showLoading('Building claim transaction...');
         const data = await api('POST', '/api/wallet/claim', {
-            wallet_address: walletPublicKey,
             card_id: cardId,
             game_id: gameId,
         });
         hideLoading();
 
         showLoading('Confirm in Phantom...');
-        const sig = await signAndSubmitTransaction(data.transaction);
+        const sig = await signAndSubmitMint(data);
         hideLoading();
 
         await refreshOwnedCards();
@@ -155,33 +234,37 @@ async function claimCard(cardId, gameId) {
 
 // --- Pack Purchase ---
 
-async function buyPack(packType) {
+async function buyPack(packType, payWithEssence = false) {
     if (!walletPublicKey) return;
 
     try {
         showLoading('Preparing pack...');
         const data = await api('POST', '/api/wallet/pack/buy', {
-            wallet_address: walletPublicKey,
             pack_type: packType,
+            pay_with_essence: payWithEssence,
         });
         hideLoading();
 
-        // User signs one payment transaction
-        showLoading('Confirm payment in Phantom...');
-        const paymentSig = await signAndSubmitTransaction(data.payment_transaction);
-        hideLoading();
+        // User signs one payment transaction, unless essence already paid
+        let paymentSig = 'essence';
+        if (data.payment_transaction) {
+            showLoading('Confirm payment in Phantom...');
+            paymentSig = await signAndSubmitTransaction(data.payment_transaction);
+            hideLoading();
+        }
 
-        // Server mints all cards
+        // Server draws the cards from its committed seed and mints them
         showLoading('Minting cards...');
-        await api('POST', '/api/wallet/pack/confirm', {
+        const seed = crypto.getRandomValues(new Uint8Array(16));
+        const opened = await api('POST', '/api/wallet/pack/confirm', {
+            purchase_id: data.purchase_id,
             payment_signature: paymentSig,
-            wallet_address: walletPublicKey,
-            pack_cards: data.pack_cards,
+            client_seed: Array.from(seed, b => b.toString(16).padStart(2, '0')).join(''),
         });
         hideLoading();
 
         await refreshOwnedCards();
-        await showPackReveal(data.cards);
+        await showPackReveal(opened);
     } catch (e) {
         hideLoading();
         showOverlay(`
@@ -192,21 +275,48 @@ async function buyPack(packType) {
     }
 }
 
-async function showPackReveal(cards) {
+async function showPackReveal(opened) {
+    const cards = opened.cards;
     for (let i = 0; i < cards.length; i++) {
         await showCardReveal(cards[i]);
     }
+    const notes = [];
+    if (opened.retrying.length) {
+        notes.push(`${opened.retrying.length} card(s) are still minting and will arrive shortly.`);
+    }
+    if (opened.refunded_essence) {
+        notes.push(`${opened.sold_out.length} card(s) sold out; you got ${opened.refunded_essence} essence instead.`);
+    }
     showOverlay(`
         <h2>Pack Complete!</h2>
         <p>You received ${cards.length} cards. Check your collection!</p>
+        ${notes.map(n => `<p>${n}</p>`).join('')}
         <button onclick="hideOverlay()">Continue</button>
     `);
 }
 
 // --- Store Screen ---
 
-function showStore() {
+async function showStore() {
     showScreen('store-screen');
+    const container = document.getElementById('store-packs');
+    try {
+        const { packs } = await api('GET', '/api/packs');
+        container.innerHTML = packs.map(pack => `
+            <div class="pack-card">
+                <div class="pack-title">${pack.name}</div>
+                <div class="pack-contents">${pack.description}</div>
+                ${pack.price_lamports ? `
+                    <div class="pack-price">${pack.price_lamports / 1e9} SOL</div>
+                    <button onclick="buyPack('${pack.id}')">Buy Pack</button>` : ''}
+                ${pack.price_essence ? `
+                    <div class="pack-price">${pack.price_essence} essence</div>
+                    <button onclick="buyPack('${pack.id}', true)">Pay with Essence</button>` : ''}
+            </div>
+        `).join('');
+    } catch (e) {
+        container.innerHTML = `<p>Couldn't load packs: ${e.message}</p>`;
+    }
 }
 
 function closeStore() {
@@ -248,7 +358,7 @@ function renderCollection() {
         }
 
         const img = document.createElement('img');
-        img.src = card.image_path || '/cards/placeholder.png';
+        img.src = thumbUrl(card.image_path) || '/cards/placeholder.png';
         img.alt = card.name;
         div.appendChild(img);
 
@@ -259,12 +369,10 @@ function renderCollection() {
 
         const kind = document.createElement('div');
         kind.className = 'collection-card-kind ' + card.kind;
-        kind.textContent = card.kind;
+        kind.textContent = card.soulbound ? `${card.kind} · soulbound` : card.kind;
         div.appendChild(kind);
 
-        if (combineMode) {
-            div.onclick = () => toggleCombineSelect(i);
-        }
+        div.onclick = combineMode ? () => toggleCombineSelect(i) : () => showGiftCard(i);
 
         grid.appendChild(div);
     });
@@ -299,6 +407,224 @@ function updateCombineButton() {
     } else {
         btn.classList.add('hidden');
     }
+    const burnBtn = document.getElementById('do-burn-btn');
+    if (burnBtn) burnBtn.classList.toggle('hidden', !(combineMode && selectedForCombine.size >= 1));
+    const essence = document.getElementById('essence-balance');
+    if (essence) essence.textContent = `${walletEssence} essence`;
+}
+
+// --- Burn for Essence ---
+
+async function burnOwnedCards() {
+    if (!walletPublicKey || selectedForCombine.size < 1) return;
+
+    const mintAddresses = Array.from(selectedForCombine).map(i => ownedCards[i].mint_address);
+
+    try {
+        showLoading('Building burn...');
+        const data = await api('POST', '/api/wallet/burn', {
+            mint_addresses: mintAddresses,
+        });
+        hideLoading();
+
+        showLoading('Confirm in Phantom...');
+        const sig = await signAndSubmitTransaction(data.transaction);
+        hideLoading();
+
+        for (const m of mintAddresses) burnedMints.add(m);
+        localStorage.setItem('burnedMints', JSON.stringify([...burnedMints]));
+        ownedCards = ownedCards.filter(c => !burnedMints.has(c.mint_address));
+        combineMode = false;
+        selectedForCombine.clear();
+        renderCollection();
+
+        // Credit the essence as soon as the burn lands, if the webhook hasn't yet
+        showLoading('Waiting for the burn to land...');
+        for (let attempt = 0; attempt < 10; attempt++) {
+            const confirm = await api('POST', '/api/wallet/burn/confirm', {
+                mint_addresses: mintAddresses,
+                signature: sig,
+            });
+            walletEssence = confirm.essence;
+            if (confirm.burned.length > 0) break;
+            await new Promise(r => setTimeout(r, 2000));
+        }
+        hideLoading();
+        renderCollection();
+
+        showOverlay(`
+            <h2>Cards Burned</h2>
+            <p>${mintAddresses.length} card${mintAddresses.length > 1 ? 's' : ''} burned for <strong>${data.essence} essence</strong>.</p>
+            <button onclick="hideOverlay()">Continue</button>
+        `);
+    } catch (e) {
+        hideLoading();
+        showOverlay(`
+            <h2>Burn Failed</h2>
+            <p>${e.message}</p>
+            <button onclick="hideOverlay()">Dismiss</button>
+        `);
+    }
+}
+
+// --- Gifting ---
+
+function showGiftCard(index) {
+    const card = ownedCards[index];
+    if (!card) return;
+    if (card.soulbound) {
+        showOverlay(`
+            <h2>${card.name}</h2>
+            <p>This card is soulbound: it can't be sent or traded, but you can still combine or burn it.</p>
+            <button onclick="hideOverlay()">OK</button>
+        `);
+        return;
+    }
+    showOverlay(`
+        <h2>Gift ${card.name}</h2>
+        <p>Send this card to another Solana wallet.</p>
+        <input id="gift-recipient" class="gift-recipient" placeholder="Recipient wallet address">
+        <button onclick="giftCard(${index})">Send</button>
+        <button onclick="hideOverlay()">Cancel</button>
+    `);
+}
+
+async function giftCard(index) {
+    const card = ownedCards[index];
+    const recipient = document.getElementById('gift-recipient')?.value.trim();
+    if (!card || !recipient) return;
+    hideOverlay();
+
+    try {
+        showLoading('Building transfer...');
+        const data = await api('POST', '/api/wallet/transfer', {
+            mint_address: card.mint_address,
+            recipient,
+        });
+        hideLoading();
+
+        showLoading('Confirm in Phantom...');
+        const sig = await signAndSubmitTransaction(data.transaction);
+        hideLoading();
+
+        // Hide it like a burned card until DAS catches up
+        burnedMints.add(card.mint_address);
+        localStorage.setItem('burnedMints', JSON.stringify([...burnedMints]));
+        ownedCards = ownedCards.filter(c => !burnedMints.has(c.mint_address));
+        renderCollection();
+
+        showOverlay(`
+            <h2>Card Sent!</h2>
+            <p><strong>${card.name}</strong> is on its way to ${recipient.slice(0, 4)}...${recipient.slice(-4)}.</p>
+            <p style="font-size:0.7rem;color:#6a5a40;word-break:break-all">Signature: ${sig}</p>
+            <button onclick="hideOverlay()">Continue</button>
+        `);
+    } catch (e) {
+        hideLoading();
+        showOverlay(`
+            <h2>Gift Failed</h2>
+            <p>${e.message}</p>
+            <button onclick="hideOverlay()">Dismiss</button>
+        `);
+    }
+}
+
+// --- Trades ---
+
+function shortAddress(address) {
+    return `${address.slice(0, 4)}...${address.slice(-4)}`;
+}
+
+async function showTrades() {
+    if (!walletPublicKey) return;
+
+    try {
+        showLoading('Loading trades...');
+        const data = await api('POST', '/api/wallet/trades');
+        hideLoading();
+
+        const live = data.trades.filter(t => t.status === 'open' || t.status === 'accepted');
+        const rows = live.map(t => {
+            const incoming = t.counterparty === walletPublicKey;
+            const who = incoming ? `From ${shortAddress(t.proposer)}` : `To ${shortAddress(t.counterparty)}`;
+            const actions = incoming
+                ? `<button onclick="acceptTrade('${t.id}')">Accept</button>
+                   <button onclick="cancelTrade('${t.id}')">Decline</button>`
+                : `<button onclick="cancelTrade('${t.id}')">Cancel</button>`;
+            return `
+                <div class="trade-row">
+                    <p>${who}: ${t.offered.length} card${t.offered.length > 1 ? 's' : ''} for ${t.requested.length} (${t.status})</p>
+                    ${actions}
+                </div>`;
+        }).join('');
+
+        showOverlay(`
+            <h2>Trades</h2>
+            ${rows || '<p>No open trades.</p>'}
+            <button onclick="hideOverlay()">Close</button>
+        `);
+    } catch (e) {
+        hideLoading();
+        showOverlay(`
+            <h2>Trades Unavailable</h2>
+            <p>${e.message}</p>
+            <button onclick="hideOverlay()">Dismiss</button>
+        `);
+    }
+}
+
+async function acceptTrade(tradeId) {
+    hideOverlay();
+
+    try {
+        showLoading('Building trade...');
+        const data = await api('POST', '/api/wallet/trade/accept', { trade_id: tradeId });
+        hideLoading();
+
+        showLoading('Confirm in Phantom...');
+        const sig = await signAndSubmitTransaction(data.transaction);
+        hideLoading();
+
+        for (const m of data.trade.requested) burnedMints.add(m);
+        localStorage.setItem('burnedMints', JSON.stringify([...burnedMints]));
+        await refreshOwnedCards();
+        renderCollection();
+
+        showOverlay(`
+            <h2>Trade Complete!</h2>
+            <p>${data.trade.offered.length} card${data.trade.offered.length > 1 ? 's' : ''} will show up in your collection shortly.</p>
+            <p style="font-size:0.7rem;color:#6a5a40;word-break:break-all">Signature: ${sig}</p>
+            <button onclick="hideOverlay()">Continue</button>
+        `);
+    } catch (e) {
+        hideLoading();
+        showOverlay(`
+            <h2>Trade Failed</h2>
+            <p>${e.message}</p>
+            <button onclick="hideOverlay()">Dismiss</button>
+        `);
+    }
+}
+
+async function cancelTrade(tradeId) {
+    hideOverlay();
+
+    try {
+        showLoading('Cancelling trade...');
+        await api('POST', '/api/wallet/trade/cancel', { trade_id: tradeId });
+        hideLoading();
+        // Escrowed cards are back in the proposer's wallet
+        await refreshOwnedCards();
+        renderCollection();
+        showTrades();
+    } catch (e) {
+        hideLoading();
+        showOverlay(`
+            <h2>Cancel Failed</h2>
+            <p>${e.message}</p>
+            <button onclick="hideOverlay()">Dismiss</button>
+        `);
+    }
 }
 
 async function combineOwnedCards() {
@@ -309,13 +635,12 @@ async function combineOwnedCards() {
     try {
         showLoading('Checking combination...');
         const data = await api('POST', '/api/wallet/combine', {
-            wallet_address: walletPublicKey,
             mint_addresses: mintAddresses,
         });
         hideLoading();
 
         showLoading('Confirm in Phantom...');
-        const sig = await signAndSubmitTransaction(data.transaction);
+        const sig = await signAndSubmitMint(data);
         hideLoading();
 
         // Optimistically remove burned cards and add the new one
@@ -26,6 +26,14 @@ pub struct CombineRequest {
     pub cards: Vec<Card>,
 }
 
+#[derive(Serialize)]
+pub struct CombineResponse {
+    #[serde(flatten)]
+    pub card: Card,
+    /// See `CardGenerator::combine_version`.
+    pub combine_version: String,
+}
+
 #[derive(Serialize)]
 pub struct CombineError {
     pub reason: String,
@@ -34,7 +42,7 @@ pub struct CombineError {
 pub async fn combine<G: CardGenerator>(
     State(generator): State<Arc<G>>,
     Json(req): Json<CombineRequest>,
-) -> Result<Json<Card>, (StatusCode, Json<CombineError>)> {
+) -> Result<Json<CombineResponse>, (StatusCode, Json<CombineError>)> {
     let material_count = req.cards.iter().filter(|c| c.kind == CardKind::Material).count();
     let intent_count = req.cards.iter().filter(|c| c.kind == CardKind::Intent).count();
     if material_count < 1 {
@@ -54,10 +62,26 @@ pub async fn combine<G: CardGenerator>(
         ));
     }
     match generator.generate(&req.cards).await {
-        Ok(card) => Ok(Json(card)),
+        Ok(card) => Ok(Json(CombineResponse {
+            card,
+            combine_version: generator.combine_version().to_string(),
+        })),
         Err(reason) => Err((
             StatusCode::UNPROCESSABLE_ENTITY,
             Json(CombineError { reason }),
         )),
     }
 }
+
+#[derive(Serialize)]
+pub struct Version {
+    pub combine_version: String,
+}
+
+/// GET /version — the current combine fingerprint, so the game server can
+/// spot cached verdicts from an older model or prompt.
+pub async fn version<G: CardGenerator>(State(generator): State<Arc<G>>) -> Json<Version> {
+    Json(Version {
+        combine_version: generator.combine_version().to_string(),
+    })
+}
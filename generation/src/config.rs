@@ -0,0 +1,118 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Config file shared with the game server. Override the location with ALCHEMAYBE_CONFIG.
+const DEFAULT_CONFIG_PATH: &str = "alchemaybe.toml";
+
+/// Generation server configuration: built-in defaults, then `alchemaybe.toml`,
+/// then environment variables (OLLAMA_URL etc. still work and win over the file).
+/// Sections belonging to the game server are ignored.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub generation: ServerSettings,
+    pub ollama: OllamaSettings,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    pub listen_addr: String,
+    /// PEM certificate chain and private key. Setting both serves HTTPS.
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            listen_addr: "0.0.0.0:3000".to_string(),
+            tls_cert: None,
+            tls_key: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct OllamaSettings {
+    pub url: String,
+    /// Text model; required.
+    pub model: Option<String>,
+    /// Image model; image generation is disabled when unset.
+    pub image_model: Option<String>,
+    /// Model that looks over art players upload. It must accept images; the
+    /// text model is used when unset.
+    pub moderation_model: Option<String>,
+    pub request_timeout_secs: u64,
+}
+
+impl Default for OllamaSettings {
+    fn default() -> Self {
+        OllamaSettings {
+            url: "http://localhost:11434".to_string(),
+            model: None,
+            image_model: None,
+            moderation_model: None,
+            request_timeout_secs: 120,
+        }
+    }
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+impl Config {
+    /// Load defaults, then the config file (if present), then env overrides.
+    pub fn load() -> Result<Self, String> {
+        let path = env("ALCHEMAYBE_CONFIG").unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(data) => {
+                log::info!("Loading config from {path}");
+                toml::from_str(&data).map_err(|e| format!("Invalid config {path}: {e}"))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(format!("Failed to read config {path}: {e}")),
+        };
+        config.apply_env();
+        let server = &config.generation;
+        if server.tls_cert.is_some() != server.tls_key.is_some() {
+            return Err("TLS needs both [generation] tls_cert and tls_key".to_string());
+        }
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) {
+        let server = &mut self.generation;
+        if let Some(v) = env("GENERATION_LISTEN_ADDR") {
+            server.listen_addr = v;
+        }
+        if let Some(v) = env("GENERATION_TLS_CERT") {
+            server.tls_cert = Some(v.into());
+        }
+        if let Some(v) = env("GENERATION_TLS_KEY") {
+            server.tls_key = Some(v.into());
+        }
+
+        let ollama = &mut self.ollama;
+        if let Some(v) = env("OLLAMA_URL") {
+            ollama.url = v;
+        }
+        if let Some(v) = env("OLLAMA_MODEL") {
+            ollama.model = Some(v);
+        }
+        if let Some(v) = env("OLLAMA_IMAGE_MODEL") {
+            ollama.image_model = Some(v);
+        }
+        if let Some(v) = env("OLLAMA_MODERATION_MODEL") {
+            ollama.moderation_model = Some(v);
+        }
+        if let Some(v) = env("OLLAMA_TIMEOUT_SECS") {
+            match v.parse() {
+                Ok(secs) => ollama.request_timeout_secs = secs,
+                Err(_) => log::warn!("Ignoring invalid OLLAMA_TIMEOUT_SECS={v}"),
+            }
+        }
+    }
+}
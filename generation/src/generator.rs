@@ -5,6 +5,11 @@ pub trait CardGenerator: Send + Sync {
         &self,
         cards: &[Card],
     ) -> impl std::future::Future<Output = Result<Card, String>> + Send;
+
+    /// Fingerprint of the model and prompt behind `generate`. It changes
+    /// whenever their verdicts might, so callers caching results can tell
+    /// which generation produced them.
+    fn combine_version(&self) -> &str;
 }
 
 pub trait ImageGenerator: Send + Sync {
@@ -40,6 +45,30 @@ pub trait JudgeGenerator: Send + Sync {
     ) -> impl std::future::Future<Output = Result<JudgeResult, String>> + Send;
 }
 
+// --- Moderation ---
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ModerationRequest {
+    /// The card the art is meant for.
+    pub name: String,
+    pub description: String,
+    /// The art, base64-encoded PNG, JPEG or WebP.
+    pub image: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ModerationResult {
+    pub allowed: bool,
+    pub reason: String,
+}
+
+pub trait ModerationGenerator: Send + Sync {
+    fn moderate_image(
+        &self,
+        req: &ModerationRequest,
+    ) -> impl std::future::Future<Output = Result<ModerationResult, String>> + Send;
+}
+
 // --- Bot Combine ---
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
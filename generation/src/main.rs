@@ -1,15 +1,22 @@
 mod bot_move;
 mod combine;
+mod config;
 mod generator;
 mod image;
 mod judge;
+mod moderate;
 mod ollama;
+mod telemetry;
+mod tls;
 
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::StatusCode;
 use axum::routing::{get, post};
 use axum::{Json, Router};
 use ollama::{OllamaConfig, OllamaGenerator};
 use serde::Serialize;
 use std::sync::Arc;
+use tower_http::trace::TraceLayer;
 
 #[derive(Serialize)]
 struct Status {
@@ -20,21 +27,57 @@ async fn status() -> Json<Status> {
     Json(Status { status: "ok" })
 }
 
+#[derive(Serialize)]
+struct Readiness {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Ready once Ollama answers; the game server's /readyz only checks /healthz here.
+async fn readyz(State(generator): State<Arc<OllamaGenerator>>) -> (StatusCode, Json<Readiness>) {
+    match generator.ping().await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(Readiness {
+                status: "ready",
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(Readiness {
+                status: "not_ready",
+                error: Some(e),
+            }),
+        ),
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    let telemetry = telemetry::init();
 
-    let config = OllamaConfig::from_env();
-    let generator = Arc::new(OllamaGenerator::new(config));
+    let config = config::Config::load().unwrap_or_else(|e| panic!("{e}"));
+    let ollama = OllamaConfig::from_config(&config.ollama).unwrap_or_else(|e| panic!("{e}"));
+    let generator = Arc::new(OllamaGenerator::new(ollama));
 
     let app = Router::new()
         .route("/status", get(status))
+        .route("/healthz", get(status))
+        .route("/readyz", get(readyz))
+        .route("/version", get(combine::version::<OllamaGenerator>))
         .route("/combine", post(combine::combine::<OllamaGenerator>))
         .route(
             "/generate-image",
             post(image::generate_image::<OllamaGenerator>),
         )
         .route("/judge", post(judge::judge::<OllamaGenerator>))
+        .route(
+            "/moderate-image",
+            post(moderate::moderate_image::<OllamaGenerator>)
+                .layer(DefaultBodyLimit::max(moderate::MAX_BODY_BYTES)),
+        )
         .route(
             "/bot-combine",
             post(bot_move::bot_combine::<OllamaGenerator>),
@@ -43,9 +86,27 @@ async fn main() {
             "/bot-place",
             post(bot_move::bot_place::<OllamaGenerator>),
         )
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::request_span))
         .with_state(generator);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    log::info!("Generation server listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    let server = &config.generation;
+    let listen_addr = &server.listen_addr;
+    if let (Some(cert), Some(key)) = (&server.tls_cert, &server.tls_key) {
+        let tls_config = tls::load(cert, key).await.unwrap_or_else(|e| panic!("{e}"));
+        let listener = std::net::TcpListener::bind(listen_addr)
+            .and_then(|l| l.set_nonblocking(true).map(|_| l))
+            .unwrap_or_else(|e| panic!("Failed to bind {listen_addr}: {e}"));
+        log::info!("Generation server listening on {} (HTTPS)", listener.local_addr().unwrap());
+        axum_server::from_tcp_rustls(listener, tls_config)
+            .serve(app.into_make_service())
+            .await
+            .unwrap();
+    } else {
+        let listener = tokio::net::TcpListener::bind(listen_addr)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to bind {listen_addr}: {e}"));
+        log::info!("Generation server listening on {}", listener.local_addr().unwrap());
+        axum::serve(listener, app).await.unwrap();
+    }
+    telemetry.shutdown();
 }
@@ -0,0 +1,37 @@
+use crate::generator::{ModerationGenerator, ModerationRequest, ModerationResult};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Largest request body accepted: the art arrives base64-encoded, so this
+/// is above axum's default.
+pub const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Serialize)]
+pub struct ModerationError {
+    pub reason: String,
+}
+
+pub async fn moderate_image<G: ModerationGenerator>(
+    State(generator): State<Arc<G>>,
+    Json(req): Json<ModerationRequest>,
+) -> Result<Json<ModerationResult>, (StatusCode, Json<ModerationError>)> {
+    log::info!("Moderating uploaded art for '{}'", req.name);
+
+    match generator.moderate_image(&req).await {
+        Ok(result) => {
+            let verdict = if result.allowed { "allowed" } else { "rejected" };
+            log::info!("Art for '{}' {verdict} — {}", req.name, result.reason);
+            Ok(Json(result))
+        }
+        Err(reason) => {
+            log::error!("Moderation failed for '{}': {reason}", req.name);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ModerationError { reason }),
+            ))
+        }
+    }
+}
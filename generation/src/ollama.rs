@@ -1,42 +1,74 @@
 use crate::combine::{Card, CardKind};
+use crate::config::OllamaSettings;
 use crate::generator::{
     BotCombineGenerator, BotCombineRequest, BotCombineResult, BotPlaceGenerator, BotPlaceRequest,
     BotPlaceResult, CardGenerator, ImageGenerator, JudgeGenerator, JudgeRequest, JudgeResult,
+    ModerationGenerator, ModerationRequest, ModerationResult,
 };
 use base64::Engine;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
+use tracing::Instrument;
 
 pub struct OllamaConfig {
     base_url: String,
     model: String,
     image_model: Option<String>,
+    moderation_model: String,
+    timeout: Duration,
 }
 
 impl OllamaConfig {
-    pub fn from_env() -> Self {
-        Self {
-            base_url: std::env::var("OLLAMA_URL").expect("OLLAMA_URL must be set"),
-            model: std::env::var("OLLAMA_MODEL").expect("OLLAMA_MODEL must be set"),
-            image_model: std::env::var("OLLAMA_IMAGE_MODEL").ok(),
-        }
+    /// Build from the `[ollama]` section. A text model is required.
+    pub fn from_config(settings: &OllamaSettings) -> Result<Self, String> {
+        let model = settings
+            .model
+            .clone()
+            .ok_or("No Ollama model configured (set [ollama] model or OLLAMA_MODEL)")?;
+        Ok(Self {
+            base_url: settings.url.clone(),
+            moderation_model: settings.moderation_model.clone().unwrap_or_else(|| model.clone()),
+            model,
+            image_model: settings.image_model.clone(),
+            timeout: Duration::from_secs(settings.request_timeout_secs),
+        })
     }
 }
 
 pub struct OllamaGenerator {
     client: Client,
     config: OllamaConfig,
+    combine_version: String,
 }
 
 impl OllamaGenerator {
     pub fn new(config: OllamaConfig) -> Self {
         let client = Client::builder()
-            .timeout(Duration::from_secs(120))
+            .timeout(config.timeout)
             .build()
             .expect("failed to build HTTP client");
-        Self { client, config }
+        let combine_version = combine_fingerprint(&config.model);
+        log::info!("Combine version {combine_version} ({})", config.model);
+        Self {
+            client,
+            config,
+            combine_version,
+        }
+    }
+
+    /// Check that Ollama is reachable, for readiness probes.
+    pub async fn ping(&self) -> Result<(), String> {
+        self.client
+            .get(format!("{}/api/tags", self.config.base_url))
+            .timeout(Duration::from_secs(3))
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map(|_| ())
+            .map_err(|e| format!("Ollama unreachable: {e}"))
     }
 }
 
@@ -101,6 +133,21 @@ not a poetic abstraction. \"Sword\" is real. \"Flame Crystal\" is not. \"Bread\"
 - Name: 1-3 words.
 - Description: MUST start with an adjective or noun. NEVER start with A, An, The, This, It, or Its. One short funny sentence about what it is, not how it was made.";
 
+/// Bump when `build_user_prompt` or the checks on the model's answer change
+/// in a way that could flip verdicts. Edits to `SYSTEM_PROMPT` and model
+/// swaps are picked up automatically.
+const COMBINE_PROMPT_REVISION: u32 = 1;
+
+/// First 12 hex chars of SHA-256 over everything that shapes a combine.
+fn combine_fingerprint(model: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(SYSTEM_PROMPT.as_bytes());
+    hasher.update(COMBINE_PROMPT_REVISION.to_le_bytes());
+    format!("{:x}", hasher.finalize())[..12].to_string()
+}
+
 #[derive(Serialize)]
 struct GenerateRequest {
     model: String,
@@ -109,6 +156,9 @@ struct GenerateRequest {
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     format: Option<serde_json::Value>,
+    /// Base64 images for vision models.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
     options: GenerateOptions,
 }
 
@@ -189,6 +239,7 @@ Wind Silk, Ember Stone, Soul Vessel, Fire Dough, Light Weave, Bone Whisper.
 Output JSON: {\"real\": true} or {\"real\": false}";
 
 impl CardGenerator for OllamaGenerator {
+    #[tracing::instrument(name = "ollama.combine", skip_all, fields(model = %self.config.model, cards = cards.len()))]
     async fn generate(&self, cards: &[Card]) -> Result<Card, String> {
         let url = format!("{}/api/generate", self.config.base_url);
         let prompt = build_user_prompt(cards);
@@ -207,6 +258,7 @@ impl CardGenerator for OllamaGenerator {
                 },
                 "required": ["name", "description"]
             })),
+            images: Vec::new(),
             options: GenerateOptions {
                 temperature: 0.0,
                 seed: 42,
@@ -260,6 +312,7 @@ impl CardGenerator for OllamaGenerator {
                 },
                 "required": ["real"]
             })),
+            images: Vec::new(),
             options: GenerateOptions {
                 temperature: 0.0,
                 seed: 42,
@@ -271,6 +324,7 @@ impl CardGenerator for OllamaGenerator {
             .post(&url)
             .json(&validate_request)
             .send()
+            .instrument(tracing::info_span!("ollama.validate", card = %llm_card.name))
             .await
             .map_err(|e| format!("Validation request failed: {e}"))?;
 
@@ -296,6 +350,10 @@ impl CardGenerator for OllamaGenerator {
             kind: Default::default(),
         })
     }
+
+    fn combine_version(&self) -> &str {
+        &self.combine_version
+    }
 }
 
 #[derive(Serialize)]
@@ -378,6 +436,7 @@ impl OllamaGenerator {
         Err(last_err)
     }
 
+    #[tracing::instrument(name = "ollama.describe_image", skip_all, fields(model = %self.config.model, card = %card.name, attempt))]
     async fn try_describe_card_image(&self, card: &Card, attempt: u32) -> Result<String, String> {
         let start = Instant::now();
         log::info!("Generating image description for '{}' (attempt {attempt})...", card.name);
@@ -400,6 +459,7 @@ impl OllamaGenerator {
             .to_string(),
             stream: false,
             format: None,
+            images: Vec::new(),
             options: GenerateOptions {
                 temperature: 0.0,
                 seed: 42,
@@ -440,6 +500,7 @@ impl OllamaGenerator {
 }
 
 impl ImageGenerator for OllamaGenerator {
+    #[tracing::instrument(name = "ollama.generate_image", skip_all, fields(card = %card.name))]
     async fn generate_image(&self, card: &Card) -> Result<Vec<u8>, String> {
         let image_model = self
             .config
@@ -470,6 +531,7 @@ impl ImageGenerator for OllamaGenerator {
             .post(&url)
             .json(&request)
             .send()
+            .instrument(tracing::info_span!("ollama.render_image", model = %image_model))
             .await
             .map_err(|e| {
                 log::error!("Ollama image request failed after {:.1}s: {e}", start.elapsed().as_secs_f64());
@@ -512,6 +574,7 @@ Output JSON with:
 - \"reason\": One short sentence explaining why the winner fits the category better.";
 
 impl JudgeGenerator for OllamaGenerator {
+    #[tracing::instrument(name = "ollama.judge", skip_all, fields(model = %self.config.model, category = %req.category))]
     async fn judge(&self, req: &JudgeRequest) -> Result<JudgeResult, String> {
         let url = format!("{}/api/generate", self.config.base_url);
 
@@ -533,6 +596,7 @@ impl JudgeGenerator for OllamaGenerator {
                 },
                 "required": ["winner", "reason"]
             })),
+            images: Vec::new(),
             options: GenerateOptions {
                 temperature: 0.0,
                 seed: 42,
@@ -565,6 +629,79 @@ impl JudgeGenerator for OllamaGenerator {
     }
 }
 
+const MODERATION_SYSTEM_PROMPT: &str = "\
+You moderate art that players upload for cards in a family-friendly fantasy card game. \
+You are given the card's name and description and the uploaded image.
+
+Reject the image if ANY of these are true:
+- It contains nudity, sexual content, or sexualized poses.
+- It contains graphic violence, gore, or self-harm.
+- It contains hate symbols, slurs, or harassment of real people.
+- It contains readable text, watermarks, URLs, QR codes, or advertising.
+- It is a photo of a real, identifiable person.
+- It clearly doesn't show the card's subject (a \"Sword\" card showing a cat, say).
+
+Otherwise allow it. Stylized, painted or photographic art of the subject is all fine, and \
+so is fantasy combat without gore.
+
+Output JSON with:
+- \"allowed\": true or false
+- \"reason\": One short sentence explaining the decision.";
+
+impl ModerationGenerator for OllamaGenerator {
+    #[tracing::instrument(name = "ollama.moderate_image", skip_all, fields(model = %self.config.moderation_model, card = %req.name))]
+    async fn moderate_image(&self, req: &ModerationRequest) -> Result<ModerationResult, String> {
+        let url = format!("{}/api/generate", self.config.base_url);
+
+        let prompt = format!(
+            "Card name: {}\nCard description: {}\n\nShould this art be allowed on the card?",
+            req.name, req.description
+        );
+
+        let request = GenerateRequest {
+            model: self.config.moderation_model.clone(),
+            prompt,
+            system: MODERATION_SYSTEM_PROMPT.to_string(),
+            stream: false,
+            format: Some(serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "allowed": { "type": "boolean" },
+                    "reason": { "type": "string" }
+                },
+                "required": ["allowed", "reason"]
+            })),
+            images: vec![req.image.clone()],
+            options: GenerateOptions {
+                temperature: 0.0,
+                seed: 42,
+            },
+        };
+
+        let resp = self
+            .client
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| format!("Moderation request failed: {e}"))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("Ollama returned {status}: {body}"));
+        }
+
+        let gen_resp: GenerateResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse moderation response: {e}"))?;
+
+        serde_json::from_str(&gen_resp.response)
+            .map_err(|e| format!("Failed to parse moderation output: {e}"))
+    }
+}
+
 const BOT_COMBINE_SYSTEM_PROMPT: &str = "\
 You are an AI player in an alchemy card game. You need to choose cards from your hand to combine.
 
@@ -586,6 +723,7 @@ Output JSON with:
 - \"combine\": array of hand indices (0-based) to combine (2-4 cards, at least 2 must be materials/crafted)";
 
 impl BotCombineGenerator for OllamaGenerator {
+    #[tracing::instrument(name = "ollama.bot_combine", skip_all, fields(model = %self.config.model))]
     async fn bot_combine(&self, req: &BotCombineRequest) -> Result<BotCombineResult, String> {
         let url = format!("{}/api/generate", self.config.base_url);
 
@@ -615,6 +753,7 @@ impl BotCombineGenerator for OllamaGenerator {
                 },
                 "required": ["combine"]
             })),
+            images: Vec::new(),
             options: GenerateOptions {
                 temperature: 0.3,
                 seed: 42,
@@ -670,6 +809,7 @@ Output JSON with:
 - \"skip\": true if you want to skip placing this turn (save crafted cards for later)";
 
 impl BotPlaceGenerator for OllamaGenerator {
+    #[tracing::instrument(name = "ollama.bot_place", skip_all, fields(model = %self.config.model))]
     async fn bot_place(&self, req: &BotPlaceRequest) -> Result<BotPlaceResult, String> {
         let url = format!("{}/api/generate", self.config.base_url);
 
@@ -718,6 +858,7 @@ impl BotPlaceGenerator for OllamaGenerator {
                 },
                 "required": ["hand_index", "target_row", "target_col", "skip"]
             })),
+            images: Vec::new(),
             options: GenerateOptions {
                 temperature: 0.3,
                 seed: 42,
@@ -0,0 +1,73 @@
+use axum::extract::Request;
+use opentelemetry::global;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+const SERVICE_NAME: &str = "alchemaybe-generation";
+
+/// Holds the OTLP exporter (if any) so pending spans can be flushed on exit.
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+/// Install the global subscriber. Logs go to stderr filtered by RUST_LOG; spans
+/// are also exported over OTLP/HTTP when OTEL_EXPORTER_OTLP_ENDPOINT is set.
+/// Existing `log::` calls are bridged into tracing.
+pub fn init() -> Telemetry {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok().and_then(|endpoint| {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .build()
+            .map_err(|e| eprintln!("Failed to build OTLP exporter for {endpoint}: {e}"))
+            .ok()?;
+        Some(
+            SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+                .build(),
+        )
+    });
+    let otel_layer = provider
+        .as_ref()
+        .map(|p| tracing_opentelemetry::layer().with_tracer(p.tracer(SERVICE_NAME)));
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel_layer)
+        .init();
+
+    if provider.is_some() {
+        log::info!("OTLP trace export enabled");
+    }
+    Telemetry { provider }
+}
+
+impl Telemetry {
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider {
+            if let Err(e) = provider.shutdown() {
+                log::warn!("Failed to flush traces: {e}");
+            }
+        }
+    }
+}
+
+/// Root span for an incoming request, parented to the caller's `traceparent`
+/// header so game server spans and ours land in the same trace.
+pub fn request_span(req: &Request) -> Span {
+    let parent = global::get_text_map_propagator(|p| p.extract(&HeaderExtractor(req.headers())));
+    let span = tracing::info_span!("request", method = %req.method(), path = %req.uri().path());
+    span.set_parent(parent);
+    span
+}
@@ -0,0 +1,15 @@
+use axum_server::tls_rustls::RustlsConfig;
+use std::path::Path;
+
+/// Load the PEM certificate chain and private key used to serve HTTPS.
+pub async fn load(cert: &Path, key: &Path) -> Result<RustlsConfig, String> {
+    // Only the ring provider is compiled in; make it the process default
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(cert, key).await.map_err(|e| {
+        format!(
+            "Failed to load TLS cert {} / key {}: {e}",
+            cert.display(),
+            key.display()
+        )
+    })
+}
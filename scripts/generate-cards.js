@@ -5,7 +5,10 @@
  *
  * Requires both servers running (make run). Calls the generation server for
  * combine + image, then the game server for card rendering. Saves results
- * to cards/card-cache.json with discovered=false.
+ * to the card cache database (data/card-cache.db, or CARD_CACHE_DB) with
+ * discovered=false, tagged with the generation server's combine version.
+ * Entries from other combine versions are regenerated. Safe to run while the
+ * game server is up.
  *
  * Usage:
  *   node scripts/generate-cards.js
@@ -14,11 +17,12 @@
 const fs = require("fs");
 const path = require("path");
 const crypto = require("crypto");
+const Database = require("better-sqlite3");
 
 const GENERATION_URL = process.env.GENERATION_URL || "http://localhost:3000";
 const GAME_URL = process.env.GAME_URL || "http://localhost:3001";
 const CARDS_JSON = "cards.json";
-const CACHE_PATH = "cards/card-cache.json";
+const CACHE_PATH = process.env.CARD_CACHE_DB || "data/card-cache.db";
 const CRAFTED_DIR = "cards/crafted";
 
 function loadCards() {
@@ -26,17 +30,35 @@ function loadCards() {
   return { materials: data.materials, intents: data.intents };
 }
 
-function loadCache() {
-  try {
-    return JSON.parse(fs.readFileSync(CACHE_PATH, "utf8"));
-  } catch {
-    return { entries: {} };
-  }
+// Start the game server once first so it creates the schema (and imports any
+// old card-cache.json).
+function openCache(combineVersion) {
+  const db = new Database(CACHE_PATH, { fileMustExist: true });
+  db.pragma("busy_timeout = 5000");
+  const current = db.prepare(
+    "SELECT 1 FROM cards WHERE id = ? AND (discovered = 1 OR combine_version = ?)"
+  );
+  const insert = db.prepare(`
+    INSERT OR REPLACE INTO cards
+      (id, name, description, image_path, discovered, impossible, recipe, created_at, combine_version)
+    VALUES
+      (@id, @name, @description, @image_path, 0, @impossible, @recipe, unixepoch(), @combine_version)
+  `);
+  return {
+    size: () => db.prepare("SELECT COUNT(*) AS n FROM cards").get().n,
+    isCurrent: (id) => current.get(id, combineVersion) !== undefined,
+    save: (card) => insert.run({ ...card, combine_version: combineVersion }),
+  };
+}
+
+async function fetchCombineVersion() {
+  const resp = await fetch(`${GENERATION_URL}/version`);
+  if (!resp.ok) throw new Error(`GET /version failed: ${resp.status}`);
+  return (await resp.json()).combine_version;
 }
 
-function saveCache(cache) {
-  fs.mkdirSync(path.dirname(CACHE_PATH), { recursive: true });
-  fs.writeFileSync(CACHE_PATH, JSON.stringify(cache, null, 2));
+function recipeJson(materialIds, intentId) {
+  return JSON.stringify({ material_ids: [...materialIds].sort(), intent_id: intentId });
 }
 
 function computeBaseCardId(name) {
@@ -138,14 +160,17 @@ async function generateImage(name, description) {
   }
 }
 
-async function saveCardImage(name, cardId, artBytes) {
+// Same naming as the game server: content hash, so identical renders share a file
+function writeCraftedImage(bytes) {
   fs.mkdirSync(CRAFTED_DIR, { recursive: true });
-  const safeName = name
-    .replace(/[^a-zA-Z0-9 -]/g, "_")
-    .replace(/ /g, "-");
-  const filename = `${safeName}-${cardId}.png`;
+  const hash = crypto.createHash("sha256").update(bytes).digest("hex").slice(0, 16);
+  const filename = `${hash}.png`;
   const diskPath = path.join(CRAFTED_DIR, filename);
+  if (!fs.existsSync(diskPath)) fs.writeFileSync(diskPath, bytes);
+  return `/cards/crafted/${filename}`;
+}
 
+async function saveCardImage(name, artBytes) {
   // Call game server to render card frame around the art
   try {
     const resp = await fetch(`${GAME_URL}/generate-card`, {
@@ -154,14 +179,12 @@ async function saveCardImage(name, cardId, artBytes) {
       body: JSON.stringify({ name, description: "" }),
     });
     if (resp.ok) {
-      fs.writeFileSync(diskPath, Buffer.from(await resp.arrayBuffer()));
-      return `/cards/crafted/${filename}`;
+      return writeCraftedImage(Buffer.from(await resp.arrayBuffer()));
     }
   } catch {}
 
   // Fallback: save raw art
-  fs.writeFileSync(diskPath, artBytes);
-  return `/cards/crafted/${filename}`;
+  return writeCraftedImage(artBytes);
 }
 
 async function main() {
@@ -169,10 +192,12 @@ async function main() {
   // Compute base card IDs
   for (const m of materials) m.id = computeBaseCardId(m.name);
   for (const i of intents) i.id = computeBaseCardId(i.name);
-  const cache = loadCache();
+  const combineVersion = await fetchCombineVersion();
+  const cache = openCache(combineVersion);
 
   console.log(`Loaded ${materials.length} materials, ${intents.length} intents`);
-  console.log(`Cache has ${Object.keys(cache.entries || {}).length} existing entries`);
+  console.log(`Combine version ${combineVersion}`);
+  console.log(`Cache has ${cache.size()} existing entries`);
   console.log(`Running continuously. Press Ctrl+C to stop.\n`);
 
   let success = 0;
@@ -192,8 +217,8 @@ async function main() {
     let label = matNames.join(" + ");
     if (intent) label += ` [${intent.name}]`;
 
-    // Skip if already cached
-    if (cache.entries && cache.entries[key]) {
+    // Skip if discovered, or already cached by this combine version
+    if (cache.isCurrent(key)) {
       skipped++;
       continue;
     }
@@ -204,16 +229,14 @@ async function main() {
     const result = await tryCombine(mats, intent);
     if (!result) {
       console.log(`  Not possible`);
-      if (!cache.entries) cache.entries = {};
-      cache.entries[key] = {
+      cache.save({
+        id: key,
         name: "Not possible",
         description: "",
         image_path: "",
-        id: key,
-        discovered: false,
-        impossible: true,
-      };
-      saveCache(cache);
+        impossible: 1,
+        recipe: recipeJson(matIds, intentId),
+      });
       notPossible++;
       console.log(`  [${success} ok, ${notPossible} impossible, ${failed} failed, ${skipped} skipped]`);
       continue;
@@ -233,18 +256,17 @@ async function main() {
 
     // Step 3: Render and save card
     console.log(`  Rendering card...`);
-    const servePath = await saveCardImage(result.name, key, artBytes);
+    const servePath = await saveCardImage(result.name, artBytes);
 
     // Step 4: Save to cache as undiscovered
-    if (!cache.entries) cache.entries = {};
-    cache.entries[key] = {
+    cache.save({
+      id: key,
       name: result.name,
       description: result.description,
       image_path: servePath,
-      id: key,
-      discovered: false,
-    };
-    saveCache(cache);
+      impossible: 0,
+      recipe: recipeJson(matIds, intentId),
+    });
 
     success++;
     console.log(`  Saved! (${servePath})`);
@@ -1,26 +1,26 @@
 #!/usr/bin/env node
 /**
- * Reset all cards in card-cache.json to discovered: false.
+ * Reset all cards in the card cache database to discovered: false.
+ * Safe to run while the game server is up; POST /api/admin/reload-content
+ * afterwards (or restart it) so its in-memory cache picks up the change.
  *
  * Usage:
  *   node scripts/reset-discoveries.js
+ *   CARD_CACHE_DB=path/to/card-cache.db node scripts/reset-discoveries.js
  */
 
-const fs = require("fs");
+const Database = require("better-sqlite3");
 
-const CACHE_PATH = "cards/card-cache.json";
+const CACHE_PATH = process.env.CARD_CACHE_DB || "data/card-cache.db";
 
 try {
-  const cache = JSON.parse(fs.readFileSync(CACHE_PATH, "utf8"));
-  let count = 0;
-  for (const key of Object.keys(cache.entries || {})) {
-    if (cache.entries[key].discovered) {
-      cache.entries[key].discovered = false;
-      count++;
-    }
-  }
-  fs.writeFileSync(CACHE_PATH, JSON.stringify(cache, null, 2));
-  console.log(`Reset ${count} cards to undiscovered (${Object.keys(cache.entries || {}).length} total)`);
+  const db = new Database(CACHE_PATH, { fileMustExist: true });
+  const total = db.prepare("SELECT COUNT(*) AS n FROM cards").get().n;
+  const { changes } = db
+    .prepare("UPDATE cards SET discovered = 0, discovered_at = NULL WHERE discovered = 1")
+    .run();
+  db.close();
+  console.log(`Reset ${changes} cards to undiscovered (${total} total)`);
 } catch (e) {
   console.error(`Error: ${e.message}`);
   process.exit(1);